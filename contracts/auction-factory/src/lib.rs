@@ -0,0 +1,434 @@
+//! This is the example auction factory contract. This contract is able to deploy new auction
+//! contracts selling tokens. The contract keeps track of the deployed auction contracts, their
+//! addresses and their statuses, such that bidders can find them. Bidders then go to the deployed
+//! contracts to submit their bids.
+//!
+//! The owner of the factory deploys the auctions, and becomes the contract owner of each of them.
+//! Each auction is deployed as a single winner auction settled at the first price, without
+//! royalties, bid retraction or automatic refunds. Once the auction has been deployed, the owner
+//! approves the auction to transfer the tokens for sale and calls `start` on it, which moves the
+//! tokens for sale into the auction and opens the bidding. The proceeds of the auction are
+//! credited to the owner.
+//!
+//! Auctions must be executed once they end before the highest bidder can claim the tokens for
+//! sale. Anyone can remind the factory to execute the auctions that have ended, which relays an
+//! `execute` call to each of them, such that bidders do not have to track the end of each auction.
+//! Since the deployed auctions settle without querying an oracle, executing an ended auction only
+//! fails if it was never started or has already been executed, and the factory does not retry it.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::BTreeMap;
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::WriteRPC;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+const PUB_DEPLOY_ADDRESS: Address = Address {
+    address_type: AddressType::SystemContract,
+    identifier: [
+        0x97, 0xa0, 0xe2, 0x38, 0xe9, 0x24, 0x02, 0x5b, 0xad, 0x14, 0x4a, 0xa0, 0xc4, 0x91, 0x3e,
+        0x46, 0x30, 0x8f, 0x9a, 0x4d,
+    ],
+};
+
+/// The maximum number of auctions that can be reminded to execute in a single batch.
+const MAX_EXECUTE_BATCH_SIZE: usize = 50;
+
+/// The parameters of a deployed auction contract. Mirrors the corresponding arguments of the
+/// `initialize` function of the auction contract.
+///
+/// ### Fields:
+///
+/// * `token_amount_for_sale`: [`u128`], the amount of tokens for sale.
+/// * `token_for_sale`: [`Address`], the address of the token for sale.
+/// * `token_for_bidding`: [`Address`], the address of the token used for bidding.
+/// * `reserve_price`: [`u128`], the reserve price (minimum cost of the tokens for sale).
+/// * `min_increment`: [`u128`], the minimum increment of each bid.
+/// * `auction_duration_hours`: [`u32`], the duration of the auction in hours.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct AuctionParameters {
+    token_amount_for_sale: u128,
+    token_for_sale: Address,
+    token_for_bidding: Address,
+    reserve_price: u128,
+    min_increment: u128,
+    auction_duration_hours: u32,
+}
+
+/// The status of a deployed auction contract.
+#[derive(ReadWriteState, CreateTypeSpec, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum AuctionStatus {
+    /// The auction contract is being deployed.
+    #[discriminant(0)]
+    Deploying {},
+    /// The auction contract has been deployed, and has not been executed by the factory.
+    #[discriminant(1)]
+    Deployed {},
+    /// The deployment of the auction contract failed.
+    #[discriminant(2)]
+    Failed {},
+    /// The factory is executing the auction contract.
+    #[discriminant(3)]
+    Executing {},
+    /// The auction contract has been executed by the factory.
+    #[discriminant(4)]
+    Executed {},
+    /// The execution of the auction contract failed, as it was never started or has already been
+    /// executed.
+    #[discriminant(5)]
+    ExecutionFailed {},
+}
+
+/// A deployed auction contract.
+///
+/// ### Fields:
+///
+/// * `address`: [`Option<Address>`], the address of the auction contract, or None until it has
+///   been deployed.
+/// * `parameters`: [`AuctionParameters`], the parameters the auction contract was deployed with.
+/// * `end_time_millis`: [`i64`], the time in UTC millis from which the auction can be executed.
+/// * `status`: [`AuctionStatus`], the status of the auction contract.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct DeployedAuction {
+    address: Option<Address>,
+    parameters: AuctionParameters,
+    end_time_millis: i64,
+    status: AuctionStatus,
+}
+
+impl DeployedAuction {
+    /// Determines whether the factory should execute the auction at a point in time.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now_millis`: [`i64`], the point in time in UTC millis.
+    ///
+    /// ### Returns:
+    /// True if the auction is deployed and has ended, otherwise false.
+    fn is_ready_to_execute(&self, now_millis: i64) -> bool {
+        self.status == (AuctionStatus::Deployed {}) && now_millis >= self.end_time_millis
+    }
+}
+
+/// Contract state.
+///
+/// ### Fields:
+///
+/// * `owner`: [`Address`], the owner of the contract.
+/// * `auctions`: [`BTreeMap<u64, DeployedAuction>`], a map from auction ids to auction contracts.
+/// * `next_auction_id`: [`u64`], the id of the next auction contract to be deployed.
+/// * `auction_contract_wasm`: [`Vec<u8>`], bytes of the auction contract wasm.
+/// * `auction_contract_abi`: [`Vec<u8>`], bytes of the auction contract abi.
+#[state]
+pub struct AuctionFactoryState {
+    owner: Address,
+    auctions: BTreeMap<u64, DeployedAuction>,
+    next_auction_id: u64,
+    auction_contract_wasm: Vec<u8>,
+    auction_contract_abi: Vec<u8>,
+}
+
+/// Initial function to create the initial state.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], initial context.
+/// * `auction_contract_wasm`: [`Vec<u8>`], wasm bytes of an auction contract.
+/// * `auction_contract_abi`: [`Vec<u8>`], abi bytes of an auction contract.
+///
+/// ### Returns:
+/// The initial state of type [`AuctionFactoryState`].
+#[init]
+pub fn initialize(
+    ctx: ContractContext,
+    auction_contract_wasm: Vec<u8>,
+    auction_contract_abi: Vec<u8>,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    let state = AuctionFactoryState {
+        owner: ctx.sender,
+        auctions: BTreeMap::new(),
+        next_auction_id: 0,
+        auction_contract_wasm,
+        auction_contract_abi,
+    };
+
+    (state, vec![])
+}
+
+/// Deploys a new auction contract with the given parameters, under the next auction id. The
+/// address of the new auction contract is computed from the original transaction hash. Only the
+/// owner can deploy auction contracts, and the owner becomes the contract owner of the auction,
+/// who starts it and receives its proceeds. This creates an event to the public deploy contract as
+/// well as a callback to `deploy_auction_callback`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`AuctionFactoryState`], the state before the call.
+/// * `parameters`: [`AuctionParameters`], the parameters of the new auction contract.
+///
+/// ### Returns:
+/// The new state of type [`AuctionFactoryState`].
+#[action(shortname = 0x01)]
+pub fn deploy_auction(
+    ctx: ContractContext,
+    state: AuctionFactoryState,
+    parameters: AuctionParameters,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    assert_eq!(ctx.sender, state.owner, "Only owner can deploy auctions");
+    if parameters.token_for_sale.address_type != AddressType::PublicContract
+        || parameters.token_for_bidding.address_type != AddressType::PublicContract
+    {
+        panic!("Auctions can only trade publicContract tokens");
+    }
+
+    let auction_id = state.next_auction_id;
+    let auction_address = Address {
+        address_type: AddressType::PublicContract,
+        identifier: ctx.original_transaction[12..32].try_into().unwrap(),
+    };
+
+    let mut event_group = EventGroup::builder();
+
+    event_group
+        .call(PUB_DEPLOY_ADDRESS, Shortname::from_u32(1))
+        .argument(state.auction_contract_wasm.clone())
+        .argument(state.auction_contract_abi.clone())
+        .argument(create_auction_init_bytes(&parameters, ctx.sender))
+        .done();
+
+    event_group
+        .with_callback(SHORTNAME_DEPLOY_AUCTION_CALLBACK)
+        .with_cost(1000)
+        .argument(auction_id)
+        .argument(auction_address)
+        .done();
+
+    let duration_millis = i64::from(parameters.auction_duration_hours) * 60 * 60 * 1000;
+    let mut new_state = state;
+    new_state.next_auction_id += 1;
+    new_state.auctions.insert(
+        auction_id,
+        DeployedAuction {
+            address: None,
+            parameters,
+            end_time_millis: ctx.block_production_time + duration_millis,
+            status: AuctionStatus::Deploying {},
+        },
+    );
+
+    (new_state, vec![event_group.build()])
+}
+
+/// Marks the deployment of the auction contract with given id as failed.
+fn mark_deployment_failed(state: &mut AuctionFactoryState, auction_id: u64) {
+    if let Some(auction) = state.auctions.get_mut(&auction_id) {
+        auction.status = AuctionStatus::Failed {};
+    }
+}
+
+/// Callback for deploying a new auction contract. If the deployment was unsuccessful the entry in
+/// `auctions` is marked as failed. If it instead was successful, an empty invocation is made to
+/// the new contract to check if it really has been deployed. A new callback to
+/// `auction_exists_callback` is also created.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback.
+/// * `state`: [`AuctionFactoryState`], the state before the call.
+/// * `auction_id`: [`u64`], the id of the new auction contract.
+/// * `auction_address`: [`Address`], the address of the the new auction contract.
+///
+/// ### Returns:
+/// The new state of type [`AuctionFactoryState`].
+#[callback(shortname = 0x02)]
+pub fn deploy_auction_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionFactoryState,
+    auction_id: u64,
+    auction_address: Address,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.results[0].succeeded {
+        mark_deployment_failed(&mut new_state, auction_id);
+        (new_state, vec![])
+    } else {
+        let mut event_group = EventGroup::builder();
+
+        event_group.ping(auction_address, None);
+        event_group
+            .with_callback(SHORTNAME_AUCTION_EXISTS_CALLBACK)
+            .argument(auction_id)
+            .argument(auction_address)
+            .done();
+
+        (new_state, vec![event_group.build()])
+    }
+}
+
+/// Callback for checking if an auction contract has been deployed successfully. If it is the
+/// address is stored in the entry in `auctions`. If it is not the entry is marked as failed
+/// instead.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback.
+/// * `state`: [`AuctionFactoryState`], the state before the call.
+/// * `auction_id`: [`u64`], the id of the new auction contract.
+/// * `auction_address`: [`Address`], the address of the the new auction contract.
+///
+/// ### Returns:
+/// The new state of type [`AuctionFactoryState`].
+#[callback(shortname = 0x03)]
+pub fn auction_exists_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionFactoryState,
+    auction_id: u64,
+    auction_address: Address,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.results[0].succeeded {
+        mark_deployment_failed(&mut new_state, auction_id);
+    } else if let Some(auction) = new_state.auctions.get_mut(&auction_id) {
+        auction.address = Some(auction_address);
+        auction.status = AuctionStatus::Deployed {};
+    }
+    (new_state, vec![])
+}
+
+/// Reminds the factory to execute the auctions that have ended, by relaying an `execute` call to
+/// each deployed auction contract whose end time has passed, up to [`MAX_EXECUTE_BATCH_SIZE`]
+/// auctions at a time. Anyone can send reminders. The relayed calls callback to
+/// `execute_auctions_callback`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`AuctionFactoryState`], the state before the call.
+///
+/// ### Returns:
+/// The new state of type [`AuctionFactoryState`].
+#[action(shortname = 0x04)]
+pub fn execute_ended_auctions(
+    ctx: ContractContext,
+    state: AuctionFactoryState,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    let auction_ids: Vec<u64> = state
+        .auctions
+        .iter()
+        .filter(|(_, auction)| auction.is_ready_to_execute(ctx.block_production_time))
+        .map(|(auction_id, _)| *auction_id)
+        .take(MAX_EXECUTE_BATCH_SIZE)
+        .collect();
+    if auction_ids.is_empty() {
+        panic!("No auctions are ready to be executed");
+    }
+
+    let mut new_state = state;
+    let mut event_group = EventGroup::builder();
+    for auction_id in &auction_ids {
+        let auction = new_state.auctions.get_mut(auction_id).unwrap();
+        auction.status = AuctionStatus::Executing {};
+        event_group
+            .call(auction.address.unwrap(), auction_contract_execute())
+            .done();
+    }
+    event_group
+        .with_callback(SHORTNAME_EXECUTE_AUCTIONS_CALLBACK)
+        .argument(auction_ids)
+        .done();
+
+    (new_state, vec![event_group.build()])
+}
+
+/// Callback for executing auctions. Auctions that were executed successfully are marked as
+/// executed. Auctions whose execution failed are marked as such, and are not reminded again.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback.
+/// * `state`: [`AuctionFactoryState`], the state before the call.
+/// * `auction_ids`: [`Vec<u64>`], the ids of the auctions in the order they were executed.
+///
+/// ### Returns:
+/// The new state of type [`AuctionFactoryState`].
+#[callback(shortname = 0x05)]
+pub fn execute_auctions_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionFactoryState,
+    auction_ids: Vec<u64>,
+) -> (AuctionFactoryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    for (auction_id, result) in auction_ids.iter().zip(callback_ctx.results.iter()) {
+        if let Some(auction) = new_state.auctions.get_mut(auction_id) {
+            auction.status = if result.succeeded {
+                AuctionStatus::Executed {}
+            } else {
+                AuctionStatus::ExecutionFailed {}
+            };
+        }
+    }
+    (new_state, vec![])
+}
+
+/// Creates the initialization bytes of an auction contract, in the order of the arguments of the
+/// `initialize` function of the auction contract.
+fn create_auction_init_bytes(parameters: &AuctionParameters, owner: Address) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![0xff, 0xff, 0xff, 0xff, 0x0f];
+    WriteRPC::rpc_write_to(&parameters.token_amount_for_sale, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.token_for_sale, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.token_for_bidding, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.reserve_price, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.min_increment, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.auction_duration_hours, &mut bytes).unwrap();
+    // Deployed auctions have a single winner, written as the discriminant of the mode
+    WriteRPC::rpc_write_to(&0u8, &mut bytes).unwrap();
+    // Deployed auctions pay no royalties
+    WriteRPC::rpc_write_to(&Vec::<Address>::new(), &mut bytes).unwrap();
+    // Deployed auctions do not allow retracting bids
+    WriteRPC::rpc_write_to(&None::<u32>, &mut bytes).unwrap();
+    // Deployed auctions are settled at the first price, written as the discriminant of the mode
+    WriteRPC::rpc_write_to(&0u8, &mut bytes).unwrap();
+    // Deployed auctions do not refund outbid bidders directly
+    WriteRPC::rpc_write_to(&false, &mut bytes).unwrap();
+    // Unclaimed tokens of deployed auctions never expire, and there is no sweep recipient
+    WriteRPC::rpc_write_to(&None::<i64>, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&None::<Address>, &mut bytes).unwrap();
+    // Deployed auctions do not allow credit bids
+    WriteRPC::rpc_write_to(&None::<u128>, &mut bytes).unwrap();
+    // Deployed auctions have no oracle reserve
+    WriteRPC::rpc_write_to(&None::<Address>, &mut bytes).unwrap();
+    // Deployed auctions do not compensate the highest bidder when cancelled
+    WriteRPC::rpc_write_to(&None::<u32>, &mut bytes).unwrap();
+    // Deployed auctions do not require a deposit to bid
+    WriteRPC::rpc_write_to(&None::<u32>, &mut bytes).unwrap();
+    // Deployed auctions are paid in full by the winner
+    WriteRPC::rpc_write_to(&None::<u32>, &mut bytes).unwrap();
+    // The owner of the deployed auction is the creator, rather than the factory
+    WriteRPC::rpc_write_to(&Some(owner), &mut bytes).unwrap();
+    bytes
+}
+
+/// Auction contract actions
+#[inline]
+fn auction_contract_execute() -> Shortname {
+    Shortname::from_u32(0x06)
+}
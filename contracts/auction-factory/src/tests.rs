@@ -0,0 +1,280 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::ReadRPC;
+
+use crate::{
+    auction_exists_callback, create_auction_init_bytes, deploy_auction, deploy_auction_callback,
+    execute_auctions_callback, execute_ended_auctions, initialize, AuctionFactoryState,
+    AuctionParameters, AuctionStatus, PUB_DEPLOY_ADDRESS,
+};
+
+const HOUR_MILLIS: i64 = 3_600_000;
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    let mut original_transaction = [0u8; 32];
+    original_transaction[31] = 7;
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn create_callback_ctx(results: &[bool]) -> CallbackContext {
+    CallbackContext {
+        success: results.iter().all(|succeeded| *succeeded),
+        results: results
+            .iter()
+            .map(|succeeded| ExecutionResult {
+                succeeded: *succeeded,
+                return_data: vec![],
+            })
+            .collect(),
+    }
+}
+
+fn owner() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+/// The address of the auction deployed in the original transaction of [`create_ctx`].
+fn auction_address() -> Address {
+    create_address(AddressType::PublicContract, 7)
+}
+
+fn parameters() -> AuctionParameters {
+    AuctionParameters {
+        token_amount_for_sale: 100,
+        token_for_sale: create_address(AddressType::PublicContract, 3),
+        token_for_bidding: create_address(AddressType::PublicContract, 4),
+        reserve_price: 10,
+        min_increment: 1,
+        auction_duration_hours: 2,
+    }
+}
+
+fn initial_state() -> AuctionFactoryState {
+    let (state, _) = initialize(create_ctx(owner(), 0), vec![1, 2, 3], vec![4, 5]);
+    state
+}
+
+/// A factory with a single auction, deployed at time zero, which ends after two hours.
+fn state_with_deployed_auction() -> AuctionFactoryState {
+    let (state, _) = deploy_auction(create_ctx(owner(), 0), initial_state(), parameters());
+    let (state, _) = deploy_auction_callback(
+        create_ctx(owner(), 0),
+        create_callback_ctx(&[true]),
+        state,
+        0,
+        auction_address(),
+    );
+    let (state, _) = auction_exists_callback(
+        create_ctx(owner(), 0),
+        create_callback_ctx(&[true]),
+        state,
+        0,
+        auction_address(),
+    );
+    state
+}
+
+#[test]
+fn deploy_auction_deploys_auction_owned_by_the_creator() {
+    let (state, events) = deploy_auction(create_ctx(owner(), 0), initial_state(), parameters());
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(PUB_DEPLOY_ADDRESS, Shortname::from_u32(1))
+        .argument(vec![1u8, 2, 3])
+        .argument(vec![4u8, 5])
+        .argument(create_auction_init_bytes(&parameters(), owner()))
+        .done();
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x02))
+        .with_cost(1000)
+        .argument(0u64)
+        .argument(auction_address())
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+
+    let auction = &state.auctions[&0];
+    assert_eq!(auction.status, AuctionStatus::Deploying {});
+    assert_eq!(auction.address, None);
+    assert_eq!(auction.end_time_millis, 2 * HOUR_MILLIS);
+    assert_eq!(state.next_auction_id, 1);
+}
+
+#[test]
+#[should_panic(expected = "Only owner can deploy auctions")]
+fn only_owner_can_deploy_auctions() {
+    deploy_auction(
+        create_ctx(create_address(AddressType::Account, 9), 0),
+        initial_state(),
+        parameters(),
+    );
+}
+
+#[test]
+fn init_bytes_follow_the_arguments_of_the_auction() {
+    let bytes = create_auction_init_bytes(&parameters(), owner());
+    let mut reader = bytes.as_slice();
+    let mut init_shortname = [0u8; 5];
+    std::io::Read::read_exact(&mut reader, &mut init_shortname).unwrap();
+    assert_eq!(init_shortname, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+
+    assert_eq!(u128::rpc_read_from(&mut reader), 100);
+    assert_eq!(
+        Address::rpc_read_from(&mut reader),
+        parameters().token_for_sale
+    );
+    assert_eq!(
+        Address::rpc_read_from(&mut reader),
+        parameters().token_for_bidding
+    );
+    assert_eq!(u128::rpc_read_from(&mut reader), 10);
+    assert_eq!(u128::rpc_read_from(&mut reader), 1);
+    assert_eq!(u32::rpc_read_from(&mut reader), 2);
+    // Single winner mode
+    assert_eq!(u8::rpc_read_from(&mut reader), 0);
+    // No royalty payees
+    assert_eq!(Vec::<Address>::rpc_read_from(&mut reader), vec![]);
+    // No retraction penalty
+    assert_eq!(Option::<u32>::rpc_read_from(&mut reader), None);
+    // First price settlement
+    assert_eq!(u8::rpc_read_from(&mut reader), 0);
+    // No automatic refunds
+    assert!(!bool::rpc_read_from(&mut reader));
+    // No expiry of unclaimed tokens, sweep recipient, credit bids, oracle reserve, cancel
+    // compensation, bid deposit or installment plan
+    for _ in 0..7 {
+        assert_eq!(u8::rpc_read_from(&mut reader), 0);
+    }
+    assert_eq!(Option::<Address>::rpc_read_from(&mut reader), Some(owner()));
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn failed_deployment_is_marked_failed() {
+    let (state, _) = deploy_auction(create_ctx(owner(), 0), initial_state(), parameters());
+    let (state, events) = deploy_auction_callback(
+        create_ctx(owner(), 0),
+        create_callback_ctx(&[false]),
+        state,
+        0,
+        auction_address(),
+    );
+    assert!(events.is_empty());
+    assert_eq!(state.auctions[&0].status, AuctionStatus::Failed {});
+}
+
+#[test]
+fn successful_deployment_checks_that_the_auction_exists() {
+    let (state, _) = deploy_auction(create_ctx(owner(), 0), initial_state(), parameters());
+    let (state, events) = deploy_auction_callback(
+        create_ctx(owner(), 0),
+        create_callback_ctx(&[true]),
+        state,
+        0,
+        auction_address(),
+    );
+
+    let mut expected = EventGroup::builder();
+    expected.ping(auction_address(), None);
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x03))
+        .argument(0u64)
+        .argument(auction_address())
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+    assert_eq!(state.auctions[&0].status, AuctionStatus::Deploying {});
+}
+
+#[test]
+fn existing_auction_is_marked_deployed() {
+    let state = state_with_deployed_auction();
+    let auction = &state.auctions[&0];
+    assert_eq!(auction.status, AuctionStatus::Deployed {});
+    assert_eq!(auction.address, Some(auction_address()));
+}
+
+#[test]
+fn missing_auction_is_marked_failed() {
+    let (state, _) = deploy_auction(create_ctx(owner(), 0), initial_state(), parameters());
+    let (state, _) = auction_exists_callback(
+        create_ctx(owner(), 0),
+        create_callback_ctx(&[false]),
+        state,
+        0,
+        auction_address(),
+    );
+    assert_eq!(state.auctions[&0].status, AuctionStatus::Failed {});
+    assert_eq!(state.auctions[&0].address, None);
+}
+
+#[test]
+fn ended_auctions_are_executed() {
+    let anyone = create_address(AddressType::Account, 9);
+    let (state, events) = execute_ended_auctions(
+        create_ctx(anyone, 2 * HOUR_MILLIS),
+        state_with_deployed_auction(),
+    );
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(auction_address(), Shortname::from_u32(0x06))
+        .done();
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x05))
+        .argument(vec![0u64])
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+    assert_eq!(state.auctions[&0].status, AuctionStatus::Executing {});
+
+    let (state, _) = execute_auctions_callback(
+        create_ctx(anyone, 2 * HOUR_MILLIS),
+        create_callback_ctx(&[true]),
+        state,
+        vec![0],
+    );
+    assert_eq!(state.auctions[&0].status, AuctionStatus::Executed {});
+}
+
+#[test]
+#[should_panic(expected = "No auctions are ready to be executed")]
+fn running_auctions_are_not_executed() {
+    execute_ended_auctions(
+        create_ctx(owner(), 2 * HOUR_MILLIS - 1),
+        state_with_deployed_auction(),
+    );
+}
+
+#[test]
+#[should_panic(expected = "No auctions are ready to be executed")]
+fn failed_execution_is_not_retried() {
+    let (state, _) = execute_ended_auctions(
+        create_ctx(owner(), 2 * HOUR_MILLIS),
+        state_with_deployed_auction(),
+    );
+    let (state, _) = execute_auctions_callback(
+        create_ctx(owner(), 2 * HOUR_MILLIS),
+        create_callback_ctx(&[false]),
+        state,
+        vec![0],
+    );
+    assert_eq!(state.auctions[&0].status, AuctionStatus::ExecutionFailed {});
+
+    execute_ended_auctions(create_ctx(owner(), 3 * HOUR_MILLIS), state);
+}
@@ -0,0 +1,56 @@
+//! Fuzzes bidding on the auction, invoking the generated entry points of `initialize`, `start`,
+//! `start_callback`, `bid`, `bid_for`, `bid_callback`, `bid_with_deposit_callback`,
+//! `bid_quantity`, `bid_quantity_callback`, `retract_bid` and `credit_bid` with arbitrary RPC
+//! payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use auction_contract::{
+    __pbc_autogen__bid_callback_wrapped, __pbc_autogen__bid_for_wrapped,
+    __pbc_autogen__bid_quantity_callback_wrapped, __pbc_autogen__bid_quantity_wrapped,
+    __pbc_autogen__bid_with_deposit_callback_wrapped, __pbc_autogen__bid_wrapped,
+    __pbc_autogen__credit_bid_wrapped, __pbc_autogen__initialize_wrapped,
+    __pbc_autogen__retract_bid_wrapped, __pbc_autogen__start_callback_wrapped,
+    __pbc_autogen__start_wrapped,
+};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+
+const CALLS: [Call; 10] = [
+    Call::Action(__pbc_autogen__start_wrapped),
+    Call::Callback(__pbc_autogen__start_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__bid_wrapped),
+    Call::Action(__pbc_autogen__bid_for_wrapped),
+    Call::Callback(__pbc_autogen__bid_callback_wrapped, 1),
+    Call::Callback(__pbc_autogen__bid_with_deposit_callback_wrapped, 2),
+    Call::Action(__pbc_autogen__bid_quantity_wrapped),
+    Call::Callback(__pbc_autogen__bid_quantity_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__retract_bid_wrapped),
+    Call::Action(__pbc_autogen__credit_bid_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_bid(&mut Unstructured::new(data));
+});
+
+fn fuzz_bid(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
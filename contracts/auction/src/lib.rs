@@ -6,7 +6,13 @@
 //! This is done through `transfer` calls to the token contracts with
 //! callbacks ensuring that the transfers were successful.
 //! If a bid is not the current highest bid the transferred bidding tokens can
-//! be claimed during any phase.
+//! be claimed during any phase. Anyone can trigger a claim on behalf of a beneficiary using
+//! `claim_for`, which is useful for bidders that are contracts, but the claimed tokens are always
+//! transferred to the beneficiary.
+//!
+//! The auction can be configured to refund displaced bidders automatically. When a bidder is
+//! outbid, their bid is then transferred back to them directly instead of being added to the claim
+//! map. If the refund transfer fails, the bid is added to the claim map as a fallback.
 //!
 //! The auction has a set `duration`. After this duration the auction no longer accepts bids and can
 //! be executed by anyone. Once `execute` has been called the contract moves the tokens for sale
@@ -16,10 +22,104 @@
 //! from the bidder to the contract. Once the transfer is done the contract updates its
 //! highest bidder accordingly.
 //!
+//! A bidder can also bid on behalf of another address using `bid_for`. The bidder pays for the
+//! bid, and is refunded if the bid is outbid or not accepted, while the tokens for sale are
+//! credited to the beneficiary of the bid if it wins.
+//!
+//! The auction can be configured with a retraction penalty, allowing the highest bidder to
+//! `retract_bid` before the auction ends. The retracting bidder can claim their bid less the
+//! penalty in per mille, which is credited to the contract owner. The previous highest bids are
+//! kept in a bid history, such that the latest previous highest bid whose tokens are still held
+//! by the contract is reinstated as the highest bid.
+//!
+//! In the partial fill mode, the tokens for sale are divisible goods that can be sold to several
+//! winning bidders at a uniform clearing price. Bidders call `bid_quantity` with the quantity of
+//! tokens they want and the price they offer per token, escrowing `quantity * price`. When the
+//! auction is executed the bids are filled from the highest price down until the tokens for sale
+//! are sold out, and the price of the last filled bid becomes the clearing price paid by all
+//! winners. Bids at the clearing price share the remaining tokens pro-rata to their quantities.
+//! The escrow exceeding the payment of each bidder can be claimed back.
+//!
+//! The single winner auction is settled either at the first price, where the highest bidder pays
+//! their bid, or at the second price, where the highest bidder only pays the runner-up bid plus
+//! the minimum increment (but at least the reserve price). In the second price settlement the
+//! difference to the highest bid is refunded to the highest bidder through the claim map. The
+//! runner-up bid is the highest bid that was placed during the bidding phase besides the highest
+//! bid.
+//!
+//! The auction can be configured with royalty payees, each receiving a share of the proceeds in
+//! per mille when the auction is executed. The proceeds are divided into the claims of the payees
+//! and the contract owner, where the owner receives the remainder after the royalties, including
+//! any rounding remainder.
+//!
+//! If no bid meeting the reserve price has been accepted when the auction is executed, the reserve
+//! is not met and nothing is sold. The tokens for sale are returned to the claims of the contract
+//! owner, and any highest bid to the claims of its bidder. The owner can then `relist` the tokens
+//! for sale with a new reserve price and duration, which starts a new bidding phase without
+//! redeploying the contract, as long as the tokens for sale have not been claimed.
+//!
+//! The auction can be configured with a credit bid cap, allowing the contract owner to
+//! `credit_bid` up to the cap, as is common in debt auctions. A credit bid is paid with the
+//! proceeds of the auction itself, so no tokens are transferred. If the credit bid wins, the
+//! tokens for sale are returned to the owner and no proceeds are credited. An outbid credit bid
+//! is simply dropped, since no tokens were escrowed for it.
+//!
+//! The auction can be configured with a reserve denominated in USD, converted into tokens for
+//! bidding with the median price of a price oracle when the auction is executed. `execute` then
+//! queries the oracle, and the auction is settled in `execute_callback` with the reserve price
+//! raised to the converted reserve, if that is higher. The reserve price given at initialization
+//! still applies to the bids, while the converted reserve only decides whether the reserve is met.
+//! The oracle price is cached with the `cached-query` library. If the query fails the pending
+//! query is cleared and the auction can be executed again, and a price fetched within the last
+//! five minutes is used without querying the oracle again.
+//!
 //! The contract owner also has the ability to `cancel` the contract during the bidding phase.
 //! If cancel is called the highest bid is taken out of escrow such that the highest bidder can
 //! claim it again. The same is done for the tokens for sale which the contract owner
 //! then can claim.
+//!
+//! The auction can be configured to compensate the displaced highest bidder when it is cancelled,
+//! with a consolation fee in per mille of their bid. The fee is either taken from the tokens for
+//! sale returned to the owner, valued at the price of the highest bid, or paid in tokens for
+//! bidding from a compensation pot that the owner funds with `fund_compensation_pot`. A pot that
+//! is too small pays what it holds, and whatever is left in the pot is returned to the owner when
+//! the auction is cancelled or executed.
+//!
+//! The auction can be configured to require a flat deposit in a separate token to bid, against
+//! spam bids. A bidder locks the deposit along with their first bid, and the deposit is refunded
+//! when they claim once the auction is over. With a deposit requirement, executing the auction
+//! does not settle it right away. The auction instead awaits the completion by the highest bidder,
+//! who must `complete` the settlement within a grace period. If the highest bidder fails to do so,
+//! anyone can `forfeit_deposit` once the grace period has passed, which forfeits the deposit of
+//! the highest bidder to the contract owner and voids the sale as if the reserve was not met.
+//!
+//! The auction can be configured with an installment plan, where bids only escrow an up-front
+//! payment in per mille of the bid. When the auction is executed, the up-front payment of the
+//! highest bid is credited to the proceeds, and the highest bidder pays the remainder of their bid
+//! in a number of equal installments with `pay_installment`, each due one interval after the
+//! previous. The tokens for sale vest to the winner pro-rata to the paid installments, and once all
+//! installments are paid the auction has ended. If an installment is not paid before it is due,
+//! the contract owner can `reclaim_unvested` tokens for sale, which ends the auction with the
+//! winner keeping the vested tokens and the owner keeping the payments.
+//!
+//! Once the auction has ended the contract owner can choose to `settle_via_splitter` instead of
+//! claiming the proceeds. This transfers the proceeds to a payment splitter contract and registers
+//! the payment there, such that the revenue can be divided between several sellers. If the
+//! transfer fails the proceeds are restored to the owner's claim, and if only the registration
+//! fails the payment is kept as unregistered, such that the owner can retry it with
+//! `register_splitter_payment`.
+//!
+//! The auction can be configured with an expiry of unclaimed tokens. Once the auction is over, the
+//! contract owner can `sweep_expired_claims` to collect the tokens left in the claim map for longer
+//! than the expiry, measured from the end of the auction or the latest credit of the claim. The
+//! swept tokens are transferred to the owner, or donated to a configured sweep recipient.
+//!
+//! Every address that is credited a claim or locks a deposit is assigned a claimant nonce, counting
+//! from zero in the order the addresses became claimants. Anyone can `claim_range` to pay out the
+//! claims of the claimants with a range of nonces, such that the claims of thousands of displaced
+//! bidders can be paid out in pages of bounded size, without iterating the claim map. Once the
+//! claims and deposit of a claimant have been paid out, the claimant is removed, and is assigned a
+//! new nonce if it becomes a claimant again.
 #![allow(unused_variables)]
 
 #[macro_use]
@@ -27,11 +127,13 @@ extern crate pbc_contract_codegen;
 
 use std::collections::BTreeMap;
 
+use cached_query::CachedQuery;
+use claim_map::{failed_payouts, payout_event_group, Claim, ClaimMap, Payout};
 use create_type_spec_derive::CreateTypeSpec;
 use pbc_contract_common::address::{Address, AddressType, Shortname};
 use pbc_contract_common::context::{CallbackContext, ContractContext};
 use pbc_contract_common::events::EventGroup;
-use read_write_rpc_derive::{ReadRPC, WriteRPC};
+use read_write_rpc_derive::{ReadRPC, ReadWriteRPC, WriteRPC};
 use read_write_state_derive::ReadWriteState;
 
 mod tests;
@@ -40,14 +142,175 @@ mod tests;
 ///
 /// ### Fields:
 ///
-/// * `bidder`: [`Address`], the address of the bidder.
+/// * `bidder`: [`Address`], the address of the bidder, who paid for the bid and is refunded the
+///   bidding tokens.
+///
+/// * `beneficiary`: [`Address`], the address credited the tokens for sale if the bid wins, which
+///   is the bidder unless the bid was made with `bid_for`.
 ///
 /// * `amount`: [`u128`], the bid amount.
 #[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
 #[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
 pub struct Bid {
     bidder: Address,
+    beneficiary: Address,
+    amount: u128,
+}
+
+/// Custom struct for bids in the partial fill mode.
+///
+/// ### Fields:
+///
+/// * `bidder`: [`Address`], the address of the bidder.
+///
+/// * `quantity`: [`u128`], the amount of tokens for sale that the bidder wants to buy.
+///
+/// * `price`: [`u128`], the maximum price per token for sale, in tokens for bidding.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct QuantityBid {
+    bidder: Address,
+    quantity: u128,
+    price: u128,
+}
+
+impl QuantityBid {
+    /// The amount of tokens for bidding escrowed for the bid.
+    fn escrow(&self) -> u128 {
+        self.quantity
+            .checked_mul(self.price)
+            .expect("The escrow of the bid is too large")
+    }
+}
+
+/// Custom struct for royalty payees, receiving a share of the proceeds of the auction.
+///
+/// ### Fields:
+///
+/// * `payee`: [`Address`], the address of the payee.
+///
+/// * `per_mille`: [`u32`], the share of the proceeds received by the payee, in per mille.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct RoyaltyPayee {
+    payee: Address,
+    per_mille: u32,
+}
+
+/// Custom struct for a reserve denominated in USD, converted into tokens for bidding with the
+/// median price of a price oracle when the auction is executed.
+///
+/// ### Fields:
+///
+/// * `oracle`: [`Address`], the address of the price oracle, whose median price is the price of
+///   one token for bidding in micro USD.
+///
+/// * `min_reserve_usd`: [`u128`], the minimum reserve for the tokens for sale in USD.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct OracleReserve {
+    oracle: Address,
+    min_reserve_usd: u128,
+}
+
+/// Custom struct for the compensation paid to the highest bidder when the auction is cancelled.
+///
+/// ### Fields:
+///
+/// * `per_mille`: [`u32`], the compensation in per mille of the highest bid.
+///
+/// * `source`: [`CompensationSource`], where the compensation is taken from.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct CancelCompensation {
+    per_mille: u32,
+    source: CompensationSource,
+}
+
+/// The source of the compensation paid to the highest bidder when the auction is cancelled.
+#[derive(PartialEq, Eq, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Clone, Debug))]
+pub enum CompensationSource {
+    /// The compensation is paid in tokens for sale, taken from the tokens returned to the owner.
+    /// Valued at the price of the highest bid, the compensation is the same per mille of the
+    /// tokens for sale.
+    #[discriminant(0)]
+    SaleTokens {},
+    /// The compensation is paid in tokens for bidding from the compensation pot funded by the
+    /// owner.
+    #[discriminant(1)]
+    Pot {},
+}
+
+/// Custom struct for the deposit required to bid, locked by each bidder along with their first bid.
+///
+/// ### Fields:
+///
+/// * `token`: [`Address`], the address of the token of the deposit.
+///
+/// * `amount`: [`u128`], the flat amount of the deposit.
+///
+/// * `completion_grace_millis`: [`i64`], the time in millis after the auction is executed within
+///   which the highest bidder must complete the settlement, or forfeit their deposit.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct BidDeposit {
+    token: Address,
     amount: u128,
+    completion_grace_millis: i64,
+}
+
+/// Custom struct for the installment plan of the winner, who escrows an up-front payment with their
+/// bids and pays the remainder of the winning bid in installments.
+///
+/// ### Fields:
+///
+/// * `upfront_per_mille`: [`u32`], the up-front payment escrowed with each bid, in per mille of the
+///   bid.
+///
+/// * `installments`: [`u32`], the number of installments the remainder of the winning bid is
+///   paid in.
+///
+/// * `interval_millis`: [`i64`], the time in millis between the due dates of the installments,
+///   the first being due one interval after the auction is executed.
+#[derive(ReadRPC, WriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct InstallmentPlan {
+    upfront_per_mille: u32,
+    installments: u32,
+    interval_millis: i64,
+}
+
+/// The number of micro USD in a USD, being the unit of the prices of the oracle.
+const MICRO_USD_PER_USD: u128 = 1_000_000;
+
+/// The number of millis a cached oracle price stays fresh.
+const ORACLE_PRICE_TTL_MILLIS: i64 = 5 * 60 * 1000;
+
+/// The mode of the auction, deciding how the tokens for sale are sold.
+#[derive(PartialEq, Eq, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Clone, Debug))]
+pub enum AuctionMode {
+    /// All tokens for sale are sold to the highest bidder.
+    #[discriminant(0)]
+    SingleWinner {},
+    /// The tokens for sale are divided between several winning bidders at a uniform clearing
+    /// price.
+    #[discriminant(1)]
+    PartialFill {},
+}
+
+/// The settlement of a single winner auction, deciding the price paid by the highest bidder.
+#[derive(PartialEq, Eq, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Clone, Debug))]
+pub enum SettlementMode {
+    /// The highest bidder pays their bid.
+    #[discriminant(0)]
+    FirstPrice {},
+    /// The highest bidder pays the runner-up bid plus the minimum increment, and at least the
+    /// reserve price.
+    #[discriminant(1)]
+    SecondPrice {},
 }
 
 /// Custom struct for TokenClaims used by the contracts claim-map.
@@ -57,11 +320,35 @@ pub struct Bid {
 /// * `tokens_for_bidding`: [`u128`], The claimable tokens for bidding.
 ///
 /// * `tokens_for_sale`: [`u128`], The claimable tokens for sale.
+///
+/// * `credited_at_millis`: [`i64`], The time in millis UTC at which tokens were last credited to
+///   the claim, from which the expiry of the claim is measured.
 #[derive(ReadWriteState, CreateTypeSpec)]
 #[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
 pub struct TokenClaim {
     tokens_for_bidding: u128,
     tokens_for_sale: u128,
+    credited_at_millis: i64,
+}
+
+impl Claim for TokenClaim {
+    fn empty() -> Self {
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 0,
+            credited_at_millis: 0,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.tokens_for_bidding += other.tokens_for_bidding;
+        self.tokens_for_sale += other.tokens_for_sale;
+        self.credited_at_millis = self.credited_at_millis.max(other.credited_at_millis);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens_for_bidding == 0 && self.tokens_for_sale == 0
+    }
 }
 
 //// Constants for the different phases of the contract.
@@ -71,6 +358,12 @@ const CREATION: ContractStatus = 0;
 const BIDDING: ContractStatus = 1;
 const ENDED: ContractStatus = 2;
 const CANCELLED: ContractStatus = 3;
+const RESERVE_NOT_MET: ContractStatus = 4;
+const AWAITING_COMPLETION: ContractStatus = 5;
+const PAYING_INSTALLMENTS: ContractStatus = 6;
+
+/// The maximum number of claimants whose claims can be paid out by a single `claim_range`.
+const MAX_CLAIM_RANGE: u64 = 50;
 
 /// Token contract actions
 #[inline]
@@ -83,6 +376,25 @@ fn token_contract_transfer_from() -> Shortname {
     Shortname::from_u32(0x03)
 }
 
+/// Price oracle contract actions
+#[inline]
+fn oracle_contract_query_median() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
+/// Payment splitter contract actions
+///
+/// A payment splitter is any contract exposing `register_payment` with shortname 0x02 and the
+/// arguments `token`: [`Address`] and `amount`: [`u128`]. It registers a payment of `amount`
+/// tokens of `token`, which have been transferred to the splitter beforehand, for division among
+/// its payees. The splitter must fail the registration if it has not received `amount` tokens of
+/// `token` that are not yet registered, such that a payment is only registered once and only when
+/// it has been received.
+#[inline]
+fn payment_splitter_register_payment() -> Shortname {
+    Shortname::from_u32(0x02)
+}
+
 /// Custom struct for the state of the contract.
 ///
 /// The "state" attribute is attached.
@@ -103,12 +415,90 @@ fn token_contract_transfer_from() -> Shortname {
 ///
 /// * `highest_bidder`: [`Bid`], the current highest `Bid`.
 ///
-/// * `reserve_price`: [`u128`], the reserve price (minimum cost of the tokens for sale).
+/// * `runner_up`: [`Bid`], the highest `Bid` besides the highest bid, used for second price
+///   settlement.
+///
+/// * `bid_history`: [`Vec<Bid>`], the previous highest bids, with the latest last. A bid in the
+///   history is reinstated if the highest bid is retracted, as long as its tokens have not been
+///   claimed.
+///
+/// * `retraction_penalty_per_mille`: [`Option<u32>`], the penalty in per mille paid to the contract
+///   owner for retracting the highest bid, or None if the highest bid cannot be retracted.
+///
+/// * `reserve_price`: [`u128`], the reserve price (minimum cost of the tokens for sale). In the
+///   partial fill mode this is the minimum price per token for sale.
 ///
 /// * `min_increment`: [`u128`], the minimum increment of each bid.
 ///
+/// * `mode`: [`AuctionMode`], the mode of the auction.
+///
+/// * `settlement`: [`SettlementMode`], the settlement of the auction in the single winner mode.
+///
+/// * `quantity_bids`: [`Vec<QuantityBid>`], the accepted bids in the partial fill mode.
+///
+/// * `royalty_payees`: [`Vec<RoyaltyPayee>`], the payees receiving royalties of the proceeds.
+///
+/// * `clearing_price`: [`Option<u128>`], the uniform price per token paid by the winners in the
+///   partial fill mode, determined when the auction is executed.
+///
 /// * `claim_map`: [`BTreeMap<Address, TokenClaim>`], the map of all claimable tokens.
 ///
+/// * `claimant_nonces`: [`BTreeMap<Address, u64>`], the nonce of every address that has a claim
+///   or a deposit that has not been paid out.
+///
+/// * `claimants`: [`BTreeMap<u64, Address>`], the claimants indexed by their nonce, used for
+///   paying out claims in pages with `claim_range`.
+///
+/// * `next_claimant_nonce`: [`u64`], the nonce assigned to the next claimant.
+///
+/// * `auto_refund`: [`bool`], whether outbid bidders are refunded directly instead of through the
+///   claim map.
+///
+/// * `unclaimed_expiry_millis`: [`Option<i64>`], the time in millis after which unclaimed tokens
+///   can be swept from the claim map once the auction is over, or None if they never expire.
+///
+/// * `sweep_recipient`: [`Option<Address>`], the address receiving swept tokens, or None if they
+///   are received by the contract owner.
+///
+/// * `credit_bid_cap`: [`Option<u128>`], the maximum amount the contract owner can bid on credit,
+///   or None if the owner cannot bid on credit.
+///
+/// * `highest_bid_is_credit`: [`bool`], whether the highest bid is a credit bid of the contract
+///   owner, for which no tokens are escrowed.
+///
+/// * `oracle_reserve`: [`Option<OracleReserve>`], the reserve in USD converted with the price of
+///   an oracle when the auction is executed, or None if only the reserve price applies.
+///
+/// * `oracle_price`: [`Option<CachedQuery<u128>>`], the cached median price of the oracle in
+///   micro USD, if the auction has an oracle reserve.
+///
+/// * `cancel_compensation`: [`Option<CancelCompensation>`], the compensation paid to the highest
+///   bidder if the auction is cancelled, or None if the highest bidder is not compensated.
+///
+/// * `compensation_pot`: [`u128`], the tokens for bidding funded by the owner for compensating
+///   the highest bidder, which have not yet been paid or returned.
+///
+/// * `bid_deposit`: [`Option<BidDeposit>`], the deposit required to bid, or None if bidding
+///   requires no deposit.
+///
+/// * `deposits`: [`BTreeMap<Address, u128>`], the deposits held by the contract, which are
+///   refunded when claimed once the auction is over. A forfeited deposit is held for the contract
+///   owner.
+///
+/// * `completion_deadline_millis`: [`Option<i64>`], the time in millis UTC before which the
+///   highest bidder must complete the settlement, once the auction awaits completion.
+///
+/// * `installment_plan`: [`Option<InstallmentPlan>`], the installment plan of the winner, or None
+///   if bids escrow the full amount and the auction is settled when executed.
+///
+/// * `installments_paid`: [`u32`], the number of installments paid by the winner.
+///
+/// * `next_installment_due_millis`: [`Option<i64>`], the time in millis UTC before which the next
+///   installment must be paid, while the winner is paying installments.
+///
+/// * `unregistered_splitter_payments`: [`BTreeMap<Address, u128>`], the proceeds transferred to
+///   each payment splitter for which the registration of the payment failed.
+///
 /// * `status`: [`u8`], the status of the contract.
 #[state]
 #[cfg_attr(test, derive(Clone, PartialEq, Eq, Debug))]
@@ -120,9 +510,36 @@ pub struct AuctionContractState {
     token_for_sale: Address,
     token_for_bidding: Address,
     highest_bidder: Bid,
+    runner_up: Bid,
+    bid_history: Vec<Bid>,
+    retraction_penalty_per_mille: Option<u32>,
     reserve_price: u128,
     min_increment: u128,
+    mode: AuctionMode,
+    settlement: SettlementMode,
+    quantity_bids: Vec<QuantityBid>,
+    royalty_payees: Vec<RoyaltyPayee>,
+    clearing_price: Option<u128>,
     claim_map: BTreeMap<Address, TokenClaim>,
+    claimant_nonces: BTreeMap<Address, u64>,
+    claimants: BTreeMap<u64, Address>,
+    next_claimant_nonce: u64,
+    auto_refund: bool,
+    unclaimed_expiry_millis: Option<i64>,
+    sweep_recipient: Option<Address>,
+    credit_bid_cap: Option<u128>,
+    highest_bid_is_credit: bool,
+    oracle_reserve: Option<OracleReserve>,
+    oracle_price: Option<CachedQuery<u128>>,
+    cancel_compensation: Option<CancelCompensation>,
+    compensation_pot: u128,
+    bid_deposit: Option<BidDeposit>,
+    deposits: BTreeMap<Address, u128>,
+    completion_deadline_millis: Option<i64>,
+    installment_plan: Option<InstallmentPlan>,
+    installments_paid: u32,
+    next_installment_due_millis: Option<i64>,
+    unregistered_splitter_payments: BTreeMap<Address, u128>,
     status: ContractStatus,
 }
 
@@ -136,79 +553,2056 @@ impl AuctionContractState {
     /// * `additional_claim`: The additional [`TokenClaim`] that the `bidder` can claim.
     ///
     fn add_to_claim_map(&mut self, bidder: Address, additional_claim: TokenClaim) {
-        let mut entry = self.claim_map.entry(bidder).or_insert(TokenClaim {
-            tokens_for_bidding: 0,
-            tokens_for_sale: 0,
-        });
-        entry.tokens_for_bidding += additional_claim.tokens_for_bidding;
-        entry.tokens_for_sale += additional_claim.tokens_for_sale;
+        self.register_claimant(bidder);
+        self.claim_map.credit(bidder, additional_claim);
     }
-}
 
-/// Initial function to bootstrap the contracts state.
-///
-/// ### Parameters:
-///
-/// * `ctx`: [`ContractContext`], initial context.
-///
-/// * `token_amount_for_sale`: [`u128`], the amount of tokens that is going to be sold.
-///
-/// * `token_for_sale`: [`Address`], the address of the token for sale.
-///
-/// * `token_for_bidding`: [`Address`], the address of the token used for bidding.
-///
-/// * `reserve_price`: [`u128`], the reserve price (minimum cost of the tokens for sale).
-///
-/// * `min_increment`: [`u128`], the minimum increment of each bid.
-///
-/// * `auction_duration_hours`: [`u32`], the duration of the auction in hours.
-///
-/// ### Returns:
-///
-/// The new state object of type [`AuctionContractState`] with the initial state being
-/// [`CREATION`].
-#[init]
-pub fn initialize(
-    ctx: ContractContext,
-    token_amount_for_sale: u128,
-    token_for_sale: Address,
-    token_for_bidding: Address,
-    reserve_price: u128,
-    min_increment: u128,
-    auction_duration_hours: u32,
+    /// Raises the reserve price to the reserve in USD of the oracle reserve, converted into tokens
+    /// for bidding at `price_micro_usd`, if that is higher.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `price_micro_usd`: [`u128`], the median price of the oracle in micro USD.
+    ///
+    fn apply_oracle_reserve(&mut self, price_micro_usd: u128) {
+        let min_reserve_usd = self
+            .oracle_reserve
+            .as_ref()
+            .expect("The auction has no oracle reserve")
+            .min_reserve_usd;
+        let converted_reserve = oracle_reserve_price(min_reserve_usd, price_micro_usd);
+        self.reserve_price = self.reserve_price.max(converted_reserve);
+    }
+
+    /// Credits the payouts whose transfers failed back to `claimant`, see
+    /// [`ClaimMap::restore_if_failed`]. The payouts list the tokens for bidding first and the
+    /// tokens for sale second, as created by `claim_payouts`. Any further payout is not part of
+    /// the claim map.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `callback_ctx`: [`&CallbackContext`], the context of the transfer callback.
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    /// * `payouts`: [`&[Payout]`], the payouts that were transferred.
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the failed payouts are credited.
+    ///
+    fn restore_claim_if_failed(
+        &mut self,
+        callback_ctx: &CallbackContext,
+        claimant: Address,
+        payouts: &[Payout],
+        now: i64,
+    ) {
+        let restored =
+            self.claim_map
+                .restore_if_failed(callback_ctx, claimant, payouts, |index, amount| {
+                    TokenClaim {
+                        tokens_for_bidding: if index == 0 { amount } else { 0 },
+                        tokens_for_sale: if index == 1 { amount } else { 0 },
+                        credited_at_millis: now,
+                    }
+                });
+        if restored {
+            self.register_claimant(claimant);
+        }
+    }
+
+    /// Creates the payouts of `tokens_for_bidding`, `tokens_for_sale` and, if bidding requires a
+    /// deposit, the `deposit`, in that order.
+    fn claim_payouts(
+        &self,
+        tokens_for_bidding: u128,
+        tokens_for_sale: u128,
+        deposit: u128,
+    ) -> Vec<Payout> {
+        let mut payouts = vec![
+            Payout {
+                token: self.token_for_bidding,
+                amount: tokens_for_bidding,
+            },
+            Payout {
+                token: self.token_for_sale,
+                amount: tokens_for_sale,
+            },
+        ];
+        if let Some(bid_deposit) = &self.bid_deposit {
+            payouts.push(Payout {
+                token: bid_deposit.token,
+                amount: deposit,
+            });
+        }
+        payouts
+    }
+
+    /// Assigns the next claimant nonce to `claimant`, unless it already has one.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    fn register_claimant(&mut self, claimant: Address) {
+        if !self.claimant_nonces.contains_key(&claimant) {
+            let nonce = self.next_claimant_nonce;
+            self.next_claimant_nonce += 1;
+            self.claimant_nonces.insert(claimant, nonce);
+            self.claimants.insert(nonce, claimant);
+        }
+    }
+
+    /// Removes `claimant` and its claim map entry, if it has nothing left to claim and no deposit.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    fn release_claimant(&mut self, claimant: &Address) {
+        let has_claim = self
+            .claim_map
+            .get(claimant)
+            .is_some_and(|claim| !claim.is_empty());
+        if has_claim || self.deposits.contains_key(claimant) {
+            return;
+        }
+        self.claim_map.remove(claimant);
+        if let Some(nonce) = self.claimant_nonces.remove(claimant) {
+            self.claimants.remove(&nonce);
+        }
+    }
+
+    /// Takes the claimable tokens of `beneficiary` from the claim map, along with their deposit if
+    /// it can be refunded, and creates the transfers of them to `beneficiary`, with a callback to
+    /// `claim_callback`. The beneficiary is then removed as a claimant, unless its deposit is held.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `beneficiary`: The [`Address`] to transfer the claimable tokens to.
+    ///
+    /// ### Returns
+    ///
+    /// The [`EventGroup`] transferring the tokens, or None if `beneficiary` has nothing to claim.
+    fn take_payout(&mut self, beneficiary: Address) -> Option<EventGroup> {
+        let deposit = self.take_refundable_deposit(&beneficiary);
+        let claimable = match self.claim_map.take_claim(&beneficiary) {
+            None if deposit == 0 => return None,
+            claimable => claimable.unwrap_or_else(TokenClaim::empty),
+        };
+        let payouts = self.claim_payouts(
+            claimable.tokens_for_bidding,
+            claimable.tokens_for_sale,
+            deposit,
+        );
+        let mut event_group = payout_event_group(beneficiary, &payouts);
+        event_group
+            .with_callback(SHORTNAME_CLAIM_CALLBACK)
+            .argument(beneficiary)
+            .argument(claimable.tokens_for_bidding)
+            .argument(claimable.tokens_for_sale)
+            .argument(deposit)
+            .done();
+        self.release_claimant(&beneficiary);
+        Some(event_group.build())
+    }
+
+    /// Reinstates the latest bid in the bid history whose bidding tokens are still claimable, by
+    /// taking the tokens out of the claim map of its bidder. Bids whose tokens have been claimed
+    /// are dropped from the history. If no bid can be reinstated, the highest bid is reset to the
+    /// initial empty bid of the contract owner. The runner-up becomes the bid preceding the
+    /// reinstated bid in the history.
+    fn reinstate_previous_bid(&mut self) {
+        self.highest_bidder = self.empty_bid();
+        self.highest_bid_is_credit = false;
+        while let Some(previous) = self.bid_history.pop() {
+            let escrow = self.escrow_of(previous.amount);
+            if let Some(claim) = self.claim_map.get_mut(&previous.bidder) {
+                if claim.tokens_for_bidding >= escrow {
+                    claim.tokens_for_bidding -= escrow;
+                    self.highest_bidder = previous;
+                    break;
+                }
+            }
+        }
+        self.runner_up = match self.bid_history.last() {
+            Some(bid) => Bid {
+                bidder: bid.bidder,
+                beneficiary: bid.beneficiary,
+                amount: bid.amount,
+            },
+            None => self.empty_bid(),
+        };
+    }
+
+    /// Replaces the highest bid with a new higher bid during the bidding phase. The previous
+    /// highest bid becomes the runner-up, and its tokens are either refunded directly, if the
+    /// auction refunds outbid bidders automatically, or added to the claim map of its bidder.
+    /// A previous credit bid has no escrowed tokens, and is neither refunded nor kept in the bid
+    /// history.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `bid`: [`Bid`], the new highest bid.
+    ///
+    /// * `is_credit`: [`bool`], whether the new highest bid is a credit bid.
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the bid is placed.
+    ///
+    /// ### Returns
+    ///
+    /// The events refunding the previous highest bid, if any.
+    fn replace_highest_bid(&mut self, bid: Bid, is_credit: bool, now: i64) -> Vec<EventGroup> {
+        let mut events = vec![];
+        let prev_highest_bidder = std::mem::replace(&mut self.highest_bidder, bid);
+        let prev_is_credit = std::mem::replace(&mut self.highest_bid_is_credit, is_credit);
+        // the previous highest bid is now the runner-up
+        self.runner_up = Bid {
+            bidder: prev_highest_bidder.bidder,
+            beneficiary: prev_highest_bidder.beneficiary,
+            amount: prev_highest_bidder.amount,
+        };
+        if prev_is_credit {
+            return events;
+        }
+        let prev_escrow = self.escrow_of(prev_highest_bidder.amount);
+        if self.auto_refund && prev_escrow > 0 {
+            // refund the previous highest bidder directly, falling back to the claim map
+            let payouts = [Payout {
+                token: self.token_for_bidding,
+                amount: prev_escrow,
+            }];
+            let mut event_group = payout_event_group(prev_highest_bidder.bidder, &payouts);
+            event_group
+                .with_callback(SHORTNAME_REFUND_CALLBACK)
+                .argument(prev_highest_bidder.bidder)
+                .argument(prev_escrow)
+                .done();
+            events.push(event_group.build());
+        } else {
+            // move previous highest bidders coin into the claim map
+            self.add_to_claim_map(
+                prev_highest_bidder.bidder,
+                TokenClaim {
+                    tokens_for_bidding: prev_escrow,
+                    tokens_for_sale: 0,
+                    credited_at_millis: now,
+                },
+            );
+        }
+        // remember the previous highest bid in case the new bid is retracted
+        if self.retraction_penalty_per_mille.is_some() && prev_highest_bidder.amount > 0 {
+            self.bid_history.push(prev_highest_bidder);
+        }
+        events
+    }
+
+    /// Returns the escrowed highest bid to the claims of its bidder. A credit bid has no escrowed
+    /// tokens, so nothing is returned for it.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the bid is returned.
+    fn return_highest_bid(&mut self, now: i64) {
+        if self.highest_bid_is_credit {
+            return;
+        }
+        self.add_to_claim_map(
+            self.highest_bidder.bidder,
+            TokenClaim {
+                tokens_for_bidding: self.escrow_of(self.highest_bidder.amount),
+                tokens_for_sale: 0,
+                credited_at_millis: now,
+            },
+        );
+    }
+
+    /// Credits the compensation for cancelling the auction to the highest bidder, if the auction
+    /// compensates the highest bidder. A credit bid of the owner is not compensated.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the auction is cancelled.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of tokens for sale paid as compensation, which are not returned to the owner.
+    fn compensate_highest_bidder(&mut self, now: i64) -> u128 {
+        let Some(compensation) = &self.cancel_compensation else {
+            return 0;
+        };
+        if self.highest_bidder.amount == 0 || self.highest_bid_is_credit {
+            return 0;
+        }
+        let claim = match compensation.source {
+            CompensationSource::SaleTokens {} => TokenClaim {
+                tokens_for_bidding: 0,
+                tokens_for_sale: per_mille_of(self.token_amount_for_sale, compensation.per_mille),
+                credited_at_millis: now,
+            },
+            CompensationSource::Pot {} => {
+                let amount = per_mille_of(self.highest_bidder.amount, compensation.per_mille)
+                    .min(self.compensation_pot);
+                self.compensation_pot -= amount;
+                TokenClaim {
+                    tokens_for_bidding: amount,
+                    tokens_for_sale: 0,
+                    credited_at_millis: now,
+                }
+            }
+        };
+        let paid_in_tokens_for_sale = claim.tokens_for_sale;
+        self.add_to_claim_map(self.highest_bidder.bidder, claim);
+        paid_in_tokens_for_sale
+    }
+
+    /// Returns the tokens left in the compensation pot to the claims of the contract owner.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the pot is returned.
+    fn return_compensation_pot(&mut self, now: i64) {
+        if self.compensation_pot == 0 {
+            return;
+        }
+        let pot = std::mem::take(&mut self.compensation_pot);
+        self.add_to_claim_map(
+            self.contract_owner,
+            TokenClaim {
+                tokens_for_bidding: pot,
+                tokens_for_sale: 0,
+                credited_at_millis: now,
+            },
+        );
+    }
+
+    /// Takes the deposit of `bidder` if it can be refunded, which is once the auction is over. The
+    /// deposit of the highest bidder is held while the auction awaits completion.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `bidder`: [`Address`], the address of the bidder.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of the deposit taken, which is 0 if there is no refundable deposit.
+    fn take_refundable_deposit(&mut self, bidder: &Address) -> u128 {
+        if !self.is_deposit_refundable(bidder) {
+            return 0;
+        }
+        self.deposits.remove(bidder).unwrap_or(0)
+    }
+
+    /// Whether the deposit of `bidder` can be refunded, which is once the auction is over. The
+    /// deposit of the highest bidder is held while the auction awaits completion.
+    fn is_deposit_refundable(&self, bidder: &Address) -> bool {
+        match self.status {
+            ENDED | CANCELLED | RESERVE_NOT_MET => true,
+            AWAITING_COMPLETION => *bidder != self.highest_bidder.bidder,
+            _ => false,
+        }
+    }
+
+    /// Whether `claimant` has any tokens to claim, or a deposit that can be refunded.
+    fn has_payout(&self, claimant: &Address) -> bool {
+        self.claim_map
+            .get(claimant)
+            .is_some_and(|claim| !claim.is_empty())
+            || (self.deposits.contains_key(claimant) && self.is_deposit_refundable(claimant))
+    }
+
+    /// The amount of tokens for bidding escrowed for a bid of `amount`, which is only the up-front
+    /// payment if the winner pays in installments.
+    fn escrow_of(&self, amount: u128) -> u128 {
+        match &self.installment_plan {
+            Some(plan) => per_mille_of(amount, plan.upfront_per_mille),
+            None => amount,
+        }
+    }
+
+    /// The amount of tokens for bidding paid with the installment at `index`, counted from zero.
+    /// The remainder of the winning bid after the up-front payment is divided evenly between the
+    /// installments, with the rounding remainder spread such that the installments add up to it.
+    fn installment_amount(&self, index: u32) -> u128 {
+        let plan = self.installment_plan.as_ref().unwrap();
+        let remainder = self.highest_bidder.amount - self.escrow_of(self.highest_bidder.amount);
+        share_of(remainder, index + 1, plan.installments)
+            - share_of(remainder, index, plan.installments)
+    }
+
+    /// The amount of tokens for sale vested to the winner once `paid` installments are paid.
+    fn vested_tokens(&self, paid: u32) -> u128 {
+        let plan = self.installment_plan.as_ref().unwrap();
+        share_of(self.token_amount_for_sale, paid, plan.installments)
+    }
+
+    /// The initial empty bid of the contract owner.
+    fn empty_bid(&self) -> Bid {
+        Bid {
+            bidder: self.contract_owner,
+            beneficiary: self.contract_owner,
+            amount: 0,
+        }
+    }
+
+    /// Whether the accepted bids meet the reserve price, such that the tokens for sale can be sold.
+    fn reserve_met(&self) -> bool {
+        match self.mode {
+            AuctionMode::SingleWinner {} => {
+                self.highest_bidder.amount > 0 && self.highest_bidder.amount >= self.reserve_price
+            }
+            AuctionMode::PartialFill {} => !self.quantity_bids.is_empty(),
+        }
+    }
+
+    /// The price paid by the highest bidder in the single winner mode, according to the settlement
+    /// of the auction. The second price is never higher than the highest bid.
+    fn winning_price(&self) -> u128 {
+        match self.settlement {
+            SettlementMode::FirstPrice {} => self.highest_bidder.amount,
+            SettlementMode::SecondPrice {} => (self.runner_up.amount + self.min_increment)
+                .max(self.reserve_price)
+                .min(self.highest_bidder.amount),
+        }
+    }
+
+    /// Settles the partial fill auction, by allocating the tokens for sale to the quantity bids at
+    /// the clearing price. Each bidder can claim their allocated tokens for sale and the escrow
+    /// exceeding their payment. The contract owner can claim the payments and the unsold tokens.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the auction is settled.
+    fn settle_partial_fill(&mut self, now: i64) {
+        let (clearing_price, allocations) =
+            allocate_partial_fill(&self.quantity_bids, self.token_amount_for_sale);
+        let bidder_claims: Vec<(Address, u128, TokenClaim)> = self
+            .quantity_bids
+            .iter()
+            .zip(allocations)
+            .map(|(bid, allocation)| {
+                let payment = allocation * clearing_price;
+                let claim = TokenClaim {
+                    tokens_for_bidding: bid.escrow() - payment,
+                    tokens_for_sale: allocation,
+                    credited_at_millis: now,
+                };
+                (bid.bidder, payment, claim)
+            })
+            .collect();
+
+        let mut sold: u128 = 0;
+        let mut payments: u128 = 0;
+        for (bidder, payment, claim) in bidder_claims {
+            sold += claim.tokens_for_sale;
+            payments += payment;
+            self.add_to_claim_map(bidder, claim);
+        }
+        self.clearing_price = Some(clearing_price);
+        self.credit_proceeds(payments, self.token_amount_for_sale - sold, now);
+    }
+
+    /// Divides the proceeds of the auction between the royalty payees and the contract owner.
+    /// Each payee is credited their share of the proceeds rounded down, and the owner is credited
+    /// the remainder along with the unsold tokens for sale.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `proceeds`: [`u128`], the amount of tokens for bidding paid for the tokens for sale.
+    ///
+    /// * `unsold`: [`u128`], the amount of tokens for sale returned to the owner.
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the proceeds are credited.
+    fn credit_proceeds(&mut self, proceeds: u128, unsold: u128, now: i64) {
+        let royalties: Vec<(Address, u128)> = self
+            .royalty_payees
+            .iter()
+            .map(|payee| (payee.payee, per_mille_of(proceeds, payee.per_mille)))
+            .collect();
+        let mut remainder = proceeds;
+        for (payee, royalty) in royalties {
+            remainder -= royalty;
+            self.add_to_claim_map(
+                payee,
+                TokenClaim {
+                    tokens_for_bidding: royalty,
+                    tokens_for_sale: 0,
+                    credited_at_millis: now,
+                },
+            );
+        }
+        self.add_to_claim_map(
+            self.contract_owner,
+            TokenClaim {
+                tokens_for_bidding: remainder,
+                tokens_for_sale: unsold,
+                credited_at_millis: now,
+            },
+        );
+    }
+
+    /// Concludes the bidding phase. If bidding requires a deposit and the highest bid of a bidder
+    /// meets the reserve, the status is changed to `AWAITING_COMPLETION`, and the highest bidder
+    /// must complete the settlement within the grace period. If the winner pays in installments and
+    /// the highest bid of a bidder meets the reserve, the up-front payment is credited to the
+    /// proceeds, and the status is changed to `PAYING_INSTALLMENTS`. Otherwise the auction is
+    /// settled.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the bidding phase is concluded.
+    fn conclude_bidding(&mut self, now: i64) {
+        if !self.reserve_met() || self.highest_bid_is_credit {
+            self.settle(now);
+        } else if let Some(bid_deposit) = &self.bid_deposit {
+            self.completion_deadline_millis = Some(now + bid_deposit.completion_grace_millis);
+            self.status = AWAITING_COMPLETION;
+        } else if let Some(plan) = &self.installment_plan {
+            self.next_installment_due_millis = Some(now + plan.interval_millis);
+            self.status = PAYING_INSTALLMENTS;
+            self.return_compensation_pot(now);
+            self.credit_proceeds(self.escrow_of(self.highest_bidder.amount), 0, now);
+        } else {
+            self.settle(now);
+        }
+    }
+
+    /// Voids the sale, changing the status to `RESERVE_NOT_MET`. The tokens for sale and the
+    /// highest bid are returned to the claims of the owner and the highest bidder.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the sale is voided.
+    fn void_sale(&mut self, now: i64) {
+        self.status = RESERVE_NOT_MET;
+        if self.highest_bidder.amount > 0 {
+            self.return_highest_bid(now);
+        }
+        self.highest_bidder = self.empty_bid();
+        self.highest_bid_is_credit = false;
+        self.add_to_claim_map(
+            self.contract_owner,
+            TokenClaim {
+                tokens_for_bidding: 0,
+                tokens_for_sale: self.token_amount_for_sale,
+                credited_at_millis: now,
+            },
+        );
+    }
+
+    /// Settles the auction once the bidding phase is over. If the reserve is not met the status is
+    /// changed to `RESERVE_NOT_MET`, and the tokens for sale and the highest bid are returned to
+    /// the claims of the owner and the highest bidder. Otherwise the status is changed to `ENDED`,
+    /// and the tokens for sale and the proceeds are credited according to the mode of the auction.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now`: [`i64`], the time in millis UTC at which the auction is settled.
+    fn settle(&mut self, now: i64) {
+        self.return_compensation_pot(now);
+        if !self.reserve_met() {
+            self.void_sale(now);
+            return;
+        }
+        self.status = ENDED;
+        if self.mode == (AuctionMode::PartialFill {}) {
+            self.settle_partial_fill(now);
+            return;
+        }
+        if self.highest_bid_is_credit {
+            // the owner won with a credit bid, and keeps the tokens for sale without proceeds
+            assert_eq!(
+                self.highest_bidder.bidder, self.contract_owner,
+                "Credit bids can only be won by the contract owner"
+            );
+            assert!(
+                self.highest_bidder.amount <= self.credit_bid_cap.unwrap_or(0),
+                "The winning credit bid exceeds the credit bid cap"
+            );
+            self.add_to_claim_map(
+                self.contract_owner,
+                TokenClaim {
+                    tokens_for_bidding: 0,
+                    tokens_for_sale: self.token_amount_for_sale,
+                    credited_at_millis: now,
+                },
+            );
+            return;
+        }
+        let price = self.winning_price();
+        self.credit_proceeds(price, 0, now);
+        self.add_to_claim_map(
+            self.highest_bidder.bidder,
+            TokenClaim {
+                tokens_for_bidding: self.highest_bidder.amount - price,
+                tokens_for_sale: 0,
+                credited_at_millis: now,
+            },
+        );
+        self.add_to_claim_map(
+            self.highest_bidder.beneficiary,
+            TokenClaim {
+                tokens_for_bidding: 0,
+                tokens_for_sale: self.token_amount_for_sale,
+                credited_at_millis: now,
+            },
+        );
+    }
+}
+
+/// Computes `per_mille` per mille of `amount`, rounded down, without overflowing.
+fn per_mille_of(amount: u128, per_mille: u32) -> u128 {
+    let per_mille = per_mille as u128;
+    amount / 1000 * per_mille + amount % 1000 * per_mille / 1000
+}
+
+/// Computes the share `numerator / denominator` of `amount`, rounded down, without overflowing.
+fn share_of(amount: u128, numerator: u32, denominator: u32) -> u128 {
+    let numerator = numerator as u128;
+    let denominator = denominator as u128;
+    amount / denominator * numerator + amount % denominator * numerator / denominator
+}
+
+/// Converts a reserve in USD into tokens for bidding at the given oracle price, rounded up such
+/// that the converted reserve is worth at least the reserve in USD. Saturates instead of
+/// overflowing, in which case the reserve cannot be met.
+///
+/// ### Parameters:
+///
+/// * `min_reserve_usd`: [`u128`], the reserve in USD.
+///
+/// * `price_micro_usd`: [`u128`], the price of one token for bidding in micro USD.
+///
+/// ### Returns
+///
+/// The reserve in tokens for bidding.
+fn oracle_reserve_price(min_reserve_usd: u128, price_micro_usd: u128) -> u128 {
+    if price_micro_usd == 0 {
+        panic!("The oracle price of the token for bidding is zero");
+    }
+    min_reserve_usd
+        .saturating_mul(MICRO_USD_PER_USD)
+        .div_ceil(price_micro_usd)
+}
+
+/// Allocates `supply` tokens for sale to the given quantity bids. Bids are filled from the
+/// highest price down, until the supply is sold out. The price of the last filled bids is the
+/// clearing price. If the bids at the clearing price request more than the remaining supply,
+/// the remaining supply is divided between them pro-rata to their quantities, rounded down.
+///
+/// ### Parameters:
+///
+/// * `bids`: [`&[QuantityBid]`], the bids to allocate to.
+///
+/// * `supply`: [`u128`], the amount of tokens for sale.
+///
+/// ### Returns
+///
+/// The clearing price, which is 0 if there are no bids, and the amount of tokens for sale
+/// allocated to each bid, in the same order as `bids`.
+fn allocate_partial_fill(bids: &[QuantityBid], supply: u128) -> (u128, Vec<u128>) {
+    let mut order: Vec<usize> = (0..bids.len()).collect();
+    order.sort_by(|&a, &b| bids[b].price.cmp(&bids[a].price));
+
+    let mut allocations = vec![0; bids.len()];
+    let mut clearing_price = 0;
+    let mut remaining = supply;
+    let mut group_start = 0;
+    while group_start < order.len() && remaining > 0 {
+        let price = bids[order[group_start]].price;
+        let group_end = group_start
+            + order[group_start..]
+                .iter()
+                .take_while(|&&index| bids[index].price == price)
+                .count();
+        let group = &order[group_start..group_end];
+        let group_quantity: u128 = group.iter().map(|&index| bids[index].quantity).sum();
+
+        clearing_price = price;
+        if group_quantity <= remaining {
+            for &index in group {
+                allocations[index] = bids[index].quantity;
+            }
+            remaining -= group_quantity;
+        } else {
+            for &index in group {
+                allocations[index] = remaining * bids[index].quantity / group_quantity;
+            }
+            remaining = 0;
+        }
+        group_start = group_end;
+    }
+    (clearing_price, allocations)
+}
+
+/// Initial function to bootstrap the contracts state.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], initial context.
+///
+/// * `token_amount_for_sale`: [`u128`], the amount of tokens that is going to be sold.
+///
+/// * `token_for_sale`: [`Address`], the address of the token for sale.
+///
+/// * `token_for_bidding`: [`Address`], the address of the token used for bidding.
+///
+/// * `reserve_price`: [`u128`], the reserve price (minimum cost of the tokens for sale).
+///
+/// * `min_increment`: [`u128`], the minimum increment of each bid.
+///
+/// * `auction_duration_hours`: [`u32`], the duration of the auction in hours.
+///
+/// * `mode`: [`AuctionMode`], the mode of the auction.
+///
+/// * `royalty_payees`: [`Vec<RoyaltyPayee>`], the payees receiving royalties of the proceeds. The
+///   shares of the payees may at most add up to 1000 per mille.
+///
+/// * `retraction_penalty_per_mille`: [`Option<u32>`], the penalty in per mille for retracting the
+///   highest bid, or None if the highest bid cannot be retracted.
+///
+/// * `settlement`: [`SettlementMode`], the settlement of the auction. Second price settlement is
+///   only supported in the single winner mode.
+///
+/// * `auto_refund`: [`bool`], whether outbid bidders are refunded directly when they are outbid,
+///   instead of claiming their bid from the claim map.
+///
+/// * `unclaimed_expiry_millis`: [`Option<i64>`], the time in millis after which unclaimed tokens
+///   can be swept once the auction is over, or None if they never expire.
+///
+/// * `sweep_recipient`: [`Option<Address>`], the address receiving swept tokens, or None if they
+///   are received by the contract owner.
+///
+/// * `credit_bid_cap`: [`Option<u128>`], the maximum amount the contract owner can bid on credit,
+///   or None if the owner cannot bid on credit. Credit bids are only supported in the single
+///   winner mode.
+///
+/// * `oracle_reserve`: [`Option<OracleReserve>`], the reserve in USD converted with the median
+///   price of an oracle when the auction is executed, or None if only the reserve price applies.
+///   The median price is queried from the action with shortname 0x03 of the oracle, which must
+///   return the price of one token for bidding in micro USD as a `u128`. An oracle reserve is only
+///   supported in the single winner mode.
+///
+/// * `cancel_compensation`: [`Option<CancelCompensation>`], the compensation paid to the highest
+///   bidder if the auction is cancelled, or None if the highest bidder is not compensated. The
+///   compensation may at most be 1000 per mille, and is only supported in the single winner mode.
+///
+/// * `bid_deposit`: [`Option<BidDeposit>`], the deposit required to bid, or None if bidding
+///   requires no deposit. The grace period for completing the settlement must be positive, and a
+///   deposit is only supported in the single winner mode.
+///
+/// * `installment_plan`: [`Option<InstallmentPlan>`], the installment plan of the winner, or None
+///   if bids escrow the full amount. The up-front payment may at most be 1000 per mille, there
+///   must be at least one installment and the interval must be positive. An installment plan is
+///   only supported in the single winner mode with first price settlement, and cannot be combined
+///   with a bid deposit.
+///
+/// * `owner`: [`Option<Address>`], the contract owner selling the tokens, or None if the sender
+///   initializing the contract is the owner. Lets a factory deploy an auction that its creator can
+///   `start`, and whose proceeds are credited to the creator.
+///
+/// ### Returns:
+///
+/// The new state object of type [`AuctionContractState`] with the initial state being
+/// [`CREATION`].
+#[init]
+#[allow(clippy::too_many_arguments)]
+pub fn initialize(
+    ctx: ContractContext,
+    token_amount_for_sale: u128,
+    token_for_sale: Address,
+    token_for_bidding: Address,
+    reserve_price: u128,
+    min_increment: u128,
+    auction_duration_hours: u32,
+    mode: AuctionMode,
+    royalty_payees: Vec<RoyaltyPayee>,
+    retraction_penalty_per_mille: Option<u32>,
+    settlement: SettlementMode,
+    auto_refund: bool,
+    unclaimed_expiry_millis: Option<i64>,
+    sweep_recipient: Option<Address>,
+    credit_bid_cap: Option<u128>,
+    oracle_reserve: Option<OracleReserve>,
+    cancel_compensation: Option<CancelCompensation>,
+    bid_deposit: Option<BidDeposit>,
+    installment_plan: Option<InstallmentPlan>,
+    owner: Option<Address>,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if token_for_sale.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract selling a non publicContract token");
+    }
+    if token_for_bidding.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract buying a non publicContract token");
+    }
+    let total_per_mille: u64 = royalty_payees
+        .iter()
+        .map(|payee| payee.per_mille as u64)
+        .sum();
+    if total_per_mille > 1000 {
+        panic!("The royalties of the payees cannot exceed 1000 per mille");
+    }
+    if retraction_penalty_per_mille.unwrap_or(0) > 1000 {
+        panic!("The retraction penalty cannot exceed 1000 per mille");
+    }
+    if mode == (AuctionMode::PartialFill {}) && settlement != (SettlementMode::FirstPrice {}) {
+        panic!("Second price settlement is only supported in the single winner mode");
+    }
+    if unclaimed_expiry_millis.is_some_and(|expiry| expiry <= 0) {
+        panic!("The expiry of unclaimed tokens must be positive");
+    }
+    if mode == (AuctionMode::PartialFill {}) && credit_bid_cap.is_some() {
+        panic!("Credit bids are only supported in the single winner mode");
+    }
+    if mode == (AuctionMode::PartialFill {}) && oracle_reserve.is_some() {
+        panic!("An oracle reserve is only supported in the single winner mode");
+    }
+    if let Some(compensation) = &cancel_compensation {
+        if compensation.per_mille > 1000 {
+            panic!("The cancel compensation cannot exceed 1000 per mille");
+        }
+        if mode == (AuctionMode::PartialFill {}) {
+            panic!("Cancel compensation is only supported in the single winner mode");
+        }
+    }
+    if let Some(deposit) = &bid_deposit {
+        if deposit.token.address_type != AddressType::PublicContract {
+            panic!("Tried to create a contract with a non publicContract deposit token");
+        }
+        if deposit.completion_grace_millis <= 0 {
+            panic!("The grace period for completing the settlement must be positive");
+        }
+        if mode == (AuctionMode::PartialFill {}) {
+            panic!("A bid deposit is only supported in the single winner mode");
+        }
+    }
+    if let Some(plan) = &installment_plan {
+        if plan.upfront_per_mille > 1000 {
+            panic!("The up-front payment cannot exceed 1000 per mille");
+        }
+        if plan.installments == 0 {
+            panic!("The installment plan must have at least one installment");
+        }
+        if plan.interval_millis <= 0 {
+            panic!("The interval between installments must be positive");
+        }
+        if mode == (AuctionMode::PartialFill {}) || settlement != (SettlementMode::FirstPrice {}) {
+            panic!("Installments are only supported in the single winner mode at the first price");
+        }
+        if bid_deposit.is_some() {
+            panic!("Installments cannot be combined with a bid deposit");
+        }
+    }
+    let owner = owner.unwrap_or(ctx.sender);
+    let duration_millis = i64::from(auction_duration_hours) * 60 * 60 * 1000;
+    let end_time_millis = ctx.block_production_time + duration_millis;
+    let state = AuctionContractState {
+        contract_owner: owner,
+        start_time_millis: ctx.block_production_time,
+        end_time_millis,
+        token_amount_for_sale,
+        token_for_sale,
+        token_for_bidding,
+        highest_bidder: Bid {
+            bidder: owner,
+            beneficiary: owner,
+            amount: 0,
+        },
+        runner_up: Bid {
+            bidder: owner,
+            beneficiary: owner,
+            amount: 0,
+        },
+        bid_history: vec![],
+        retraction_penalty_per_mille,
+        reserve_price,
+        min_increment,
+        mode,
+        settlement,
+        quantity_bids: vec![],
+        royalty_payees,
+        clearing_price: None,
+        claim_map: BTreeMap::new(),
+        claimant_nonces: BTreeMap::new(),
+        claimants: BTreeMap::new(),
+        next_claimant_nonce: 0,
+        auto_refund,
+        unclaimed_expiry_millis,
+        sweep_recipient,
+        credit_bid_cap,
+        highest_bid_is_credit: false,
+        oracle_price: oracle_reserve.as_ref().map(|reserve| {
+            CachedQuery::new(
+                reserve.oracle,
+                oracle_contract_query_median(),
+                ORACLE_PRICE_TTL_MILLIS,
+            )
+        }),
+        oracle_reserve,
+        cancel_compensation,
+        compensation_pot: 0,
+        bid_deposit,
+        deposits: BTreeMap::new(),
+        completion_deadline_millis: None,
+        installment_plan,
+        installments_paid: 0,
+        next_installment_due_millis: None,
+        unregistered_splitter_payments: BTreeMap::new(),
+        status: CREATION,
+    };
+
+    (state, vec![])
+}
+
+/// Action for starting the contract. The function throws an error if the caller isn't the `contract_owner`
+/// or the contracts `status` isn't `STARTING`.
+/// The contract is started by creating a transfer event from the `contract_owner`
+/// to the contract of the tokens being sold as well as a callback to `start_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`AuctionContractState`].
+#[action(shortname = 0x01)]
+pub fn start(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if context.sender != state.contract_owner {
+        panic!("Start can only be called by the creator of the contract");
+    }
+    if state.status != CREATION {
+        panic!("Start should only be called while setting up the contract");
+    }
+    // Create transfer event to contract for the token_for_sale
+    // transfer should callback to start_callback (1)
+
+    // Builder
+
+    let mut event_group = EventGroup::builder();
+
+    event_group.with_callback(SHORTNAME_START_CALLBACK).done();
+
+    event_group
+        .call(state.token_for_sale, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(state.token_amount_for_sale)
+        .done();
+
+    (state, vec![event_group.build()])
+}
+
+/// Callback for starting the contract. If the transfer event was successful the `status`
+/// is updated to `BIDDING`. If the transfer event failed the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x02)]
+pub fn start_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for start");
+    }
+    new_state.status = BIDDING;
+    (new_state, vec![])
+}
+
+/// Action for bidding on the auction. The function always makes a transfer event
+/// to the token for bidding contract. On callback `bid_callback` is called to actually update
+/// the state.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bid_amount`: [`u128`], the amount of tokens in the bid.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`AuctionContractState`].
+#[action(shortname = 0x03)]
+pub fn bid(
+    context: ContractContext,
+    state: AuctionContractState,
+    bid_amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let beneficiary = context.sender;
+    place_bid(context, state, beneficiary, bid_amount)
+}
+
+/// Action for bidding on the auction on behalf of `beneficiary`. The caller pays for the bid and
+/// is refunded if the bid is outbid or not accepted, while the tokens for sale are credited to
+/// `beneficiary` if the bid wins. Otherwise the bid is handled like a bid made with `bid`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `beneficiary`: [`Address`], the address credited the tokens for sale if the bid wins.
+///
+/// * `bid_amount`: [`u128`], the amount of tokens in the bid.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`AuctionContractState`].
+#[action(shortname = 0x10)]
+pub fn bid_for(
+    context: ContractContext,
+    state: AuctionContractState,
+    beneficiary: Address,
+    bid_amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    place_bid(context, state, beneficiary, bid_amount)
+}
+
+/// Creates the transfer of a bid paid by the caller from the caller to the contract, with a
+/// callback to `bid_callback`. If bidding requires a deposit that the caller has not locked, the
+/// deposit is transferred along with the bid, with a callback to `bid_with_deposit_callback`.
+/// If the winner pays in installments, only the up-front payment of the bid is transferred.
+fn place_bid(
+    context: ContractContext,
+    state: AuctionContractState,
+    beneficiary: Address,
+    bid_amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if state.mode != (AuctionMode::SingleWinner {}) {
+        panic!("Bids in the partial fill mode must be made with bid_quantity");
+    }
+    // Potential new bid, create the transfer event
+    // transfer(auctionContract, bid_amount)
+
+    let bid: Bid = Bid {
+        bidder: context.sender,
+        beneficiary,
+        amount: bid_amount,
+    };
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.token_for_bidding, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(state.escrow_of(bid_amount))
+        .done();
+    match &state.bid_deposit {
+        Some(deposit) if !state.deposits.contains_key(&context.sender) => {
+            event_group
+                .call(deposit.token, token_contract_transfer_from())
+                .argument(context.sender)
+                .argument(context.contract_address)
+                .argument(deposit.amount)
+                .done();
+            event_group
+                .with_callback(SHORTNAME_BID_WITH_DEPOSIT_CALLBACK)
+                .argument(bid)
+                .argument(deposit.amount)
+                .done();
+        }
+        _ => {
+            event_group
+                .with_callback(SHORTNAME_BID_CALLBACK)
+                .argument(bid)
+                .done();
+        }
+    }
+    (state, vec![event_group.build()])
+}
+
+/// Callback from bidding. If the transfer event was successful the `bid` will be compared
+/// to the current highest bid and the claim map is updated accordingly. The runner-up bid is
+/// updated if the bid becomes the highest bid, or is placed in the bidding phase and exceeds the
+/// runner-up bid without becoming the highest bid.
+/// If the auction refunds outbid bidders automatically, the previous highest bid is transferred
+/// back to its bidder with a callback to `refund_callback`, instead of being added to the claim map.
+/// If the transfer event fails the state is unchanged.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bid`: [`Bid`], the bid containing information as to who the bidder was and which
+/// amount was bid.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x04)]
+pub fn bid_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    bid: Bid,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let mut events = vec![];
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for bid");
+    } else if new_state.status != BIDDING
+        || ctx.block_production_time >= new_state.end_time_millis
+        || bid.amount < new_state.highest_bidder.amount + new_state.min_increment
+        || bid.amount < new_state.reserve_price
+    {
+        // transfer succeeded, since we are no longer accepting bids we add
+        // this to the claim map so the sender can get his money back
+        // if the bid was too small we also add it to the claim map
+        let in_bidding_phase =
+            new_state.status == BIDDING && ctx.block_production_time < new_state.end_time_millis;
+        if in_bidding_phase && bid.amount > new_state.runner_up.amount {
+            new_state.runner_up = Bid {
+                bidder: bid.bidder,
+                beneficiary: bid.beneficiary,
+                amount: bid.amount,
+            };
+        }
+        new_state.add_to_claim_map(
+            bid.bidder,
+            TokenClaim {
+                tokens_for_bidding: new_state.escrow_of(bid.amount),
+                tokens_for_sale: 0,
+                credited_at_millis: ctx.block_production_time,
+            },
+        );
+    } else {
+        // bidding phase and a new highest bid
+        events = new_state.replace_highest_bid(bid, false, ctx.block_production_time);
+    }
+    (new_state, events)
+}
+
+/// Callback from bidding with the deposit of the bidder. The transfers of the bid and the deposit
+/// are checked separately. If the deposit was transferred it is held for the bidder, and if the bid
+/// was transferred as well the bid is handled as in `bid_callback`. If only the bid was
+/// transferred, the bid is not accepted and its escrowed tokens are added to the claim map of the
+/// bidder. If neither transfer succeeded the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bid`: [`Bid`], the bid containing information as to who the bidder was and which
+/// amount was bid.
+///
+/// * `deposit`: [`u128`], the amount of the deposit transferred along with the bid.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x17)]
+pub fn bid_with_deposit_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    bid: Bid,
+    deposit: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let bid_transferred = callback_ctx.results[0].succeeded;
+    let deposit_transferred = callback_ctx.results[1].succeeded;
+    if !bid_transferred && !deposit_transferred {
+        panic!("Transfer event did not succeed for bid");
+    }
+    let mut new_state = state;
+    if deposit_transferred {
+        new_state.register_claimant(bid.bidder);
+        *new_state.deposits.entry(bid.bidder).or_insert(0) += deposit;
+    }
+    if !bid_transferred {
+        return (new_state, vec![]);
+    }
+    if !deposit_transferred {
+        // the bid cannot be accepted without the deposit, so the bidder can claim it back
+        let escrowed = new_state.escrow_of(bid.amount);
+        new_state.add_to_claim_map(
+            bid.bidder,
+            TokenClaim {
+                tokens_for_bidding: escrowed,
+                tokens_for_sale: 0,
+                credited_at_millis: ctx.block_production_time,
+            },
+        );
+        return (new_state, vec![]);
+    }
+    bid_callback(ctx, callback_ctx, new_state, bid)
+}
+
+/// Action for the contract owner to bid on credit, paying with the proceeds of the auction
+/// instead of transferring tokens for bidding. Panics if the auction does not allow credit bids,
+/// if the caller is not the contract owner, if the bid exceeds the credit bid cap, if the auction
+/// is not in the bidding phase, or if the bid is not a valid new highest bid.
+/// The credit bid becomes the highest bid like a bid made with `bid`, but since no tokens are
+/// escrowed for it, it is never refunded.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bid_amount`: [`u128`], the amount of the credit bid.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x13)]
+pub fn credit_bid(
+    context: ContractContext,
+    state: AuctionContractState,
+    bid_amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let Some(cap) = new_state.credit_bid_cap else {
+        panic!("The auction does not allow credit bids");
+    };
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can bid on credit");
+    } else if bid_amount > cap {
+        panic!("The credit bid of {bid_amount} exceeds the credit bid cap of {cap}");
+    } else if new_state.status != BIDDING {
+        panic!("Tried to bid on credit when the status isn't Bidding");
+    } else if context.block_production_time >= new_state.end_time_millis {
+        panic!("Tried to bid on credit after auction end block time");
+    } else if bid_amount < new_state.highest_bidder.amount + new_state.min_increment
+        || bid_amount < new_state.reserve_price
+    {
+        panic!("The credit bid must exceed the highest bid and meet the reserve price");
+    }
+    let bid = Bid {
+        bidder: context.sender,
+        beneficiary: context.sender,
+        amount: bid_amount,
+    };
+    let events = new_state.replace_highest_bid(bid, true, context.block_production_time);
+    (new_state, events)
+}
+
+/// Callback for automatically refunding an outbid bidder. If the refund transfer failed, the bid is
+/// added to the claim map of the bidder, such that it can be claimed instead.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bidder`: [`Address`], the outbid bidder that was refunded.
+///
+/// * `amount`: [`u128`], the amount of bidding tokens that was refunded.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x0E)]
+pub fn refund_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    bidder: Address,
+    amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let payouts = new_state.claim_payouts(amount, 0, 0);
+    new_state.restore_claim_if_failed(&callback_ctx, bidder, &payouts, ctx.block_production_time);
+    (new_state, vec![])
+}
+
+/// Action for retracting the highest bid before the auction ends. Panics if the auction does not
+/// allow retractions, if the caller is not the highest bidder, if the block time is later than the
+/// contracts end time or if the status is not `BIDDING`.
+/// The retracting bidder can claim their bid less the retraction penalty, which the contract
+/// owner can claim. The latest previous highest bid whose tokens have not been claimed is
+/// reinstated as the highest bid.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x0D)]
+pub fn retract_bid(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let Some(penalty_per_mille) = new_state.retraction_penalty_per_mille else {
+        panic!("The auction does not allow retracting bids");
+    };
+    if new_state.status != BIDDING {
+        panic!("Tried to retract a bid when the status isn't Bidding");
+    } else if context.block_production_time >= new_state.end_time_millis {
+        panic!("Tried to retract a bid after auction end block time");
+    } else if context.sender != new_state.highest_bidder.bidder
+        || new_state.highest_bidder.amount == 0
+    {
+        panic!("Only the highest bidder can retract their bid");
+    } else if new_state.highest_bid_is_credit {
+        panic!("Credit bids cannot be retracted");
+    }
+    let retracted = new_state.escrow_of(new_state.highest_bidder.amount);
+    let penalty = per_mille_of(retracted, penalty_per_mille);
+    new_state.add_to_claim_map(
+        context.sender,
+        TokenClaim {
+            tokens_for_bidding: retracted - penalty,
+            tokens_for_sale: 0,
+            credited_at_millis: context.block_production_time,
+        },
+    );
+    new_state.add_to_claim_map(
+        new_state.contract_owner,
+        TokenClaim {
+            tokens_for_bidding: penalty,
+            tokens_for_sale: 0,
+            credited_at_millis: context.block_production_time,
+        },
+    );
+    new_state.reinstate_previous_bid();
+    (new_state, vec![])
+}
+
+/// Action for bidding on a quantity of the tokens for sale in the partial fill mode. The function
+/// makes a transfer event of `quantity * price` tokens for bidding to the contract as escrow.
+/// On callback `bid_quantity_callback` is called to actually update the state.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `quantity`: [`u128`], the amount of tokens for sale to buy.
+///
+/// * `price`: [`u128`], the maximum price per token for sale.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`AuctionContractState`].
+#[action(shortname = 0x0B)]
+pub fn bid_quantity(
+    context: ContractContext,
+    state: AuctionContractState,
+    quantity: u128,
+    price: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if state.mode != (AuctionMode::PartialFill {}) {
+        panic!("Quantity bids can only be made in the partial fill mode");
+    }
+    let bid = QuantityBid {
+        bidder: context.sender,
+        quantity,
+        price,
+    };
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.token_for_bidding, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(bid.escrow())
+        .done();
+    event_group
+        .with_callback(SHORTNAME_BID_QUANTITY_CALLBACK)
+        .argument(bid)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Callback from bidding on a quantity. If the transfer event was successful the bid is accepted,
+/// unless the auction is no longer accepting bids, the quantity is zero or the price is below
+/// the reserve price, in which case the escrow is added to the claim map.
+/// If the transfer event fails the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `bid`: [`QuantityBid`], the bid that was escrowed.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x0C)]
+pub fn bid_quantity_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    bid: QuantityBid,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for bid");
+    } else if new_state.status != BIDDING
+        || ctx.block_production_time >= new_state.end_time_millis
+        || bid.quantity == 0
+        || bid.price < new_state.reserve_price
+    {
+        new_state.add_to_claim_map(
+            bid.bidder,
+            TokenClaim {
+                tokens_for_bidding: bid.escrow(),
+                tokens_for_sale: 0,
+                credited_at_millis: ctx.block_production_time,
+            },
+        );
+    } else {
+        new_state.quantity_bids.push(bid);
+    }
+    (new_state, vec![])
+}
+
+/// Action for claiming tokens. Can be called at any time during the auction. Only the highest
+/// bidder and the owner of the contract cannot get their escrowed tokens.
+/// If there is any available tokens for the sender in the claim map the contract creates
+/// appropriate transfer calls for both the token for sale and the token for bidding. The entry in
+/// the claim map is then set to 0 for both token types.
+/// Once the auction is over, the deposit of the sender is refunded along with the claimed tokens,
+/// unless the sender is the highest bidder of an auction awaiting completion.
+/// The transfers are verified by `claim_callback`, which credits the tokens of any failed
+/// transfer back to the sender.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x05)]
+pub fn claim(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    claim_tokens(state, context.sender)
+}
+
+/// Action for claiming tokens on behalf of a beneficiary. Can be called by anyone at any time
+/// during the auction, which allows claiming for bidders that cannot initiate a claim themselves,
+/// such as contracts. The claimable tokens are always transferred to the beneficiary, in the same
+/// way as if the beneficiary had called `claim`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `beneficiary`: [`Address`], the address to transfer the claimable tokens to.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x0A)]
+pub fn claim_for(
+    context: ContractContext,
+    state: AuctionContractState,
+    beneficiary: Address,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    claim_tokens(state, beneficiary)
+}
+
+/// Takes the claimable tokens of `beneficiary` from the claim map, along with their deposit if it
+/// can be refunded, and creates the transfers of them to `beneficiary`, with a callback to
+/// `claim_callback`.
+fn claim_tokens(
+    state: AuctionContractState,
+    beneficiary: Address,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let events = new_state.take_payout(beneficiary).into_iter().collect();
+    (new_state, events)
+}
+
+/// Callback for claiming tokens. The tokens of each transfer that failed are credited back to the
+/// claim map of the beneficiary, and a failed refund of the deposit is held as the deposit of the
+/// beneficiary again, such that they can be claimed again. Transfers of zero tokens were not made,
+/// and have no result.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `beneficiary`: [`Address`], the address the tokens were transferred to.
+///
+/// * `tokens_for_bidding`: [`u128`], the amount of claimed tokens for bidding.
+///
+/// * `tokens_for_sale`: [`u128`], the amount of claimed tokens for sale.
+///
+/// * `deposit`: [`u128`], the amount of the refunded deposit.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x20)]
+pub fn claim_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    beneficiary: Address,
+    tokens_for_bidding: u128,
+    tokens_for_sale: u128,
+    deposit: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let payouts = new_state.claim_payouts(tokens_for_bidding, tokens_for_sale, deposit);
+    if failed_payouts(&callback_ctx, &payouts).contains(&2) {
+        new_state.register_claimant(beneficiary);
+        *new_state.deposits.entry(beneficiary).or_insert(0) += deposit;
+    }
+    new_state.restore_claim_if_failed(
+        &callback_ctx,
+        beneficiary,
+        &payouts,
+        ctx.block_production_time,
+    );
+    (new_state, vec![])
+}
+
+/// Action for paying out the claims of the claimants with nonces from `start` up to `start +
+/// count`. Can be called by anyone at any time during the auction, in the same way as calling
+/// `claim_for` for each of the claimants, such that the claims of many displaced bidders can be
+/// paid out in pages. Panics if `count` exceeds the maximum number of claimants per call.
+/// Claimants with nothing to claim are skipped, and removed unless their deposit is held.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `start`: [`u64`], the nonce of the first claimant to pay out.
+///
+/// * `count`: [`u64`], the number of claimants to pay out, at most 50.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x1D)]
+pub fn claim_range(
+    context: ContractContext,
+    state: AuctionContractState,
+    start: u64,
+    count: u64,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if count > MAX_CLAIM_RANGE {
+        panic!("Cannot claim for more than {MAX_CLAIM_RANGE} claimants at a time");
+    }
+    let mut new_state = state;
+    let (beneficiaries, idle): (Vec<Address>, Vec<Address>) = new_state
+        .claimants
+        .range(start..start.saturating_add(count))
+        .map(|(_, claimant)| *claimant)
+        .partition(|claimant| new_state.has_payout(claimant));
+    for claimant in &idle {
+        new_state.release_claimant(claimant);
+    }
+    let events = beneficiaries
+        .into_iter()
+        .filter_map(|beneficiary| new_state.take_payout(beneficiary))
+        .collect();
+    (new_state, events)
+}
+
+/// Action for executing the auction. Panics if the block time is earlier than the contracts
+/// end time or if the current status is not `BIDDING`. When the contract is executed the status
+/// is changed to `ENDED`, and the highest bidder will be able to claim the sold tokens.
+/// Similarly the contract owner is able to claim the amount of bidding tokens that the highest
+/// bidder bid, less the royalties claimable by the royalty payees. In the second price settlement
+/// the highest bidder instead pays the runner-up bid plus the minimum increment, and can claim
+/// the remainder of their bid. In the partial fill mode the tokens for sale are instead allocated
+/// to the quantity bids at the clearing price.
+/// If the contract owner wins with a credit bid, the tokens for sale are returned to the claims of
+/// the owner, and no proceeds are credited.
+/// If the reserve price is not met the status is instead changed to `RESERVE_NOT_MET`, and nothing
+/// is sold. The contract owner can then claim the tokens for sale, or `relist` them, and the
+/// highest bidder can claim their bid.
+/// If the auction has an oracle reserve, the median price is instead queried from the oracle, and
+/// the auction is settled in `execute_callback`, unless a fresh price is cached. Panics if the
+/// price is already being queried.
+/// If bidding requires a deposit and the reserve is met by a bid, the status is instead changed to
+/// `AWAITING_COMPLETION`, and the auction is settled once the highest bidder calls `complete`.
+/// If the winner pays in installments and the reserve is met by a bid, the up-front payment is
+/// instead credited to the proceeds, and the status is changed to `PAYING_INSTALLMENTS`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`], or the unchanged state and the query
+/// of the oracle price.
+#[action(shortname = 0x06)]
+pub fn execute(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if context.block_production_time < new_state.end_time_millis {
+        panic!("Tried to execute the auction before auction end block time");
+    } else if new_state.status != BIDDING {
+        panic!("Tried to execute the auction when the status isn't Bidding");
+    } else {
+        let now = context.block_production_time;
+        if let Some(oracle_price) = new_state.oracle_price.as_mut() {
+            // convert the reserve with the oracle price before settling
+            let events = oracle_price.query_if_stale(now, SHORTNAME_EXECUTE_CALLBACK);
+            if !events.is_empty() {
+                return (new_state, events);
+            }
+            let price_micro_usd = match oracle_price.fresh_value(now) {
+                Some(price_micro_usd) => *price_micro_usd,
+                None => panic!("The oracle price is already being queried"),
+            };
+            new_state.apply_oracle_reserve(price_micro_usd);
+        }
+        new_state.conclude_bidding(context.block_production_time);
+        (new_state, vec![])
+    }
+}
+
+/// Callback for executing the auction with an oracle reserve, receiving the median price of the
+/// oracle, which is cached. The reserve in USD is converted into tokens for bidding at the median
+/// price, and the reserve price is raised to the converted reserve if that is higher, before the
+/// auction is settled as in `execute`. If the query failed the pending query is cleared without
+/// settling, such that the auction can be executed again. Panics if the oracle price is zero or if
+/// the auction was executed meanwhile.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callback context containing the median price.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x14)]
+pub fn execute_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let now = context.block_production_time;
+    let oracle_price = new_state
+        .oracle_price
+        .as_mut()
+        .expect("The auction has no oracle reserve");
+    if !oracle_price.handle_response(&callback_ctx, now) {
+        return (new_state, vec![]);
+    }
+    let price_micro_usd = *oracle_price.expect_fresh(now);
+    if new_state.status != BIDDING {
+        panic!("Tried to execute the auction when the status isn't Bidding");
+    }
+    new_state.apply_oracle_reserve(price_micro_usd);
+    new_state.conclude_bidding(now);
+    (new_state, vec![])
+}
+
+/// Action for completing the settlement of an auction awaiting completion. Panics if the status is
+/// not `AWAITING_COMPLETION`, if the caller is not the highest bidder or if the completion
+/// deadline has passed. The auction is then settled as in `execute`, and the deposit of the
+/// highest bidder can be claimed along with the tokens for sale.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x18)]
+pub fn complete(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if new_state.status != AWAITING_COMPLETION {
+        panic!("Tried to complete the auction when the status isn't AwaitingCompletion");
+    } else if context.sender != new_state.highest_bidder.bidder {
+        panic!("Only the highest bidder can complete the auction");
+    } else if new_state
+        .completion_deadline_millis
+        .is_some_and(|deadline| context.block_production_time >= deadline)
+    {
+        panic!("Tried to complete the auction after the completion deadline");
+    }
+    new_state.settle(context.block_production_time);
+    (new_state, vec![])
+}
+
+/// Action for forfeiting the deposit of a highest bidder who did not complete the settlement
+/// in time. Can be called by anyone once the completion deadline has passed. Panics if the status
+/// is not `AWAITING_COMPLETION` or if the completion deadline has not passed.
+/// The deposit of the highest bidder is forfeited to the contract owner, and the sale is voided as
+/// if the reserve price was not met. The highest bidder can claim their bid, and the contract
+/// owner can claim the tokens for sale along with the forfeited deposit, or `relist` the tokens.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x19)]
+pub fn forfeit_deposit(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if new_state.status != AWAITING_COMPLETION {
+        panic!("Tried to forfeit the deposit when the status isn't AwaitingCompletion");
+    } else if new_state
+        .completion_deadline_millis
+        .is_some_and(|deadline| context.block_production_time < deadline)
+    {
+        panic!("Tried to forfeit the deposit before the completion deadline");
+    }
+    let forfeited = new_state
+        .deposits
+        .remove(&new_state.highest_bidder.bidder)
+        .unwrap_or(0);
+    *new_state
+        .deposits
+        .entry(new_state.contract_owner)
+        .or_insert(0) += forfeited;
+    new_state.return_compensation_pot(context.block_production_time);
+    new_state.void_sale(context.block_production_time);
+    (new_state, vec![])
+}
+
+/// Action for paying the next installment of the winning bid. Panics if the status is not
+/// `PAYING_INSTALLMENTS`, if the caller is not the highest bidder or if the installment is overdue.
+/// The installment is transferred from the highest bidder to the contract, and the tokens for sale
+/// vest in `pay_installment_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`AuctionContractState`].
+#[action(shortname = 0x1A)]
+pub fn pay_installment(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if state.status != PAYING_INSTALLMENTS {
+        panic!("Tried to pay an installment when the status isn't PayingInstallments");
+    } else if context.sender != state.highest_bidder.bidder {
+        panic!("Only the highest bidder can pay installments");
+    } else if state
+        .next_installment_due_millis
+        .is_some_and(|due| context.block_production_time >= due)
+    {
+        panic!("Tried to pay an installment after it was due");
+    }
+    let index = state.installments_paid;
+    let amount = state.installment_amount(index);
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.token_for_bidding, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_PAY_INSTALLMENT_CALLBACK)
+        .argument(index)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Callback for paying an installment. If the transfer event was successful the installment is
+/// credited to the proceeds, and the share of the tokens for sale vested by the installment is
+/// credited to the beneficiary of the winning bid. The next installment is due one interval after
+/// the previous due date, and once all installments are paid the status is changed to `ENDED`.
+/// If the installment was already paid or the unvested tokens were reclaimed in the meantime, the
+/// payment is added to the claims of the highest bidder instead. If the transfer event failed the
+/// callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `index`: [`u32`], the index of the paid installment, counted from zero.
+///
+/// * `amount`: [`u128`], the amount of tokens for bidding transferred to the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x1B)]
+pub fn pay_installment_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    index: u32,
+    amount: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for the installment");
+    }
+    let mut new_state = state;
+    let now = ctx.block_production_time;
+    if new_state.status != PAYING_INSTALLMENTS || new_state.installments_paid != index {
+        new_state.add_to_claim_map(
+            new_state.highest_bidder.bidder,
+            TokenClaim {
+                tokens_for_bidding: amount,
+                tokens_for_sale: 0,
+                credited_at_millis: now,
+            },
+        );
+        return (new_state, vec![]);
+    }
+    let vested = new_state.vested_tokens(index + 1) - new_state.vested_tokens(index);
+    new_state.credit_proceeds(amount, 0, now);
+    new_state.add_to_claim_map(
+        new_state.highest_bidder.beneficiary,
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: vested,
+            credited_at_millis: now,
+        },
+    );
+    new_state.installments_paid += 1;
+    let plan = new_state.installment_plan.as_ref().unwrap();
+    if new_state.installments_paid == plan.installments {
+        new_state.next_installment_due_millis = None;
+        new_state.status = ENDED;
+    } else {
+        let interval_millis = plan.interval_millis;
+        if let Some(due) = new_state.next_installment_due_millis.as_mut() {
+            *due += interval_millis;
+        }
+    }
+    (new_state, vec![])
+}
+
+/// Action for reclaiming the unvested tokens for sale once the winner has missed an installment.
+/// Panics if the caller is not the contract owner, if the status is not `PAYING_INSTALLMENTS` or
+/// if the next installment is not yet due. The tokens for sale that have not vested are credited
+/// to the contract owner, who keeps the payments made so far, and the status is changed to `ENDED`.
+/// The winner keeps the tokens for sale vested by the paid installments.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x1C)]
+pub fn reclaim_unvested(
+    context: ContractContext,
+    state: AuctionContractState,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can reclaim the unvested tokens");
+    } else if new_state.status != PAYING_INSTALLMENTS {
+        panic!("Tried to reclaim the unvested tokens when the status isn't PayingInstallments");
+    } else if new_state
+        .next_installment_due_millis
+        .is_some_and(|due| context.block_production_time < due)
+    {
+        panic!("Tried to reclaim the unvested tokens before an installment was missed");
+    }
+    let unvested =
+        new_state.token_amount_for_sale - new_state.vested_tokens(new_state.installments_paid);
+    new_state.add_to_claim_map(
+        new_state.contract_owner,
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: unvested,
+            credited_at_millis: context.block_production_time,
+        },
+    );
+    new_state.next_installment_due_millis = None;
+    new_state.status = ENDED;
+    (new_state, vec![])
+}
+
+/// Action for relisting the tokens for sale after the reserve price was not met, starting a new
+/// bidding phase with a new reserve price and duration. Panics if the caller is not the contract
+/// owner, if the status is not `RESERVE_NOT_MET` or if the owner has claimed the tokens for sale.
+/// The tokens for sale are taken back out of the claims of the owner, and the bids of the previous
+/// bidding phase are cleared, since they have already been returned to the claims of the bidders.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `new_reserve_price`: [`u128`], the reserve price of the new bidding phase.
+///
+/// * `new_duration_hours`: [`u32`], the duration of the new bidding phase in hours.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x0F)]
+pub fn relist(
+    context: ContractContext,
+    state: AuctionContractState,
+    new_reserve_price: u128,
+    new_duration_hours: u32,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can relist the auction");
+    } else if new_state.status != RESERVE_NOT_MET {
+        panic!("Tried to relist the auction when the status isn't ReserveNotMet");
+    }
+    let token_amount_for_sale = new_state.token_amount_for_sale;
+    match new_state.claim_map.get_mut(&new_state.contract_owner) {
+        Some(claim) if claim.tokens_for_sale >= token_amount_for_sale => {
+            claim.tokens_for_sale -= token_amount_for_sale;
+        }
+        _ => panic!("The tokens for sale have been claimed, and cannot be relisted"),
+    }
+
+    let duration_millis = i64::from(new_duration_hours) * 60 * 60 * 1000;
+    new_state.start_time_millis = context.block_production_time;
+    new_state.end_time_millis = context.block_production_time + duration_millis;
+    new_state.reserve_price = new_reserve_price;
+    new_state.highest_bidder = new_state.empty_bid();
+    new_state.highest_bid_is_credit = false;
+    new_state.runner_up = new_state.empty_bid();
+    new_state.bid_history.clear();
+    new_state.quantity_bids.clear();
+    new_state.completion_deadline_millis = None;
+    new_state.status = BIDDING;
+    (new_state, vec![])
+}
+
+/// Action for cancelling the auction. Panics if the caller is not the contract owner, the
+/// block time is later than the contracts end time, or if the status is not `BIDDING`.
+/// When the contract is cancelled the status is changed to `CANCELLED`, and the highest bidder
+/// will be able to claim the amount of tokens he bid, as will all quantity bidders. Similarly the
+/// contract owner is able to claim the tokens previously for sale.
+/// If the auction compensates the highest bidder, the compensation is added to their claims, and
+/// the rest of the compensation pot is returned to the contract owner.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x07)]
+pub fn cancel(
+    context: ContractContext,
+    state: AuctionContractState,
 ) -> (AuctionContractState, Vec<EventGroup>) {
-    if token_for_sale.address_type != AddressType::PublicContract {
-        panic!("Tried to create a contract selling a non publicContract token");
-    }
-    if token_for_bidding.address_type != AddressType::PublicContract {
-        panic!("Tried to create a contract buying a non publicContract token");
+    let mut new_state = state;
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can cancel the auction");
+    } else if context.block_production_time >= new_state.end_time_millis {
+        panic!("Tried to cancel the auction after auction end block time");
+    } else if new_state.status != BIDDING {
+        panic!("Tried to cancel the auction when the status isn't Bidding");
+    } else {
+        new_state.status = CANCELLED;
+        for bid in std::mem::take(&mut new_state.quantity_bids) {
+            new_state.add_to_claim_map(
+                bid.bidder,
+                TokenClaim {
+                    tokens_for_bidding: bid.escrow(),
+                    tokens_for_sale: 0,
+                    credited_at_millis: context.block_production_time,
+                },
+            );
+        }
+        new_state.return_highest_bid(context.block_production_time);
+        let compensation = new_state.compensate_highest_bidder(context.block_production_time);
+        new_state.return_compensation_pot(context.block_production_time);
+        new_state.add_to_claim_map(
+            new_state.contract_owner,
+            TokenClaim {
+                tokens_for_bidding: 0,
+                tokens_for_sale: new_state.token_amount_for_sale - compensation,
+                credited_at_millis: context.block_production_time,
+            },
+        );
+        (new_state, vec![])
     }
-    let duration_millis = i64::from(auction_duration_hours) * 60 * 60 * 1000;
-    let end_time_millis = ctx.block_production_time + duration_millis;
-    let state = AuctionContractState {
-        contract_owner: ctx.sender,
-        start_time_millis: ctx.block_production_time,
-        end_time_millis,
-        token_amount_for_sale,
-        token_for_sale,
-        token_for_bidding,
-        highest_bidder: Bid {
-            bidder: ctx.sender,
-            amount: 0,
-        },
-        reserve_price,
-        min_increment,
-        claim_map: BTreeMap::new(),
-        status: CREATION,
-    };
-
-    (state, vec![])
 }
 
-/// Action for starting the contract. The function throws an error if the caller isn't the `contract_owner`
-/// or the contracts `status` isn't `STARTING`.
-/// The contract is started by creating a transfer event from the `contract_owner`
-/// to the contract of the tokens being sold as well as a callback to `start_callback`.
+/// Action for funding the compensation pot, from which the highest bidder is compensated if the
+/// auction is cancelled. Panics if the caller is not the contract owner, if the auction does not
+/// compensate from a pot, or if the auction is over.
+/// The tokens for bidding are transferred from the owner to the contract, and added to the pot in
+/// `fund_compensation_pot_callback`.
 ///
 /// ### Parameters:
 ///
@@ -216,41 +2610,49 @@ pub fn initialize(
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
+/// * `amount`: [`u128`], the amount of tokens for bidding added to the pot.
+///
 /// ### Returns
 ///
 /// The unchanged state object of type [`AuctionContractState`].
-#[action(shortname = 0x01)]
-pub fn start(
+#[action(shortname = 0x15)]
+pub fn fund_compensation_pot(
     context: ContractContext,
     state: AuctionContractState,
+    amount: u128,
 ) -> (AuctionContractState, Vec<EventGroup>) {
     if context.sender != state.contract_owner {
-        panic!("Start can only be called by the creator of the contract");
+        panic!("Only the contract owner can fund the compensation pot");
     }
-    if state.status != CREATION {
-        panic!("Start should only be called while setting up the contract");
+    if !matches!(
+        state.cancel_compensation,
+        Some(CancelCompensation {
+            source: CompensationSource::Pot {},
+            ..
+        })
+    ) {
+        panic!("The auction does not compensate the highest bidder from a pot");
+    }
+    if state.status != CREATION && state.status != BIDDING {
+        panic!("The compensation pot can only be funded before the auction is over");
     }
-    // Create transfer event to contract for the token_for_sale
-    // transfer should callback to start_callback (1)
-
-    // Builder
-
     let mut event_group = EventGroup::builder();
-
-    event_group.with_callback(SHORTNAME_START_CALLBACK).done();
-
     event_group
-        .call(state.token_for_sale, token_contract_transfer_from())
+        .call(state.token_for_bidding, token_contract_transfer_from())
         .argument(context.sender)
         .argument(context.contract_address)
-        .argument(state.token_amount_for_sale)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_FUND_COMPENSATION_POT_CALLBACK)
+        .argument(amount)
         .done();
-
     (state, vec![event_group.build()])
 }
 
-/// Callback for starting the contract. If the transfer event was successful the `status`
-/// is updated to `BIDDING`. If the transfer event failed the callback panics.
+/// Callback for funding the compensation pot. If the transfer event was successful the amount is
+/// added to the pot. If the auction was cancelled or executed in the meantime, the amount is added
+/// to the claims of the contract owner instead. If the transfer event failed the callback panics.
 ///
 /// ### Parameters:
 ///
@@ -260,26 +2662,36 @@ pub fn start(
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
+/// * `amount`: [`u128`], the amount of tokens for bidding transferred to the contract.
+///
 /// ### Returns
 ///
 /// The new state object of type [`AuctionContractState`].
-#[callback(shortname = 0x02)]
-pub fn start_callback(
+#[callback(shortname = 0x16)]
+pub fn fund_compensation_pot_callback(
     ctx: ContractContext,
     callback_ctx: CallbackContext,
     state: AuctionContractState,
+    amount: u128,
 ) -> (AuctionContractState, Vec<EventGroup>) {
-    let mut new_state = state;
     if !callback_ctx.success {
-        panic!("Transfer event did not succeed for start");
+        panic!("Transfer event did not succeed for funding the compensation pot");
+    }
+    let mut new_state = state;
+    new_state.compensation_pot += amount;
+    if new_state.status != CREATION && new_state.status != BIDDING {
+        new_state.return_compensation_pot(ctx.block_production_time);
     }
-    new_state.status = BIDDING;
     (new_state, vec![])
 }
 
-/// Action for bidding on the auction. The function always makes a transfer event
-/// to the token for bidding contract. On callback `bid_callback` is called to actually update
-/// the state.
+/// Action for settling the proceeds of the auction through a payment splitter contract.
+/// Panics if the caller is not the contract owner, if the status is not `ENDED` or if the owner
+/// has no proceeds to settle.
+/// The owners claimable bidding tokens are transferred to the splitter, and the payment is
+/// registered at the splitter such that it can divide the revenue among its payees, see the
+/// payment splitter contract actions for the interface of the splitter.
+/// The transfer and the registration are checked separately in `settle_via_splitter_callback`.
 ///
 /// ### Parameters:
 ///
@@ -287,104 +2699,107 @@ pub fn start_callback(
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
-/// * `bid_amount`: [`u128`], the amount of tokens in the bid.
+/// * `splitter_address`: [`Address`], the address of the payment splitter contract.
 ///
 /// ### Returns
 ///
-/// The unchanged state object of type [`AuctionContractState`].
-#[action(shortname = 0x03)]
-pub fn bid(
+/// The new state object of type [`AuctionContractState`].
+#[action(shortname = 0x08)]
+pub fn settle_via_splitter(
     context: ContractContext,
     state: AuctionContractState,
-    bid_amount: u128,
+    splitter_address: Address,
 ) -> (AuctionContractState, Vec<EventGroup>) {
-    // Potential new bid, create the transfer event
-    // transfer(auctionContract, bid_amount)
-
-    let bid: Bid = Bid {
-        bidder: context.sender,
-        amount: bid_amount,
+    let mut new_state = state;
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can settle the auction");
+    } else if new_state.status != ENDED {
+        panic!("Tried to settle the auction when the status isn't Ended");
+    } else if splitter_address.address_type != AddressType::PublicContract {
+        panic!("Tried to settle to a non publicContract splitter");
+    }
+    let proceeds = match new_state.claim_map.get_mut(&new_state.contract_owner) {
+        Some(claim) if claim.tokens_for_bidding > 0 => {
+            let proceeds = claim.tokens_for_bidding;
+            claim.tokens_for_bidding = 0;
+            proceeds
+        }
+        _ => panic!("No proceeds to settle"),
     };
 
     let mut event_group = EventGroup::builder();
     event_group
-        .call(state.token_for_bidding, token_contract_transfer_from())
-        .argument(context.sender)
-        .argument(context.contract_address)
-        .argument(bid_amount)
+        .call(new_state.token_for_bidding, token_contract_transfer())
+        .argument(splitter_address)
+        .argument(proceeds)
         .done();
     event_group
-        .with_callback(SHORTNAME_BID_CALLBACK)
-        .argument(bid)
+        .call(splitter_address, payment_splitter_register_payment())
+        .argument(new_state.token_for_bidding)
+        .argument(proceeds)
         .done();
-    (state, vec![event_group.build()])
+    event_group
+        .with_callback(SHORTNAME_SETTLE_VIA_SPLITTER_CALLBACK)
+        .argument(splitter_address)
+        .argument(proceeds)
+        .done();
+    (new_state, vec![event_group.build()])
 }
 
-/// Callback from bidding. If the transfer event was successful the `bid` will be compared
-/// to the current highest bid and the claim map is updated accordingly.
-/// If the transfer event fails the state is unchanged.
+/// Callback for settling through a payment splitter. The transfer and the registration at the
+/// splitter are checked separately. If the transfer failed, the proceeds never left the contract
+/// and are moved back into the contract owners claims. If only the registration failed, the
+/// proceeds are held by the splitter, and are recorded as an unregistered payment to the splitter
+/// which the owner can register again with `register_splitter_payment`.
 ///
 /// ### Parameters:
 ///
 /// * `ctx`: [`ContractContext`], the contractContext for the callback.
 ///
-/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext, with the result of the transfer
+///   followed by the result of the registration.
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
-/// * `bid`: [`Bid`], the bid containing information as to who the bidder was and which
-/// amount was bid.
+/// * `splitter_address`: [`Address`], the address of the payment splitter contract.
+///
+/// * `proceeds`: [`u128`], the amount of bidding tokens that was settled.
 ///
 /// ### Returns
 ///
 /// The new state object of type [`AuctionContractState`].
-#[callback(shortname = 0x04)]
-pub fn bid_callback(
+#[callback(shortname = 0x09)]
+pub fn settle_via_splitter_callback(
     ctx: ContractContext,
     callback_ctx: CallbackContext,
     state: AuctionContractState,
-    bid: Bid,
+    splitter_address: Address,
+    proceeds: u128,
 ) -> (AuctionContractState, Vec<EventGroup>) {
     let mut new_state = state;
-    if !callback_ctx.success {
-        panic!("Transfer event did not succeed for bid");
-    } else if new_state.status != BIDDING
-        || ctx.block_production_time >= new_state.end_time_millis
-        || bid.amount < new_state.highest_bidder.amount + new_state.min_increment
-        || bid.amount < new_state.reserve_price
-    {
-        // transfer succeeded, since we are no longer accepting bids we add
-        // this to the claim map so the sender can get his money back
-        // if the bid was too small we also add it to the claim map
-        new_state.add_to_claim_map(
-            bid.bidder,
-            TokenClaim {
-                tokens_for_bidding: bid.amount,
-                tokens_for_sale: 0,
-            },
-        );
-    } else {
-        // bidding phase and a new highest bid
-        let prev_highest_bidder = new_state.highest_bidder;
-        // update highest bidder
-        new_state.highest_bidder = bid;
-        // move previous highest bidders coin into the claim map
+    if !callback_ctx.results[0].succeeded {
+        let owner = new_state.contract_owner;
         new_state.add_to_claim_map(
-            prev_highest_bidder.bidder,
+            owner,
             TokenClaim {
-                tokens_for_bidding: prev_highest_bidder.amount,
+                tokens_for_bidding: proceeds,
                 tokens_for_sale: 0,
+                credited_at_millis: ctx.block_production_time,
             },
         );
+    } else if !callback_ctx.results[1].succeeded {
+        *new_state
+            .unregistered_splitter_payments
+            .entry(splitter_address)
+            .or_insert(0) += proceeds;
     }
     (new_state, vec![])
 }
 
-/// Action for claiming tokens. Can be called at any time during the auction. Only the highest
-/// bidder and the owner of the contract cannot get their escrowed tokens.
-/// If there is any available tokens for the sender in the claim map the contract creates
-/// appropriate transfer calls for both the token for sale and the token for bidding. The entry in
-/// the claim map is then set to 0 for both token types.
+/// Action for registering the payment to a payment splitter again, after the registration failed
+/// in `settle_via_splitter`. Panics if the caller is not the contract owner or if there is no
+/// unregistered payment to the splitter. The payment is registered again in
+/// `register_splitter_payment_callback` if the registration fails.
 ///
 /// ### Parameters:
 ///
@@ -392,96 +2807,83 @@ pub fn bid_callback(
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
+/// * `splitter_address`: [`Address`], the address of the payment splitter contract.
+///
 /// ### Returns
 ///
 /// The new state object of type [`AuctionContractState`].
-#[action(shortname = 0x05)]
-pub fn claim(
+#[action(shortname = 0x1E)]
+pub fn register_splitter_payment(
     context: ContractContext,
     state: AuctionContractState,
+    splitter_address: Address,
 ) -> (AuctionContractState, Vec<EventGroup>) {
     let mut new_state = state;
-    let opt_claimable = new_state.claim_map.get(&context.sender);
-    match opt_claimable {
-        None => (new_state, vec![]),
-        Some(claimable) => {
-            let mut event_group = EventGroup::builder();
-            if claimable.tokens_for_bidding > 0 {
-                event_group
-                    .call(new_state.token_for_bidding, token_contract_transfer())
-                    .argument(context.sender)
-                    .argument(claimable.tokens_for_bidding)
-                    .done();
-            }
-            if claimable.tokens_for_sale > 0 {
-                event_group
-                    .call(new_state.token_for_sale, token_contract_transfer())
-                    .argument(context.sender)
-                    .argument(claimable.tokens_for_sale)
-                    .done();
-            }
-            new_state.claim_map.insert(
-                context.sender,
-                TokenClaim {
-                    tokens_for_bidding: 0,
-                    tokens_for_sale: 0,
-                },
-            );
-            (new_state, vec![event_group.build()])
-        }
+    if context.sender != new_state.contract_owner {
+        panic!("Only the contract owner can register payments to a splitter");
     }
+    let amount = new_state
+        .unregistered_splitter_payments
+        .remove(&splitter_address)
+        .unwrap_or_else(|| panic!("No unregistered payment to {splitter_address:?}"));
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(splitter_address, payment_splitter_register_payment())
+        .argument(new_state.token_for_bidding)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_REGISTER_SPLITTER_PAYMENT_CALLBACK)
+        .argument(splitter_address)
+        .argument(amount)
+        .done();
+    (new_state, vec![event_group.build()])
 }
 
-/// Action for executing the auction. Panics if the block time is earlier than the contracts
-/// end time or if the current status is not `BIDDING`. When the contract is executed the status
-/// is changed to `ENDED`, and the highest bidder will be able to claim the sold tokens.
-/// Similarly the contract owner is able to claim the amount of bidding tokens that the highest
-/// bidder bid.
+/// Callback for registering a payment to a payment splitter. If the registration failed the
+/// payment is recorded as unregistered again.
 ///
 /// ### Parameters:
 ///
-/// * `context`: [`ContractContext`], the context for the action call.
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
+/// * `splitter_address`: [`Address`], the address of the payment splitter contract.
+///
+/// * `amount`: [`u128`], the amount of bidding tokens that was registered.
+///
 /// ### Returns
 ///
 /// The new state object of type [`AuctionContractState`].
-#[action(shortname = 0x06)]
-pub fn execute(
-    context: ContractContext,
+#[callback(shortname = 0x1F)]
+pub fn register_splitter_payment_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
     state: AuctionContractState,
+    splitter_address: Address,
+    amount: u128,
 ) -> (AuctionContractState, Vec<EventGroup>) {
     let mut new_state = state;
-    if context.block_production_time < new_state.end_time_millis {
-        panic!("Tried to execute the auction before auction end block time");
-    } else if new_state.status != BIDDING {
-        panic!("Tried to execute the auction when the status isn't Bidding");
-    } else {
-        new_state.status = ENDED;
-        new_state.add_to_claim_map(
-            new_state.contract_owner,
-            TokenClaim {
-                tokens_for_bidding: new_state.highest_bidder.amount,
-                tokens_for_sale: 0,
-            },
-        );
-        new_state.add_to_claim_map(
-            new_state.highest_bidder.bidder,
-            TokenClaim {
-                tokens_for_bidding: 0,
-                tokens_for_sale: new_state.token_amount_for_sale,
-            },
-        );
-        (new_state, vec![])
+    if !callback_ctx.success {
+        *new_state
+            .unregistered_splitter_payments
+            .entry(splitter_address)
+            .or_insert(0) += amount;
     }
+    (new_state, vec![])
 }
 
-/// Action for cancelling the auction. Panics if the caller is not the contract owner, the
-/// block time is later than the contracts end time, or if the status is not `BIDDING`.
-/// When the contract is cancelled the status is changed to `CANCELLED`, and the highest bidder
-/// will be able to claim the amount of tokens he bid. Similarly the contract owner is
-/// able to claim the tokens previously for sale.
+/// Action for sweeping the claims that have been left unclaimed for too long. Panics if the caller
+/// is not the contract owner, if the auction has no expiry of unclaimed tokens, or if the auction
+/// is not over. A claim expires `unclaimed_expiry_millis` after the later of the end time of the
+/// auction and the time tokens were last credited to the claim. At most `max_claims` expired
+/// claims are swept, such that the sweep can be split into several calls. The swept tokens are
+/// transferred to the sweep recipient, or to the contract owner if there is none, with a callback
+/// to `sweep_expired_claims_callback`.
 ///
 /// ### Parameters:
 ///
@@ -489,37 +2891,105 @@ pub fn execute(
 ///
 /// * `state`: [`AuctionContractState`], the current state of the contract.
 ///
+/// * `max_claims`: [`u32`], the maximum number of claims to sweep.
+///
 /// ### Returns
 ///
 /// The new state object of type [`AuctionContractState`].
-#[action(shortname = 0x07)]
-pub fn cancel(
+#[action(shortname = 0x11)]
+pub fn sweep_expired_claims(
     context: ContractContext,
     state: AuctionContractState,
+    max_claims: u32,
 ) -> (AuctionContractState, Vec<EventGroup>) {
+    if context.sender != state.contract_owner {
+        panic!("Only the contract owner can sweep expired claims");
+    }
+    let expiry_millis = state
+        .unclaimed_expiry_millis
+        .unwrap_or_else(|| panic!("Unclaimed tokens do not expire in this auction"));
+    if state.status != ENDED && state.status != CANCELLED && state.status != RESERVE_NOT_MET {
+        panic!("Tried to sweep expired claims before the auction is over");
+    }
+
     let mut new_state = state;
-    if context.sender != new_state.contract_owner {
-        panic!("Only the contract owner can cancel the auction");
-    } else if context.block_production_time >= new_state.end_time_millis {
-        panic!("Tried to cancel the auction after auction end block time");
-    } else if new_state.status != BIDDING {
-        panic!("Tried to cancel the auction when the status isn't Bidding");
-    } else {
-        new_state.status = CANCELLED;
-        new_state.add_to_claim_map(
-            new_state.highest_bidder.bidder,
-            TokenClaim {
-                tokens_for_bidding: new_state.highest_bidder.amount,
-                tokens_for_sale: 0,
-            },
-        );
-        new_state.add_to_claim_map(
-            new_state.contract_owner,
-            TokenClaim {
-                tokens_for_bidding: 0,
-                tokens_for_sale: new_state.token_amount_for_sale,
-            },
-        );
-        (new_state, vec![])
+    let now = context.block_production_time;
+    let end_time_millis = new_state.end_time_millis;
+    let mut remaining = max_claims;
+    let swept = new_state.claim_map.sweep(|_, claim| {
+        let expires_at = claim
+            .credited_at_millis
+            .max(end_time_millis)
+            .saturating_add(expiry_millis);
+        let sweep = remaining > 0 && !claim.is_empty() && now >= expires_at;
+        if sweep {
+            remaining -= 1;
+        }
+        sweep
+    });
+    if swept.is_empty() {
+        return (new_state, vec![]);
+    }
+    for (claimant, _) in &swept {
+        new_state.release_claimant(claimant);
     }
+
+    let tokens_for_bidding: u128 = swept
+        .iter()
+        .map(|(_, claim)| claim.tokens_for_bidding)
+        .sum();
+    let tokens_for_sale: u128 = swept.iter().map(|(_, claim)| claim.tokens_for_sale).sum();
+    let recipient = new_state
+        .sweep_recipient
+        .unwrap_or(new_state.contract_owner);
+    let payouts = new_state.claim_payouts(tokens_for_bidding, tokens_for_sale, 0);
+    let mut event_group = payout_event_group(recipient, &payouts);
+    event_group
+        .with_callback(SHORTNAME_SWEEP_EXPIRED_CLAIMS_CALLBACK)
+        .argument(recipient)
+        .argument(tokens_for_bidding)
+        .argument(tokens_for_sale)
+        .done();
+    (new_state, vec![event_group.build()])
+}
+
+/// Callback for sweeping expired claims. The tokens of each transfer of swept tokens that failed
+/// are added to the claim map of the recipient, such that they can be claimed instead. Transfers
+/// of zero tokens were not made, and have no result.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`AuctionContractState`], the current state of the contract.
+///
+/// * `recipient`: [`Address`], the recipient of the swept tokens.
+///
+/// * `tokens_for_bidding`: [`u128`], the amount of swept tokens for bidding.
+///
+/// * `tokens_for_sale`: [`u128`], the amount of swept tokens for sale.
+///
+/// ### Returns
+///
+/// The new state object of type [`AuctionContractState`].
+#[callback(shortname = 0x12)]
+pub fn sweep_expired_claims_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: AuctionContractState,
+    recipient: Address,
+    tokens_for_bidding: u128,
+    tokens_for_sale: u128,
+) -> (AuctionContractState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let payouts = new_state.claim_payouts(tokens_for_bidding, tokens_for_sale, 0);
+    new_state.restore_claim_if_failed(
+        &callback_ctx,
+        recipient,
+        &payouts,
+        ctx.block_production_time,
+    );
+    (new_state, vec![])
 }
@@ -6,8 +6,17 @@ use pbc_contract_common::events::EventGroup;
 use pbc_contract_common::Hash;
 
 use crate::{
-    bid, bid_callback, cancel, claim, execute, initialize, start, start_callback,
-    AuctionContractState, Bid, Shortname, TokenClaim, BIDDING, CANCELLED, ENDED,
+    allocate_partial_fill, bid, bid_callback, bid_for, bid_quantity_callback,
+    bid_with_deposit_callback, cancel, claim, claim_callback, claim_for, claim_range, complete,
+    credit_bid, execute, execute_callback, forfeit_deposit, fund_compensation_pot,
+    fund_compensation_pot_callback, initialize, oracle_reserve_price, pay_installment,
+    pay_installment_callback, per_mille_of, reclaim_unvested, refund_callback,
+    register_splitter_payment, register_splitter_payment_callback, relist, retract_bid,
+    settle_via_splitter, settle_via_splitter_callback, start, start_callback, sweep_expired_claims,
+    sweep_expired_claims_callback, AuctionContractState, AuctionMode, Bid, BidDeposit,
+    CancelCompensation, CompensationSource, InstallmentPlan, OracleReserve, QuantityBid,
+    RoyaltyPayee, SettlementMode, Shortname, TokenClaim, AWAITING_COMPLETION, BIDDING, CANCELLED,
+    ENDED, PAYING_INSTALLMENTS, RESERVE_NOT_MET,
 };
 
 fn create_ctx(sender: Address, block_time: i64) -> ContractContext {
@@ -70,6 +79,20 @@ fn get_third_party_address() -> Address {
     }
 }
 
+fn get_splitter_address() -> Address {
+    Address {
+        address_type: AddressType::PublicContract,
+        identifier: [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6],
+    }
+}
+
+fn get_oracle_address() -> Address {
+    Address {
+        address_type: AddressType::PublicContract,
+        identifier: [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7],
+    }
+}
+
 fn create_callback_ctx(success: bool) -> CallbackContext {
     let ctx: CallbackContext = CallbackContext {
         success,
@@ -94,6 +117,19 @@ fn initialize_contract() -> (AuctionContractState, Vec<EventGroup>) {
         1_000,
         100,
         100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
     )
 }
 
@@ -111,6 +147,19 @@ pub fn test_initialize() {
         1_000,
         100,
         100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
     );
     assert_eq!(0, events.len());
     assert_eq!(0, state.status);
@@ -128,6 +177,39 @@ pub fn test_initialize() {
     assert_eq!(0, state.claim_map.len());
 }
 
+#[test]
+pub fn test_initialize_with_owner() {
+    let factory = get_contract_address();
+    let owner = get_owner_address();
+    let (state, _) = initialize(
+        create_ctx(factory, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(owner),
+    );
+    assert_eq!(owner, state.contract_owner);
+    assert_eq!(owner, state.highest_bidder.bidder);
+
+    let (_, events) = start(create_ctx(owner, 3), state);
+    assert_eq!(events.len(), 1);
+}
+
 #[test]
 #[should_panic]
 pub fn test_initialize_wrong_commodity() {
@@ -146,6 +228,19 @@ pub fn test_initialize_wrong_commodity() {
         1_000,
         100,
         100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
     );
 }
 
@@ -167,6 +262,19 @@ pub fn test_initialize_wrong_currency() {
         1_000,
         100,
         100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
     );
 }
 
@@ -261,11 +369,107 @@ pub fn test_bid() {
     expected_event
         .with_callback(ShortnameCallback::from_u32(4))
         .argument(bidder)
+        .argument(bidder)
         .argument(10u128)
         .done();
     assert_eq!(*bid_event, expected_event.build());
 }
 
+#[test]
+pub fn test_bid_for_credits_beneficiary() {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let payer = get_bidder_address();
+    let beneficiary = get_third_party_address();
+
+    let (_, events) = bid_for(
+        create_ctx(payer, 5),
+        started_state.clone(),
+        beneficiary,
+        2_000,
+    );
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(3))
+        .argument(payer)
+        .argument(get_contract_address())
+        .argument(2_000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(4))
+        .argument(payer)
+        .argument(beneficiary)
+        .argument(2_000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    let (bid_state, _) = bid_callback(
+        create_ctx(payer, 6),
+        create_callback_ctx(true),
+        started_state,
+        Bid {
+            bidder: payer,
+            beneficiary,
+            amount: 2_000,
+        },
+    );
+    let (execute_state, _) = execute(create_ctx(owner, 102), bid_state);
+    assert_eq!(
+        *execute_state.claim_map.get(&beneficiary).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        execute_state.claim_map.get(&payer).unwrap().tokens_for_sale,
+        0
+    );
+}
+
+#[test]
+pub fn test_bid_for_outbid_refunds_payer() {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let payer = get_bidder_address();
+    let beneficiary = get_third_party_address();
+
+    let (bid_state, _) = bid_callback(
+        create_ctx(payer, 5),
+        create_callback_ctx(true),
+        started_state,
+        Bid {
+            bidder: payer,
+            beneficiary,
+            amount: 2_000,
+        },
+    );
+    let (outbid_state, _) = bid_callback(
+        create_ctx(owner, 6),
+        create_callback_ctx(true),
+        bid_state,
+        Bid {
+            bidder: owner,
+            beneficiary: owner,
+            amount: 3_000,
+        },
+    );
+    assert_eq!(
+        *outbid_state.claim_map.get(&payer).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2_000,
+            tokens_for_sale: 0,
+            credited_at_millis: 6 * 3_600_000,
+        }
+    );
+    assert!(!outbid_state.claim_map.contains_key(&beneficiary));
+}
+
 #[test]
 pub fn test_bid_callback_new_highest_bid() {
     let (init_state, _) = initialize_contract();
@@ -278,6 +482,7 @@ pub fn test_bid_callback_new_highest_bid() {
     let bid_callback_ctx = create_callback_ctx(true);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 1000,
     };
     assert_eq!(start_callback_state.claim_map.len(), 0);
@@ -293,6 +498,7 @@ pub fn test_bid_callback_new_highest_bid() {
         TokenClaim {
             tokens_for_bidding: 0,
             tokens_for_sale: 0,
+            credited_at_millis: 4 * 3_600_000,
         }
     );
     assert_eq!(bid_callback_state.highest_bidder, bid);
@@ -308,6 +514,7 @@ pub fn test_bid_callback_not_bidding() {
     let bid_callback_ctx = create_callback_ctx(true);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 1000,
     };
     assert_eq!(init_state.claim_map.len(), 0);
@@ -323,12 +530,14 @@ pub fn test_bid_callback_not_bidding() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 0,
+            credited_at_millis: 4 * 3_600_000,
         }
     );
     assert_eq!(
         bid_callback_state.highest_bidder,
         Bid {
             bidder: owner,
+            beneficiary: owner,
             amount: 0,
         }
     );
@@ -347,6 +556,7 @@ pub fn test_bid_callback_end_time_reached() {
     let bid_callback_ctx = create_callback_ctx(true);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 1000,
     };
     assert_eq!(start_callback_state.claim_map.len(), 0);
@@ -362,12 +572,14 @@ pub fn test_bid_callback_end_time_reached() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
         }
     );
     assert_eq!(
         bid_callback_state.highest_bidder,
         Bid {
             bidder: owner,
+            beneficiary: owner,
             amount: 0,
         }
     );
@@ -386,6 +598,7 @@ pub fn test_bid_callback_multiple_claimable_bids() {
     let bid_callback_ctx = create_callback_ctx(true);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 1000,
     };
     assert_eq!(start_callback_state.claim_map.len(), 0);
@@ -405,12 +618,14 @@ pub fn test_bid_callback_multiple_claimable_bids() {
         TokenClaim {
             tokens_for_bidding: 2000,
             tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
         }
     );
     assert_eq!(
         bid2_callback_state.highest_bidder,
         Bid {
             bidder: owner,
+            beneficiary: owner,
             amount: 0,
         }
     );
@@ -429,7 +644,11 @@ pub fn test_bid_callback_not_highest_bid_cause_increment() {
     let bidder = get_bidder_address();
     let bid_ctx = create_ctx(bidder, 101);
     let bid_callback_ctx = create_callback_ctx(true);
-    let bid = Bid { bidder, amount: 99 };
+    let bid = Bid {
+        bidder,
+        beneficiary: bidder,
+        amount: 99,
+    };
     assert_eq!(start_callback_state.claim_map.len(), 0);
     let (bid_callback_state, bid_callback_events) =
         bid_callback(bid_ctx, bid_callback_ctx, start_callback_state, bid);
@@ -443,12 +662,14 @@ pub fn test_bid_callback_not_highest_bid_cause_increment() {
         TokenClaim {
             tokens_for_bidding: 99,
             tokens_for_sale: 0,
+            credited_at_millis: 101 * 3_600_000,
         }
     );
     assert_eq!(
         bid_callback_state.highest_bidder,
         Bid {
             bidder: owner,
+            beneficiary: owner,
             amount: 0,
         }
     );
@@ -469,6 +690,7 @@ pub fn test_bid_callback_not_highest_bid_cause_reserve() {
     let bid_callback_ctx = create_callback_ctx(true);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 999,
     };
     assert_eq!(start_callback_state.claim_map.len(), 0);
@@ -484,12 +706,14 @@ pub fn test_bid_callback_not_highest_bid_cause_reserve() {
         TokenClaim {
             tokens_for_bidding: 999,
             tokens_for_sale: 0,
+            credited_at_millis: 101 * 3_600_000,
         }
     );
     assert_eq!(
         bid_callback_state.highest_bidder,
         Bid {
             bidder: owner,
+            beneficiary: owner,
             amount: 0,
         }
     );
@@ -508,6 +732,7 @@ pub fn test_bid_callback_transfer_unsuccessful() {
     let bid_callback_ctx = create_callback_ctx(false);
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 1000,
     };
     bid_callback(bid_ctx, bid_callback_ctx, start_callback_state, bid);
@@ -522,6 +747,7 @@ pub fn test_claim_no_entry() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 0,
+            credited_at_millis: 0,
         },
     );
     let other_address = get_third_party_address();
@@ -536,6 +762,7 @@ pub fn test_claim_no_entry() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 0,
+            credited_at_millis: 0,
         }
     );
 }
@@ -549,20 +776,15 @@ pub fn test_claim_currency() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 0,
+            credited_at_millis: 0,
         },
     );
     let claim_ctx = create_ctx(address, 4);
     let (claim_state, claim_events) = claim(claim_ctx, init_state.clone());
-    assert_eq!(claim_state.claim_map.len(), 1);
-    let claim_entry = claim_state.claim_map.get(&address);
-    assert!(claim_entry.is_some());
-    assert_eq!(
-        *claim_entry.unwrap(),
-        TokenClaim {
-            tokens_for_bidding: 0,
-            tokens_for_sale: 0,
-        }
-    );
+    // The paid out claimant is removed
+    assert!(claim_state.claim_map.is_empty());
+    assert!(claim_state.claimants.is_empty());
+    assert!(!claim_state.claimant_nonces.contains_key(&address));
     assert_eq!(claim_events.len(), 1);
     let event = claim_events.get(0).unwrap();
     let mut expected_event = EventGroup::builder();
@@ -571,6 +793,13 @@ pub fn test_claim_currency() {
         .argument(get_owner_address())
         .argument(1000u128)
         .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x20))
+        .argument(get_owner_address())
+        .argument(1000u128)
+        .argument(0u128)
+        .argument(0u128)
+        .done();
     assert_eq!(*event, expected_event.build());
 }
 
@@ -583,20 +812,15 @@ pub fn test_claim_commodity() {
         TokenClaim {
             tokens_for_bidding: 0,
             tokens_for_sale: 100,
+            credited_at_millis: 0,
         },
     );
     let claim_ctx = create_ctx(address, 4);
     let (claim_state, claim_events) = claim(claim_ctx, init_state.clone());
-    assert_eq!(claim_state.claim_map.len(), 1);
-    let claim_entry = claim_state.claim_map.get(&address);
-    assert!(claim_entry.is_some());
-    assert_eq!(
-        *claim_entry.unwrap(),
-        TokenClaim {
-            tokens_for_bidding: 0,
-            tokens_for_sale: 0,
-        }
-    );
+    // The paid out claimant is removed
+    assert!(claim_state.claim_map.is_empty());
+    assert!(claim_state.claimants.is_empty());
+    assert!(!claim_state.claimant_nonces.contains_key(&address));
     assert_eq!(claim_events.len(), 1);
     let event = claim_events.get(0).unwrap();
     let mut expected_event = EventGroup::builder();
@@ -605,6 +829,13 @@ pub fn test_claim_commodity() {
         .argument(get_owner_address())
         .argument(100u128)
         .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x20))
+        .argument(get_owner_address())
+        .argument(0u128)
+        .argument(100u128)
+        .argument(0u128)
+        .done();
     assert_eq!(*event, expected_event.build());
 }
 
@@ -617,20 +848,15 @@ pub fn test_claim_both() {
         TokenClaim {
             tokens_for_bidding: 1000,
             tokens_for_sale: 100,
+            credited_at_millis: 0,
         },
     );
     let claim_ctx = create_ctx(address, 4);
     let (claim_state, claim_events) = claim(claim_ctx, init_state.clone());
-    assert_eq!(claim_state.claim_map.len(), 1);
-    let claim_entry = claim_state.claim_map.get(&address);
-    assert!(claim_entry.is_some());
-    assert_eq!(
-        *claim_entry.unwrap(),
-        TokenClaim {
-            tokens_for_bidding: 0,
-            tokens_for_sale: 0,
-        }
-    );
+    // The paid out claimant is removed
+    assert!(claim_state.claim_map.is_empty());
+    assert!(claim_state.claimants.is_empty());
+    assert!(!claim_state.claimant_nonces.contains_key(&address));
     assert_eq!(claim_events.len(), 1);
     let event = claim_events.get(0).unwrap();
     let mut expected_event = EventGroup::builder();
@@ -644,9 +870,144 @@ pub fn test_claim_both() {
         .argument(get_owner_address())
         .argument(100u128)
         .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x20))
+        .argument(get_owner_address())
+        .argument(1000u128)
+        .argument(100u128)
+        .argument(0u128)
+        .done();
+    assert_eq!(*event, expected_event.build());
+}
+
+#[test]
+pub fn test_claim_for() {
+    let (mut init_state, _) = initialize_contract();
+    let beneficiary = get_bidder_address();
+    init_state.add_to_claim_map(
+        beneficiary,
+        TokenClaim {
+            tokens_for_bidding: 1000,
+            tokens_for_sale: 0,
+            credited_at_millis: 0,
+        },
+    );
+    let claim_ctx = create_ctx(get_third_party_address(), 4);
+    let (claim_state, claim_events) = claim_for(claim_ctx, init_state, beneficiary);
+    assert_eq!(claim_state.claim_map.get(&beneficiary), None);
+    assert!(!claim_state.claimant_nonces.contains_key(&beneficiary));
+    assert_eq!(claim_events.len(), 1);
+    let event = claim_events.get(0).unwrap();
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(1))
+        .argument(beneficiary)
+        .argument(1000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x20))
+        .argument(beneficiary)
+        .argument(1000u128)
+        .argument(0u128)
+        .argument(0u128)
+        .done();
     assert_eq!(*event, expected_event.build());
 }
 
+#[test]
+pub fn test_claim_callback_restores_only_failed_transfers() {
+    let (init_state, _) = initialize_contract();
+    let beneficiary = get_bidder_address();
+    let callback_ctx = CallbackContext {
+        success: false,
+        results: vec![
+            ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: false,
+                return_data: vec![],
+            },
+        ],
+    };
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 5),
+        callback_ctx,
+        init_state,
+        beneficiary,
+        1000,
+        100,
+        0,
+    );
+    assert_eq!(
+        state.claim_map.get(&beneficiary),
+        Some(&TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100,
+            credited_at_millis: 5 * 3_600_000,
+        })
+    );
+    assert!(state.claimant_nonces.contains_key(&beneficiary));
+}
+
+#[test]
+pub fn test_claim_callback_restores_failed_deposit_refund() {
+    let bidder = get_bidder_address();
+    // the deposit was taken for the refund by the claim
+    let mut state = deposit_auction_with_bid();
+    state.deposits.remove(&bidder);
+    let callback_ctx = CallbackContext {
+        success: false,
+        results: vec![
+            ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: false,
+                return_data: vec![],
+            },
+        ],
+    };
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 103),
+        callback_ctx,
+        state,
+        bidder,
+        0,
+        100_000,
+        50,
+    );
+    assert_eq!(state.claim_map.get(&bidder), None);
+    assert_eq!(state.deposits.get(&bidder), Some(&50));
+}
+
+#[test]
+pub fn test_claim_callback_succeeded() {
+    let (init_state, _) = initialize_contract();
+    let beneficiary = get_bidder_address();
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 5),
+        create_callback_ctx(true),
+        init_state,
+        beneficiary,
+        1000,
+        0,
+        0,
+    );
+    assert_eq!(state.claim_map.get(&beneficiary), None);
+}
+
+#[test]
+pub fn test_claim_for_no_entry() {
+    let (init_state, _) = initialize_contract();
+    let claim_ctx = create_ctx(get_third_party_address(), 4);
+    let (claim_state, claim_events) = claim_for(claim_ctx, init_state, get_bidder_address());
+    assert_eq!(claim_events.len(), 0);
+    assert_eq!(claim_state.claim_map.len(), 0);
+}
+
 #[test]
 pub fn test_execute() {
     let (init_state, _) = initialize_contract();
@@ -656,6 +1017,7 @@ pub fn test_execute() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -682,6 +1044,7 @@ pub fn test_execute() {
         TokenClaim {
             tokens_for_bidding: 0,
             tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
         }
     );
     assert_eq!(
@@ -689,6 +1052,7 @@ pub fn test_execute() {
         TokenClaim {
             tokens_for_bidding: 2000,
             tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
         }
     );
 }
@@ -703,6 +1067,7 @@ pub fn test_execute_early() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -738,6 +1103,7 @@ pub fn test_cancel() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -762,6 +1128,7 @@ pub fn test_cancel() {
         TokenClaim {
             tokens_for_bidding: 2000,
             tokens_for_sale: 0,
+            credited_at_millis: 101 * 3_600_000,
         }
     );
     assert_eq!(
@@ -769,6 +1136,7 @@ pub fn test_cancel() {
         TokenClaim {
             tokens_for_bidding: 0,
             tokens_for_sale: 100_000,
+            credited_at_millis: 101 * 3_600_000,
         }
     );
 }
@@ -783,6 +1151,7 @@ pub fn test_cancel_not_owner() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -806,6 +1175,7 @@ pub fn test_cancel_after_end_time() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -839,6 +1209,7 @@ pub fn test_cancel_after_execute() {
     let bidder = get_bidder_address();
     let bid = Bid {
         bidder,
+        beneficiary: bidder,
         amount: 2000,
     };
     let (bid_state, _) = bid_callback(
@@ -855,3 +1226,2280 @@ pub fn test_cancel_after_execute() {
     let cancel_ctx = create_ctx(owner, 103);
     cancel(cancel_ctx, execute_state);
 }
+
+fn executed_contract() -> AuctionContractState {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let bidder = get_bidder_address();
+    let bid = Bid {
+        bidder,
+        beneficiary: bidder,
+        amount: 2000,
+    };
+    let (bid_state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        started_state,
+        bid,
+    );
+    let (execute_state, _) = execute(create_ctx(get_third_party_address(), 102), bid_state);
+    execute_state
+}
+
+#[test]
+pub fn test_settle_via_splitter() {
+    let executed_state = executed_contract();
+    let owner = get_owner_address();
+    let splitter = get_splitter_address();
+    let (settle_state, settle_events) =
+        settle_via_splitter(create_ctx(owner, 103), executed_state, splitter);
+    assert_eq!(
+        *settle_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(settle_events.len(), 1);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(1))
+        .argument(splitter)
+        .argument(2000u128)
+        .done();
+    expected_event
+        .call(splitter, Shortname::from_u32(2))
+        .argument(get_currency_token_address())
+        .argument(2000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(9))
+        .argument(splitter)
+        .argument(2000u128)
+        .done();
+    assert_eq!(settle_events[0], expected_event.build());
+}
+
+fn settlement_callback_ctx(
+    transfer_succeeded: bool,
+    registration_succeeded: bool,
+) -> CallbackContext {
+    CallbackContext {
+        success: transfer_succeeded && registration_succeeded,
+        results: vec![
+            ExecutionResult {
+                succeeded: transfer_succeeded,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: registration_succeeded,
+                return_data: vec![],
+            },
+        ],
+    }
+}
+
+fn settle_with_results(
+    transfer_succeeded: bool,
+    registration_succeeded: bool,
+) -> AuctionContractState {
+    let owner = get_owner_address();
+    let (settle_state, _) = settle_via_splitter(
+        create_ctx(owner, 103),
+        executed_contract(),
+        get_splitter_address(),
+    );
+    let (callback_state, callback_events) = settle_via_splitter_callback(
+        create_ctx(owner, 104),
+        settlement_callback_ctx(transfer_succeeded, registration_succeeded),
+        settle_state,
+        get_splitter_address(),
+        2000,
+    );
+    assert_eq!(callback_events.len(), 0);
+    callback_state
+}
+
+#[test]
+pub fn test_settle_via_splitter_callback_failed() {
+    let callback_state = settle_with_results(false, false);
+    assert_eq!(
+        *callback_state.claim_map.get(&get_owner_address()).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 0,
+            credited_at_millis: 104 * 3_600_000,
+        }
+    );
+    assert!(callback_state.unregistered_splitter_payments.is_empty());
+}
+
+#[test]
+pub fn test_settle_via_splitter_callback_succeeded() {
+    let callback_state = settle_with_results(true, true);
+    assert_eq!(
+        callback_state
+            .claim_map
+            .get(&get_owner_address())
+            .unwrap()
+            .tokens_for_bidding,
+        0
+    );
+    assert!(callback_state.unregistered_splitter_payments.is_empty());
+}
+
+#[test]
+pub fn test_settle_via_splitter_failed_transfer_restores_proceeds() {
+    let callback_state = settle_with_results(false, true);
+    assert_eq!(
+        callback_state
+            .claim_map
+            .get(&get_owner_address())
+            .unwrap()
+            .tokens_for_bidding,
+        2000
+    );
+    assert!(callback_state.unregistered_splitter_payments.is_empty());
+}
+
+#[test]
+pub fn test_settle_via_splitter_failed_registration_keeps_payment_at_splitter() {
+    let callback_state = settle_with_results(true, false);
+    assert_eq!(
+        callback_state
+            .claim_map
+            .get(&get_owner_address())
+            .unwrap()
+            .tokens_for_bidding,
+        0
+    );
+    assert_eq!(
+        callback_state
+            .unregistered_splitter_payments
+            .get(&get_splitter_address()),
+        Some(&2000)
+    );
+}
+
+#[test]
+pub fn test_register_splitter_payment_again() {
+    let owner = get_owner_address();
+    let splitter = get_splitter_address();
+    let state = settle_with_results(true, false);
+    let (register_state, register_events) =
+        register_splitter_payment(create_ctx(owner, 105), state, splitter);
+    assert!(register_state.unregistered_splitter_payments.is_empty());
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(splitter, Shortname::from_u32(2))
+        .argument(get_currency_token_address())
+        .argument(2000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x1F))
+        .argument(splitter)
+        .argument(2000u128)
+        .done();
+    assert_eq!(register_events, vec![expected_event.build()]);
+
+    let (callback_state, _) = register_splitter_payment_callback(
+        create_ctx(owner, 106),
+        create_callback_ctx(false),
+        register_state,
+        splitter,
+        2000,
+    );
+    assert_eq!(
+        callback_state.unregistered_splitter_payments.get(&splitter),
+        Some(&2000)
+    );
+}
+
+#[test]
+#[should_panic(expected = "No unregistered payment to")]
+pub fn test_register_splitter_payment_without_unregistered_payment() {
+    let state = settle_with_results(true, true);
+    register_splitter_payment(
+        create_ctx(get_owner_address(), 105),
+        state,
+        get_splitter_address(),
+    );
+}
+
+#[test]
+#[should_panic]
+pub fn test_settle_via_splitter_not_owner() {
+    let executed_state = executed_contract();
+    let bidder = get_bidder_address();
+    settle_via_splitter(
+        create_ctx(bidder, 103),
+        executed_state,
+        get_splitter_address(),
+    );
+}
+
+#[test]
+#[should_panic]
+pub fn test_settle_via_splitter_not_ended() {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    settle_via_splitter(create_ctx(owner, 4), started_state, get_splitter_address());
+}
+
+#[test]
+#[should_panic]
+pub fn test_settle_via_splitter_twice() {
+    let executed_state = executed_contract();
+    let owner = get_owner_address();
+    let (settle_state, _) = settle_via_splitter(
+        create_ctx(owner, 103),
+        executed_state,
+        get_splitter_address(),
+    );
+    settle_via_splitter(create_ctx(owner, 104), settle_state, get_splitter_address());
+}
+
+fn initialize_partial_fill_contract() -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        10,
+        0,
+        100,
+        AuctionMode::PartialFill {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    started_state
+}
+
+fn quantity_bid(bidder: Address, quantity: u128, price: u128) -> QuantityBid {
+    QuantityBid {
+        bidder,
+        quantity,
+        price,
+    }
+}
+
+#[test]
+pub fn test_allocate_partial_fill_undersubscribed() {
+    let bidder = get_bidder_address();
+    let bids = vec![quantity_bid(bidder, 30, 12), quantity_bid(bidder, 20, 15)];
+    let (clearing_price, allocations) = allocate_partial_fill(&bids, 100);
+    assert_eq!(clearing_price, 12);
+    assert_eq!(allocations, vec![30, 20]);
+}
+
+#[test]
+pub fn test_allocate_partial_fill_pro_rata_at_margin() {
+    let bidder = get_bidder_address();
+    let bids = vec![
+        quantity_bid(bidder, 60, 20),
+        quantity_bid(bidder, 50, 12),
+        quantity_bid(bidder, 30, 12),
+        quantity_bid(bidder, 40, 11),
+    ];
+    let (clearing_price, allocations) = allocate_partial_fill(&bids, 100);
+    assert_eq!(clearing_price, 12);
+    // 40 tokens remain for the bids at the clearing price, divided 50:30
+    assert_eq!(allocations, vec![60, 25, 15, 0]);
+}
+
+#[test]
+pub fn test_allocate_partial_fill_no_bids() {
+    let (clearing_price, allocations) = allocate_partial_fill(&[], 100);
+    assert_eq!(clearing_price, 0);
+    assert_eq!(allocations, Vec::<u128>::new());
+}
+
+#[test]
+pub fn test_bid_quantity_callback_below_reserve() {
+    let state = initialize_partial_fill_contract();
+    let bidder = get_bidder_address();
+    let (bid_state, _) = bid_quantity_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        quantity_bid(bidder, 10, 9),
+    );
+    assert_eq!(bid_state.quantity_bids.len(), 0);
+    assert_eq!(
+        *bid_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 90,
+            tokens_for_sale: 0,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_execute_partial_fill() {
+    let mut state = initialize_partial_fill_contract();
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    for bid in [
+        quantity_bid(bidder, 80, 20),
+        quantity_bid(third_party, 40, 15),
+    ] {
+        let (bid_state, _) = bid_quantity_callback(
+            create_ctx(bid.bidder, 5),
+            create_callback_ctx(true),
+            state,
+            bid,
+        );
+        state = bid_state;
+    }
+    assert_eq!(state.quantity_bids.len(), 2);
+
+    let (execute_state, _) = execute(create_ctx(third_party, 102), state);
+    assert_eq!(execute_state.status, ENDED);
+    assert_eq!(execute_state.clearing_price, Some(15));
+    assert_eq!(
+        *execute_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 80 * 20 - 80 * 15,
+            tokens_for_sale: 80,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *execute_state.claim_map.get(&third_party).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 40 * 15 - 20 * 15,
+            tokens_for_sale: 20,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *execute_state.claim_map.get(&get_owner_address()).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 100 * 15,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_per_mille_of() {
+    assert_eq!(per_mille_of(2000, 25), 50);
+    assert_eq!(per_mille_of(1999, 25), 49);
+    assert_eq!(per_mille_of(u128::MAX, 1000), u128::MAX);
+}
+
+#[test]
+#[should_panic(expected = "The royalties of the payees cannot exceed 1000 per mille")]
+pub fn test_initialize_royalties_exceed_proceeds() {
+    let payees = vec![
+        RoyaltyPayee {
+            payee: get_third_party_address(),
+            per_mille: 600,
+        },
+        RoyaltyPayee {
+            payee: get_splitter_address(),
+            per_mille: 401,
+        },
+    ];
+    initialize(
+        create_ctx(get_owner_address(), 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        payees,
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+pub fn test_execute_with_royalties() {
+    let owner = get_owner_address();
+    let creator = get_third_party_address();
+    let platform = get_splitter_address();
+    let payees = vec![
+        RoyaltyPayee {
+            payee: creator,
+            per_mille: 75,
+        },
+        RoyaltyPayee {
+            payee: platform,
+            per_mille: 25,
+        },
+    ];
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        payees,
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let bidder = get_bidder_address();
+    let (bid_state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        started_state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2019,
+        },
+    );
+    let (execute_state, _) = execute(create_ctx(bidder, 102), bid_state);
+
+    // 7.5% of 2019 is 151.425 and 2.5% is 50.475, the rounding remainder goes to the owner
+    assert_eq!(
+        *execute_state.claim_map.get(&creator).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 151,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *execute_state.claim_map.get(&platform).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 50,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *execute_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2019 - 151 - 50,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *execute_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+}
+
+/// Starts an auction allowing retractions with a penalty of 10%, where the bidder outbids the
+/// third party.
+fn retractable_auction_with_two_bids() -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        Some(100),
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let third_party = get_third_party_address();
+    let (state, _) = bid_callback(
+        create_ctx(third_party, 4),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 2000,
+        },
+    );
+    let bidder = get_bidder_address();
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 3000,
+        },
+    );
+    state
+}
+
+#[test]
+pub fn test_retract_bid() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let state = retractable_auction_with_two_bids();
+    assert_eq!(
+        *state.claim_map.get(&third_party).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 0,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+
+    let (retracted_state, events) = retract_bid(create_ctx(bidder, 6), state);
+    assert_eq!(events.len(), 0);
+    assert_eq!(
+        retracted_state.highest_bidder,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 2000,
+        }
+    );
+    assert_eq!(
+        *retracted_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2700,
+            tokens_for_sale: 0,
+            credited_at_millis: 6 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *retracted_state.claim_map.get(&get_owner_address()).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 300,
+            tokens_for_sale: 0,
+            credited_at_millis: 6 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *retracted_state.claim_map.get(&third_party).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 0,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_retract_bid_previous_bid_claimed() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let state = retractable_auction_with_two_bids();
+    let (claimed_state, _) = claim(create_ctx(third_party, 6), state);
+
+    let (retracted_state, _) = retract_bid(create_ctx(bidder, 7), claimed_state);
+    assert_eq!(
+        retracted_state.highest_bidder,
+        Bid {
+            bidder: get_owner_address(),
+            beneficiary: get_owner_address(),
+            amount: 0,
+        }
+    );
+    assert_eq!(retracted_state.bid_history.len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Only the highest bidder can retract their bid")]
+pub fn test_retract_bid_not_highest_bidder() {
+    let state = retractable_auction_with_two_bids();
+    retract_bid(create_ctx(get_third_party_address(), 6), state);
+}
+
+#[test]
+#[should_panic(expected = "The auction does not allow retracting bids")]
+pub fn test_retract_bid_not_allowed() {
+    let (init_state, _) = initialize_contract();
+    let (state, _) = start_callback(
+        create_ctx(get_owner_address(), 3),
+        create_callback_ctx(true),
+        init_state,
+    );
+    retract_bid(create_ctx(get_bidder_address(), 4), state);
+}
+
+/// Starts a second price auction with a reserve price of 1000 and a minimum increment of 100.
+fn started_second_price_auction() -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::SecondPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    state
+}
+
+#[test]
+pub fn test_execute_second_price() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let state = started_second_price_auction();
+    let (state, _) = bid_callback(
+        create_ctx(third_party, 4),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 2000,
+        },
+    );
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 3000,
+        },
+    );
+    // A bid too low to outbid the highest bid still raises the runner-up bid
+    let (state, _) = bid_callback(
+        create_ctx(third_party, 6),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 2500,
+        },
+    );
+    assert_eq!(
+        state.runner_up,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 2500,
+        }
+    );
+
+    let (executed_state, _) = execute(create_ctx(get_owner_address(), 102), state);
+    assert_eq!(
+        *executed_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 400,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *executed_state.claim_map.get(&get_owner_address()).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2600,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_execute_second_price_single_bid_pays_reserve_price() {
+    let bidder = get_bidder_address();
+    let state = started_second_price_auction();
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 3000,
+        },
+    );
+
+    let (executed_state, _) = execute(create_ctx(get_owner_address(), 102), state);
+    assert_eq!(
+        *executed_state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(
+        *executed_state.claim_map.get(&get_owner_address()).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 1000,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "Second price settlement is only supported in the single winner mode")]
+pub fn test_initialize_second_price_partial_fill() {
+    initialize(
+        create_ctx(get_owner_address(), 2),
+        100,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        10,
+        0,
+        100,
+        AuctionMode::PartialFill {},
+        vec![],
+        None,
+        SettlementMode::SecondPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+}
+
+fn started_auto_refund_auction_with_bid() -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let (state, events) = bid_callback(
+        create_ctx(get_third_party_address(), 4),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder: get_third_party_address(),
+            beneficiary: get_third_party_address(),
+            amount: 2000,
+        },
+    );
+    // The initial empty bid of the owner is not refunded
+    assert_eq!(events.len(), 0);
+    state
+}
+
+#[test]
+pub fn test_bid_callback_auto_refund() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let state = started_auto_refund_auction_with_bid();
+    let (state, events) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 3000,
+        },
+    );
+
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(1))
+        .argument(third_party)
+        .argument(2000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x0E))
+        .argument(third_party)
+        .argument(2000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+    assert!(!state.claim_map.contains_key(&third_party));
+
+    let (state, _) = refund_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        third_party,
+        2000,
+    );
+    assert!(!state.claim_map.contains_key(&third_party));
+}
+
+#[test]
+pub fn test_refund_callback_failed_adds_to_claim_map() {
+    let third_party = get_third_party_address();
+    let state = started_auto_refund_auction_with_bid();
+    let (state, _) = refund_callback(
+        create_ctx(get_bidder_address(), 5),
+        create_callback_ctx(false),
+        state,
+        third_party,
+        2000,
+    );
+    assert_eq!(
+        *state.claim_map.get(&third_party).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 0,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_execute_reserve_not_met_and_relist() {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+
+    let (execute_state, _) = execute(create_ctx(get_third_party_address(), 102), started_state);
+    assert_eq!(execute_state.status, RESERVE_NOT_MET);
+    assert_eq!(
+        *execute_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+
+    let (relisted_state, _) = relist(create_ctx(owner, 110), execute_state, 500, 10);
+    assert_eq!(relisted_state.status, BIDDING);
+    assert_eq!(relisted_state.reserve_price, 500);
+    assert_eq!(relisted_state.end_time_millis, 120 * 3_600_000);
+    assert_eq!(
+        *relisted_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+
+    let bidder = get_bidder_address();
+    let (bid_state, _) = bid_callback(
+        create_ctx(bidder, 115),
+        create_callback_ctx(true),
+        relisted_state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 600,
+        },
+    );
+    let (execute_state, _) = execute(create_ctx(bidder, 120), bid_state);
+    assert_eq!(execute_state.status, ENDED);
+    assert_eq!(
+        execute_state
+            .claim_map
+            .get(&bidder)
+            .unwrap()
+            .tokens_for_sale,
+        100_000
+    );
+}
+
+#[test]
+#[should_panic(expected = "The tokens for sale have been claimed, and cannot be relisted")]
+pub fn test_relist_after_claiming_tokens_for_sale() {
+    let (init_state, _) = initialize_contract();
+    let owner = get_owner_address();
+    let (started_state, _) =
+        start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let (execute_state, _) = execute(create_ctx(owner, 102), started_state);
+    let (claim_state, _) = claim(create_ctx(owner, 103), execute_state);
+    relist(create_ctx(owner, 104), claim_state, 500, 10);
+}
+
+/// Creates an executed auction whose unclaimed tokens expire after 10 hours and are donated to
+/// the splitter address. The auction ends at hour 102, where the bidder won with a bid of 2000.
+fn executed_auction_with_claim_expiry() -> AuctionContractState {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        Some(10 * 3_600_000),
+        Some(get_splitter_address()),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+    );
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    state
+}
+
+#[test]
+pub fn test_sweep_expired_claims() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = executed_auction_with_claim_expiry();
+    let (state, _) = claim(create_ctx(owner, 103), state);
+
+    let (state, events) = sweep_expired_claims(create_ctx(owner, 111), state, 5);
+    assert_eq!(events.len(), 0);
+    assert!(state.claim_map.contains_key(&bidder));
+
+    let (state, events) = sweep_expired_claims(create_ctx(owner, 112), state, 5);
+    assert!(!state.claim_map.contains_key(&bidder));
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_commodity_token_address(), Shortname::from_u32(1))
+        .argument(get_splitter_address())
+        .argument(100_000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x12))
+        .argument(get_splitter_address())
+        .argument(0u128)
+        .argument(100_000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    let (state, _) = sweep_expired_claims_callback(
+        create_ctx(get_contract_address(), 112),
+        create_callback_ctx(false),
+        state,
+        get_splitter_address(),
+        0,
+        100_000,
+    );
+    assert_eq!(
+        state.claim_map.get(&get_splitter_address()),
+        Some(&TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 112 * 3_600_000,
+        })
+    );
+}
+
+#[test]
+pub fn test_sweep_expired_claims_callback_restores_only_failed_transfers() {
+    let callback_ctx = CallbackContext {
+        success: false,
+        results: vec![
+            ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: false,
+                return_data: vec![],
+            },
+        ],
+    };
+    let (state, _) = sweep_expired_claims_callback(
+        create_ctx(get_contract_address(), 112),
+        callback_ctx,
+        executed_auction_with_claim_expiry(),
+        get_splitter_address(),
+        2_000,
+        100_000,
+    );
+    assert_eq!(
+        state.claim_map.get(&get_splitter_address()),
+        Some(&TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 112 * 3_600_000,
+        })
+    );
+}
+
+#[test]
+pub fn test_sweep_expired_claims_callback_succeeded() {
+    let callback_ctx = CallbackContext {
+        success: true,
+        results: vec![
+            ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            },
+        ],
+    };
+    let (state, _) = sweep_expired_claims_callback(
+        create_ctx(get_contract_address(), 112),
+        callback_ctx,
+        executed_auction_with_claim_expiry(),
+        get_splitter_address(),
+        2_000,
+        100_000,
+    );
+    assert_eq!(state.claim_map.get(&get_splitter_address()), None);
+}
+
+#[test]
+pub fn test_sweep_expired_claims_bounded() {
+    let owner = get_owner_address();
+    let state = executed_auction_with_claim_expiry();
+
+    let (state, _) = sweep_expired_claims(create_ctx(owner, 112), state, 1);
+    assert_eq!(state.claim_map.len(), 1);
+    let (state, _) = sweep_expired_claims(create_ctx(owner, 112), state, 1);
+    assert!(state.claim_map.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Tried to sweep expired claims before the auction is over")]
+pub fn test_sweep_expired_claims_while_bidding() {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        Some(3_600_000),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    sweep_expired_claims(create_ctx(owner, 50), state, 1);
+}
+
+/// Starts an auction with a reserve price of 1000, a minimum increment of 100 and a credit bid cap
+/// of 5000.
+fn started_credit_bid_auction() -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        Some(5_000),
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    state
+}
+
+#[test]
+pub fn test_credit_bid_wins_without_proceeds() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = started_credit_bid_auction();
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 4),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+    );
+    let (state, events) = credit_bid(create_ctx(owner, 5), state, 4000);
+    assert_eq!(events.len(), 0);
+    assert!(state.highest_bid_is_credit);
+    assert_eq!(
+        *state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 0,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+
+    let (executed_state, _) = execute(create_ctx(owner, 102), state);
+    assert_eq!(executed_state.status, ENDED);
+    assert_eq!(
+        *executed_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+    assert_eq!(executed_state.claim_map.len(), 2);
+}
+
+#[test]
+pub fn test_outbid_credit_bid_is_not_refunded() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = started_credit_bid_auction();
+    let (state, _) = credit_bid(create_ctx(owner, 4), state, 3000);
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 3500,
+        },
+    );
+    assert!(!state.highest_bid_is_credit);
+    assert_eq!(
+        state
+            .claim_map
+            .get(&owner)
+            .map_or(0, |claim| claim.tokens_for_bidding),
+        0
+    );
+
+    let (executed_state, _) = execute(create_ctx(owner, 102), state);
+    assert_eq!(
+        *executed_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 3500,
+            tokens_for_sale: 0,
+            credited_at_millis: 102 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_cancel_with_credit_bid_returns_only_tokens_for_sale() {
+    let owner = get_owner_address();
+    let state = started_credit_bid_auction();
+    let (state, _) = credit_bid(create_ctx(owner, 4), state, 3000);
+    let (cancelled_state, _) = cancel(create_ctx(owner, 5), state);
+    assert_eq!(
+        *cancelled_state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 0,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 5 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "The credit bid of 5001 exceeds the credit bid cap of 5000")]
+pub fn test_credit_bid_exceeding_cap() {
+    let state = started_credit_bid_auction();
+    credit_bid(create_ctx(get_owner_address(), 4), state, 5001);
+}
+
+#[test]
+#[should_panic(expected = "Only the contract owner can bid on credit")]
+pub fn test_credit_bid_not_owner() {
+    let state = started_credit_bid_auction();
+    credit_bid(create_ctx(get_bidder_address(), 4), state, 2000);
+}
+
+#[test]
+#[should_panic(expected = "The auction does not allow credit bids")]
+pub fn test_credit_bid_not_allowed() {
+    let (state, _) = initialize_contract();
+    let (state, _) = start_callback(
+        create_ctx(get_owner_address(), 3),
+        create_callback_ctx(true),
+        state,
+    );
+    credit_bid(create_ctx(get_owner_address(), 4), state, 2000);
+}
+
+fn oracle_reserve_auction_with_bid() -> AuctionContractState {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        Some(OracleReserve {
+            oracle: get_oracle_address(),
+            min_reserve_usd: 3_000,
+        }),
+        None,
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+    );
+    state
+}
+
+fn oracle_price_callback_ctx(price_micro_usd: u128) -> CallbackContext {
+    CallbackContext {
+        success: true,
+        results: vec![ExecutionResult {
+            succeeded: true,
+            return_data: price_micro_usd.to_be_bytes().to_vec(),
+        }],
+    }
+}
+
+#[test]
+pub fn test_oracle_reserve_price_rounds_up() {
+    assert_eq!(oracle_reserve_price(3_000, 2_000_000), 1_500);
+    assert_eq!(oracle_reserve_price(3_000, 7_000_000), 429);
+    assert_eq!(oracle_reserve_price(u128::MAX, 1), u128::MAX);
+}
+
+#[test]
+pub fn test_execute_with_oracle_reserve_queries_oracle() {
+    let state = oracle_reserve_auction_with_bid();
+    let (state, events) = execute(create_ctx(get_third_party_address(), 102), state);
+    assert_eq!(events.len(), 1);
+    assert_eq!(state.status, BIDDING);
+    assert_eq!(state.highest_bidder.amount, 2000);
+}
+
+#[test]
+pub fn test_execute_callback_oracle_reserve_met() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = oracle_reserve_auction_with_bid();
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    // at 2 USD per token for bidding the reserve of 3000 USD is 1500 tokens
+    let (state, events) = execute_callback(
+        create_ctx(get_third_party_address(), 102),
+        oracle_price_callback_ctx(2_000_000),
+        state,
+    );
+    assert_eq!(events.len(), 0);
+    assert_eq!(state.status, ENDED);
+    assert_eq!(state.reserve_price, 1_500);
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_bidding,
+        2000
+    );
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_sale,
+        100_000
+    );
+}
+
+#[test]
+pub fn test_execute_callback_oracle_reserve_not_met() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = oracle_reserve_auction_with_bid();
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    // at 1 USD per token for bidding the reserve of 3000 USD is 3000 tokens
+    let (state, _) = execute_callback(
+        create_ctx(get_third_party_address(), 102),
+        oracle_price_callback_ctx(1_000_000),
+        state,
+    );
+    assert_eq!(state.status, RESERVE_NOT_MET);
+    assert_eq!(state.reserve_price, 3_000);
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        2000
+    );
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_sale,
+        100_000
+    );
+}
+
+#[test]
+pub fn test_execute_callback_oracle_query_failed() {
+    let state = oracle_reserve_auction_with_bid();
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    let (state, events) = execute_callback(
+        create_ctx(get_third_party_address(), 102),
+        create_callback_ctx(false),
+        state,
+    );
+    assert_eq!(events.len(), 0);
+    assert_eq!(state.status, BIDDING);
+    assert_eq!(
+        state.oracle_price.as_ref().unwrap().pending_since_millis,
+        None
+    );
+
+    // the auction can be executed again, querying the oracle again
+    let (state, events) = execute(create_ctx(get_third_party_address(), 103), state);
+    assert_eq!(events.len(), 1);
+    assert_eq!(state.status, BIDDING);
+}
+
+#[test]
+#[should_panic(expected = "The oracle price is already being queried")]
+pub fn test_execute_while_oracle_price_is_queried() {
+    let state = oracle_reserve_auction_with_bid();
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    execute(create_ctx(get_third_party_address(), 102), state);
+}
+
+fn compensated_auction(source: CompensationSource) -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        Some(CancelCompensation {
+            per_mille: 50,
+            source,
+        }),
+        None,
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    state
+}
+
+fn bid_on(state: AuctionContractState, amount: u128) -> AuctionContractState {
+    let bidder = get_bidder_address();
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount,
+        },
+    );
+    state
+}
+
+#[test]
+pub fn test_cancel_compensates_highest_bidder_with_tokens_for_sale() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = bid_on(compensated_auction(CompensationSource::SaleTokens {}), 2000);
+    let (state, _) = cancel(create_ctx(owner, 101), state);
+    assert_eq!(
+        *state.claim_map.get(&bidder).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 2000,
+            tokens_for_sale: 5_000,
+            credited_at_millis: 101 * 3_600_000,
+        }
+    );
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_sale, 95_000);
+}
+
+#[test]
+pub fn test_cancel_compensates_highest_bidder_from_pot() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = compensated_auction(CompensationSource::Pot {});
+    let (state, events) = fund_compensation_pot(create_ctx(owner, 4), state, 500);
+    assert_eq!(events.len(), 1);
+    assert_eq!(state.compensation_pot, 0);
+    let (state, _) =
+        fund_compensation_pot_callback(create_ctx(owner, 4), create_callback_ctx(true), state, 500);
+    assert_eq!(state.compensation_pot, 500);
+
+    let state = bid_on(state, 2000);
+    let (state, _) = cancel(create_ctx(owner, 101), state);
+    assert_eq!(state.compensation_pot, 0);
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        2100
+    );
+    assert_eq!(
+        *state.claim_map.get(&owner).unwrap(),
+        TokenClaim {
+            tokens_for_bidding: 400,
+            tokens_for_sale: 100_000,
+            credited_at_millis: 101 * 3_600_000,
+        }
+    );
+}
+
+#[test]
+pub fn test_cancel_compensation_is_limited_by_pot() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let state = compensated_auction(CompensationSource::Pot {});
+    let (state, _) =
+        fund_compensation_pot_callback(create_ctx(owner, 4), create_callback_ctx(true), state, 30);
+    let state = bid_on(state, 2000);
+    let (state, _) = cancel(create_ctx(owner, 101), state);
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        2030
+    );
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_bidding, 0);
+}
+
+#[test]
+pub fn test_execute_returns_compensation_pot() {
+    let owner = get_owner_address();
+    let state = compensated_auction(CompensationSource::Pot {});
+    let (state, _) =
+        fund_compensation_pot_callback(create_ctx(owner, 4), create_callback_ctx(true), state, 500);
+    let state = bid_on(state, 2000);
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    assert_eq!(state.status, ENDED);
+    assert_eq!(state.compensation_pot, 0);
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_bidding,
+        2500
+    );
+}
+
+#[test]
+pub fn test_cancel_without_bid_pays_no_compensation() {
+    let owner = get_owner_address();
+    let state = compensated_auction(CompensationSource::SaleTokens {});
+    let (state, _) = cancel(create_ctx(owner, 101), state);
+    assert_eq!(state.claim_map.len(), 1);
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_sale,
+        100_000
+    );
+}
+
+#[test]
+#[should_panic(expected = "The auction does not compensate the highest bidder from a pot")]
+pub fn test_fund_compensation_pot_without_pot() {
+    let state = compensated_auction(CompensationSource::SaleTokens {});
+    fund_compensation_pot(create_ctx(get_owner_address(), 4), state, 500);
+}
+
+#[test]
+#[should_panic(expected = "The cancel compensation cannot exceed 1000 per mille")]
+pub fn test_initialize_cancel_compensation_exceeds_bid() {
+    initialize(
+        create_ctx(get_owner_address(), 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        Some(CancelCompensation {
+            per_mille: 1001,
+            source: CompensationSource::Pot {},
+        }),
+        None,
+        None,
+        None,
+    );
+}
+
+fn get_deposit_token_address() -> Address {
+    Address {
+        address_type: AddressType::PublicContract,
+        identifier: [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8],
+    }
+}
+
+fn deposit_auction_with_bid() -> AuctionContractState {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        SettlementMode::FirstPrice {},
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(BidDeposit {
+            token: get_deposit_token_address(),
+            amount: 50,
+            completion_grace_millis: 3_600_000,
+        }),
+        None,
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    let (state, _) = bid_with_deposit_callback(
+        create_ctx(bidder, 5),
+        bid_and_deposit_callback_ctx(true, true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+        50,
+    );
+    state
+}
+
+fn bid_and_deposit_callback_ctx(bid_succeeded: bool, deposit_succeeded: bool) -> CallbackContext {
+    CallbackContext {
+        success: bid_succeeded && deposit_succeeded,
+        results: vec![
+            ExecutionResult {
+                succeeded: bid_succeeded,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: deposit_succeeded,
+                return_data: vec![],
+            },
+        ],
+    }
+}
+
+#[test]
+pub fn test_bid_without_transferred_deposit_is_claimable() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let (state, _) = bid_with_deposit_callback(
+        create_ctx(third_party, 6),
+        bid_and_deposit_callback_ctx(true, false),
+        deposit_auction_with_bid(),
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 3000,
+        },
+        50,
+    );
+    assert_eq!(state.highest_bidder.bidder, bidder);
+    assert_eq!(state.deposits.get(&third_party), None);
+    assert_eq!(
+        state
+            .claim_map
+            .get(&third_party)
+            .unwrap()
+            .tokens_for_bidding,
+        3000
+    );
+}
+
+#[test]
+pub fn test_deposit_without_transferred_bid_is_held() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let (state, _) = bid_with_deposit_callback(
+        create_ctx(third_party, 6),
+        bid_and_deposit_callback_ctx(false, true),
+        deposit_auction_with_bid(),
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 3000,
+        },
+        50,
+    );
+    assert_eq!(state.highest_bidder.bidder, bidder);
+    assert_eq!(state.deposits.get(&third_party), Some(&50));
+    assert_eq!(state.claim_map.get(&third_party), None);
+}
+
+#[test]
+#[should_panic(expected = "Transfer event did not succeed for bid")]
+pub fn test_bid_with_deposit_fails_if_no_transfer_succeeded() {
+    let third_party = get_third_party_address();
+    bid_with_deposit_callback(
+        create_ctx(third_party, 6),
+        bid_and_deposit_callback_ctx(false, false),
+        deposit_auction_with_bid(),
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 3000,
+        },
+        50,
+    );
+}
+
+#[test]
+pub fn test_first_bid_locks_deposit() {
+    let bidder = get_bidder_address();
+    let third_party = get_third_party_address();
+    let state = deposit_auction_with_bid();
+    assert_eq!(state.deposits.get(&bidder), Some(&50));
+    assert_eq!(state.highest_bidder.amount, 2000);
+
+    let (_, events) = bid(create_ctx(third_party, 6), state.clone(), 3000);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(3))
+        .argument(third_party)
+        .argument(get_contract_address())
+        .argument(3000u128)
+        .done();
+    expected_event
+        .call(get_deposit_token_address(), Shortname::from_u32(3))
+        .argument(third_party)
+        .argument(get_contract_address())
+        .argument(50u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x17))
+        .argument(third_party)
+        .argument(third_party)
+        .argument(3000u128)
+        .argument(50u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    // the deposit is only locked with the first bid
+    let (_, events) = bid(create_ctx(bidder, 6), state, 3000);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(3))
+        .argument(bidder)
+        .argument(get_contract_address())
+        .argument(3000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(4))
+        .argument(bidder)
+        .argument(bidder)
+        .argument(3000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+pub fn test_complete_settles_and_refunds_deposit() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (state, _) = execute(
+        create_ctx(get_third_party_address(), 102),
+        deposit_auction_with_bid(),
+    );
+    assert_eq!(state.status, AWAITING_COMPLETION);
+    assert_eq!(state.completion_deadline_millis, Some(103 * 3_600_000));
+    assert_eq!(state.claim_map.get(&bidder), None);
+    // the deposit of the highest bidder is held until the auction is completed
+    let (state, events) = claim(create_ctx(bidder, 102), state);
+    assert_eq!(events.len(), 0);
+
+    let (state, _) = complete(create_ctx(bidder, 102), state);
+    assert_eq!(state.status, ENDED);
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_bidding,
+        2000
+    );
+    let (state, events) = claim(create_ctx(bidder, 103), state);
+    assert_eq!(state.deposits.len(), 0);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_commodity_token_address(), Shortname::from_u32(1))
+        .argument(bidder)
+        .argument(100_000u128)
+        .done();
+    expected_event
+        .call(get_deposit_token_address(), Shortname::from_u32(1))
+        .argument(bidder)
+        .argument(50u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x20))
+        .argument(bidder)
+        .argument(0u128)
+        .argument(100_000u128)
+        .argument(50u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+pub fn test_forfeit_deposit_voids_sale() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (state, _) = execute(
+        create_ctx(get_third_party_address(), 102),
+        deposit_auction_with_bid(),
+    );
+    let (state, _) = forfeit_deposit(create_ctx(get_third_party_address(), 103), state);
+    assert_eq!(state.status, RESERVE_NOT_MET);
+    assert_eq!(state.deposits.get(&bidder), None);
+    assert_eq!(state.deposits.get(&owner), Some(&50));
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        2000
+    );
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_sale,
+        100_000
+    );
+}
+
+#[test]
+pub fn test_cancel_refunds_deposit() {
+    let owner = get_owner_address();
+    let state = deposit_auction_with_bid();
+    let (state, _) = cancel(create_ctx(owner, 101), state);
+    assert_eq!(state.status, CANCELLED);
+    // the deposit is refunded along with the bid once the auction is cancelled
+    let (state, events) = claim(create_ctx(get_bidder_address(), 101), state);
+    assert_eq!(state.deposits.len(), 0);
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Tried to complete the auction after the completion deadline")]
+pub fn test_complete_after_deadline() {
+    let (state, _) = execute(
+        create_ctx(get_third_party_address(), 102),
+        deposit_auction_with_bid(),
+    );
+    complete(create_ctx(get_bidder_address(), 103), state);
+}
+
+#[test]
+#[should_panic(expected = "Only the highest bidder can complete the auction")]
+pub fn test_complete_not_highest_bidder() {
+    let (state, _) = execute(
+        create_ctx(get_third_party_address(), 102),
+        deposit_auction_with_bid(),
+    );
+    complete(create_ctx(get_third_party_address(), 102), state);
+}
+
+#[test]
+#[should_panic(expected = "Tried to forfeit the deposit before the completion deadline")]
+pub fn test_forfeit_deposit_before_deadline() {
+    let (state, _) = execute(
+        create_ctx(get_third_party_address(), 102),
+        deposit_auction_with_bid(),
+    );
+    forfeit_deposit(create_ctx(get_third_party_address(), 102), state);
+}
+
+fn installment_auction(settlement: SettlementMode) -> AuctionContractState {
+    let owner = get_owner_address();
+    let (init_state, _) = initialize(
+        create_ctx(owner, 2),
+        100_000,
+        get_commodity_token_address(),
+        get_currency_token_address(),
+        1_000,
+        100,
+        100,
+        AuctionMode::SingleWinner {},
+        vec![],
+        None,
+        settlement,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(InstallmentPlan {
+            upfront_per_mille: 200,
+            installments: 4,
+            interval_millis: 3_600_000,
+        }),
+        None,
+    );
+    let (state, _) = start_callback(create_ctx(owner, 3), create_callback_ctx(true), init_state);
+    state
+}
+
+/// An auction paid in four installments after an up-front payment of 20%, executed with a winning
+/// bid of 2000.
+fn executed_installment_auction() -> AuctionContractState {
+    let bidder = get_bidder_address();
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        installment_auction(SettlementMode::FirstPrice {}),
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+    );
+    let (state, _) = execute(create_ctx(get_third_party_address(), 102), state);
+    state
+}
+
+#[test]
+pub fn test_installment_bid_escrows_upfront_payment() {
+    let bidder = get_bidder_address();
+    let state = installment_auction(SettlementMode::FirstPrice {});
+    let (_, events) = bid(create_ctx(bidder, 5), state.clone(), 2000);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(3))
+        .argument(bidder)
+        .argument(get_contract_address())
+        .argument(400u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(4))
+        .argument(bidder)
+        .argument(bidder)
+        .argument(2000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    // an outbid bidder gets their up-front payment back
+    let (state, _) = bid_callback(
+        create_ctx(bidder, 5),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 2000,
+        },
+    );
+    let third_party = get_third_party_address();
+    let (state, _) = bid_callback(
+        create_ctx(third_party, 6),
+        create_callback_ctx(true),
+        state,
+        Bid {
+            bidder: third_party,
+            beneficiary: third_party,
+            amount: 3000,
+        },
+    );
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        400
+    );
+}
+
+#[test]
+pub fn test_installments_vest_tokens_for_sale() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let mut state = executed_installment_auction();
+    assert_eq!(state.status, PAYING_INSTALLMENTS);
+    assert_eq!(state.next_installment_due_millis, Some(103 * 3_600_000));
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_bidding, 400);
+    assert_eq!(state.claim_map.get(&bidder), None);
+
+    let (_, events) = pay_installment(create_ctx(bidder, 102), state.clone());
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_currency_token_address(), Shortname::from_u32(3))
+        .argument(bidder)
+        .argument(get_contract_address())
+        .argument(400u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x1B))
+        .argument(0u32)
+        .argument(400u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    for index in 0..4 {
+        (state, _) = pay_installment_callback(
+            create_ctx(bidder, 102 + index as i64),
+            create_callback_ctx(true),
+            state,
+            index,
+            400,
+        );
+        assert_eq!(
+            state.claim_map.get(&bidder).unwrap().tokens_for_sale,
+            25_000 * (index as u128 + 1)
+        );
+    }
+    assert_eq!(state.status, ENDED);
+    assert_eq!(state.next_installment_due_millis, None);
+    assert_eq!(
+        state.claim_map.get(&owner).unwrap().tokens_for_bidding,
+        2000
+    );
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_sale, 0);
+}
+
+#[test]
+pub fn test_repeated_installment_is_claimable() {
+    let bidder = get_bidder_address();
+    let (state, _) = pay_installment_callback(
+        create_ctx(bidder, 102),
+        create_callback_ctx(true),
+        executed_installment_auction(),
+        0,
+        400,
+    );
+    let (state, _) = pay_installment_callback(
+        create_ctx(bidder, 102),
+        create_callback_ctx(true),
+        state,
+        0,
+        400,
+    );
+    assert_eq!(state.installments_paid, 1);
+    assert_eq!(
+        state.claim_map.get(&bidder),
+        Some(&TokenClaim {
+            tokens_for_bidding: 400,
+            tokens_for_sale: 25_000,
+            credited_at_millis: 102 * 3_600_000,
+        })
+    );
+}
+
+#[test]
+pub fn test_reclaim_unvested_after_missed_installment() {
+    let owner = get_owner_address();
+    let bidder = get_bidder_address();
+    let (state, _) = pay_installment_callback(
+        create_ctx(bidder, 102),
+        create_callback_ctx(true),
+        executed_installment_auction(),
+        0,
+        400,
+    );
+    assert_eq!(state.next_installment_due_millis, Some(104 * 3_600_000));
+    let (state, _) = reclaim_unvested(create_ctx(owner, 104), state);
+    assert_eq!(state.status, ENDED);
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_bidding, 800);
+    assert_eq!(state.claim_map.get(&owner).unwrap().tokens_for_sale, 75_000);
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_sale,
+        25_000
+    );
+}
+
+#[test]
+#[should_panic(expected = "Tried to reclaim the unvested tokens before an installment was missed")]
+pub fn test_reclaim_unvested_before_due() {
+    reclaim_unvested(
+        create_ctx(get_owner_address(), 102),
+        executed_installment_auction(),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Tried to pay an installment after it was due")]
+pub fn test_pay_installment_after_due() {
+    pay_installment(
+        create_ctx(get_bidder_address(), 103),
+        executed_installment_auction(),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only the highest bidder can pay installments")]
+pub fn test_pay_installment_not_highest_bidder() {
+    pay_installment(
+        create_ctx(get_third_party_address(), 102),
+        executed_installment_auction(),
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "Installments are only supported in the single winner mode at the first price"
+)]
+pub fn test_initialize_installments_second_price() {
+    installment_auction(SettlementMode::SecondPrice {});
+}
+
+fn get_displaced_bidder_address(id: u16) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[0] = 0x0d;
+    identifier[18..].copy_from_slice(&id.to_be_bytes());
+    Address {
+        address_type: AddressType::Account,
+        identifier,
+    }
+}
+
+/// An auction in which `bidders` bidders have each been outbid by the next, such that every bid
+/// but the last has been added to the claim map.
+fn auction_with_displaced_bidders(bidders: u16) -> AuctionContractState {
+    let (init_state, _) = initialize_contract();
+    let (mut state, _) = start_callback(
+        create_ctx(get_owner_address(), 3),
+        create_callback_ctx(true),
+        init_state,
+    );
+    for id in 0..bidders {
+        let bidder = get_displaced_bidder_address(id);
+        let bid = Bid {
+            bidder,
+            beneficiary: bidder,
+            amount: 1_000 + 100 * id as u128,
+        };
+        (state, _) = bid_callback(create_ctx(bidder, 4), create_callback_ctx(true), state, bid);
+    }
+    state
+}
+
+#[test]
+pub fn test_claim_range_pays_out_large_population() {
+    let state = auction_with_displaced_bidders(1_000);
+    // The owner holds the initial empty bid at nonce 0, followed by the displaced bidders
+    assert_eq!(state.claimants.len(), 1_000);
+    assert_eq!(
+        state.claimant_nonces.get(&get_displaced_bidder_address(0)),
+        Some(&1)
+    );
+
+    let mut state = state;
+    let mut paid_out = 0;
+    for page in 0..20 {
+        let events;
+        (state, events) = claim_range(
+            create_ctx(get_third_party_address(), 5),
+            state,
+            page * 50,
+            50,
+        );
+        if page == 0 {
+            let mut expected_event = EventGroup::builder();
+            expected_event
+                .call(get_currency_token_address(), Shortname::from_u32(1))
+                .argument(get_displaced_bidder_address(0))
+                .argument(1_000u128)
+                .done();
+            expected_event
+                .with_callback(ShortnameCallback::from_u32(0x20))
+                .argument(get_displaced_bidder_address(0))
+                .argument(1000u128)
+                .argument(0u128)
+                .argument(0u128)
+                .done();
+            assert_eq!(events[0], expected_event.build());
+        }
+        paid_out += events.len();
+    }
+    assert_eq!(paid_out, 999);
+    assert!(state
+        .claim_map
+        .values()
+        .all(|claim| claim.tokens_for_bidding == 0));
+    // The paid out claimants, and the owner without anything to claim, are removed
+    assert!(state.claim_map.is_empty());
+    assert!(state.claimants.is_empty());
+    assert!(state.claimant_nonces.is_empty());
+    let highest_bidder = get_displaced_bidder_address(999);
+    assert_eq!(state.highest_bidder.bidder, highest_bidder);
+    assert!(!state.claimant_nonces.contains_key(&highest_bidder));
+}
+
+#[test]
+pub fn test_claim_range_skips_paid_out_claimants() {
+    let state = auction_with_displaced_bidders(10);
+    let (state, events) = claim_range(create_ctx(get_third_party_address(), 5), state, 3, 4);
+    assert_eq!(events.len(), 4);
+    assert_eq!(
+        state
+            .claim_map
+            .get(&get_displaced_bidder_address(1))
+            .unwrap()
+            .tokens_for_bidding,
+        1_100
+    );
+
+    let (_, events) = claim_range(create_ctx(get_third_party_address(), 5), state, 0, 50);
+    assert_eq!(events.len(), 5);
+}
+
+#[test]
+pub fn test_failed_claim_registers_claimant_again() {
+    let state = auction_with_displaced_bidders(3);
+    let bidder = get_displaced_bidder_address(0);
+    let (state, _) = claim(create_ctx(bidder, 5), state);
+    assert!(!state.claimant_nonces.contains_key(&bidder));
+    assert_eq!(state.claimants.len(), 2);
+
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 5),
+        create_callback_ctx(false),
+        state,
+        bidder,
+        1_000,
+        0,
+        0,
+    );
+    // The nonce of a removed claimant is not reused
+    assert_eq!(state.claimant_nonces.get(&bidder), Some(&3));
+    assert_eq!(state.claimants.get(&3), Some(&bidder));
+    assert_eq!(
+        state.claim_map.get(&bidder).unwrap().tokens_for_bidding,
+        1_000
+    );
+
+    let (state, events) = claim_range(create_ctx(get_third_party_address(), 5), state, 3, 1);
+    assert_eq!(events.len(), 1);
+    assert!(!state.claimant_nonces.contains_key(&bidder));
+}
+
+#[test]
+#[should_panic(expected = "Cannot claim for more than 50 claimants at a time")]
+pub fn test_claim_range_too_many_claimants() {
+    let state = auction_with_displaced_bidders(10);
+    claim_range(create_ctx(get_third_party_address(), 5), state, 0, 51);
+}
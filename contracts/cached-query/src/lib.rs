@@ -0,0 +1,201 @@
+//! Helpers for contracts reading the state of other contracts.
+//!
+//! A contract cannot read the state of another contract directly. Instead it calls an action on
+//! the remote contract, which responds using return data, and reads the response in a callback.
+//! [`CachedQuery`] implements this query-and-callback pattern and caches the response in the
+//! state of the querying contract, together with the time it was fetched.
+//! A cached response is considered fresh for `ttl_millis` milliseconds, after which it is stale
+//! and must be queried again before being used.
+//!
+//! While a query is pending no further queries are sent. The pending query is cleared when its
+//! response is handled, whether the query succeeded or failed. If the callback never handles the
+//! response, for instance because it panics and its state changes are discarded, the pending
+//! query expires after `ttl_millis` milliseconds, after which the remote contract can be queried
+//! again.
+//!
+//! A typical usage is:
+//!
+//! 1. An action calls [`CachedQuery::query_if_stale`] and returns the resulting event groups.
+//! 2. The callback calls [`CachedQuery::handle_response`] to store the response.
+//! 3. Actions depending on the remote state use [`CachedQuery::expect_fresh`].
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, Shortname, ShortnameCallback};
+use pbc_contract_common::context::CallbackContext;
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::ReadRPC;
+use read_write_state_derive::ReadWriteState;
+
+mod tests;
+
+/// A response received from a remote contract.
+///
+/// ### Fields:
+///
+/// * `value`: [`T`], the value returned by the remote contract.
+///
+/// * `fetched_at_millis`: [`i64`], the block production time at which the value was received.
+#[derive(ReadWriteState, CreateTypeSpec, Clone, PartialEq, Eq, Debug)]
+pub struct CachedResponse<T> {
+    pub value: T,
+    pub fetched_at_millis: i64,
+}
+
+/// A cached query of a remote contract.
+///
+/// ### Fields:
+///
+/// * `remote`: [`Address`], the address of the queried contract.
+///
+/// * `query_shortname`: [`u32`], the shortname of the action answering the query.
+///
+/// * `ttl_millis`: [`i64`], the number of milliseconds a response stays fresh.
+///
+/// * `response`: [`Option<CachedResponse<T>>`], the latest response, if any.
+///
+/// * `pending_since_millis`: [`Option<i64>`], the block production time at which a query was sent
+///   without its response being handled, if any.
+#[derive(ReadWriteState, CreateTypeSpec, Clone, PartialEq, Eq, Debug)]
+pub struct CachedQuery<T> {
+    pub remote: Address,
+    pub query_shortname: u32,
+    pub ttl_millis: i64,
+    pub response: Option<CachedResponse<T>>,
+    pub pending_since_millis: Option<i64>,
+}
+
+impl<T: ReadRPC> CachedQuery<T> {
+    /// Creates a new cached query without any response.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `remote`: [`Address`], the address of the queried contract.
+    ///
+    /// * `query_shortname`: [`Shortname`], the shortname of the action answering the query.
+    ///
+    /// * `ttl_millis`: [`i64`], the number of milliseconds a response stays fresh.
+    pub fn new(remote: Address, query_shortname: Shortname, ttl_millis: i64) -> Self {
+        if ttl_millis < 0 {
+            panic!("The time to live of a cached query cannot be negative");
+        }
+        CachedQuery {
+            remote,
+            query_shortname: query_shortname.as_u32(),
+            ttl_millis,
+            response: None,
+            pending_since_millis: None,
+        }
+    }
+
+    /// Returns whether a query is pending at `now_millis`, which is the case if a query has been
+    /// sent without its response being handled, and it has not yet expired.
+    pub fn is_query_pending(&self, now_millis: i64) -> bool {
+        match self.pending_since_millis {
+            Some(sent_at_millis) => now_millis - sent_at_millis <= self.ttl_millis,
+            None => false,
+        }
+    }
+
+    /// Returns whether a response exists which is still fresh at `now_millis`.
+    pub fn is_fresh(&self, now_millis: i64) -> bool {
+        match &self.response {
+            Some(response) => now_millis - response.fetched_at_millis <= self.ttl_millis,
+            None => false,
+        }
+    }
+
+    /// Returns the cached value if it is still fresh at `now_millis`.
+    pub fn fresh_value(&self, now_millis: i64) -> Option<&T> {
+        if self.is_fresh(now_millis) {
+            self.response.as_ref().map(|response| &response.value)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the cached value, panicking if it is missing or stale at `now_millis`.
+    pub fn expect_fresh(&self, now_millis: i64) -> &T {
+        match self.fresh_value(now_millis) {
+            Some(value) => value,
+            None => panic!("Cached response from remote contract is missing or stale"),
+        }
+    }
+
+    /// Creates the event group querying the remote contract if the cached value is stale at
+    /// `now_millis` and no query is pending, see [`CachedQuery::is_query_pending`].
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now_millis`: [`i64`], the current block production time.
+    ///
+    /// * `callback`: [`ShortnameCallback`], the callback of the querying contract, which must
+    ///   call [`CachedQuery::handle_response`].
+    ///
+    /// ### Returns
+    ///
+    /// The event groups to send, which are empty if no query is needed.
+    pub fn query_if_stale(
+        &mut self,
+        now_millis: i64,
+        callback: ShortnameCallback,
+    ) -> Vec<EventGroup> {
+        if self.is_fresh(now_millis) || self.is_query_pending(now_millis) {
+            return vec![];
+        }
+        vec![self.query(now_millis, callback)]
+    }
+
+    /// Creates the event group querying the remote contract, regardless of the cached value.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now_millis`: [`i64`], the current block production time.
+    ///
+    /// * `callback`: [`ShortnameCallback`], the callback of the querying contract, which must
+    ///   call [`CachedQuery::handle_response`].
+    ///
+    /// ### Returns
+    ///
+    /// The event group containing the query and the callback.
+    pub fn query(&mut self, now_millis: i64, callback: ShortnameCallback) -> EventGroup {
+        self.pending_since_millis = Some(now_millis);
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(self.remote, Shortname::from_u32(self.query_shortname))
+            .done();
+        event_group.with_callback(callback).done();
+        event_group.build()
+    }
+
+    /// Stores the response of a query. The response is read from the return data of the first
+    /// result of the callback context. If the query failed the cached value is left unchanged.
+    /// The pending query is cleared in either case.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `callback_ctx`: [`&CallbackContext`], the context of the query callback.
+    ///
+    /// * `now_millis`: [`i64`], the current block production time.
+    ///
+    /// ### Returns
+    ///
+    /// Whether the cached value was updated.
+    pub fn handle_response(&mut self, callback_ctx: &CallbackContext, now_millis: i64) -> bool {
+        self.pending_since_millis = None;
+        let result = match callback_ctx.results.first() {
+            Some(result) if callback_ctx.success && result.succeeded => result,
+            _ => return false,
+        };
+        let value = T::rpc_read_from(&mut result.return_data.as_slice());
+        self.response = Some(CachedResponse {
+            value,
+            fetched_at_millis: now_millis,
+        });
+        true
+    }
+
+    /// Discards the cached value, forcing the next query.
+    pub fn invalidate(&mut self) {
+        self.response = None;
+    }
+}
@@ -0,0 +1,136 @@
+#![cfg(test)]
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::WriteRPC;
+
+use crate::CachedQuery;
+
+const TTL_MILLIS: i64 = 1000;
+
+fn get_remote_address() -> Address {
+    Address {
+        address_type: AddressType::PublicContract,
+        identifier: [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+    }
+}
+
+fn query_shortname() -> Shortname {
+    Shortname::from_u32(0x10)
+}
+
+fn callback_shortname() -> ShortnameCallback {
+    ShortnameCallback::from_u32(0x11)
+}
+
+fn create_callback_ctx(success: bool, value: u128) -> CallbackContext {
+    let mut return_data = vec![];
+    value.rpc_write_to(&mut return_data).unwrap();
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data,
+        }],
+    }
+}
+
+fn create_query() -> CachedQuery<u128> {
+    CachedQuery::new(get_remote_address(), query_shortname(), TTL_MILLIS)
+}
+
+#[test]
+pub fn test_query_when_empty() {
+    let mut query = create_query();
+    let events = query.query_if_stale(0, callback_shortname());
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(get_remote_address(), query_shortname())
+        .done();
+    expected.with_callback(callback_shortname()).done();
+    assert_eq!(events, vec![expected.build()]);
+    assert!(query.is_query_pending(0));
+    assert_eq!(query.fresh_value(0), None);
+}
+
+#[test]
+pub fn test_no_query_while_pending() {
+    let mut query = create_query();
+    query.query_if_stale(0, callback_shortname());
+    let events = query.query_if_stale(10, callback_shortname());
+    assert_eq!(events.len(), 0);
+}
+
+#[test]
+pub fn test_pending_query_expires() {
+    let mut query = create_query();
+    query.query_if_stale(0, callback_shortname());
+    assert!(query.is_query_pending(TTL_MILLIS));
+    assert!(!query.is_query_pending(TTL_MILLIS + 1));
+    let events = query.query_if_stale(TTL_MILLIS + 1, callback_shortname());
+    assert_eq!(events.len(), 1);
+    assert_eq!(query.pending_since_millis, Some(TTL_MILLIS + 1));
+}
+
+#[test]
+pub fn test_failed_query_clears_pending_query() {
+    let mut query = create_query();
+    query.query_if_stale(0, callback_shortname());
+    assert!(!query.handle_response(&create_callback_ctx(false, 7), 10));
+    assert_eq!(query.pending_since_millis, None);
+    assert_eq!(query.query_if_stale(10, callback_shortname()).len(), 1);
+}
+
+#[test]
+pub fn test_response_is_cached_until_expiry() {
+    let mut query = create_query();
+    query.query_if_stale(0, callback_shortname());
+    assert!(query.handle_response(&create_callback_ctx(true, 42), 100));
+
+    assert!(!query.is_query_pending(100));
+    assert_eq!(query.fresh_value(100 + TTL_MILLIS), Some(&42));
+    assert_eq!(*query.expect_fresh(100), 42);
+    assert_eq!(
+        query
+            .query_if_stale(100 + TTL_MILLIS, callback_shortname())
+            .len(),
+        0
+    );
+
+    assert_eq!(query.fresh_value(101 + TTL_MILLIS), None);
+    assert_eq!(
+        query
+            .query_if_stale(101 + TTL_MILLIS, callback_shortname())
+            .len(),
+        1
+    );
+}
+
+#[test]
+pub fn test_failed_response_keeps_cached_value() {
+    let mut query = create_query();
+    query.query_if_stale(0, callback_shortname());
+    query.handle_response(&create_callback_ctx(true, 42), 0);
+
+    query.query(10, callback_shortname());
+    assert!(!query.handle_response(&create_callback_ctx(false, 7), 10));
+    assert!(!query.is_query_pending(10));
+    assert_eq!(query.fresh_value(10), Some(&42));
+}
+
+#[test]
+pub fn test_invalidate() {
+    let mut query = create_query();
+    query.handle_response(&create_callback_ctx(true, 42), 0);
+    query.invalidate();
+    assert_eq!(query.fresh_value(0), None);
+}
+
+#[test]
+#[should_panic(expected = "Cached response from remote contract is missing or stale")]
+pub fn test_expect_fresh_stale() {
+    let mut query = create_query();
+    query.handle_response(&create_callback_ctx(true, 42), 0);
+    query.expect_fresh(TTL_MILLIS + 1);
+}
@@ -0,0 +1,230 @@
+//! Library implementing the "pull payment" claim-map pattern shared by contracts.
+//!
+//! Instead of transferring tokens to users as part of other actions, contracts credit the tokens
+//! to a claim map, from which each user later claims their tokens. The claim map is a
+//! [`BTreeMap<Address, C>`] where `C` implements [`Claim`], and the operations are provided by
+//! the [`ClaimMap`] trait:
+//!
+//! * `credit` merges an additional claim into the claim of an address.
+//! * `take_claim` zeroes the claim of an address, returning what could be claimed.
+//! * `restore_if_failed` credits the payouts of a taken claim whose transfers failed back to the
+//!   claimant, which is used in the callback of a payout. Payouts whose transfers succeeded are
+//!   not credited again.
+//! * `sweep` removes the claims matching a predicate, such as claims that have expired.
+//!
+//! [`payout_event_group`] creates the transfer events paying out a taken claim, and
+//! [`failed_payouts`] finds the payouts whose transfers failed in the callback of the payout.
+
+use std::collections::BTreeMap;
+
+use pbc_contract_common::address::{Address, Shortname};
+use pbc_contract_common::context::CallbackContext;
+use pbc_contract_common::events::{EventGroup, EventGroupBuilder};
+
+mod tests;
+
+/// A claim that can be stored in a claim map.
+pub trait Claim {
+    /// Creates a claim of nothing.
+    fn empty() -> Self;
+
+    /// Adds `other` to this claim.
+    fn merge(&mut self, other: Self);
+
+    /// Returns whether nothing can be claimed.
+    fn is_empty(&self) -> bool;
+}
+
+impl Claim for u128 {
+    fn empty() -> Self {
+        0
+    }
+
+    fn merge(&mut self, other: Self) {
+        *self += other;
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == 0
+    }
+}
+
+/// Operations on a claim map of claims of type `C`.
+pub trait ClaimMap<C: Claim> {
+    /// Adds `claim` to the claim of `claimant`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    /// * `claim`: The additional claim that the `claimant` can claim.
+    fn credit(&mut self, claimant: Address, claim: C);
+
+    /// Takes the claim of `claimant`, leaving an empty claim in its place.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    /// ### Returns
+    ///
+    /// The claim of the `claimant`, or `None` if the `claimant` has no entry in the map.
+    fn take_claim(&mut self, claimant: &Address) -> Option<C>;
+
+    /// Credits the payouts of a taken claim whose transfers failed back to `claimant`, see
+    /// [`failed_payouts`].
+    ///
+    /// ### Parameters:
+    ///
+    /// * `callback_ctx`: [`&CallbackContext`], the context of the payout callback.
+    ///
+    /// * `claimant`: The [`Address`] of the claimant.
+    ///
+    /// * `payouts`: [`&[Payout]`], the payouts that were passed to [`payout_event_group`].
+    ///
+    /// * `claim_of`: Creates the claim of the amount of a failed payout, given the index of the
+    ///   payout in `payouts` and the amount.
+    ///
+    /// ### Returns
+    ///
+    /// Whether anything was restored.
+    fn restore_if_failed<F: FnMut(usize, u128) -> C>(
+        &mut self,
+        callback_ctx: &CallbackContext,
+        claimant: Address,
+        payouts: &[Payout],
+        claim_of: F,
+    ) -> bool;
+
+    /// Removes all claims matching `predicate`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `predicate`: Decides whether the claim of an address is removed.
+    ///
+    /// ### Returns
+    ///
+    /// The removed non-empty claims, ordered by address.
+    fn sweep<F: FnMut(&Address, &C) -> bool>(&mut self, predicate: F) -> Vec<(Address, C)>;
+}
+
+impl<C: Claim> ClaimMap<C> for BTreeMap<Address, C> {
+    fn credit(&mut self, claimant: Address, claim: C) {
+        self.entry(claimant).or_insert_with(C::empty).merge(claim);
+    }
+
+    fn take_claim(&mut self, claimant: &Address) -> Option<C> {
+        self.get_mut(claimant)
+            .map(|claim| std::mem::replace(claim, C::empty()))
+    }
+
+    fn restore_if_failed<F: FnMut(usize, u128) -> C>(
+        &mut self,
+        callback_ctx: &CallbackContext,
+        claimant: Address,
+        payouts: &[Payout],
+        mut claim_of: F,
+    ) -> bool {
+        let mut restored = C::empty();
+        for index in failed_payouts(callback_ctx, payouts) {
+            restored.merge(claim_of(index, payouts[index].amount));
+        }
+        if restored.is_empty() {
+            return false;
+        }
+        self.credit(claimant, restored);
+        true
+    }
+
+    fn sweep<F: FnMut(&Address, &C) -> bool>(&mut self, mut predicate: F) -> Vec<(Address, C)> {
+        let swept: Vec<Address> = self
+            .iter()
+            .filter(|(claimant, claim)| predicate(claimant, claim))
+            .map(|(claimant, _)| *claimant)
+            .collect();
+        swept
+            .into_iter()
+            .filter_map(|claimant| {
+                let claim = self.remove(&claimant).unwrap();
+                if claim.is_empty() {
+                    None
+                } else {
+                    Some((claimant, claim))
+                }
+            })
+            .collect()
+    }
+}
+
+/// A single token transfer of a payout.
+///
+/// ### Fields:
+///
+/// * `token`: [`Address`], the address of the token contract.
+///
+/// * `amount`: [`u128`], the amount of tokens to transfer.
+pub struct Payout {
+    pub token: Address,
+    pub amount: u128,
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Creates an event group transferring the payouts of a taken claim to `recipient`. Payouts of
+/// zero tokens are skipped. A callback verifying the payout, by calling
+/// [`ClaimMap::restore_if_failed`] or [`failed_payouts`], should be added to the returned builder,
+/// as the tokens of a failed transfer are otherwise lost.
+///
+/// ### Parameters:
+///
+/// * `recipient`: [`Address`], the receiver of the tokens.
+///
+/// * `payouts`: [`&[Payout]`], the token transfers to make.
+///
+/// ### Returns
+///
+/// The [`EventGroupBuilder`] containing the transfer events.
+pub fn payout_event_group(recipient: Address, payouts: &[Payout]) -> EventGroupBuilder {
+    let mut event_group = EventGroup::builder();
+    for payout in payouts.iter().filter(|payout| payout.amount > 0) {
+        event_group
+            .call(payout.token, token_contract_transfer())
+            .argument(recipient)
+            .argument(payout.amount)
+            .done();
+    }
+    event_group
+}
+
+/// Finds the payouts whose transfers failed, given the context of the callback of the event group
+/// created by [`payout_event_group`]. The results of the callback are matched with the payouts of
+/// non-zero amounts, as payouts of zero tokens have no transfer. A payout without a result has
+/// failed if the event group as a whole failed.
+///
+/// ### Parameters:
+///
+/// * `callback_ctx`: [`&CallbackContext`], the context of the payout callback.
+///
+/// * `payouts`: [`&[Payout]`], the payouts that were passed to [`payout_event_group`].
+///
+/// ### Returns
+///
+/// The indices in `payouts` of the payouts that failed.
+pub fn failed_payouts(callback_ctx: &CallbackContext, payouts: &[Payout]) -> Vec<usize> {
+    let mut results = callback_ctx.results.iter();
+    payouts
+        .iter()
+        .enumerate()
+        .filter(|(_, payout)| payout.amount > 0)
+        .filter(|_| {
+            !results
+                .next()
+                .map_or(callback_ctx.success, |result| result.succeeded)
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
@@ -0,0 +1,232 @@
+#![cfg(test)]
+use std::collections::BTreeMap;
+
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+
+use crate::{failed_payouts, payout_event_group, Claim, ClaimMap, Payout};
+
+/// Claim of two kinds of tokens, as used by contracts trading one token for another.
+#[derive(PartialEq, Eq, Debug)]
+struct PairClaim {
+    first: u128,
+    second: u128,
+}
+
+impl Claim for PairClaim {
+    fn empty() -> Self {
+        PairClaim {
+            first: 0,
+            second: 0,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.first += other.first;
+        self.second += other.second;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.first == 0 && self.second == 0
+    }
+}
+
+fn get_address(id: u8) -> Address {
+    Address {
+        address_type: AddressType::Account,
+        identifier: [
+            0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, id,
+        ],
+    }
+}
+
+fn get_token_address(id: u8) -> Address {
+    Address {
+        address_type: AddressType::PublicContract,
+        identifier: [
+            1u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, id,
+        ],
+    }
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+#[test]
+pub fn test_credit_merges_claims() {
+    let mut claims: BTreeMap<Address, PairClaim> = BTreeMap::new();
+    claims.credit(
+        get_address(1),
+        PairClaim {
+            first: 10,
+            second: 0,
+        },
+    );
+    claims.credit(
+        get_address(1),
+        PairClaim {
+            first: 5,
+            second: 7,
+        },
+    );
+    assert_eq!(claims.len(), 1);
+    assert_eq!(
+        claims.get(&get_address(1)),
+        Some(&PairClaim {
+            first: 15,
+            second: 7
+        })
+    );
+}
+
+#[test]
+pub fn test_take_claim_zeroes_entry() {
+    let mut claims: BTreeMap<Address, u128> = BTreeMap::new();
+    claims.credit(get_address(1), 100);
+
+    assert_eq!(claims.take_claim(&get_address(1)), Some(100));
+    assert_eq!(claims.get(&get_address(1)), Some(&0));
+    assert_eq!(claims.take_claim(&get_address(1)), Some(0));
+    assert_eq!(claims.take_claim(&get_address(2)), None);
+}
+
+fn payouts(first: u128, second: u128) -> [Payout; 2] {
+    [
+        Payout {
+            token: get_token_address(1),
+            amount: first,
+        },
+        Payout {
+            token: get_token_address(2),
+            amount: second,
+        },
+    ]
+}
+
+fn pair_claim_of(index: usize, amount: u128) -> PairClaim {
+    match index {
+        0 => PairClaim {
+            first: amount,
+            second: 0,
+        },
+        _ => PairClaim {
+            first: 0,
+            second: amount,
+        },
+    }
+}
+
+fn create_results_ctx(results: &[bool]) -> CallbackContext {
+    CallbackContext {
+        success: results.iter().all(|succeeded| *succeeded),
+        results: results
+            .iter()
+            .map(|succeeded| ExecutionResult {
+                succeeded: *succeeded,
+                return_data: vec![],
+            })
+            .collect(),
+    }
+}
+
+#[test]
+pub fn test_restore_if_failed() {
+    let mut claims: BTreeMap<Address, u128> = BTreeMap::new();
+    claims.credit(get_address(1), 100);
+    let taken = claims.take_claim(&get_address(1)).unwrap();
+    let payouts = [Payout {
+        token: get_token_address(1),
+        amount: taken,
+    }];
+
+    assert!(!claims.restore_if_failed(
+        &create_callback_ctx(true),
+        get_address(1),
+        &payouts,
+        |_, amount| amount
+    ));
+    assert_eq!(claims.get(&get_address(1)), Some(&0));
+
+    assert!(claims.restore_if_failed(
+        &create_callback_ctx(false),
+        get_address(1),
+        &payouts,
+        |_, amount| amount
+    ));
+    assert_eq!(claims.get(&get_address(1)), Some(&100));
+}
+
+#[test]
+pub fn test_restore_if_failed_restores_only_failed_payouts() {
+    let mut claims: BTreeMap<Address, PairClaim> = BTreeMap::new();
+    assert!(claims.restore_if_failed(
+        &create_results_ctx(&[true, false]),
+        get_address(1),
+        &payouts(10, 20),
+        pair_claim_of
+    ));
+    assert_eq!(
+        claims.get(&get_address(1)),
+        Some(&PairClaim {
+            first: 0,
+            second: 20,
+        })
+    );
+}
+
+#[test]
+pub fn test_failed_payouts_skip_zero_amounts() {
+    // Only the second payout is transferred, so the only result belongs to it
+    let failed = failed_payouts(&create_results_ctx(&[false]), &payouts(0, 20));
+    assert_eq!(failed, vec![1]);
+
+    let failed = failed_payouts(&create_results_ctx(&[true]), &payouts(0, 20));
+    assert!(failed.is_empty());
+
+    let failed = failed_payouts(&create_results_ctx(&[false, false]), &payouts(10, 20));
+    assert_eq!(failed, vec![0, 1]);
+}
+
+#[test]
+pub fn test_sweep() {
+    let mut claims: BTreeMap<Address, u128> = BTreeMap::new();
+    claims.credit(get_address(1), 100);
+    claims.credit(get_address(2), 0);
+    claims.credit(get_address(3), 300);
+
+    let swept = claims.sweep(|claimant, _| *claimant != get_address(3));
+    assert_eq!(swept, vec![(get_address(1), 100)]);
+    assert_eq!(claims.len(), 1);
+    assert_eq!(claims.get(&get_address(3)), Some(&300));
+}
+
+#[test]
+pub fn test_payout_event_group() {
+    let payouts = [
+        Payout {
+            token: get_token_address(1),
+            amount: 100,
+        },
+        Payout {
+            token: get_token_address(2),
+            amount: 0,
+        },
+    ];
+    let event_group = payout_event_group(get_address(1), &payouts).build();
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(get_token_address(1), Shortname::from_u32(0x01))
+        .argument(get_address(1))
+        .argument(100u128)
+        .done();
+    assert_eq!(event_group, expected.build());
+}
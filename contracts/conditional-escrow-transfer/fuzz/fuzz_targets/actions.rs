@@ -0,0 +1,63 @@
+//! Fuzzes the conditional escrow transfer contract, invoking the generated entry points of
+//! `initialize` and of every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use conditional_escrow_transfer::{
+    __pbc_autogen__approve_wrapped, __pbc_autogen__cancel_by_mutual_consent_wrapped,
+    __pbc_autogen__claim_wrapped, __pbc_autogen__deposit_callback_wrapped,
+    __pbc_autogen__deposit_native_wrapped, __pbc_autogen__deposit_wrapped,
+    __pbc_autogen__export_receipt_wrapped, __pbc_autogen__extend_deadline_wrapped,
+    __pbc_autogen__flag_breach_wrapped, __pbc_autogen__initialize_wrapped,
+    __pbc_autogen__post_security_deposit_wrapped, __pbc_autogen__query_status_wrapped,
+    __pbc_autogen__release_installment_wrapped, __pbc_autogen__security_deposit_callback_wrapped,
+    __pbc_autogen__set_audit_contract_wrapped, __pbc_autogen__set_watchers_wrapped,
+    __pbc_autogen__trigger_refund_wrapped,
+};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+
+const CALLS: [Call; 16] = [
+    Call::Action(__pbc_autogen__deposit_wrapped),
+    Call::Action(__pbc_autogen__deposit_native_wrapped),
+    Call::Callback(__pbc_autogen__deposit_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__approve_wrapped),
+    Call::Action(__pbc_autogen__release_installment_wrapped),
+    Call::Action(__pbc_autogen__claim_wrapped),
+    Call::Action(__pbc_autogen__trigger_refund_wrapped),
+    Call::Action(__pbc_autogen__cancel_by_mutual_consent_wrapped),
+    Call::Action(__pbc_autogen__extend_deadline_wrapped),
+    Call::Action(__pbc_autogen__post_security_deposit_wrapped),
+    Call::Callback(__pbc_autogen__security_deposit_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__flag_breach_wrapped),
+    Call::Action(__pbc_autogen__query_status_wrapped),
+    Call::Action(__pbc_autogen__set_watchers_wrapped),
+    Call::Action(__pbc_autogen__set_audit_contract_wrapped),
+    Call::Action(__pbc_autogen__export_receipt_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
@@ -2,20 +2,245 @@
 //!
 //! Conditional Escrow Transfer allows a sender to put tokens into an escrow contract which a
 //! receiver can receive when a condition has been fulfilled.
-//! The escrow transfer contract handles a specific token type.
+//! The escrow transfer contract handles a primary token type, and optionally additional token
+//! types, such that a deal can be denominated in several assets. The sender can deposit any of the
+//! accepted tokens, the balance of each token is tracked separately, and claiming transfers every
+//! held token.
 //! A sender can place tokens into escrow specifying the receiver and an approver that signals
 //! condition fulfilment and a deadline.
 //! The approver can signal fulfilment of the condition. The condition itself is not part of the
-//! contract, only the signalling of the fulfilment of the condition.
+//! contract, only the signalling of the fulfilment of the condition. When approving, the approver
+//! attaches an attestation consisting of the hash of the evidence proving that the condition was
+//! fulfilled and a free-form note, such that off-chain systems can link the approval to the
+//! document proving the fulfilment.
 //! The receiver can claim the tokens when the condition has been fulfilled.
 //! The sender can claim the tokens when the deadline is met and the condition is not fulfilled.
+//! Before the deadline the sender and the receiver can cancel the escrow by mutual consent,
+//! without involving the approver. Either party proposes the cancellation by calling
+//! `cancel_by_mutual_consent`, and the other party confirms it by calling it as well, which
+//! returns the balance to the sender.
+//! Likewise, the sender and the receiver can extend the deadline by mutual agreement. Either
+//! party proposes a new deadline by calling `extend_deadline`, and the extension takes effect
+//! once the other party has proposed the same deadline.
+//! If the deadline passes without the condition being fulfilled, anyone can call `trigger_refund`
+//! to return the balance to the sender, such that the tokens are not stranded if the sender loses
+//! access to their account. The caller receives a bounty of a configurable per mille of the
+//! balance as an incentive, and the remainder is transferred to the sender.
+//!
+//! The escrow can instead be initialized as a recurring subscription, where the sender escrows a
+//! total budget of the primary token, and the approver releases a fixed installment of the budget
+//! at most once per period, counted from the start of the contract, until the deadline. Rather
+//! than approving the condition, each release signals that the service of the period was
+//! delivered. The receiver can claim the released installments at any time, and after the deadline
+//! the sender reclaims the remainder of the budget, while any installments not yet claimed are
+//! transferred to the receiver.
+//!
+//! The escrow can also be initialized as a reverse escrow, where the receiver must post a
+//! security deposit of the primary token with `post_security_deposit` before the condition can be
+//! approved. If the receiver fails to deliver, the approver can `flag_breach`, which forfeits the
+//! security deposit to the sender and lets the sender claim the escrowed tokens immediately.
+//! Otherwise the security deposit is returned to the receiver when the escrow is claimed, refunded
+//! or cancelled. The security deposit is kept apart from the balances of the escrowed tokens.
+//!
+//! The primary escrowed asset is either an MPC-20 token or the native MPC token of the chain, as
+//! chosen by the `asset_kind` at initialization. Native MPC tokens are deposited using
+//! `deposit_native`, which transfers the tokens from the sender through the MPC token system
+//! contract. The additional tokens are always MPC-20 tokens.
+//!
+//! Other contracts, such as an escrow factory, can call `query_status` to receive the status of
+//! the escrow as return data.
+//!
+//! The sender or the receiver can register watcher contracts with `set_watchers`. Every watcher is
+//! notified of each transition of the escrow, such as a confirmed deposit, an approval, a claim or
+//! a refund, such that marketplaces building on the escrow can react without polling its state.
+//! The notifications are sent without callbacks, so a failing watcher cannot block the escrow.
+//!
+//! Every transition is also appended to the history of the escrow, together with the address
+//! causing it, the amounts of the tokens it moved and its time. The history is never rewritten.
+//! For auditors, anyone can `export_receipt`, which sends the serialized history along with the
+//! parties and the status of the escrow to the audit contract configured by the sender or the
+//! receiver with `set_audit_contract`. The receipt is attested by its SHA-256 digest, and is sent
+//! by the escrow itself, such that the audit contract knows its origin.
 
 #[macro_use]
 extern crate pbc_contract_codegen;
 
+use create_type_spec_derive::CreateTypeSpec;
 use pbc_contract_common::address::{Address, AddressType, Shortname};
 use pbc_contract_common::context::{CallbackContext, ContractContext};
 use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use pbc_traits::WriteRPC;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(test)]
+mod tests;
+
+/// The address of the MPC token system contract, which handles the native MPC token.
+const MPC_TOKEN_ADDRESS: Address = Address {
+    address_type: AddressType::SystemContract,
+    identifier: [
+        0xa4, 0x08, 0x2d, 0x9d, 0x56, 0x07, 0x49, 0xec, 0xd0, 0xff, 0xa1, 0xdc, 0xae, 0xae, 0xe2,
+        0xc2, 0xcb, 0x25, 0xd8, 0x81,
+    ],
+};
+
+/// The kind of asset held in escrow.
+#[derive(PartialEq, Eq, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+pub enum AssetKind {
+    /// An MPC-20 token, located at the `token_type` address.
+    #[discriminant(0)]
+    Mpc20Token {},
+    /// The native MPC token of the chain.
+    #[discriminant(1)]
+    NativeMpc {},
+}
+
+/// The maximum bounty in per mille of the balance paid for triggering a refund.
+const MAX_REFUND_BOUNTY_PER_MILLE: u32 = 100;
+
+/// The maximum length in bytes of the note attached to an approval.
+const MAX_NOTE_LENGTH: usize = 256;
+
+/// The maximum number of watchers notified of the transitions of the escrow.
+const MAX_WATCHERS: usize = 8;
+
+/// A transition of the escrow, which the watchers are notified of.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum EscrowTransition {
+    /// A deposit of tokens into the escrow was confirmed.
+    #[discriminant(0)]
+    DepositConfirmed {},
+    /// The approver signalled fulfilment of the condition.
+    #[discriminant(1)]
+    Approved {},
+    /// The approver released an installment of a subscription.
+    #[discriminant(2)]
+    InstallmentReleased {},
+    /// Tokens were claimed by the sender or the receiver.
+    #[discriminant(3)]
+    Claimed {},
+    /// The balance was refunded to the sender after the deadline.
+    #[discriminant(4)]
+    Refunded {},
+    /// The escrow was cancelled by mutual consent.
+    #[discriminant(5)]
+    Cancelled {},
+    /// The receiver posted the security deposit of a reverse escrow.
+    #[discriminant(6)]
+    SecurityDepositPosted {},
+    /// The approver flagged that the receiver failed to deliver, forfeiting the security deposit.
+    #[discriminant(7)]
+    BreachFlagged {},
+}
+
+/// An amount of a token moved by a transition of the escrow.
+///
+/// ### Fields:
+///
+///   * `token`: [`Address`], the address of the token.
+///
+///   * `amount`: [`u128`], the amount of the token.
+///
+#[derive(PartialEq, Eq, Clone, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub struct TokenAmount {
+    token: Address,
+    amount: u128,
+}
+
+/// An entry in the history of the escrow, recording a transition.
+///
+/// ### Fields:
+///
+///   * `transition`: [`EscrowTransition`], the transition of the escrow.
+///
+///   * `actor`: [`Address`], the address causing the transition.
+///
+///   * `amounts`: [`Vec<TokenAmount>`], the amounts of the tokens deposited, released or
+///     transferred out of the escrow by the transition.
+///
+///   * `time_millis`: [`i64`], the time of the transition in milliseconds.
+///
+#[derive(PartialEq, Eq, Clone, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub struct HistoryEntry {
+    transition: EscrowTransition,
+    actor: Address,
+    amounts: Vec<TokenAmount>,
+    time_millis: i64,
+}
+
+/// The receipt of the escrow exported to the audit contract.
+///
+/// ### Fields:
+///
+///   * `escrow`: [`Address`], the address of the escrow contract.
+///
+///   * `sender`: [`Address`], the sender of the tokens.
+///
+///   * `receiver`: [`Address`], the receiver of the tokens.
+///
+///   * `approver`: [`Address`], the approver of the condition.
+///
+///   * `status`: [`u8`], the status of the escrow when the receipt was exported.
+///
+///   * `history`: [`Vec<HistoryEntry>`], the full history of the escrow.
+///
+#[derive(ReadWriteRPC, CreateTypeSpec)]
+pub struct EscrowReceipt {
+    escrow: Address,
+    sender: Address,
+    receiver: Address,
+    approver: Address,
+    status: u8,
+    history: Vec<HistoryEntry>,
+}
+
+/// The attestation attached by the approver when signalling fulfilment of the condition.
+///
+/// ### Fields:
+///
+///   * `evidence_hash`: [`Hash`], the hash of the evidence proving that the condition was
+///     fulfilled.
+///
+///   * `note`: [`String`], a free-form note from the approver.
+///
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+pub struct ApprovalAttestation {
+    evidence_hash: Hash,
+    note: String,
+}
+
+impl ApprovalAttestation {
+    /// The hash of the evidence proving that the condition was fulfilled.
+    pub fn evidence_hash(&self) -> &Hash {
+        &self.evidence_hash
+    }
+
+    /// The free-form note from the approver.
+    pub fn note(&self) -> &str {
+        &self.note
+    }
+}
+
+/// The terms of an escrow acting as a recurring subscription.
+///
+/// ### Fields:
+///
+///   * `installment_amount`: [`u128`], the amount of the primary token released per period.
+///
+///   * `period_millis`: [`i64`], the length of a period in milliseconds.
+///
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+pub struct SubscriptionTerms {
+    installment_amount: u128,
+    period_millis: i64,
+}
 
 /// Constants for different phases of the contract.
 
@@ -26,6 +251,10 @@ const STATE_CREATED: u8 = 0;
 const STATE_AWAITING_APPROVAL: u8 = 1;
 /// State after the approver has signalled fulfilment of the condition
 const STATE_APPROVED: u8 = 2;
+/// State after the sender and the receiver have cancelled the escrow by mutual consent.
+const STATE_CANCELLED: u8 = 3;
+/// State after the approver has flagged that the receiver failed to deliver.
+const STATE_BREACHED: u8 = 4;
 
 /// The contract state.
 ///
@@ -37,9 +266,15 @@ const STATE_APPROVED: u8 = 2;
 ///
 ///   * `approver`: [`Address`], the approver that can signal fulfilment of the condition.
 ///
-///   * `token_type`: [`Address`], the address of the token used in the contract.
+///   * `token_type`: [`Address`], the address of the primary token used in the contract.
+///
+///   * `asset_kind`: [`AssetKind`], the kind of the primary asset held in escrow.
 ///
-///   * `balance`: [`u128`], the amount of tokens currently in the contract.
+///   * `accepted_tokens`: [`BTreeSet<Address>`], the addresses of the tokens that can be deposited,
+///     including the primary token.
+///
+///   * `balances`: [`BTreeMap<Address, u128>`], the amount of each token currently in the contract,
+///     keyed by the address of the token contract.
 ///
 ///   * `start_time_millis`: [`i64`], the start time of the contract milliseconds.
 ///
@@ -47,16 +282,254 @@ const STATE_APPROVED: u8 = 2;
 ///
 ///   * `status`: [`u8`], the current status of the contract.
 ///
+///   * `cancel_proposals`: [`BTreeSet<Address>`], the parties that have proposed to cancel the
+///     escrow by mutual consent.
+///
+///   * `extension_proposals`: [`BTreeMap<Address, i64>`], the new deadlines in milliseconds
+///     proposed by the parties to extend the deadline by mutual agreement.
+///
+///   * `attestation`: [`Option<ApprovalAttestation>`], the attestation attached by the approver
+///     when signalling fulfilment of the condition.
+///
+///   * `refund_bounty_per_mille`: [`u32`], the per mille of the balance paid to the caller of
+///     `trigger_refund`.
+///
+///   * `subscription`: [`Option<SubscriptionTerms>`], the terms of the subscription, if the escrow
+///     is a recurring subscription.
+///
+///   * `last_released_period`: [`Option<i64>`], the index of the latest period an installment was
+///     released in, counted from the start of the contract.
+///
+///   * `released_amount`: [`u128`], the amount of the primary token released to the receiver and
+///     not yet claimed.
+///
+///   * `watchers`: [`Vec<Address>`], the contracts notified of each transition of the escrow.
+///
+///   * `history`: [`Vec<HistoryEntry>`], the append-only history of the transitions of the escrow.
+///
+///   * `audit_contract`: [`Option<Address>`], the contract receiving the exported receipts of the
+///     escrow, if any.
+///
+///   * `required_security_deposit`: [`Option<u128>`], the amount of the primary token the
+///     receiver must post as a security deposit, if the escrow is a reverse escrow.
+///
+///   * `security_deposit`: [`u128`], the amount of the primary token posted by the receiver as a
+///     security deposit, kept apart from the `balances`.
+///
 #[state]
 pub struct ContractState {
     sender: Address,
     receiver: Address,
     approver: Address,
     token_type: Address,
-    balance: u128,
+    asset_kind: AssetKind,
+    accepted_tokens: BTreeSet<Address>,
+    balances: BTreeMap<Address, u128>,
     start_time_millis: i64,
     end_time_millis: i64,
     status: u8,
+    cancel_proposals: BTreeSet<Address>,
+    extension_proposals: BTreeMap<Address, i64>,
+    attestation: Option<ApprovalAttestation>,
+    refund_bounty_per_mille: u32,
+    subscription: Option<SubscriptionTerms>,
+    last_released_period: Option<i64>,
+    released_amount: u128,
+    watchers: Vec<Address>,
+    history: Vec<HistoryEntry>,
+    audit_contract: Option<Address>,
+    required_security_deposit: Option<u128>,
+    security_deposit: u128,
+}
+
+impl ContractState {
+    /// The attestation attached by the approver, if the condition has been fulfilled.
+    pub fn attestation(&self) -> Option<&ApprovalAttestation> {
+        self.attestation.as_ref()
+    }
+
+    /// Asserts that `context.sender` is allowed to deposit tokens into the contract.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`], the context for the action call.
+    ///
+    fn assert_can_deposit(&self, context: &ContractContext) {
+        if context.sender != self.sender {
+            panic!("Deposit can only be called by the sender");
+        }
+        if self.status == STATE_APPROVED {
+            panic!("Cannot deposit tokens after the condition has been fulfilled");
+        }
+        if self.status == STATE_CANCELLED {
+            panic!("Cannot deposit tokens after the escrow has been cancelled");
+        }
+        if self.status == STATE_BREACHED {
+            panic!("Cannot deposit tokens after a breach has been flagged");
+        }
+        if context.block_production_time > self.end_time_millis {
+            panic!("Cannot deposit tokens after deadline is passed");
+        }
+    }
+
+    /// Whether the contract holds no tokens.
+    fn has_no_balance(&self) -> bool {
+        self.balances.values().all(|amount| *amount == 0)
+    }
+
+    /// Creates the event groups transferring all escrowed tokens from the contract to `to`, one
+    /// per held token, and clears the balances.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `to`: [`Address`], the receiver of the tokens.
+    ///
+    /// ### Returns
+    ///
+    /// The event groups containing the transfer events.
+    fn transfer_all_balances(&mut self, to: Address) -> Vec<EventGroup> {
+        let balances = std::mem::take(&mut self.balances);
+        balances
+            .into_iter()
+            .filter(|(_, amount)| *amount > 0)
+            .map(|(token, amount)| transfer_event(token, to, amount))
+            .collect()
+    }
+
+    /// Creates the event group transferring the security deposit from the contract to `to`, and
+    /// clears it.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `to`: [`Address`], the receiver of the security deposit.
+    ///
+    /// ### Returns
+    ///
+    /// The event group containing the transfer event, if a security deposit has been posted.
+    fn transfer_security_deposit(&mut self, to: Address) -> Vec<EventGroup> {
+        let security_deposit = std::mem::take(&mut self.security_deposit);
+        if security_deposit == 0 {
+            return vec![];
+        }
+        vec![transfer_event(self.token_type, to, security_deposit)]
+    }
+
+    /// The amounts of the tokens currently held by the contract.
+    fn held_amounts(&self) -> Vec<TokenAmount> {
+        self.balances
+            .iter()
+            .filter(|(_, amount)| **amount > 0)
+            .map(|(token, amount)| TokenAmount {
+                token: *token,
+                amount: *amount,
+            })
+            .collect()
+    }
+
+    /// Appends a transition to the history of the escrow, and creates the event group notifying
+    /// the watchers of it.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `transition`: [`EscrowTransition`], the transition of the escrow.
+    ///
+    /// * `actor`: [`Address`], the address causing the transition.
+    ///
+    /// * `amounts`: [`Vec<TokenAmount>`], the amounts of the tokens moved by the transition.
+    ///
+    /// * `time_millis`: [`i64`], the time of the transition in milliseconds.
+    ///
+    /// ### Returns
+    ///
+    /// The event group containing the notification events, or no events if there are no watchers.
+    fn record_transition(
+        &mut self,
+        transition: EscrowTransition,
+        actor: Address,
+        amounts: Vec<TokenAmount>,
+        time_millis: i64,
+    ) -> Vec<EventGroup> {
+        self.history.push(HistoryEntry {
+            transition,
+            actor,
+            amounts,
+            time_millis,
+        });
+        self.notify_watchers(transition)
+    }
+
+    /// Creates the event group notifying every watcher of a transition of the escrow. Each watcher
+    /// receives the transition and the new status of the escrow.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `transition`: [`EscrowTransition`], the transition of the escrow.
+    ///
+    /// ### Returns
+    ///
+    /// The event group containing the notification events, or no events if there are no watchers.
+    fn notify_watchers(&self, transition: EscrowTransition) -> Vec<EventGroup> {
+        if self.watchers.is_empty() {
+            return vec![];
+        }
+        let mut e = EventGroup::builder();
+        for watcher in &self.watchers {
+            e.call(*watcher, watcher_contract_escrow_transition())
+                .argument(transition)
+                .argument(self.status)
+                .done();
+        }
+        vec![e.build()]
+    }
+
+    /// Creates the event groups transferring the released installments of a subscription to the
+    /// receiver, and deducts them from the balance of the primary token.
+    ///
+    /// ### Returns
+    ///
+    /// The event group containing the transfer event, if any installments have been released.
+    fn transfer_released_installments(&mut self) -> Vec<EventGroup> {
+        let released_amount = std::mem::take(&mut self.released_amount);
+        if released_amount == 0 {
+            return vec![];
+        }
+        *self.balances.get_mut(&self.token_type).unwrap() -= released_amount;
+        vec![transfer_event(
+            self.token_type,
+            self.receiver,
+            released_amount,
+        )]
+    }
+}
+
+/// Creates an event group transferring escrowed tokens from the contract, using the transfer of
+/// the MPC token contract for native MPC tokens, and the MPC-20 transfer otherwise.
+///
+/// ### Parameters:
+///
+/// * `token`: [`Address`], the address of the token to transfer.
+///
+/// * `to`: [`Address`], the receiver of the tokens.
+///
+/// * `amount`: [`u128`], the amount of tokens to transfer.
+///
+/// ### Returns
+///
+/// The event group containing the transfer event.
+fn transfer_event(token: Address, to: Address, amount: u128) -> EventGroup {
+    let mut e = EventGroup::builder();
+    if token == MPC_TOKEN_ADDRESS {
+        e.call(MPC_TOKEN_ADDRESS, mpc_token_contract_transfer())
+            .argument(to)
+            .argument(native_amount(amount))
+            .done();
+    } else {
+        e.call(token, token_contract_transfer())
+            .argument(to)
+            .argument(amount)
+            .done();
+    }
+    e.build()
 }
 
 /// Initial function to bootstrap the contract's state.
@@ -69,15 +542,33 @@ pub struct ContractState {
 ///
 ///   * `approver`: [`Address`], the approver that can signal fulfilment of the condition.
 ///
-///   * `token_type`: [`Address`], the address of the token used in the contract.
+///   * `token_type`: [`Address`], the address of the primary token used in the contract.
+///     Ignored if `asset_kind` is `NativeMpc`.
 ///
 ///   * `hours_until_deadline`: [`u32`], the number of hours until the deadline gets passed.
 ///
+///   * `asset_kind`: [`AssetKind`], the kind of the primary asset held in escrow.
+///
+///   * `additional_tokens`: [`Vec<Address>`], the addresses of additional MPC-20 tokens that can be
+///     deposited.
+///
+///   * `refund_bounty_per_mille`: [`u32`], the per mille of the balance paid to the caller of
+///     `trigger_refund`, at most `MAX_REFUND_BOUNTY_PER_MILLE`.
+///
+///   * `subscription`: [`Option<SubscriptionTerms>`], the terms of the subscription, making the
+///     escrow a recurring subscription of the primary token, or None for a conditional transfer.
+///     A subscription cannot accept additional tokens.
+///
+///   * `required_security_deposit`: [`Option<u128>`], the amount of the primary token the
+///     receiver must post as a security deposit, making the escrow a reverse escrow, or None if no
+///     security deposit is required. A subscription cannot require a security deposit.
+///
 /// ### Returns
 ///
 /// The new state object of type [`ContractState`] with the initial state being `STATE_CREATED`.
 ///
 #[init]
+#[allow(clippy::too_many_arguments)]
 pub fn initialize(
     context: ContractContext,
     sender: Address,
@@ -85,9 +576,44 @@ pub fn initialize(
     approver: Address,
     token_type: Address,
     hours_until_deadline: u32,
+    asset_kind: AssetKind,
+    additional_tokens: Vec<Address>,
+    refund_bounty_per_mille: u32,
+    subscription: Option<SubscriptionTerms>,
+    required_security_deposit: Option<u128>,
 ) -> ContractState {
-    if token_type.address_type != AddressType::PublicContract {
-        panic!("Tried to create a contract selling a non publicContract token");
+    let token_type = match asset_kind {
+        AssetKind::Mpc20Token {} => {
+            if token_type.address_type != AddressType::PublicContract {
+                panic!("Tried to create a contract selling a non publicContract token");
+            }
+            token_type
+        }
+        AssetKind::NativeMpc {} => MPC_TOKEN_ADDRESS,
+    };
+    let mut accepted_tokens = BTreeSet::from([token_type]);
+    for token in additional_tokens {
+        if token.address_type != AddressType::PublicContract {
+            panic!("Tried to accept a non publicContract token");
+        }
+        accepted_tokens.insert(token);
+    }
+    if refund_bounty_per_mille > MAX_REFUND_BOUNTY_PER_MILLE {
+        panic!("The refund bounty cannot exceed {MAX_REFUND_BOUNTY_PER_MILLE} per mille");
+    }
+    if let Some(terms) = &subscription {
+        if terms.installment_amount == 0 || terms.period_millis <= 0 {
+            panic!("The installment amount and the period of a subscription must be positive");
+        }
+        if accepted_tokens.len() > 1 {
+            panic!("A subscription cannot accept additional tokens");
+        }
+        if required_security_deposit.is_some() {
+            panic!("A subscription cannot require a security deposit");
+        }
+    }
+    if required_security_deposit == Some(0) {
+        panic!("The security deposit must be positive");
     }
     let millis_until_deadline = i64::from(hours_until_deadline) * 60 * 60 * 1000;
     let end_time_millis = context.block_production_time + millis_until_deadline;
@@ -96,16 +622,30 @@ pub fn initialize(
         receiver,
         approver,
         token_type,
-        balance: 0,
+        asset_kind,
+        accepted_tokens,
+        balances: BTreeMap::new(),
         start_time_millis: context.block_production_time,
         end_time_millis,
         status: STATE_CREATED,
+        cancel_proposals: BTreeSet::new(),
+        extension_proposals: BTreeMap::new(),
+        attestation: None,
+        refund_bounty_per_mille,
+        subscription,
+        last_released_period: None,
+        released_amount: 0,
+        watchers: vec![],
+        history: vec![],
+        audit_contract: None,
+        required_security_deposit,
+        security_deposit: 0,
     }
 }
 
-/// Action for the sender to deposit tokens into the contract.
-/// Throws an error if not called by the `sender` or if
-/// the status is not `STATE_CREATED`.
+/// Action for the sender to deposit MPC-20 tokens into the contract.
+/// Throws an error if the token is not accepted by the contract or is the native MPC token, if not
+/// called by the `sender` or if the status is not `STATE_CREATED`.
 /// The function creates a transfer event of tokens from the `sender` to the contract, and
 /// a callback to `deposit_callback`.
 ///
@@ -115,6 +655,8 @@ pub fn initialize(
 ///
 /// * `state`: [`ContractState`], the current state of the contract.
 ///
+/// * `token`: [`Address`], the address of the token to deposit.
+///
 /// * `amount`: [`u128`], the amount of tokens to deposit
 ///
 /// ### Returns
@@ -126,26 +668,76 @@ pub fn initialize(
 pub fn deposit(
     context: ContractContext,
     state: ContractState,
+    token: Address,
     amount: u128,
 ) -> (ContractState, Vec<EventGroup>) {
-    if context.sender != state.sender {
-        panic!("Deposit can only be called by the sender");
-    }
-    if state.status == STATE_APPROVED {
-        panic!("Cannot deposit tokens after the condition has been fulfilled");
+    if token == MPC_TOKEN_ADDRESS {
+        panic!("Deposit can only be used for MPC-20 tokens, use deposit_native instead");
     }
-    if context.block_production_time > state.end_time_millis {
-        panic!("Cannot deposit tokens after deadline is passed");
+    if !state.accepted_tokens.contains(&token) {
+        panic!("The token is not accepted by the escrow");
     }
+    state.assert_can_deposit(&context);
     // Create transfer event of tokens from the sender to the contract
     // transfer should callback to deposit_callback
     let mut e = EventGroup::builder();
-    e.call(state.token_type, token_contract_transfer_from())
+    e.call(token, token_contract_transfer_from())
         .argument(context.sender)
         .argument(context.contract_address)
         .argument(amount)
         .done();
     e.with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(token)
+        .argument(amount)
+        .done();
+    let event_group: EventGroup = e.build();
+
+    (state, vec![event_group])
+}
+
+/// Action for the sender to deposit native MPC tokens into the contract.
+/// Throws an error if the native MPC token is not accepted by the contract, if not called by the
+/// `sender`, if the `sender` is not an account, if the status is `STATE_APPROVED`,
+/// `STATE_CANCELLED` or `STATE_BREACHED`, if the deadline has passed, or if `amount` does not fit
+/// the [`u64`] amounts of the MPC token contract.
+/// The function creates a transfer event of MPC tokens from the original sender to the contract,
+/// and a callback to `deposit_callback`. The original sender is the sender of the action only
+/// when the sender is an account, so native deposits are only supported for account senders.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of MPC tokens to deposit
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`ContractState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x05)]
+pub fn deposit_native(
+    context: ContractContext,
+    state: ContractState,
+    amount: u128,
+) -> (ContractState, Vec<EventGroup>) {
+    if !state.accepted_tokens.contains(&MPC_TOKEN_ADDRESS) {
+        panic!("Native deposits can only be used for the native MPC token");
+    }
+    state.assert_can_deposit(&context);
+    assert_native_sender(&context);
+    // Create transfer event of MPC tokens from the original sender to the contract
+    // transfer should callback to deposit_callback
+    let mut e = EventGroup::builder();
+    e.call(MPC_TOKEN_ADDRESS, mpc_token_contract_transfer())
+        .from_original_sender()
+        .argument(context.contract_address)
+        .argument(native_amount(amount))
+        .done();
+    e.with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(MPC_TOKEN_ADDRESS)
         .argument(amount)
         .done();
     let event_group: EventGroup = e.build();
@@ -154,7 +746,9 @@ pub fn deposit(
 }
 
 /// Callback for depositing tokens. If the transfer was successful the status of the contract
-/// is updated to `STATE_AWAITING_APPROVAL`. Otherwise the callback panics.
+/// is updated to `STATE_AWAITING_APPROVAL`, unless the escrow has been approved, cancelled or
+/// breached in the meantime, in which case the deposit is returned to the sender and the status is
+/// kept. Otherwise the callback panics.
 ///
 /// ### Parameters:
 ///
@@ -164,29 +758,50 @@ pub fn deposit(
 ///
 /// * `state`: [`ContractState`], the current state of the contract.
 ///
+/// * `token`: [`Address`], the address of the deposited token.
+///
+/// * `amount`: [`u128`], the amount of deposited tokens.
+///
 /// ### Returns
 ///
 /// The new state object of type [`ContractState`].
 ///
 #[callback(shortname = 0x02)]
 pub fn deposit_callback(
-    _ctx: ContractContext,
+    ctx: ContractContext,
     callback_ctx: CallbackContext,
     state: ContractState,
+    token: Address,
     amount: u128,
 ) -> (ContractState, Vec<EventGroup>) {
     if !callback_ctx.success {
         panic!("Transfer event did not succeed for deposit");
     }
+    let settled = state.status == STATE_APPROVED
+        || state.status == STATE_CANCELLED
+        || state.status == STATE_BREACHED;
+    if settled {
+        // The escrow was settled while the deposit was in flight, so return it to the sender
+        let event_group = transfer_event(token, state.sender, amount);
+        return (state, vec![event_group]);
+    }
     let mut new_state = state;
-    new_state.balance += amount;
+    *new_state.balances.entry(token).or_insert(0) += amount;
     new_state.status = STATE_AWAITING_APPROVAL;
-    (new_state, vec![])
+    let sender = new_state.sender;
+    let events = new_state.record_transition(
+        EscrowTransition::DepositConfirmed {},
+        sender,
+        vec![TokenAmount { token, amount }],
+        ctx.block_production_time,
+    );
+    (new_state, events)
 }
 
 /// Action for signalling fulfilment of the condition. Panics if the deadline of the
 /// contract has been passed, if the caller is not the correct `approver` or if the contract is
-/// not in state `STATE_AWAITING_APPROVAL`. Otherwise updates the status of the contract to `STATE_APPROVED`.
+/// not in state `STATE_AWAITING_APPROVAL`. Otherwise updates the status of the contract to `STATE_APPROVED`
+/// and stores the attestation of the approver.
 ///
 /// ### Parameters:
 ///
@@ -194,12 +809,21 @@ pub fn deposit_callback(
 ///
 /// * `state`: [`ContractState`], the current state of the contract.
 ///
+/// * `evidence_hash`: [`Hash`], the hash of the evidence proving that the condition was fulfilled.
+///
+/// * `note`: [`String`], a free-form note of at most `MAX_NOTE_LENGTH` bytes.
+///
 /// ### Returns
 ///
 /// The new state object of type [`ContractState`].
 ///
 #[action(shortname = 0x03)]
-pub fn approve(context: ContractContext, state: ContractState) -> (ContractState, Vec<EventGroup>) {
+pub fn approve(
+    context: ContractContext,
+    state: ContractState,
+    evidence_hash: Hash,
+    note: String,
+) -> (ContractState, Vec<EventGroup>) {
     if context.sender != state.approver {
         panic!("Only the designated approver can approve")
     }
@@ -209,19 +833,105 @@ pub fn approve(context: ContractContext, state: ContractState) -> (ContractState
     if state.status != STATE_AWAITING_APPROVAL {
         panic!("Tried to approve when status was not STATE_AWAITING_APPROVAL")
     }
+    if note.len() > MAX_NOTE_LENGTH {
+        panic!("The note cannot be longer than {MAX_NOTE_LENGTH} bytes");
+    }
+    if state.subscription.is_some() {
+        panic!("Cannot approve a subscription, release installments instead");
+    }
+    if state.required_security_deposit.is_some() && state.security_deposit == 0 {
+        panic!("Cannot approve before the receiver has posted the security deposit");
+    }
 
     let mut new_state = state;
     new_state.status = STATE_APPROVED;
-    (new_state, vec![])
+    new_state.attestation = Some(ApprovalAttestation {
+        evidence_hash,
+        note,
+    });
+    let events = new_state.record_transition(
+        EscrowTransition::Approved {},
+        context.sender,
+        vec![],
+        context.block_production_time,
+    );
+    (new_state, events)
+}
+
+/// Action for the approver to release an installment of a subscription to the receiver.
+/// Panics if the escrow is not a subscription, if the caller is not the `approver`, if the
+/// deadline has been passed, if the status is not `STATE_AWAITING_APPROVAL`, or if an installment
+/// has already been released in the current period. The released amount is the installment
+/// amount, or the remaining budget if it is smaller.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`].
+///
+#[action(shortname = 0x09)]
+pub fn release_installment(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    let Some(terms) = &state.subscription else {
+        panic!("Installments can only be released in a subscription");
+    };
+    if context.sender != state.approver {
+        panic!("Only the designated approver can release installments");
+    }
+    if context.block_production_time > state.end_time_millis {
+        panic!("Cannot release an installment after deadline is passed");
+    }
+    if state.status != STATE_AWAITING_APPROVAL {
+        panic!("Tried to release an installment when status was not STATE_AWAITING_APPROVAL");
+    }
+    let period = (context.block_production_time - state.start_time_millis) / terms.period_millis;
+    if state.last_released_period >= Some(period) {
+        panic!("An installment has already been released in the current period");
+    }
+    let balance = state.balances.get(&state.token_type).copied().unwrap_or(0);
+    let installment = terms
+        .installment_amount
+        .min(balance - state.released_amount);
+    if installment == 0 {
+        panic!("The budget of the subscription has been released");
+    }
+
+    let mut new_state = state;
+    new_state.last_released_period = Some(period);
+    new_state.released_amount += installment;
+    let token = new_state.token_type;
+    let events = new_state.record_transition(
+        EscrowTransition::InstallmentReleased {},
+        context.sender,
+        vec![TokenAmount {
+            token,
+            amount: installment,
+        }],
+        context.block_production_time,
+    );
+    (new_state, events)
 }
 
 /// Action for claiming tokens.
 /// The `receiver` is allowed to claim the tokens if the status is `STATE_APPROVED`.
 /// The `sender` is allowed to claim the tokens if the status is `AWAITING_APPROVAL`
 /// and the deadline has been passed.
+/// In a subscription the `receiver` is instead allowed to claim the released installments at any
+/// time, and the `sender` claims the remainder of the budget after the deadline, which also
+/// transfers the unclaimed installments to the receiver.
+/// In a reverse escrow the `sender` can also claim the tokens before the deadline once a breach has
+/// been flagged, and the security deposit of the receiver is returned to the receiver when the
+/// tokens are claimed without a breach.
 /// No other addresses can claim tokens
-/// If the tokens are claimed a corresponding transfer event is created and the status is
-/// updated to `TOKENS_CLAIMED`.
+/// If the tokens are claimed a transfer event is created for each held token and the balances are
+/// cleared.
 ///
 /// ### Parameters:
 ///
@@ -231,8 +941,8 @@ pub fn approve(context: ContractContext, state: ContractState) -> (ContractState
 ///
 /// ### Returns
 ///
-/// The new state object of type [`ContractState`] and an event group possibly containing a
-/// transfer event.
+/// The new state object of type [`ContractState`] and the event groups containing the transfer
+/// events.
 ///
 #[action(shortname = 0x04)]
 pub fn claim(context: ContractContext, state: ContractState) -> (ContractState, Vec<EventGroup>) {
@@ -243,9 +953,27 @@ pub fn claim(context: ContractContext, state: ContractState) -> (ContractState,
     if state.status == STATE_CREATED {
         panic!("Cannot claim tokens when no tokens have been deposited");
     }
-    if state.balance == 0 {
+    if state.has_no_balance() {
         panic!("Cannot claim tokens when balance is zero");
     }
+    if state.subscription.is_some() && context.sender == state.receiver {
+        if state.released_amount == 0 {
+            panic!("Cannot claim installments when none have been released");
+        }
+        let mut new_state = state;
+        let claimed = vec![TokenAmount {
+            token: new_state.token_type,
+            amount: new_state.released_amount,
+        }];
+        let mut events = new_state.transfer_released_installments();
+        events.extend(new_state.record_transition(
+            EscrowTransition::Claimed {},
+            context.sender,
+            claimed,
+            context.block_production_time,
+        ));
+        return (new_state, events);
+    }
     if context.sender == state.receiver && state.status != STATE_APPROVED {
         panic!("The receiver cannot claim unless transfer condition has been fulfilled");
     }
@@ -253,22 +981,518 @@ pub fn claim(context: ContractContext, state: ContractState) -> (ContractState,
         if state.status == STATE_APPROVED {
             panic!("The sender cannot claim tokens since the condition has been fulfilled");
         }
-        if context.block_production_time < state.end_time_millis {
+        if context.block_production_time < state.end_time_millis && state.status != STATE_BREACHED {
             panic!("The sender cannot claim tokens before the deadline is passed");
         }
     }
 
+    let mut new_state = state;
+    let claimed = new_state.held_amounts();
+    let receiver = new_state.receiver;
+    let mut events = new_state.transfer_released_installments();
+    events.extend(new_state.transfer_all_balances(context.sender));
+    events.extend(new_state.transfer_security_deposit(receiver));
+    events.extend(new_state.record_transition(
+        EscrowTransition::Claimed {},
+        context.sender,
+        claimed,
+        context.block_production_time,
+    ));
+
+    (new_state, events)
+}
+
+/// Action for refunding the balance to the sender after the deadline has passed without the
+/// condition being fulfilled. Can be called by anyone, who receives a bounty of
+/// `refund_bounty_per_mille` of the balance of each held token, while the remainder is transferred
+/// to the sender. In a subscription the unclaimed installments are first transferred to the
+/// receiver, and are not part of the refunded balance. Panics if the deadline has not been passed, if the status is not
+/// `STATE_AWAITING_APPROVAL` or if the balance is zero. The security deposit of a reverse escrow is
+/// returned to the receiver.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`] and the event groups containing the transfer
+/// events of the bounty and the refund.
+///
+#[action(shortname = 0x08)]
+pub fn trigger_refund(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.block_production_time < state.end_time_millis {
+        panic!("Cannot trigger a refund before the deadline is passed");
+    }
+    if state.status != STATE_AWAITING_APPROVAL {
+        panic!("Tried to trigger a refund when status was not STATE_AWAITING_APPROVAL");
+    }
+    if state.has_no_balance() {
+        panic!("Cannot trigger a refund when balance is zero");
+    }
+
+    let mut new_state = state;
+    let refunded = new_state.held_amounts();
+    let mut events = new_state.transfer_released_installments();
+    let balances = std::mem::take(&mut new_state.balances);
+    for (token, amount) in balances {
+        let bounty = amount * new_state.refund_bounty_per_mille as u128 / 1000;
+        if bounty > 0 {
+            events.push(transfer_event(token, context.sender, bounty));
+        }
+        if amount > bounty {
+            events.push(transfer_event(token, new_state.sender, amount - bounty));
+        }
+    }
+    let receiver = new_state.receiver;
+    events.extend(new_state.transfer_security_deposit(receiver));
+    events.extend(new_state.record_transition(
+        EscrowTransition::Refunded {},
+        context.sender,
+        refunded,
+        context.block_production_time,
+    ));
+    (new_state, events)
+}
+
+/// Action for cancelling the escrow by mutual consent of the sender and the receiver.
+/// The first party to call the action proposes the cancellation, and the other party confirms it
+/// by calling the action as well. Once both parties have consented the status is updated to
+/// `STATE_CANCELLED` and the balances are transferred back to the sender, except for the unclaimed
+/// installments of a subscription and the security deposit of a reverse escrow, which are
+/// transferred to the receiver.
+/// Panics if not called by the sender or the receiver, if the deadline has been passed, or if the
+/// condition has already been fulfilled, a breach has been flagged or the escrow cancelled.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`] and event groups possibly containing transfer
+/// events.
+///
+#[action(shortname = 0x06)]
+pub fn cancel_by_mutual_consent(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.sender != state.sender && context.sender != state.receiver {
+        panic!("Only the sender and the receiver in the escrow transfer can cancel it");
+    }
+    if context.block_production_time > state.end_time_millis {
+        panic!("Cannot cancel the escrow after deadline is passed");
+    }
+    if state.status == STATE_APPROVED {
+        panic!("Cannot cancel the escrow after the condition has been fulfilled");
+    }
+    if state.status == STATE_CANCELLED {
+        panic!("The escrow has already been cancelled");
+    }
+    if state.status == STATE_BREACHED {
+        panic!("Cannot cancel the escrow after a breach has been flagged");
+    }
+
+    let mut new_state = state;
+    new_state.cancel_proposals.insert(context.sender);
+    let both_consent = new_state.cancel_proposals.contains(&new_state.sender)
+        && new_state.cancel_proposals.contains(&new_state.receiver);
+    if !both_consent {
+        return (new_state, vec![]);
+    }
+
+    new_state.status = STATE_CANCELLED;
+    let sender = new_state.sender;
+    let receiver = new_state.receiver;
+    let returned = new_state.held_amounts();
+    let mut events = new_state.transfer_released_installments();
+    events.extend(new_state.transfer_all_balances(sender));
+    events.extend(new_state.transfer_security_deposit(receiver));
+    events.extend(new_state.record_transition(
+        EscrowTransition::Cancelled {},
+        context.sender,
+        returned,
+        context.block_production_time,
+    ));
+    (new_state, events)
+}
+
+/// Action for extending the deadline by mutual agreement of the sender and the receiver.
+/// The first party to call the action proposes the new deadline, and the other party agrees to it
+/// by calling the action with the same deadline. Once both parties have proposed the same
+/// deadline, the deadline of the contract is updated and the proposals are cleared.
+/// Panics if not called by the sender or the receiver, if the deadline has been passed, if the
+/// new deadline is not later than the current deadline, or if the condition has already been
+/// fulfilled, a breach has been flagged or the escrow cancelled.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// * `new_end_time_millis`: [`i64`], the proposed new deadline of the contract in milliseconds.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`].
+///
+#[action(shortname = 0x07)]
+pub fn extend_deadline(
+    context: ContractContext,
+    state: ContractState,
+    new_end_time_millis: i64,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.sender != state.sender && context.sender != state.receiver {
+        panic!("Only the sender and the receiver in the escrow transfer can extend the deadline");
+    }
+    if context.block_production_time > state.end_time_millis {
+        panic!("Cannot extend the deadline after it is passed");
+    }
+    if new_end_time_millis <= state.end_time_millis {
+        panic!("The new deadline must be later than the current deadline");
+    }
+    if state.status == STATE_APPROVED {
+        panic!("Cannot extend the deadline after the condition has been fulfilled");
+    }
+    if state.status == STATE_CANCELLED {
+        panic!("Cannot extend the deadline after the escrow has been cancelled");
+    }
+    if state.status == STATE_BREACHED {
+        panic!("Cannot extend the deadline after a breach has been flagged");
+    }
+
+    let mut new_state = state;
+    new_state
+        .extension_proposals
+        .insert(context.sender, new_end_time_millis);
+    let both_agree = new_state.extension_proposals.get(&new_state.sender)
+        == Some(&new_end_time_millis)
+        && new_state.extension_proposals.get(&new_state.receiver) == Some(&new_end_time_millis);
+    if both_agree {
+        new_state.end_time_millis = new_end_time_millis;
+        new_state.extension_proposals.clear();
+    }
+    (new_state, vec![])
+}
+
+/// Action for the receiver of a reverse escrow to post the required security deposit of the
+/// primary token. Panics if the escrow does not require a security deposit, if not called by the
+/// `receiver`, if the deadline has been passed, if the security deposit has already been posted,
+/// or if the condition has already been fulfilled, a breach has been flagged or the escrow
+/// cancelled. A security deposit of native MPC tokens can only be posted by an account receiver.
+/// The function creates a transfer event of the security deposit from the `receiver` to the
+/// contract, and a callback to `security_deposit_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`ContractState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x0E)]
+pub fn post_security_deposit(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    let Some(amount) = state.required_security_deposit else {
+        panic!("The escrow does not require a security deposit");
+    };
+    if context.sender != state.receiver {
+        panic!("Only the receiver can post the security deposit");
+    }
+    if context.block_production_time > state.end_time_millis {
+        panic!("Cannot post the security deposit after deadline is passed");
+    }
+    if state.security_deposit > 0 {
+        panic!("The security deposit has already been posted");
+    }
+    if state.status == STATE_APPROVED
+        || state.status == STATE_CANCELLED
+        || state.status == STATE_BREACHED
+    {
+        panic!("Cannot post the security deposit after the escrow has been settled");
+    }
     let mut e = EventGroup::builder();
-    e.call(state.token_type, token_contract_transfer())
-        .argument(context.sender)
-        .argument(state.balance)
+    if state.token_type == MPC_TOKEN_ADDRESS {
+        assert_native_sender(&context);
+        e.call(MPC_TOKEN_ADDRESS, mpc_token_contract_transfer())
+            .from_original_sender()
+            .argument(context.contract_address)
+            .argument(native_amount(amount))
+            .done();
+    } else {
+        e.call(state.token_type, token_contract_transfer_from())
+            .argument(context.sender)
+            .argument(context.contract_address)
+            .argument(amount)
+            .done();
+    }
+    e.with_callback(SHORTNAME_SECURITY_DEPOSIT_CALLBACK)
+        .argument(amount)
         .done();
-    let event_group = e.build();
+    (state, vec![e.build()])
+}
 
+/// Callback for posting the security deposit. If the transfer was successful the security deposit
+/// is recorded, unless the escrow has been settled or the security deposit posted in the
+/// meantime, in which case the tokens are returned to the receiver. Otherwise the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of the posted security deposit.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`].
+///
+#[callback(shortname = 0x10)]
+pub fn security_deposit_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: ContractState,
+    amount: u128,
+) -> (ContractState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for the security deposit");
+    }
+    let settled = state.status == STATE_APPROVED
+        || state.status == STATE_CANCELLED
+        || state.status == STATE_BREACHED;
+    if settled || state.security_deposit > 0 {
+        let event_group = transfer_event(state.token_type, state.receiver, amount);
+        return (state, vec![event_group]);
+    }
     let mut new_state = state;
-    new_state.balance = 0;
+    new_state.security_deposit = amount;
+    let (receiver, token) = (new_state.receiver, new_state.token_type);
+    let events = new_state.record_transition(
+        EscrowTransition::SecurityDepositPosted {},
+        receiver,
+        vec![TokenAmount { token, amount }],
+        ctx.block_production_time,
+    );
+    (new_state, events)
+}
 
-    (new_state, vec![event_group])
+/// Action for the approver to flag that the receiver of a reverse escrow failed to deliver.
+/// The security deposit is forfeited and transferred to the sender, and the status is updated to
+/// `STATE_BREACHED`, after which the sender can claim the escrowed tokens without waiting for the
+/// deadline. Panics if not called by the `approver`, if the security deposit has not been posted,
+/// or if the status is not `STATE_AWAITING_APPROVAL`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`] and the event groups containing the transfer
+/// event of the forfeited security deposit.
+///
+#[action(shortname = 0x0F)]
+pub fn flag_breach(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.sender != state.approver {
+        panic!("Only the designated approver can flag a breach");
+    }
+    if state.security_deposit == 0 {
+        panic!("Cannot flag a breach when no security deposit has been posted");
+    }
+    if state.status != STATE_AWAITING_APPROVAL {
+        panic!("Tried to flag a breach when status was not STATE_AWAITING_APPROVAL");
+    }
+
+    let mut new_state = state;
+    new_state.status = STATE_BREACHED;
+    let forfeited = vec![TokenAmount {
+        token: new_state.token_type,
+        amount: new_state.security_deposit,
+    }];
+    let sender = new_state.sender;
+    let mut events = new_state.transfer_security_deposit(sender);
+    events.extend(new_state.record_transition(
+        EscrowTransition::BreachFlagged {},
+        context.sender,
+        forfeited,
+        context.block_production_time,
+    ));
+    (new_state, events)
+}
+
+/// Action for querying the status of the escrow. The status is returned as return data, such that
+/// contracts calling the action can read it in their callback.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`ContractState`] and an event group returning the status.
+///
+#[action(shortname = 0x0A)]
+pub fn query_status(
+    _context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    let mut event_group = EventGroup::builder();
+    event_group.return_data(state.status);
+    (state, vec![event_group.build()])
+}
+
+/// Action for setting the watcher contracts notified of each transition of the escrow, replacing
+/// any previous watchers. Each watcher is called with the transition and the new status of the
+/// escrow. Panics if not called by the sender or the receiver, if a watcher is not a public
+/// contract, if a watcher is given twice, or if more than `MAX_WATCHERS` watchers are given.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// * `watchers`: [`Vec<Address>`], the watcher contracts. The notification is sent to the action
+///   with shortname 0x01 of each watcher, with the [`EscrowTransition`] and the status as
+///   arguments.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`].
+///
+#[action(shortname = 0x0B)]
+pub fn set_watchers(
+    context: ContractContext,
+    state: ContractState,
+    watchers: Vec<Address>,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.sender != state.sender && context.sender != state.receiver {
+        panic!("Only the sender and the receiver in the escrow transfer can set the watchers");
+    }
+    if watchers.len() > MAX_WATCHERS {
+        panic!("The escrow cannot have more than {MAX_WATCHERS} watchers");
+    }
+    if watchers
+        .iter()
+        .any(|watcher| watcher.address_type != AddressType::PublicContract)
+    {
+        panic!("Watchers must be public contracts");
+    }
+    let unique_watchers: BTreeSet<Address> = watchers.iter().copied().collect();
+    if unique_watchers.len() != watchers.len() {
+        panic!("Each watcher can only be given once");
+    }
+
+    let mut new_state = state;
+    new_state.watchers = watchers;
+    (new_state, vec![])
+}
+
+/// Action for setting the audit contract receiving the exported receipts of the escrow, replacing
+/// any previous audit contract. Panics if not called by the sender or the receiver, or if the
+/// audit contract is not a public contract.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// * `audit_contract`: [`Option<Address>`], the audit contract, or None to remove it. The receipt
+///   is sent to the action with shortname 0x01 of the audit contract, with the serialized
+///   [`EscrowReceipt`] and its SHA-256 digest as arguments.
+///
+/// ### Returns
+///
+/// The new state object of type [`ContractState`].
+///
+#[action(shortname = 0x0C)]
+pub fn set_audit_contract(
+    context: ContractContext,
+    state: ContractState,
+    audit_contract: Option<Address>,
+) -> (ContractState, Vec<EventGroup>) {
+    if context.sender != state.sender && context.sender != state.receiver {
+        panic!(
+            "Only the sender and the receiver in the escrow transfer can set the audit contract"
+        );
+    }
+    if audit_contract.is_some_and(|audit| audit.address_type != AddressType::PublicContract) {
+        panic!("The audit contract must be a public contract");
+    }
+
+    let mut new_state = state;
+    new_state.audit_contract = audit_contract;
+    (new_state, vec![])
+}
+
+/// Action for exporting the receipt of the escrow to the audit contract. Can be called by anyone.
+/// The receipt contains the parties, the status and the full history of the escrow, and is sent
+/// serialized together with its SHA-256 digest. Panics if no audit contract has been set.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`ContractState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`ContractState`] and the event group sending the receipt.
+///
+#[action(shortname = 0x0D)]
+pub fn export_receipt(
+    context: ContractContext,
+    state: ContractState,
+) -> (ContractState, Vec<EventGroup>) {
+    let Some(audit_contract) = state.audit_contract else {
+        panic!("No audit contract has been set");
+    };
+    let receipt = EscrowReceipt {
+        escrow: context.contract_address,
+        sender: state.sender,
+        receiver: state.receiver,
+        approver: state.approver,
+        status: state.status,
+        history: state.history.clone(),
+    };
+    let mut payload: Vec<u8> = vec![];
+    receipt
+        .rpc_write_to(&mut payload)
+        .expect("Could not serialize the receipt");
+    let digest: Hash = Sha256::digest(&payload).into();
+
+    let mut e = EventGroup::builder();
+    e.call(audit_contract, audit_contract_receive_receipt())
+        .argument(payload)
+        .argument(digest)
+        .done();
+    (state, vec![e.build()])
 }
 
 /// Token contract actions
@@ -281,3 +1505,34 @@ fn token_contract_transfer() -> Shortname {
 fn token_contract_transfer_from() -> Shortname {
     Shortname::from_u32(0x03)
 }
+
+/// Watcher contract actions
+#[inline]
+fn watcher_contract_escrow_transition() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Audit contract actions
+#[inline]
+fn audit_contract_receive_receipt() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// MPC token contract actions
+#[inline]
+fn mpc_token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
+/// Asserts that the sender of `context` is an account. Native MPC tokens are transferred from the
+/// original sender of the transaction, which is the sender of the action only if it is an account.
+fn assert_native_sender(context: &ContractContext) {
+    if context.sender.address_type != AddressType::Account {
+        panic!("Native MPC tokens can only be transferred from account senders");
+    }
+}
+
+/// Converts an amount of native MPC tokens to the representation used by the MPC token contract.
+fn native_amount(amount: u128) -> u64 {
+    u64::try_from(amount).expect("Amount of native MPC tokens is too large")
+}
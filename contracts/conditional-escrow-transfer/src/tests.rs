@@ -0,0 +1,919 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use pbc_traits::{ReadRPC, ReadWriteState, WriteRPC};
+use sha2::Digest;
+
+use crate::{
+    approve, cancel_by_mutual_consent, claim, deposit, deposit_callback, deposit_native,
+    export_receipt, extend_deadline, flag_breach, initialize, post_security_deposit, query_status,
+    release_installment, security_deposit_callback, set_audit_contract, set_watchers,
+    trigger_refund, ApprovalAttestation, AssetKind, ContractState, EscrowReceipt, EscrowTransition,
+    HistoryEntry, SubscriptionTerms, TokenAmount, MPC_TOKEN_ADDRESS, SHORTNAME_DEPOSIT_CALLBACK,
+    SHORTNAME_SECURITY_DEPOSIT_CALLBACK, STATE_APPROVED, STATE_AWAITING_APPROVAL, STATE_BREACHED,
+    STATE_CANCELLED,
+};
+
+fn create_ctx(sender: Address, block_time: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time,
+        block_production_time: block_time * 3_600_000,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_sender_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_receiver_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn get_approver_address() -> Address {
+    create_address(AddressType::Account, 3)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn get_token_address() -> Address {
+    create_address(AddressType::PublicContract, 5)
+}
+
+fn get_second_token_address() -> Address {
+    create_address(AddressType::PublicContract, 6)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// Creates an escrow of 100 tokens with a deadline at hour 10, awaiting approval.
+fn funded_escrow() -> ContractState {
+    let state = initialize(
+        create_ctx(get_sender_address(), 0),
+        get_sender_address(),
+        get_receiver_address(),
+        get_approver_address(),
+        get_token_address(),
+        10,
+        AssetKind::Mpc20Token {},
+        vec![get_second_token_address()],
+        50,
+        None,
+        None,
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 1),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        100,
+    );
+    state
+}
+
+#[test]
+pub fn test_approve_stores_attestation() {
+    let state = funded_escrow();
+    assert!(state.attestation().is_none());
+
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    assert_eq!(state.status, STATE_APPROVED);
+    let attestation = state.attestation().unwrap();
+    assert_eq!(attestation.evidence_hash(), &[7u8; 32]);
+    assert_eq!(attestation.note(), "Delivery confirmed");
+}
+
+#[test]
+#[should_panic(expected = "The note cannot be longer than 256 bytes")]
+pub fn test_approve_note_too_long() {
+    let state = funded_escrow();
+    approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "a".repeat(257),
+    );
+}
+
+#[test]
+pub fn test_attestation_state_serialization() {
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        funded_escrow(),
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+
+    let mut serialized = vec![];
+    state.state_write_to(&mut serialized).unwrap();
+    let read_state = ContractState::state_read_from(&mut serialized.as_slice());
+
+    let attestation = read_state.attestation().unwrap();
+    assert_eq!(attestation.evidence_hash(), &[7u8; 32]);
+    assert_eq!(attestation.note(), "Delivery confirmed");
+    assert_eq!(read_state.status, STATE_APPROVED);
+    assert_eq!(read_state.balances.get(&get_token_address()), Some(&100));
+}
+
+#[test]
+pub fn test_attestation_rpc_serialization() {
+    let attestation = ApprovalAttestation {
+        evidence_hash: [3u8; 32],
+        note: "Signed contract".to_string(),
+    };
+
+    let mut serialized = vec![];
+    attestation.rpc_write_to(&mut serialized).unwrap();
+    let read_attestation = ApprovalAttestation::rpc_read_from(&mut serialized.as_slice());
+
+    assert_eq!(read_attestation.evidence_hash(), &[3u8; 32]);
+    assert_eq!(read_attestation.note(), "Signed contract");
+}
+
+fn expected_transfer(token: Address, to: Address, amount: u128) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(token, Shortname::from_u32(1))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event.build()
+}
+
+#[test]
+pub fn test_claim_transfers_every_held_token() {
+    let state = funded_escrow();
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 2),
+        create_callback_ctx(true),
+        state,
+        get_second_token_address(),
+        40,
+    );
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+
+    let (state, events) = claim(create_ctx(get_receiver_address(), 6), state);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_receiver_address(), 100),
+            expected_transfer(get_second_token_address(), get_receiver_address(), 40),
+        ]
+    );
+    assert!(state.balances.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "The token is not accepted by the escrow")]
+pub fn test_deposit_token_not_accepted() {
+    let state = funded_escrow();
+    deposit(
+        create_ctx(get_sender_address(), 2),
+        state,
+        create_address(AddressType::PublicContract, 7),
+        10,
+    );
+}
+
+#[test]
+pub fn test_trigger_refund_pays_bounty() {
+    let keeper = create_address(AddressType::Account, 8);
+    let (state, events) = trigger_refund(create_ctx(keeper, 10), funded_escrow());
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), keeper, 5),
+            expected_transfer(get_token_address(), get_sender_address(), 95),
+        ]
+    );
+    assert!(state.balances.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Cannot trigger a refund before the deadline is passed")]
+pub fn test_trigger_refund_before_deadline() {
+    let keeper = create_address(AddressType::Account, 8);
+    trigger_refund(create_ctx(keeper, 9), funded_escrow());
+}
+
+/// Creates a subscription with a budget of 100 tokens, releasing 30 tokens every 2 hours until
+/// the deadline at hour 10.
+fn funded_subscription() -> ContractState {
+    let state = initialize(
+        create_ctx(get_sender_address(), 0),
+        get_sender_address(),
+        get_receiver_address(),
+        get_approver_address(),
+        get_token_address(),
+        10,
+        AssetKind::Mpc20Token {},
+        vec![],
+        0,
+        Some(SubscriptionTerms {
+            installment_amount: 30,
+            period_millis: 2 * 3_600_000,
+        }),
+        None,
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 1),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        100,
+    );
+    state
+}
+
+#[test]
+pub fn test_subscription_installments() {
+    let state = funded_subscription();
+    let (state, _) = release_installment(create_ctx(get_approver_address(), 1), state);
+    let (state, _) = release_installment(create_ctx(get_approver_address(), 3), state);
+    assert_eq!(state.released_amount, 60);
+
+    let (state, events) = claim(create_ctx(get_receiver_address(), 4), state);
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_receiver_address(),
+            60
+        )]
+    );
+    assert_eq!(state.balances.get(&get_token_address()), Some(&40));
+
+    let (state, _) = release_installment(create_ctx(get_approver_address(), 5), state);
+    let (state, events) = claim(create_ctx(get_sender_address(), 10), state);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_receiver_address(), 30),
+            expected_transfer(get_token_address(), get_sender_address(), 10),
+        ]
+    );
+    assert!(state.balances.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "An installment has already been released in the current period")]
+pub fn test_subscription_release_twice_in_period() {
+    let state = funded_subscription();
+    let (state, _) = release_installment(create_ctx(get_approver_address(), 2), state);
+    release_installment(create_ctx(get_approver_address(), 3), state);
+}
+
+#[test]
+#[should_panic(expected = "Cannot approve a subscription, release installments instead")]
+pub fn test_subscription_cannot_be_approved() {
+    approve(
+        create_ctx(get_approver_address(), 5),
+        funded_subscription(),
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+}
+
+#[test]
+pub fn test_query_status_returns_status() {
+    let keeper = create_address(AddressType::PublicContract, 8);
+    let (_, events) = query_status(create_ctx(keeper, 2), funded_escrow());
+
+    let mut expected_event = EventGroup::builder();
+    expected_event.return_data(STATE_AWAITING_APPROVAL);
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+fn expected_notification(
+    watchers: &[Address],
+    transition: EscrowTransition,
+    status: u8,
+) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    for watcher in watchers {
+        expected_event
+            .call(*watcher, Shortname::from_u32(1))
+            .argument(transition)
+            .argument(status)
+            .done();
+    }
+    expected_event.build()
+}
+
+#[test]
+pub fn test_watchers_are_notified_of_transitions() {
+    let watchers = vec![
+        create_address(AddressType::PublicContract, 9),
+        create_address(AddressType::PublicContract, 10),
+    ];
+    let (state, events) = set_watchers(
+        create_ctx(get_receiver_address(), 2),
+        funded_escrow(),
+        watchers.clone(),
+    );
+    assert!(events.is_empty());
+
+    let (state, events) = approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    assert_eq!(
+        events,
+        vec![expected_notification(
+            &watchers,
+            EscrowTransition::Approved {},
+            STATE_APPROVED
+        )]
+    );
+
+    let (_, events) = claim(create_ctx(get_receiver_address(), 6), state);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_receiver_address(), 100),
+            expected_notification(&watchers, EscrowTransition::Claimed {}, STATE_APPROVED),
+        ]
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "Only the sender and the receiver in the escrow transfer can set the watchers"
+)]
+pub fn test_set_watchers_not_party() {
+    set_watchers(
+        create_ctx(get_approver_address(), 2),
+        funded_escrow(),
+        vec![create_address(AddressType::PublicContract, 9)],
+    );
+}
+
+#[test]
+#[should_panic(expected = "Watchers must be public contracts")]
+pub fn test_set_watchers_account() {
+    set_watchers(
+        create_ctx(get_sender_address(), 2),
+        funded_escrow(),
+        vec![create_address(AddressType::Account, 9)],
+    );
+}
+
+#[test]
+pub fn test_history_records_transitions() {
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        funded_escrow(),
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    let (state, _) = claim(create_ctx(get_receiver_address(), 6), state);
+    let deposited = vec![TokenAmount {
+        token: get_token_address(),
+        amount: 100,
+    }];
+    assert_eq!(
+        state.history,
+        vec![
+            HistoryEntry {
+                transition: EscrowTransition::DepositConfirmed {},
+                actor: get_sender_address(),
+                amounts: deposited.clone(),
+                time_millis: 3_600_000,
+            },
+            HistoryEntry {
+                transition: EscrowTransition::Approved {},
+                actor: get_approver_address(),
+                amounts: vec![],
+                time_millis: 5 * 3_600_000,
+            },
+            HistoryEntry {
+                transition: EscrowTransition::Claimed {},
+                actor: get_receiver_address(),
+                amounts: deposited,
+                time_millis: 6 * 3_600_000,
+            },
+        ]
+    );
+}
+
+#[test]
+pub fn test_export_receipt_sends_history_to_audit_contract() {
+    let audit_contract = create_address(AddressType::PublicContract, 11);
+    let (state, _) = set_audit_contract(
+        create_ctx(get_sender_address(), 2),
+        funded_escrow(),
+        Some(audit_contract),
+    );
+    let keeper = create_address(AddressType::Account, 12);
+    let (state, events) = export_receipt(create_ctx(keeper, 3), state);
+
+    let receipt = EscrowReceipt {
+        escrow: get_contract_address(),
+        sender: get_sender_address(),
+        receiver: get_receiver_address(),
+        approver: get_approver_address(),
+        status: STATE_AWAITING_APPROVAL,
+        history: state.history.clone(),
+    };
+    let mut payload = vec![];
+    receipt.rpc_write_to(&mut payload).unwrap();
+    let digest: Hash = sha2::Sha256::digest(&payload).into();
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(audit_contract, Shortname::from_u32(1))
+        .argument(payload)
+        .argument(digest)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+#[should_panic(expected = "No audit contract has been set")]
+pub fn test_export_receipt_without_audit_contract() {
+    export_receipt(create_ctx(get_approver_address(), 3), funded_escrow());
+}
+
+#[test]
+#[should_panic(expected = "The audit contract must be a public contract")]
+pub fn test_set_audit_contract_account() {
+    set_audit_contract(
+        create_ctx(get_receiver_address(), 2),
+        funded_escrow(),
+        Some(create_address(AddressType::Account, 11)),
+    );
+}
+
+/// Creates a reverse escrow of 100 tokens with a deadline at hour 10, where the receiver has
+/// posted a security deposit of 20 tokens.
+fn funded_reverse_escrow() -> ContractState {
+    let state = initialize(
+        create_ctx(get_sender_address(), 0),
+        get_sender_address(),
+        get_receiver_address(),
+        get_approver_address(),
+        get_token_address(),
+        10,
+        AssetKind::Mpc20Token {},
+        vec![],
+        0,
+        None,
+        Some(20),
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 1),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        100,
+    );
+    let (state, events) = post_security_deposit(create_ctx(get_receiver_address(), 2), state);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(3))
+        .argument(get_receiver_address())
+        .argument(get_contract_address())
+        .argument(20u128)
+        .done();
+    expected_event
+        .with_callback(SHORTNAME_SECURITY_DEPOSIT_CALLBACK)
+        .argument(20u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    let (state, _) = security_deposit_callback(
+        create_ctx(get_receiver_address(), 2),
+        create_callback_ctx(true),
+        state,
+        20,
+    );
+    assert_eq!(state.security_deposit, 20);
+    state
+}
+
+#[test]
+pub fn test_security_deposit_is_returned_on_claim() {
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        funded_reverse_escrow(),
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    let (state, events) = claim(create_ctx(get_receiver_address(), 6), state);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_receiver_address(), 100),
+            expected_transfer(get_token_address(), get_receiver_address(), 20),
+        ]
+    );
+    assert_eq!(state.security_deposit, 0);
+}
+
+#[test]
+pub fn test_flagged_breach_forfeits_security_deposit() {
+    let (state, events) = flag_breach(
+        create_ctx(get_approver_address(), 5),
+        funded_reverse_escrow(),
+    );
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            20
+        )]
+    );
+    assert_eq!(state.status, STATE_BREACHED);
+    assert_eq!(
+        state.history.last().unwrap().transition,
+        EscrowTransition::BreachFlagged {}
+    );
+
+    // The sender can claim the escrowed tokens before the deadline
+    let (state, events) = claim(create_ctx(get_sender_address(), 6), state);
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            100
+        )]
+    );
+    assert!(state.balances.is_empty());
+}
+
+#[test]
+pub fn test_security_deposit_is_returned_on_refund() {
+    let keeper = create_address(AddressType::Account, 8);
+    let (_, events) = trigger_refund(create_ctx(keeper, 10), funded_reverse_escrow());
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_sender_address(), 100),
+            expected_transfer(get_token_address(), get_receiver_address(), 20),
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Cannot approve before the receiver has posted the security deposit")]
+pub fn test_reverse_escrow_cannot_be_approved_without_security_deposit() {
+    let state = initialize(
+        create_ctx(get_sender_address(), 0),
+        get_sender_address(),
+        get_receiver_address(),
+        get_approver_address(),
+        get_token_address(),
+        10,
+        AssetKind::Mpc20Token {},
+        vec![],
+        0,
+        None,
+        Some(20),
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 1),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        100,
+    );
+    approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+}
+
+#[test]
+pub fn test_deposit_after_breach_is_returned() {
+    let (state, _) = flag_breach(
+        create_ctx(get_approver_address(), 5),
+        funded_reverse_escrow(),
+    );
+    // A deposit that was in flight when the breach was flagged
+    let (state, events) = deposit_callback(
+        create_ctx(get_sender_address(), 5),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        30,
+    );
+    assert_eq!(state.status, STATE_BREACHED);
+    assert_eq!(state.balances.get(&get_token_address()), Some(&100));
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            30
+        )]
+    );
+}
+
+#[test]
+pub fn test_deposit_after_approval_is_returned() {
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        funded_escrow(),
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    let (state, events) = deposit_callback(
+        create_ctx(get_sender_address(), 5),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        30,
+    );
+    assert_eq!(state.status, STATE_APPROVED);
+    assert_eq!(state.balances.get(&get_token_address()), Some(&100));
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            30
+        )]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only the designated approver can flag a breach")]
+pub fn test_only_approver_can_flag_breach() {
+    flag_breach(create_ctx(get_sender_address(), 5), funded_reverse_escrow());
+}
+
+/// Creates an escrow of native MPC tokens with a deadline at hour 10, awaiting a deposit.
+fn native_escrow() -> ContractState {
+    initialize(
+        create_ctx(get_sender_address(), 0),
+        get_sender_address(),
+        get_receiver_address(),
+        get_approver_address(),
+        get_token_address(),
+        10,
+        AssetKind::NativeMpc {},
+        vec![],
+        0,
+        None,
+        None,
+    )
+}
+
+#[test]
+pub fn test_deposit_native() {
+    let (_, events) = deposit_native(create_ctx(get_sender_address(), 1), native_escrow(), 100);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(MPC_TOKEN_ADDRESS, Shortname::from_u32(3))
+        .from_original_sender()
+        .argument(get_contract_address())
+        .argument(100u64)
+        .done();
+    expected_event
+        .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(MPC_TOKEN_ADDRESS)
+        .argument(100u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+#[should_panic(expected = "Native MPC tokens can only be transferred from account senders")]
+pub fn test_deposit_native_from_contract_sender() {
+    let sender = create_address(AddressType::PublicContract, 9);
+    let mut state = native_escrow();
+    state.sender = sender;
+    deposit_native(create_ctx(sender, 1), state, 100);
+}
+
+#[test]
+#[should_panic(expected = "Amount of native MPC tokens is too large")]
+pub fn test_deposit_native_amount_overflow() {
+    deposit_native(
+        create_ctx(get_sender_address(), 1),
+        native_escrow(),
+        u64::MAX as u128 + 1,
+    );
+}
+
+#[test]
+pub fn test_native_payout() {
+    let (state, _) = deposit_callback(
+        create_ctx(get_sender_address(), 1),
+        create_callback_ctx(true),
+        native_escrow(),
+        MPC_TOKEN_ADDRESS,
+        100,
+    );
+    let (state, _) = approve(
+        create_ctx(get_approver_address(), 5),
+        state,
+        [7u8; 32],
+        "Delivery confirmed".to_string(),
+    );
+    let (state, events) = claim(create_ctx(get_receiver_address(), 6), state);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(MPC_TOKEN_ADDRESS, Shortname::from_u32(3))
+        .argument(get_receiver_address())
+        .argument(100u64)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+    assert!(state.balances.is_empty());
+}
+
+#[test]
+pub fn test_cancel_proposal_alone() {
+    let (state, events) =
+        cancel_by_mutual_consent(create_ctx(get_sender_address(), 5), funded_escrow());
+    assert!(events.is_empty());
+    assert_eq!(state.status, STATE_AWAITING_APPROVAL);
+    assert!(state.cancel_proposals.contains(&get_sender_address()));
+    assert_eq!(state.balances.get(&get_token_address()), Some(&100));
+
+    // Proposing again does not cancel the escrow
+    let (state, events) = cancel_by_mutual_consent(create_ctx(get_sender_address(), 6), state);
+    assert!(events.is_empty());
+    assert_eq!(state.status, STATE_AWAITING_APPROVAL);
+}
+
+#[test]
+pub fn test_cancel_by_mutual_consent_refunds_sender() {
+    let (state, _) = cancel_by_mutual_consent(create_ctx(get_sender_address(), 5), funded_escrow());
+    let (state, events) = cancel_by_mutual_consent(create_ctx(get_receiver_address(), 6), state);
+    assert_eq!(state.status, STATE_CANCELLED);
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            100
+        )]
+    );
+    assert!(state.has_no_balance());
+    let entry = state.history.last().unwrap();
+    assert_eq!(entry.transition, EscrowTransition::Cancelled {});
+    assert_eq!(entry.actor, get_receiver_address());
+    assert_eq!(
+        entry.amounts,
+        vec![TokenAmount {
+            token: get_token_address(),
+            amount: 100,
+        }]
+    );
+}
+
+#[test]
+pub fn test_cancel_by_mutual_consent_returns_security_deposit() {
+    let (state, _) = cancel_by_mutual_consent(
+        create_ctx(get_receiver_address(), 5),
+        funded_reverse_escrow(),
+    );
+    let (_, events) = cancel_by_mutual_consent(create_ctx(get_sender_address(), 6), state);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_token_address(), get_sender_address(), 100),
+            expected_transfer(get_token_address(), get_receiver_address(), 20),
+        ]
+    );
+}
+
+#[test]
+pub fn test_deposit_after_cancellation_is_returned() {
+    let (state, _) = cancel_by_mutual_consent(create_ctx(get_sender_address(), 5), funded_escrow());
+    let (state, _) = cancel_by_mutual_consent(create_ctx(get_receiver_address(), 6), state);
+    // A deposit that was in flight when the escrow was cancelled
+    let (state, events) = deposit_callback(
+        create_ctx(get_sender_address(), 6),
+        create_callback_ctx(true),
+        state,
+        get_token_address(),
+        30,
+    );
+    assert_eq!(state.status, STATE_CANCELLED);
+    assert!(state.has_no_balance());
+    assert_eq!(
+        events,
+        vec![expected_transfer(
+            get_token_address(),
+            get_sender_address(),
+            30
+        )]
+    );
+}
+
+#[test]
+#[should_panic(expected = "The escrow has already been cancelled")]
+pub fn test_cancel_after_cancellation() {
+    let (state, _) = cancel_by_mutual_consent(create_ctx(get_sender_address(), 5), funded_escrow());
+    let (state, _) = cancel_by_mutual_consent(create_ctx(get_receiver_address(), 6), state);
+    cancel_by_mutual_consent(create_ctx(get_sender_address(), 7), state);
+}
+
+#[test]
+pub fn test_extend_deadline_agreed() {
+    let new_deadline = 20 * 3_600_000;
+    let (state, _) = extend_deadline(
+        create_ctx(get_sender_address(), 5),
+        funded_escrow(),
+        new_deadline,
+    );
+    // A proposal alone does not extend the deadline
+    assert_eq!(state.end_time_millis, 10 * 3_600_000);
+    assert_eq!(
+        state.extension_proposals.get(&get_sender_address()),
+        Some(&new_deadline)
+    );
+
+    let (state, _) = extend_deadline(create_ctx(get_receiver_address(), 6), state, new_deadline);
+    assert_eq!(state.end_time_millis, new_deadline);
+    assert!(state.extension_proposals.is_empty());
+
+    // Tokens can be deposited until the new deadline
+    let (_, events) = deposit(
+        create_ctx(get_sender_address(), 15),
+        state,
+        get_token_address(),
+        10,
+    );
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+pub fn test_extend_deadline_mismatched_proposals() {
+    let (state, _) = extend_deadline(
+        create_ctx(get_sender_address(), 5),
+        funded_escrow(),
+        20 * 3_600_000,
+    );
+    let (state, _) = extend_deadline(create_ctx(get_receiver_address(), 6), state, 30 * 3_600_000);
+    assert_eq!(state.end_time_millis, 10 * 3_600_000);
+    assert_eq!(state.extension_proposals.len(), 2);
+
+    // The sender agrees to the proposal of the receiver by proposing it as well
+    let (state, _) = extend_deadline(create_ctx(get_sender_address(), 7), state, 30 * 3_600_000);
+    assert_eq!(state.end_time_millis, 30 * 3_600_000);
+    assert!(state.extension_proposals.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "The new deadline must be later than the current deadline")]
+pub fn test_extend_deadline_not_later() {
+    extend_deadline(
+        create_ctx(get_sender_address(), 5),
+        funded_escrow(),
+        10 * 3_600_000,
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "Only the sender and the receiver in the escrow transfer can extend the deadline"
+)]
+pub fn test_extend_deadline_by_approver() {
+    extend_deadline(
+        create_ctx(get_approver_address(), 5),
+        funded_escrow(),
+        20 * 3_600_000,
+    );
+}
@@ -0,0 +1,363 @@
+//! This is an example all-or-nothing Crowdfunding contract
+//!
+//! A creator raises funds in a specific token type for a project, with a funding goal and a
+//! deadline.
+//! Until the deadline anyone can `contribute` tokens to the campaign, which transfers the tokens
+//! from the contributor to the contract.
+//! If the funding goal has been reached at the deadline the campaign succeeded, and the creator
+//! can `claim_funds` to receive all contributed tokens.
+//! Otherwise the campaign failed, and each contributor can `reclaim` their contributions.
+//!
+//! The contributions are kept in a claim map, such that a failed campaign refunds the
+//! contributors through the pull payment pattern of the claim-map library.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use std::collections::BTreeMap;
+
+use claim_map::{payout_event_group, ClaimMap, Payout};
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+
+#[cfg(test)]
+mod tests;
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `creator`: [`Address`], the creator of the campaign, receiving the funds if it succeeds.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `funding_goal`: [`u128`], the amount of tokens required for the campaign to succeed.
+///
+///   * `deadline_millis`: [`i64`], the deadline of the campaign in milliseconds.
+///
+///   * `total_raised`: [`u128`], the total amount of tokens contributed.
+///
+///   * `contributions`: [`BTreeMap<Address, u128>`], the claim map of the contributions of each
+///     contributor, which can be reclaimed if the campaign fails.
+///
+///   * `funds_claimed`: [`bool`], whether the creator has claimed the funds.
+///
+#[state]
+pub struct CrowdfundingState {
+    creator: Address,
+    token_type: Address,
+    funding_goal: u128,
+    deadline_millis: i64,
+    total_raised: u128,
+    contributions: BTreeMap<Address, u128>,
+    funds_claimed: bool,
+}
+
+impl CrowdfundingState {
+    /// Determines whether the campaign has ended at the given time.
+    fn has_ended(&self, time_millis: i64) -> bool {
+        time_millis >= self.deadline_millis
+    }
+
+    /// Determines whether the funding goal has been reached.
+    fn goal_reached(&self) -> bool {
+        self.total_raised >= self.funding_goal
+    }
+}
+
+/// Initial function to bootstrap the contract's state. The creator of the campaign is the sender
+/// of the initialization.
+///
+/// ### Parameters
+///
+///   * `context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `funding_goal`: [`u128`], the amount of tokens required for the campaign to succeed.
+///
+///   * `hours_until_deadline`: [`u32`], the number of hours until the deadline gets passed.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`] with no contributions.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    token_type: Address,
+    funding_goal: u128,
+    hours_until_deadline: u32,
+) -> CrowdfundingState {
+    if token_type.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract raising a non publicContract token");
+    }
+    if funding_goal == 0 {
+        panic!("The funding goal must be positive");
+    }
+    let millis_until_deadline = i64::from(hours_until_deadline) * 60 * 60 * 1000;
+    CrowdfundingState {
+        creator: context.sender,
+        token_type,
+        funding_goal,
+        deadline_millis: context.block_production_time + millis_until_deadline,
+        total_raised: 0,
+        contributions: BTreeMap::new(),
+        funds_claimed: false,
+    }
+}
+
+/// Action for contributing tokens to the campaign. Throws an error if the deadline has passed.
+/// The function creates a transfer event of tokens from the contributor to the contract, and
+/// a callback to `contribute_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of tokens to contribute.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`CrowdfundingState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x01)]
+pub fn contribute(
+    context: ContractContext,
+    state: CrowdfundingState,
+    amount: u128,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    if state.has_ended(context.block_production_time) {
+        panic!("Cannot contribute after the deadline is passed");
+    }
+    let mut e = EventGroup::builder();
+    e.call(state.token_type, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    e.with_callback(SHORTNAME_CONTRIBUTE_CALLBACK)
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    (state, vec![e.build()])
+}
+
+/// Callback for contributing tokens. If the transfer was successful the contribution is credited
+/// to the contributor. If the deadline passed while the transfer was in flight, the contribution
+/// is returned to the contributor instead. If the transfer failed the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// * `contributor`: [`Address`], the address of the contributor.
+///
+/// * `amount`: [`u128`], the amount of contributed tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`].
+///
+#[callback(shortname = 0x02)]
+pub fn contribute_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: CrowdfundingState,
+    contributor: Address,
+    amount: u128,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for contribution");
+    }
+    if state.has_ended(ctx.block_production_time) {
+        let payouts = [Payout {
+            token: state.token_type,
+            amount,
+        }];
+        let event_group = payout_event_group(contributor, &payouts);
+        return (state, vec![event_group.build()]);
+    }
+    let mut new_state = state;
+    new_state.contributions.credit(contributor, amount);
+    new_state.total_raised += amount;
+    (new_state, vec![])
+}
+
+/// Action for the creator to claim the funds of a successful campaign. Throws an error if not
+/// called by the `creator`, if the deadline has not passed, if the funding goal was not reached,
+/// or if the funds have already been claimed. The funds are marked as claimed until
+/// `claim_funds_callback` finds that the transfer failed.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`] and the event group containing the
+/// transfer event.
+///
+#[action(shortname = 0x03)]
+pub fn claim_funds(
+    context: ContractContext,
+    state: CrowdfundingState,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    if context.sender != state.creator {
+        panic!("Only the creator can claim the funds");
+    }
+    if !state.has_ended(context.block_production_time) {
+        panic!("Cannot claim the funds before the deadline is passed");
+    }
+    if !state.goal_reached() {
+        panic!("Cannot claim the funds since the funding goal was not reached");
+    }
+    if state.funds_claimed {
+        panic!("The funds have already been claimed");
+    }
+
+    let payouts = [Payout {
+        token: state.token_type,
+        amount: state.total_raised,
+    }];
+    let mut event_group = payout_event_group(state.creator, &payouts);
+    event_group
+        .with_callback(SHORTNAME_CLAIM_FUNDS_CALLBACK)
+        .argument(state.total_raised)
+        .done();
+
+    let mut new_state = state;
+    new_state.funds_claimed = true;
+    (new_state, vec![event_group.build()])
+}
+
+/// Callback for claiming the funds. If the transfer failed the funds are marked as unclaimed
+/// again, such that the creator can claim them again.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of claimed tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`].
+///
+#[callback(shortname = 0x05)]
+pub fn claim_funds_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: CrowdfundingState,
+    _amount: u128,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.success {
+        new_state.funds_claimed = false;
+    }
+    (new_state, vec![])
+}
+
+/// Action for a contributor to reclaim their contributions to a failed campaign. Throws an error
+/// if the deadline has not passed or if the funding goal was reached. If the sender has no
+/// contributions to reclaim nothing happens. The transfer is verified by `reclaim_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`] and an event group possibly containing a
+/// transfer event.
+///
+#[action(shortname = 0x04)]
+pub fn reclaim(
+    context: ContractContext,
+    state: CrowdfundingState,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    if !state.has_ended(context.block_production_time) {
+        panic!("Cannot reclaim contributions before the deadline is passed");
+    }
+    if state.goal_reached() {
+        panic!("Cannot reclaim contributions since the funding goal was reached");
+    }
+
+    let mut new_state = state;
+    match new_state.contributions.take_claim(&context.sender) {
+        Some(amount) if amount > 0 => {
+            let payouts = [Payout {
+                token: new_state.token_type,
+                amount,
+            }];
+            let mut event_group = payout_event_group(context.sender, &payouts);
+            event_group
+                .with_callback(SHORTNAME_RECLAIM_CALLBACK)
+                .argument(context.sender)
+                .argument(amount)
+                .done();
+            (new_state, vec![event_group.build()])
+        }
+        _ => (new_state, vec![]),
+    }
+}
+
+/// Callback for reclaiming contributions. If the transfer failed the contributions are credited
+/// back to the contributor, such that they can be reclaimed again.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`CrowdfundingState`], the current state of the contract.
+///
+/// * `contributor`: [`Address`], the address of the contributor.
+///
+/// * `amount`: [`u128`], the amount of reclaimed tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`CrowdfundingState`].
+///
+#[callback(shortname = 0x06)]
+pub fn reclaim_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: CrowdfundingState,
+    contributor: Address,
+    amount: u128,
+) -> (CrowdfundingState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let payouts = [Payout {
+        token: new_state.token_type,
+        amount,
+    }];
+    new_state
+        .contributions
+        .restore_if_failed(&callback_ctx, contributor, &payouts, |_, amount| amount);
+    (new_state, vec![])
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
@@ -0,0 +1,219 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+
+use crate::{
+    claim_funds, claim_funds_callback, contribute_callback, initialize, reclaim, reclaim_callback,
+    CrowdfundingState,
+};
+
+fn create_ctx(sender: Address, block_time: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time,
+        block_production_time: block_time * 3_600_000,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_creator_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_contributor_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn get_token_address() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// Creates a campaign with a funding goal of 1000 tokens and a deadline at hour 10, with a
+/// contribution of `contributed` tokens.
+fn campaign_with_contribution(contributed: u128) -> CrowdfundingState {
+    let state = initialize(
+        create_ctx(get_creator_address(), 0),
+        get_token_address(),
+        1000,
+        10,
+    );
+    let (state, _) = contribute_callback(
+        create_ctx(get_contributor_address(), 5),
+        create_callback_ctx(true),
+        state,
+        get_contributor_address(),
+        contributed,
+    );
+    state
+}
+
+fn expected_transfer(to: Address, amount: u128) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(1))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event.build()
+}
+
+#[test]
+pub fn test_claim_funds() {
+    let state = campaign_with_contribution(1000);
+    let (state, events) = claim_funds(create_ctx(get_creator_address(), 10), state);
+    assert!(state.funds_claimed);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(1))
+        .argument(get_creator_address())
+        .argument(1000u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x05))
+        .argument(1000u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+pub fn test_claim_funds_callback_failed() {
+    let state = campaign_with_contribution(1000);
+    let (state, _) = claim_funds(create_ctx(get_creator_address(), 10), state);
+    let (state, _) = claim_funds_callback(
+        create_ctx(get_creator_address(), 10),
+        create_callback_ctx(false),
+        state,
+        1000,
+    );
+    assert!(!state.funds_claimed);
+
+    // The funds can be claimed again
+    let (state, events) = claim_funds(create_ctx(get_creator_address(), 11), state);
+    assert!(state.funds_claimed);
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+pub fn test_claim_funds_callback_succeeded() {
+    let state = campaign_with_contribution(1000);
+    let (state, _) = claim_funds(create_ctx(get_creator_address(), 10), state);
+    let (state, _) = claim_funds_callback(
+        create_ctx(get_creator_address(), 10),
+        create_callback_ctx(true),
+        state,
+        1000,
+    );
+    assert!(state.funds_claimed);
+}
+
+#[test]
+#[should_panic(expected = "Cannot claim the funds since the funding goal was not reached")]
+pub fn test_claim_funds_goal_not_reached() {
+    let state = campaign_with_contribution(999);
+    claim_funds(create_ctx(get_creator_address(), 10), state);
+}
+
+#[test]
+#[should_panic(expected = "Cannot claim the funds before the deadline is passed")]
+pub fn test_claim_funds_before_deadline() {
+    let state = campaign_with_contribution(1000);
+    claim_funds(create_ctx(get_creator_address(), 9), state);
+}
+
+#[test]
+pub fn test_reclaim() {
+    let state = campaign_with_contribution(999);
+    let (state, events) = reclaim(create_ctx(get_contributor_address(), 10), state);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(1))
+        .argument(get_contributor_address())
+        .argument(999u128)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(0x06))
+        .argument(get_contributor_address())
+        .argument(999u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+    assert_eq!(
+        *state.contributions.get(&get_contributor_address()).unwrap(),
+        0
+    );
+
+    // Reclaiming again transfers nothing
+    let (_, events) = reclaim(create_ctx(get_contributor_address(), 11), state);
+    assert_eq!(events.len(), 0);
+}
+
+#[test]
+pub fn test_reclaim_callback_failed() {
+    let state = campaign_with_contribution(999);
+    let (state, _) = reclaim(create_ctx(get_contributor_address(), 10), state);
+    let (state, _) = reclaim_callback(
+        create_ctx(get_contributor_address(), 10),
+        create_callback_ctx(false),
+        state,
+        get_contributor_address(),
+        999,
+    );
+    assert_eq!(
+        *state.contributions.get(&get_contributor_address()).unwrap(),
+        999
+    );
+
+    // The contributions can be reclaimed again
+    let (_, events) = reclaim(create_ctx(get_contributor_address(), 11), state);
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Cannot reclaim contributions since the funding goal was reached")]
+pub fn test_reclaim_goal_reached() {
+    let state = campaign_with_contribution(1000);
+    reclaim(create_ctx(get_contributor_address(), 10), state);
+}
+
+#[test]
+pub fn test_contribute_callback_after_deadline() {
+    let state = campaign_with_contribution(500);
+    let (state, events) = contribute_callback(
+        create_ctx(get_contributor_address(), 10),
+        create_callback_ctx(true),
+        state,
+        get_contributor_address(),
+        600,
+    );
+    assert_eq!(state.total_raised, 500);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_contributor_address(), 600)]
+    );
+}
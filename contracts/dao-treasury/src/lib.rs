@@ -0,0 +1,338 @@
+//! Example smart contract implementing a DAO treasury, holding tokens that are spent by vote of
+//! its members.
+//!
+//! How it works
+//! * The treasury is initialized with a fixed list of members and the duration of votes. Tokens
+//!   are added to the treasury by transferring them to the address of the contract.
+//! * Any member can propose to spend tokens, given by the recipient, the token and the amount.
+//!   The vote on the proposal runs until the deadline, which is the time of the proposal plus the
+//!   duration of votes.
+//! * Until the deadline, each member can vote for or against the proposal once.
+//! * Once a majority of the members has voted for the proposal, it passes, and the treasury
+//!   transfers the tokens to the recipient in the same transaction. A callback records whether
+//!   the transfer succeeded.
+//! * Once a majority can no longer be reached, the proposal is rejected. A proposal without a
+//!   majority when the deadline passes can be closed by anyone, rejecting it.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// The status of a spending proposal.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum ProposalStatus {
+    /// The members are voting on the proposal.
+    #[discriminant(0)]
+    Voting {},
+    /// The proposal passed, and the transfer is awaiting the callback.
+    #[discriminant(1)]
+    Executing {},
+    /// The tokens were transferred to the recipient.
+    #[discriminant(2)]
+    Executed {},
+    /// The transfer of the tokens failed.
+    #[discriminant(3)]
+    Failed {},
+    /// The proposal did not get the votes of a majority of the members.
+    #[discriminant(4)]
+    Rejected {},
+}
+
+/// A proposal to spend tokens of the treasury.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct SpendingProposal {
+    /// The member who made the proposal.
+    pub proposer: Address,
+    /// The address receiving the tokens.
+    pub recipient: Address,
+    /// The address of the token contract of the spent tokens.
+    pub token: Address,
+    /// The amount of tokens spent.
+    pub amount: u128,
+    /// The deadline of the vote in UTC millis
+    /// (milliseconds after 1970-01-01 00:00:00 UTC)
+    pub deadline_utc_millis: i64,
+    /// The members who voted for the proposal.
+    pub votes_for: BTreeSet<Address>,
+    /// The members who voted against the proposal.
+    pub votes_against: BTreeSet<Address>,
+    /// The status of the proposal.
+    pub status: ProposalStatus,
+}
+
+/// The state of the treasury, which is persisted on-chain.
+#[state]
+pub struct TreasuryState {
+    /// The members of the treasury, who propose and vote on spending.
+    pub members: Vec<Address>,
+    /// The duration of the vote on each proposal in millis.
+    pub voting_duration_millis: i64,
+    /// The spending proposals, indexed by their id.
+    pub proposals: BTreeMap<u64, SpendingProposal>,
+    /// The id of the next proposal.
+    pub next_proposal_id: u64,
+}
+
+impl TreasuryState {
+    /// Asserts that `address` is one of the members.
+    fn assert_member(&self, address: &Address) {
+        assert!(
+            self.members.contains(address),
+            "Only members can propose and vote on spending"
+        );
+    }
+
+    /// The number of votes required for a proposal to pass, being a majority of the members.
+    fn majority(&self) -> usize {
+        self.members.len() / 2 + 1
+    }
+
+    /// Returns the proposal with the given id, panicking if it doesn't exist or the vote on it
+    /// has ended.
+    fn proposal_in_vote(&mut self, proposal_id: u64) -> &mut SpendingProposal {
+        let proposal = self
+            .proposals
+            .get_mut(&proposal_id)
+            .unwrap_or_else(|| panic!("No proposal with id {proposal_id}"));
+        assert!(
+            proposal.status == ProposalStatus::Voting {},
+            "The vote on the proposal has ended"
+        );
+        proposal
+    }
+
+    /// Decides the proposal if a majority has voted for it, or can no longer vote for it.
+    /// A passed proposal transfers the tokens to the recipient.
+    ///
+    /// # Arguments
+    ///
+    /// * `proposal_id` - the id of the proposal.
+    ///
+    /// # Returns
+    ///
+    /// The event groups containing the transfer, which are empty unless the proposal passed.
+    ///
+    fn decide_if_majority(&mut self, proposal_id: u64) -> Vec<EventGroup> {
+        let majority = self.majority();
+        let members = self.members.len();
+        let proposal = self.proposal_in_vote(proposal_id);
+        if proposal.votes_against.len() > members - majority {
+            proposal.status = ProposalStatus::Rejected {};
+            return vec![];
+        }
+        if proposal.votes_for.len() < majority {
+            return vec![];
+        }
+        proposal.status = ProposalStatus::Executing {};
+
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(proposal.token, token_contract_transfer())
+            .argument(proposal.recipient)
+            .argument(proposal.amount)
+            .done();
+        event_group
+            .with_callback(SHORTNAME_TRANSFER_CALLBACK)
+            .argument(proposal_id)
+            .done();
+        vec![event_group.build()]
+    }
+}
+
+/// Initialize a new treasury.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `members` - the members of the treasury.
+/// * `voting_duration_millis` - the duration of the vote on each proposal in millis.
+///
+/// # Returns
+///
+/// The initial state of the treasury, with no proposals.
+///
+#[init]
+pub fn initialize(
+    ctx: ContractContext,
+    members: Vec<Address>,
+    voting_duration_millis: i64,
+) -> TreasuryState {
+    assert_ne!(members.len(), 0, "Members are required");
+    let unique_members: BTreeSet<Address> = members.iter().copied().collect();
+    assert_eq!(unique_members.len(), members.len(), "Duplicate members");
+    assert!(
+        voting_duration_millis > 0,
+        "The duration of votes must be positive"
+    );
+    TreasuryState {
+        members,
+        voting_duration_millis,
+        proposals: BTreeMap::new(),
+        next_proposal_id: 0,
+    }
+}
+
+/// Propose to spend tokens of the treasury. The proposer votes for the proposal, which passes
+/// immediately if the proposer is a majority of the members.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the treasury.
+/// * `recipient` - the address receiving the tokens.
+/// * `token` - the address of the token contract of the spent tokens.
+/// * `amount` - the amount of tokens spent.
+///
+/// # Returns
+///
+/// The updated state with the new proposal, and the transfer if the proposal passed.
+///
+#[action(shortname = 0x01)]
+pub fn propose(
+    ctx: ContractContext,
+    state: TreasuryState,
+    recipient: Address,
+    token: Address,
+    amount: u128,
+) -> (TreasuryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    new_state.assert_member(&ctx.sender);
+    assert_eq!(
+        token.address_type,
+        AddressType::PublicContract,
+        "The token must be a public contract"
+    );
+    assert!(amount > 0, "The amount must be positive");
+    let proposal_id = new_state.next_proposal_id;
+    new_state.next_proposal_id += 1;
+    new_state.proposals.insert(
+        proposal_id,
+        SpendingProposal {
+            proposer: ctx.sender,
+            recipient,
+            token,
+            amount,
+            deadline_utc_millis: ctx.block_production_time + new_state.voting_duration_millis,
+            votes_for: BTreeSet::from([ctx.sender]),
+            votes_against: BTreeSet::new(),
+            status: ProposalStatus::Voting {},
+        },
+    );
+    let events = new_state.decide_if_majority(proposal_id);
+    (new_state, events)
+}
+
+/// Vote for or against a proposal until its deadline. Each member can vote once on each proposal.
+/// Transfers the tokens if the proposal passes.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the treasury.
+/// * `proposal_id` - the id of the proposal to vote on.
+/// * `approve` - whether the vote is for the proposal.
+///
+/// # Returns
+///
+/// The updated state, and the transfer if the proposal passed.
+///
+#[action(shortname = 0x02)]
+pub fn vote(
+    ctx: ContractContext,
+    state: TreasuryState,
+    proposal_id: u64,
+    approve: bool,
+) -> (TreasuryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    new_state.assert_member(&ctx.sender);
+    let proposal = new_state.proposal_in_vote(proposal_id);
+    assert!(
+        ctx.block_production_time < proposal.deadline_utc_millis,
+        "The deadline has passed"
+    );
+    assert!(
+        !proposal.votes_for.contains(&ctx.sender) && !proposal.votes_against.contains(&ctx.sender),
+        "The sender has already voted on the proposal"
+    );
+    if approve {
+        proposal.votes_for.insert(ctx.sender);
+    } else {
+        proposal.votes_against.insert(ctx.sender);
+    }
+    let events = new_state.decide_if_majority(proposal_id);
+    (new_state, events)
+}
+
+/// Close a proposal which has not passed by its deadline, rejecting it.
+/// Anyone can close a proposal once the deadline has passed.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the treasury.
+/// * `proposal_id` - the id of the proposal to close.
+///
+/// # Returns
+///
+/// The updated state with the proposal rejected.
+///
+#[action(shortname = 0x03)]
+pub fn close(ctx: ContractContext, state: TreasuryState, proposal_id: u64) -> TreasuryState {
+    let mut new_state = state;
+    let proposal = new_state.proposal_in_vote(proposal_id);
+    assert!(
+        ctx.block_production_time >= proposal.deadline_utc_millis,
+        "The deadline has not yet passed"
+    );
+    proposal.status = ProposalStatus::Rejected {};
+    new_state
+}
+
+/// Records the outcome of the transfer of a passed proposal.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the treasury.
+/// * `proposal_id` - the id of the passed proposal.
+///
+/// # Returns
+///
+/// The updated state.
+///
+#[callback(shortname = 0x10)]
+pub fn transfer_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: TreasuryState,
+    proposal_id: u64,
+) -> TreasuryState {
+    let mut new_state = state;
+    let proposal = new_state.proposals.get_mut(&proposal_id).unwrap();
+    proposal.status = if callback_ctx.success {
+        ProposalStatus::Executed {}
+    } else {
+        ProposalStatus::Failed {}
+    };
+    new_state
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
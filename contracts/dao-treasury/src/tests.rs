@@ -0,0 +1,145 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+
+use crate::{
+    close, initialize, propose, transfer_callback, vote, ProposalStatus, TreasuryState,
+    SHORTNAME_TRANSFER_CALLBACK,
+};
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn member(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+fn recipient() -> Address {
+    create_address(AddressType::Account, 50)
+}
+
+fn token() -> Address {
+    create_address(AddressType::PublicContract, 2)
+}
+
+/// A treasury with five members and votes lasting 100 millis, with one proposal by member 1.
+fn state_with_proposal() -> TreasuryState {
+    let state = initialize(create_ctx(member(1), 0), (1..=5).map(member).collect(), 100);
+    let (state, events) = propose(create_ctx(member(1), 10), state, recipient(), token(), 500);
+    assert_eq!(events.len(), 0);
+    state
+}
+
+fn callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![],
+    }
+}
+
+#[test]
+fn proposal_passes_with_majority_and_transfers_tokens() {
+    let mut state = state_with_proposal();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    let (state, events) = vote(create_ctx(member(3), 30), state, 0, true);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Executing {});
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x01))
+        .argument(recipient())
+        .argument(500u128)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_TRANSFER_CALLBACK)
+        .argument(0u64)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+
+    let state = transfer_callback(create_ctx(member(3), 30), callback_ctx(true), state, 0);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Executed {});
+}
+
+#[test]
+fn failed_transfer_is_recorded() {
+    let mut state = state_with_proposal();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    (state, _) = vote(create_ctx(member(3), 30), state, 0, true);
+    let state = transfer_callback(create_ctx(member(3), 30), callback_ctx(false), state, 0);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Failed {});
+}
+
+#[test]
+fn proposal_is_rejected_once_majority_is_unreachable() {
+    let mut state = state_with_proposal();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, false);
+    (state, _) = vote(create_ctx(member(3), 30), state, 0, false);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Voting {});
+    let (state, events) = vote(create_ctx(member(4), 40), state, 0, false);
+    assert_eq!(events.len(), 0);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Rejected {});
+}
+
+#[test]
+fn proposal_without_majority_is_closed_after_deadline() {
+    let mut state = state_with_proposal();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    let state = close(create_ctx(recipient(), 110), state, 0);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Rejected {});
+}
+
+#[test]
+fn sole_member_proposal_passes_immediately() {
+    let state = initialize(create_ctx(member(1), 0), vec![member(1)], 100);
+    let (state, events) = propose(create_ctx(member(1), 10), state, recipient(), token(), 500);
+    assert_eq!(events.len(), 1);
+    assert_eq!(state.proposals[&0].status, ProposalStatus::Executing {});
+}
+
+#[test]
+#[should_panic(expected = "The deadline has not yet passed")]
+fn cannot_close_before_deadline() {
+    close(create_ctx(member(2), 109), state_with_proposal(), 0);
+}
+
+#[test]
+#[should_panic(expected = "The deadline has passed")]
+fn cannot_vote_after_deadline() {
+    vote(create_ctx(member(2), 110), state_with_proposal(), 0, true);
+}
+
+#[test]
+#[should_panic(expected = "The sender has already voted on the proposal")]
+fn cannot_vote_twice() {
+    vote(create_ctx(member(1), 20), state_with_proposal(), 0, false);
+}
+
+#[test]
+#[should_panic(expected = "Only members can propose and vote on spending")]
+fn non_member_cannot_vote() {
+    vote(create_ctx(recipient(), 20), state_with_proposal(), 0, true);
+}
+
+#[test]
+#[should_panic(expected = "The vote on the proposal has ended")]
+fn cannot_vote_on_rejected_proposal() {
+    let state = close(create_ctx(member(2), 110), state_with_proposal(), 0);
+    vote(create_ctx(member(2), 20), state, 0, true);
+}
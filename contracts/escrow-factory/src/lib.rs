@@ -0,0 +1,381 @@
+//! This is the example escrow factory contract. This contract is able to deploy new conditional
+//! escrow transfer contracts on demand, such that a marketplace can create an escrow agreement for
+//! each deal without deploying the escrow contract itself. The contract keeps track of the
+//! deployed escrow contracts, their addresses and the creators that requested them, such that the
+//! escrows of each creator can be found.
+//!
+//! Anyone can deploy an escrow, and becomes the sender of the deployed escrow, who deposits the
+//! escrowed tokens. Each escrow is deployed with an MPC-20 token as its only accepted token, and
+//! without a subscription.
+//!
+//! The factory relays status queries to the deployed escrows. Anyone can ask the factory to
+//! `query_escrow_status`, which calls the `query_status` action of the escrow, and records the
+//! returned status, such that the progress of all escrows can be followed in the state of the
+//! factory.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::BTreeMap;
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::{ReadRPC, WriteRPC};
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+const PUB_DEPLOY_ADDRESS: Address = Address {
+    address_type: AddressType::SystemContract,
+    identifier: [
+        0x97, 0xa0, 0xe2, 0x38, 0xe9, 0x24, 0x02, 0x5b, 0xad, 0x14, 0x4a, 0xa0, 0xc4, 0x91, 0x3e,
+        0x46, 0x30, 0x8f, 0x9a, 0x4d,
+    ],
+};
+
+/// The parameters of a deployed escrow contract. Mirrors the corresponding arguments of the
+/// `initialize` function of the conditional escrow transfer contract.
+///
+/// ### Fields:
+///
+/// * `receiver`: [`Address`], the receiver of the escrowed tokens.
+/// * `approver`: [`Address`], the approver signalling the fulfilment of the condition.
+/// * `token_type`: [`Address`], the address of the escrowed token.
+/// * `hours_until_deadline`: [`u32`], the number of hours until the deadline of the escrow.
+/// * `refund_bounty_per_mille`: [`u32`], the bounty for triggering a refund after the deadline.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct EscrowParameters {
+    receiver: Address,
+    approver: Address,
+    token_type: Address,
+    hours_until_deadline: u32,
+    refund_bounty_per_mille: u32,
+}
+
+/// The status of the deployment of an escrow contract.
+#[derive(ReadWriteState, CreateTypeSpec, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum DeploymentStatus {
+    /// The escrow contract is being deployed.
+    #[discriminant(0)]
+    Deploying {},
+    /// The escrow contract has been deployed.
+    #[discriminant(1)]
+    Deployed {},
+    /// The deployment of the escrow contract failed.
+    #[discriminant(2)]
+    Failed {},
+}
+
+/// A deployed escrow contract.
+///
+/// ### Fields:
+///
+/// * `creator`: [`Address`], the creator of the escrow, being the sender of the escrowed tokens.
+/// * `address`: [`Option<Address>`], the address of the escrow contract, or None until it has
+///   been deployed.
+/// * `parameters`: [`EscrowParameters`], the parameters the escrow contract was deployed with.
+/// * `deployment_status`: [`DeploymentStatus`], the status of the deployment.
+/// * `escrow_status`: [`Option<u8>`], the status of the escrow contract as returned by the latest
+///   status query, or None if it has not been queried.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct DeployedEscrow {
+    creator: Address,
+    address: Option<Address>,
+    parameters: EscrowParameters,
+    deployment_status: DeploymentStatus,
+    escrow_status: Option<u8>,
+}
+
+/// Contract state.
+///
+/// ### Fields:
+///
+/// * `owner`: [`Address`], the owner of the contract.
+/// * `escrows`: [`BTreeMap<u64, DeployedEscrow>`], a map from escrow ids to escrow contracts.
+/// * `escrows_by_creator`: [`BTreeMap<Address, Vec<u64>>`], the ids of the escrows deployed by
+///   each creator.
+/// * `next_escrow_id`: [`u64`], the id of the next escrow contract to be deployed.
+/// * `escrow_contract_wasm`: [`Vec<u8>`], bytes of the escrow contract wasm.
+/// * `escrow_contract_abi`: [`Vec<u8>`], bytes of the escrow contract abi.
+#[state]
+pub struct EscrowFactoryState {
+    owner: Address,
+    escrows: BTreeMap<u64, DeployedEscrow>,
+    escrows_by_creator: BTreeMap<Address, Vec<u64>>,
+    next_escrow_id: u64,
+    escrow_contract_wasm: Vec<u8>,
+    escrow_contract_abi: Vec<u8>,
+}
+
+/// Initial function to create the initial state.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], initial context.
+/// * `escrow_contract_wasm`: [`Vec<u8>`], wasm bytes of a conditional escrow transfer contract.
+/// * `escrow_contract_abi`: [`Vec<u8>`], abi bytes of a conditional escrow transfer contract.
+///
+/// ### Returns:
+/// The initial state of type [`EscrowFactoryState`].
+#[init]
+pub fn initialize(
+    ctx: ContractContext,
+    escrow_contract_wasm: Vec<u8>,
+    escrow_contract_abi: Vec<u8>,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    let state = EscrowFactoryState {
+        owner: ctx.sender,
+        escrows: BTreeMap::new(),
+        escrows_by_creator: BTreeMap::new(),
+        next_escrow_id: 0,
+        escrow_contract_wasm,
+        escrow_contract_abi,
+    };
+
+    (state, vec![])
+}
+
+/// Deploys a new escrow contract with the given parameters, under the next escrow id. The sender
+/// of the action becomes the creator of the escrow, and the sender of the escrowed tokens. The
+/// address of the new escrow contract is computed from the original transaction hash. This
+/// creates an event to the public deploy contract as well as a callback to
+/// `deploy_escrow_callback`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`EscrowFactoryState`], the state before the call.
+/// * `parameters`: [`EscrowParameters`], the parameters of the new escrow contract.
+///
+/// ### Returns:
+/// The new state of type [`EscrowFactoryState`].
+#[action(shortname = 0x01)]
+pub fn deploy_escrow(
+    ctx: ContractContext,
+    state: EscrowFactoryState,
+    parameters: EscrowParameters,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    if parameters.token_type.address_type != AddressType::PublicContract {
+        panic!("Escrows can only hold publicContract tokens");
+    }
+
+    let escrow_id = state.next_escrow_id;
+    let escrow_address = Address {
+        address_type: AddressType::PublicContract,
+        identifier: ctx.original_transaction[12..32].try_into().unwrap(),
+    };
+
+    let mut event_group = EventGroup::builder();
+
+    event_group
+        .call(PUB_DEPLOY_ADDRESS, Shortname::from_u32(1))
+        .argument(state.escrow_contract_wasm.clone())
+        .argument(state.escrow_contract_abi.clone())
+        .argument(create_escrow_init_bytes(ctx.sender, &parameters))
+        .done();
+
+    event_group
+        .with_callback(SHORTNAME_DEPLOY_ESCROW_CALLBACK)
+        .with_cost(1000)
+        .argument(escrow_id)
+        .argument(escrow_address)
+        .done();
+
+    let mut new_state = state;
+    new_state.next_escrow_id += 1;
+    new_state.escrows.insert(
+        escrow_id,
+        DeployedEscrow {
+            creator: ctx.sender,
+            address: None,
+            parameters,
+            deployment_status: DeploymentStatus::Deploying {},
+            escrow_status: None,
+        },
+    );
+    new_state
+        .escrows_by_creator
+        .entry(ctx.sender)
+        .or_default()
+        .push(escrow_id);
+
+    (new_state, vec![event_group.build()])
+}
+
+/// Marks the deployment of the escrow contract with given id as failed.
+fn mark_deployment_failed(state: &mut EscrowFactoryState, escrow_id: u64) {
+    if let Some(escrow) = state.escrows.get_mut(&escrow_id) {
+        escrow.deployment_status = DeploymentStatus::Failed {};
+    }
+}
+
+/// Callback for deploying a new escrow contract. If the deployment was unsuccessful the entry in
+/// `escrows` is marked as failed. If it instead was successful, an empty invocation is made to
+/// the new contract to check if it really has been deployed. A new callback to
+/// `escrow_exists_callback` is also created.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback.
+/// * `state`: [`EscrowFactoryState`], the state before the call.
+/// * `escrow_id`: [`u64`], the id of the new escrow contract.
+/// * `escrow_address`: [`Address`], the address of the the new escrow contract.
+///
+/// ### Returns:
+/// The new state of type [`EscrowFactoryState`].
+#[callback(shortname = 0x02)]
+pub fn deploy_escrow_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: EscrowFactoryState,
+    escrow_id: u64,
+    escrow_address: Address,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.results[0].succeeded {
+        mark_deployment_failed(&mut new_state, escrow_id);
+        (new_state, vec![])
+    } else {
+        let mut event_group = EventGroup::builder();
+
+        event_group.ping(escrow_address, None);
+        event_group
+            .with_callback(SHORTNAME_ESCROW_EXISTS_CALLBACK)
+            .argument(escrow_id)
+            .argument(escrow_address)
+            .done();
+
+        (new_state, vec![event_group.build()])
+    }
+}
+
+/// Callback for checking if an escrow contract has been deployed successfully. If it is the
+/// address is stored in the entry in `escrows`. If it is not the entry is marked as failed
+/// instead.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback.
+/// * `state`: [`EscrowFactoryState`], the state before the call.
+/// * `escrow_id`: [`u64`], the id of the new escrow contract.
+/// * `escrow_address`: [`Address`], the address of the the new escrow contract.
+///
+/// ### Returns:
+/// The new state of type [`EscrowFactoryState`].
+#[callback(shortname = 0x03)]
+pub fn escrow_exists_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: EscrowFactoryState,
+    escrow_id: u64,
+    escrow_address: Address,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.results[0].succeeded {
+        mark_deployment_failed(&mut new_state, escrow_id);
+    } else if let Some(escrow) = new_state.escrows.get_mut(&escrow_id) {
+        escrow.address = Some(escrow_address);
+        escrow.deployment_status = DeploymentStatus::Deployed {};
+    }
+    (new_state, vec![])
+}
+
+/// Relays a status query to the deployed escrow contract with the given id, by calling its
+/// `query_status` action. Anyone can query the status of an escrow. The returned status is
+/// recorded in `query_escrow_status_callback`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`EscrowFactoryState`], the state before the call.
+/// * `escrow_id`: [`u64`], the id of the escrow contract to query.
+///
+/// ### Returns:
+/// The unchanged state of type [`EscrowFactoryState`].
+#[action(shortname = 0x04)]
+pub fn query_escrow_status(
+    ctx: ContractContext,
+    state: EscrowFactoryState,
+    escrow_id: u64,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    let escrow = state
+        .escrows
+        .get(&escrow_id)
+        .unwrap_or_else(|| panic!("No escrow with id {escrow_id}"));
+    if escrow.deployment_status != (DeploymentStatus::Deployed {}) {
+        panic!("The escrow with id {escrow_id} has not been deployed");
+    }
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(escrow.address.unwrap(), escrow_contract_query_status())
+        .done();
+    event_group
+        .with_callback(SHORTNAME_QUERY_ESCROW_STATUS_CALLBACK)
+        .argument(escrow_id)
+        .done();
+
+    (state, vec![event_group.build()])
+}
+
+/// Callback for querying the status of an escrow contract. The status returned by the escrow is
+/// recorded in the entry in `escrows`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the call.
+/// * `callback_ctx`: [`CallbackContext`], the context of the callback, containing the status.
+/// * `state`: [`EscrowFactoryState`], the state before the call.
+/// * `escrow_id`: [`u64`], the id of the queried escrow contract.
+///
+/// ### Returns:
+/// The new state of type [`EscrowFactoryState`].
+#[callback(shortname = 0x05)]
+pub fn query_escrow_status_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: EscrowFactoryState,
+    escrow_id: u64,
+) -> (EscrowFactoryState, Vec<EventGroup>) {
+    assert!(callback_ctx.success, "Status query did not succeed");
+    let escrow_status = u8::rpc_read_from(&mut callback_ctx.results[0].return_data.as_slice());
+    let mut new_state = state;
+    if let Some(escrow) = new_state.escrows.get_mut(&escrow_id) {
+        escrow.escrow_status = Some(escrow_status);
+    }
+    (new_state, vec![])
+}
+
+fn create_escrow_init_bytes(sender: Address, parameters: &EscrowParameters) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![0xff, 0xff, 0xff, 0xff, 0x0f];
+    WriteRPC::rpc_write_to(&sender, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.receiver, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.approver, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.token_type, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.hours_until_deadline, &mut bytes).unwrap();
+    // Deployed escrows hold an MPC-20 token, written as the discriminant of the asset kind
+    WriteRPC::rpc_write_to(&0u8, &mut bytes).unwrap();
+    // Deployed escrows accept no additional tokens
+    WriteRPC::rpc_write_to(&Vec::<Address>::new(), &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&parameters.refund_bounty_per_mille, &mut bytes).unwrap();
+    // Deployed escrows are not subscriptions
+    WriteRPC::rpc_write_to(&None::<u8>, &mut bytes).unwrap();
+    // Deployed escrows require no security deposit from the receiver
+    WriteRPC::rpc_write_to(&None::<u8>, &mut bytes).unwrap();
+    bytes
+}
+
+/// Escrow contract actions
+#[inline]
+fn escrow_contract_query_status() -> Shortname {
+    Shortname::from_u32(0x0A)
+}
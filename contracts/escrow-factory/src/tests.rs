@@ -0,0 +1,261 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_traits::ReadRPC;
+
+use crate::{
+    create_escrow_init_bytes, deploy_escrow, deploy_escrow_callback, escrow_exists_callback,
+    initialize, query_escrow_status, query_escrow_status_callback, DeploymentStatus,
+    EscrowFactoryState, EscrowParameters, PUB_DEPLOY_ADDRESS,
+};
+
+fn create_ctx(sender: Address) -> ContractContext {
+    let mut original_transaction = [0u8; 32];
+    original_transaction[31] = 7;
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time: 0,
+        current_transaction: [0u8; 32],
+        original_transaction,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn create_callback_ctx(success: bool, return_data: Vec<u8>) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data,
+        }],
+    }
+}
+
+fn creator() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+/// The address of the escrow deployed in the original transaction of [`create_ctx`].
+fn escrow_address() -> Address {
+    create_address(AddressType::PublicContract, 7)
+}
+
+fn parameters() -> EscrowParameters {
+    EscrowParameters {
+        receiver: create_address(AddressType::Account, 3),
+        approver: create_address(AddressType::Account, 4),
+        token_type: create_address(AddressType::PublicContract, 5),
+        hours_until_deadline: 24,
+        refund_bounty_per_mille: 10,
+    }
+}
+
+fn initial_state() -> EscrowFactoryState {
+    let (state, _) = initialize(create_ctx(creator()), vec![1, 2, 3], vec![4, 5]);
+    state
+}
+
+/// A factory with a single escrow, which has been deployed.
+fn state_with_deployed_escrow() -> EscrowFactoryState {
+    let (state, _) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+    let (state, _) = deploy_escrow_callback(
+        create_ctx(creator()),
+        create_callback_ctx(true, vec![]),
+        state,
+        0,
+        escrow_address(),
+    );
+    let (state, _) = escrow_exists_callback(
+        create_ctx(creator()),
+        create_callback_ctx(true, vec![]),
+        state,
+        0,
+        escrow_address(),
+    );
+    state
+}
+
+#[test]
+fn deploy_escrow_deploys_escrow_sent_by_the_creator() {
+    let (state, events) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(PUB_DEPLOY_ADDRESS, Shortname::from_u32(1))
+        .argument(vec![1u8, 2, 3])
+        .argument(vec![4u8, 5])
+        .argument(create_escrow_init_bytes(creator(), &parameters()))
+        .done();
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x02))
+        .with_cost(1000)
+        .argument(0u64)
+        .argument(escrow_address())
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+
+    let escrow = &state.escrows[&0];
+    assert_eq!(escrow.creator, creator());
+    assert_eq!(escrow.address, None);
+    assert_eq!(escrow.deployment_status, DeploymentStatus::Deploying {});
+    assert_eq!(state.escrows_by_creator[&creator()], vec![0]);
+    assert_eq!(state.next_escrow_id, 1);
+}
+
+#[test]
+#[should_panic(expected = "Escrows can only hold publicContract tokens")]
+fn deploy_escrow_of_non_contract_token() {
+    let mut parameters = parameters();
+    parameters.token_type = create_address(AddressType::Account, 5);
+    deploy_escrow(create_ctx(creator()), initial_state(), parameters);
+}
+
+#[test]
+fn init_bytes_follow_the_arguments_of_the_escrow() {
+    let bytes = create_escrow_init_bytes(creator(), &parameters());
+    let mut reader = bytes.as_slice();
+    let mut init_shortname = [0u8; 5];
+    std::io::Read::read_exact(&mut reader, &mut init_shortname).unwrap();
+    assert_eq!(init_shortname, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+
+    // sender, receiver, approver and token_type
+    assert_eq!(Address::rpc_read_from(&mut reader), creator());
+    assert_eq!(Address::rpc_read_from(&mut reader), parameters().receiver);
+    assert_eq!(Address::rpc_read_from(&mut reader), parameters().approver);
+    assert_eq!(Address::rpc_read_from(&mut reader), parameters().token_type);
+    // hours_until_deadline
+    assert_eq!(u32::rpc_read_from(&mut reader), 24);
+    // asset_kind, being an MPC-20 token
+    assert_eq!(u8::rpc_read_from(&mut reader), 0);
+    // additional_tokens
+    assert_eq!(Vec::<Address>::rpc_read_from(&mut reader), vec![]);
+    // refund_bounty_per_mille
+    assert_eq!(u32::rpc_read_from(&mut reader), 10);
+    // No subscription and no required_security_deposit
+    assert_eq!(u8::rpc_read_from(&mut reader), 0);
+    assert_eq!(Option::<u128>::rpc_read_from(&mut reader), None);
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn failed_deployment_is_marked_failed() {
+    let (state, _) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+    let (state, events) = deploy_escrow_callback(
+        create_ctx(creator()),
+        create_callback_ctx(false, vec![]),
+        state,
+        0,
+        escrow_address(),
+    );
+    assert!(events.is_empty());
+    assert_eq!(
+        state.escrows[&0].deployment_status,
+        DeploymentStatus::Failed {}
+    );
+}
+
+#[test]
+fn successful_deployment_checks_that_the_escrow_exists() {
+    let (state, _) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+    let (state, events) = deploy_escrow_callback(
+        create_ctx(creator()),
+        create_callback_ctx(true, vec![]),
+        state,
+        0,
+        escrow_address(),
+    );
+
+    let mut expected = EventGroup::builder();
+    expected.ping(escrow_address(), None);
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x03))
+        .argument(0u64)
+        .argument(escrow_address())
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+    assert_eq!(
+        state.escrows[&0].deployment_status,
+        DeploymentStatus::Deploying {}
+    );
+}
+
+#[test]
+fn existing_escrow_is_marked_deployed() {
+    let state = state_with_deployed_escrow();
+    let escrow = &state.escrows[&0];
+    assert_eq!(escrow.address, Some(escrow_address()));
+    assert_eq!(escrow.deployment_status, DeploymentStatus::Deployed {});
+}
+
+#[test]
+fn missing_escrow_is_marked_failed() {
+    let (state, _) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+    let (state, _) = escrow_exists_callback(
+        create_ctx(creator()),
+        create_callback_ctx(false, vec![]),
+        state,
+        0,
+        escrow_address(),
+    );
+    let escrow = &state.escrows[&0];
+    assert_eq!(escrow.address, None);
+    assert_eq!(escrow.deployment_status, DeploymentStatus::Failed {});
+}
+
+#[test]
+fn query_escrow_status_calls_the_escrow() {
+    let (_, events) = query_escrow_status(
+        create_ctx(create_address(AddressType::Account, 9)),
+        state_with_deployed_escrow(),
+        0,
+    );
+
+    let mut expected = EventGroup::builder();
+    expected
+        .call(escrow_address(), Shortname::from_u32(0x0A))
+        .done();
+    expected
+        .with_callback(ShortnameCallback::from_u32(0x05))
+        .argument(0u64)
+        .done();
+    assert_eq!(events, vec![expected.build()]);
+}
+
+#[test]
+#[should_panic(expected = "The escrow with id 0 has not been deployed")]
+fn query_status_of_undeployed_escrow() {
+    let (state, _) = deploy_escrow(create_ctx(creator()), initial_state(), parameters());
+    query_escrow_status(create_ctx(creator()), state, 0);
+}
+
+#[test]
+fn query_escrow_status_callback_records_the_status() {
+    let (state, _) = query_escrow_status_callback(
+        create_ctx(escrow_address()),
+        create_callback_ctx(true, vec![2]),
+        state_with_deployed_escrow(),
+        0,
+    );
+    assert_eq!(state.escrows[&0].escrow_status, Some(2));
+}
+
+#[test]
+#[should_panic(expected = "Status query did not succeed")]
+fn failed_status_query() {
+    query_escrow_status_callback(
+        create_ctx(escrow_address()),
+        create_callback_ctx(false, vec![]),
+        state_with_deployed_escrow(),
+        0,
+    );
+}
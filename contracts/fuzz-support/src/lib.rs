@@ -0,0 +1,300 @@
+//! Helpers shared by the fuzz targets of the contracts.
+//!
+//! Each contract with fuzz targets has a `fuzz` directory set up for `cargo fuzz`. The fuzz targets
+//! invoke the entry points generated by `#[init]`, `#[action]` and `#[callback]` with randomized
+//! contexts and arbitrary RPC payloads, such that the arguments are decoded exactly as on chain:
+//!
+//! ```bash
+//! cd contracts/auction/fuzz
+//! cargo +nightly fuzz run bid
+//! ```
+//!
+//! Contracts reject invalid input by panicking with an explicit message, which aborts the
+//! transaction on chain. These panics are expected, and are ignored by [`invoke`], as are panics
+//! caused by payloads that cannot be decoded. Panics caused by runtime errors, such as arithmetic
+//! overflow or indexing out of bounds, indicate a bug in the contract, and are reported as crashes.
+
+#[cfg(feature = "zk")]
+use std::io::{Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+use arbitrary::{Result, Unstructured};
+use pbc_contract_common::address::{Address, AddressType};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+#[cfg(feature = "zk")]
+use pbc_contract_common::zk::{CalculationStatus, SecretVarId, ZkClosed, ZkState};
+use pbc_contract_common::ContractResultBuffer;
+use pbc_traits::{ReadWriteState, WriteRPC};
+
+/// Messages of panics caused by RPC payloads that cannot be decoded, which reject the transaction
+/// before it reaches the contract.
+const DECODING_ERROR_MESSAGES: [&str; 2] = ["UnexpectedEof", "FromUtf8Error"];
+
+/// Messages of panics caused by runtime errors rather than explicit rejection of input.
+const RUNTIME_ERROR_MESSAGES: [&str; 6] = [
+    "overflow",
+    "out of bounds",
+    "divide by zero",
+    "remainder with a divisor of zero",
+    "called `Option::unwrap()` on a `None` value",
+    "called `Result::unwrap()` on an `Err` value",
+];
+
+/// The number of distinct addresses generated by [`arbitrary_address`].
+///
+/// A small pool makes it likely that the same address is used in several roles, such as being
+/// both the owner and the sender.
+const ADDRESS_POOL_SIZE: u8 = 4;
+
+/// Generates an address from a small pool of accounts and contracts.
+pub fn arbitrary_address(u: &mut Unstructured) -> Result<Address> {
+    let address_type = match u.int_in_range(0..=3)? {
+        0 => AddressType::Account,
+        1 => AddressType::SystemContract,
+        2 => AddressType::PublicContract,
+        _ => AddressType::ZkContract,
+    };
+    let mut identifier = [0u8; 20];
+    identifier[19] = u.int_in_range(0..=ADDRESS_POOL_SIZE - 1)?;
+    Ok(Address {
+        address_type,
+        identifier,
+    })
+}
+
+/// Generates a context for an action call, with an arbitrary sender and block time.
+pub fn arbitrary_context(
+    u: &mut Unstructured,
+    contract_address: Address,
+) -> Result<ContractContext> {
+    let block_production_time: i64 = u.arbitrary()?;
+    let hash: [u8; 32] = u.arbitrary()?;
+    Ok(ContractContext {
+        contract_address,
+        sender: arbitrary_address(u)?,
+        block_time: block_production_time / 3_600_000,
+        block_production_time,
+        current_transaction: hash,
+        original_transaction: hash,
+    })
+}
+
+/// Generates a callback context where each of the `num_events` events succeeded or failed, with
+/// arbitrary return data.
+pub fn arbitrary_callback_context(
+    u: &mut Unstructured,
+    num_events: usize,
+) -> Result<CallbackContext> {
+    let mut results = vec![];
+    for _ in 0..num_events {
+        results.push(ExecutionResult {
+            succeeded: u.arbitrary()?,
+            return_data: u.arbitrary()?,
+        });
+    }
+    Ok(CallbackContext {
+        success: results.iter().all(|result| result.succeeded),
+        results,
+    })
+}
+
+/// The number of distinct secret variables generated by [`arbitrary_zk_state`].
+#[cfg(feature = "zk")]
+const SECRET_VARIABLE_POOL_SIZE: u32 = 4;
+
+/// Generates a serialized zk state with pending inputs and secret variables owned by addresses
+/// from the pool of [`arbitrary_address`].
+///
+/// The metadata of each variable is generated by `metadata`, already serialized as state, such
+/// that the metadata types of the contracts need not be public.
+#[cfg(feature = "zk")]
+pub fn arbitrary_zk_state(
+    u: &mut Unstructured,
+    metadata: impl Fn(&mut Unstructured) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let calculation_state = *u.choose(&[
+        CalculationStatus::Waiting,
+        CalculationStatus::Calculating,
+        CalculationStatus::Output,
+        CalculationStatus::MaliciousBehaviour,
+        CalculationStatus::Done,
+    ])?;
+    let mut pending_inputs = vec![];
+    let mut secret_variables = vec![];
+    for raw_id in 1..=u.int_in_range(0..=SECRET_VARIABLE_POOL_SIZE)? {
+        let variable = ZkClosed {
+            variable_id: SecretVarId::new(raw_id),
+            owner: arbitrary_address(u)?,
+            is_sealed: u.arbitrary()?,
+            metadata: SerializedMetadata(metadata(u)?),
+            data: u.arbitrary()?,
+        };
+        if u.ratio(1, 4)? {
+            pending_inputs.push(variable);
+        } else {
+            secret_variables.push(variable);
+        }
+    }
+    Ok(serialize_state(&ZkState {
+        calculation_state,
+        pending_inputs,
+        secret_variables,
+        data_attestations: vec![],
+    }))
+}
+
+/// Metadata of a secret variable, already serialized as state.
+#[cfg(feature = "zk")]
+struct SerializedMetadata(Vec<u8>);
+
+#[cfg(feature = "zk")]
+impl ReadWriteState for SerializedMetadata {
+    fn state_read_from<T: Read>(_reader: &mut T) -> Self {
+        unreachable!("Serialized metadata is only written")
+    }
+
+    fn state_write_to<T: Write>(&self, writer: &mut T) -> std::io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+/// An entry point generated by `#[init]`, `#[action]` or `#[callback]`, which reads the arguments
+/// of the annotated function from its input and returns the serialized results.
+pub type EntryPoint = fn(&mut &[u8]) -> ContractResultBuffer;
+
+/// The input of an [`EntryPoint`], holding the serialized arguments of the annotated function.
+///
+/// The arguments must be added in the order of the parameters of the function, ending with the
+/// RPC payload holding the remaining arguments.
+pub struct EntryPointInput {
+    bytes: Vec<u8>,
+}
+
+impl EntryPointInput {
+    /// Starts the input of an entry point invoked with `context`.
+    pub fn new(context: &ContractContext) -> Self {
+        let mut bytes = vec![];
+        context
+            .rpc_write_to(&mut bytes)
+            .expect("Serializing the context should not fail");
+        EntryPointInput { bytes }
+    }
+
+    /// Adds the callback context of a callback.
+    pub fn callback_context(mut self, callback_context: &CallbackContext) -> Self {
+        callback_context
+            .rpc_write_to(&mut self.bytes)
+            .expect("Serializing the callback context should not fail");
+        self
+    }
+
+    /// Adds a serialized state, such as the state returned by [`invoke`] or a zk state generated by
+    /// `arbitrary_zk_state`.
+    pub fn state(mut self, state: &[u8]) -> Self {
+        self.bytes.extend_from_slice(state);
+        self
+    }
+
+    /// Adds the RPC payload holding the remaining arguments.
+    pub fn rpc(mut self, payload: &[u8]) -> Self {
+        self.bytes.extend_from_slice(payload);
+        self
+    }
+}
+
+/// An action or callback of a contract, to be invoked with an arbitrary context.
+#[derive(Clone, Copy)]
+pub enum Call {
+    /// An action, taking the context and the state.
+    Action(EntryPoint),
+    /// A callback of an event group with the given number of events, taking the context, the
+    /// callback context and the state.
+    Callback(EntryPoint, usize),
+}
+
+impl Call {
+    /// Starts the input of the call with `context` and `state`, generating a callback context if
+    /// the call is a callback.
+    pub fn input(
+        self,
+        u: &mut Unstructured,
+        context: &ContractContext,
+        state: &[u8],
+    ) -> Result<(EntryPoint, EntryPointInput)> {
+        let input = EntryPointInput::new(context);
+        Ok(match self {
+            Call::Action(entry_point) => (entry_point, input.state(state)),
+            Call::Callback(entry_point, num_events) => {
+                let callback_context = arbitrary_callback_context(u, num_events)?;
+                (
+                    entry_point,
+                    input.callback_context(&callback_context).state(state),
+                )
+            }
+        })
+    }
+}
+
+/// Serializes `state` as it is stored on chain.
+pub fn serialize_state<S: ReadWriteState>(state: &S) -> Vec<u8> {
+    let mut serialized = vec![];
+    state
+        .state_write_to(&mut serialized)
+        .expect("Serializing the state should not fail");
+    serialized
+}
+
+/// The id of the section of a [`ContractResultBuffer`] holding the serialized state.
+const STATE_SECTION_ID: u8 = 0x02;
+
+/// Invokes an entry point, ignoring panics that explicitly reject the input.
+///
+/// ### Returns
+///
+/// The serialized state resulting from the call, or None if the call rejected the input.
+///
+/// ### Panics
+///
+/// Resumes panics caused by runtime errors, such that they are reported by the fuzzer.
+pub fn invoke(entry_point: EntryPoint, input: EntryPointInput) -> Option<Vec<u8>> {
+    let result = quietly(|| entry_point(&mut input.bytes.as_slice()))?;
+    let mut sections = result.data.as_slice();
+    while let [id, rest @ ..] = sections {
+        let (length, rest) = rest.split_at(4);
+        let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+        let (section, rest) = rest.split_at(length);
+        if *id == STATE_SECTION_ID {
+            return Some(section.to_vec());
+        }
+        sections = rest;
+    }
+    panic!("The result of the call holds no state")
+}
+
+/// Runs `f`, catching panics. Panics caused by runtime errors are resumed.
+fn quietly<R>(f: impl FnOnce() -> R) -> Option<R> {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(previous_hook);
+    match result {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("");
+            let is_decoding_error = DECODING_ERROR_MESSAGES
+                .iter()
+                .any(|decoding_error| message.contains(decoding_error));
+            let is_runtime_error = RUNTIME_ERROR_MESSAGES
+                .iter()
+                .any(|runtime_error| message.contains(runtime_error));
+            if is_runtime_error && !is_decoding_error {
+                panic::resume_unwind(payload);
+            }
+            None
+        }
+    }
+}
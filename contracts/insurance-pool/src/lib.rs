@@ -0,0 +1,468 @@
+//! Example smart contract implementing a mutual insurance pool, where members pay premiums into a
+//! shared pool of tokens, and claims against the pool are approved by vote of the members.
+//!
+//! How it works
+//! * The pool is initialized with the token it holds, the premium, the coverage period bought by
+//!   each premium and the duration of votes on claims.
+//! * Anyone can pay the premium to become a member. The premium is transferred to the pool, and
+//!   covers the member for the coverage period, starting now or when their current coverage ends.
+//! * A covered member can file a claim for an amount of tokens, along with the hash of the
+//!   evidence of the loss. The claimed amount is reserved from the pool while the members vote,
+//!   such that approved claims can always be paid out.
+//! * The members covered when the claim is filed, except the claimant, can vote for or against
+//!   the claim once until the deadline.
+//! * Once a majority of the voters has approved the claim, the pool transfers the claimed amount
+//!   to the claimant in the same transaction. A callback records whether the transfer succeeded,
+//!   and the amount is returned to the pool if it failed.
+//! * Once a majority can no longer be reached, the claim is rejected and the amount is returned to
+//!   the pool. A claim without a majority when the deadline passes can be closed by anyone,
+//!   rejecting it.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// The status of a claim.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum ClaimStatus {
+    /// The members are voting on the claim.
+    #[discriminant(0)]
+    Voting {},
+    /// The claim was approved, and the payout is awaiting the callback.
+    #[discriminant(1)]
+    Paying {},
+    /// The claimed amount was transferred to the claimant.
+    #[discriminant(2)]
+    Paid {},
+    /// The transfer of the payout failed, and the amount was returned to the pool.
+    #[discriminant(3)]
+    Failed {},
+    /// The claim did not get the votes of a majority of the voters.
+    #[discriminant(4)]
+    Rejected {},
+}
+
+/// A claim against the pool.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Claim {
+    /// The member who filed the claim, receiving the payout.
+    pub claimant: Address,
+    /// The amount of tokens claimed, which is reserved from the pool while the claim is open.
+    pub amount: u128,
+    /// The hash of the evidence of the loss, which is kept off-chain.
+    pub evidence_hash: Hash,
+    /// The deadline of the vote in UTC millis
+    /// (milliseconds after 1970-01-01 00:00:00 UTC)
+    pub deadline_utc_millis: i64,
+    /// The members who can vote on the claim, being the members covered when it was filed,
+    /// except the claimant.
+    pub voters: Vec<Address>,
+    /// The voters who voted for the claim.
+    pub votes_for: BTreeSet<Address>,
+    /// The voters who voted against the claim.
+    pub votes_against: BTreeSet<Address>,
+    /// The status of the claim.
+    pub status: ClaimStatus,
+}
+
+impl Claim {
+    /// The number of votes required for the claim to be approved, being a majority of the voters.
+    fn majority(&self) -> usize {
+        self.voters.len() / 2 + 1
+    }
+}
+
+/// The state of the pool, which is persisted on-chain.
+#[state]
+pub struct InsurancePoolState {
+    /// The address of the token contract of the pooled tokens.
+    pub token: Address,
+    /// The amount of tokens paid for each premium.
+    pub premium: u128,
+    /// The duration of the coverage bought by each premium in millis.
+    pub coverage_period_millis: i64,
+    /// The duration of the vote on each claim in millis.
+    pub voting_duration_millis: i64,
+    /// The members of the pool, mapped to the time in UTC millis at which their coverage ends.
+    pub members: BTreeMap<Address, i64>,
+    /// The tokens held by the pool that are not reserved for open claims.
+    pub pool_balance: u128,
+    /// The claims against the pool, indexed by their id.
+    pub claims: BTreeMap<u64, Claim>,
+    /// The id of the next claim.
+    pub next_claim_id: u64,
+}
+
+impl InsurancePoolState {
+    /// Whether `address` is a member whose coverage has not ended at `now`.
+    fn is_covered(&self, address: &Address, now: i64) -> bool {
+        self.members
+            .get(address)
+            .is_some_and(|covered_until| now < *covered_until)
+    }
+
+    /// Returns the claim with the given id, panicking if it doesn't exist or the vote on it has
+    /// ended.
+    fn claim_in_vote(&mut self, claim_id: u64) -> &mut Claim {
+        let claim = self
+            .claims
+            .get_mut(&claim_id)
+            .unwrap_or_else(|| panic!("No claim with id {claim_id}"));
+        assert!(
+            claim.status == ClaimStatus::Voting {},
+            "The vote on the claim has ended"
+        );
+        claim
+    }
+
+    /// Rejects the claim, returning the reserved amount to the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `claim_id` - the id of the claim.
+    ///
+    fn reject(&mut self, claim_id: u64) {
+        let claim = self.claim_in_vote(claim_id);
+        claim.status = ClaimStatus::Rejected {};
+        let amount = claim.amount;
+        self.pool_balance += amount;
+    }
+
+    /// Decides the claim if a majority of the voters has approved it, or can no longer approve it.
+    /// An approved claim is paid out to the claimant.
+    ///
+    /// # Arguments
+    ///
+    /// * `claim_id` - the id of the claim.
+    ///
+    /// # Returns
+    ///
+    /// The event groups containing the payout, which are empty unless the claim was approved.
+    ///
+    fn decide_if_majority(&mut self, claim_id: u64) -> Vec<EventGroup> {
+        let token = self.token;
+        let claim = self.claim_in_vote(claim_id);
+        let majority = claim.majority();
+        if claim.votes_against.len() > claim.voters.len() - majority {
+            self.reject(claim_id);
+            return vec![];
+        }
+        if claim.votes_for.len() < majority {
+            return vec![];
+        }
+        claim.status = ClaimStatus::Paying {};
+
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(token, token_contract_transfer())
+            .argument(claim.claimant)
+            .argument(claim.amount)
+            .done();
+        event_group
+            .with_callback(SHORTNAME_PAYOUT_CALLBACK)
+            .argument(claim_id)
+            .done();
+        vec![event_group.build()]
+    }
+}
+
+/// Initialize a new insurance pool.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `token` - the address of the token contract of the pooled tokens.
+/// * `premium` - the amount of tokens paid for each premium.
+/// * `coverage_period_millis` - the duration of the coverage bought by each premium in millis.
+/// * `voting_duration_millis` - the duration of the vote on each claim in millis.
+///
+/// # Returns
+///
+/// The initial state of the pool, with no members and no claims.
+///
+#[init]
+pub fn initialize(
+    ctx: ContractContext,
+    token: Address,
+    premium: u128,
+    coverage_period_millis: i64,
+    voting_duration_millis: i64,
+) -> InsurancePoolState {
+    assert_eq!(
+        token.address_type,
+        AddressType::PublicContract,
+        "The token must be a public contract"
+    );
+    assert!(premium > 0, "The premium must be positive");
+    assert!(
+        coverage_period_millis > 0,
+        "The coverage period must be positive"
+    );
+    assert!(
+        voting_duration_millis > 0,
+        "The duration of votes must be positive"
+    );
+    InsurancePoolState {
+        token,
+        premium,
+        coverage_period_millis,
+        voting_duration_millis,
+        members: BTreeMap::new(),
+        pool_balance: 0,
+        claims: BTreeMap::new(),
+        next_claim_id: 0,
+    }
+}
+
+/// Pay the premium, transferring it from the sender to the pool. The coverage of the sender is
+/// extended in `pay_premium_callback`.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the pool.
+///
+/// # Returns
+///
+/// The unchanged state, and the transfer of the premium.
+///
+#[action(shortname = 0x01)]
+pub fn pay_premium(
+    ctx: ContractContext,
+    state: InsurancePoolState,
+) -> (InsurancePoolState, Vec<EventGroup>) {
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.token, token_contract_transfer_from())
+        .argument(ctx.sender)
+        .argument(ctx.contract_address)
+        .argument(state.premium)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_PAY_PREMIUM_CALLBACK)
+        .argument(ctx.sender)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Records a paid premium. The premium is added to the pool, and the member is covered for the
+/// coverage period, starting now or when their current coverage ends.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the pool.
+/// * `member` - the member who paid the premium.
+///
+/// # Returns
+///
+/// The updated state with the coverage of the member extended.
+///
+#[callback(shortname = 0x10)]
+pub fn pay_premium_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: InsurancePoolState,
+    member: Address,
+) -> InsurancePoolState {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the premium did not succeed"
+    );
+    let mut new_state = state;
+    new_state.pool_balance += new_state.premium;
+    let coverage_period_millis = new_state.coverage_period_millis;
+    let covered_until = new_state.members.entry(member).or_insert(0);
+    *covered_until = (*covered_until).max(ctx.block_production_time) + coverage_period_millis;
+    new_state
+}
+
+/// File a claim against the pool. Only covered members can file claims. The claimed amount is
+/// reserved from the pool, and the members covered at the time of the claim, except the claimant,
+/// vote on the claim until the deadline.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the pool.
+/// * `amount` - the amount of tokens claimed.
+/// * `evidence_hash` - the hash of the evidence of the loss.
+///
+/// # Returns
+///
+/// The updated state with the new claim.
+///
+#[action(shortname = 0x02)]
+pub fn file_claim(
+    ctx: ContractContext,
+    state: InsurancePoolState,
+    amount: u128,
+    evidence_hash: Hash,
+) -> InsurancePoolState {
+    let now = ctx.block_production_time;
+    assert!(
+        state.is_covered(&ctx.sender, now),
+        "Only covered members can file claims"
+    );
+    assert!(amount > 0, "The amount must be positive");
+    assert!(
+        amount <= state.pool_balance,
+        "The pool cannot cover the claim"
+    );
+    let voters: Vec<Address> = state
+        .members
+        .keys()
+        .filter(|member| **member != ctx.sender && state.is_covered(member, now))
+        .copied()
+        .collect();
+    assert!(
+        !voters.is_empty(),
+        "No other covered members can vote on the claim"
+    );
+    let mut new_state = state;
+    new_state.pool_balance -= amount;
+    let claim_id = new_state.next_claim_id;
+    new_state.next_claim_id += 1;
+    new_state.claims.insert(
+        claim_id,
+        Claim {
+            claimant: ctx.sender,
+            amount,
+            evidence_hash,
+            deadline_utc_millis: now + new_state.voting_duration_millis,
+            voters,
+            votes_for: BTreeSet::new(),
+            votes_against: BTreeSet::new(),
+            status: ClaimStatus::Voting {},
+        },
+    );
+    new_state
+}
+
+/// Vote for or against a claim until its deadline. Each voter of the claim can vote once.
+/// Pays out the claim if it is approved.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the pool.
+/// * `claim_id` - the id of the claim to vote on.
+/// * `approve` - whether the vote is for the claim.
+///
+/// # Returns
+///
+/// The updated state, and the payout if the claim was approved.
+///
+#[action(shortname = 0x03)]
+pub fn vote(
+    ctx: ContractContext,
+    state: InsurancePoolState,
+    claim_id: u64,
+    approve: bool,
+) -> (InsurancePoolState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let claim = new_state.claim_in_vote(claim_id);
+    assert!(
+        claim.voters.contains(&ctx.sender),
+        "Only the members covered when the claim was filed can vote on it"
+    );
+    assert!(
+        ctx.block_production_time < claim.deadline_utc_millis,
+        "The deadline has passed"
+    );
+    assert!(
+        !claim.votes_for.contains(&ctx.sender) && !claim.votes_against.contains(&ctx.sender),
+        "The sender has already voted on the claim"
+    );
+    if approve {
+        claim.votes_for.insert(ctx.sender);
+    } else {
+        claim.votes_against.insert(ctx.sender);
+    }
+    let events = new_state.decide_if_majority(claim_id);
+    (new_state, events)
+}
+
+/// Close a claim which has not been approved by its deadline, rejecting it and returning the
+/// claimed amount to the pool. Anyone can close a claim once the deadline has passed.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the pool.
+/// * `claim_id` - the id of the claim to close.
+///
+/// # Returns
+///
+/// The updated state with the claim rejected.
+///
+#[action(shortname = 0x04)]
+pub fn close(ctx: ContractContext, state: InsurancePoolState, claim_id: u64) -> InsurancePoolState {
+    let mut new_state = state;
+    let claim = new_state.claim_in_vote(claim_id);
+    assert!(
+        ctx.block_production_time >= claim.deadline_utc_millis,
+        "The deadline has not yet passed"
+    );
+    new_state.reject(claim_id);
+    new_state
+}
+
+/// Records the outcome of the payout of an approved claim. If the transfer failed, the claimed
+/// amount is returned to the pool.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the pool.
+/// * `claim_id` - the id of the approved claim.
+///
+/// # Returns
+///
+/// The updated state.
+///
+#[callback(shortname = 0x11)]
+pub fn payout_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: InsurancePoolState,
+    claim_id: u64,
+) -> InsurancePoolState {
+    let mut new_state = state;
+    let claim = new_state.claims.get_mut(&claim_id).unwrap();
+    if callback_ctx.success {
+        claim.status = ClaimStatus::Paid {};
+    } else {
+        claim.status = ClaimStatus::Failed {};
+        let amount = claim.amount;
+        new_state.pool_balance += amount;
+    }
+    new_state
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
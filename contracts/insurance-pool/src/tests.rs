@@ -0,0 +1,205 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+
+use crate::{
+    close, file_claim, initialize, pay_premium, pay_premium_callback, payout_callback, vote,
+    ClaimStatus, InsurancePoolState, SHORTNAME_PAYOUT_CALLBACK, SHORTNAME_PAY_PREMIUM_CALLBACK,
+};
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn member(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+fn token() -> Address {
+    create_address(AddressType::PublicContract, 2)
+}
+
+fn callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![],
+    }
+}
+
+/// A pool with premiums of 100 covering 1000 millis and votes lasting 100 millis, where four
+/// members have paid the premium at time 0, and member 1 has claimed 300 at time 10.
+fn state_with_claim() -> InsurancePoolState {
+    let mut state = initialize(create_ctx(member(1), 0), token(), 100, 1000, 100);
+    for id in 1..=4 {
+        state = pay_premium_callback(
+            create_ctx(member(id), 0),
+            callback_ctx(true),
+            state,
+            member(id),
+        );
+    }
+    assert_eq!(state.pool_balance, 400);
+    let state = file_claim(create_ctx(member(1), 10), state, 300, [1u8; 32]);
+    assert_eq!(state.pool_balance, 100);
+    state
+}
+
+#[test]
+fn premium_is_transferred_to_the_pool() {
+    let state = initialize(create_ctx(member(1), 0), token(), 100, 1000, 100);
+    let (state, events) = pay_premium(create_ctx(member(1), 0), state);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x03))
+        .argument(member(1))
+        .argument(create_address(AddressType::PublicContract, 1))
+        .argument(100u128)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_PAY_PREMIUM_CALLBACK)
+        .argument(member(1))
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+
+    let state = pay_premium_callback(
+        create_ctx(member(1), 0),
+        callback_ctx(true),
+        state,
+        member(1),
+    );
+    assert_eq!(state.members[&member(1)], 1000);
+}
+
+#[test]
+fn renewed_premium_extends_coverage() {
+    let state = state_with_claim();
+    let state = pay_premium_callback(
+        create_ctx(member(2), 500),
+        callback_ctx(true),
+        state,
+        member(2),
+    );
+    assert_eq!(state.members[&member(2)], 2000);
+    let state = pay_premium_callback(
+        create_ctx(member(3), 1500),
+        callback_ctx(true),
+        state,
+        member(3),
+    );
+    assert_eq!(state.members[&member(3)], 2500);
+}
+
+#[test]
+fn approved_claim_is_paid_out() {
+    let mut state = state_with_claim();
+    assert_eq!(
+        state.claims[&0].voters,
+        vec![member(2), member(3), member(4)]
+    );
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    let (state, events) = vote(create_ctx(member(3), 30), state, 0, true);
+    assert_eq!(state.claims[&0].status, ClaimStatus::Paying {});
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x01))
+        .argument(member(1))
+        .argument(300u128)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_PAYOUT_CALLBACK)
+        .argument(0u64)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+
+    let state = payout_callback(create_ctx(member(3), 30), callback_ctx(true), state, 0);
+    assert_eq!(state.claims[&0].status, ClaimStatus::Paid {});
+    assert_eq!(state.pool_balance, 100);
+}
+
+#[test]
+fn failed_payout_returns_amount_to_pool() {
+    let mut state = state_with_claim();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    (state, _) = vote(create_ctx(member(3), 30), state, 0, true);
+    let state = payout_callback(create_ctx(member(3), 30), callback_ctx(false), state, 0);
+    assert_eq!(state.claims[&0].status, ClaimStatus::Failed {});
+    assert_eq!(state.pool_balance, 400);
+}
+
+#[test]
+fn claim_is_rejected_once_majority_is_unreachable() {
+    let mut state = state_with_claim();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, false);
+    let (state, events) = vote(create_ctx(member(3), 30), state, 0, false);
+    assert_eq!(events.len(), 0);
+    assert_eq!(state.claims[&0].status, ClaimStatus::Rejected {});
+    assert_eq!(state.pool_balance, 400);
+}
+
+#[test]
+fn claim_without_majority_is_closed_after_deadline() {
+    let mut state = state_with_claim();
+    (state, _) = vote(create_ctx(member(2), 20), state, 0, true);
+    let state = close(create_ctx(member(4), 110), state, 0);
+    assert_eq!(state.claims[&0].status, ClaimStatus::Rejected {});
+    assert_eq!(state.pool_balance, 400);
+}
+
+#[test]
+#[should_panic(expected = "Only covered members can file claims")]
+fn expired_member_cannot_file_claim() {
+    file_claim(
+        create_ctx(member(2), 1000),
+        state_with_claim(),
+        50,
+        [2u8; 32],
+    );
+}
+
+#[test]
+#[should_panic(expected = "The pool cannot cover the claim")]
+fn claim_cannot_exceed_pool() {
+    file_claim(
+        create_ctx(member(2), 20),
+        state_with_claim(),
+        101,
+        [2u8; 32],
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only the members covered when the claim was filed can vote on it")]
+fn claimant_cannot_vote_on_own_claim() {
+    vote(create_ctx(member(1), 20), state_with_claim(), 0, true);
+}
+
+#[test]
+#[should_panic(expected = "The sender has already voted on the claim")]
+fn cannot_vote_twice() {
+    let (state, _) = vote(create_ctx(member(2), 20), state_with_claim(), 0, false);
+    vote(create_ctx(member(2), 30), state, 0, false);
+}
+
+#[test]
+#[should_panic(expected = "The deadline has not yet passed")]
+fn cannot_close_before_deadline() {
+    close(create_ctx(member(2), 109), state_with_claim(), 0);
+}
@@ -0,0 +1,778 @@
+//! This is an example liquidity swap smart contract for three tokens. <br>
+//! It generalizes the two token liquidity swap contract to a pool of three tokens, A, B and C,
+//! such that users can swap between any pair of the tokens. <br>
+//!
+//! Unlike the two token contract, the pool uses a flat invariant, `constant sum formula: x + y + z = k`,
+//! as used by stable swaps for tokens of (approximately) equal value. <br>
+//! We consider `x`, `y` and `z` to be the balances of token pool A, B and C, and `k` to be their sum. <br>
+//! Swapping between any two of the tokens happens at a fixed rate of 1:1, as long as the target pool has
+//! enough tokens to pay out the swap. <br>
+//! When performing a swap, a fee (given in per mille) is applied, based on the input amount, which is deducted from the output of the swap. <br>
+//! This effectively increases `k` after each swap.<br><br>
+//!
+//! In order to perform a swap, it is a prerequisite that the swapping user has already transferred
+//! the token to the contract via a call to [`deposit`]. <br>
+//! Additionally, some user (typically the creator of the contract) must have already deposited an amount of all three token types and initialized the pools by a call to [`provide_initial_liquidity`]. <br><br>
+//!
+//! A user may [`withdraw`] the resulting tokens of a swap (or simply his own deposited tokens)
+//! to have the tokens transferred to his account, at any point.<br><br>
+//!
+//! A user may become a liquidity provider (LP) of the contract by providing an amount of pre-deposited tokens.
+//! Since liquidity tokens represent an equal share of all three pools, when providing liquidity it is
+//! enforced that the user provides amounts of the other two tokens matching the current ratio of the pools. <br>
+//! Liquidity tokens may later be burned to receive a proportionate share of each pool. <br>
+//!
+#![allow(unused_variables)]
+
+mod tests;
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate core;
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+use std::collections::btree_map::BTreeMap;
+
+/// Enum for token types
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteRPC, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Token {
+    /// The value representing token A.
+    #[discriminant(0)]
+    TokenA {},
+    /// The value representing token B.
+    #[discriminant(1)]
+    TokenB {},
+    /// The value representing token C.
+    #[discriminant(2)]
+    TokenC {},
+    /// The value representing a liquidity token.
+    #[discriminant(3)]
+    LiquidityToken {},
+}
+
+/// Make reference to tokens more readable
+impl Token {
+    const A: Token = Token::TokenA {};
+    const B: Token = Token::TokenB {};
+    const C: Token = Token::TokenC {};
+    const LIQUIDITY: Token = Token::LiquidityToken {};
+
+    /// The tokens of the pools, in order.
+    const POOLS: [Token; 3] = [Token::A, Token::B, Token::C];
+}
+
+/// Keeps track of how much of a given token a user owns within the scope of the contract.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct TokenBalance {
+    /// The amount of token A that a user can withdraw from the contract.
+    pub a_tokens: u128,
+    /// The amount of token B that a user can withdraw from the contract.
+    pub b_tokens: u128,
+    /// The amount of token C that a user can withdraw from the contract.
+    pub c_tokens: u128,
+    /// The amount of liquidity tokens that a user may burn.
+    pub liquidity_tokens: u128,
+}
+
+impl TokenBalance {
+    /// Retrieves a copy of the amount that matches `token`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token matching the desired amount.
+    ///
+    /// # Returns
+    /// A value of type [`u128`]
+    fn get_amount_of(&self, token: &Token) -> u128 {
+        match token {
+            Token::TokenA {} => self.a_tokens,
+            Token::TokenB {} => self.b_tokens,
+            Token::TokenC {} => self.c_tokens,
+            Token::LiquidityToken {} => self.liquidity_tokens,
+        }
+    }
+
+    /// Retrieves a mutable reference to the amount that matches `token`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token matching the desired amount.
+    ///
+    /// # Returns
+    /// A mutable value of type [`&mut u128`]
+    fn get_mut_amount_of(&mut self, token: &Token) -> &mut u128 {
+        match token {
+            Token::TokenA {} => &mut self.a_tokens,
+            Token::TokenB {} => &mut self.b_tokens,
+            Token::TokenC {} => &mut self.c_tokens,
+            Token::LiquidityToken {} => &mut self.liquidity_tokens,
+        }
+    }
+
+    /// Retrieves the amounts of the pool tokens A, B and C.
+    ///
+    /// # Returns
+    /// The amounts of type [`[u128; 3]`]
+    fn pool_amounts(&self) -> [u128; 3] {
+        [self.a_tokens, self.b_tokens, self.c_tokens]
+    }
+
+    /// Checks that the user has no tokens.
+    ///
+    /// ### Returns:
+    /// True if the user has no tokens, false otherwise [`bool`]
+    fn user_has_no_tokens(&self) -> bool {
+        self.a_tokens == 0 && self.b_tokens == 0 && self.c_tokens == 0 && self.liquidity_tokens == 0
+    }
+}
+
+/// Empty token balance.
+const EMPTY_BALANCE: TokenBalance = TokenBalance {
+    a_tokens: 0,
+    b_tokens: 0,
+    c_tokens: 0,
+    liquidity_tokens: 0,
+};
+
+/// This is the state of the contract which is persisted on the chain.
+///
+/// The #\[state\] macro generates serialization logic for the struct.
+#[state]
+pub struct LiquiditySwapTriContractState {
+    /// The address of this contract
+    pub contract: Address,
+    /// The address of the first token.
+    pub token_a_address: Address,
+    /// The address of the second token.
+    pub token_b_address: Address,
+    /// The address of the third token.
+    pub token_c_address: Address,
+    /// The fee for making swaps per mille.
+    pub swap_fee_per_mille: u128,
+    /// The map containing all token balances of all users and the contract itself. <br>
+    /// The contract should always have a balance equal to the sum of all token balances.
+    pub token_balances: BTreeMap<Address, TokenBalance>,
+}
+
+impl LiquiditySwapTriContractState {
+    /// Adds tokens to the `token_balances` map of the contract. <br>
+    /// If the user isn't already present, creates an entry with an empty TokenBalance.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `user`: [`&Address`] - A reference to the user to add `amount` to.
+    ///
+    /// * `token`: [`Token`] - The token to add to.
+    ///
+    /// * `amount`: [`u128`] - The amount to add.
+    ///
+    fn add_to_token_balance(&mut self, user: Address, token: Token, amount: u128) {
+        let token_balance = self.get_mut_balance_for(&user);
+        *token_balance.get_mut_amount_of(&token) += amount;
+    }
+
+    /// Deducts tokens from the `token_balances` map of the contract. <br>
+    /// Requires that the user has at least as many tokens as is being deducted.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `user`: [`&Address`] - A reference to the user to deduct `amount` from.
+    ///
+    /// * `token`: [`Token`] - The token to subtract from.
+    ///
+    /// * `amount`: [`u128`] - The amount to subtract.
+    ///
+    fn deduct_from_token_balance(&mut self, user: Address, token: &Token, amount: u128) {
+        let token_balance = self.get_mut_balance_for(&user);
+        *token_balance.get_mut_amount_of(token) = token_balance
+            .get_amount_of(token)
+            .checked_sub(amount)
+            .expect("Insufficient funds");
+
+        if token_balance.user_has_no_tokens() {
+            self.token_balances.remove(&user);
+        }
+    }
+
+    /// Moves internal tokens from the `from`-address to the `to`-address.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `from`: [`Address`] - The address of the transferring party.
+    ///
+    /// * `to`: [`Address`] - The address of the receiving party.
+    ///
+    /// * `moved_token`: [`Token`] - The token being transferred.
+    ///
+    /// * `amount`: [`u128`] - The amount being transferred.
+    ///
+    fn move_tokens(&mut self, from: Address, to: Address, moved_token: Token, amount: u128) {
+        self.deduct_from_token_balance(from, &moved_token, amount);
+        self.add_to_token_balance(to, moved_token, amount);
+    }
+
+    /// Retrieves a copy of the token balance that matches `user`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `user`: [`&Address`] - A reference to the desired user address.
+    ///
+    /// # Returns
+    /// A copy of the token balance that matches `user`.
+    fn get_balance_for(&self, user: &Address) -> &TokenBalance {
+        self.token_balances.get(user).unwrap_or(&EMPTY_BALANCE)
+    }
+
+    /// Retrieves a mutable reference to the token balance that matches `user`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `user`: [`&Address`] - A reference to the desired user address.
+    ///
+    /// # Returns
+    /// The mutable reference to the token balance that matches `user`.
+    fn get_mut_balance_for(&mut self, user: &Address) -> &mut TokenBalance {
+        self.token_balances.entry(*user).or_insert(EMPTY_BALANCE)
+    }
+
+    /// Retrieves the pool token matching `token_address`. <br>
+    /// Requires that `token_address` matches one of the contract's pools.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token_address`: [`Address`] - The address of the token.
+    ///
+    /// # Returns
+    /// The matching token of type [`Token`]
+    fn deduce_token(&self, token_address: Address) -> Token {
+        if token_address == self.token_a_address {
+            Token::A
+        } else if token_address == self.token_b_address {
+            Token::B
+        } else if token_address == self.token_c_address {
+            Token::C
+        } else {
+            panic!("Provided invalid token address")
+        }
+    }
+
+    /// Retrieves the token with the `provided_token_address` being the "provided"-token
+    /// and the remaining two tokens being "opposite". <br>
+    /// Requires that `provided_token_address` matches the contract's pools.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `provided_token_address`: [`Address`] - The desired token to work with.
+    ///
+    /// # Returns
+    /// The provided token and the opposite tokens of type [`(Token, [Token; 2])`]
+    fn deduce_provided_opposite_tokens(
+        &self,
+        provided_token_address: Address,
+    ) -> (Token, [Token; 2]) {
+        let provided = self.deduce_token(provided_token_address);
+        let opposite = match provided {
+            Token::TokenA {} => [Token::B, Token::C],
+            Token::TokenB {} => [Token::A, Token::C],
+            _ => [Token::A, Token::B],
+        };
+        (provided, opposite)
+    }
+
+    /// Checks that the pools of the contracts have liquidity.
+    ///
+    /// ### Returns:
+    /// True if all three pools have liquidity, false otherwise [`bool`]
+    fn contract_pools_have_liquidity(&self) -> bool {
+        let contract_token_balance = self.get_balance_for(&self.contract);
+        contract_token_balance
+            .pool_amounts()
+            .iter()
+            .all(|amount| *amount != 0)
+    }
+}
+
+/// Initialize the contract.
+///
+/// # Parameters
+///
+///   * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///   * `token_a_address`: [`Address`] - The address of token A.
+///
+///   * `token_b_address`: [`Address`] - The address of token B.
+///
+///   * `token_c_address`: [`Address`] - The address of token C.
+///
+///   * `swap_fee_per_mille`: [`u128`] - The fee for swapping, in per mille, i.e. a fee set to 3 corresponds to a fee of 0.3%.
+///
+///
+/// The new state object of type [`LiquiditySwapTriContractState`] with all address fields initialized to their final state and remaining fields initialized to a default value.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    token_a_address: Address,
+    token_b_address: Address,
+    token_c_address: Address,
+    swap_fee_per_mille: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    for token_address in [token_a_address, token_b_address, token_c_address] {
+        assert_ne!(
+            token_address.address_type,
+            AddressType::Account,
+            "Tried to provide an account as token"
+        );
+    }
+    assert!(
+        token_a_address != token_b_address
+            && token_a_address != token_c_address
+            && token_b_address != token_c_address,
+        "Cannot initialize swap with duplicate tokens"
+    );
+    assert!(
+        swap_fee_per_mille <= 1000,
+        "Swap fee should not exceed 1000"
+    );
+
+    let new_state = LiquiditySwapTriContractState {
+        contract: context.contract_address,
+        token_a_address,
+        token_b_address,
+        token_c_address,
+        swap_fee_per_mille,
+        token_balances: BTreeMap::new(),
+    };
+
+    (new_state, vec![])
+}
+
+/// Deposit token {A, B, C} into the calling user's balance on the contract.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the deposited token contract.
+///
+///  * `amount`: [`u128`] - The amount to deposit.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapTriContractState`].
+#[action(shortname = 0x01)]
+pub fn deposit(
+    context: ContractContext,
+    state: LiquiditySwapTriContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    let from_token = state.deduce_token(token_address);
+    let mut event_group_builder = EventGroup::builder();
+    event_group_builder
+        .call(token_address, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+
+    event_group_builder
+        .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(from_token)
+        .argument(amount)
+        .done();
+
+    (state, vec![event_group_builder.build()])
+}
+
+/// Handles callback from [`deposit`]. <br>
+/// If the transfer event is successful,
+/// the caller of [`deposit`] is registered as a user of the contract with (additional) `amount` added to their balance.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The contractContext for the callback.
+///
+/// * `callback_context`: [`CallbackContext`] - The callbackContext.
+///
+/// * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+/// * `token`: [`Token`] - Indicating the token of which to add `amount` to.
+///
+/// * `amount`: [`u128`] - The desired amount to add to the user's total amount of `token`.
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapTriContractState`] with an updated entry for the caller of `deposit`.
+#[callback(shortname = 0x10)]
+pub fn deposit_callback(
+    context: ContractContext,
+    callback_context: CallbackContext,
+    mut state: LiquiditySwapTriContractState,
+    token: Token,
+    amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    assert!(callback_context.success, "Transfer did not succeed");
+
+    state.add_to_token_balance(context.sender, token, amount);
+
+    (state, vec![])
+}
+
+/// <pre>
+/// Swap <em>amount</em> of one of the tokens A, B or C to another of the tokens at the exchange rate dictated by <em>the constant sum formula</em>.
+/// The swap is executed on the token balances for the calling user.
+/// If the contract has empty pools, if the target pool cannot pay out the swap or if the caller does not have a sufficient balance of the token, the action fails.
+/// </pre>
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `from_token_address`: [`Address`] - The address of the token contract being swapped from.
+///
+///  * `to_token_address`: [`Address`] - The address of the token contract being swapped to.
+///
+///  * `amount`: [`u128`] - The amount to swap of the token matching `from_token_address`.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapTriContractState`] yielding the result of the swap.
+#[action(shortname = 0x02)]
+pub fn swap(
+    context: ContractContext,
+    mut state: LiquiditySwapTriContractState,
+    from_token_address: Address,
+    to_token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to perform a swap"
+    );
+
+    let from_token = state.deduce_token(from_token_address);
+    let to_token = state.deduce_token(to_token_address);
+    assert!(from_token != to_token, "Cannot swap a token to itself");
+
+    let to_pool = state
+        .get_balance_for(&state.contract)
+        .get_amount_of(&to_token);
+    let to_token_amount = calculate_swap_to_amount(amount, state.swap_fee_per_mille);
+    assert!(
+        to_token_amount < to_pool,
+        "Insufficient liquidity in the pool being swapped to"
+    );
+
+    state.move_tokens(context.sender, state.contract, from_token, amount);
+    state.move_tokens(state.contract, context.sender, to_token, to_token_amount);
+    (state, vec![])
+}
+
+/// <pre>
+/// Withdraw <em>amount</em> of token {A, B, C} from the contract for the calling user.
+/// This fails if `amount` is larger than the token balance of the corresponding token.
+///
+/// It preemptively updates the state of the user's balance before making the transfer.
+/// This means that if the transfer fails, the contract could end up with more money than it has registered, which is acceptable.
+/// This is to incentivize the user to spend enough gas to complete the transfer.
+/// </pre>
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the token contract to withdraw to.
+///
+///  * `amount`: [`u128`] - The amount to withdraw.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapTriContractState`].
+#[action(shortname = 0x03)]
+pub fn withdraw(
+    context: ContractContext,
+    mut state: LiquiditySwapTriContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    let token = state.deduce_token(token_address);
+
+    state.deduct_from_token_balance(context.sender, &token, amount);
+
+    let mut event_group_builder = EventGroup::builder();
+    event_group_builder
+        .call(token_address, token_contract_transfer())
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+
+    (state, vec![event_group_builder.build()])
+}
+
+/// Become a liquidity provider to the contract by providing `amount` of tokens from the caller's balance. <br>
+/// Amounts of the two other tokens matching the ratio of the pools are required to succeed and will be provided implicitly. <br>
+/// This is the inverse of [`reclaim_liquidity`].
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the provided token.
+///
+///  * `amount`: [`u128`] - The amount to provide.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapTriContractState`].
+#[action(shortname = 0x04)]
+pub fn provide_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapTriContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to provide liquidity"
+    );
+    let user = context.sender;
+    let (provided_token, opposite_tokens) = state.deduce_provided_opposite_tokens(token_address);
+    let contract_token_balance = state.get_balance_for(&state.contract);
+
+    let (opposite_equivalents, minted_liquidity_tokens) = calculate_equivalent_and_minted_tokens(
+        amount,
+        contract_token_balance.get_amount_of(&provided_token),
+        [
+            contract_token_balance.get_amount_of(&opposite_tokens[0]),
+            contract_token_balance.get_amount_of(&opposite_tokens[1]),
+        ],
+        contract_token_balance.liquidity_tokens,
+    );
+    assert!(
+        minted_liquidity_tokens > 0,
+        "Provided amount yielded 0 minted liquidity"
+    );
+
+    state.move_tokens(user, state.contract, provided_token, amount);
+    for (token, equivalent) in opposite_tokens.into_iter().zip(opposite_equivalents) {
+        state.move_tokens(user, state.contract, token, equivalent);
+    }
+    mint_liquidity_tokens(&mut state, user, minted_liquidity_tokens);
+    (state, vec![])
+}
+
+/// Reclaim a calling user's share of the contract's total liquidity based on `liquidity_token_amount`. <br>
+/// This is the inverse of [`provide_liquidity`].
+///
+/// Liquidity tokens are synonymous to weighted shares of the contract's total liquidity. <br>
+/// As such, we calculate how much to output of token A, B and C,
+/// based on the ratio between the input liquidity token amount and the total amount of liquidity minted by the contract.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The context for the action call.
+///
+/// * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+/// * `liquidity_token_amount`: [`u128`] - The amount of liquidity tokens to burn.
+///
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapTriContractState`].
+#[action(shortname = 0x05)]
+pub fn reclaim_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapTriContractState,
+    liquidity_token_amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    let user = context.sender;
+
+    state.deduct_from_token_balance(user, &Token::LIQUIDITY, liquidity_token_amount);
+
+    let contract_token_balance = state.get_balance_for(&state.contract);
+
+    let outputs = calculate_reclaim_output(
+        liquidity_token_amount,
+        contract_token_balance.pool_amounts(),
+        contract_token_balance.liquidity_tokens,
+    );
+
+    for (token, output) in Token::POOLS.into_iter().zip(outputs) {
+        state.move_tokens(state.contract, user, token, output);
+    }
+    state.deduct_from_token_balance(state.contract, &Token::LIQUIDITY, liquidity_token_amount);
+
+    (state, vec![])
+}
+
+/// <pre>
+/// Initialize pool {A, B, C} of the contract and mint initial liquidity tokens.
+/// This effectively makes the calling user the first LP,
+/// receiving liquidity tokens amounting to 100% of the contract's total liquidity,
+/// until another user becomes an LP.</pre>
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `token_a_amount`: [`u128`] - The amount to initialize pool A with.
+///
+///  * `token_b_amount`: [`u128`] - The amount to initialize pool B with.
+///
+///  * `token_c_amount`: [`u128`] - The amount to initialize pool C with.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapTriContractState`].
+#[action(shortname = 0x06)]
+pub fn provide_initial_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapTriContractState,
+    token_a_amount: u128,
+    token_b_amount: u128,
+    token_c_amount: u128,
+) -> (LiquiditySwapTriContractState, Vec<EventGroup>) {
+    assert!(
+        state.get_balance_for(&state.contract).liquidity_tokens == 0,
+        "Can only initialize when all pools are empty"
+    );
+    let amounts = [token_a_amount, token_b_amount, token_c_amount];
+    assert!(
+        amounts.iter().all(|amount| *amount > 0),
+        "All pools must be initialized with a positive amount"
+    );
+
+    let minted_liquidity_tokens = initial_liquidity_tokens(amounts);
+
+    let user = context.sender;
+    for (token, amount) in Token::POOLS.into_iter().zip(amounts) {
+        state.move_tokens(user, state.contract, token, amount);
+    }
+    mint_liquidity_tokens(&mut state, user, minted_liquidity_tokens);
+    (state, vec![])
+}
+
+/// Determines the initial amount of liquidity tokens, or shares, representing '100%' of the contract's liquidity. <br>
+/// Under the constant sum formula all three tokens are valued equally,
+/// so the initial liquidity is the total amount of tokens provided.
+fn initial_liquidity_tokens(amounts: [u128; 3]) -> u128 {
+    amounts.iter().sum()
+}
+
+/// Creates the `Shortname` corresponding to the `transfer` action of a token contract. <br>
+/// This is utilized in combination with an `EventGroupBuilder`'s `call` function.
+///
+/// ### Returns:
+///
+/// The `Shortname` corresponding to the `transfer` action of a token contract.
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Creates the `Shortname` corresponding to the `transfer_from` action of a token contract. <br>
+/// This is utilized in combination with an `EventGroupBuilder`'s `call` function.
+///
+/// ### Returns:
+///
+/// The `Shortname` corresponding to the `transfer_from` action of a token contract.
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
+/// Calculates how many of the target token you get for `swap_from_amount` given an exchange fee in per mille. <br>
+/// Under the constant sum formula tokens are exchanged 1:1, so the output is the input amount minus the fee, rounded down. <br>
+/// The fee remains in the pool being swapped from, which increases `k`.
+///
+/// ### Parameters:
+///
+/// * `swap_from_amount`: [`u128`] - The amount being swapped.
+///
+/// * `swap_fee_per_mille`: [`u128`] - The fee for swapping, in per mille.
+/// # Returns
+/// The amount received after swapping. [`u128`]
+fn calculate_swap_to_amount(swap_from_amount: u128, swap_fee_per_mille: u128) -> u128 {
+    let remainder_ratio = 1000 - swap_fee_per_mille;
+    remainder_ratio * swap_from_amount / 1000
+}
+
+/// Finds the amounts of the two opposite tokens during [`provide_liquidity`] matching the ratio of the pools,
+/// and the weighted shares that the provided amounts correspond to. <br>
+/// Due to integer rounding, a user may be depositing an additional token of each opposite token and mint one less than expected.
+///
+/// ### Parameters:
+///
+/// * `provided_amount`: [`u128`] - The amount being provided to the contract.
+///
+/// * `provided_pool`: [`u128`] - The token pool matching the provided amount.
+///
+/// * `opposite_pools`: [`[u128; 2]`] - The two opposite pools.
+///
+/// * `total_minted_liquidity` [`u128`] - The total current minted liquidity.
+/// # Returns
+/// The amounts of the opposite tokens and the minted liquidity ([`[u128; 2]`], [`u128`])
+fn calculate_equivalent_and_minted_tokens(
+    provided_amount: u128,
+    provided_pool: u128,
+    opposite_pools: [u128; 2],
+    total_minted_liquidity: u128,
+) -> ([u128; 2], u128) {
+    // Handle zero-case
+    let opposite_equivalents = opposite_pools.map(|opposite_pool| {
+        if provided_amount > 0 {
+            (provided_amount * opposite_pool / provided_pool) + 1
+        } else {
+            0
+        }
+    });
+    let minted_liquidity_tokens = provided_amount * total_minted_liquidity / provided_pool;
+    (opposite_equivalents, minted_liquidity_tokens)
+}
+
+/// Calculates the amount of token {A, B, C} that the input amount of liquidity tokens correspond to during [`reclaim_liquidity`]. <br>
+/// Due to integer rounding, a user may be withdrawing less of each pool token than expected.
+///
+/// ### Parameters:
+///
+/// * `liquidity_token_amount`: [`u128`] - The amount of liquidity tokens being reclaimed.
+///
+/// * `pools`: [`[u128; 3]`] - The pools A, B and C of this contract.
+///
+/// * `minted_liquidity` [`u128`] - The total current minted liquidity.
+/// # Returns
+/// The outputs of token A, B and C [`[u128; 3]`]
+fn calculate_reclaim_output(
+    liquidity_token_amount: u128,
+    pools: [u128; 3],
+    minted_liquidity: u128,
+) -> [u128; 3] {
+    pools.map(|pool| pool * liquidity_token_amount / minted_liquidity)
+}
+
+/// Mints liquidity tokens to the providing user and the contract.
+///
+/// ### Parameters:
+///
+///  * `state`: [`LiquiditySwapTriContractState`] - The current state of the contract.
+///
+///  * `user`: [`Address`] - The address of the user providing liquidity.
+///
+///  * `minted_liquidity_tokens`: [`u128`] - The amount of liquidity tokens that the provided tokens yields.
+fn mint_liquidity_tokens(
+    state: &mut LiquiditySwapTriContractState,
+    user: Address,
+    minted_liquidity_tokens: u128,
+) {
+    state.add_to_token_balance(user, Token::LIQUIDITY, minted_liquidity_tokens);
+    state.add_to_token_balance(state.contract, Token::LIQUIDITY, minted_liquidity_tokens);
+}
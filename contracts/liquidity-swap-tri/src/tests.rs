@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod test {
+    use crate::{
+        calculate_equivalent_and_minted_tokens, calculate_reclaim_output, calculate_swap_to_amount,
+        initial_liquidity_tokens,
+    };
+    use rand::Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    #[test]
+    pub fn test_calculate_swap_to_amount() {
+        assert_eq!(calculate_swap_to_amount(1000, 3), 997);
+        assert_eq!(calculate_swap_to_amount(1000, 0), 1000);
+        assert_eq!(calculate_swap_to_amount(1000, 1000), 0);
+        // Rounds down in favour of the pools
+        assert_eq!(calculate_swap_to_amount(10, 3), 9);
+    }
+
+    #[test]
+    pub fn test_initial_liquidity_tokens() {
+        assert_eq!(initial_liquidity_tokens([100, 200, 300]), 600);
+    }
+
+    #[test]
+    pub fn swap_stress_test() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(10);
+        let end_range = 10000;
+        for _ in 0..101 {
+            let mut pools: [u128; 3] = [
+                rng.gen_range(1..=end_range),
+                rng.gen_range(1..=end_range),
+                rng.gen_range(1..=end_range),
+            ];
+            let mut invariant: u128 = pools.iter().sum();
+
+            for _ in 0..10001 {
+                let from = rng.gen_range(0..3);
+                let to = (from + rng.gen_range(1..3)) % 3;
+                let input: u128 = rng.gen_range(1..=end_range);
+                let output = calculate_swap_to_amount(input, 3);
+
+                // Check invariants
+                assert!(output <= input);
+                assert!(input - output <= input * 3 / 1000 + 1);
+
+                // The contract rejects swaps draining the target pool
+                if output >= pools[to] {
+                    continue;
+                }
+
+                // Update state
+                pools[from] += input;
+                pools[to] -= output;
+                let new_invariant: u128 = pools.iter().sum();
+                assert!(invariant <= new_invariant);
+                invariant = new_invariant;
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_calculate_equivalent_and_minted_tokens() {
+        // Equal pools, providing 10% of token A
+        let (opposite, minted) = calculate_equivalent_and_minted_tokens(10, 100, [100, 100], 300);
+        assert_eq!(opposite, [11, 11]); // Depositing an additional token of each opposite token
+        assert_eq!(minted, 30);
+
+        // Token pools in ratio 1:2:3, providing 10% of token A
+        let (opposite, minted) = calculate_equivalent_and_minted_tokens(10, 100, [199, 299], 600);
+        assert_eq!(opposite, [20, 30]);
+        assert_eq!(minted, 60);
+    }
+
+    #[test]
+    pub fn calculate_equivalent_and_minted_tokens_stress_test() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(10);
+        let end_range = 10000;
+        for _ in 0..101 {
+            let mut pools: [u128; 3] = [
+                rng.gen_range(1..=end_range),
+                rng.gen_range(1..=end_range),
+                rng.gen_range(1..=end_range),
+            ];
+            let mut total_minted_liquidity = initial_liquidity_tokens(pools);
+
+            for _ in 0..10001 {
+                let provided_a_tokens = rng.gen_range(1..=end_range);
+
+                let (opposite, minted_liquidity) = calculate_equivalent_and_minted_tokens(
+                    provided_a_tokens,
+                    pools[0],
+                    [pools[1], pools[2]],
+                    total_minted_liquidity,
+                );
+
+                // Check invariants
+                assert_eq!(opposite[0], pools[1] * provided_a_tokens / pools[0] + 1);
+                assert_eq!(opposite[1], pools[2] * provided_a_tokens / pools[0] + 1);
+                assert_eq!(
+                    minted_liquidity,
+                    total_minted_liquidity * provided_a_tokens / pools[0]
+                );
+
+                // The value of a liquidity token never decreases by providing liquidity
+                let new_pools = [
+                    pools[0] + provided_a_tokens,
+                    pools[1] + opposite[0],
+                    pools[2] + opposite[1],
+                ];
+                let new_total_minted_liquidity = total_minted_liquidity + minted_liquidity;
+                for (pool, new_pool) in pools.iter().zip(new_pools) {
+                    assert!(pool * new_total_minted_liquidity <= new_pool * total_minted_liquidity);
+                }
+
+                // Update state
+                pools = new_pools;
+                total_minted_liquidity = new_total_minted_liquidity;
+            }
+        }
+    }
+
+    #[test]
+    pub fn calculate_reclaim_output_stress_test() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(10);
+        for _ in 0..1001 {
+            let mut pools: [u128; 3] = [
+                rng.gen_range(1..10001),
+                rng.gen_range(1..10001),
+                rng.gen_range(1..10001),
+            ];
+            let mut total_minted_liquidity = initial_liquidity_tokens(pools);
+
+            for _ in 0..101 {
+                let reclaimed_liquidity = rng.gen_range(1..total_minted_liquidity);
+
+                let outputs =
+                    calculate_reclaim_output(reclaimed_liquidity, pools, total_minted_liquidity);
+
+                // Check invariants
+                for (pool, output) in pools.iter_mut().zip(outputs) {
+                    assert_eq!(output, *pool * reclaimed_liquidity / total_minted_liquidity);
+                    assert!(output <= *pool);
+                    *pool -= output;
+                }
+                total_minted_liquidity -= reclaimed_liquidity;
+
+                // Stop early if we cannot reclaim more than 1 token
+                if total_minted_liquidity <= 1 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn zero_cases() {
+        assert_eq!(calculate_swap_to_amount(0, 3), 0);
+
+        let (opposite, minted) = calculate_equivalent_and_minted_tokens(0, 100, [200, 300], 600);
+        assert_eq!(opposite, [0, 0]);
+        assert_eq!(minted, 0);
+
+        assert_eq!(calculate_reclaim_output(0, [100, 200, 300], 600), [0, 0, 0]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Fuzzes the liquidity swap contract, invoking the generated entry points of `initialize` and of
+//! every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+use liquidity_swap::{
+    __pbc_autogen__batch_swap_wrapped, __pbc_autogen__burn_position_wrapped,
+    __pbc_autogen__deposit_and_provide_liquidity_callback_wrapped,
+    __pbc_autogen__deposit_and_provide_liquidity_wrapped, __pbc_autogen__deposit_callback_wrapped,
+    __pbc_autogen__deposit_measured_callback_wrapped, __pbc_autogen__deposit_wrapped,
+    __pbc_autogen__initialize_wrapped, __pbc_autogen__pause_wrapped,
+    __pbc_autogen__provide_initial_liquidity_wrapped, __pbc_autogen__provide_liquidity_wrapped,
+    __pbc_autogen__quote_provide_wrapped, __pbc_autogen__quote_reclaim_wrapped,
+    __pbc_autogen__quote_swap_wrapped, __pbc_autogen__reclaim_liquidity_wrapped,
+    __pbc_autogen__reset_epoch_stats_wrapped, __pbc_autogen__set_batch_settler_wrapped,
+    __pbc_autogen__set_referrer_share_wrapped, __pbc_autogen__swap_wrapped,
+    __pbc_autogen__sync_callback_wrapped, __pbc_autogen__sync_fees_wrapped,
+    __pbc_autogen__sync_wrapped, __pbc_autogen__transfer_liquidity_tokens_wrapped,
+    __pbc_autogen__transfer_position_wrapped, __pbc_autogen__unpause_wrapped,
+    __pbc_autogen__withdraw_all_wrapped, __pbc_autogen__withdraw_wrapped,
+};
+
+const CALLS: [Call; 26] = [
+    Call::Action(__pbc_autogen__deposit_wrapped),
+    Call::Callback(__pbc_autogen__deposit_callback_wrapped, 1),
+    Call::Callback(__pbc_autogen__deposit_measured_callback_wrapped, 3),
+    Call::Action(__pbc_autogen__swap_wrapped),
+    Call::Action(__pbc_autogen__withdraw_wrapped),
+    Call::Action(__pbc_autogen__withdraw_all_wrapped),
+    Call::Action(__pbc_autogen__deposit_and_provide_liquidity_wrapped),
+    Call::Callback(
+        __pbc_autogen__deposit_and_provide_liquidity_callback_wrapped,
+        2,
+    ),
+    Call::Action(__pbc_autogen__provide_liquidity_wrapped),
+    Call::Action(__pbc_autogen__reclaim_liquidity_wrapped),
+    Call::Action(__pbc_autogen__provide_initial_liquidity_wrapped),
+    Call::Action(__pbc_autogen__transfer_liquidity_tokens_wrapped),
+    Call::Action(__pbc_autogen__transfer_position_wrapped),
+    Call::Action(__pbc_autogen__burn_position_wrapped),
+    Call::Action(__pbc_autogen__set_batch_settler_wrapped),
+    Call::Action(__pbc_autogen__batch_swap_wrapped),
+    Call::Action(__pbc_autogen__quote_swap_wrapped),
+    Call::Action(__pbc_autogen__quote_provide_wrapped),
+    Call::Action(__pbc_autogen__quote_reclaim_wrapped),
+    Call::Action(__pbc_autogen__pause_wrapped),
+    Call::Action(__pbc_autogen__unpause_wrapped),
+    Call::Action(__pbc_autogen__set_referrer_share_wrapped),
+    Call::Action(__pbc_autogen__reset_epoch_stats_wrapped),
+    Call::Action(__pbc_autogen__sync_fees_wrapped),
+    Call::Action(__pbc_autogen__sync_wrapped),
+    Call::Callback(__pbc_autogen__sync_callback_wrapped, 2),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
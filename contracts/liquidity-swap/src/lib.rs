@@ -4,28 +4,78 @@
 //! The contracts exchanges (or swaps) between two types of tokens, <br>
 //! with an the exchange rate as given by the `constant product formula: x * y = k`. <br>
 //! We consider `x` to be the balance of token pool A and `y` to be the balance of token pool B and `k` to be their product. <br>
-//! When performing a swap, a fee of 0.3% is applied, based on the input amount, which is deducted from the output of the swap. <br>
-//! This effectively increases `k` after each swap.<br><br>
+//! When performing a swap, a fee is applied, based on the input amount, which is deducted from the input of the swap. <br>
+//! The fee is determined by the [`FeeTier`] selected at initialization, ranging from no fee to a fee of 1%. <br>
+//! The fee is not added to the pools, but is accrued to the liquidity providers in proportion to their liquidity tokens.
+//! Depending on the fee tier, a share of the fee is credited to the owner of the contract as a protocol fee.<br>
+//! A swap can name a referrer, such as the aggregator routing the swap, which is credited a share of the swap fee to its token balance.
+//! The referrer share is set by the owner with [`set_referrer_share`], and is zero until set.<br><br>
 //!
 //! In order to perform a swap, it is a prerequisite that the swapping user has already transferred
 //! at least one of the tokens to the contract via a call to [`deposit`]. <br>
+//! A liquidity provider can deposit both tokens and provide them as liquidity in a single transaction with [`deposit_and_provide_liquidity`]. <br>
 //! Additionally, some user (typically the creator of the contract) must have already deposited an amount of both token types and initialized both pools by a call to [`provide_initial_liquidity`]. <br><br>
 //!
+//! Market makers aggregating the intents of users can settle many swaps in a single transaction with [`batch_swap`].
+//! A user authorizes a settler with [`set_batch_settler`], after which the settler can swap the user's deposited tokens
+//! on behalf of the user, each request bounded by a minimum output. Requests that cannot be executed are skipped,
+//! and the outcome of every request of the latest batch is recorded in the state.<br><br>
+//!
 //! A user may [`withdraw`] the resulting tokens of a swap (or simply his own deposited tokens)
-//! to have the tokens transferred to his account, at any point.<br><br>
+//! to have the tokens transferred to his account, at any point.
+//! The entire balance of a token can be withdrawn with [`withdraw_all`].<br><br>
+//!
+//! The owner of the contract, being the account that initialized it, can [`pause`] the contract in an emergency,
+//! which blocks swaps and providing liquidity until the owner calls [`unpause`].
+//! Withdrawals and reclaiming liquidity are never blocked, such that users can always exit the contract.<br><br>
+//!
+//! The contract counts the volume, fees and number of swaps in `swap_stats`.
+//! The owner can end an epoch of statistics with [`reset_epoch_stats`], which moves the counters into `epoch_stats`.<br><br>
 //!
 //! Finally, a user may choose to become a liquidity provider (LP) of the contract
 //! by providing an amount of pre-deposited tokens taken from the user's internal token balance.
 //! This yields the LP a share of the contract's total liquidity, based on the ratio between the amount of provided liquidity and the contract's total liquidity at the time of providing. <br>
 //! These shares are referred to as `liquidity tokens` which are minted upon becoming an LP and may later be burned to receive a proportionate share of the contract's liquidity. <br>
-//! Since `k` increases between swaps, an LP stands to profit from burning their liquidity token after x amount of swaps has occurred.<br>
+//! Liquidity tokens can be moved between users with [`transfer_liquidity_tokens`].<br>
+//! When the pools are initialized, [`MINIMUM_LIQUIDITY`] of the minted liquidity tokens are permanently locked to the unreachable
+//! [`MINIMUM_LIQUIDITY_HOLDER`] address, such that the pools can never be fully drained.
+//! This avoids division by zero and makes manipulating the price of tiny pools expensive. The fees accrued to the locked
+//! liquidity tokens remain in the fee reserve.<br>
+//! The contract can be initialized with a lockup period for the initial liquidity, during which the initial LP
+//! can neither reclaim nor transfer their liquidity tokens. This prevents the initial LP from draining the pools
+//! shortly after attracting swaps and other LPs.<br>
+//! An LP earns the fees of the swaps made while holding liquidity tokens. The accrued fees are tracked with a fee growth accumulator
+//! per pool, holding the fees earned per liquidity token since the contract was initialized, and a checkpoint of the accumulators per LP.<br>
+//! The accrued fees of an LP can be read with [`LiquiditySwapContractState::accrued_fees`] and are added to the LP's token balance
+//! by calling [`sync_fees`], without burning any liquidity tokens. The fees are also synced whenever the LP's liquidity tokens change.<br>
 //! The larger the shares an LP has, the larger the profit. <br>
+//! The contract can instead be initialized in position mode, where each provision of liquidity mints a distinct [`Position`]
+//! holding the minted liquidity tokens, the provided amounts and the time of provision, rather than adding fungible liquidity tokens
+//! to the balance of the LP. Positions are identified by id, can be moved to another user with [`transfer_position`],
+//! and are burned with [`burn_position`] to reclaim their share of the pools. The fees accrued to a position are added to
+//! the balance of its owner whenever it is transferred or burned.<br>
 //! However, as with all investing, an LP also risks losing profit if the market-clearing price of at least one of the tokens decreases to a point that exceeds the rewards gained from swap-fees.<br><br>
 //! Since liquidity tokens represent an equal share of both tokens, when providing liquidity it is enforced that the user provides an equivalent value of the opposite token to the tokens provided. <br><br>
 //!
 //! Because the relative price of the two tokens can only be changed through swapping,
 //! divergences between the prices of the contract and the prices of similar external contracts create arbitrage opportunities.
-//! This mechanism ensures that the contract's prices always trend toward the market-clearing price.
+//! This mechanism ensures that the contract's prices always trend toward the market-clearing price.<br><br>
+//!
+//! The contract can be initialized to measure deposits, for tokens that take a fee on transfer or whose balances otherwise differ from the transferred amounts.
+//! A measured [`deposit`] queries the balance of the contract at the token contract before and after the transfer,
+//! and credits the user the amount actually received instead of the requested amount.
+//! The balances are queried with the `balance_of` action of the token contract (shortname 0x06), which returns the balance
+//! of the given account as return data, as implemented by the example token contract in `contracts/token`.
+//! For rebasing tokens, where the balance of the contract changes without any transfer, anyone can [`sync`] the pools with the balances of the contract.
+//! The difference between the balances and the tokens accounted to users, pools and the fee reserve is added to or deducted from the pools.<br><br>
+//!
+//! Every deposit, swap, withdrawal and change of liquidity is recorded as an [`Interaction`] with a sequence number,
+//! allowing off-chain indexers to reconstruct the trade history without replaying transactions. <br>
+//! Only the latest [`MAX_INTERACTIONS`] interactions are kept in the state, such that the state stays bounded.<br><br>
+//!
+//! Front-ends can fetch the exact outcome of a swap, a provision or a reclaim of liquidity with [`quote_swap`],
+//! [`quote_provide`] and [`quote_reclaim`]. These compute the outcome with the same rounding as the actual interaction,
+//! and record it as the [`Quote`] in `last_quote`, without changing any balances or pools.
 //!
 #![allow(unused_variables)]
 
@@ -37,14 +87,15 @@ extern crate core;
 
 use create_type_spec_derive::CreateTypeSpec;
 use pbc_contract_common::address::{Address, AddressType, Shortname};
-use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
 use pbc_contract_common::events::EventGroup;
+use pbc_traits::ReadRPC;
 use read_write_rpc_derive::ReadWriteRPC;
 use read_write_state_derive::ReadWriteState;
 use std::collections::btree_map::BTreeMap;
 
 /// Enum for token types
-#[derive(PartialEq, Eq, ReadWriteRPC, CreateTypeSpec)]
+#[derive(PartialEq, Eq, Clone, ReadWriteRPC, CreateTypeSpec)]
 #[cfg_attr(test, derive(Debug))]
 pub enum Token {
     /// The value representing token A.
@@ -65,8 +116,53 @@ impl Token {
     const LIQUIDITY: Token = Token::LiquidityToken {};
 }
 
+/// The fee tier of the contract, selected at initialization. <br>
+/// Each tier determines the fee for making swaps, and the share of the fee credited to the owner as a protocol fee.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum FeeTier {
+    /// No fee, for pairs of tokens that are pegged to each other.
+    #[discriminant(0)]
+    Zero {},
+    /// A fee of 0.1%, for pairs of stable tokens. No protocol fee is taken.
+    #[discriminant(1)]
+    Low {},
+    /// A fee of 0.3%, for most pairs of tokens. No protocol fee is taken.
+    #[discriminant(2)]
+    Standard {},
+    /// A fee of 1%, for pairs of volatile tokens. A tenth of the fee is taken as protocol fee.
+    #[discriminant(3)]
+    High {},
+}
+
+impl FeeTier {
+    /// The fee for making swaps per mille.
+    ///
+    /// # Returns
+    /// A value of type [`u128`]
+    pub fn swap_fee_per_mille(&self) -> u128 {
+        match self {
+            FeeTier::Zero {} => 0,
+            FeeTier::Low {} => 1,
+            FeeTier::Standard {} => 3,
+            FeeTier::High {} => 10,
+        }
+    }
+
+    /// The share of the swap fee credited to the owner as a protocol fee, per mille of the fee.
+    ///
+    /// # Returns
+    /// A value of type [`u128`]
+    pub fn protocol_share_per_mille(&self) -> u128 {
+        match self {
+            FeeTier::Zero {} | FeeTier::Low {} | FeeTier::Standard {} => 0,
+            FeeTier::High {} => 100,
+        }
+    }
+}
+
 /// Keeps track of how much of a given token a user owns within the scope of the contract.
-#[derive(ReadWriteState, CreateTypeSpec)]
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
 #[cfg_attr(test, derive())]
 pub struct TokenBalance {
     /// The amount of token A that a user can withdraw from the contract.
@@ -114,6 +210,35 @@ impl TokenBalance {
         }
     }
 
+    /// Creates a token balance holding only `amount` of `token`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token to hold.
+    ///
+    /// * `amount`: [`u128`] - The amount of `token` to hold.
+    ///
+    /// # Returns
+    /// A value of type [`TokenBalance`]
+    fn of(token: Token, amount: u128) -> TokenBalance {
+        EMPTY_BALANCE.and(token, amount)
+    }
+
+    /// Adds `amount` of `token` to the token balance.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token to add to.
+    ///
+    /// * `amount`: [`u128`] - The amount to add.
+    ///
+    /// # Returns
+    /// The updated value of type [`TokenBalance`]
+    fn and(mut self, token: Token, amount: u128) -> TokenBalance {
+        *self.get_mut_amount_of(&token) += amount;
+        self
+    }
+
     /// Checks that the user has no tokens.
     ///
     /// ### Returns:
@@ -123,6 +248,257 @@ impl TokenBalance {
     }
 }
 
+/// A compact digest of the pools of the contract, for light clients reading pricing data
+/// without reading the full `token_balances` map.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct PoolDigest {
+    /// The amount of token A in the pool of the contract.
+    pub a_reserve: u128,
+    /// The amount of token B in the pool of the contract.
+    pub b_reserve: u128,
+    /// The total amount of minted liquidity tokens.
+    pub total_liquidity: u128,
+    /// The fee for making swaps per mille.
+    pub swap_fee_per_mille: u128,
+    /// The block production time of the latest state change, in UTC millis.
+    pub last_update_millis: i64,
+}
+
+/// Cumulative swap statistics of the contract over an epoch. <br>
+/// The epoch is ended by the owner with [`reset_epoch_stats`].
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct SwapStats {
+    /// The block production time at which the epoch started, in UTC millis.
+    pub epoch_start_millis: i64,
+    /// The amount of token A swapped into or out of the pools.
+    pub total_volume_a: u128,
+    /// The amount of token B swapped into or out of the pools.
+    pub total_volume_b: u128,
+    /// The swap fees collected, including the referrer and protocol shares.
+    pub total_fees_collected: TokenBalance,
+    /// The number of swaps.
+    pub swap_count: u64,
+}
+
+impl SwapStats {
+    /// Creates the statistics of an epoch without swaps.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `epoch_start_millis`: [`i64`] - The block production time at which the epoch starts.
+    ///
+    /// # Returns
+    /// A value of type [`SwapStats`]
+    fn starting_at(epoch_start_millis: i64) -> SwapStats {
+        SwapStats {
+            epoch_start_millis,
+            total_volume_a: 0,
+            total_volume_b: 0,
+            total_fees_collected: EMPTY_BALANCE,
+            swap_count: 0,
+        }
+    }
+
+    /// Adds a swap to the statistics.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `spent`: [`&TokenBalance`] - The amount swapped into the pools.
+    ///
+    /// * `received`: [`&TokenBalance`] - The amount swapped out of the pools.
+    ///
+    /// * `fee`: [`TokenBalance`] - The fee of the swap.
+    ///
+    fn record_swap(&mut self, spent: &TokenBalance, received: &TokenBalance, fee: TokenBalance) {
+        self.total_volume_a += spent.a_tokens + received.a_tokens;
+        self.total_volume_b += spent.b_tokens + received.b_tokens;
+        self.total_fees_collected.a_tokens += fee.a_tokens;
+        self.total_fees_collected.b_tokens += fee.b_tokens;
+        self.swap_count += 1;
+    }
+}
+
+/// The kind of a user interaction with the contract.
+#[derive(PartialEq, Eq, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum InteractionKind {
+    /// Tokens were deposited to the user's balance, see [`deposit`].
+    #[discriminant(0)]
+    Deposit {},
+    /// Tokens were swapped, see [`swap`].
+    #[discriminant(1)]
+    Swap {},
+    /// Tokens were withdrawn from the user's balance, see [`withdraw`].
+    #[discriminant(2)]
+    Withdraw {},
+    /// Liquidity was provided, see [`provide_liquidity`] and [`provide_initial_liquidity`].
+    #[discriminant(3)]
+    ProvideLiquidity {},
+    /// Liquidity was reclaimed, see [`reclaim_liquidity`].
+    #[discriminant(4)]
+    ReclaimLiquidity {},
+    /// Liquidity tokens were transferred, see [`transfer_liquidity_tokens`].
+    #[discriminant(5)]
+    TransferLiquidity {},
+    /// Accrued fees were added to the user's balance, see [`sync_fees`].
+    #[discriminant(6)]
+    SyncFees {},
+    /// A share of a swap fee was added to the balance of the referrer of the swap, see [`swap`].
+    #[discriminant(7)]
+    ReferralReward {},
+    /// A share of a swap fee was added to the balance of the owner as protocol fee, see [`swap`].
+    #[discriminant(8)]
+    ProtocolFee {},
+    /// The pools were synced with the token balances of the contract, see [`sync`].
+    #[discriminant(9)]
+    Sync {},
+}
+
+/// A user interaction with the contract, recorded in the state for off-chain indexers.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Interaction {
+    /// The sequence number of the interaction, increasing by one for every recorded interaction.
+    pub sequence_number: u64,
+    /// The kind of the interaction.
+    pub kind: InteractionKind,
+    /// The user performing the interaction.
+    pub user: Address,
+    /// The receiving user of transferred liquidity tokens, if any.
+    pub recipient: Option<Address>,
+    /// The amounts of tokens deducted from the user's balance.
+    pub spent: TokenBalance,
+    /// The amounts of tokens added to the user's balance.
+    pub received: TokenBalance,
+    /// The block production time of the interaction, in UTC millis.
+    pub block_production_time: i64,
+}
+
+/// The fees accrued per liquidity token since the contract was initialized, for each pool. <br>
+/// The amounts are scaled by [`FEE_GROWTH_PRECISION`].
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct FeeGrowth {
+    /// The fees of token A accrued per liquidity token.
+    pub a_per_liquidity_token: u128,
+    /// The fees of token B accrued per liquidity token.
+    pub b_per_liquidity_token: u128,
+}
+
+impl FeeGrowth {
+    /// Retrieves a mutable reference to the fee growth of the pool of `token`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token of the pool, being either token A or B.
+    ///
+    /// # Returns
+    /// A mutable value of type [`&mut u128`]
+    fn get_mut_growth_of(&mut self, token: &Token) -> &mut u128 {
+        if token == &Token::A {
+            &mut self.a_per_liquidity_token
+        } else {
+            &mut self.b_per_liquidity_token
+        }
+    }
+}
+
+/// A request to swap tokens of a user, settled as part of a [`batch_swap`].
+#[derive(ReadWriteRPC, CreateTypeSpec)]
+pub struct SwapRequest {
+    /// The user whose tokens are swapped, who must have authorized the settler of the batch.
+    pub user: Address,
+    /// The address of the token contract being swapped from, deciding the direction of the swap.
+    pub token_address: Address,
+    /// The amount to swap of the token.
+    pub amount: u128,
+    /// The minimum amount of the opposite token the user must receive for the swap to be executed.
+    pub min_amount_out: u128,
+}
+
+/// The outcome of a [`SwapRequest`] in a batch.
+#[derive(PartialEq, Eq, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum BatchSwapStatus {
+    /// The swap was executed.
+    #[discriminant(0)]
+    Executed {},
+    /// The user has not authorized the settler of the batch.
+    #[discriminant(1)]
+    Unauthorized {},
+    /// The token is neither token A nor token B.
+    #[discriminant(2)]
+    InvalidToken {},
+    /// The user does not have a sufficient balance of the token.
+    #[discriminant(3)]
+    InsufficientBalance {},
+    /// The output of the swap would be less than the minimum output of the request.
+    #[discriminant(4)]
+    BelowMinimumOutput {},
+}
+
+/// The recorded result of a [`SwapRequest`] in the latest batch.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct BatchSwapResult {
+    /// The user of the request.
+    pub user: Address,
+    /// The outcome of the request.
+    pub status: BatchSwapStatus,
+    /// The amount of the opposite token received by the user, or zero if the swap was not executed.
+    pub amount_out: u128,
+}
+
+/// The maximum number of swap requests in a batch.
+pub const MAX_BATCH_SWAP_REQUESTS: usize = 50;
+
+/// The computed outcome of an interaction, recorded by [`quote_swap`], [`quote_provide`] and [`quote_reclaim`].
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Quote {
+    /// The kind of the quoted interaction.
+    pub kind: InteractionKind,
+    /// The amounts of tokens that would be deducted from the user's balance.
+    pub spent: TokenBalance,
+    /// The amounts of tokens that would be added to the user's balance.
+    pub received: TokenBalance,
+}
+
+/// A liquidity position minted by providing liquidity, when the contract is in position mode.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Position {
+    /// The owner of the position, who can transfer or burn it.
+    pub owner: Address,
+    /// The liquidity tokens minted for the position.
+    pub liquidity_tokens: u128,
+    /// The amount of token A provided for the position.
+    pub token_a_amount: u128,
+    /// The amount of token B provided for the position.
+    pub token_b_amount: u128,
+    /// The time at which the position was minted, in UTC millis.
+    pub created_at_millis: i64,
+    /// The fee growth at the latest fee sync of the position.
+    pub fee_checkpoint: FeeGrowth,
+}
+
+/// The scale of the fee growth accumulators, such that fees smaller than the number of liquidity tokens are not lost.
+pub const FEE_GROWTH_PRECISION: u128 = 1_000_000_000_000;
+
+/// Fee growth of a contract without swaps.
+const ZERO_FEE_GROWTH: FeeGrowth = FeeGrowth {
+    a_per_liquidity_token: 0,
+    b_per_liquidity_token: 0,
+};
+
+/// The maximum number of interactions kept in the state. <br>
+/// When the limit is reached the oldest interaction is dropped for every new interaction.
+pub const MAX_INTERACTIONS: usize = 100;
+
+/// The amount of liquidity tokens permanently locked when the pools are initialized.
+pub const MINIMUM_LIQUIDITY: u128 = 1000;
+
+/// The address holding the permanently locked liquidity tokens. No account controls this address.
+pub const MINIMUM_LIQUIDITY_HOLDER: Address = Address {
+    address_type: AddressType::Account,
+    identifier: [0; 20],
+};
+
 /// Empty token balance.
 const EMPTY_BALANCE: TokenBalance = TokenBalance {
     a_tokens: 0,
@@ -137,15 +513,59 @@ const EMPTY_BALANCE: TokenBalance = TokenBalance {
 pub struct LiquiditySwapContractState {
     /// The address of this contract
     pub contract: Address,
+    /// The digest of the pools, updated on every state change. <br>
+    /// It is stored directly after the fixed size `contract` field, so it can be read at a fixed offset in the serialized state.
+    pub digest: PoolDigest,
     /// The address of the first token.
     pub token_a_address: Address,
     /// The address of the second token.
     pub token_b_address: Address,
-    /// The fee for making swaps per mille.
-    pub swap_fee_per_mille: u128,
+    /// The fee tier of the contract, determining the swap fee and the protocol fee.
+    pub fee_tier: FeeTier,
     /// The map containing all token balances of all users and the contract itself. <br>
-    /// The contract should always have a balance equal to the sum of all token balances.
+    /// The contract should always have a balance equal to the sum of all token balances and the fee reserve.
     pub token_balances: BTreeMap<Address, TokenBalance>,
+    /// The latest interactions with the contract, ordered by sequence number. <br>
+    /// Holds at most [`MAX_INTERACTIONS`] interactions.
+    pub interactions: Vec<Interaction>,
+    /// The sequence number of the next recorded interaction.
+    pub next_sequence_number: u64,
+    /// The duration in millis for which the liquidity tokens of the initial LP are locked, if any.
+    pub initial_lp_lock_millis: Option<i64>,
+    /// The liquidity providers whose liquidity tokens are locked, mapped to the time in UTC millis at which the lock expires.
+    pub lp_locks: BTreeMap<Address, i64>,
+    /// The owner of the contract, who can pause and unpause it.
+    pub owner: Address,
+    /// Whether the contract is paused, blocking swaps and providing liquidity.
+    pub paused: bool,
+    /// The fees accrued per liquidity token since the contract was initialized.
+    pub fee_growth: FeeGrowth,
+    /// The fee growth at the latest fee sync of each liquidity provider.
+    pub fee_checkpoints: BTreeMap<Address, FeeGrowth>,
+    /// The swap fees accrued to the liquidity providers that have not yet been synced to their balances.
+    pub fee_reserve: TokenBalance,
+    /// The share of the swap fee credited to the referrer of a swap, per mille of the fee.
+    pub referrer_share_per_mille: u128,
+    /// The swap statistics of the current epoch.
+    pub swap_stats: SwapStats,
+    /// The number of the current epoch, starting from zero.
+    pub current_epoch: u32,
+    /// The swap statistics of the ended epochs, mapped by epoch number.
+    pub epoch_stats: BTreeMap<u32, SwapStats>,
+    /// Whether deposits are credited with the amount received by the contract, measured by querying the balance of the contract.
+    pub measure_deposits: bool,
+    /// Whether providing liquidity mints a [`Position`] instead of fungible liquidity tokens.
+    pub position_mode: bool,
+    /// The liquidity positions, mapped by id. Only used in position mode.
+    pub positions: BTreeMap<u64, Position>,
+    /// The id of the next minted position.
+    pub next_position_id: u64,
+    /// The settlers authorized by each user to swap the user's tokens in a batch.
+    pub batch_settlers: BTreeMap<Address, Vec<Address>>,
+    /// The results of the requests of the latest batch, in the order of the requests.
+    pub last_batch_results: Vec<BatchSwapResult>,
+    /// The latest quote computed by [`quote_swap`], [`quote_provide`] or [`quote_reclaim`].
+    pub last_quote: Option<Quote>,
 }
 
 impl LiquiditySwapContractState {
@@ -255,114 +675,618 @@ impl LiquiditySwapContractState {
         }
     }
 
-    /// Checks that the pools of the contracts have liquidity.
+    /// Computes the amount of `opposite_token` received for swapping `amount` of `provided_token`, given the current pools.
     ///
     /// ### Parameters:
     ///
-    ///  * `state`: [`&LiquiditySwapContractState`] - A reference to the current state of the contract.
+    /// * `provided_token`: [`&Token`] - The token being swapped from.
     ///
-    /// ### Returns:
-    /// True if the pools have liquidity, false otherwise [`bool`]
-    fn contract_pools_have_liquidity(&self) -> bool {
+    /// * `opposite_token`: [`&Token`] - The token being swapped to.
+    ///
+    /// * `amount`: [`u128`] - The amount of the provided token to swap.
+    ///
+    /// # Returns
+    /// A value of type [`u128`]
+    fn swap_output(&self, provided_token: &Token, opposite_token: &Token, amount: u128) -> u128 {
         let contract_token_balance = self.get_balance_for(&self.contract);
-        contract_token_balance.a_tokens != 0 && contract_token_balance.b_tokens != 0
+        calculate_swap_to_amount(
+            contract_token_balance.get_amount_of(provided_token),
+            contract_token_balance.get_amount_of(opposite_token),
+            amount,
+            self.fee_tier.swap_fee_per_mille(),
+        )
     }
-}
-
-/// Initialize the contract.
-///
-/// # Parameters
-///
-///   * `context`: [`ContractContext`] - The contract context containing sender and chain information.
-///
-///   * `token_a_address`: [`Address`] - The address of token A.
-///
-///   * `token_b_address`: [`Address`] - The address of token B.
-///
-///   * `swap_fee_per_mille`: [`u128`] - The fee for swapping, in per mille, i.e. a fee set to 3 corresponds to a fee of 0.3%.
-///
-///
-/// The new state object of type [`LiquiditySwapContractState`] with all address fields initialized to their final state and remaining fields initialized to a default value.
-///
-#[init]
-pub fn initialize(
-    context: ContractContext,
-    token_a_address: Address,
-    token_b_address: Address,
-    swap_fee_per_mille: u128,
-) -> (LiquiditySwapContractState, Vec<EventGroup>) {
-    assert_ne!(
-        token_a_address.address_type,
-        AddressType::Account,
-        "Tried to provide an account as token for token A"
-    );
-    assert_ne!(
-        token_b_address.address_type,
-        AddressType::Account,
-        "Tried to provide an account as token for token B"
-    );
-    assert_ne!(
-        token_a_address, token_b_address,
-        "Cannot initialize swap with duplicate tokens"
-    );
-    assert!(
-        swap_fee_per_mille <= 1000,
-        "Swap fee should not exceed 1000"
-    );
 
-    let new_state = LiquiditySwapContractState {
-        contract: context.contract_address,
-        token_a_address,
-        token_b_address,
-        swap_fee_per_mille,
-        token_balances: BTreeMap::new(),
-    };
+    /// Determines the outcome of a swap request settled by `settler`, without executing it.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `settler`: [`&Address`] - The settler of the batch.
+    ///
+    /// * `request`: [`&SwapRequest`] - The swap request.
+    ///
+    /// # Returns
+    /// The status of type [`BatchSwapStatus`], and the output of the swap if it can be executed.
+    fn check_swap_request(
+        &self,
+        settler: &Address,
+        request: &SwapRequest,
+    ) -> (BatchSwapStatus, u128) {
+        let authorized = &request.user == settler
+            || self
+                .batch_settlers
+                .get(&request.user)
+                .is_some_and(|settlers| settlers.contains(settler));
+        if !authorized {
+            return (BatchSwapStatus::Unauthorized {}, 0);
+        }
+        if request.token_address != self.token_a_address
+            && request.token_address != self.token_b_address
+        {
+            return (BatchSwapStatus::InvalidToken {}, 0);
+        }
+        let (provided_token, opposite_token) =
+            self.deduce_provided_opposite_tokens(request.token_address);
+        if self
+            .get_balance_for(&request.user)
+            .get_amount_of(&provided_token)
+            < request.amount
+        {
+            return (BatchSwapStatus::InsufficientBalance {}, 0);
+        }
+        let amount_out = self.swap_output(&provided_token, &opposite_token, request.amount);
+        if amount_out == 0 || amount_out < request.min_amount_out {
+            return (BatchSwapStatus::BelowMinimumOutput {}, 0);
+        }
+        (BatchSwapStatus::Executed {}, amount_out)
+    }
 
-    (new_state, vec![])
-}
+    /// Updates the digest to reflect the current pools of the contract.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the state changing call.
+    ///
+    fn update_digest(&mut self, context: &ContractContext) {
+        let contract_token_balance = self.get_balance_for(&self.contract);
+        self.digest = PoolDigest {
+            a_reserve: contract_token_balance.a_tokens,
+            b_reserve: contract_token_balance.b_tokens,
+            total_liquidity: contract_token_balance.liquidity_tokens,
+            swap_fee_per_mille: self.fee_tier.swap_fee_per_mille(),
+            last_update_millis: context.block_production_time,
+        };
+    }
 
-/// Deposit token {A, B} into the calling user's balance on the contract.
-///
-/// ### Parameters:
-///
-///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
-///
-///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
-///
-///  * `token_address`: [`Address`] - The address of the deposited token contract.
-///
-///  * `amount`: [`u128`] - The amount to deposit.
-///
-/// # Returns
-/// The unchanged state object of type [`LiquiditySwapContractState`].
-#[action(shortname = 0x01)]
-pub fn deposit(
-    context: ContractContext,
-    state: LiquiditySwapContractState,
-    token_address: Address,
-    amount: u128,
-) -> (LiquiditySwapContractState, Vec<EventGroup>) {
-    let (from_token, _) = state.deduce_provided_opposite_tokens(token_address);
-    let mut event_group_builder = EventGroup::builder();
-    event_group_builder
-        .call(token_address, token_contract_transfer_from())
-        .argument(context.sender)
-        .argument(context.contract_address)
-        .argument(amount)
-        .done();
+    /// Records an interaction with the contract, dropping the oldest interaction if the log is full.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the interacting call.
+    ///
+    /// * `kind`: [`InteractionKind`] - The kind of the interaction.
+    ///
+    /// * `recipient`: [`Option<Address>`] - The receiving user of transferred liquidity tokens, if any.
+    ///
+    /// * `spent`: [`TokenBalance`] - The amounts deducted from the user's balance.
+    ///
+    /// * `received`: [`TokenBalance`] - The amounts added to the user's balance.
+    ///
+    fn record_interaction(
+        &mut self,
+        context: &ContractContext,
+        kind: InteractionKind,
+        recipient: Option<Address>,
+        spent: TokenBalance,
+        received: TokenBalance,
+    ) {
+        self.record_interaction_of(context, context.sender, kind, recipient, spent, received);
+    }
 
-    event_group_builder
-        .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
-        .argument(from_token)
-        .argument(amount)
-        .done();
+    /// Records an interaction of `user` with the contract, which may differ from the sender of the call.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the interacting call.
+    ///
+    /// * `user`: [`Address`] - The user the interaction concerns.
+    ///
+    /// * `kind`: [`InteractionKind`] - The kind of the interaction.
+    ///
+    /// * `recipient`: [`Option<Address>`] - The receiving user of transferred liquidity tokens, if any.
+    ///
+    /// * `spent`: [`TokenBalance`] - The amounts deducted from the user's balance.
+    ///
+    /// * `received`: [`TokenBalance`] - The amounts added to the user's balance.
+    ///
+    fn record_interaction_of(
+        &mut self,
+        context: &ContractContext,
+        user: Address,
+        kind: InteractionKind,
+        recipient: Option<Address>,
+        spent: TokenBalance,
+        received: TokenBalance,
+    ) {
+        if self.interactions.len() >= MAX_INTERACTIONS {
+            self.interactions.remove(0);
+        }
+        self.interactions.push(Interaction {
+            sequence_number: self.next_sequence_number,
+            kind,
+            user,
+            recipient,
+            spent,
+            received,
+            block_production_time: context.block_production_time,
+        });
+        self.next_sequence_number += 1;
+    }
 
-    (state, vec![event_group_builder.build()])
-}
+    /// Requires that the liquidity tokens of `user` are not locked.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the call using the liquidity tokens.
+    ///
+    /// * `user`: [`&Address`] - A reference to the user owning the liquidity tokens.
+    ///
+    fn assert_liquidity_unlocked(&self, context: &ContractContext, user: &Address) {
+        if let Some(unlock_time) = self.lp_locks.get(user) {
+            assert!(
+                context.block_production_time >= *unlock_time,
+                "Liquidity tokens are locked until {unlock_time}"
+            );
+        }
+    }
 
-/// Handles callback from [`deposit`]. <br>
-/// If the transfer event is successful,
-/// the caller of [`deposit`] is registered as a user of the contract with (additional) `amount` added to their balance.
+    /// Computes the fees accrued to `user` since the user's latest fee sync, based on the user's liquidity tokens.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `user`: [`&Address`] - A reference to the liquidity provider.
+    ///
+    /// # Returns
+    /// The accrued fees of type [`TokenBalance`], holding no liquidity tokens.
+    pub fn accrued_fees(&self, user: &Address) -> TokenBalance {
+        let liquidity_tokens = self.get_balance_for(user).liquidity_tokens;
+        let checkpoint = self.fee_checkpoints.get(user).unwrap_or(&ZERO_FEE_GROWTH);
+        self.fees_since(liquidity_tokens, checkpoint)
+    }
+
+    /// Computes the fees accrued to the position with id `position_id` since its latest fee sync.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `position_id`: [`u64`] - The id of the position.
+    ///
+    /// # Returns
+    /// The accrued fees of type [`TokenBalance`], holding no liquidity tokens.
+    pub fn position_fees(&self, position_id: u64) -> TokenBalance {
+        let position = self.get_position(position_id);
+        self.fees_since(position.liquidity_tokens, &position.fee_checkpoint)
+    }
+
+    /// Computes the fees accrued to `liquidity_tokens` since the fee growth was at `checkpoint`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `liquidity_tokens`: [`u128`] - The amount of liquidity tokens earning fees.
+    ///
+    /// * `checkpoint`: [`&FeeGrowth`] - The fee growth at the latest fee sync.
+    ///
+    /// # Returns
+    /// The accrued fees of type [`TokenBalance`], holding no liquidity tokens.
+    fn fees_since(&self, liquidity_tokens: u128, checkpoint: &FeeGrowth) -> TokenBalance {
+        let a_growth = self.fee_growth.a_per_liquidity_token - checkpoint.a_per_liquidity_token;
+        let b_growth = self.fee_growth.b_per_liquidity_token - checkpoint.b_per_liquidity_token;
+        TokenBalance {
+            a_tokens: liquidity_tokens * a_growth / FEE_GROWTH_PRECISION,
+            b_tokens: liquidity_tokens * b_growth / FEE_GROWTH_PRECISION,
+            liquidity_tokens: 0,
+        }
+    }
+
+    /// Adds the accrued fees of `user` to the user's token balance, and checkpoints the current fee growth for the user. <br>
+    /// Must be called before the liquidity tokens of `user` change, such that the fees are accrued to the correct amount of liquidity tokens.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the syncing call.
+    ///
+    /// * `user`: [`Address`] - The liquidity provider to sync the fees of.
+    ///
+    /// # Returns
+    /// The synced fees of type [`TokenBalance`].
+    fn sync_fees_of(&mut self, context: &ContractContext, user: Address) -> TokenBalance {
+        let fees = self.accrued_fees(&user);
+        self.fee_checkpoints.insert(user, self.fee_growth.clone());
+        self.credit_fees(context, user, fees)
+    }
+
+    /// Adds the accrued fees of the position with id `position_id` to the token balance of its owner,
+    /// and checkpoints the current fee growth for the position. <br>
+    /// Must be called before the position changes owner or is burned.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the syncing call.
+    ///
+    /// * `position_id`: [`u64`] - The id of the position to sync the fees of.
+    ///
+    /// # Returns
+    /// The synced fees of type [`TokenBalance`].
+    fn sync_position_fees(&mut self, context: &ContractContext, position_id: u64) -> TokenBalance {
+        let fees = self.position_fees(position_id);
+        let fee_growth = self.fee_growth.clone();
+        let position = self.positions.get_mut(&position_id).unwrap();
+        position.fee_checkpoint = fee_growth;
+        let owner = position.owner;
+        self.credit_fees(context, owner, fees)
+    }
+
+    /// Moves synced `fees` from the fee reserve to the token balance of `user`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the syncing call.
+    ///
+    /// * `user`: [`Address`] - The user receiving the fees.
+    ///
+    /// * `fees`: [`TokenBalance`] - The synced fees.
+    ///
+    /// # Returns
+    /// The synced fees of type [`TokenBalance`].
+    fn credit_fees(
+        &mut self,
+        context: &ContractContext,
+        user: Address,
+        fees: TokenBalance,
+    ) -> TokenBalance {
+        if fees.user_has_no_tokens() {
+            return fees;
+        }
+        self.fee_reserve.a_tokens -= fees.a_tokens;
+        self.fee_reserve.b_tokens -= fees.b_tokens;
+        self.add_to_token_balance(user, Token::A, fees.a_tokens);
+        self.add_to_token_balance(user, Token::B, fees.b_tokens);
+        self.record_interaction_of(
+            context,
+            user,
+            InteractionKind::SyncFees {},
+            None,
+            EMPTY_BALANCE,
+            fees.clone(),
+        );
+        fees
+    }
+
+    /// Retrieves the position with id `position_id`, panicking if it does not exist.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `position_id`: [`u64`] - The id of the position.
+    ///
+    /// # Returns
+    /// A value of type [`&Position`]
+    fn get_position(&self, position_id: u64) -> &Position {
+        self.positions
+            .get(&position_id)
+            .unwrap_or_else(|| panic!("No position with id {position_id}"))
+    }
+
+    /// Requires that the position with id `position_id` exists and is owned by `user`,
+    /// and that the liquidity of `user` is not locked.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`] - The context of the call using the position.
+    ///
+    /// * `position_id`: [`u64`] - The id of the position.
+    ///
+    /// * `user`: [`&Address`] - A reference to the user using the position.
+    ///
+    fn assert_position_usable_by(
+        &self,
+        context: &ContractContext,
+        position_id: u64,
+        user: &Address,
+    ) {
+        assert_eq!(
+            &self.get_position(position_id).owner,
+            user,
+            "Only the owner of the position can transfer or burn it"
+        );
+        self.assert_liquidity_unlocked(context, user);
+    }
+
+    /// Accrues a swap fee to the liquidity providers, by adding it to the fee reserve and the fee growth of its pool.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`&Token`] - The token the fee was paid in.
+    ///
+    /// * `fee`: [`u128`] - The amount of the fee.
+    ///
+    fn accrue_swap_fee(&mut self, token: &Token, fee: u128) {
+        let total_liquidity = self.get_balance_for(&self.contract).liquidity_tokens;
+        *self.fee_reserve.get_mut_amount_of(token) += fee;
+        *self.fee_growth.get_mut_growth_of(token) += fee * FEE_GROWTH_PRECISION / total_liquidity;
+    }
+
+    /// Computes the amount of `token` that the contract accounts for, being the sum of all token balances,
+    /// including the pools, and the fee reserve. <br>
+    /// The balance of the contract at the token contract should be at least this amount.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`&Token`] - The token to compute the accounted amount of.
+    ///
+    /// # Returns
+    /// A value of type [`u128`]
+    fn accounted_amount_of(&self, token: &Token) -> u128 {
+        let balances: u128 = self
+            .token_balances
+            .values()
+            .map(|balance| balance.get_amount_of(token))
+            .sum();
+        balances + self.fee_reserve.get_amount_of(token)
+    }
+
+    /// Adjusts the pool of `token` such that the accounted amount matches the `actual_balance` of the contract. <br>
+    /// A surplus is added to the pool, and a shortfall is deducted from the pool, as far as the pool allows.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`Token`] - The token of the pool.
+    ///
+    /// * `actual_balance`: [`u128`] - The balance of the contract at the token contract.
+    ///
+    /// # Returns
+    /// The amount added to the pool, and the amount deducted from the pool, of type [`(u128, u128)`]
+    fn sync_pool(&mut self, token: Token, actual_balance: u128) -> (u128, u128) {
+        let accounted = self.accounted_amount_of(&token);
+        let contract = self.contract;
+        let pool = self
+            .get_mut_balance_for(&contract)
+            .get_mut_amount_of(&token);
+        if actual_balance >= accounted {
+            let surplus = actual_balance - accounted;
+            *pool += surplus;
+            (surplus, 0)
+        } else {
+            let shortfall = (accounted - actual_balance).min(*pool);
+            *pool -= shortfall;
+            (0, shortfall)
+        }
+    }
+
+    /// Requires that the contract is not paused.
+    fn assert_not_paused(&self) {
+        assert!(!self.paused, "The contract is paused");
+    }
+
+    /// Checks that the pools of the contracts have liquidity.
+    ///
+    /// ### Parameters:
+    ///
+    ///  * `state`: [`&LiquiditySwapContractState`] - A reference to the current state of the contract.
+    ///
+    /// ### Returns:
+    /// True if the pools have liquidity, false otherwise [`bool`]
+    fn contract_pools_have_liquidity(&self) -> bool {
+        let contract_token_balance = self.get_balance_for(&self.contract);
+        contract_token_balance.a_tokens != 0 && contract_token_balance.b_tokens != 0
+    }
+
+    /// Checks whether `user` can provide `amount` of `provided_token` as liquidity from the user's balance,
+    /// such that [`provide_liquidity`] would succeed.
+    ///
+    /// ### Parameters:
+    ///
+    ///  * `user`: [`&Address`] - The user providing liquidity.
+    ///
+    ///  * `provided_token`: [`&Token`] - The token being provided.
+    ///
+    ///  * `opposite_token`: [`&Token`] - The opposite token, of which an equivalent amount is provided.
+    ///
+    ///  * `amount`: [`u128`] - The amount of `provided_token` to provide.
+    ///
+    /// ### Returns:
+    /// True if the liquidity can be provided, false otherwise [`bool`]
+    fn can_provide_liquidity(
+        &self,
+        user: &Address,
+        provided_token: &Token,
+        opposite_token: &Token,
+        amount: u128,
+    ) -> bool {
+        if self.paused || !self.contract_pools_have_liquidity() {
+            return false;
+        }
+        let contract_token_balance = self.get_balance_for(&self.contract);
+        let (opposite_equivalent, minted_liquidity_tokens) = calculate_equivalent_and_minted_tokens(
+            amount,
+            contract_token_balance.get_amount_of(provided_token),
+            contract_token_balance.get_amount_of(opposite_token),
+            contract_token_balance.liquidity_tokens,
+        );
+        let user_balance = self.get_balance_for(user);
+        minted_liquidity_tokens > 0
+            && user_balance.get_amount_of(provided_token) >= amount
+            && user_balance.get_amount_of(opposite_token) >= opposite_equivalent
+    }
+
+    /// Retrieves the address of the token contract of `token`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token`: [`&Token`] - Either token A or token B.
+    ///
+    /// # Returns
+    /// The address of the token contract of type [`Address`]
+    fn token_address_of(&self, token: &Token) -> Address {
+        if token == &Token::A {
+            self.token_a_address
+        } else {
+            self.token_b_address
+        }
+    }
+}
+
+/// Initialize the contract.
+///
+/// # Parameters
+///
+///   * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///   * `token_a_address`: [`Address`] - The address of token A.
+///
+///   * `token_b_address`: [`Address`] - The address of token B.
+///
+///   * `fee_tier`: [`FeeTier`] - The fee tier of the contract, determining the fee for swapping and the protocol fee.
+///
+///   * `initial_lp_lock_millis`: [`Option<i64>`] - The duration in millis for which the initial LP cannot reclaim or transfer
+///     their liquidity tokens, or None if the initial liquidity is not locked.
+///
+///   * `measure_deposits`: [`bool`] - Whether deposits are credited with the amount received by the contract, for tokens that take
+///     a fee on transfer. The tokens must then provide a `balance_of` action with shortname 0x06,
+///     returning the balance of the given account as return data, like the example token contract.
+///
+///   * `position_mode`: [`bool`] - Whether providing liquidity mints a [`Position`] instead of fungible liquidity tokens.
+///
+///
+/// The new state object of type [`LiquiditySwapContractState`] with all address fields initialized to their final state and remaining fields initialized to a default value.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    token_a_address: Address,
+    token_b_address: Address,
+    fee_tier: FeeTier,
+    initial_lp_lock_millis: Option<i64>,
+    measure_deposits: bool,
+    position_mode: bool,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert_ne!(
+        token_a_address.address_type,
+        AddressType::Account,
+        "Tried to provide an account as token for token A"
+    );
+    assert_ne!(
+        token_b_address.address_type,
+        AddressType::Account,
+        "Tried to provide an account as token for token B"
+    );
+    assert_ne!(
+        token_a_address, token_b_address,
+        "Cannot initialize swap with duplicate tokens"
+    );
+    assert!(
+        initial_lp_lock_millis.unwrap_or(0) >= 0,
+        "Lockup period of the initial liquidity cannot be negative"
+    );
+
+    let new_state = LiquiditySwapContractState {
+        contract: context.contract_address,
+        digest: PoolDigest {
+            a_reserve: 0,
+            b_reserve: 0,
+            total_liquidity: 0,
+            swap_fee_per_mille: fee_tier.swap_fee_per_mille(),
+            last_update_millis: context.block_production_time,
+        },
+        token_a_address,
+        token_b_address,
+        fee_tier,
+        token_balances: BTreeMap::new(),
+        interactions: vec![],
+        next_sequence_number: 0,
+        initial_lp_lock_millis,
+        lp_locks: BTreeMap::new(),
+        owner: context.sender,
+        paused: false,
+        fee_growth: ZERO_FEE_GROWTH,
+        fee_checkpoints: BTreeMap::new(),
+        fee_reserve: EMPTY_BALANCE,
+        referrer_share_per_mille: 0,
+        swap_stats: SwapStats::starting_at(context.block_production_time),
+        current_epoch: 0,
+        epoch_stats: BTreeMap::new(),
+        measure_deposits,
+        position_mode,
+        positions: BTreeMap::new(),
+        next_position_id: 0,
+        batch_settlers: BTreeMap::new(),
+        last_batch_results: vec![],
+        last_quote: None,
+    };
+
+    (new_state, vec![])
+}
+
+/// Deposit token {A, B} into the calling user's balance on the contract. <br>
+/// If the contract measures deposits, the balance of the contract is queried before and after the transfer,
+/// and the amount received is credited in [`deposit_measured_callback`].
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the deposited token contract.
+///
+///  * `amount`: [`u128`] - The amount to deposit.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x01)]
+pub fn deposit(
+    context: ContractContext,
+    state: LiquiditySwapContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let (from_token, _) = state.deduce_provided_opposite_tokens(token_address);
+    let mut event_group_builder = EventGroup::builder();
+    if state.measure_deposits {
+        event_group_builder
+            .call(token_address, token_contract_balance_of())
+            .argument(context.contract_address)
+            .done();
+    }
+    event_group_builder
+        .call(token_address, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+
+    if state.measure_deposits {
+        event_group_builder
+            .call(token_address, token_contract_balance_of())
+            .argument(context.contract_address)
+            .done();
+        event_group_builder
+            .with_callback(SHORTNAME_DEPOSIT_MEASURED_CALLBACK)
+            .argument(from_token)
+            .argument(amount)
+            .done();
+    } else {
+        event_group_builder
+            .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+            .argument(from_token)
+            .argument(amount)
+            .done();
+    }
+
+    (state, vec![event_group_builder.build()])
+}
+
+/// Handles callback from [`deposit`]. <br>
+/// If the transfer event is successful,
+/// the caller of [`deposit`] is registered as a user of the contract with (additional) `amount` added to their balance.
 ///
 /// ### Parameters:
 ///
@@ -387,16 +1311,90 @@ pub fn deposit_callback(
     amount: u128,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
     assert!(callback_context.success, "Transfer did not succeed");
+    credit_deposit(&mut state, &context, token, amount);
+    (state, vec![])
+}
 
-    state.add_to_token_balance(context.sender, token, amount);
-
+/// Handles callback from a measured [`deposit`]. <br>
+/// The results of the balance queries and the transfer are checked separately.
+/// If the transfer is successful, the caller of [`deposit`] is credited the amount received by the contract,
+/// being the difference between the balances of the contract after and before the transfer, and at most `amount`.
+/// If either balance query failed, the received amount cannot be measured, and the requested `amount` is credited instead.
+/// Any shortfall is then deducted from the pools by the next [`sync`].
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The contractContext for the callback.
+///
+/// * `callback_context`: [`CallbackContext`] - The callbackContext, with the results of the balance queries and the transfer.
+///
+/// * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// * `token`: [`Token`] - Indicating the token of which the received amount is added.
+///
+/// * `amount`: [`u128`] - The amount requested to be transferred.
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapContractState`] with an updated entry for the caller of `deposit`.
+#[callback(shortname = 0x12)]
+pub fn deposit_measured_callback(
+    context: ContractContext,
+    callback_context: CallbackContext,
+    mut state: LiquiditySwapContractState,
+    token: Token,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert!(
+        callback_context.results[1].succeeded,
+        "Transfer did not succeed"
+    );
+    let received = match (
+        read_balance(&callback_context.results[0]),
+        read_balance(&callback_context.results[2]),
+    ) {
+        (Some(balance_before), Some(balance_after)) => {
+            balance_after.saturating_sub(balance_before).min(amount)
+        }
+        _ => amount,
+    };
+    assert!(received > 0, "No tokens were received");
+    credit_deposit(&mut state, &context, token, received);
     (state, vec![])
 }
 
+/// Credits a deposit of `amount` of `token` to the balance of the depositing user, and records it as an interaction.
+///
+/// ### Parameters:
+///
+/// * `state`: [`&mut LiquiditySwapContractState`] - The current state of the contract.
+///
+/// * `context`: [`&ContractContext`] - The context of the deposit callback.
+///
+/// * `token`: [`Token`] - The deposited token.
+///
+/// * `amount`: [`u128`] - The amount to credit.
+fn credit_deposit(
+    state: &mut LiquiditySwapContractState,
+    context: &ContractContext,
+    token: Token,
+    amount: u128,
+) {
+    state.record_interaction(
+        context,
+        InteractionKind::Deposit {},
+        None,
+        EMPTY_BALANCE,
+        TokenBalance::of(token.clone(), amount),
+    );
+    state.add_to_token_balance(context.sender, token, amount);
+    state.update_digest(context);
+}
+
 /// <pre>
 /// Swap <em>amount</em> of token A or B to the opposite token at the exchange rate dictated by <em>the constant product formula</em>.
 /// The swap is executed on the token balances for the calling user.
 /// If the contract has empty pools or if the caller does not have a sufficient balance of the token, the action fails.
+/// If a referrer is given, the referrer share of the swap fee is added to the referrer's balance instead of being accrued to the liquidity providers.
 /// </pre>
 /// ### Parameters:
 ///
@@ -408,6 +1406,8 @@ pub fn deposit_callback(
 ///
 ///  * `amount`: [`u128`] - The amount to swap of the token matching `input_token`.
 ///
+///  * `referrer`: [`Option<Address>`] - The referrer of the swap, if any. The caller cannot refer themselves.
+///
 /// # Returns
 /// The updated state object of type [`LiquiditySwapContractState`] yielding the result of the swap.
 #[action(shortname = 0x02)]
@@ -416,28 +1416,30 @@ pub fn swap(
     mut state: LiquiditySwapContractState,
     token_address: Address,
     amount: u128,
+    referrer: Option<Address>,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_not_paused();
     assert!(
         state.contract_pools_have_liquidity(),
         "Pools must have existing liquidity to perform a swap"
     );
-
-    let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
-    let contract_token_balance = state.get_balance_for(&state.contract);
-
-    let opposite_token_amount = calculate_swap_to_amount(
-        contract_token_balance.get_amount_of(&provided_token),
-        contract_token_balance.get_amount_of(&opposite_token),
-        amount,
-        state.swap_fee_per_mille,
+    assert_ne!(
+        referrer,
+        Some(context.sender),
+        "Cannot refer one's own swap"
     );
 
-    state.move_tokens(context.sender, state.contract, provided_token, amount);
-    state.move_tokens(
-        state.contract,
+    let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
+    let opposite_token_amount = state.swap_output(&provided_token, &opposite_token, amount);
+    execute_swap(
+        &mut state,
+        &context,
         context.sender,
+        provided_token,
         opposite_token,
+        amount,
         opposite_token_amount,
+        referrer,
     );
     (state, vec![])
 }
@@ -456,36 +1458,678 @@ pub fn swap(
 ///
 ///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
 ///
-///  * `token_address`: [`Address`] - The address of the token contract to withdraw to.
+///  * `token_address`: [`Address`] - The address of the token contract to withdraw to.
+///
+///  * `amount`: [`u128`] - The amount to withdraw.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x03)]
+pub fn withdraw(
+    context: ContractContext,
+    state: LiquiditySwapContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let (provided_token, _) = state.deduce_provided_opposite_tokens(token_address);
+    withdraw_internal(context, state, token_address, provided_token, amount)
+}
+
+/// Withdraw the entire balance of token {A, B} from the contract for the calling user. <br>
+/// This works like [`withdraw`] with the amount being the user's balance of the token.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the token contract to withdraw to.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x0A)]
+pub fn withdraw_all(
+    context: ContractContext,
+    state: LiquiditySwapContractState,
+    token_address: Address,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let (provided_token, _) = state.deduce_provided_opposite_tokens(token_address);
+    let amount = state
+        .get_balance_for(&context.sender)
+        .get_amount_of(&provided_token);
+    assert!(amount > 0, "No tokens to withdraw");
+    withdraw_internal(context, state, token_address, provided_token, amount)
+}
+
+/// Deducts `amount` of `token` from the calling user's balance and transfers it to the user.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the token contract to withdraw to.
+///
+///  * `token`: [`Token`] - The withdrawn token, matching `token_address`.
+///
+///  * `amount`: [`u128`] - The amount to withdraw.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`] and the transfer event.
+fn withdraw_internal(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    token_address: Address,
+    token: Token,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.deduct_from_token_balance(context.sender, &token, amount);
+    state.update_digest(&context);
+    state.record_interaction(
+        &context,
+        InteractionKind::Withdraw {},
+        None,
+        TokenBalance::of(token, amount),
+        EMPTY_BALANCE,
+    );
+
+    let mut event_group_builder = EventGroup::builder();
+    event_group_builder
+        .call(token_address, token_contract_transfer())
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+
+    (state, vec![event_group_builder.build()])
+}
+
+/// Deposit `amount` of token {A, B} together with the equivalent amount of the opposite token, and provide them as liquidity,
+/// in a single transaction. <br>
+/// The equivalent amount of the opposite token is computed from the pools at the time of the call,
+/// and both tokens are transferred to the contract in the same event group, with a callback to [`deposit_and_provide_liquidity_callback`].
+/// This is not supported if the contract measures deposits.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the provided token.
+///
+///  * `amount`: [`u128`] - The amount to deposit and provide.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x0D)]
+pub fn deposit_and_provide_liquidity(
+    context: ContractContext,
+    state: LiquiditySwapContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_not_paused();
+    assert!(
+        !state.measure_deposits,
+        "Deposits are measured, and must be made with deposit"
+    );
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to provide liquidity"
+    );
+    let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
+    let contract_token_balance = state.get_balance_for(&state.contract);
+    let (opposite_equivalent, _) = calculate_equivalent_and_minted_tokens(
+        amount,
+        contract_token_balance.get_amount_of(&provided_token),
+        contract_token_balance.get_amount_of(&opposite_token),
+        contract_token_balance.liquidity_tokens,
+    );
+
+    let mut event_group_builder = EventGroup::builder();
+    event_group_builder
+        .call(token_address, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    event_group_builder
+        .call(
+            state.token_address_of(&opposite_token),
+            token_contract_transfer_from(),
+        )
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(opposite_equivalent)
+        .done();
+    event_group_builder
+        .with_callback(SHORTNAME_DEPOSIT_AND_PROVIDE_LIQUIDITY_CALLBACK)
+        .argument(provided_token)
+        .argument(amount)
+        .argument(opposite_token)
+        .argument(opposite_equivalent)
+        .done();
+
+    (state, vec![event_group_builder.build()])
+}
+
+/// Handles callback from [`deposit_and_provide_liquidity`]. <br>
+/// Each successful transfer is added to the caller's balance, like a [`deposit`].
+/// If both transfers succeeded, `amount` of the provided token is then provided as liquidity, like with [`provide_liquidity`]. <br>
+/// If a transfer failed, or the liquidity can no longer be provided, for instance because the pools have changed since the call,
+/// the deposited tokens are left in the caller's balance, from where they can be withdrawn or provided later.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The contractContext for the callback.
+///
+/// * `callback_context`: [`CallbackContext`] - The callbackContext.
+///
+/// * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// * `provided_token`: [`Token`] - The provided token.
+///
+/// * `amount`: [`u128`] - The deposited amount of the provided token, which is provided as liquidity.
+///
+/// * `opposite_token`: [`Token`] - The opposite token.
+///
+/// * `opposite_amount`: [`u128`] - The deposited amount of the opposite token.
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[callback(shortname = 0x11)]
+pub fn deposit_and_provide_liquidity_callback(
+    context: ContractContext,
+    callback_context: CallbackContext,
+    mut state: LiquiditySwapContractState,
+    provided_token: Token,
+    amount: u128,
+    opposite_token: Token,
+    opposite_amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let deposits = [
+        (provided_token.clone(), amount),
+        (opposite_token.clone(), opposite_amount),
+    ];
+    for (result, (token, deposited)) in callback_context.results.iter().zip(deposits) {
+        if result.succeeded {
+            state.record_interaction(
+                &context,
+                InteractionKind::Deposit {},
+                None,
+                EMPTY_BALANCE,
+                TokenBalance::of(token.clone(), deposited),
+            );
+            state.add_to_token_balance(context.sender, token, deposited);
+        }
+    }
+    state.update_digest(&context);
+
+    if callback_context.success
+        && state.can_provide_liquidity(&context.sender, &provided_token, &opposite_token, amount)
+    {
+        let token_address = state.token_address_of(&provided_token);
+        return provide_liquidity(context, state, token_address, amount);
+    }
+    (state, vec![])
+}
+
+/// Become a liquidity provider to the contract by providing `amount` of tokens from the caller's balance. <br>
+/// An equivalent amount of the opposite token is required to succeed and will be provided implicitly. <br>
+/// This is the inverse of [`reclaim_liquidity`].
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the provided token.
+///
+///  * `token_amount`: [`u128`] - The amount to provide.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x04)]
+pub fn provide_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    token_address: Address,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_not_paused();
+    let user = &context.sender;
+    state.sync_fees_of(&context, *user);
+    let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
+    let contract_token_balance = state.get_balance_for(&state.contract);
+
+    let (opposite_equivalent, minted_liquidity_tokens) = calculate_equivalent_and_minted_tokens(
+        amount,
+        contract_token_balance.get_amount_of(&provided_token),
+        contract_token_balance.get_amount_of(&opposite_token),
+        contract_token_balance.liquidity_tokens,
+    );
+    assert!(
+        minted_liquidity_tokens > 0,
+        "Provided amount yielded 0 minted liquidity"
+    );
+
+    provide_liquidity_internal(
+        &mut state,
+        &context,
+        user,
+        token_address,
+        amount,
+        opposite_equivalent,
+        minted_liquidity_tokens,
+    );
+    state.update_digest(&context);
+    state.record_interaction(
+        &context,
+        InteractionKind::ProvideLiquidity {},
+        None,
+        TokenBalance::of(provided_token, amount).and(opposite_token, opposite_equivalent),
+        TokenBalance::of(Token::LIQUIDITY, minted_liquidity_tokens),
+    );
+    (state, vec![])
+}
+
+/// Reclaim a calling user's share of the contract's total liquidity based on `liquidity_token_amount`. <br>
+/// This is the inverse of [`provide_liquidity`].
+///
+/// Liquidity tokens are synonymous to weighted shares of the contract's total liquidity. <br>
+/// As such, we calculate how much to output of token A and B,
+/// based on the ratio between the input liquidity token amount and the total amount of liquidity minted by the contract. <br>
+/// Fails if the liquidity tokens of the caller are locked.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The context for the action call.
+///
+/// * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// * `liquidity_token_amount`: [`u128`] - The amount of liquidity tokens to burn.
+///
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x05)]
+pub fn reclaim_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    liquidity_token_amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let user = &context.sender;
+
+    state.assert_liquidity_unlocked(&context, user);
+    state.sync_fees_of(&context, *user);
+    state.deduct_from_token_balance(*user, &Token::LIQUIDITY, liquidity_token_amount);
+
+    let contract_token_balance = state.get_balance_for(&state.contract);
+
+    let (a_output, b_output) = calculate_reclaim_output(
+        liquidity_token_amount,
+        contract_token_balance.a_tokens,
+        contract_token_balance.b_tokens,
+        contract_token_balance.liquidity_tokens,
+    );
+
+    state.move_tokens(state.contract, *user, Token::A, a_output);
+    state.move_tokens(state.contract, *user, Token::B, b_output);
+    state.deduct_from_token_balance(state.contract, &Token::LIQUIDITY, liquidity_token_amount);
+    state.update_digest(&context);
+    state.record_interaction(
+        &context,
+        InteractionKind::ReclaimLiquidity {},
+        None,
+        TokenBalance::of(Token::LIQUIDITY, liquidity_token_amount),
+        TokenBalance::of(Token::A, a_output).and(Token::B, b_output),
+    );
+
+    (state, vec![])
+}
+
+/// <pre>
+/// Initialize pool {A, B} of the contract and mint initial liquidity tokens.
+/// This effectively makes the calling user the first LP,
+/// receiving liquidity tokens amounting to 100% of the contract's total liquidity,
+/// less the MINIMUM_LIQUIDITY locked to the MINIMUM_LIQUIDITY_HOLDER,
+/// until another user becomes an LP.
+/// If the contract has a lockup period for the initial liquidity,
+/// the liquidity tokens of the calling user are locked for that duration.</pre>
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_a_amount`: [`u128`] - The amount to initialize pool A with.
+///
+///  * `token_b_amount`: [`u128`] - The amount to initialize pool B with.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x06)]
+pub fn provide_initial_liquidity(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    token_a_amount: u128,
+    token_b_amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_not_paused();
+    assert!(
+        !state.contract_pools_have_liquidity(),
+        "Can only initialize when both pools are empty"
+    );
+
+    let total_liquidity_tokens = initial_liquidity_tokens(token_a_amount, token_b_amount);
+    assert!(
+        total_liquidity_tokens > MINIMUM_LIQUIDITY,
+        "Provided amount must yield more than {MINIMUM_LIQUIDITY} liquidity tokens"
+    );
+    let minted_liquidity_tokens = total_liquidity_tokens - MINIMUM_LIQUIDITY;
+
+    state.sync_fees_of(&context, context.sender);
+    let provided_address = state.token_a_address;
+    provide_liquidity_internal(
+        &mut state,
+        &context,
+        &context.sender,
+        provided_address,
+        token_a_amount,
+        token_b_amount,
+        minted_liquidity_tokens,
+    );
+    // Permanently lock the minimum liquidity, such that the pools can never be fully drained
+    state.add_to_token_balance(
+        MINIMUM_LIQUIDITY_HOLDER,
+        Token::LIQUIDITY,
+        MINIMUM_LIQUIDITY,
+    );
+    state.add_to_token_balance(state.contract, Token::LIQUIDITY, MINIMUM_LIQUIDITY);
+    state.update_digest(&context);
+    state.record_interaction(
+        &context,
+        InteractionKind::ProvideLiquidity {},
+        None,
+        TokenBalance::of(Token::A, token_a_amount).and(Token::B, token_b_amount),
+        TokenBalance::of(Token::LIQUIDITY, minted_liquidity_tokens),
+    );
+    if let Some(lock_millis) = state.initial_lp_lock_millis {
+        state
+            .lp_locks
+            .insert(context.sender, context.block_production_time + lock_millis);
+    }
+    (state, vec![])
+}
+
+/// Transfers `amount` of the caller's liquidity tokens to another user. <br>
+/// The receiving user becomes a liquidity provider with the same rights as if the tokens were
+/// minted to them, allowing liquidity tokens to be traded or managed by other contracts. <br>
+/// Fails if the liquidity tokens of the caller are locked, if the caller has too few liquidity
+/// tokens, or if `to` is the contract itself or the [`MINIMUM_LIQUIDITY_HOLDER`], whose liquidity
+/// tokens back the pools and could never be reclaimed.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `to`: [`Address`] - The address of the receiving user.
+///
+///  * `amount`: [`u128`] - The amount of liquidity tokens to transfer.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x07)]
+pub fn transfer_liquidity_tokens(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    to: Address,
+    amount: u128,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert!(
+        to != state.contract && to != MINIMUM_LIQUIDITY_HOLDER,
+        "Liquidity tokens cannot be transferred to {to:?}"
+    );
+    state.assert_liquidity_unlocked(&context, &context.sender);
+    state.sync_fees_of(&context, context.sender);
+    state.sync_fees_of(&context, to);
+    state.move_tokens(context.sender, to, Token::LIQUIDITY, amount);
+    state.record_interaction(
+        &context,
+        InteractionKind::TransferLiquidity {},
+        Some(to),
+        TokenBalance::of(Token::LIQUIDITY, amount),
+        EMPTY_BALANCE,
+    );
+    (state, vec![])
+}
+
+/// Transfers the position with id `position_id` to another user. <br>
+/// The fees accrued to the position are first added to the caller's balance. <br>
+/// Fails if the caller does not own the position, or the liquidity of the caller is locked.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `position_id`: [`u64`] - The id of the transferred position.
+///
+///  * `to`: [`Address`] - The address of the receiving user.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x14)]
+pub fn transfer_position(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    position_id: u64,
+    to: Address,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_position_usable_by(&context, position_id, &context.sender);
+    state.sync_position_fees(&context, position_id);
+    let position = state.positions.get_mut(&position_id).unwrap();
+    position.owner = to;
+    let liquidity_tokens = position.liquidity_tokens;
+    state.record_interaction(
+        &context,
+        InteractionKind::TransferLiquidity {},
+        Some(to),
+        TokenBalance::of(Token::LIQUIDITY, liquidity_tokens),
+        EMPTY_BALANCE,
+    );
+    (state, vec![])
+}
+
+/// Burns the position with id `position_id`, reclaiming its share of the contract's total liquidity
+/// to the caller's balance, like [`reclaim_liquidity`]. <br>
+/// The fees accrued to the position are added to the caller's balance. <br>
+/// Fails if the caller does not own the position, or the liquidity of the caller is locked.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `position_id`: [`u64`] - The id of the burned position.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x15)]
+pub fn burn_position(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    position_id: u64,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let user = &context.sender;
+    state.assert_position_usable_by(&context, position_id, user);
+    state.sync_position_fees(&context, position_id);
+    let liquidity_token_amount = state
+        .positions
+        .remove(&position_id)
+        .unwrap()
+        .liquidity_tokens;
+
+    let contract_token_balance = state.get_balance_for(&state.contract);
+    let (a_output, b_output) = calculate_reclaim_output(
+        liquidity_token_amount,
+        contract_token_balance.a_tokens,
+        contract_token_balance.b_tokens,
+        contract_token_balance.liquidity_tokens,
+    );
+
+    state.move_tokens(state.contract, *user, Token::A, a_output);
+    state.move_tokens(state.contract, *user, Token::B, b_output);
+    state.deduct_from_token_balance(state.contract, &Token::LIQUIDITY, liquidity_token_amount);
+    state.update_digest(&context);
+    state.record_interaction(
+        &context,
+        InteractionKind::ReclaimLiquidity {},
+        None,
+        TokenBalance::of(Token::LIQUIDITY, liquidity_token_amount),
+        TokenBalance::of(Token::A, a_output).and(Token::B, b_output),
+    );
+    (state, vec![])
+}
+
+/// Authorizes or revokes `settler` to swap the caller's tokens in a [`batch_swap`].
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `settler`: [`Address`] - The settler being authorized or revoked.
+///
+///  * `authorized`: [`bool`] - Whether the settler is authorized.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x16)]
+pub fn set_batch_settler(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    settler: Address,
+    authorized: bool,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let settlers = state.batch_settlers.entry(context.sender).or_default();
+    settlers.retain(|existing| existing != &settler);
+    if authorized {
+        settlers.push(settler);
+    }
+    if settlers.is_empty() {
+        state.batch_settlers.remove(&context.sender);
+    }
+    (state, vec![])
+}
+
+/// Settles a batch of swap requests in a single transaction, in the order of the requests. <br>
+/// Each request swaps the deposited tokens of its user, who must be the caller or have authorized the caller with [`set_batch_settler`].
+/// A request that cannot be executed, because the caller is not authorized, the user's balance is insufficient,
+/// or the output would be less than the minimum output of the request, is skipped without failing the batch. <br>
+/// The outcome of every request is recorded in `last_batch_results`, replacing the results of the previous batch. <br>
+/// Referrers are not supported in batches.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `requests`: [`Vec<SwapRequest>`] - The swap requests, at most [`MAX_BATCH_SWAP_REQUESTS`].
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x17)]
+pub fn batch_swap(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+    requests: Vec<SwapRequest>,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    state.assert_not_paused();
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to perform a swap"
+    );
+    assert!(
+        requests.len() <= MAX_BATCH_SWAP_REQUESTS,
+        "A batch can contain at most {MAX_BATCH_SWAP_REQUESTS} swap requests"
+    );
+    let mut results = Vec::with_capacity(requests.len());
+    for request in requests {
+        let (status, amount_out) = state.check_swap_request(&context.sender, &request);
+        if status == (BatchSwapStatus::Executed {}) {
+            let (provided_token, opposite_token) =
+                state.deduce_provided_opposite_tokens(request.token_address);
+            execute_swap(
+                &mut state,
+                &context,
+                request.user,
+                provided_token,
+                opposite_token,
+                request.amount,
+                amount_out,
+                None,
+            );
+        }
+        results.push(BatchSwapResult {
+            user: request.user,
+            status,
+            amount_out,
+        });
+    }
+    state.last_batch_results = results;
+    (state, vec![])
+}
+
+/// Quotes a swap of `amount` of the token at `token_address`, recording the output in `last_quote`. <br>
+/// The output is computed exactly as by [`swap`], but no balances or pools are changed.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `token_address`: [`Address`] - The address of the token contract being swapped from.
 ///
-///  * `amount`: [`u128`] - The amount to withdraw.
+///  * `amount`: [`u128`] - The amount to swap.
 ///
 /// # Returns
-/// The unchanged state object of type [`LiquiditySwapContractState`].
-#[action(shortname = 0x03)]
-pub fn withdraw(
+/// The updated state object of type [`LiquiditySwapContractState`] holding the quote.
+#[action(shortname = 0x18)]
+pub fn quote_swap(
     context: ContractContext,
     mut state: LiquiditySwapContractState,
     token_address: Address,
     amount: u128,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
-    let (provided_token, _) = state.deduce_provided_opposite_tokens(token_address);
-
-    state.deduct_from_token_balance(context.sender, &provided_token, amount);
-
-    let mut event_group_builder = EventGroup::builder();
-    event_group_builder
-        .call(token_address, token_contract_transfer())
-        .argument(context.sender)
-        .argument(amount)
-        .done();
-
-    (state, vec![event_group_builder.build()])
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to perform a swap"
+    );
+    let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
+    let opposite_token_amount = state.swap_output(&provided_token, &opposite_token, amount);
+    state.last_quote = Some(Quote {
+        kind: InteractionKind::Swap {},
+        spent: TokenBalance::of(provided_token, amount),
+        received: TokenBalance::of(opposite_token, opposite_token_amount),
+    });
+    (state, vec![])
 }
 
-/// Become a liquidity provider to the contract by providing `amount` of tokens from the caller's balance. <br>
-/// An equivalent amount of the opposite token is required to succeed and will be provided implicitly. <br>
-/// This is the inverse of [`reclaim_liquidity`].
+/// Quotes providing `amount` of the token at `token_address` as liquidity, recording the required amount
+/// of the opposite token and the minted liquidity tokens in `last_quote`. <br>
+/// The amounts are computed exactly as by [`provide_liquidity`], but no balances or pools are changed.
 ///
 /// ### Parameters:
 ///
@@ -495,92 +2139,105 @@ pub fn withdraw(
 ///
 ///  * `token_address`: [`Address`] - The address of the provided token.
 ///
-///  * `token_amount`: [`u128`] - The amount to provide.
+///  * `amount`: [`u128`] - The amount to provide.
 ///
 /// # Returns
-/// The unchanged state object of type [`LiquiditySwapContractState`].
-#[action(shortname = 0x04)]
-pub fn provide_liquidity(
+/// The updated state object of type [`LiquiditySwapContractState`] holding the quote.
+#[action(shortname = 0x19)]
+pub fn quote_provide(
     context: ContractContext,
     mut state: LiquiditySwapContractState,
     token_address: Address,
     amount: u128,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
-    let user = &context.sender;
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to provide liquidity"
+    );
     let (provided_token, opposite_token) = state.deduce_provided_opposite_tokens(token_address);
     let contract_token_balance = state.get_balance_for(&state.contract);
-
     let (opposite_equivalent, minted_liquidity_tokens) = calculate_equivalent_and_minted_tokens(
         amount,
         contract_token_balance.get_amount_of(&provided_token),
         contract_token_balance.get_amount_of(&opposite_token),
         contract_token_balance.liquidity_tokens,
     );
-    assert!(
-        minted_liquidity_tokens > 0,
-        "Provided amount yielded 0 minted liquidity"
-    );
-
-    provide_liquidity_internal(
-        &mut state,
-        user,
-        token_address,
-        amount,
-        opposite_equivalent,
-        minted_liquidity_tokens,
-    );
+    state.last_quote = Some(Quote {
+        kind: InteractionKind::ProvideLiquidity {},
+        spent: TokenBalance::of(provided_token, amount).and(opposite_token, opposite_equivalent),
+        received: TokenBalance::of(Token::LIQUIDITY, minted_liquidity_tokens),
+    });
     (state, vec![])
 }
 
-/// Reclaim a calling user's share of the contract's total liquidity based on `liquidity_token_amount`. <br>
-/// This is the inverse of [`provide_liquidity`].
-///
-/// Liquidity tokens are synonymous to weighted shares of the contract's total liquidity. <br>
-/// As such, we calculate how much to output of token A and B,
-/// based on the ratio between the input liquidity token amount and the total amount of liquidity minted by the contract.
+/// Quotes reclaiming `liquidity_token_amount` liquidity tokens, recording the output of token A and B in `last_quote`. <br>
+/// The output is computed exactly as by [`reclaim_liquidity`], but no balances or pools are changed.
 ///
 /// ### Parameters:
 ///
-/// * `context`: [`ContractContext`] - The context for the action call.
-///
-/// * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
 ///
-/// * `liquidity_token_amount`: [`u128`] - The amount of liquidity tokens to burn.
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
 ///
-/// ### Returns
+///  * `liquidity_token_amount`: [`u128`] - The amount of liquidity tokens to burn.
 ///
-/// The updated state object of type [`LiquiditySwapContractState`].
-#[action(shortname = 0x05)]
-pub fn reclaim_liquidity(
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`] holding the quote.
+#[action(shortname = 0x1A)]
+pub fn quote_reclaim(
     context: ContractContext,
     mut state: LiquiditySwapContractState,
     liquidity_token_amount: u128,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
-    let user = &context.sender;
-
-    state.deduct_from_token_balance(*user, &Token::LIQUIDITY, liquidity_token_amount);
-
+    assert!(
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to reclaim liquidity"
+    );
     let contract_token_balance = state.get_balance_for(&state.contract);
-
+    assert!(
+        liquidity_token_amount <= contract_token_balance.liquidity_tokens,
+        "Cannot reclaim more liquidity tokens than have been minted"
+    );
     let (a_output, b_output) = calculate_reclaim_output(
         liquidity_token_amount,
         contract_token_balance.a_tokens,
         contract_token_balance.b_tokens,
         contract_token_balance.liquidity_tokens,
     );
+    state.last_quote = Some(Quote {
+        kind: InteractionKind::ReclaimLiquidity {},
+        spent: TokenBalance::of(Token::LIQUIDITY, liquidity_token_amount),
+        received: TokenBalance::of(Token::A, a_output).and(Token::B, b_output),
+    });
+    (state, vec![])
+}
 
-    state.move_tokens(state.contract, *user, Token::A, a_output);
-    state.move_tokens(state.contract, *user, Token::B, b_output);
-    state.deduct_from_token_balance(state.contract, &Token::LIQUIDITY, liquidity_token_amount);
-
+/// Pauses the contract, blocking swaps and providing liquidity until the contract is unpaused. <br>
+/// Withdrawals and reclaiming liquidity remain possible. Only the owner can pause the contract.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x08)]
+pub fn pause(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only the owner can pause the contract"
+    );
+    state.assert_not_paused();
+    state.paused = true;
     (state, vec![])
 }
 
-/// <pre>
-/// Initialize pool {A, B} of the contract and mint initial liquidity tokens.
-/// This effectively makes the calling user the first LP,
-/// receiving liquidity tokens amounting to 100% of the contract's total liquidity,
-/// until another user becomes an LP.</pre>
+/// Unpauses the contract, allowing swaps and providing liquidity again. Only the owner can unpause the contract.
 ///
 /// ### Parameters:
 ///
@@ -588,42 +2245,208 @@ pub fn reclaim_liquidity(
 ///
 ///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
 ///
-///  * `token_a_amount`: [`u128`] - The amount to initialize pool A with.
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x09)]
+pub fn unpause(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only the owner can unpause the contract"
+    );
+    assert!(state.paused, "The contract is not paused");
+    state.paused = false;
+    (state, vec![])
+}
+
+/// Sets the share of the swap fee credited to the referrer of a swap. Only the owner can set the referrer share. <br>
+/// The referrer share and the protocol share of the fee tier cannot exceed the full fee combined.
 ///
-///  * `token_b_amount`: [`u128`] - The amount to initialize pool B with.
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `referrer_share_per_mille`: [`u128`] - The share of the swap fee credited to the referrer, per mille of the fee.
 ///
 /// # Returns
 /// The updated state object of type [`LiquiditySwapContractState`].
-#[action(shortname = 0x06)]
-pub fn provide_initial_liquidity(
+#[action(shortname = 0x0C)]
+pub fn set_referrer_share(
     context: ContractContext,
     mut state: LiquiditySwapContractState,
-    token_a_amount: u128,
-    token_b_amount: u128,
+    referrer_share_per_mille: u128,
 ) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only the owner can set the referrer share"
+    );
+    let protocol_share_per_mille = state.fee_tier.protocol_share_per_mille();
     assert!(
-        !state.contract_pools_have_liquidity(),
-        "Can only initialize when both pools are empty"
+        referrer_share_per_mille <= 1000 - protocol_share_per_mille,
+        "Referrer share should not exceed {}, as the protocol share is {}",
+        1000 - protocol_share_per_mille,
+        protocol_share_per_mille,
+    );
+    state.referrer_share_per_mille = referrer_share_per_mille;
+    (state, vec![])
+}
+
+/// Ends the current epoch of swap statistics. Only the owner can end an epoch. <br>
+/// The statistics of the current epoch are stored in `epoch_stats` under the current epoch number,
+/// and the statistics of the next epoch start from zero.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x0E)]
+pub fn reset_epoch_stats(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only the owner can reset the epoch statistics"
+    );
+    let ended_stats = std::mem::replace(
+        &mut state.swap_stats,
+        SwapStats::starting_at(context.block_production_time),
     );
+    state.epoch_stats.insert(state.current_epoch, ended_stats);
+    state.current_epoch += 1;
+    (state, vec![])
+}
+
+/// Adds the fees accrued to the calling liquidity provider to their token balance, without burning any liquidity tokens. <br>
+/// The synced fees can afterwards be swapped or withdrawn like deposited tokens.
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// # Returns
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x0B)]
+pub fn sync_fees(
+    context: ContractContext,
+    mut state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let fees = state.sync_fees_of(&context, context.sender);
+    assert!(!fees.user_has_no_tokens(), "No accrued fees to sync");
+    state.update_digest(&context);
+    (state, vec![])
+}
 
-    let minted_liquidity_tokens = initial_liquidity_tokens(token_a_amount, token_b_amount);
+/// Syncs the pools with the balances of the contract at the token contracts, for rebasing tokens. <br>
+/// Anyone can sync the pools. The balances of the contract are queried, and the pools are adjusted in [`sync_callback`].
+///
+/// ### Parameters:
+///
+///  * `context`: [`ContractContext`] - The contract context containing sender and chain information.
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+/// # Returns
+/// The unchanged state object of type [`LiquiditySwapContractState`].
+#[action(shortname = 0x0F)]
+pub fn sync(
+    context: ContractContext,
+    state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
     assert!(
-        minted_liquidity_tokens > 0,
-        "Provided amount yielded 0 minted liquidity"
+        state.contract_pools_have_liquidity(),
+        "Pools must have existing liquidity to sync"
     );
+    let mut event_group_builder = EventGroup::builder();
+    event_group_builder
+        .call(state.token_a_address, token_contract_balance_of())
+        .argument(context.contract_address)
+        .done();
+    event_group_builder
+        .call(state.token_b_address, token_contract_balance_of())
+        .argument(context.contract_address)
+        .done();
+    event_group_builder
+        .with_callback(SHORTNAME_SYNC_CALLBACK)
+        .done();
 
-    let provided_address = state.token_a_address;
-    provide_liquidity_internal(
-        &mut state,
-        &context.sender,
-        provided_address,
-        token_a_amount,
-        token_b_amount,
-        minted_liquidity_tokens,
+    (state, vec![event_group_builder.build()])
+}
+
+/// Handles callback from [`sync`]. <br>
+/// The balance queries are checked separately, and only the pools of the tokens whose balance was read are synced.
+/// For each token, the difference between the balance of the contract and the amount accounted to users, pools and the fee reserve
+/// is added to the pool if the contract holds a surplus, or deducted from the pool if it holds a shortfall.
+/// The liquidity providers thereby share the gains and losses of rebasing.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`] - The contractContext for the callback.
+///
+/// * `callback_context`: [`CallbackContext`] - The callbackContext, with the balances of token A and B.
+///
+/// * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+/// ### Returns
+///
+/// The updated state object of type [`LiquiditySwapContractState`].
+#[callback(shortname = 0x13)]
+pub fn sync_callback(
+    context: ContractContext,
+    callback_context: CallbackContext,
+    mut state: LiquiditySwapContractState,
+) -> (LiquiditySwapContractState, Vec<EventGroup>) {
+    let balance_a = read_balance(&callback_context.results[0]);
+    let balance_b = read_balance(&callback_context.results[1]);
+    assert!(
+        balance_a.is_some() || balance_b.is_some(),
+        "Balance query did not succeed"
+    );
+    let (added_a, deducted_a) =
+        balance_a.map_or((0, 0), |balance| state.sync_pool(Token::A, balance));
+    let (added_b, deducted_b) =
+        balance_b.map_or((0, 0), |balance| state.sync_pool(Token::B, balance));
+    state.update_digest(&context);
+    let contract = state.contract;
+    state.record_interaction_of(
+        &context,
+        contract,
+        InteractionKind::Sync {},
+        None,
+        EMPTY_BALANCE
+            .and(Token::A, deducted_a)
+            .and(Token::B, deducted_b),
+        EMPTY_BALANCE.and(Token::A, added_a).and(Token::B, added_b),
     );
+
     (state, vec![])
 }
 
+/// Reads the balance returned by a successful `balance_of` query of a token contract.
+///
+/// ### Parameters:
+///
+/// * `result`: [`&ExecutionResult`] - The result of the balance query.
+///
+/// ### Returns:
+/// The queried balance, or None if the query failed.
+fn read_balance(result: &ExecutionResult) -> Option<u128> {
+    if result.succeeded {
+        Some(u128::rpc_read_from(&mut result.return_data.as_slice()))
+    } else {
+        None
+    }
+}
+
 /// Determines the initial amount of liquidity tokens, or shares, representing some sensible '100%' of the contract's liquidity. <br>
 /// This implementation is derived from section 3.4 of: [Uniswap v2 whitepaper](https://uniswap.org/whitepaper.pdf). <br>
 /// It guarantees that the value of a liquidity token becomes independent of the ratio at which liquidity was initially provided.
@@ -653,6 +2476,17 @@ fn token_contract_transfer_from() -> Shortname {
     Shortname::from_u32(0x03)
 }
 
+/// Creates the `Shortname` corresponding to the `balance_of` action of a token contract, returning the balance of the given account. <br>
+/// This is utilized in combination with an `EventGroupBuilder`'s `call` function.
+///
+/// ### Returns:
+///
+/// The `Shortname` corresponding to the `balance_of` action of a token contract.
+#[inline]
+fn token_contract_balance_of() -> Shortname {
+    Shortname::from_u32(0x06)
+}
+
 /// Find the u128 square root of `y` (using binary search) rounding down.
 ///
 /// ### Parameters:
@@ -689,6 +2523,8 @@ fn u128_sqrt(y: u128) -> u128 {
 /// * `to_pool`: [`u128`] - The opposite token pool.
 ///
 /// * `swap_from_amount`: [`u128`] - The amount being swapped.
+///
+/// * `swap_fee_per_mille`: [`u128`] - The fee for swapping, in per mille.
 /// # Returns
 /// The amount received after swapping. [`u128`]
 fn calculate_swap_to_amount(
@@ -760,12 +2596,103 @@ fn calculate_reclaim_output(
     (a_output, b_output)
 }
 
-/// Moves tokens from the providing user's balance to the contract's and mints liquidity tokens.
+/// Executes a swap of `amount` of the provided token from the balance of `user`, for `opposite_token_amount` of the opposite token.
+/// The swap fee is deducted from the input, and shared between the liquidity providers, the referrer and the owner.
+///
+/// ### Parameters:
+///
+///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
+///
+///  * `context`: [`&ContractContext`] - The context of the swapping call.
+///
+///  * `user`: [`Address`] - The user whose tokens are swapped.
+///
+///  * `provided_token`: [`Token`] - The token being swapped from.
+///
+///  * `opposite_token`: [`Token`] - The token being swapped to.
+///
+///  * `amount`: [`u128`] - The amount of the provided token to swap.
+///
+///  * `opposite_token_amount`: [`u128`] - The amount of the opposite token received, as computed by [`LiquiditySwapContractState::swap_output`].
+///
+///  * `referrer`: [`Option<Address>`] - The referrer of the swap, if any.
+#[allow(clippy::too_many_arguments)]
+fn execute_swap(
+    state: &mut LiquiditySwapContractState,
+    context: &ContractContext,
+    user: Address,
+    provided_token: Token,
+    opposite_token: Token,
+    amount: u128,
+    opposite_token_amount: u128,
+    referrer: Option<Address>,
+) {
+    // The fee is kept out of the pool and accrued to the liquidity providers
+    let fee = amount * state.fee_tier.swap_fee_per_mille() / 1000;
+    state.move_tokens(user, state.contract, provided_token.clone(), amount - fee);
+    state.deduct_from_token_balance(user, &provided_token, fee);
+    let referral_reward = match referrer {
+        Some(_) => fee * state.referrer_share_per_mille / 1000,
+        None => 0,
+    };
+    let protocol_fee = fee * state.fee_tier.protocol_share_per_mille() / 1000;
+    state.accrue_swap_fee(&provided_token, fee - referral_reward - protocol_fee);
+    state.move_tokens(
+        state.contract,
+        user,
+        opposite_token.clone(),
+        opposite_token_amount,
+    );
+    state.update_digest(context);
+    let spent = TokenBalance::of(provided_token.clone(), amount);
+    let received = TokenBalance::of(opposite_token, opposite_token_amount);
+    state.swap_stats.record_swap(
+        &spent,
+        &received,
+        TokenBalance::of(provided_token.clone(), fee),
+    );
+    state.record_interaction_of(
+        context,
+        user,
+        InteractionKind::Swap {},
+        None,
+        spent,
+        received,
+    );
+    if let Some(referrer) = referrer.filter(|_| referral_reward > 0) {
+        state.add_to_token_balance(referrer, provided_token.clone(), referral_reward);
+        state.record_interaction_of(
+            context,
+            referrer,
+            InteractionKind::ReferralReward {},
+            None,
+            EMPTY_BALANCE,
+            TokenBalance::of(provided_token.clone(), referral_reward),
+        );
+    }
+    if protocol_fee > 0 {
+        let owner = state.owner;
+        state.add_to_token_balance(owner, provided_token.clone(), protocol_fee);
+        state.record_interaction_of(
+            context,
+            owner,
+            InteractionKind::ProtocolFee {},
+            None,
+            EMPTY_BALANCE,
+            TokenBalance::of(provided_token, protocol_fee),
+        );
+    }
+}
+
+/// Moves tokens from the providing user's balance to the contract's and mints liquidity tokens. <br>
+/// In position mode the liquidity tokens are minted to a new [`Position`] owned by the user.
 ///
 /// ### Parameters:
 ///
 ///  * `state`: [`LiquiditySwapContractState`] - The current state of the contract.
 ///
+///  * `context`: [`&ContractContext`] - The context of the providing call.
+///
 /// * `user`: [`&Address`] - The address of the user providing liquidity.
 ///
 /// * `provided_token_address`: [`Address`] - The address of the token being provided.
@@ -777,6 +2704,7 @@ fn calculate_reclaim_output(
 ///  * `minted_liquidity_tokens`: [`u128`] - The amount of liquidity tokens that the provided tokens yields.
 fn provide_liquidity_internal(
     state: &mut LiquiditySwapContractState,
+    context: &ContractContext,
     user: &Address,
     provided_token_address: Address,
     provided_amount: u128,
@@ -785,10 +2713,27 @@ fn provide_liquidity_internal(
 ) {
     let (provided_token, opposite_token) =
         state.deduce_provided_opposite_tokens(provided_token_address);
+    let provided = TokenBalance::of(provided_token.clone(), provided_amount)
+        .and(opposite_token.clone(), opposite_amount);
 
     state.move_tokens(*user, state.contract, provided_token, provided_amount);
     state.move_tokens(*user, state.contract, opposite_token, opposite_amount);
 
-    state.add_to_token_balance(*user, Token::LIQUIDITY, minted_liquidity_tokens);
+    if state.position_mode {
+        state.positions.insert(
+            state.next_position_id,
+            Position {
+                owner: *user,
+                liquidity_tokens: minted_liquidity_tokens,
+                token_a_amount: provided.a_tokens,
+                token_b_amount: provided.b_tokens,
+                created_at_millis: context.block_production_time,
+                fee_checkpoint: state.fee_growth.clone(),
+            },
+        );
+        state.next_position_id += 1;
+    } else {
+        state.add_to_token_balance(*user, Token::LIQUIDITY, minted_liquidity_tokens);
+    }
     state.add_to_token_balance(state.contract, Token::LIQUIDITY, minted_liquidity_tokens);
 }
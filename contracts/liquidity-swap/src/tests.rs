@@ -1,9 +1,18 @@
 #[cfg(test)]
 mod test {
     use crate::{
-        calculate_equivalent_and_minted_tokens, calculate_reclaim_output, calculate_swap_to_amount,
-        u128_sqrt,
+        batch_swap, burn_position, calculate_equivalent_and_minted_tokens,
+        calculate_reclaim_output, calculate_swap_to_amount, deposit, deposit_and_provide_liquidity,
+        deposit_and_provide_liquidity_callback, deposit_callback, deposit_measured_callback,
+        initialize, pause, provide_initial_liquidity, provide_liquidity, quote_provide,
+        quote_reclaim, quote_swap, reclaim_liquidity, reset_epoch_stats, set_batch_settler,
+        set_referrer_share, swap, sync, sync_callback, sync_fees, transfer_liquidity_tokens,
+        transfer_position, u128_sqrt, withdraw_all, BatchSwapStatus, FeeTier, InteractionKind,
+        LiquiditySwapContractState, SwapRequest, Token, MAX_INTERACTIONS, MINIMUM_LIQUIDITY,
+        MINIMUM_LIQUIDITY_HOLDER,
     };
+    use pbc_contract_common::address::{Address, AddressType};
+    use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
     use rand::Rng;
     use rand_chacha::rand_core::SeedableRng;
 
@@ -277,4 +286,1055 @@ mod test {
         assert_eq!(a_output, 0);
         assert_eq!(b_output, 0);
     }
+
+    #[test]
+    pub fn calculate_swap_to_amount_across_fee_tiers() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(10);
+        let end_input_range = 10000000;
+        let fee_tiers = [
+            FeeTier::Zero {},
+            FeeTier::Low {},
+            FeeTier::Standard {},
+            FeeTier::High {},
+        ];
+
+        for fee_tier in fee_tiers {
+            let swap_fee_per_mille = fee_tier.swap_fee_per_mille();
+            for _ in 0..=10000 {
+                let input_a: u64 = rng.gen_range(1..=end_input_range);
+                let a_pool: u64 = rng.gen_range(1..=end_input_range);
+                let b_pool: u64 = rng.gen_range(1..=end_input_range);
+
+                let output_b: u128 = calculate_swap_to_amount(
+                    a_pool as u128,
+                    b_pool as u128,
+                    input_a as u128,
+                    swap_fee_per_mille,
+                );
+
+                // The output must be floored from the exact value, computed with integers to avoid rounding
+                let input_after_fee = (1000 - swap_fee_per_mille) * input_a as u128;
+                let numerator = input_after_fee * b_pool as u128;
+                let denominator = 1000 * a_pool as u128 + input_after_fee;
+                assert!(output_b * denominator <= numerator);
+                assert!((output_b + 1) * denominator > numerator);
+            }
+        }
+    }
+
+    #[test]
+    pub fn zero_fee_swap_keeps_constant_product() {
+        let a_pool: u128 = 10_000;
+        let b_pool: u128 = 40_000;
+        let output_b = calculate_swap_to_amount(a_pool, b_pool, 10_000, 0);
+        assert_eq!(output_b, 20_000);
+        assert_eq!((a_pool + 10_000) * (b_pool - output_b), a_pool * b_pool);
+
+        // Without a fee, swapping back returns exactly the swapped amount
+        let output_a = calculate_swap_to_amount(b_pool - output_b, a_pool + 10_000, output_b, 0);
+        assert_eq!(output_a, 10_000);
+        assert_eq!(calculate_swap_to_amount(a_pool, b_pool, 0, 0), 0);
+    }
+
+    #[test]
+    pub fn fee_tiers_determine_fees() {
+        assert_eq!(FeeTier::Zero {}.swap_fee_per_mille(), 0);
+        assert_eq!(FeeTier::Low {}.swap_fee_per_mille(), 1);
+        assert_eq!(FeeTier::Standard {}.swap_fee_per_mille(), 3);
+        assert_eq!(FeeTier::High {}.swap_fee_per_mille(), 10);
+        assert_eq!(FeeTier::Zero {}.protocol_share_per_mille(), 0);
+        assert_eq!(FeeTier::Standard {}.protocol_share_per_mille(), 0);
+        assert_eq!(FeeTier::High {}.protocol_share_per_mille(), 100);
+    }
+
+    fn create_address(address_type: AddressType, id: u8) -> Address {
+        let mut identifier = [0u8; 20];
+        identifier[19] = id;
+        Address {
+            address_type,
+            identifier,
+        }
+    }
+
+    fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+        ContractContext {
+            contract_address: create_address(AddressType::PublicContract, 1),
+            sender,
+            block_time: 0,
+            block_production_time,
+            current_transaction: [0u8; 32],
+            original_transaction: [0u8; 32],
+        }
+    }
+
+    fn create_callback_ctx() -> CallbackContext {
+        CallbackContext {
+            success: true,
+            results: vec![ExecutionResult {
+                succeeded: true,
+                return_data: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    pub fn interactions_are_bounded() {
+        let user = create_address(AddressType::Account, 4);
+        let (mut state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            false,
+            false,
+        );
+        for time in 0..(MAX_INTERACTIONS as i64 + 5) {
+            (state, _) = deposit_callback(
+                create_ctx(user, time),
+                create_callback_ctx(),
+                state,
+                Token::A,
+                10,
+            );
+        }
+
+        assert_eq!(state.interactions.len(), MAX_INTERACTIONS);
+        assert_eq!(state.next_sequence_number, MAX_INTERACTIONS as u64 + 5);
+        let oldest = &state.interactions[0];
+        assert_eq!(oldest.sequence_number, 5);
+        assert_eq!(oldest.kind, InteractionKind::Deposit {});
+        assert_eq!(oldest.block_production_time, 5);
+        assert_eq!(oldest.received.a_tokens, 10);
+        assert_eq!(
+            state.token_balances.get(&user).unwrap().a_tokens,
+            10 * (MAX_INTERACTIONS as u128 + 5)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidity tokens are locked until 1000")]
+    pub fn initial_liquidity_is_locked() {
+        let user = create_address(AddressType::Account, 4);
+        let (mut state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            Some(900),
+            false,
+            false,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(user, 50),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(user, 50),
+            create_callback_ctx(),
+            state,
+            Token::B,
+            10_000,
+        );
+        (state, _) = provide_initial_liquidity(create_ctx(user, 100), state, 10_000, 10_000);
+        assert_eq!(state.lp_locks.get(&user), Some(&1000));
+
+        let (state, _) = reclaim_liquidity(create_ctx(user, 1000), state, 10);
+        assert_eq!(
+            state.token_balances.get(&user).unwrap().liquidity_tokens,
+            8990
+        );
+
+        reclaim_liquidity(create_ctx(user, 999), state, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is paused")]
+    pub fn paused_contract_allows_withdrawals_but_not_swaps() {
+        let owner = create_address(AddressType::Account, 4);
+        let token_a = create_address(AddressType::PublicContract, 2);
+        let (mut state, _) = initialize(
+            create_ctx(owner, 0),
+            token_a,
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            false,
+            false,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(owner, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            100,
+        );
+        (state, _) = pause(create_ctx(owner, 0), state);
+        assert!(state.paused);
+
+        let (state, events) = withdraw_all(create_ctx(owner, 0), state, token_a);
+        assert_eq!(events.len(), 1);
+        assert!(!state.token_balances.contains_key(&owner));
+
+        swap(create_ctx(owner, 0), state, token_a, 10, None);
+    }
+
+    #[test]
+    pub fn swap_fees_are_accrued_to_liquidity_providers() {
+        let provider = create_address(AddressType::Account, 4);
+        let trader = create_address(AddressType::Account, 5);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let (mut state, _) = initialize(
+            create_ctx(provider, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            false,
+            false,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(provider, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(provider, 0),
+            create_callback_ctx(),
+            state,
+            Token::B,
+            10_000,
+        );
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = deposit_callback(
+            create_ctx(trader, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+
+        let token_a = state.token_a_address;
+        (state, _) = swap(create_ctx(trader, 0), state, token_a, 10_000, None);
+        assert_eq!(
+            state.token_balances.get(&contract).unwrap().a_tokens,
+            19_970
+        );
+        assert_eq!(state.fee_reserve.a_tokens, 30);
+        assert_eq!(state.accrued_fees(&provider).a_tokens, 27);
+        assert_eq!(state.accrued_fees(&trader).a_tokens, 0);
+
+        let (state, _) = sync_fees(create_ctx(provider, 1), state);
+        assert_eq!(state.token_balances.get(&provider).unwrap().a_tokens, 27);
+        // The fees of the locked minimum liquidity remain in the fee reserve
+        assert_eq!(state.fee_reserve.a_tokens, 3);
+        assert_eq!(state.accrued_fees(&provider).a_tokens, 0);
+        assert_eq!(
+            state.interactions.last().unwrap().kind,
+            InteractionKind::SyncFees {}
+        );
+    }
+
+    #[test]
+    pub fn swap_fees_are_shared_with_referrer() {
+        let provider = create_address(AddressType::Account, 4);
+        let trader = create_address(AddressType::Account, 5);
+        let referrer = create_address(AddressType::Account, 6);
+        let mut state = state_with_deposits(provider, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = set_referrer_share(create_ctx(provider, 0), state, 200);
+        (state, _) = deposit_callback(
+            create_ctx(trader, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+
+        let token_a = state.token_a_address;
+        (state, _) = swap(
+            create_ctx(trader, 0),
+            state,
+            token_a,
+            10_000,
+            Some(referrer),
+        );
+        assert_eq!(state.token_balances.get(&referrer).unwrap().a_tokens, 6);
+        assert_eq!(state.fee_reserve.a_tokens, 24);
+        let interaction = state.interactions.last().unwrap();
+        assert_eq!(interaction.kind, InteractionKind::ReferralReward {});
+        assert_eq!(interaction.user, referrer);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can set the referrer share")]
+    pub fn only_owner_can_set_referrer_share() {
+        let owner = create_address(AddressType::Account, 4);
+        let state = state_with_deposits(owner, 0, 0);
+        set_referrer_share(
+            create_ctx(create_address(AddressType::Account, 5), 0),
+            state,
+            200,
+        );
+    }
+
+    #[test]
+    pub fn swaps_are_counted_in_epoch_stats() {
+        let provider = create_address(AddressType::Account, 4);
+        let mut state = state_with_deposits(provider, 20_000, 20_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+
+        let token_a = state.token_a_address;
+        let token_b = state.token_b_address;
+        (state, _) = swap(create_ctx(provider, 1), state, token_a, 1_000, None);
+        (state, _) = swap(create_ctx(provider, 2), state, token_b, 2_000, None);
+        assert_eq!(state.swap_stats.swap_count, 2);
+        assert_eq!(state.swap_stats.total_fees_collected.a_tokens, 3);
+        assert_eq!(state.swap_stats.total_fees_collected.b_tokens, 6);
+        assert_eq!(state.swap_stats.total_volume_a, 1_000 + 1_977);
+        assert_eq!(state.swap_stats.total_volume_b, 906 + 2_000);
+
+        (state, _) = reset_epoch_stats(create_ctx(provider, 3), state);
+        assert_eq!(state.current_epoch, 1);
+        assert_eq!(state.swap_stats.swap_count, 0);
+        assert_eq!(state.swap_stats.epoch_start_millis, 3);
+        let ended = state.epoch_stats.get(&0).unwrap();
+        assert_eq!(ended.swap_count, 2);
+        assert_eq!(ended.epoch_start_millis, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can reset the epoch statistics")]
+    pub fn only_owner_can_reset_epoch_stats() {
+        let owner = create_address(AddressType::Account, 4);
+        let state = state_with_deposits(owner, 0, 0);
+        reset_epoch_stats(
+            create_ctx(create_address(AddressType::Account, 5), 0),
+            state,
+        );
+    }
+
+    #[test]
+    pub fn position_mode_mints_positions() {
+        let provider = create_address(AddressType::Account, 4);
+        let second = create_address(AddressType::Account, 5);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let mut state =
+            state_with_mode_and_deposits(provider, FeeTier::Standard {}, true, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 7), state, 10_000, 10_000);
+        (state, _) = deposit_callback(
+            create_ctx(second, 8),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            1_000,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(second, 8),
+            create_callback_ctx(),
+            state,
+            Token::B,
+            1_001,
+        );
+        let token_a = state.token_a_address;
+        (state, _) = provide_liquidity(create_ctx(second, 9), state, token_a, 1_000);
+
+        assert_eq!(state.positions.len(), 2);
+        assert_eq!(state.next_position_id, 2);
+        let initial = state.positions.get(&0).unwrap();
+        assert_eq!(initial.owner, provider);
+        assert_eq!(initial.liquidity_tokens, 10_000 - MINIMUM_LIQUIDITY);
+        assert_eq!(initial.created_at_millis, 7);
+        let position = state.positions.get(&1).unwrap();
+        assert_eq!(position.owner, second);
+        assert_eq!(position.liquidity_tokens, 1_000);
+        assert_eq!(position.token_a_amount, 1_000);
+        assert_eq!(position.token_b_amount, 1_001);
+        // No fungible liquidity tokens are minted to the providers
+        assert_eq!(state.get_balance_for(&provider).liquidity_tokens, 0);
+        assert_eq!(state.get_balance_for(&second).liquidity_tokens, 0);
+        assert_eq!(
+            state
+                .token_balances
+                .get(&contract)
+                .unwrap()
+                .liquidity_tokens,
+            11_000
+        );
+    }
+
+    #[test]
+    pub fn burned_position_reclaims_share_and_fees() {
+        let provider = create_address(AddressType::Account, 4);
+        let trader = create_address(AddressType::Account, 5);
+        let mut state =
+            state_with_mode_and_deposits(provider, FeeTier::Standard {}, true, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = deposit_callback(
+            create_ctx(trader, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+        let token_a = state.token_a_address;
+        (state, _) = swap(create_ctx(trader, 0), state, token_a, 10_000, None);
+        assert_eq!(state.position_fees(0).a_tokens, 27);
+
+        (state, _) = burn_position(create_ctx(provider, 1), state, 0);
+        assert!(state.positions.is_empty());
+        let balance = state.get_balance_for(&provider);
+        // 90% of the pools of 19970 A and 5008 B, and the fees of 27 A
+        assert_eq!(balance.a_tokens, 17_973 + 27);
+        assert_eq!(balance.b_tokens, 4_507);
+        assert_eq!(
+            state.interactions.last().unwrap().kind,
+            InteractionKind::ReclaimLiquidity {}
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner of the position can transfer or burn it")]
+    pub fn transferred_position_can_only_be_burned_by_new_owner() {
+        let provider = create_address(AddressType::Account, 4);
+        let receiver = create_address(AddressType::Account, 5);
+        let mut state =
+            state_with_mode_and_deposits(provider, FeeTier::Standard {}, true, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = transfer_position(create_ctx(provider, 1), state, 0, receiver);
+        assert_eq!(state.positions.get(&0).unwrap().owner, receiver);
+        assert_eq!(state.interactions.last().unwrap().recipient, Some(receiver));
+
+        burn_position(create_ctx(provider, 2), state, 0);
+    }
+
+    fn balance_results_ctx(balances: &[Option<u128>]) -> CallbackContext {
+        let results: Vec<(bool, Option<u128>)> =
+            balances.iter().map(|balance| (true, *balance)).collect();
+        partial_results_ctx(&results)
+    }
+
+    /// A callback context with the given success and balance of each result.
+    fn partial_results_ctx(results: &[(bool, Option<u128>)]) -> CallbackContext {
+        CallbackContext {
+            success: results.iter().all(|(succeeded, _)| *succeeded),
+            results: results
+                .iter()
+                .map(|(succeeded, balance)| ExecutionResult {
+                    succeeded: *succeeded,
+                    return_data: balance
+                        .map(|balance| balance.to_be_bytes().to_vec())
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn measured_state(user: Address) -> LiquiditySwapContractState {
+        let (state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            true,
+            false,
+        );
+        state
+    }
+
+    #[test]
+    pub fn measured_deposit_credits_requested_amount_if_balance_query_fails() {
+        let user = create_address(AddressType::Account, 4);
+        let state = deposit_measured_callback(
+            create_ctx(user, 0),
+            partial_results_ctx(&[(false, None), (true, None), (true, Some(5_980))]),
+            measured_state(user),
+            Token::A,
+            1_000,
+        )
+        .0;
+        assert_eq!(state.token_balances.get(&user).unwrap().a_tokens, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfer did not succeed")]
+    pub fn measured_deposit_with_failed_transfer_credits_nothing() {
+        let user = create_address(AddressType::Account, 4);
+        deposit_measured_callback(
+            create_ctx(user, 0),
+            partial_results_ctx(&[(true, Some(5_000)), (false, None), (true, Some(5_000))]),
+            measured_state(user),
+            Token::A,
+            1_000,
+        );
+    }
+
+    #[test]
+    pub fn sync_only_adjusts_pools_with_queried_balances() {
+        let provider = create_address(AddressType::Account, 4);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let mut state = state_with_deposits(provider, 11_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = sync_callback(
+            create_ctx(provider, 1),
+            partial_results_ctx(&[(true, Some(11_500)), (false, None)]),
+            state,
+        );
+        let pools = state.token_balances.get(&contract).unwrap();
+        assert_eq!(pools.a_tokens, 10_500);
+        assert_eq!(pools.b_tokens, 10_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Balance query did not succeed")]
+    pub fn sync_fails_if_no_balance_is_queried() {
+        let provider = create_address(AddressType::Account, 4);
+        let mut state = state_with_deposits(provider, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        sync_callback(
+            create_ctx(provider, 1),
+            partial_results_ctx(&[(false, None), (false, None)]),
+            state,
+        );
+    }
+
+    #[test]
+    pub fn measured_deposit_credits_received_amount() {
+        let user = create_address(AddressType::Account, 4);
+        let (mut state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            true,
+            false,
+        );
+        let token_a = state.token_a_address;
+        let events;
+        (state, events) = deposit(create_ctx(user, 0), state, token_a, 1_000);
+        assert_eq!(events.len(), 1);
+
+        // The token takes a fee of 2% on transfer
+        (state, _) = deposit_measured_callback(
+            create_ctx(user, 0),
+            balance_results_ctx(&[Some(5_000), None, Some(5_980)]),
+            state,
+            Token::A,
+            1_000,
+        );
+        assert_eq!(state.token_balances.get(&user).unwrap().a_tokens, 980);
+        assert_eq!(state.interactions.last().unwrap().received.a_tokens, 980);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposits are measured, and must be made with deposit")]
+    pub fn measured_deposits_cannot_be_provided_directly() {
+        let user = create_address(AddressType::Account, 4);
+        let (state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            FeeTier::Standard {},
+            None,
+            true,
+            false,
+        );
+        let token_a = state.token_a_address;
+        deposit_and_provide_liquidity(create_ctx(user, 0), state, token_a, 1_000);
+    }
+
+    #[test]
+    pub fn sync_adjusts_pools_to_balances() {
+        let provider = create_address(AddressType::Account, 4);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let mut state = state_with_deposits(provider, 11_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        let events;
+        (state, events) = sync(create_ctx(provider, 1), state);
+        assert_eq!(events.len(), 1);
+
+        // Token A rebased up by 500, while 300 of token B were lost
+        (state, _) = sync_callback(
+            create_ctx(provider, 1),
+            balance_results_ctx(&[Some(11_500), Some(9_700)]),
+            state,
+        );
+        let pools = state.token_balances.get(&contract).unwrap();
+        assert_eq!(pools.a_tokens, 10_500);
+        assert_eq!(pools.b_tokens, 9_700);
+        assert_eq!(state.digest.a_reserve, 10_500);
+        // The balances of users are not affected
+        assert_eq!(state.token_balances.get(&provider).unwrap().a_tokens, 1_000);
+        let interaction = state.interactions.last().unwrap();
+        assert_eq!(interaction.kind, InteractionKind::Sync {});
+        assert_eq!(interaction.received.a_tokens, 500);
+        assert_eq!(interaction.spent.b_tokens, 300);
+    }
+
+    fn state_with_deposits(
+        user: Address,
+        a_tokens: u128,
+        b_tokens: u128,
+    ) -> LiquiditySwapContractState {
+        state_with_fee_tier_and_deposits(user, FeeTier::Standard {}, a_tokens, b_tokens)
+    }
+
+    fn state_with_fee_tier_and_deposits(
+        user: Address,
+        fee_tier: FeeTier,
+        a_tokens: u128,
+        b_tokens: u128,
+    ) -> LiquiditySwapContractState {
+        state_with_mode_and_deposits(user, fee_tier, false, a_tokens, b_tokens)
+    }
+
+    fn state_with_mode_and_deposits(
+        user: Address,
+        fee_tier: FeeTier,
+        position_mode: bool,
+        a_tokens: u128,
+        b_tokens: u128,
+    ) -> LiquiditySwapContractState {
+        let (mut state, _) = initialize(
+            create_ctx(user, 0),
+            create_address(AddressType::PublicContract, 2),
+            create_address(AddressType::PublicContract, 3),
+            fee_tier,
+            None,
+            false,
+            position_mode,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(user, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            a_tokens,
+        );
+        (state, _) = deposit_callback(
+            create_ctx(user, 0),
+            create_callback_ctx(),
+            state,
+            Token::B,
+            b_tokens,
+        );
+        state
+    }
+
+    #[test]
+    pub fn minimum_liquidity_is_locked_on_initialization() {
+        let user = create_address(AddressType::Account, 4);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let state = state_with_deposits(user, 10_000, 40_000);
+        let (state, _) = provide_initial_liquidity(create_ctx(user, 0), state, 10_000, 40_000);
+
+        let locked = state.token_balances.get(&MINIMUM_LIQUIDITY_HOLDER).unwrap();
+        assert_eq!(locked.liquidity_tokens, MINIMUM_LIQUIDITY);
+        let user_liquidity = state.token_balances.get(&user).unwrap().liquidity_tokens;
+        assert_eq!(user_liquidity, 20_000 - MINIMUM_LIQUIDITY);
+        assert_eq!(
+            state
+                .token_balances
+                .get(&contract)
+                .unwrap()
+                .liquidity_tokens,
+            20_000
+        );
+
+        // Reclaiming all liquidity of the user leaves the locked share in the pools
+        let (state, _) = reclaim_liquidity(create_ctx(user, 0), state, user_liquidity);
+        let contract_balance = state.token_balances.get(&contract).unwrap();
+        assert_eq!(contract_balance.a_tokens, 500);
+        assert_eq!(contract_balance.b_tokens, 2000);
+        assert_eq!(contract_balance.liquidity_tokens, MINIMUM_LIQUIDITY);
+        assert_eq!(
+            state.token_balances.get(&user).unwrap().a_tokens,
+            10_000 - 500
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Provided amount must yield more than 1000 liquidity tokens")]
+    pub fn initial_liquidity_must_exceed_minimum_liquidity() {
+        let user = create_address(AddressType::Account, 4);
+        let state = state_with_deposits(user, 1000, 1000);
+        provide_initial_liquidity(create_ctx(user, 0), state, 1000, 1000);
+    }
+
+    #[test]
+    pub fn zero_fee_tier_accrues_no_fees() {
+        let provider = create_address(AddressType::Account, 4);
+        let trader = create_address(AddressType::Account, 5);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let mut state =
+            state_with_fee_tier_and_deposits(provider, FeeTier::Zero {}, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = deposit_callback(
+            create_ctx(trader, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+
+        let token_a = state.token_a_address;
+        (state, _) = swap(create_ctx(trader, 0), state, token_a, 10_000, None);
+        assert_eq!(state.fee_tier, FeeTier::Zero {});
+        assert_eq!(state.digest.swap_fee_per_mille, 0);
+        assert_eq!(
+            state.token_balances.get(&contract).unwrap().a_tokens,
+            20_000
+        );
+        assert_eq!(state.token_balances.get(&trader).unwrap().b_tokens, 5_000);
+        assert_eq!(state.fee_reserve.a_tokens, 0);
+        assert_eq!(state.accrued_fees(&provider).a_tokens, 0);
+    }
+
+    #[test]
+    pub fn high_fee_tier_takes_protocol_fee() {
+        let owner = create_address(AddressType::Account, 4);
+        let trader = create_address(AddressType::Account, 5);
+        let referrer = create_address(AddressType::Account, 6);
+        let mut state = state_with_fee_tier_and_deposits(owner, FeeTier::High {}, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(owner, 0), state, 10_000, 10_000);
+        (state, _) = set_referrer_share(create_ctx(owner, 0), state, 200);
+        (state, _) = deposit_callback(
+            create_ctx(trader, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            10_000,
+        );
+
+        let token_a = state.token_a_address;
+        (state, _) = swap(
+            create_ctx(trader, 0),
+            state,
+            token_a,
+            10_000,
+            Some(referrer),
+        );
+        assert_eq!(state.digest.swap_fee_per_mille, 10);
+        assert_eq!(state.token_balances.get(&referrer).unwrap().a_tokens, 20);
+        assert_eq!(state.token_balances.get(&owner).unwrap().a_tokens, 10);
+        assert_eq!(state.fee_reserve.a_tokens, 70);
+        let interaction = state.interactions.last().unwrap();
+        assert_eq!(interaction.kind, InteractionKind::ProtocolFee {});
+        assert_eq!(interaction.user, owner);
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer share should not exceed 900, as the protocol share is 100")]
+    pub fn referrer_share_cannot_exceed_remainder_of_protocol_share() {
+        let owner = create_address(AddressType::Account, 4);
+        let state = state_with_fee_tier_and_deposits(owner, FeeTier::High {}, 0, 0);
+        set_referrer_share(create_ctx(owner, 0), state, 901);
+    }
+
+    fn deposit_and_provide_callback_ctx(
+        provided_succeeded: bool,
+        opposite_succeeded: bool,
+    ) -> CallbackContext {
+        CallbackContext {
+            success: provided_succeeded && opposite_succeeded,
+            results: vec![
+                ExecutionResult {
+                    succeeded: provided_succeeded,
+                    return_data: vec![],
+                },
+                ExecutionResult {
+                    succeeded: opposite_succeeded,
+                    return_data: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    pub fn deposit_and_provide_liquidity_mints_liquidity() {
+        let owner = create_address(AddressType::Account, 4);
+        let provider = create_address(AddressType::Account, 5);
+        let contract = create_address(AddressType::PublicContract, 1);
+        let mut state = state_with_deposits(owner, 10_000, 40_000);
+        (state, _) = provide_initial_liquidity(create_ctx(owner, 0), state, 10_000, 40_000);
+
+        let token_a = state.token_a_address;
+        let (state, events) =
+            deposit_and_provide_liquidity(create_ctx(provider, 0), state, token_a, 1_000);
+        assert_eq!(events.len(), 1);
+        let (state, _) = deposit_and_provide_liquidity_callback(
+            create_ctx(provider, 0),
+            deposit_and_provide_callback_ctx(true, true),
+            state,
+            Token::A,
+            1_000,
+            Token::B,
+            4_001,
+        );
+
+        let provider_balance = state.token_balances.get(&provider).unwrap();
+        assert_eq!(provider_balance.a_tokens, 0);
+        assert_eq!(provider_balance.b_tokens, 0);
+        assert_eq!(provider_balance.liquidity_tokens, 2_000);
+        let contract_balance = state.token_balances.get(&contract).unwrap();
+        assert_eq!(contract_balance.a_tokens, 11_000);
+        assert_eq!(contract_balance.b_tokens, 44_001);
+        assert_eq!(
+            state.interactions.last().unwrap().kind,
+            InteractionKind::ProvideLiquidity {}
+        );
+    }
+
+    #[test]
+    pub fn failed_transfer_leaves_deposit_in_balance() {
+        let owner = create_address(AddressType::Account, 4);
+        let provider = create_address(AddressType::Account, 5);
+        let mut state = state_with_deposits(owner, 10_000, 40_000);
+        (state, _) = provide_initial_liquidity(create_ctx(owner, 0), state, 10_000, 40_000);
+
+        let (state, _) = deposit_and_provide_liquidity_callback(
+            create_ctx(provider, 0),
+            deposit_and_provide_callback_ctx(true, false),
+            state,
+            Token::A,
+            1_000,
+            Token::B,
+            4_000,
+        );
+
+        let provider_balance = state.token_balances.get(&provider).unwrap();
+        assert_eq!(provider_balance.a_tokens, 1_000);
+        assert_eq!(provider_balance.b_tokens, 0);
+        assert_eq!(provider_balance.liquidity_tokens, 0);
+        assert_eq!(
+            state.interactions.last().unwrap().kind,
+            InteractionKind::Deposit {}
+        );
+    }
+    #[test]
+    pub fn batch_swap_skips_failing_requests() {
+        let provider = create_address(AddressType::Account, 4);
+        let authorizing = create_address(AddressType::Account, 5);
+        let unauthorizing = create_address(AddressType::Account, 6);
+        let settler = create_address(AddressType::Account, 7);
+        let mut state = state_with_deposits(provider, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        for user in [authorizing, unauthorizing] {
+            (state, _) = deposit_callback(
+                create_ctx(user, 0),
+                create_callback_ctx(),
+                state,
+                Token::A,
+                2_000,
+            );
+        }
+        (state, _) = set_batch_settler(create_ctx(authorizing, 1), state, settler, true);
+
+        let token_a = state.token_a_address;
+        let request = |user, amount, min_amount_out| SwapRequest {
+            user,
+            token_address: token_a,
+            amount,
+            min_amount_out,
+        };
+        (state, _) = batch_swap(
+            create_ctx(settler, 2),
+            state,
+            vec![
+                request(authorizing, 1_000, 906),
+                request(unauthorizing, 1_000, 0),
+                request(authorizing, 1_001, 0),
+                request(authorizing, 1_000, 1_000),
+            ],
+        );
+
+        let statuses: Vec<&BatchSwapStatus> = state
+            .last_batch_results
+            .iter()
+            .map(|result| &result.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                &BatchSwapStatus::Executed {},
+                &BatchSwapStatus::Unauthorized {},
+                &BatchSwapStatus::InsufficientBalance {},
+                &BatchSwapStatus::BelowMinimumOutput {},
+            ]
+        );
+        assert_eq!(state.last_batch_results[0].amount_out, 906);
+        assert_eq!(state.last_batch_results[3].amount_out, 0);
+        let balance = state.token_balances.get(&authorizing).unwrap();
+        assert_eq!(balance.a_tokens, 1_000);
+        assert_eq!(balance.b_tokens, 906);
+        assert_eq!(state.swap_stats.swap_count, 1);
+        assert_eq!(state.interactions.last().unwrap().user, authorizing);
+    }
+
+    #[test]
+    pub fn revoked_settler_cannot_swap_for_user() {
+        let provider = create_address(AddressType::Account, 4);
+        let settler = create_address(AddressType::Account, 7);
+        let mut state = state_with_deposits(provider, 20_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        (state, _) = set_batch_settler(create_ctx(provider, 1), state, settler, true);
+        (state, _) = set_batch_settler(create_ctx(provider, 2), state, settler, false);
+        assert!(state.batch_settlers.is_empty());
+
+        let token_a = state.token_a_address;
+        (state, _) = batch_swap(
+            create_ctx(settler, 3),
+            state,
+            vec![SwapRequest {
+                user: provider,
+                token_address: token_a,
+                amount: 1_000,
+                min_amount_out: 0,
+            }],
+        );
+        assert_eq!(
+            state.last_batch_results[0].status,
+            BatchSwapStatus::Unauthorized {}
+        );
+        assert_eq!(state.swap_stats.swap_count, 0);
+    }
+
+    /// Asserts that the latest quote matches the latest recorded interaction.
+    fn assert_quote_matches_last_interaction(state: &LiquiditySwapContractState) {
+        let quote = state.last_quote.as_ref().unwrap();
+        let interaction = state.interactions.last().unwrap();
+        assert_eq!(quote.kind, interaction.kind);
+        for (quoted, actual) in [
+            (&quote.spent, &interaction.spent),
+            (&quote.received, &interaction.received),
+        ] {
+            assert_eq!(quoted.a_tokens, actual.a_tokens);
+            assert_eq!(quoted.b_tokens, actual.b_tokens);
+            assert_eq!(quoted.liquidity_tokens, actual.liquidity_tokens);
+        }
+    }
+
+    #[test]
+    pub fn quotes_match_executed_interactions() {
+        let provider = create_address(AddressType::Account, 4);
+        let user = create_address(AddressType::Account, 5);
+        let mut state = state_with_deposits(provider, 10_000, 30_001);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 30_001);
+        (state, _) = deposit_callback(
+            create_ctx(user, 0),
+            create_callback_ctx(),
+            state,
+            Token::A,
+            5_000,
+        );
+        let token_a = state.token_a_address;
+        let token_b = state.token_b_address;
+
+        (state, _) = quote_swap(create_ctx(user, 1), state, token_a, 1_337);
+        let pools = state.token_balances.get(&state.contract).unwrap();
+        assert_eq!((pools.a_tokens, pools.b_tokens), (10_000, 30_001));
+        assert_eq!(state.interactions.len(), 4);
+        (state, _) = swap(create_ctx(user, 1), state, token_a, 1_337, None);
+        assert_quote_matches_last_interaction(&state);
+
+        (state, _) = quote_provide(create_ctx(user, 2), state, token_b, 777);
+        (state, _) = provide_liquidity(create_ctx(user, 2), state, token_b, 777);
+        assert_quote_matches_last_interaction(&state);
+
+        let liquidity_tokens = state.token_balances.get(&user).unwrap().liquidity_tokens;
+        (state, _) = quote_reclaim(create_ctx(user, 3), state, liquidity_tokens);
+        (state, _) = reclaim_liquidity(create_ctx(user, 3), state, liquidity_tokens);
+        assert_quote_matches_last_interaction(&state);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot reclaim more liquidity tokens than have been minted")]
+    pub fn cannot_quote_reclaim_of_more_than_minted_liquidity() {
+        let provider = create_address(AddressType::Account, 4);
+        let mut state = state_with_deposits(provider, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        quote_reclaim(create_ctx(provider, 1), state, 10_001);
+    }
+
+    fn state_with_liquidity(provider: Address) -> LiquiditySwapContractState {
+        let mut state = state_with_deposits(provider, 10_000, 10_000);
+        (state, _) = provide_initial_liquidity(create_ctx(provider, 0), state, 10_000, 10_000);
+        state
+    }
+
+    #[test]
+    pub fn liquidity_tokens_are_transferred() {
+        let provider = create_address(AddressType::Account, 4);
+        let receiver = create_address(AddressType::Account, 5);
+        let state = state_with_liquidity(provider);
+        let minted = state
+            .token_balances
+            .get(&provider)
+            .unwrap()
+            .liquidity_tokens;
+
+        let (state, _) = transfer_liquidity_tokens(create_ctx(provider, 1), state, receiver, 1_000);
+        assert_eq!(
+            state
+                .token_balances
+                .get(&provider)
+                .unwrap()
+                .liquidity_tokens,
+            minted - 1_000
+        );
+        assert_eq!(
+            state
+                .token_balances
+                .get(&receiver)
+                .unwrap()
+                .liquidity_tokens,
+            1_000
+        );
+        let interaction = state.interactions.last().unwrap();
+        assert_eq!(interaction.kind, InteractionKind::TransferLiquidity {});
+        assert_eq!(interaction.spent.liquidity_tokens, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient funds")]
+    pub fn cannot_transfer_more_liquidity_tokens_than_owned() {
+        let provider = create_address(AddressType::Account, 4);
+        let receiver = create_address(AddressType::Account, 5);
+        let state = state_with_liquidity(provider);
+        let minted = state
+            .token_balances
+            .get(&provider)
+            .unwrap()
+            .liquidity_tokens;
+        transfer_liquidity_tokens(create_ctx(provider, 1), state, receiver, minted + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidity tokens cannot be transferred to")]
+    pub fn cannot_transfer_liquidity_tokens_to_contract() {
+        let provider = create_address(AddressType::Account, 4);
+        let state = state_with_liquidity(provider);
+        let contract = state.contract;
+        transfer_liquidity_tokens(create_ctx(provider, 1), state, contract, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidity tokens cannot be transferred to")]
+    pub fn cannot_transfer_liquidity_tokens_to_minimum_liquidity_holder() {
+        let provider = create_address(AddressType::Account, 4);
+        let state = state_with_liquidity(provider);
+        transfer_liquidity_tokens(
+            create_ctx(provider, 1),
+            state,
+            MINIMUM_LIQUIDITY_HOLDER,
+            1_000,
+        );
+    }
 }
@@ -0,0 +1,60 @@
+//! Fuzzes the lottery contract, invoking the generated entry points of `initialize` and of every
+//! action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{
+    arbitrary_address, arbitrary_context, arbitrary_zk_state, invoke, Call, EntryPointInput,
+};
+use libfuzzer_sys::fuzz_target;
+use lottery::{
+    __pbc_autogen__buy_tickets_callback_wrapped, __pbc_autogen__buy_tickets_wrapped,
+    __pbc_autogen__claim_callback_wrapped, __pbc_autogen__claim_wrapped,
+    __pbc_autogen__draw_wrapped, __pbc_autogen__initialize_wrapped,
+    __pbc_autogen__prize_payout_callback_wrapped,
+};
+
+const CALLS: [Call; 6] = [
+    Call::Action(__pbc_autogen__buy_tickets_wrapped),
+    Call::Callback(__pbc_autogen__buy_tickets_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__draw_wrapped),
+    Call::Callback(__pbc_autogen__prize_payout_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__claim_wrapped),
+    Call::Callback(__pbc_autogen__claim_callback_wrapped, 1),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+/// Generates the metadata of a secret variable, holding the type of a variable, being contributed
+/// randomness or the drawn random number.
+fn metadata(u: &mut Unstructured) -> Result<Vec<u8>> {
+    Ok(vec![u.int_in_range(1..=2)?])
+}
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_zk_state = arbitrary_zk_state(u, metadata)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx)
+            .state(&init_zk_state)
+            .rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let zk_state = arbitrary_zk_state(u, metadata)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.state(&zk_state).rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,511 @@
+//! Lottery Contract.
+//!
+//! In a lottery players buy tickets, and a single winning ticket is drawn at random, whose holder
+//! receives the tokens paid for all tickets. Drawing the winner on-chain requires randomness that
+//! no single party can predict or manipulate. This contract generates the randomness in a ZK
+//! computation, by combining secret random numbers contributed by the players.
+//!
+//! This implementation works in the following steps:
+//!
+//! 1. Initialization on the blockchain, with the token tickets are paid in, the price of a ticket,
+//!    the duration of the ticket sale, the duration of the contribution window following it, and
+//!    the minimum number of players contributing randomness.
+//! 2. Players buy tickets using `buy_tickets`, which transfers the price of the tickets from the
+//!    player to the contract using `transfer_from`. Each ticket is an equal chance to win.
+//! 3. Once the sale has closed, and until the contribution window closes, each ticket holder can
+//!    contribute a secret random number, using zero-knowledge protocols. Since no tickets can be
+//!    bought once the contributions start, the tickets are fixed before any randomness is known.
+//! 4. Once the contribution window has closed, anyone can start the draw. If fewer than the
+//!    minimum number of players have contributed, the lottery is cancelled instead, and the price
+//!    of every ticket is credited to the claim map of its holder.
+//! 5. The ZK computation sums the secret random numbers. The sum is uniformly random as long as
+//!    a single contribution is, so any player contributing a random number can trust the draw,
+//!    without any contribution being revealed. Requiring several contributors means that a single
+//!    player cannot decide the draw alone.
+//! 6. Once the ZK computation concludes, the random number is published, and the winning ticket is
+//!    the random number modulo the number of tickets sold. The tokens paid for all tickets are
+//!    transferred to the holder of the winning ticket. If the transfer fails, the prize is instead
+//!    credited to the claim map, from which it can be claimed using `claim`.
+//!
+//! The random number is a uniformly random `u32`, so unless the number of tickets divides 2^32,
+//! taking it modulo the number of tickets favours the first `2^32 % tickets` tickets slightly.
+//! Each of them wins with probability `(⌊2^32 / tickets⌋ + 1) / 2^32` rather than
+//! `⌊2^32 / tickets⌋ / 2^32`, a relative advantage of `1 / ⌊2^32 / tickets⌋`, which is below one
+//! in a million for up to 4000 tickets.
+//!
+//! Tickets whose payment arrives after the sale has closed are not issued, and the price paid for
+//! them is credited to the claim map of the player.
+
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::BTreeMap;
+
+use claim_map::{payout_event_group, ClaimMap, Payout};
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::zk::{CalculationStatus, SecretVarId, ZkInputDef, ZkState, ZkStateChange};
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// Secret variable metadata. Contains the type of the variable.
+#[derive(ReadWriteState, Debug)]
+#[repr(C)]
+struct SecretVarMetadata {
+    variable_type: SecretVarType,
+}
+
+#[derive(ReadWriteState, Debug, PartialEq)]
+#[repr(u8)]
+enum SecretVarType {
+    Randomness = 1,
+    RandomNumber = 2,
+}
+
+/// The size of the MPC randomness input variables.
+const BITLENGTH_OF_SECRET_RANDOMNESS_VARIABLES: [u32; 1] = [32];
+
+/// The maximum number of tickets that can be bought at a time.
+const MAX_TICKETS_PER_PURCHASE: u32 = 100;
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
+/// The result of the draw.
+#[derive(Clone, ReadWriteState, CreateTypeSpec)]
+struct DrawResult {
+    /// The random number generated by the ZK computation
+    random_number: u32,
+    /// The index of the winning ticket
+    winning_ticket: u32,
+    /// The holder of the winning ticket
+    winner: Address,
+    /// The tokens won
+    prize: u128,
+}
+
+/// This state of the contract.
+#[state]
+struct ContractState {
+    /// Owner of the contract
+    owner: Address,
+    /// The token tickets are paid in
+    ticket_token: Address,
+    /// The price of a single ticket
+    ticket_price: u128,
+    /// When the ticket sale closes and the contribution window opens, in milliseconds since the
+    /// epoch
+    close_time_millis: i64,
+    /// When the contribution window closes, in milliseconds since the epoch
+    contribution_close_time_millis: i64,
+    /// The minimum number of ticket holders contributing randomness for the draw to be made
+    min_contributors: u32,
+    /// The holders of the sold tickets, one entry for each ticket
+    tickets: Vec<Address>,
+    /// The result of the draw, once drawn
+    draw_result: Option<DrawResult>,
+    /// Tokens that could not be transferred, and can be claimed instead
+    claim_map: BTreeMap<Address, u128>,
+}
+
+impl ContractState {
+    /// Asserts that the ticket sale has not closed at `now`.
+    fn assert_sale_open(&self, now: i64) {
+        assert!(
+            now < self.close_time_millis,
+            "The ticket sale closed at {} ms UTC, current time is {} ms UTC",
+            self.close_time_millis,
+            now,
+        );
+    }
+
+    /// Asserts that randomness can be contributed at `now`, which is the case after the ticket
+    /// sale has closed and before the contribution window closes.
+    fn assert_contribution_window_open(&self, now: i64) {
+        assert!(
+            self.close_time_millis <= now && now < self.contribution_close_time_millis,
+            "Randomness can only be contributed between {} ms UTC and {} ms UTC, current time is {} ms UTC",
+            self.close_time_millis,
+            self.contribution_close_time_millis,
+            now,
+        );
+    }
+}
+
+/// Initializes contract
+///
+/// Note that owner is set to whoever initializes the contact. Tickets are paid in `ticket_token`
+/// at `ticket_price` each, and can be bought for `sale_duration_millis` after initialization.
+/// Ticket holders can then contribute randomness for `contribution_duration_millis`, and the draw
+/// requires at least `min_contributors` contributions.
+#[init]
+fn initialize(
+    context: ContractContext,
+    zk_state: ZkState<SecretVarMetadata>,
+    ticket_token: Address,
+    ticket_price: u128,
+    sale_duration_millis: u32,
+    contribution_duration_millis: u32,
+    min_contributors: u32,
+) -> ContractState {
+    assert!(
+        ticket_token.address_type == AddressType::PublicContract,
+        "Ticket token must be a public contract"
+    );
+    assert!(ticket_price > 0, "The ticket price must be positive");
+    assert!(
+        contribution_duration_millis > 0,
+        "The contribution window must have a positive duration"
+    );
+    assert!(
+        min_contributors > 0,
+        "At least one contributor must be required"
+    );
+    let close_time_millis = context.block_production_time + sale_duration_millis as i64;
+    ContractState {
+        owner: context.sender,
+        ticket_token,
+        ticket_price,
+        close_time_millis,
+        contribution_close_time_millis: close_time_millis + contribution_duration_millis as i64,
+        min_contributors,
+        tickets: Vec::new(),
+        draw_result: None,
+        claim_map: BTreeMap::new(),
+    }
+}
+
+/// Buys `num_tickets` tickets for the sender, by transferring the price of the tickets to the
+/// contract.
+///
+/// Tickets can only be bought until the sale closes, and at most [`MAX_TICKETS_PER_PURCHASE`] at
+/// a time.
+#[action(shortname = 0x01)]
+fn buy_tickets(
+    context: ContractContext,
+    state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    num_tickets: u32,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    state.assert_sale_open(context.block_production_time);
+    assert!(
+        0 < num_tickets && num_tickets <= MAX_TICKETS_PER_PURCHASE,
+        "Between 1 and {MAX_TICKETS_PER_PURCHASE} tickets can be bought at a time",
+    );
+    let amount = state.ticket_price * num_tickets as u128;
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.ticket_token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_BUY_TICKETS_CALLBACK)
+        .argument(context.sender)
+        .argument(num_tickets)
+        .done();
+
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when the price of bought tickets has been transferred. Issues the tickets if the
+/// transfer succeeded.
+///
+/// If the sale has closed in the meantime, no tickets are issued, and the price is credited to the
+/// claim map of the player instead, such that it can be claimed back using `claim`.
+#[callback(shortname = 0x10)]
+fn buy_tickets_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    player: Address,
+    num_tickets: u32,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the ticket price did not succeed"
+    );
+    let sale_closed = context.block_production_time >= state.close_time_millis
+        || zk_state.calculation_state != CalculationStatus::Waiting
+        || state.draw_result.is_some();
+    if sale_closed {
+        let amount = state.ticket_price * num_tickets as u128;
+        state.claim_map.credit(player, amount);
+    } else {
+        for _ in 0..num_tickets {
+            state.tickets.push(player);
+        }
+    }
+    (state, vec![], vec![])
+}
+
+/// Adds the secret random number of a ticket holder to the ZkState.
+///
+/// Each ticket holder can contribute a single random number in the contribution window, which
+/// opens when the sale closes. The ZkInputDef encodes that variables should have size
+/// [`BITLENGTH_OF_SECRET_RANDOMNESS_VARIABLES`].
+#[zk_on_secret_input(shortname = 0x40)]
+fn add_randomness(
+    context: ContractContext,
+    state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (
+    ContractState,
+    Vec<EventGroup>,
+    ZkInputDef<SecretVarMetadata>,
+) {
+    state.assert_contribution_window_open(context.block_production_time);
+    assert!(
+        state.tickets.contains(&context.sender),
+        "Only ticket holders can contribute randomness"
+    );
+    assert!(
+        zk_state
+            .secret_variables
+            .iter()
+            .chain(zk_state.pending_inputs.iter())
+            .all(|v| v.owner != context.sender),
+        "Each ticket holder is only allowed to contribute randomness once. Sender: {:?}",
+        context.sender
+    );
+
+    let input_def = ZkInputDef {
+        seal: false,
+        metadata: SecretVarMetadata {
+            variable_type: SecretVarType::Randomness,
+        },
+        expected_bit_lengths: BITLENGTH_OF_SECRET_RANDOMNESS_VARIABLES.to_vec(),
+    };
+
+    (state, vec![], input_def)
+}
+
+/// Allows anybody to start the draw, once the contribution window has closed.
+///
+/// The draw is automatic beyond this call, involving several steps, as described in the module
+/// documentation. If fewer than the minimum number of ticket holders have contributed randomness,
+/// the lottery is cancelled instead: the price of every ticket is credited to the claim map of its
+/// holder, and the contributed randomness is deleted.
+///
+/// NOTE: Randomness that is still pending input is not included in the draw.
+#[action(shortname = 0x02)]
+fn draw(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        context.block_production_time >= state.contribution_close_time_millis,
+        "The draw cannot start before the contribution window closes at {} ms UTC, current time is {} ms UTC",
+        state.contribution_close_time_millis,
+        context.block_production_time,
+    );
+    assert_eq!(
+        zk_state.calculation_state,
+        CalculationStatus::Waiting,
+        "The draw must start from Waiting state, but was {:?}",
+        zk_state.calculation_state,
+    );
+    assert!(
+        state.draw_result.is_none(),
+        "The draw has already been made"
+    );
+    assert!(!state.tickets.is_empty(), "No tickets have been sold");
+    if (zk_state.secret_variables.len() as u32) < state.min_contributors {
+        for holder in std::mem::take(&mut state.tickets) {
+            state.claim_map.credit(holder, state.ticket_price);
+        }
+        let variables_to_delete = zk_state
+            .secret_variables
+            .iter()
+            .map(|v| v.variable_id)
+            .collect();
+        return (
+            state,
+            vec![],
+            vec![ZkStateChange::DeleteVariables {
+                variables_to_delete,
+            }],
+        );
+    }
+
+    (
+        state,
+        vec![],
+        vec![ZkStateChange::start_computation(vec![SecretVarMetadata {
+            variable_type: SecretVarType::RandomNumber,
+        }])],
+    )
+}
+
+/// Automatically called when the computation is completed
+///
+/// The only thing we do is to instantly open/declassify the output variable.
+#[zk_on_compute_complete]
+fn draw_compute_complete(
+    context: ContractContext,
+    state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    output_variables: Vec<SecretVarId>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    (
+        state,
+        vec![],
+        vec![ZkStateChange::OpenVariables {
+            variables: output_variables,
+        }],
+    )
+}
+
+/// Automatically called when the random number is declassified. Determines the winning ticket,
+/// and transfers the prize to its holder. The secret random numbers of the players are deleted.
+///
+/// The winning ticket is the random number modulo the number of tickets, which has a slight bias
+/// towards the first tickets, as described in the module documentation.
+#[zk_on_variables_opened]
+fn open_random_number(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    opened_variables: Vec<SecretVarId>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert_eq!(
+        opened_variables.len(),
+        1,
+        "Unexpected number of output variables"
+    );
+    let random_number = read_variable_u32_le(&zk_state, opened_variables.first());
+    let winning_ticket = random_number % state.tickets.len() as u32;
+    let winner = state.tickets[winning_ticket as usize];
+    let prize = state.ticket_price * state.tickets.len() as u128;
+
+    state.draw_result = Some(DrawResult {
+        random_number,
+        winning_ticket,
+        winner,
+        prize,
+    });
+
+    let mut event_group = payout_event_group(
+        winner,
+        &[Payout {
+            token: state.ticket_token,
+            amount: prize,
+        }],
+    );
+    event_group
+        .with_callback(SHORTNAME_PRIZE_PAYOUT_CALLBACK)
+        .argument(winner)
+        .argument(prize)
+        .done();
+
+    let variables_to_delete: Vec<SecretVarId> = zk_state
+        .secret_variables
+        .iter()
+        .filter(|v| v.metadata.variable_type == SecretVarType::Randomness)
+        .map(|v| v.variable_id)
+        .collect();
+
+    (
+        state,
+        vec![event_group.build()],
+        vec![ZkStateChange::OutputComplete {
+            variables_to_delete,
+        }],
+    )
+}
+
+/// Called when the prize has been transferred to the winner. If the transfer failed, the prize is
+/// credited to the claim map of the winner.
+#[callback(shortname = 0x11)]
+fn prize_payout_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    winner: Address,
+    prize: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let payouts = [Payout {
+        token: state.ticket_token,
+        amount: prize,
+    }];
+    state
+        .claim_map
+        .restore_if_failed(&callback_ctx, winner, &payouts, |_, amount| amount);
+    (state, vec![], vec![])
+}
+
+/// Claims the tokens credited to the sender, if the transfer of the prize failed, tickets were
+/// bought after the sale closed, or the lottery was cancelled. The transfer is verified by
+/// `claim_callback`.
+#[action(shortname = 0x03)]
+fn claim(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let amount = match state.claim_map.take_claim(&context.sender) {
+        Some(amount) if amount > 0 => amount,
+        _ => panic!("Nothing to claim for {:?}", context.sender),
+    };
+    let mut event_group = payout_event_group(
+        context.sender,
+        &[Payout {
+            token: state.ticket_token,
+            amount,
+        }],
+    );
+    event_group
+        .with_callback(SHORTNAME_CLAIM_CALLBACK)
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when claimed tokens have been transferred. If the transfer failed, the tokens are
+/// credited back to the claim map of the claimant, such that they can be claimed again.
+#[callback(shortname = 0x12)]
+fn claim_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    claimant: Address,
+    amount: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let payouts = [Payout {
+        token: state.ticket_token,
+        amount,
+    }];
+    state
+        .claim_map
+        .restore_if_failed(&callback_ctx, claimant, &payouts, |_, amount| amount);
+    (state, vec![], vec![])
+}
+
+/// Reads a variable's data as an u32.
+fn read_variable_u32_le(
+    zk_state: &ZkState<SecretVarMetadata>,
+    variable_id: Option<&SecretVarId>,
+) -> u32 {
+    let variable_id = *variable_id.unwrap();
+    let variable = zk_state.get_variable(variable_id).unwrap();
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(variable.data.as_ref().unwrap().as_slice());
+    <u32>::from_le_bytes(buffer)
+}
@@ -0,0 +1,265 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::zk::{CalculationStatus, SecretVarId, ZkClosed, ZkState, ZkStateChange};
+use pbc_contract_common::Hash;
+
+use crate::{
+    buy_tickets_callback, claim, claim_callback, draw, initialize, open_random_number,
+    prize_payout_callback, ContractState, SecretVarMetadata, SecretVarType,
+};
+
+const TICKET_PRICE: u128 = 10;
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: block_production_time / 3_600_000,
+        block_production_time,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+fn owner() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn ticket_token() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn player(id: u8) -> Address {
+    create_address(AddressType::Account, 10 + id)
+}
+
+fn create_variable(
+    raw_id: u32,
+    owner: Address,
+    variable_type: SecretVarType,
+) -> ZkClosed<SecretVarMetadata> {
+    ZkClosed {
+        variable_id: SecretVarId::new(raw_id),
+        owner,
+        is_sealed: false,
+        metadata: SecretVarMetadata { variable_type },
+        data: None,
+    }
+}
+
+/// A zk state holding the randomness contributed by the first `contributors` players.
+fn zk_state_with_randomness(contributors: u8) -> ZkState<SecretVarMetadata> {
+    ZkState {
+        calculation_state: CalculationStatus::Waiting,
+        pending_inputs: vec![],
+        secret_variables: (0..contributors)
+            .map(|id| create_variable(id as u32 + 1, player(id), SecretVarType::Randomness))
+            .collect(),
+        data_attestations: vec![],
+    }
+}
+
+/// A lottery whose sale closes at 1000 ms and contribution window at 2000 ms, requiring two
+/// contributors, where each of three players has bought one ticket.
+fn create_state() -> ContractState {
+    let mut state = initialize(
+        create_ctx(owner(), 0),
+        zk_state_with_randomness(0),
+        ticket_token(),
+        TICKET_PRICE,
+        1000,
+        1000,
+        2,
+    );
+    for id in 0..3 {
+        (state, _, _) = buy_tickets_callback(
+            create_ctx(ticket_token(), 500),
+            create_callback_ctx(true),
+            state,
+            zk_state_with_randomness(0),
+            player(id),
+            1,
+        );
+    }
+    state
+}
+
+fn expected_payout(to: Address, amount: u128, callback: u32) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(ticket_token(), Shortname::from_u32(1))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event
+        .with_callback(ShortnameCallback::from_u32(callback))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event.build()
+}
+
+#[test]
+fn draw_starts_computation() {
+    let (state, events, changes) = draw(
+        create_ctx(player(0), 2000),
+        create_state(),
+        zk_state_with_randomness(2),
+    );
+    assert!(events.is_empty());
+    assert_eq!(
+        changes,
+        vec![ZkStateChange::start_computation(vec![SecretVarMetadata {
+            variable_type: SecretVarType::RandomNumber,
+        }])]
+    );
+    assert_eq!(state.tickets.len(), 3);
+    assert!(state.claim_map.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "The draw cannot start before the contribution window closes")]
+fn draw_before_contribution_window_closes() {
+    draw(
+        create_ctx(player(0), 1999),
+        create_state(),
+        zk_state_with_randomness(2),
+    );
+}
+
+#[test]
+fn draw_with_too_few_contributors_cancels() {
+    let (state, events, changes) = draw(
+        create_ctx(player(0), 2000),
+        create_state(),
+        zk_state_with_randomness(1),
+    );
+    assert!(events.is_empty());
+    assert_eq!(
+        changes,
+        vec![ZkStateChange::DeleteVariables {
+            variables_to_delete: vec![SecretVarId::new(1)],
+        }]
+    );
+    assert!(state.tickets.is_empty());
+    for id in 0..3 {
+        assert_eq!(state.claim_map.get(&player(id)), Some(&TICKET_PRICE));
+    }
+}
+
+#[test]
+fn winner_is_paid_the_prize() {
+    let mut zk_state = zk_state_with_randomness(2);
+    let mut random_number = create_variable(3, owner(), SecretVarType::RandomNumber);
+    random_number.data = Some(7u32.to_le_bytes().to_vec());
+    zk_state.secret_variables.push(random_number);
+
+    let (state, events, changes) = open_random_number(
+        create_ctx(owner(), 2500),
+        create_state(),
+        zk_state,
+        vec![SecretVarId::new(3)],
+    );
+    // 7 modulo 3 tickets
+    let draw_result = state.draw_result.unwrap();
+    assert_eq!(draw_result.winning_ticket, 1);
+    assert_eq!(draw_result.winner, player(1));
+    assert_eq!(events, vec![expected_payout(player(1), 30, 0x11)]);
+    assert_eq!(
+        changes,
+        vec![ZkStateChange::OutputComplete {
+            variables_to_delete: vec![SecretVarId::new(1), SecretVarId::new(2)],
+        }]
+    );
+}
+
+#[test]
+fn failed_prize_payout_is_credited() {
+    let (state, _, _) = prize_payout_callback(
+        create_ctx(owner(), 2500),
+        create_callback_ctx(false),
+        create_state(),
+        zk_state_with_randomness(0),
+        player(1),
+        30,
+    );
+    assert_eq!(state.claim_map.get(&player(1)), Some(&30));
+}
+
+#[test]
+fn late_tickets_are_credited() {
+    let (state, _, _) = buy_tickets_callback(
+        create_ctx(ticket_token(), 1000),
+        create_callback_ctx(true),
+        create_state(),
+        zk_state_with_randomness(0),
+        player(3),
+        2,
+    );
+    assert_eq!(state.tickets.len(), 3);
+    assert_eq!(state.claim_map.get(&player(3)), Some(&(2 * TICKET_PRICE)));
+}
+
+#[test]
+fn claim_transfers_credited_tokens() {
+    let mut state = create_state();
+    state.claim_map.insert(player(3), 20);
+    let (state, events, _) = claim(
+        create_ctx(player(3), 1500),
+        state,
+        zk_state_with_randomness(0),
+    );
+    assert_eq!(events, vec![expected_payout(player(3), 20, 0x12)]);
+    assert_eq!(state.claim_map.get(&player(3)), Some(&0));
+}
+
+#[test]
+#[should_panic(expected = "Nothing to claim")]
+fn claim_without_credit() {
+    claim(
+        create_ctx(player(0), 1500),
+        create_state(),
+        zk_state_with_randomness(0),
+    );
+}
+
+#[test]
+fn failed_claim_is_credited_again() {
+    let mut state = create_state();
+    state.claim_map.insert(player(3), 20);
+    let (state, _, _) = claim(
+        create_ctx(player(3), 1500),
+        state,
+        zk_state_with_randomness(0),
+    );
+    let (state, _, _) = claim_callback(
+        create_ctx(ticket_token(), 1500),
+        create_callback_ctx(false),
+        state,
+        zk_state_with_randomness(0),
+        player(3),
+        20,
+    );
+    assert_eq!(state.claim_map.get(&player(3)), Some(&20));
+}
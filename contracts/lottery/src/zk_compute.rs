@@ -0,0 +1,14 @@
+/// Perform a zk computation on secret-shared data.
+/// Combines the secret randomness contributed by the players into a single random number.
+use pbc_zk::*;
+
+pub fn zk_compute() -> Sbi32 {
+    let mut random_number: Sbi32 = Sbi32::from(0);
+
+    // The sum is uniformly random as long as a single contribution is uniformly random
+    for variable_id in secret_variable_ids() {
+        random_number = random_number + load_sbi::<Sbi32>(variable_id);
+    }
+
+    random_number
+}
@@ -0,0 +1,61 @@
+//! Fuzzes the voter management and proposal actions of the multi-voting contract, invoking the
+//! generated entry points of `initialize` and of every action and callback with arbitrary RPC
+//! payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+use multi_voting_contract::{
+    __pbc_autogen__add_voter_wrapped, __pbc_autogen__add_voters_wrapped,
+    __pbc_autogen__add_voting_contract_callback_wrapped,
+    __pbc_autogen__add_voting_contract_wrapped, __pbc_autogen__initialize_wrapped,
+    __pbc_autogen__prune_expired_wrapped, __pbc_autogen__receive_result_wrapped,
+    __pbc_autogen__remove_voter_wrapped, __pbc_autogen__remove_voters_wrapped,
+    __pbc_autogen__retry_deployment_wrapped,
+    __pbc_autogen__voting_contract_exists_callback_wrapped,
+};
+
+const CALLS: [Call; 10] = [
+    Call::Action(__pbc_autogen__add_voter_wrapped),
+    Call::Action(__pbc_autogen__remove_voter_wrapped),
+    Call::Action(__pbc_autogen__add_voters_wrapped),
+    Call::Action(__pbc_autogen__remove_voters_wrapped),
+    Call::Action(__pbc_autogen__add_voting_contract_wrapped),
+    Call::Action(__pbc_autogen__retry_deployment_wrapped),
+    Call::Callback(__pbc_autogen__add_voting_contract_callback_wrapped, 1),
+    Call::Callback(__pbc_autogen__voting_contract_exists_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__prune_expired_wrapped),
+    Call::Action(__pbc_autogen__receive_result_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let owner = init_ctx.sender;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let mut ctx = arbitrary_context(u, contract)?;
+        // Most actions are restricted to the owner, so let the owner send most of them.
+        if u.ratio(3, 4)? {
+            ctx.sender = owner;
+        }
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
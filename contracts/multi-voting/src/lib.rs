@@ -1,18 +1,43 @@
 //! This is the example multi-voting contract. This contract is able to deploy new voting contracts
 //! that can be voted on. The contract keeps track of deployed voting contracts and their proposal
 //! ids, such that voters can vote on them. Users can then go to the deployed contracts to submit their votes.
+//!
+//! The deadline of each deployed vote is tracked along with its address, such that the status of
+//! each vote can be read from the state. The owner can prune votes that expired long ago to keep
+//! the state small, once their results have been received.
+//!
+//! If the deployment of a voting contract fails, the entry is kept with a failed status, and the
+//! owner can retry the deployment a limited number of times.
+//!
+//! The set of eligible voters is versioned, and each change to the set starts a new version. Each
+//! voting contract records the version of the voter set it was deployed with, and a snapshot of
+//! every version in use is kept in the state, such that auditors can verify who was eligible to
+//! vote on each proposal. Voters added or removed after a deployment do not affect the deployed
+//! voting contract.
+//!
+//! Each voting contract is deployed with its own configuration, such that votes on different
+//! proposals can require different quorums, be anonymous ballots, or report to a notification or
+//! registry contract.
+//!
+//! Each voting contract is deployed with this contract as its parent, such that the voting contract
+//! pushes the result of the vote to this contract when the votes are counted. Whether each
+//! proposal passed can thereby be read from the state.
 #![allow(unused_variables)]
 
 #[macro_use]
 extern crate pbc_contract_codegen;
 extern crate pbc_contract_common;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
+use create_type_spec_derive::CreateTypeSpec;
 use pbc_contract_common::address::{Address, AddressType, Shortname};
 use pbc_contract_common::context::{CallbackContext, ContractContext};
 use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
 use pbc_traits::WriteRPC;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
 
 const PUB_DEPLOY_ADDRESS: Address = Address {
     address_type: AddressType::SystemContract,
@@ -22,22 +47,236 @@ const PUB_DEPLOY_ADDRESS: Address = Address {
     ],
 };
 
+/// The maximum number of voters that can be added or removed in a single batch.
+const MAX_VOTER_BATCH_SIZE: usize = 100;
+
+/// The maximum number of times the deployment of a voting contract can be retried.
+const MAX_DEPLOYMENT_RETRIES: u32 = 3;
+
+/// An option that can be voted for in a deployed voting contract. Mirrors the `OptionSpec` of the
+/// voting contract.
+///
+/// ### Fields:
+///
+/// * `id`: [`u32`], the id of the option.
+/// * `label_hash`: [`Hash`], the hash of the label of the option.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct OptionSpec {
+    id: u32,
+    label_hash: Hash,
+}
+
+/// The fraction of the eligible voters required to participate in a deployed vote. Mirrors the
+/// `QuorumFraction` of the voting contract.
+///
+/// ### Fields:
+///
+/// * `numerator`: [`u32`], the numerator of the fraction.
+/// * `denominator`: [`u32`], the denominator of the fraction.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct QuorumFraction {
+    numerator: u32,
+    denominator: u32,
+}
+
+/// The configuration of a deployed voting contract, passed to the voting contract when it is
+/// initialized.
+///
+/// ### Fields:
+///
+/// * `quorum`: [`QuorumFraction`], the fraction of the eligible voters required to participate.
+///   A quorum of 0 means that no quorum is required.
+/// * `reveal_deadline`: [`Option<i64>`], the deadline for revealing committed votes in UTC
+///   millis, making the vote an anonymous ballot, or None for an open ballot.
+/// * `notification_contract`: [`Option<Address>`], the contract receiving vote receipts, if any.
+/// * `registry_address`: [`Option<Address>`], the registry contract the result is certified to,
+///   if any.
+/// * `lock_votes_after_millis`: [`Option<i64>`], the time in UTC millis after which votes can no
+///   longer be changed, or None if they can be changed until the deadline.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct VoteConfiguration {
+    quorum: QuorumFraction,
+    reveal_deadline: Option<i64>,
+    notification_contract: Option<Address>,
+    registry_address: Option<Address>,
+    lock_votes_after_millis: Option<i64>,
+}
+
+impl VoteConfiguration {
+    /// Asserts that the configuration is valid for a vote with the given deadline, such that the
+    /// voting contract can be initialized with it.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `deadline`: [`i64`], the deadline of the vote in UTC millis.
+    fn assert_valid(&self, deadline: i64) {
+        assert!(
+            self.quorum.denominator > 0 && self.quorum.numerator <= self.quorum.denominator,
+            "The quorum must be a fraction between 0 and 1"
+        );
+        if let Some(reveal_deadline) = self.reveal_deadline {
+            assert!(
+                reveal_deadline >= deadline,
+                "The reveal deadline cannot be before the deadline"
+            );
+        }
+        if let Some(lock_time) = self.lock_votes_after_millis {
+            assert!(
+                lock_time <= deadline,
+                "The vote lock cannot be after the deadline"
+            );
+        }
+    }
+}
+
+/// The status of the deployment of a voting contract.
+#[derive(ReadWriteState, CreateTypeSpec, PartialEq, Eq)]
+pub enum DeploymentStatus {
+    /// The voting contract is being deployed.
+    #[discriminant(0)]
+    Deploying {},
+    /// The voting contract has been deployed.
+    #[discriminant(1)]
+    Deployed {},
+    /// The deployment of the voting contract failed, and can be retried.
+    #[discriminant(2)]
+    Failed {},
+}
+
+/// A deployed voting contract.
+///
+/// ### Fields:
+///
+/// * `address`: [`Option<Address>`], the address of the voting contract, or None until it has
+///   been deployed.
+/// * `deadline`: [`i64`], the deadline of the vote in UTC millis.
+/// * `options`: [`Vec<OptionSpec>`], the options that can be voted for, kept for retrying the
+///   deployment.
+/// * `configuration`: [`VoteConfiguration`], the configuration of the vote, kept for retrying the
+///   deployment.
+/// * `deployment_status`: [`DeploymentStatus`], the status of the deployment.
+/// * `retries`: [`u32`], the number of times the deployment has been retried.
+/// * `voter_set_version`: [`u32`], the version of the voter set the voting contract was deployed
+///   with, see [`MultiVotingState::voters_for_proposal`].
+/// * `passed`: [`Option<bool>`], whether the proposal passed, or None until the voting contract
+///   has pushed its result.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct VotingContract {
+    address: Option<Address>,
+    deadline: i64,
+    options: Vec<OptionSpec>,
+    configuration: VoteConfiguration,
+    deployment_status: DeploymentStatus,
+    retries: u32,
+    voter_set_version: u32,
+    passed: Option<bool>,
+}
+
+/// The status of a deployed voting contract.
+#[derive(PartialEq, Eq, Debug)]
+pub enum VotingContractStatus {
+    /// The voting contract is being deployed.
+    Deploying,
+    /// The deployment of the voting contract failed.
+    Failed,
+    /// The voting contract is deployed, and accepts votes until the deadline.
+    Active,
+    /// The deadline of the voting contract has passed.
+    Expired,
+    /// The votes have been counted, and the voting contract has pushed its result.
+    Counted,
+}
+
+impl VotingContract {
+    /// Determines the status of the voting contract at a point in time.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `now_millis`: [`i64`], the point in time in UTC millis.
+    ///
+    /// ### Returns:
+    /// The [`VotingContractStatus`] of the voting contract.
+    pub fn status(&self, now_millis: i64) -> VotingContractStatus {
+        if self.deployment_status == (DeploymentStatus::Deploying {}) {
+            VotingContractStatus::Deploying
+        } else if self.deployment_status == (DeploymentStatus::Failed {}) {
+            VotingContractStatus::Failed
+        } else if self.passed.is_some() {
+            VotingContractStatus::Counted
+        } else if now_millis < self.deadline {
+            VotingContractStatus::Active
+        } else {
+            VotingContractStatus::Expired
+        }
+    }
+}
+
 /// Contract state.
 ///
 /// ### Fields:
 ///
 /// * `owner`: [`Address`], the owner of the contract.
-/// * `eligible_voters`: [`Vec<Address>`], the list of legal voters.
-/// * `voting_contracts`: [`BTreeMap<u64, Option<Address>`], A map from proposal ids to voting contracts.
+/// * `eligible_voters`: [`BTreeSet<Address>`], the set of legal voters.
+/// * `voting_contracts`: [`BTreeMap<u64, VotingContract>`], A map from proposal ids to voting contracts.
 /// * `voting_contract_wasm`: [`Vec<u8>`], bytes of the voting contract wasm.
 /// * `voting_contract_abi`: [`Vec<u8>`], bytes of the voting contract abi.
+/// * `voter_set_version`: [`u32`], the version of the current set of eligible voters, incremented
+///   whenever voters are added or removed.
+/// * `voter_set_snapshots`: [`BTreeMap<u32, BTreeSet<Address>>`], the voter sets of the versions
+///   that voting contracts were deployed with.
 #[state]
 pub struct MultiVotingState {
     owner: Address,
-    eligible_voters: Vec<Address>,
-    voting_contracts: BTreeMap<u64, Option<Address>>,
+    eligible_voters: BTreeSet<Address>,
+    voting_contracts: BTreeMap<u64, VotingContract>,
     voting_contract_wasm: Vec<u8>,
     voting_contract_abi: Vec<u8>,
+    voter_set_version: u32,
+    voter_set_snapshots: BTreeMap<u32, BTreeSet<Address>>,
+}
+
+impl MultiVotingState {
+    /// Looks up the voters that were eligible to vote on a proposal, being the voter set the
+    /// voting contract of the proposal was deployed with.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `p_id`: [`u64`], the proposal id of the voting contract.
+    ///
+    /// ### Returns:
+    /// The eligible voters of the proposal, or None if the proposal id does not exist.
+    pub fn voters_for_proposal(&self, p_id: u64) -> Option<&BTreeSet<Address>> {
+        let voting_contract = self.voting_contracts.get(&p_id)?;
+        self.voter_set_snapshots
+            .get(&voting_contract.voter_set_version)
+    }
+
+    /// Starts a new version of the voter set, after voters have been added or removed.
+    fn bump_voter_set_version(&mut self) {
+        self.voter_set_version += 1;
+    }
+
+    /// Records a snapshot of the current voter set, unless its version has already been recorded.
+    ///
+    /// ### Returns:
+    /// The version of the current voter set.
+    fn snapshot_voter_set(&mut self) -> u32 {
+        self.voter_set_snapshots
+            .entry(self.voter_set_version)
+            .or_insert_with(|| self.eligible_voters.clone());
+        self.voter_set_version
+    }
+
+    /// Removes the snapshots of voter sets that no voting contract was deployed with.
+    fn prune_voter_set_snapshots(&mut self) {
+        let versions_in_use: BTreeSet<u32> = self
+            .voting_contracts
+            .values()
+            .map(|voting_contract| voting_contract.voter_set_version)
+            .collect();
+        self.voter_set_snapshots
+            .retain(|version, _| versions_in_use.contains(version));
+    }
 }
 
 /// Initial function to create the initial state.
@@ -56,13 +295,15 @@ pub fn initialize(
     voting_contract_wasm: Vec<u8>,
     voting_contract_abi: Vec<u8>,
 ) -> (MultiVotingState, Vec<EventGroup>) {
-    let eligible_voters = vec![ctx.sender];
+    let eligible_voters = BTreeSet::from([ctx.sender]);
     let state = MultiVotingState {
         owner: ctx.sender,
         eligible_voters,
         voting_contracts: BTreeMap::new(),
         voting_contract_wasm,
         voting_contract_abi,
+        voter_set_version: 0,
+        voter_set_snapshots: BTreeMap::new(),
     };
 
     (state, vec![])
@@ -86,12 +327,11 @@ pub fn add_voter(
     voter: Address,
 ) -> (MultiVotingState, Vec<EventGroup>) {
     assert_eq!(ctx.sender, state.owner, "Only owner can add voters");
-    let voter_exists = state.eligible_voters.iter().any(|x| *x == voter);
-    if voter_exists {
+    let mut new_state = state;
+    if !new_state.eligible_voters.insert(voter) {
         panic!("Voter already exists");
     }
-    let mut new_state = state;
-    new_state.eligible_voters.push(voter);
+    new_state.bump_voter_set_version();
     (new_state, vec![])
 }
 
@@ -114,19 +354,85 @@ pub fn remove_voter(
 ) -> (MultiVotingState, Vec<EventGroup>) {
     assert_eq!(ctx.sender, state.owner, "Only owner can remove voters");
     let mut new_state = state;
-    let index = new_state
-        .eligible_voters
-        .iter()
-        .position(|x| *x == voter)
-        .expect("Voter does not exist");
-    new_state.eligible_voters.remove(index);
+    if !new_state.eligible_voters.remove(&voter) {
+        panic!("Voter does not exist");
+    }
+    new_state.bump_voter_set_version();
+    (new_state, vec![])
+}
+
+/// Adds a batch of voters to eligible voters. Only the owner of the contract can add voters.
+/// Fails if the batch exceeds [`MAX_VOTER_BATCH_SIZE`], contains duplicates, or contains a voter
+/// that already exists.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`MultiVotingState`], the state before the call.
+/// * `voters`: [`Vec<Address>`], the voters to be added.
+///
+/// ### Returns:
+/// The new state of type [`MultiVotingState`].
+#[action]
+pub fn add_voters(
+    ctx: ContractContext,
+    state: MultiVotingState,
+    voters: Vec<Address>,
+) -> (MultiVotingState, Vec<EventGroup>) {
+    assert_eq!(ctx.sender, state.owner, "Only owner can add voters");
+    assert_batch_size(&voters);
+    let mut new_state = state;
+    for voter in voters {
+        if !new_state.eligible_voters.insert(voter) {
+            panic!("Voter already exists or is duplicated: {voter:?}");
+        }
+    }
+    new_state.bump_voter_set_version();
     (new_state, vec![])
 }
 
+/// Removes a batch of voters from eligible voters. Only the owner of the contract can remove
+/// voters. Fails if the batch exceeds [`MAX_VOTER_BATCH_SIZE`], contains duplicates, or contains
+/// a voter that does not exist.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`MultiVotingState`], the state before the call.
+/// * `voters`: [`Vec<Address>`], the voters to be removed.
+///
+/// ### Returns:
+/// The new state of type [`MultiVotingState`].
+#[action]
+pub fn remove_voters(
+    ctx: ContractContext,
+    state: MultiVotingState,
+    voters: Vec<Address>,
+) -> (MultiVotingState, Vec<EventGroup>) {
+    assert_eq!(ctx.sender, state.owner, "Only owner can remove voters");
+    assert_batch_size(&voters);
+    let mut new_state = state;
+    for voter in voters {
+        if !new_state.eligible_voters.remove(&voter) {
+            panic!("Voter does not exist or is duplicated: {voter:?}");
+        }
+    }
+    new_state.bump_voter_set_version();
+    (new_state, vec![])
+}
+
+/// Checks that a batch of voters does not exceed [`MAX_VOTER_BATCH_SIZE`].
+fn assert_batch_size(voters: &[Address]) {
+    assert!(
+        voters.len() <= MAX_VOTER_BATCH_SIZE,
+        "At most {MAX_VOTER_BATCH_SIZE} voters can be changed at a time"
+    );
+}
+
 /// Deploys a new voting contract with given proposal id. The voting contract is deployed with
 /// eligible voters as those who can vote. The address of the new voting contract is computed
 /// from the original transaction hash. Only the owner can add new voting contracts, and the
-/// proposal id has to be unique.
+/// proposal id has to be unique. A snapshot of the eligible voters is recorded for the proposal.
 /// This creates an event to the public deploy contract as well as creates a callback to
 /// `add_voting_contract_callback`.
 ///
@@ -135,6 +441,9 @@ pub fn remove_voter(
 /// * `ctx`: [`ContractContext`], the context of the action call.
 /// * `state`: [`MultiVotingState`], the state before the call.
 /// * `p_id`: [`u64`], the proposal id of the new voting contract.
+/// * `deadline`: [`i64`], the deadline of the vote in UTC millis.
+/// * `options`: [`Vec<OptionSpec>`], the options that can be voted for.
+/// * `configuration`: [`VoteConfiguration`], the configuration of the vote.
 ///
 /// ### Returns:
 /// The new state of type [`MultiVotingState`].
@@ -144,16 +453,119 @@ pub fn add_voting_contract(
     state: MultiVotingState,
     p_id: u64,
     deadline: i64,
+    options: Vec<OptionSpec>,
+    configuration: VoteConfiguration,
 ) -> (MultiVotingState, Vec<EventGroup>) {
     assert_eq!(ctx.sender, state.owner, "Only owner can add contracts");
     if state.voting_contracts.contains_key(&p_id) {
         panic!("Proposal id already exists");
     }
+    configuration.assert_valid(deadline);
+
+    let event_group =
+        deploy_voting_contract(&ctx, &state, p_id, deadline, &options, &configuration);
+
+    let mut new_state = state;
+    let voter_set_version = new_state.snapshot_voter_set();
+    new_state.voting_contracts.insert(
+        p_id,
+        VotingContract {
+            address: None,
+            deadline,
+            options,
+            configuration,
+            deployment_status: DeploymentStatus::Deploying {},
+            retries: 0,
+            voter_set_version,
+            passed: None,
+        },
+    );
+
+    (new_state, vec![event_group])
+}
+
+/// Retries the deployment of the voting contract with given proposal id, after its deployment
+/// failed. The voting contract is deployed with the current eligible voters, and the deadline,
+/// options and configuration given when the voting contract was added. The snapshot of the eligible voters recorded
+/// for the proposal is replaced by the current voters. Only the owner can retry deployments, and
+/// only until the deadline of the vote has passed or the deployment has been retried
+/// [`MAX_DEPLOYMENT_RETRIES`] times.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`MultiVotingState`], the state before the call.
+/// * `p_id`: [`u64`], the proposal id of the failed voting contract.
+///
+/// ### Returns:
+/// The new state of type [`MultiVotingState`].
+#[action]
+pub fn retry_deployment(
+    ctx: ContractContext,
+    state: MultiVotingState,
+    p_id: u64,
+) -> (MultiVotingState, Vec<EventGroup>) {
+    assert_eq!(ctx.sender, state.owner, "Only owner can retry deployments");
+    let voting_contract = state
+        .voting_contracts
+        .get(&p_id)
+        .expect("Proposal id does not exist");
+    assert!(
+        voting_contract.deployment_status == (DeploymentStatus::Failed {}),
+        "Only failed deployments can be retried"
+    );
+    assert!(
+        voting_contract.retries < MAX_DEPLOYMENT_RETRIES,
+        "The deployment has already been retried {MAX_DEPLOYMENT_RETRIES} times"
+    );
+    assert!(
+        ctx.block_production_time < voting_contract.deadline,
+        "The deadline of the vote has passed"
+    );
+
+    let event_group = deploy_voting_contract(
+        &ctx,
+        &state,
+        p_id,
+        voting_contract.deadline,
+        &voting_contract.options,
+        &voting_contract.configuration,
+    );
 
     let mut new_state = state;
+    let voter_set_version = new_state.snapshot_voter_set();
+    let voting_contract = new_state.voting_contracts.get_mut(&p_id).unwrap();
+    voting_contract.deployment_status = DeploymentStatus::Deploying {};
+    voting_contract.retries += 1;
+    voting_contract.voter_set_version = voter_set_version;
+    new_state.prune_voter_set_snapshots();
 
-    new_state.voting_contracts.insert(p_id, None);
+    (new_state, vec![event_group])
+}
 
+/// Creates the event group deploying a voting contract for a proposal, with the eligible voters
+/// as those who can vote. The address of the new voting contract is computed from the original
+/// transaction hash. The deployment calls back to `add_voting_contract_callback`.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`&ContractContext`], the context of the action call.
+/// * `state`: [`&MultiVotingState`], the current state.
+/// * `p_id`: [`u64`], the proposal id of the voting contract.
+/// * `deadline`: [`i64`], the deadline of the vote in UTC millis.
+/// * `options`: [`&Vec<OptionSpec>`], the options that can be voted for.
+/// * `configuration`: [`&VoteConfiguration`], the configuration of the vote.
+///
+/// ### Returns:
+/// The [`EventGroup`] deploying the voting contract.
+fn deploy_voting_contract(
+    ctx: &ContractContext,
+    state: &MultiVotingState,
+    p_id: u64,
+    deadline: i64,
+    options: &Vec<OptionSpec>,
+    configuration: &VoteConfiguration,
+) -> EventGroup {
     let voting_address = Address {
         address_type: AddressType::PublicContract,
         identifier: ctx.original_transaction[12..32].try_into().unwrap(),
@@ -163,12 +575,15 @@ pub fn add_voting_contract(
 
     event_group
         .call(PUB_DEPLOY_ADDRESS, Shortname::from_u32(1))
-        .argument(new_state.voting_contract_wasm.clone())
-        .argument(new_state.voting_contract_abi.clone())
+        .argument(state.voting_contract_wasm.clone())
+        .argument(state.voting_contract_abi.clone())
         .argument(create_voting_init_bytes(
+            ctx.contract_address,
             p_id,
-            &new_state.eligible_voters,
+            &state.eligible_voters.iter().copied().collect(),
             deadline,
+            options,
+            configuration,
         ))
         .done();
 
@@ -179,11 +594,19 @@ pub fn add_voting_contract(
         .argument(voting_address)
         .done();
 
-    (new_state, vec![event_group.build()])
+    event_group.build()
+}
+
+/// Marks the deployment of the voting contract with given proposal id as failed, such that it
+/// can be retried using `retry_deployment`.
+fn mark_deployment_failed(state: &mut MultiVotingState, p_id: u64) {
+    if let Some(voting_contract) = state.voting_contracts.get_mut(&p_id) {
+        voting_contract.deployment_status = DeploymentStatus::Failed {};
+    }
 }
 
 /// Callback for adding a new voting contract. If the deployment was unsuccessful the entry in
-/// `voting_contracts` is deleted. If it instead was successful, an empty invocation is made to
+/// `voting_contracts` is marked as failed. If it instead was successful, an empty invocation is made to
 /// the new contract to check if it really has been deployed. A new callback to
 /// `voting_contract_exists_callback` is also created.
 ///
@@ -207,7 +630,7 @@ pub fn add_voting_contract_callback(
 ) -> (MultiVotingState, Vec<EventGroup>) {
     let mut new_state = state;
     if !callback_ctx.results[0].succeeded {
-        new_state.voting_contracts.remove(&p_id);
+        mark_deployment_failed(&mut new_state, p_id);
         (new_state, vec![])
     } else {
         let mut event_group = EventGroup::builder();
@@ -224,7 +647,8 @@ pub fn add_voting_contract_callback(
 }
 
 /// Callback for checking if a voting contract has been deployed successfully. If it is the
-/// address is inserted into `voting_contracts`. If it is not the entry is deleted instead.
+/// address is stored in the entry in `voting_contracts`. If it is not the entry is marked as
+/// failed instead.
 ///
 /// ### Parameters:
 ///
@@ -246,19 +670,108 @@ pub fn voting_contract_exists_callback(
 ) -> (MultiVotingState, Vec<EventGroup>) {
     let mut new_state = state;
     if !callback_ctx.results[0].succeeded {
-        new_state.voting_contracts.remove(&p_id);
-    } else {
-        new_state
-            .voting_contracts
-            .insert(p_id, Some(voting_address));
+        mark_deployment_failed(&mut new_state, p_id);
+    } else if let Some(voting_contract) = new_state.voting_contracts.get_mut(&p_id) {
+        voting_contract.address = Some(voting_address);
+        voting_contract.deployment_status = DeploymentStatus::Deployed {};
     }
     (new_state, vec![])
 }
 
-fn create_voting_init_bytes(proposal_id: u64, voters: &Vec<Address>, deadline: i64) -> Vec<u8> {
+/// Removes the entries of counted or failed voting contracts whose deadline is before `before`.
+/// Only the owner can prune entries, and only entries of votes that have expired. Entries of
+/// voting contracts that are still being deployed, or that have not yet pushed their result, are
+/// kept, such that the result can still be received. Snapshots of voter sets that are no longer
+/// used by any entry are removed as well.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`MultiVotingState`], the state before the call.
+/// * `before`: [`i64`], the time in UTC millis before which the deadlines of pruned votes are.
+///
+/// ### Returns:
+/// The new state of type [`MultiVotingState`].
+#[action]
+pub fn prune_expired(
+    ctx: ContractContext,
+    state: MultiVotingState,
+    before: i64,
+) -> (MultiVotingState, Vec<EventGroup>) {
+    assert_eq!(ctx.sender, state.owner, "Only owner can prune contracts");
+    assert!(
+        before <= ctx.block_production_time,
+        "Only votes that have expired can be pruned"
+    );
+    let mut new_state = state;
+    new_state.voting_contracts.retain(|_, voting_contract| {
+        voting_contract.deployment_status == (DeploymentStatus::Deploying {})
+            || (voting_contract.deployment_status == (DeploymentStatus::Deployed {})
+                && voting_contract.passed.is_none())
+            || voting_contract.deadline >= before
+    });
+    new_state.prune_voter_set_snapshots();
+    (new_state, vec![])
+}
+
+/// Receives the result of a vote from the voting contract deployed for the proposal, when its
+/// votes are counted. Only the voting contract of the proposal can push its result. A reopened
+/// vote pushes the result of every round, replacing the result of the previous round.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context of the action call.
+/// * `state`: [`MultiVotingState`], the state before the call.
+/// * `p_id`: [`u64`], the proposal id of the voting contract.
+/// * `passed`: [`bool`], whether the proposal passed.
+///
+/// ### Returns:
+/// The new state of type [`MultiVotingState`].
+#[action(shortname = 0x01)]
+pub fn receive_result(
+    ctx: ContractContext,
+    state: MultiVotingState,
+    p_id: u64,
+    passed: bool,
+) -> (MultiVotingState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let voting_contract = new_state
+        .voting_contracts
+        .get_mut(&p_id)
+        .expect("Proposal id does not exist");
+    assert_eq!(
+        voting_contract.address,
+        Some(ctx.sender),
+        "Only the voting contract of the proposal can push its result"
+    );
+    voting_contract.passed = Some(passed);
+    (new_state, vec![])
+}
+
+fn create_voting_init_bytes(
+    parent: Address,
+    proposal_id: u64,
+    voters: &Vec<Address>,
+    deadline: i64,
+    options: &Vec<OptionSpec>,
+    configuration: &VoteConfiguration,
+) -> Vec<u8> {
     let mut bytes: Vec<u8> = vec![0xff, 0xff, 0xff, 0xff, 0x0f];
     WriteRPC::rpc_write_to(&proposal_id, &mut bytes).unwrap();
     WriteRPC::rpc_write_to(voters, &mut bytes).unwrap();
     WriteRPC::rpc_write_to(&deadline, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(options, &mut bytes).unwrap();
+    // Deployed votes have no keeper reward
+    WriteRPC::rpc_write_to(&None::<u8>, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&configuration.quorum, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&configuration.reveal_deadline, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&configuration.notification_contract, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&configuration.registry_address, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&configuration.lock_votes_after_millis, &mut bytes).unwrap();
+    // Deployed votes have a fixed list of voters, rather than staking eligibility
+    WriteRPC::rpc_write_to(&None::<u8>, &mut bytes).unwrap();
+    // Deployed votes open for voting immediately, without a registration phase
+    WriteRPC::rpc_write_to(&None::<u8>, &mut bytes).unwrap();
+    WriteRPC::rpc_write_to(&Some(parent), &mut bytes).unwrap();
     bytes
 }
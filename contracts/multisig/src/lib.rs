@@ -0,0 +1,277 @@
+//! Example smart contract implementing a simple multisig wallet.
+//!
+//! A fixed set of owners control the contract. Any owner can propose an arbitrary call from the
+//! contract to another contract, given by the target address, the shortname of the action and the
+//! raw RPC bytes of its arguments. The proposer confirms the proposal automatically, and the other
+//! owners can confirm it or revoke their confirmation. Once the number of confirmations reaches
+//! the threshold, the call is sent. A callback records whether the call succeeded.
+//!
+//! Since the contract constructs the calls from raw bytes, it can interact with any contract,
+//! for instance transferring tokens owned by the multisig, without knowing the target beforehand.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// The status of a proposal.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteState, CreateTypeSpec)]
+pub enum ProposalStatus {
+    /// The proposal is waiting for confirmations.
+    #[discriminant(0)]
+    Pending {},
+    /// The call has been sent, and is awaiting the callback.
+    #[discriminant(1)]
+    Executing {},
+    /// The call succeeded.
+    #[discriminant(2)]
+    Executed {},
+    /// The call failed.
+    #[discriminant(3)]
+    Failed {},
+}
+
+/// A proposed call from the multisig to another contract.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Proposal {
+    /// The address of the contract to call.
+    pub target: Address,
+    /// The shortname of the action to call.
+    pub shortname: u32,
+    /// The raw RPC bytes of the arguments of the action.
+    pub rpc: Vec<u8>,
+    /// The owners that have confirmed the proposal.
+    pub confirmations: BTreeSet<Address>,
+    /// The status of the proposal.
+    pub status: ProposalStatus,
+}
+
+/// The state of the multisig, which is persisted on-chain.
+#[state]
+pub struct MultisigState {
+    /// The owners of the multisig.
+    pub owners: Vec<Address>,
+    /// The number of confirmations required to execute a proposal.
+    pub threshold: u32,
+    /// The proposals, indexed by their id.
+    pub proposals: BTreeMap<u64, Proposal>,
+    /// The id of the next proposal.
+    pub next_proposal_id: u64,
+}
+
+impl MultisigState {
+    /// Asserts that `address` is one of the owners.
+    fn assert_owner(&self, address: &Address) {
+        assert!(
+            self.owners.contains(address),
+            "Only owners can manage proposals"
+        );
+    }
+
+    /// Returns the pending proposal with the given id, panicking if it doesn't exist or is
+    /// no longer pending.
+    fn pending_proposal(&mut self, proposal_id: u64) -> &mut Proposal {
+        let proposal = self
+            .proposals
+            .get_mut(&proposal_id)
+            .unwrap_or_else(|| panic!("No proposal with id {proposal_id}"));
+        assert!(
+            proposal.status == ProposalStatus::Pending {},
+            "The proposal is no longer pending"
+        );
+        proposal
+    }
+
+    /// Sends the call of the proposal if it has reached the threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `proposal_id` - the id of the proposal.
+    ///
+    /// # Returns
+    ///
+    /// The event groups containing the call, which are empty if the threshold isn't reached.
+    ///
+    fn execute_if_confirmed(&mut self, proposal_id: u64) -> Vec<EventGroup> {
+        let threshold = self.threshold as usize;
+        let proposal = self.pending_proposal(proposal_id);
+        if proposal.confirmations.len() < threshold {
+            return vec![];
+        }
+        proposal.status = ProposalStatus::Executing {};
+
+        let mut event_group = EventGroup::builder();
+        let mut call = event_group.call(proposal.target, Shortname::from_u32(proposal.shortname));
+        for byte in &proposal.rpc {
+            call = call.argument(*byte);
+        }
+        call.done();
+        event_group
+            .with_callback(SHORTNAME_EXECUTE_CALLBACK)
+            .argument(proposal_id)
+            .done();
+        vec![event_group.build()]
+    }
+}
+
+/// Initialize a new multisig.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `owners` - the owners of the multisig.
+/// * `threshold` - the number of confirmations required to execute a proposal.
+///
+/// # Returns
+///
+/// The initial state of the multisig, with no proposals.
+///
+#[init]
+pub fn initialize(ctx: ContractContext, owners: Vec<Address>, threshold: u32) -> MultisigState {
+    let unique_owners: BTreeSet<Address> = owners.iter().copied().collect();
+    assert_eq!(unique_owners.len(), owners.len(), "Duplicate owners");
+    assert!(
+        threshold > 0 && threshold as usize <= owners.len(),
+        "The threshold must be between 1 and the number of owners"
+    );
+    MultisigState {
+        owners,
+        threshold,
+        proposals: BTreeMap::new(),
+        next_proposal_id: 0,
+    }
+}
+
+/// Propose a call from the multisig. The proposer confirms the proposal.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the multisig.
+/// * `target` - the address of the contract to call.
+/// * `shortname` - the shortname of the action to call.
+/// * `rpc` - the raw RPC bytes of the arguments of the action.
+///
+/// # Returns
+///
+/// The updated state with the new proposal, and the call if the threshold is reached.
+///
+#[action(shortname = 0x01)]
+pub fn propose(
+    ctx: ContractContext,
+    state: MultisigState,
+    target: Address,
+    shortname: u32,
+    rpc: Vec<u8>,
+) -> (MultisigState, Vec<EventGroup>) {
+    let mut new_state = state;
+    new_state.assert_owner(&ctx.sender);
+    let proposal_id = new_state.next_proposal_id;
+    new_state.next_proposal_id += 1;
+    new_state.proposals.insert(
+        proposal_id,
+        Proposal {
+            target,
+            shortname,
+            rpc,
+            confirmations: BTreeSet::from([ctx.sender]),
+            status: ProposalStatus::Pending {},
+        },
+    );
+    let events = new_state.execute_if_confirmed(proposal_id);
+    (new_state, events)
+}
+
+/// Confirm a pending proposal. Sends the call if the threshold is reached.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the multisig.
+/// * `proposal_id` - the id of the proposal to confirm.
+///
+/// # Returns
+///
+/// The updated state, and the call if the threshold is reached.
+///
+#[action(shortname = 0x02)]
+pub fn confirm(
+    ctx: ContractContext,
+    state: MultisigState,
+    proposal_id: u64,
+) -> (MultisigState, Vec<EventGroup>) {
+    let mut new_state = state;
+    new_state.assert_owner(&ctx.sender);
+    let proposal = new_state.pending_proposal(proposal_id);
+    assert!(
+        proposal.confirmations.insert(ctx.sender),
+        "The proposal has already been confirmed by the sender"
+    );
+    let events = new_state.execute_if_confirmed(proposal_id);
+    (new_state, events)
+}
+
+/// Revoke a confirmation of a pending proposal.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the multisig.
+/// * `proposal_id` - the id of the proposal to revoke the confirmation of.
+///
+/// # Returns
+///
+/// The updated state.
+///
+#[action(shortname = 0x03)]
+pub fn revoke(ctx: ContractContext, state: MultisigState, proposal_id: u64) -> MultisigState {
+    let mut new_state = state;
+    new_state.assert_owner(&ctx.sender);
+    let proposal = new_state.pending_proposal(proposal_id);
+    assert!(
+        proposal.confirmations.remove(&ctx.sender),
+        "The proposal has not been confirmed by the sender"
+    );
+    new_state
+}
+
+/// Records the outcome of an executed proposal.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the call.
+/// * `state` - the current state of the multisig.
+/// * `proposal_id` - the id of the executed proposal.
+///
+/// # Returns
+///
+/// The updated state.
+///
+#[callback(shortname = 0x10)]
+pub fn execute_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: MultisigState,
+    proposal_id: u64,
+) -> MultisigState {
+    let mut new_state = state;
+    let proposal = new_state.proposals.get_mut(&proposal_id).unwrap();
+    proposal.status = if callback_ctx.success {
+        ProposalStatus::Executed {}
+    } else {
+        ProposalStatus::Failed {}
+    };
+    new_state
+}
@@ -0,0 +1,178 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+
+use crate::{
+    confirm, execute_callback, initialize, propose, revoke, MultisigState, ProposalStatus,
+};
+
+fn create_ctx(sender: Address) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time: 0,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+fn owner(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+fn target() -> Address {
+    create_address(AddressType::PublicContract, 2)
+}
+
+/// A multisig of the owners 1 to 3 with a threshold of 2.
+fn initial_state() -> MultisigState {
+    initialize(create_ctx(owner(1)), (1..=3).map(owner).collect(), 2)
+}
+
+/// A multisig like [`initial_state`] with a proposal by owner 1 calling 0x01 on the target with
+/// the RPC bytes 1, 2 and 3.
+fn state_with_proposal() -> MultisigState {
+    let (state, events) = propose(
+        create_ctx(owner(1)),
+        initial_state(),
+        target(),
+        0x01,
+        vec![1, 2, 3],
+    );
+    assert_eq!(events.len(), 0);
+    state
+}
+
+fn expected_call(proposal_id: u64) -> EventGroup {
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(target(), Shortname::from_u32(0x01))
+        .argument(1u8)
+        .argument(2u8)
+        .argument(3u8)
+        .done();
+    event_group
+        .with_callback(ShortnameCallback::from_u32(0x10))
+        .argument(proposal_id)
+        .done();
+    event_group.build()
+}
+
+#[test]
+fn proposal_is_confirmed_by_proposer() {
+    let state = state_with_proposal();
+    let proposal = state.proposals.get(&0).unwrap();
+    assert_eq!(proposal.confirmations.len(), 1);
+    assert!(proposal.confirmations.contains(&owner(1)));
+    assert!(proposal.status == ProposalStatus::Pending {});
+    assert_eq!(state.next_proposal_id, 1);
+}
+
+#[test]
+fn call_is_sent_when_threshold_is_reached() {
+    let (state, events) = confirm(create_ctx(owner(2)), state_with_proposal(), 0);
+    assert_eq!(events, vec![expected_call(0)]);
+    assert!(state.proposals.get(&0).unwrap().status == ProposalStatus::Executing {});
+}
+
+#[test]
+fn threshold_of_one_executes_on_proposal() {
+    let state = initialize(create_ctx(owner(1)), (1..=3).map(owner).collect(), 1);
+    let (state, events) = propose(create_ctx(owner(2)), state, target(), 0x01, vec![1, 2, 3]);
+    assert_eq!(events, vec![expected_call(0)]);
+    assert!(state.proposals.get(&0).unwrap().status == ProposalStatus::Executing {});
+}
+
+#[test]
+fn revoked_confirmation_does_not_count_towards_threshold() {
+    let state = initialize(create_ctx(owner(1)), (1..=3).map(owner).collect(), 3);
+    let (state, _) = propose(create_ctx(owner(1)), state, target(), 0x01, vec![1, 2, 3]);
+    let (state, _) = confirm(create_ctx(owner(2)), state, 0);
+    let state = revoke(create_ctx(owner(2)), state, 0);
+    let (state, events) = confirm(create_ctx(owner(3)), state, 0);
+    assert_eq!(events.len(), 0);
+    assert_eq!(state.proposals.get(&0).unwrap().confirmations.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "The proposal has already been confirmed by the sender")]
+fn owner_cannot_confirm_twice() {
+    confirm(create_ctx(owner(1)), state_with_proposal(), 0);
+}
+
+#[test]
+#[should_panic(expected = "The proposal has not been confirmed by the sender")]
+fn owner_cannot_revoke_without_confirming() {
+    revoke(create_ctx(owner(2)), state_with_proposal(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Only owners can manage proposals")]
+fn non_owner_cannot_propose() {
+    propose(
+        create_ctx(owner(4)),
+        initial_state(),
+        target(),
+        0x01,
+        vec![],
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only owners can manage proposals")]
+fn non_owner_cannot_confirm() {
+    confirm(create_ctx(owner(4)), state_with_proposal(), 0);
+}
+
+#[test]
+#[should_panic(expected = "The proposal is no longer pending")]
+fn executing_proposal_cannot_be_confirmed() {
+    let (state, _) = confirm(create_ctx(owner(2)), state_with_proposal(), 0);
+    confirm(create_ctx(owner(3)), state, 0);
+}
+
+#[test]
+fn successful_call_marks_proposal_executed() {
+    let (state, _) = confirm(create_ctx(owner(2)), state_with_proposal(), 0);
+    let state = execute_callback(create_ctx(target()), create_callback_ctx(true), state, 0);
+    assert!(state.proposals.get(&0).unwrap().status == ProposalStatus::Executed {});
+}
+
+#[test]
+fn failed_call_marks_proposal_failed() {
+    let (state, _) = confirm(create_ctx(owner(2)), state_with_proposal(), 0);
+    let state = execute_callback(create_ctx(target()), create_callback_ctx(false), state, 0);
+    assert!(state.proposals.get(&0).unwrap().status == ProposalStatus::Failed {});
+}
+
+#[test]
+#[should_panic(expected = "The threshold must be between 1 and the number of owners")]
+fn threshold_cannot_exceed_number_of_owners() {
+    initialize(create_ctx(owner(1)), (1..=3).map(owner).collect(), 4);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate owners")]
+fn owners_must_be_unique() {
+    initialize(create_ctx(owner(1)), vec![owner(1), owner(1)], 1);
+}
@@ -113,6 +113,23 @@ impl NFTContractState {
         }
     }
 
+    /// Get the URI of the metadata of an NFT.
+    /// Throws if no such token exists.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `token_id`: [`u128`] The identifier for an NFT.
+    ///
+    /// ### Returns:
+    ///
+    /// A [`String`] URI of the metadata of the NFT.
+    pub fn token_uri(&self, token_id: u128) -> String {
+        match self.token_uris.get(&token_id) {
+            None => panic!("ERC721: URI query for nonexistent token"),
+            Some(uri) => uri.clone(),
+        }
+    }
+
     /// Helper function to check whether a tokenId exists.
     ///
     /// Tokens start existing when they are minted (`mint`),
@@ -289,6 +306,38 @@ pub fn set_approval_for_all(
     }
 }
 
+/// Transfer ownership of an NFT owned by `ctx.sender` -- THE CALLER IS RESPONSIBLE
+/// TO CONFIRM THAT `to` IS CAPABLE OF RECEIVING NFTS OR ELSE
+/// THEY MAY BE PERMANENTLY LOST
+///
+/// Throws unless `ctx.sender` is the current owner of the NFT.
+/// Throws if `token_id` is not a valid NFT.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`NFTContractState`], the current state of the contract.
+///
+/// * `to`: [`Address`], The new owner
+///
+/// * `token_id`: [`u128`], The NFT to transfer
+///
+/// ### Returns
+///
+/// The new state object of type [`NFTContractState`] with an updated ledger.
+#[action]
+pub fn transfer(
+    ctx: ContractContext,
+    state: NFTContractState,
+    to: Address,
+    token_id: u128,
+) -> NFTContractState {
+    let mut new_state = state;
+    new_state._transfer(ctx.sender, to, token_id);
+    new_state
+}
+
 /// Transfer ownership of an NFT -- THE CALLER IS RESPONSIBLE
 /// TO CONFIRM THAT `to` IS CAPABLE OF RECEIVING NFTS OR ELSE
 /// THEY MAY BE PERMANENTLY LOST
@@ -346,6 +395,8 @@ pub fn transfer_from(
 ///
 /// * `token_id`: [`u128`], The new id for the minted token.
 ///
+/// * `token_uri`: [`String`], The URI of the metadata of the minted token.
+///
 /// ### Returns
 ///
 /// The new state object of type [`NFTContractState`] with an updated ledger.
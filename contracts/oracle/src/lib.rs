@@ -0,0 +1,647 @@
+//! This is an example price oracle aggregator contract.
+//!
+//! Registered reporters push price observations signed by their accounts, and the contract keeps
+//! a bounded history of the most recent observations. The median of the prices in the history is
+//! stored in the `median_price` state field, and is updated on every accepted observation.
+//! Other contracts, such as an auction setting its reserve price or a swap limiting its prices,
+//! can read the median by calling `query_median`, which returns the median as return data.
+//!
+//! A reporter registers with `register_reporter` by staking tokens of the stake token, and can
+//! only report while the stake is at least the minimum stake. A signed observation can be
+//! submitted by anyone with `submit_observation`, allowing a relayer to pay for the transaction
+//! of the reporter. The signature must be made on the hash computed by
+//! [`signed_observation_hash`], using the current nonce of the reporter.
+//!
+//! Once the history holds enough observations, an observation deviating from the median by more
+//! than the maximum deviation is an outlier. Outliers are not added to the history, and the
+//! reporter is slashed a share of its stake. The slashed stake can be collected by the owner of the
+//! contract with `collect_slashed_stake`. Reporters can `withdraw_stake` at any time.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use std::collections::BTreeMap;
+
+use create_type_spec_derive::CreateTypeSpec;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use pbc_traits::WriteRPC;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+use sha2::{Digest, Sha256};
+
+#[cfg(test)]
+mod tests;
+
+/// The least number of observations in the history before outliers are slashed, such that the
+/// median is meaningful.
+pub const MIN_OBSERVATIONS_FOR_SLASHING: usize = 3;
+
+/// A signature made with the secp256k1 key of an account, in the format used for signing
+/// transactions on the blockchain.
+#[derive(ReadWriteRPC, CreateTypeSpec, Clone)]
+pub struct ObservationSignature {
+    /// The recovery id of the signature.
+    pub recovery_id: u8,
+    /// The r value of the signature.
+    pub value_r: [u8; 32],
+    /// The s value of the signature.
+    pub value_s: [u8; 32],
+}
+
+/// A registered reporter.
+///
+/// ### Fields:
+///
+/// * `stake`: [`u128`], the amount of stake tokens staked by the reporter.
+///
+/// * `nonce`: [`u64`], the nonce of the next observation signed by the reporter.
+#[derive(ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct Reporter {
+    stake: u128,
+    nonce: u64,
+}
+
+/// A price observation in the history.
+///
+/// ### Fields:
+///
+/// * `reporter`: [`Address`], the reporter that signed the observation.
+///
+/// * `price`: [`u128`], the observed price.
+///
+/// * `observed_at_millis`: [`i64`], the time in millis UTC at which the price was observed.
+#[derive(ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct Observation {
+    reporter: Address,
+    price: u128,
+    observed_at_millis: i64,
+}
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `owner`: [`Address`], the owner of the contract, who can collect the slashed stake.
+///
+///   * `stake_token`: [`Address`], the address of the token staked by reporters.
+///
+///   * `min_stake`: [`u128`], the minimum stake of a reporter to submit observations.
+///
+///   * `max_deviation_per_mille`: [`u32`], the maximum deviation of an observation from the
+///     median in per mille of the median, before the observation is an outlier.
+///
+///   * `slash_per_mille`: [`u32`], the share of the stake slashed from the reporter of an outlier,
+///     in per mille.
+///
+///   * `history_length`: [`u32`], the maximum number of observations in the history.
+///
+///   * `reporters`: [`BTreeMap<Address, Reporter>`], the registered reporters.
+///
+///   * `observations`: [`Vec<Observation>`], the history of accepted observations, oldest first.
+///
+///   * `median_price`: [`Option<u128>`], the median of the prices in the history, or None if no
+///     observations have been accepted.
+///
+///   * `slashed_stake`: [`u128`], the slashed stake that has not been collected by the owner.
+///
+#[state]
+#[cfg_attr(test, derive(Clone, PartialEq, Eq, Debug))]
+pub struct OracleState {
+    owner: Address,
+    stake_token: Address,
+    min_stake: u128,
+    max_deviation_per_mille: u32,
+    slash_per_mille: u32,
+    history_length: u32,
+    reporters: BTreeMap<Address, Reporter>,
+    observations: Vec<Observation>,
+    median_price: Option<u128>,
+    slashed_stake: u128,
+}
+
+impl OracleState {
+    /// Whether `price` deviates from the current median by more than the maximum deviation. An
+    /// observation is only an outlier once the history holds enough observations.
+    fn is_outlier(&self, price: u128) -> bool {
+        if self.observations.len() < MIN_OBSERVATIONS_FOR_SLASHING {
+            return false;
+        }
+        let Some(median) = self.median_price else {
+            return false;
+        };
+        median.abs_diff(price) > per_mille_of(median, self.max_deviation_per_mille)
+    }
+
+    /// Adds an observation to the history, dropping the oldest observation if the history is full,
+    /// and updates the median.
+    fn add_observation(&mut self, observation: Observation) {
+        if self.observations.len() >= self.history_length as usize {
+            self.observations.remove(0);
+        }
+        self.observations.push(observation);
+        let prices: Vec<u128> = self.observations.iter().map(|obs| obs.price).collect();
+        self.median_price = median(prices);
+    }
+}
+
+/// Computes `per_mille` per mille of `amount`, rounded down, without overflowing.
+fn per_mille_of(amount: u128, per_mille: u32) -> u128 {
+    let per_mille = per_mille as u128;
+    amount / 1000 * per_mille + amount % 1000 * per_mille / 1000
+}
+
+/// Computes the median of `prices`, being the average of the two middle prices rounded down if
+/// there is an even number of prices.
+///
+/// ### Parameters:
+///
+/// * `prices`: [`Vec<u128>`], the prices to compute the median of.
+///
+/// ### Returns
+///
+/// The median of type [`Option<u128>`], or None if there are no prices.
+fn median(mut prices: Vec<u128>) -> Option<u128> {
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_unstable();
+    let middle = prices.len() / 2;
+    if prices.len() % 2 == 1 {
+        Some(prices[middle])
+    } else {
+        let (low, high) = (prices[middle - 1], prices[middle]);
+        Some(low + (high - low) / 2)
+    }
+}
+
+/// Computes the hash signed by a reporter to submit an observation. The hash includes the address
+/// of the oracle contract and the nonce of the reporter, such that the signed observation can only
+/// be submitted once, and cannot be submitted to any other oracle.
+///
+/// ### Parameters:
+///
+/// * `oracle_contract`: [`&Address`], the address of the oracle contract.
+///
+/// * `reporter`: [`&Address`], the reporter signing the observation.
+///
+/// * `price`: [`u128`], the observed price.
+///
+/// * `observed_at_millis`: [`i64`], the time in millis UTC at which the price was observed.
+///
+/// * `nonce`: [`u64`], the current nonce of the reporter.
+///
+/// ### Returns
+///
+/// The hash to be signed by the reporter.
+pub fn signed_observation_hash(
+    oracle_contract: &Address,
+    reporter: &Address,
+    price: u128,
+    observed_at_millis: i64,
+    nonce: u64,
+) -> Hash {
+    let mut preimage: Vec<u8> = vec![];
+    oracle_contract.rpc_write_to(&mut preimage).unwrap();
+    reporter.rpc_write_to(&mut preimage).unwrap();
+    price.rpc_write_to(&mut preimage).unwrap();
+    observed_at_millis.rpc_write_to(&mut preimage).unwrap();
+    nonce.rpc_write_to(&mut preimage).unwrap();
+    Sha256::digest(preimage).into()
+}
+
+/// Recovers the account that made a signature, whose address is the last 20 bytes of the SHA-256
+/// hash of the compressed public key of the account.
+///
+/// ### Parameters:
+///
+/// * `message_hash`: [`&Hash`], the hash that was signed.
+///
+/// * `signature`: [`&ObservationSignature`], the signature of the hash.
+///
+/// ### Returns
+///
+/// The address of the signing account, or None if the signature is invalid.
+pub fn recover_signer(message_hash: &Hash, signature: &ObservationSignature) -> Option<Address> {
+    let mut signature_bytes: Vec<u8> = signature.value_r.to_vec();
+    signature_bytes.extend_from_slice(&signature.value_s);
+    let ecdsa_signature = Signature::from_slice(&signature_bytes).ok()?;
+    let recovery_id = RecoveryId::from_byte(signature.recovery_id)?;
+    let public_key =
+        VerifyingKey::recover_from_prehash(message_hash, &ecdsa_signature, recovery_id).ok()?;
+    let public_key_hash: Hash = Sha256::digest(public_key.to_sec1_bytes()).into();
+    let mut identifier = [0u8; 20];
+    identifier.copy_from_slice(&public_key_hash[12..]);
+    Some(Address {
+        address_type: AddressType::Account,
+        identifier,
+    })
+}
+
+/// Initial function to bootstrap the contract's state.
+///
+/// ### Parameters
+///
+///   * `context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `stake_token`: [`Address`], the address of the token staked by reporters.
+///
+///   * `min_stake`: [`u128`], the minimum stake of a reporter to submit observations.
+///
+///   * `max_deviation_per_mille`: [`u32`], the maximum deviation of an observation from the
+///     median in per mille of the median.
+///
+///   * `slash_per_mille`: [`u32`], the share of the stake slashed for an outlier, in per mille.
+///
+///   * `history_length`: [`u32`], the maximum number of observations in the history.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`] with no reporters or observations.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    stake_token: Address,
+    min_stake: u128,
+    max_deviation_per_mille: u32,
+    slash_per_mille: u32,
+    history_length: u32,
+) -> OracleState {
+    if stake_token.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract staking a non publicContract token");
+    }
+    if slash_per_mille > 1000 {
+        panic!("The slashed share cannot exceed 1000 per mille");
+    }
+    if history_length == 0 {
+        panic!("The history must hold at least one observation");
+    }
+    OracleState {
+        owner: context.sender,
+        stake_token,
+        min_stake,
+        max_deviation_per_mille,
+        slash_per_mille,
+        history_length,
+        reporters: BTreeMap::new(),
+        observations: vec![],
+        median_price: None,
+        slashed_stake: 0,
+    }
+}
+
+/// Action for registering as a reporter, or adding to the stake of a registered reporter. The
+/// function creates a transfer event of the stake from the sender to the contract, and a callback
+/// to `register_reporter_callback`, which adds the stake to the reporter.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of stake tokens to stake.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`OracleState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x01)]
+pub fn register_reporter(
+    context: ContractContext,
+    state: OracleState,
+    amount: u128,
+) -> (OracleState, Vec<EventGroup>) {
+    if amount == 0 {
+        panic!("The stake must be positive");
+    }
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.stake_token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_REGISTER_REPORTER_CALLBACK)
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Callback for registering a reporter. If the transfer was successful the stake is added to the
+/// reporter, registering it if it was not already registered. If the transfer failed the callback
+/// panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `reporter`: [`Address`], the staking reporter.
+///
+/// * `amount`: [`u128`], the amount of stake tokens that was staked.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`].
+///
+#[callback(shortname = 0x10)]
+pub fn register_reporter_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: OracleState,
+    reporter: Address,
+    amount: u128,
+) -> (OracleState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for staking");
+    }
+    state
+        .reporters
+        .entry(reporter)
+        .or_insert(Reporter { stake: 0, nonce: 0 })
+        .stake += amount;
+    (state, vec![])
+}
+
+/// Action for submitting a price observation signed by a reporter. Anyone can submit a signed
+/// observation. Panics if the reporter is not registered, if its stake is below the minimum
+/// stake, if the observation is from the future, or if the signature was not made by the reporter
+/// on the hash computed by [`signed_observation_hash`] with the current nonce of the reporter.
+/// The nonce of the reporter is incremented. If the observation is an outlier, the reporter is
+/// slashed and the observation is discarded. Otherwise it is added to the history and the median
+/// is updated.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `reporter`: [`Address`], the reporter that signed the observation.
+///
+/// * `price`: [`u128`], the observed price.
+///
+/// * `observed_at_millis`: [`i64`], the time in millis UTC at which the price was observed.
+///
+/// * `signature`: [`ObservationSignature`], the signature of the reporter.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`].
+///
+#[action(shortname = 0x02)]
+pub fn submit_observation(
+    context: ContractContext,
+    mut state: OracleState,
+    reporter: Address,
+    price: u128,
+    observed_at_millis: i64,
+    signature: ObservationSignature,
+) -> (OracleState, Vec<EventGroup>) {
+    let min_stake = state.min_stake;
+    let Some(registered) = state.reporters.get(&reporter) else {
+        panic!("Only registered reporters can submit observations");
+    };
+    if registered.stake < min_stake {
+        panic!(
+            "Insufficient stake. Required {min_stake}, but was {}",
+            registered.stake
+        );
+    }
+    if observed_at_millis > context.block_production_time {
+        panic!("Observations cannot be made in the future");
+    }
+    let message_hash = signed_observation_hash(
+        &context.contract_address,
+        &reporter,
+        price,
+        observed_at_millis,
+        registered.nonce,
+    );
+    assert_eq!(
+        recover_signer(&message_hash, &signature),
+        Some(reporter),
+        "The signature was not made by the reporter"
+    );
+
+    let is_outlier = state.is_outlier(price);
+    let slash_per_mille = state.slash_per_mille;
+    let registered = state.reporters.get_mut(&reporter).unwrap();
+    registered.nonce += 1;
+    if is_outlier {
+        let slashed = per_mille_of(registered.stake, slash_per_mille);
+        registered.stake -= slashed;
+        state.slashed_stake += slashed;
+    } else {
+        state.add_observation(Observation {
+            reporter,
+            price,
+            observed_at_millis,
+        });
+    }
+    (state, vec![])
+}
+
+/// Action for reading the median price from another contract. The median is returned as return
+/// data, which the calling contract can read in its callback. Panics if no observations have been
+/// accepted.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`OracleState`] and the event group returning the median.
+///
+#[action(shortname = 0x03)]
+pub fn query_median(
+    _context: ContractContext,
+    state: OracleState,
+) -> (OracleState, Vec<EventGroup>) {
+    let Some(median_price) = state.median_price else {
+        panic!("No observations have been accepted");
+    };
+    let mut event_group = EventGroup::builder();
+    event_group.return_data(median_price);
+    (state, vec![event_group.build()])
+}
+
+/// Action for withdrawing stake. The amount is deducted from the stake of the sender and
+/// transferred to the sender, with a callback to `withdraw_stake_callback` restoring the stake if
+/// the transfer failed. Panics if the sender is not a reporter or has staked less than `amount`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of stake tokens to withdraw.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`] and the event group containing the transfer.
+///
+#[action(shortname = 0x04)]
+pub fn withdraw_stake(
+    context: ContractContext,
+    mut state: OracleState,
+    amount: u128,
+) -> (OracleState, Vec<EventGroup>) {
+    let Some(reporter) = state.reporters.get_mut(&context.sender) else {
+        panic!("Only registered reporters can withdraw stake");
+    };
+    if amount > reporter.stake {
+        panic!(
+            "Cannot withdraw {amount} stake tokens, only {} are staked",
+            reporter.stake
+        );
+    }
+    reporter.stake -= amount;
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.stake_token, token_contract_transfer())
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_STAKE_CALLBACK)
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Callback for withdrawing stake. If the transfer failed the withdrawn amount is added back to the
+/// stake of the reporter.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `reporter`: [`Address`], the withdrawing reporter.
+///
+/// * `amount`: [`u128`], the amount of stake tokens that was withdrawn.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`].
+///
+#[callback(shortname = 0x11)]
+pub fn withdraw_stake_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: OracleState,
+    reporter: Address,
+    amount: u128,
+) -> (OracleState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        if let Some(registered) = state.reporters.get_mut(&reporter) {
+            registered.stake += amount;
+        }
+    }
+    (state, vec![])
+}
+
+/// Action for the owner to collect the stake slashed from reporters of outliers. The slashed
+/// stake is transferred to the owner, with a callback to `collect_slashed_stake_callback`
+/// restoring it if the transfer failed. Panics if the sender is not the owner or there is no
+/// slashed stake.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`] and the event group containing the transfer.
+///
+#[action(shortname = 0x05)]
+pub fn collect_slashed_stake(
+    context: ContractContext,
+    mut state: OracleState,
+) -> (OracleState, Vec<EventGroup>) {
+    if context.sender != state.owner {
+        panic!("Only the owner can collect the slashed stake");
+    }
+    if state.slashed_stake == 0 {
+        panic!("No slashed stake to collect");
+    }
+    let amount = std::mem::take(&mut state.slashed_stake);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.stake_token, token_contract_transfer())
+        .argument(state.owner)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_COLLECT_SLASHED_STAKE_CALLBACK)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Callback for collecting the slashed stake. If the transfer failed the collected amount is
+/// added back to the slashed stake.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`OracleState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of slashed stake that was collected.
+///
+/// ### Returns
+///
+/// The new state object of type [`OracleState`].
+///
+#[callback(shortname = 0x12)]
+pub fn collect_slashed_stake_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: OracleState,
+    amount: u128,
+) -> (OracleState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        state.slashed_stake += amount;
+    }
+    (state, vec![])
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
@@ -0,0 +1,225 @@
+use k256::ecdsa::SigningKey;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    initialize, query_median, register_reporter_callback, signed_observation_hash,
+    submit_observation, withdraw_stake, withdraw_stake_callback, ObservationSignature, OracleState,
+    Reporter, SHORTNAME_WITHDRAW_STAKE_CALLBACK,
+};
+
+fn create_ctx(sender: Address, hours: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time: hours,
+        block_production_time: hours * 3_600_000,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_owner_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 2)
+}
+
+fn get_stake_token_address() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// Creates the signing key of a reporter, deterministically from `id`.
+fn reporter_key(id: u8) -> SigningKey {
+    SigningKey::from_slice(&[id; 32]).unwrap()
+}
+
+/// The account address of the reporter with the signing key `key`.
+fn reporter_address(key: &SigningKey) -> Address {
+    let public_key_hash: Hash = Sha256::digest(key.verifying_key().to_sec1_bytes()).into();
+    let mut identifier = [0u8; 20];
+    identifier.copy_from_slice(&public_key_hash[12..]);
+    Address {
+        address_type: AddressType::Account,
+        identifier,
+    }
+}
+
+fn sign(
+    key: &SigningKey,
+    price: u128,
+    observed_at_millis: i64,
+    nonce: u64,
+) -> ObservationSignature {
+    let message_hash = signed_observation_hash(
+        &get_contract_address(),
+        &reporter_address(key),
+        price,
+        observed_at_millis,
+        nonce,
+    );
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&message_hash).unwrap();
+    let bytes = signature.to_bytes();
+    ObservationSignature {
+        recovery_id: recovery_id.to_byte(),
+        value_r: bytes[..32].try_into().unwrap(),
+        value_s: bytes[32..].try_into().unwrap(),
+    }
+}
+
+/// Initializes an oracle with a minimum stake of 100, a maximum deviation of 10%, a slashed share
+/// of 50% and a history of 4 observations, and registers reporters 1 to 4 with a stake of 1000.
+fn state_with_reporters() -> OracleState {
+    let mut state = initialize(
+        create_ctx(get_owner_address(), 0),
+        get_stake_token_address(),
+        100,
+        100,
+        500,
+        4,
+    );
+    for id in 1..=4 {
+        (state, _) = register_reporter_callback(
+            create_ctx(get_contract_address(), 0),
+            create_callback_ctx(true),
+            state,
+            reporter_address(&reporter_key(id)),
+            1000,
+        );
+    }
+    state
+}
+
+fn submit(state: OracleState, reporter_id: u8, price: u128, nonce: u64) -> OracleState {
+    let key = reporter_key(reporter_id);
+    let signature = sign(&key, price, 3_600_000, nonce);
+    let (state, _) = submit_observation(
+        create_ctx(get_owner_address(), 1),
+        state,
+        reporter_address(&key),
+        price,
+        3_600_000,
+        signature,
+    );
+    state
+}
+
+#[test]
+pub fn test_median_of_recent_observations() {
+    let state = state_with_reporters();
+    let state = submit(state, 1, 100, 0);
+    assert_eq!(state.median_price, Some(100));
+    let state = submit(state, 2, 104, 0);
+    assert_eq!(state.median_price, Some(102));
+    let state = submit(state, 3, 98, 0);
+    let state = submit(state, 4, 105, 0);
+    assert_eq!(state.median_price, Some(102));
+
+    // The history holds 4 observations, so the oldest observation is dropped
+    let state = submit(state, 1, 107, 1);
+    assert_eq!(state.observations.len(), 4);
+    assert_eq!(state.median_price, Some(104));
+
+    let (_, events) = query_median(create_ctx(get_owner_address(), 2), state);
+    let mut expected_event = EventGroup::builder();
+    expected_event.return_data(104u128);
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+pub fn test_outlier_is_slashed() {
+    let state = state_with_reporters();
+    let state = submit(state, 1, 100, 0);
+    let state = submit(state, 2, 102, 0);
+    let state = submit(state, 3, 98, 0);
+    let state = submit(state, 4, 120, 0);
+
+    assert_eq!(state.observations.len(), 3);
+    assert_eq!(state.median_price, Some(100));
+    assert_eq!(state.slashed_stake, 500);
+    assert_eq!(
+        state.reporters.get(&reporter_address(&reporter_key(4))),
+        Some(&Reporter {
+            stake: 500,
+            nonce: 1,
+        })
+    );
+}
+
+#[test]
+#[should_panic(expected = "The signature was not made by the reporter")]
+pub fn test_replayed_observation() {
+    let state = state_with_reporters();
+    let key = reporter_key(1);
+    let signature = sign(&key, 100, 3_600_000, 0);
+    let (state, _) = submit_observation(
+        create_ctx(get_owner_address(), 1),
+        state,
+        reporter_address(&key),
+        100,
+        3_600_000,
+        signature.clone(),
+    );
+    submit_observation(
+        create_ctx(get_owner_address(), 1),
+        state,
+        reporter_address(&key),
+        100,
+        3_600_000,
+        signature,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Insufficient stake. Required 100, but was 50")]
+pub fn test_observation_below_minimum_stake() {
+    let state = state_with_reporters();
+    let reporter = reporter_address(&reporter_key(1));
+    let (state, events) = withdraw_stake(create_ctx(reporter, 1), state, 950);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_stake_token_address(), Shortname::from_u32(1))
+        .argument(reporter)
+        .argument(950u128)
+        .done();
+    expected_event
+        .with_callback(SHORTNAME_WITHDRAW_STAKE_CALLBACK)
+        .argument(reporter)
+        .argument(950u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    let (state, _) = withdraw_stake_callback(
+        create_ctx(get_contract_address(), 1),
+        create_callback_ctx(true),
+        state,
+        reporter,
+        950,
+    );
+    submit(state, 1, 100, 0);
+}
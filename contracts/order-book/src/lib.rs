@@ -0,0 +1,498 @@
+//! This is an example order book exchange contract
+//!
+//! The order book trades a base token for a quote token, where the price of an order is the
+//! amount of quote tokens paid per base token.
+//! Anyone can `place_order` a limit order to buy or sell an amount of base tokens at a price,
+//! which transfers the tokens needed to settle the order from the sender to the contract.
+//! A buy order escrows `price * amount` quote tokens, and a sell order escrows `amount` base
+//! tokens.
+//!
+//! A placed order is matched against the resting orders of the opposite side that cross it, best
+//! price first and oldest order first within the same price. A match is always traded at the
+//! price of the resting order, and a buyer receives any price improvement back in quote tokens.
+//! Any unmatched remainder of the order rests in the book until it is matched by a later order,
+//! or until its owner cancels it with `cancel_order`, which returns the remaining escrow.
+//!
+//! Fills and returned escrow are kept in a claim map, and are transferred to the traders when they
+//! `claim` them, following the pull payment pattern of the claim-map library.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use claim_map::{payout_event_group, Claim, ClaimMap, Payout};
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// The side of an order.
+#[derive(PartialEq, Eq, Clone, Copy, ReadWriteRPC, ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(Debug))]
+pub enum OrderSide {
+    /// The order buys base tokens for quote tokens.
+    #[discriminant(0)]
+    Buy {},
+    /// The order sells base tokens for quote tokens.
+    #[discriminant(1)]
+    Sell {},
+}
+
+/// An order resting in the order book.
+///
+/// ### Fields:
+///
+/// * `owner`: [`Address`], the placer of the order.
+///
+/// * `side`: [`OrderSide`], whether the order buys or sells base tokens.
+///
+/// * `price`: [`u128`], the amount of quote tokens per base token.
+///
+/// * `remaining`: [`u128`], the amount of base tokens that has not yet been filled.
+#[derive(ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct Order {
+    owner: Address,
+    side: OrderSide,
+    price: u128,
+    remaining: u128,
+}
+
+impl Order {
+    /// The tokens escrowed for the remaining amount of the order, in the claim the owner gets if
+    /// the order is cancelled.
+    fn remaining_escrow(&self) -> Fill {
+        match self.side {
+            OrderSide::Buy {} => Fill {
+                base_tokens: 0,
+                quote_tokens: self.price * self.remaining,
+            },
+            OrderSide::Sell {} => Fill {
+                base_tokens: self.remaining,
+                quote_tokens: 0,
+            },
+        }
+    }
+}
+
+/// The claimable tokens of a trader, used by the contracts claim-map.
+///
+/// ### Fields:
+///
+/// * `base_tokens`: [`u128`], the claimable base tokens.
+///
+/// * `quote_tokens`: [`u128`], the claimable quote tokens.
+#[derive(ReadWriteState, CreateTypeSpec)]
+#[cfg_attr(test, derive(PartialEq, Eq, Clone, Debug))]
+pub struct Fill {
+    base_tokens: u128,
+    quote_tokens: u128,
+}
+
+impl Claim for Fill {
+    fn empty() -> Self {
+        Fill {
+            base_tokens: 0,
+            quote_tokens: 0,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.base_tokens += other.base_tokens;
+        self.quote_tokens += other.quote_tokens;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.base_tokens == 0 && self.quote_tokens == 0
+    }
+}
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `base_token`: [`Address`], the address of the token being bought and sold.
+///
+///   * `quote_token`: [`Address`], the address of the token that prices are given in.
+///
+///   * `next_order_id`: [`u64`], the identifier given to the next resting order.
+///
+///   * `orders`: [`BTreeMap<u64, Order>`], the resting orders of the book, by identifier.
+///
+///   * `claims`: [`BTreeMap<Address, Fill>`], the claim map of the fills and returned escrow of
+///     each trader.
+///
+#[state]
+pub struct OrderBookState {
+    base_token: Address,
+    quote_token: Address,
+    next_order_id: u64,
+    orders: BTreeMap<u64, Order>,
+    claims: BTreeMap<Address, Fill>,
+}
+
+impl OrderBookState {
+    /// Creates the payouts of a claim of `base_tokens` and `quote_tokens`, in that order.
+    fn claim_payouts(&self, base_tokens: u128, quote_tokens: u128) -> [Payout; 2] {
+        [
+            Payout {
+                token: self.base_token,
+                amount: base_tokens,
+            },
+            Payout {
+                token: self.quote_token,
+                amount: quote_tokens,
+            },
+        ]
+    }
+
+    /// Finds the identifiers of the resting orders crossing an order of `side` at `price`, in the
+    /// order they should be matched: best price first, and oldest order first within a price.
+    fn crossing_orders(&self, side: OrderSide, price: u128) -> Vec<u64> {
+        let mut crossing: Vec<(u128, u64)> = self
+            .orders
+            .iter()
+            .filter(|(_, order)| match side {
+                OrderSide::Buy {} => order.side == OrderSide::Sell {} && order.price <= price,
+                OrderSide::Sell {} => order.side == OrderSide::Buy {} && order.price >= price,
+            })
+            .map(|(id, order)| (order.price, *id))
+            .collect();
+        match side {
+            OrderSide::Buy {} => crossing.sort(),
+            OrderSide::Sell {} => crossing.sort_by_key(|(price, id)| (Reverse(*price), *id)),
+        }
+        crossing.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Matches an escrowed order against the crossing resting orders, and credits the fills to
+    /// the traders. Returns the unmatched amount of the order.
+    fn match_order(&mut self, owner: Address, side: OrderSide, price: u128, amount: u128) -> u128 {
+        let mut remaining = amount;
+        for id in self.crossing_orders(side, price) {
+            if remaining == 0 {
+                break;
+            }
+            let resting = self.orders.get_mut(&id).unwrap();
+            let filled = remaining.min(resting.remaining);
+            let trade_price = resting.price;
+            let maker = resting.owner;
+            resting.remaining -= filled;
+            if resting.remaining == 0 {
+                self.orders.remove(&id);
+            }
+            remaining -= filled;
+
+            let (buyer, seller) = match side {
+                OrderSide::Buy {} => (owner, maker),
+                OrderSide::Sell {} => (maker, owner),
+            };
+            let price_improvement = match side {
+                OrderSide::Buy {} => (price - trade_price) * filled,
+                OrderSide::Sell {} => 0,
+            };
+            self.claims.credit(
+                buyer,
+                Fill {
+                    base_tokens: filled,
+                    quote_tokens: price_improvement,
+                },
+            );
+            self.claims.credit(
+                seller,
+                Fill {
+                    base_tokens: 0,
+                    quote_tokens: trade_price * filled,
+                },
+            );
+        }
+        remaining
+    }
+}
+
+/// Initial function to bootstrap the contract's state.
+///
+/// ### Parameters
+///
+///   * `context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `base_token`: [`Address`], the address of the token being bought and sold.
+///
+///   * `quote_token`: [`Address`], the address of the token that prices are given in.
+///
+/// ### Returns
+///
+/// The new state object of type [`OrderBookState`] with an empty order book.
+///
+#[init]
+pub fn initialize(
+    _context: ContractContext,
+    base_token: Address,
+    quote_token: Address,
+) -> OrderBookState {
+    if base_token.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract with a non publicContract base token");
+    }
+    if quote_token.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract with a non publicContract quote token");
+    }
+    if base_token == quote_token {
+        panic!("The base and quote tokens must be different");
+    }
+    OrderBookState {
+        base_token,
+        quote_token,
+        next_order_id: 0,
+        orders: BTreeMap::new(),
+        claims: BTreeMap::new(),
+    }
+}
+
+/// Action for placing a limit order. Throws an error if the price or amount is zero.
+/// The function creates a transfer event of the tokens to escrow for the order from the sender to
+/// the contract, and a callback to `place_order_callback`, which matches the order.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OrderBookState`], the current state of the contract.
+///
+/// * `side`: [`OrderSide`], whether to buy or sell base tokens.
+///
+/// * `price`: [`u128`], the limit price in quote tokens per base token.
+///
+/// * `amount`: [`u128`], the amount of base tokens to buy or sell.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`OrderBookState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x01)]
+pub fn place_order(
+    context: ContractContext,
+    state: OrderBookState,
+    side: OrderSide,
+    price: u128,
+    amount: u128,
+) -> (OrderBookState, Vec<EventGroup>) {
+    if price == 0 {
+        panic!("The price of an order must be positive");
+    }
+    if amount == 0 {
+        panic!("The amount of an order must be positive");
+    }
+    let (token, escrow) = match side {
+        OrderSide::Buy {} => (
+            state.quote_token,
+            price
+                .checked_mul(amount)
+                .unwrap_or_else(|| panic!("The value of the order is too large")),
+        ),
+        OrderSide::Sell {} => (state.base_token, amount),
+    };
+
+    let mut e = EventGroup::builder();
+    e.call(token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(escrow)
+        .done();
+    e.with_callback(SHORTNAME_PLACE_ORDER_CALLBACK)
+        .argument(context.sender)
+        .argument(side)
+        .argument(price)
+        .argument(amount)
+        .done();
+    (state, vec![e.build()])
+}
+
+/// Callback for placing a limit order. If the escrow transfer was successful the order is matched
+/// against the crossing resting orders, and any unmatched remainder is added to the order book.
+/// If the transfer failed the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`OrderBookState`], the current state of the contract.
+///
+/// * `owner`: [`Address`], the placer of the order.
+///
+/// * `side`: [`OrderSide`], whether the order buys or sells base tokens.
+///
+/// * `price`: [`u128`], the limit price of the order.
+///
+/// * `amount`: [`u128`], the amount of base tokens of the order.
+///
+/// ### Returns
+///
+/// The new state object of type [`OrderBookState`].
+///
+#[callback(shortname = 0x10)]
+pub fn place_order_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: OrderBookState,
+    owner: Address,
+    side: OrderSide,
+    price: u128,
+    amount: u128,
+) -> (OrderBookState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for the order");
+    }
+    let mut new_state = state;
+    let remaining = new_state.match_order(owner, side, price, amount);
+    if remaining > 0 {
+        let id = new_state.next_order_id;
+        new_state.orders.insert(
+            id,
+            Order {
+                owner,
+                side,
+                price,
+                remaining,
+            },
+        );
+        new_state.next_order_id += 1;
+    }
+    (new_state, vec![])
+}
+
+/// Action for cancelling a resting order. Throws an error if the order does not exist or if the
+/// sender is not the owner of the order. The escrow of the remaining amount of the order is
+/// credited to the owner, who can then `claim` it.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OrderBookState`], the current state of the contract.
+///
+/// * `order_id`: [`u64`], the identifier of the order to cancel.
+///
+/// ### Returns
+///
+/// The new state object of type [`OrderBookState`].
+///
+#[action(shortname = 0x02)]
+pub fn cancel_order(
+    context: ContractContext,
+    state: OrderBookState,
+    order_id: u64,
+) -> (OrderBookState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let order = new_state
+        .orders
+        .remove(&order_id)
+        .unwrap_or_else(|| panic!("No order with the given id exists"));
+    if order.owner != context.sender {
+        panic!("Only the owner of the order can cancel it");
+    }
+    new_state
+        .claims
+        .credit(order.owner, order.remaining_escrow());
+    (new_state, vec![])
+}
+
+/// Action for claiming the fills and returned escrow of the sender. If the sender has anything to
+/// claim, the contract creates transfer events for both the base and quote tokens, and a callback
+/// to `claim_callback`. If the sender has nothing to claim nothing happens.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`OrderBookState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`OrderBookState`] and an event group possibly containing the
+/// transfer events.
+///
+#[action(shortname = 0x03)]
+pub fn claim(context: ContractContext, state: OrderBookState) -> (OrderBookState, Vec<EventGroup>) {
+    let mut new_state = state;
+    match new_state.claims.take_claim(&context.sender) {
+        Some(claimable) if !claimable.is_empty() => {
+            let payouts = new_state.claim_payouts(claimable.base_tokens, claimable.quote_tokens);
+            let mut event_group = payout_event_group(context.sender, &payouts);
+            event_group
+                .with_callback(SHORTNAME_CLAIM_CALLBACK)
+                .argument(context.sender)
+                .argument(claimable.base_tokens)
+                .argument(claimable.quote_tokens)
+                .done();
+            (new_state, vec![event_group.build()])
+        }
+        _ => (new_state, vec![]),
+    }
+}
+
+/// Callback for claiming. The tokens of each transfer that failed are credited back to the
+/// claimant, such that they can be claimed again. Transfers of zero tokens were not made, and have
+/// no result.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`OrderBookState`], the current state of the contract.
+///
+/// * `claimant`: [`Address`], the trader that claimed.
+///
+/// * `base_tokens`: [`u128`], the amount of base tokens that was claimed.
+///
+/// * `quote_tokens`: [`u128`], the amount of quote tokens that was claimed.
+///
+/// ### Returns
+///
+/// The new state object of type [`OrderBookState`].
+///
+#[callback(shortname = 0x11)]
+pub fn claim_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: OrderBookState,
+    claimant: Address,
+    base_tokens: u128,
+    quote_tokens: u128,
+) -> (OrderBookState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let payouts = new_state.claim_payouts(base_tokens, quote_tokens);
+    new_state
+        .claims
+        .restore_if_failed(&callback_ctx, claimant, &payouts, |index, amount| {
+            if index == 0 {
+                Fill {
+                    base_tokens: amount,
+                    quote_tokens: 0,
+                }
+            } else {
+                Fill {
+                    base_tokens: 0,
+                    quote_tokens: amount,
+                }
+            }
+        });
+    (new_state, vec![])
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
@@ -0,0 +1,305 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+
+use crate::{
+    cancel_order, claim, claim_callback, initialize, place_order, place_order_callback, Fill,
+    Order, OrderBookState, OrderSide, SHORTNAME_CLAIM_CALLBACK, SHORTNAME_PLACE_ORDER_CALLBACK,
+};
+
+fn create_ctx(sender: Address, block_time: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time,
+        block_production_time: block_time * 3_600_000,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_buyer_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_seller_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn get_base_token_address() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn get_quote_token_address() -> Address {
+    create_address(AddressType::PublicContract, 5)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+fn init_state() -> OrderBookState {
+    initialize(
+        create_ctx(get_buyer_address(), 0),
+        get_base_token_address(),
+        get_quote_token_address(),
+    )
+}
+
+/// Places an order as if the escrow transfer succeeded.
+fn place(
+    state: OrderBookState,
+    owner: Address,
+    side: OrderSide,
+    price: u128,
+    amount: u128,
+) -> OrderBookState {
+    let (state, _) = place_order_callback(
+        create_ctx(get_contract_address(), 1),
+        create_callback_ctx(true),
+        state,
+        owner,
+        side,
+        price,
+        amount,
+    );
+    state
+}
+
+#[test]
+pub fn test_place_order_escrows_tokens() {
+    let (_, events) = place_order(
+        create_ctx(get_buyer_address(), 1),
+        init_state(),
+        OrderSide::Buy {},
+        3,
+        10,
+    );
+
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_quote_token_address(), Shortname::from_u32(3))
+        .argument(get_buyer_address())
+        .argument(get_contract_address())
+        .argument(30u128)
+        .done();
+    expected_event
+        .with_callback(SHORTNAME_PLACE_ORDER_CALLBACK)
+        .argument(get_buyer_address())
+        .argument(OrderSide::Buy {})
+        .argument(3u128)
+        .argument(10u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+}
+
+#[test]
+pub fn test_crossing_orders_are_matched() {
+    let state = place(
+        init_state(),
+        get_seller_address(),
+        OrderSide::Sell {},
+        3,
+        10,
+    );
+    let other_seller = create_address(AddressType::Account, 6);
+    let state = place(state, other_seller, OrderSide::Sell {}, 2, 4);
+
+    // Buys the 4 tokens at price 2 and 6 tokens at price 3, improving the price of 4 tokens by 2.
+    let state = place(state, get_buyer_address(), OrderSide::Buy {}, 4, 10);
+
+    assert_eq!(
+        state.claims.get(&get_buyer_address()),
+        Some(&Fill {
+            base_tokens: 10,
+            quote_tokens: 14,
+        })
+    );
+    assert_eq!(
+        state.claims.get(&other_seller),
+        Some(&Fill {
+            base_tokens: 0,
+            quote_tokens: 8,
+        })
+    );
+    assert_eq!(
+        state.claims.get(&get_seller_address()),
+        Some(&Fill {
+            base_tokens: 0,
+            quote_tokens: 18,
+        })
+    );
+    assert_eq!(
+        state.orders.get(&0),
+        Some(&Order {
+            owner: get_seller_address(),
+            side: OrderSide::Sell {},
+            price: 3,
+            remaining: 4,
+        })
+    );
+    assert_eq!(state.orders.len(), 1);
+}
+
+#[test]
+pub fn test_unmatched_remainder_rests() {
+    let state = place(init_state(), get_buyer_address(), OrderSide::Buy {}, 5, 3);
+    let state = place(state, get_seller_address(), OrderSide::Sell {}, 6, 2);
+    let state = place(state, get_seller_address(), OrderSide::Sell {}, 4, 5);
+
+    // The sell at 4 takes the buy at 5 for 3 tokens, and the remaining 2 tokens rest.
+    assert_eq!(
+        state.claims.get(&get_seller_address()),
+        Some(&Fill {
+            base_tokens: 0,
+            quote_tokens: 15,
+        })
+    );
+    assert_eq!(
+        state.orders.get(&2),
+        Some(&Order {
+            owner: get_seller_address(),
+            side: OrderSide::Sell {},
+            price: 4,
+            remaining: 2,
+        })
+    );
+    assert!(!state.orders.contains_key(&0));
+    assert_eq!(state.orders.len(), 2);
+}
+
+#[test]
+pub fn test_cancel_and_claim_returns_escrow() {
+    let state = place(init_state(), get_buyer_address(), OrderSide::Buy {}, 3, 10);
+    let (state, _) = cancel_order(create_ctx(get_buyer_address(), 2), state, 0);
+    assert!(state.orders.is_empty());
+
+    let (state, events) = claim(create_ctx(get_buyer_address(), 3), state);
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_quote_token_address(), Shortname::from_u32(1))
+        .argument(get_buyer_address())
+        .argument(30u128)
+        .done();
+    expected_event
+        .with_callback(SHORTNAME_CLAIM_CALLBACK)
+        .argument(get_buyer_address())
+        .argument(0u128)
+        .argument(30u128)
+        .done();
+    assert_eq!(events, vec![expected_event.build()]);
+
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 3),
+        create_callback_ctx(false),
+        state,
+        get_buyer_address(),
+        0,
+        30,
+    );
+    assert_eq!(
+        state.claims.get(&get_buyer_address()),
+        Some(&Fill {
+            base_tokens: 0,
+            quote_tokens: 30,
+        })
+    );
+}
+
+fn claim_results(base_succeeded: bool, quote_succeeded: bool) -> CallbackContext {
+    CallbackContext {
+        success: base_succeeded && quote_succeeded,
+        results: vec![
+            ExecutionResult {
+                succeeded: base_succeeded,
+                return_data: vec![],
+            },
+            ExecutionResult {
+                succeeded: quote_succeeded,
+                return_data: vec![],
+            },
+        ],
+    }
+}
+
+#[test]
+pub fn test_claim_callback_restores_only_failed_transfers() {
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 3),
+        claim_results(true, false),
+        init_state(),
+        get_buyer_address(),
+        5,
+        30,
+    );
+    assert_eq!(
+        state.claims.get(&get_buyer_address()),
+        Some(&Fill {
+            base_tokens: 0,
+            quote_tokens: 30,
+        })
+    );
+
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 3),
+        claim_results(false, true),
+        init_state(),
+        get_buyer_address(),
+        5,
+        30,
+    );
+    assert_eq!(
+        state.claims.get(&get_buyer_address()),
+        Some(&Fill {
+            base_tokens: 5,
+            quote_tokens: 0,
+        })
+    );
+}
+
+#[test]
+pub fn test_claim_callback_succeeded() {
+    let (state, _) = claim_callback(
+        create_ctx(get_contract_address(), 3),
+        claim_results(true, true),
+        init_state(),
+        get_buyer_address(),
+        5,
+        30,
+    );
+    assert_eq!(state.claims.get(&get_buyer_address()), None);
+}
+
+#[test]
+#[should_panic(expected = "Only the owner of the order can cancel it")]
+pub fn test_cancel_order_not_owner() {
+    let state = place(
+        init_state(),
+        get_seller_address(),
+        OrderSide::Sell {},
+        3,
+        10,
+    );
+    cancel_order(create_ctx(get_buyer_address(), 2), state, 0);
+}
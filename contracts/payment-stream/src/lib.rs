@@ -0,0 +1,373 @@
+//! This is an example Payment Stream contract
+//!
+//! The payment stream contract streams tokens from a payer to a recipient at a fixed rate per
+//! second, between a start time and a stop time.
+//! The contract handles a specific token type.
+//! The payer funds the stream by calling `deposit`, which transfers the full amount of the stream,
+//! being the rate multiplied by the duration of the stream in seconds, into the contract.
+//! From the start time the tokens are streamed to the recipient every second, until all tokens
+//! are streamed at the stop time. Either party can call `withdraw_streamed` at any time to
+//! transfer the tokens streamed so far to the recipient.
+//! The payer can `cancel` the stream before it stops, which splits the balance of the contract
+//! according to the elapsed time: the recipient receives the streamed tokens that have not been
+//! withdrawn, and the payer receives the tokens that have not been streamed.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+
+#[cfg(test)]
+mod tests;
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `payer`: [`Address`], the payer funding the stream.
+///
+///   * `recipient`: [`Address`], the receiver of the streamed tokens.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `rate_per_second`: [`u128`], the amount of tokens streamed to the recipient per second.
+///
+///   * `start_time_millis`: [`i64`], the time in milliseconds at which the stream starts.
+///
+///   * `stop_time_millis`: [`i64`], the time in milliseconds at which the stream stops. If the
+///     stream is cancelled this is the time of cancellation.
+///
+///   * `total_amount`: [`u128`], the total amount of tokens of the stream.
+///
+///   * `withdrawn_amount`: [`u128`], the amount of tokens withdrawn to the recipient so far.
+///
+///   * `funded`: [`bool`], whether the payer has deposited the tokens of the stream.
+///
+///   * `cancelled`: [`bool`], whether the payer has cancelled the stream.
+///
+#[state]
+pub struct PaymentStreamState {
+    payer: Address,
+    recipient: Address,
+    token_type: Address,
+    rate_per_second: u128,
+    start_time_millis: i64,
+    stop_time_millis: i64,
+    total_amount: u128,
+    withdrawn_amount: u128,
+    funded: bool,
+    cancelled: bool,
+}
+
+impl PaymentStreamState {
+    /// Computes the amount of tokens streamed at the given time. Tokens are streamed once every
+    /// whole second that has elapsed since the start of the stream.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `time_millis`: [`i64`], the time to compute the streamed amount at.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of streamed tokens, including the tokens already withdrawn.
+    fn streamed_amount(&self, time_millis: i64) -> u128 {
+        let effective_time_millis = time_millis.min(self.stop_time_millis);
+        if effective_time_millis <= self.start_time_millis {
+            return 0;
+        }
+        let elapsed_seconds = ((effective_time_millis - self.start_time_millis) / 1000) as u128;
+        self.rate_per_second * elapsed_seconds
+    }
+
+    /// Computes the amount of streamed tokens that have not been withdrawn yet.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `time_millis`: [`i64`], the time to compute the withdrawable amount at.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of tokens that can be withdrawn to the recipient.
+    fn withdrawable_amount(&self, time_millis: i64) -> u128 {
+        self.streamed_amount(time_millis) - self.withdrawn_amount
+    }
+}
+
+/// Initial function to bootstrap the contract's state. The payer of the stream is the sender of
+/// the initialization.
+///
+/// ### Parameters
+///
+///   * `context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `recipient`: [`Address`] - the receiver of the streamed tokens.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `rate_per_second`: [`u128`], the amount of tokens streamed to the recipient per second.
+///
+///   * `start_time_millis`: [`i64`], the time in milliseconds at which the stream starts.
+///
+///   * `stop_time_millis`: [`i64`], the time in milliseconds at which the stream stops.
+///
+/// ### Returns
+///
+/// The new state object of type [`PaymentStreamState`] with no tokens deposited.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    recipient: Address,
+    token_type: Address,
+    rate_per_second: u128,
+    start_time_millis: i64,
+    stop_time_millis: i64,
+) -> PaymentStreamState {
+    if token_type.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract streaming a non publicContract token");
+    }
+    if rate_per_second == 0 {
+        panic!("The rate of the stream must be positive");
+    }
+    let duration_seconds = (stop_time_millis - start_time_millis) / 1000;
+    if duration_seconds <= 0 {
+        panic!("The stream must last at least one second");
+    }
+    let total_amount = rate_per_second
+        .checked_mul(duration_seconds as u128)
+        .expect("The total amount of the stream is too large");
+    PaymentStreamState {
+        payer: context.sender,
+        recipient,
+        token_type,
+        rate_per_second,
+        start_time_millis,
+        stop_time_millis,
+        total_amount,
+        withdrawn_amount: 0,
+        funded: false,
+        cancelled: false,
+    }
+}
+
+/// Action for the payer to fund the stream with its total amount of tokens.
+/// Throws an error if not called by the `payer`, if the stream is already funded or if it has
+/// been cancelled.
+/// The function creates a transfer event of tokens from the `payer` to the contract, and
+/// a callback to `deposit_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`PaymentStreamState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`PaymentStreamState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x01)]
+pub fn deposit(
+    context: ContractContext,
+    state: PaymentStreamState,
+) -> (PaymentStreamState, Vec<EventGroup>) {
+    if context.sender != state.payer {
+        panic!("Deposit can only be called by the payer");
+    }
+    if state.funded {
+        panic!("The stream has already been funded");
+    }
+    if state.cancelled {
+        panic!("Cannot deposit tokens after the stream has been cancelled");
+    }
+    // Create transfer event of tokens from the payer to the contract
+    // transfer should callback to deposit_callback
+    let mut e = EventGroup::builder();
+    e.call(state.token_type, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(state.total_amount)
+        .done();
+    e.with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(state.total_amount)
+        .done();
+    let event_group: EventGroup = e.build();
+
+    (state, vec![event_group])
+}
+
+/// Callback for depositing tokens. If the transfer was successful the stream is funded, unless
+/// the stream has been funded or cancelled in the meantime, in which case the deposit is returned
+/// to the payer. If the transfer failed the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`PaymentStreamState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of deposited tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`PaymentStreamState`].
+///
+#[callback(shortname = 0x02)]
+pub fn deposit_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: PaymentStreamState,
+    amount: u128,
+) -> (PaymentStreamState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for deposit");
+    }
+    if state.funded || state.cancelled {
+        let event_group = transfer_event(state.token_type, state.payer, amount);
+        return (state, vec![event_group]);
+    }
+    let mut new_state = state;
+    new_state.funded = true;
+    (new_state, vec![])
+}
+
+/// Action for withdrawing the tokens streamed so far to the recipient. Can be called by both the
+/// `payer` and the `recipient`, but the tokens are always transferred to the recipient.
+/// Throws an error if the stream has not been funded or if no tokens can be withdrawn.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`PaymentStreamState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`PaymentStreamState`] and the event group containing the
+/// transfer event.
+///
+#[action(shortname = 0x03)]
+pub fn withdraw_streamed(
+    context: ContractContext,
+    state: PaymentStreamState,
+) -> (PaymentStreamState, Vec<EventGroup>) {
+    if context.sender != state.payer && context.sender != state.recipient {
+        panic!("Only the payer and the recipient can withdraw streamed tokens");
+    }
+    if !state.funded {
+        panic!("The stream has not been funded");
+    }
+    let amount = state.withdrawable_amount(context.block_production_time);
+    if amount == 0 {
+        panic!("No streamed tokens to withdraw");
+    }
+
+    let event_group = transfer_event(state.token_type, state.recipient, amount);
+
+    let mut new_state = state;
+    new_state.withdrawn_amount += amount;
+    (new_state, vec![event_group])
+}
+
+/// Action for the payer to cancel the stream before it stops.
+/// Throws an error if not called by the `payer`, if the stream has already stopped or if it has
+/// already been cancelled.
+/// The balance of a funded stream is split according to the elapsed time. The streamed tokens
+/// that have not been withdrawn are transferred to the recipient, and the tokens that have not
+/// been streamed are transferred back to the payer.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`PaymentStreamState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`PaymentStreamState`] and event groups possibly containing
+/// transfer events to the recipient and the payer.
+///
+#[action(shortname = 0x04)]
+pub fn cancel(
+    context: ContractContext,
+    state: PaymentStreamState,
+) -> (PaymentStreamState, Vec<EventGroup>) {
+    if context.sender != state.payer {
+        panic!("Only the payer can cancel the stream");
+    }
+    if state.cancelled {
+        panic!("The stream has already been cancelled");
+    }
+    let now = context.block_production_time;
+    if now >= state.stop_time_millis {
+        panic!("Cannot cancel the stream after it has stopped");
+    }
+
+    let mut new_state = state;
+    new_state.cancelled = true;
+    if !new_state.funded {
+        return (new_state, vec![]);
+    }
+
+    let streamed_amount = new_state.streamed_amount(now);
+    let recipient_amount = streamed_amount - new_state.withdrawn_amount;
+    let payer_amount = new_state.total_amount - streamed_amount;
+    new_state.total_amount = streamed_amount;
+    new_state.withdrawn_amount = streamed_amount;
+    new_state.stop_time_millis = now.max(new_state.start_time_millis);
+
+    let mut events = vec![];
+    if recipient_amount > 0 {
+        events.push(transfer_event(
+            new_state.token_type,
+            new_state.recipient,
+            recipient_amount,
+        ));
+    }
+    if payer_amount > 0 {
+        events.push(transfer_event(
+            new_state.token_type,
+            new_state.payer,
+            payer_amount,
+        ));
+    }
+    (new_state, events)
+}
+
+/// Creates an event group transferring tokens from the contract.
+///
+/// ### Parameters:
+///
+/// * `token_type`: [`Address`], the address of the token to transfer.
+///
+/// * `to`: [`Address`], the receiver of the tokens.
+///
+/// * `amount`: [`u128`], the amount of tokens to transfer.
+///
+/// ### Returns
+///
+/// The event group containing the transfer event.
+fn transfer_event(token_type: Address, to: Address, amount: u128) -> EventGroup {
+    let mut e = EventGroup::builder();
+    e.call(token_type, token_contract_transfer())
+        .argument(to)
+        .argument(amount)
+        .done();
+    e.build()
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
@@ -0,0 +1,154 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+
+use crate::{cancel, deposit_callback, initialize, withdraw_streamed, PaymentStreamState};
+
+const SECOND_MILLIS: i64 = 1000;
+
+fn create_ctx(sender: Address, seconds: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time: seconds,
+        block_production_time: seconds * SECOND_MILLIS,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_payer_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_recipient_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn get_token_address() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// Creates a funded stream of 10 tokens per second, starting at second 100 and stopping at second
+/// 200.
+fn funded_stream() -> PaymentStreamState {
+    let state = initialize(
+        create_ctx(get_payer_address(), 0),
+        get_recipient_address(),
+        get_token_address(),
+        10,
+        100 * SECOND_MILLIS,
+        200 * SECOND_MILLIS,
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_payer_address(), 0),
+        create_callback_ctx(true),
+        state,
+        1000,
+    );
+    state
+}
+
+fn expected_transfer(to: Address, amount: u128) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(1))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event.build()
+}
+
+#[test]
+pub fn test_streamed_amount() {
+    let state = funded_stream();
+    assert_eq!(state.total_amount, 1000);
+    assert_eq!(state.streamed_amount(50 * SECOND_MILLIS), 0);
+    assert_eq!(state.streamed_amount(100 * SECOND_MILLIS), 0);
+    assert_eq!(state.streamed_amount(100 * SECOND_MILLIS + 999), 0);
+    assert_eq!(state.streamed_amount(125 * SECOND_MILLIS), 250);
+    assert_eq!(state.streamed_amount(200 * SECOND_MILLIS), 1000);
+    assert_eq!(state.streamed_amount(300 * SECOND_MILLIS), 1000);
+}
+
+#[test]
+pub fn test_withdraw_streamed() {
+    let state = funded_stream();
+    let (state, events) = withdraw_streamed(create_ctx(get_recipient_address(), 130), state);
+    assert_eq!(state.withdrawn_amount, 300);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_recipient_address(), 300)]
+    );
+
+    // The payer can also withdraw, but the tokens go to the recipient
+    let (state, events) = withdraw_streamed(create_ctx(get_payer_address(), 250), state);
+    assert_eq!(state.withdrawn_amount, 1000);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_recipient_address(), 700)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "No streamed tokens to withdraw")]
+pub fn test_withdraw_streamed_before_start() {
+    let state = funded_stream();
+    withdraw_streamed(create_ctx(get_recipient_address(), 50), state);
+}
+
+#[test]
+pub fn test_cancel() {
+    let state = funded_stream();
+    let (state, _) = withdraw_streamed(create_ctx(get_recipient_address(), 120), state);
+    let (state, events) = cancel(create_ctx(get_payer_address(), 160), state);
+    assert!(state.cancelled);
+    assert_eq!(state.stop_time_millis, 160 * SECOND_MILLIS);
+    assert_eq!(
+        events,
+        vec![
+            expected_transfer(get_recipient_address(), 400),
+            expected_transfer(get_payer_address(), 400),
+        ]
+    );
+    assert_eq!(state.withdrawable_amount(300 * SECOND_MILLIS), 0);
+}
+
+#[test]
+#[should_panic(expected = "Only the payer can cancel the stream")]
+pub fn test_cancel_not_payer() {
+    let state = funded_stream();
+    cancel(create_ctx(get_recipient_address(), 160), state);
+}
+
+#[test]
+#[should_panic(expected = "Cannot cancel the stream after it has stopped")]
+pub fn test_cancel_after_stop() {
+    let state = funded_stream();
+    cancel(create_ctx(get_payer_address(), 200), state);
+}
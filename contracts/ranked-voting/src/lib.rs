@@ -0,0 +1,299 @@
+//! Example smart contract implementing a ranked-choice vote among a fixed list of eligible voters,
+//! counted by instant runoff.
+//!
+//! How it works
+//! * The owner of the proposal deploys the contract and initializes it with the options that can
+//!   be ranked. Each option has an id, and the hash of its label, like in the voting contract.
+//! * Until the deadline, eligible voters submit a ballot ranking the options in order of
+//!   preference. A ballot does not have to rank every option, but can rank each option only once.
+//!   Voters can replace their ballot until the deadline.
+//! * After the deadline anyone can count the votes. Counting proceeds in rounds. In each round
+//!   every ballot counts for its highest ranked option which has not been eliminated, and ballots
+//!   ranking no remaining option are exhausted. If an option has a majority of the votes counted
+//!   in the round, it wins. Otherwise the option with the fewest votes is eliminated, and the next
+//!   round is counted.
+//! * If several options share the fewest votes, the one listed last among the options is
+//!   eliminated. If all remaining options have the same number of votes, no option can be
+//!   eliminated fairly, and the vote ends without a winner.
+//! * The tally of every round is stored in the result, such that the elimination can be audited.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::Address;
+use pbc_contract_common::context::ContractContext;
+use pbc_contract_common::Hash;
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// An option that can be ranked.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct OptionSpec {
+    /// The id of the option, referenced by ballots and results.
+    pub id: u32,
+    /// The hash of the label of the option.
+    pub label_hash: Hash,
+}
+
+/// The number of votes counted for an option in a round.
+#[derive(ReadWriteState, CreateTypeSpec, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct OptionTally {
+    /// The id of the option.
+    pub option_id: u32,
+    /// The number of ballots counting for the option in the round.
+    pub votes: u32,
+}
+
+/// A round of the instant runoff count.
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct RunoffRound {
+    /// The number of votes for each remaining option, in the order of the options.
+    pub tally: Vec<OptionTally>,
+    /// The id of the option eliminated after the round, or None if the count ended in the round.
+    pub eliminated: Option<u32>,
+}
+
+/// The result of a counted vote.
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct RankedVoteResult {
+    /// The rounds of the count, in the order they were counted.
+    pub rounds: Vec<RunoffRound>,
+    /// The id of the option with a majority in the final round,
+    /// or None if the remaining options were tied.
+    pub winning_option: Option<u32>,
+}
+
+/// The state of the vote, which is persisted on-chain.
+#[state]
+pub struct RankedVoteState {
+    /// Identification of the proposal being voted for.
+    pub proposal_id: u64,
+    /// The list of eligible voters.
+    pub voters: Vec<Address>,
+    /// The deadline of the vote in UTC millis
+    /// (milliseconds after 1970-01-01 00:00:00 UTC)
+    pub deadline_utc_millis: i64,
+    /// The options that can be ranked.
+    pub options: Vec<OptionSpec>,
+    /// The ballots submitted by the voters.
+    /// Maps a voter to the ids of the options ranked, from most to least preferred.
+    pub ballots: BTreeMap<Address, Vec<u32>>,
+    /// The result of the vote.
+    /// None until the votes has been counted.
+    pub result: Option<RankedVoteResult>,
+}
+
+impl RankedVoteState {
+    /// Asserts that `ranking` is a valid ballot, ranking at least one option, and only known
+    /// options at most once each.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranking` - the ids of the ranked options, from most to least preferred.
+    ///
+    fn assert_valid_ranking(&self, ranking: &[u32]) {
+        assert!(
+            !ranking.is_empty(),
+            "The ranking must contain at least one option"
+        );
+        let mut ranked: BTreeSet<u32> = BTreeSet::new();
+        for option_id in ranking {
+            assert!(
+                self.options.iter().any(|option| option.id == *option_id),
+                "Unknown option id {option_id}"
+            );
+            assert!(
+                ranked.insert(*option_id),
+                "Option id {option_id} is ranked more than once"
+            );
+        }
+    }
+}
+
+/// Counts `ballots` by instant runoff among `options`.
+///
+/// In each round, every ballot counts for its highest ranked remaining option. The count ends
+/// when an option has more than half of the votes counted in the round, or when all remaining
+/// options have the same number of votes. Otherwise the option with the fewest votes is
+/// eliminated, choosing the one listed last if several options share the fewest votes.
+///
+/// # Arguments
+///
+/// * `options` - the ids of the options, in the order of the options.
+/// * `ballots` - the rankings of the voters, from most to least preferred.
+///
+/// # Returns
+///
+/// The rounds of the count, and the id of the winning option, if any.
+///
+pub fn instant_runoff(options: &[u32], ballots: &[Vec<u32>]) -> (Vec<RunoffRound>, Option<u32>) {
+    let mut remaining: Vec<u32> = options.to_vec();
+    let mut rounds: Vec<RunoffRound> = vec![];
+    loop {
+        let tally: Vec<OptionTally> = remaining
+            .iter()
+            .map(|option_id| OptionTally {
+                option_id: *option_id,
+                votes: ballots
+                    .iter()
+                    .filter(|ranking| top_choice(ranking, &remaining) == Some(*option_id))
+                    .count() as u32,
+            })
+            .collect();
+        let counted: u64 = tally.iter().map(|option| option.votes as u64).sum();
+        let majority_option = tally
+            .iter()
+            .find(|option| option.votes as u64 * 2 > counted)
+            .map(|option| option.option_id);
+        let fewest_votes = tally.iter().map(|option| option.votes).min().unwrap_or(0);
+        if majority_option.is_some() || tally.iter().all(|option| option.votes == fewest_votes) {
+            rounds.push(RunoffRound {
+                tally,
+                eliminated: None,
+            });
+            return (rounds, majority_option);
+        }
+        let eliminated = tally
+            .iter()
+            .rev()
+            .find(|option| option.votes == fewest_votes)
+            .map(|option| option.option_id)
+            .unwrap();
+        remaining.retain(|option_id| *option_id != eliminated);
+        rounds.push(RunoffRound {
+            tally,
+            eliminated: Some(eliminated),
+        });
+    }
+}
+
+/// Finds the highest ranked option of `ranking` which is among the `remaining` options.
+///
+/// # Arguments
+///
+/// * `ranking` - the ids of the ranked options, from most to least preferred.
+/// * `remaining` - the ids of the options not yet eliminated.
+///
+/// # Returns
+///
+/// The id of the highest ranked remaining option, or None if the ballot is exhausted.
+///
+fn top_choice(ranking: &[u32], remaining: &[u32]) -> Option<u32> {
+    ranking
+        .iter()
+        .find(|option_id| remaining.contains(option_id))
+        .copied()
+}
+
+/// Initialize a new ranked-choice vote for a proposal.
+///
+/// # Arguments
+///
+/// * `_ctx` - the contract context containing information about the sender and the blockchain.
+/// * `proposal_id` - the id of the proposal.
+/// * `voters` - the list of eligible voters.
+/// * `deadline_utc_millis` - deadline of the vote in UTC millis.
+/// * `options` - the options that can be ranked. Option ids must be unique.
+///
+/// # Returns
+///
+/// The initial state of the vote.
+///
+#[init]
+pub fn initialize(
+    _ctx: ContractContext,
+    proposal_id: u64,
+    voters: Vec<Address>,
+    deadline_utc_millis: i64,
+    options: Vec<OptionSpec>,
+) -> RankedVoteState {
+    assert_ne!(voters.len(), 0, "Voters are required");
+    assert!(options.len() >= 2, "At least two options are required");
+    let unique_options: BTreeSet<u32> = options.iter().map(|option| option.id).collect();
+    assert_eq!(
+        options.len(),
+        unique_options.len(),
+        "All option ids must be unique"
+    );
+    let unique_voters: BTreeSet<Address> = voters.iter().cloned().collect();
+    assert_eq!(
+        voters.len(),
+        unique_voters.len(),
+        "All voters must be unique"
+    );
+    RankedVoteState {
+        proposal_id,
+        voters,
+        deadline_utc_millis,
+        options,
+        ballots: BTreeMap::new(),
+        result: None,
+    }
+}
+
+/// Submit a ballot ranking the options.
+/// The ballot is submitted by the sender of the action, and replaces any earlier ballot of the
+/// sender. Ballots can be submitted until the deadline.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `ranking` - the ids of the ranked options, from most to least preferred.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the submitted ballot.
+///
+#[action(shortname = 0x01)]
+pub fn vote(ctx: ContractContext, state: RankedVoteState, ranking: Vec<u32>) -> RankedVoteState {
+    assert!(
+        state.result.is_none() && ctx.block_production_time < state.deadline_utc_millis,
+        "The deadline has passed"
+    );
+    assert!(state.voters.contains(&ctx.sender), "Not an eligible voter");
+    state.assert_valid_ranking(&ranking);
+    let mut new_state = state;
+    new_state.ballots.insert(ctx.sender, ranking);
+    new_state
+}
+
+/// Count the ballots by instant runoff, see [`instant_runoff`].
+/// Anyone can count the ballots once the deadline has passed.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The updated vote state with the result of the count.
+///
+#[action(shortname = 0x02)]
+pub fn count(ctx: ContractContext, state: RankedVoteState) -> RankedVoteState {
+    assert!(
+        state.result.is_none(),
+        "The votes have already been counted"
+    );
+    assert!(
+        ctx.block_production_time >= state.deadline_utc_millis,
+        "The deadline has not yet passed"
+    );
+    let options: Vec<u32> = state.options.iter().map(|option| option.id).collect();
+    let ballots: Vec<Vec<u32>> = state.ballots.values().cloned().collect();
+    let (rounds, winning_option) = instant_runoff(&options, &ballots);
+    let mut new_state = state;
+    new_state.result = Some(RankedVoteResult {
+        rounds,
+        winning_option,
+    });
+    new_state
+}
@@ -0,0 +1,143 @@
+use pbc_contract_common::address::{Address, AddressType};
+use pbc_contract_common::context::ContractContext;
+
+use crate::{count, initialize, instant_runoff, vote, OptionSpec, OptionTally, RankedVoteState};
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn voter(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+/// A vote among five voters on the options 1, 2 and 3, with the deadline at 100.
+fn initial_state() -> RankedVoteState {
+    let options = (1..=3)
+        .map(|id| OptionSpec {
+            id,
+            label_hash: [id as u8; 32],
+        })
+        .collect();
+    initialize(
+        create_ctx(voter(100), 0),
+        1,
+        (1..=5).map(voter).collect(),
+        100,
+        options,
+    )
+}
+
+fn tally(votes: &[(u32, u32)]) -> Vec<OptionTally> {
+    votes
+        .iter()
+        .map(|(option_id, votes)| OptionTally {
+            option_id: *option_id,
+            votes: *votes,
+        })
+        .collect()
+}
+
+#[test]
+fn first_round_majority_wins() {
+    let ballots = vec![vec![1, 2], vec![1], vec![2, 1]];
+    let (rounds, winner) = instant_runoff(&[1, 2, 3], &ballots);
+    assert_eq!(winner, Some(1));
+    assert_eq!(rounds.len(), 1);
+    assert_eq!(rounds[0].tally, tally(&[(1, 2), (2, 1), (3, 0)]));
+    assert_eq!(rounds[0].eliminated, None);
+}
+
+#[test]
+fn eliminated_votes_transfer_to_next_preference() {
+    let ballots = vec![vec![1], vec![1, 3], vec![2, 3], vec![2], vec![3, 2]];
+    let (rounds, winner) = instant_runoff(&[1, 2, 3], &ballots);
+    assert_eq!(winner, Some(2));
+    assert_eq!(rounds.len(), 2);
+    assert_eq!(rounds[0].tally, tally(&[(1, 2), (2, 2), (3, 1)]));
+    assert_eq!(rounds[0].eliminated, Some(3));
+    assert_eq!(rounds[1].tally, tally(&[(1, 2), (2, 3)]));
+    assert_eq!(rounds[1].eliminated, None);
+}
+
+#[test]
+fn exhausted_ballots_do_not_count_towards_majority() {
+    let ballots = vec![vec![1], vec![1], vec![1], vec![3], vec![3], vec![2]];
+    let (rounds, winner) = instant_runoff(&[1, 2, 3], &ballots);
+    assert_eq!(rounds[0].tally, tally(&[(1, 3), (2, 1), (3, 2)]));
+    assert_eq!(rounds[0].eliminated, Some(2));
+    // The ballot for option 2 is exhausted, leaving option 1 with a majority of 3 of 5 votes.
+    assert_eq!(rounds[1].tally, tally(&[(1, 3), (3, 2)]));
+    assert_eq!(winner, Some(1));
+}
+
+#[test]
+fn fewest_votes_tie_eliminates_last_listed_option() {
+    let ballots = vec![vec![1], vec![1], vec![2, 3], vec![3, 2]];
+    let (rounds, winner) = instant_runoff(&[1, 2, 3], &ballots);
+    assert_eq!(rounds[0].eliminated, Some(3));
+    assert_eq!(rounds[1].tally, tally(&[(1, 2), (2, 2)]));
+    assert_eq!(winner, None);
+}
+
+#[test]
+fn no_ballots_gives_no_winner() {
+    let (rounds, winner) = instant_runoff(&[1, 2, 3], &[]);
+    assert_eq!(rounds.len(), 1);
+    assert_eq!(winner, None);
+}
+
+#[test]
+fn ballots_are_counted_after_deadline() {
+    let mut state = initial_state();
+    state = vote(create_ctx(voter(1), 10), state, vec![1, 2]);
+    state = vote(create_ctx(voter(2), 10), state, vec![3, 2]);
+    state = vote(create_ctx(voter(3), 10), state, vec![2]);
+    // A voter can replace their ballot until the deadline.
+    state = vote(create_ctx(voter(1), 20), state, vec![2, 1]);
+    state = count(create_ctx(voter(100), 100), state);
+
+    let result = state.result.unwrap();
+    assert_eq!(result.winning_option, Some(2));
+    assert_eq!(result.rounds[0].tally, tally(&[(1, 0), (2, 2), (3, 1)]));
+}
+
+#[test]
+#[should_panic(expected = "Option id 2 is ranked more than once")]
+fn option_cannot_be_ranked_twice() {
+    vote(create_ctx(voter(1), 10), initial_state(), vec![2, 1, 2]);
+}
+
+#[test]
+#[should_panic(expected = "Unknown option id 4")]
+fn unknown_option_cannot_be_ranked() {
+    vote(create_ctx(voter(1), 10), initial_state(), vec![4]);
+}
+
+#[test]
+#[should_panic(expected = "The ranking must contain at least one option")]
+fn empty_ranking_is_rejected() {
+    vote(create_ctx(voter(1), 10), initial_state(), vec![]);
+}
+
+#[test]
+#[should_panic(expected = "The deadline has not yet passed")]
+fn cannot_count_before_deadline() {
+    count(create_ctx(voter(100), 99), initial_state());
+}
@@ -0,0 +1,419 @@
+//! This is an example Name Registry contract
+//!
+//! The name registry contract lets users register names on a first-come basis. A registered name
+//! is owned by its registrant until it expires, and maps to a resolution record, being the address
+//! that the name resolves to.
+//!
+//! Registration uses a commit-reveal scheme to prevent front-running. A user first calls `commit`
+//! with a commitment to the name, which is the SHA-256 hash of the name, the address of the user
+//! and a secret salt. Since the commitment hides the name, an observer cannot register the name
+//! before the user. After waiting at least `MIN_COMMITMENT_AGE_MILLIS`, the user calls `register`
+//! with the name and the salt, which registers the name if the commitment matches. Commitments
+//! older than `MAX_COMMITMENT_AGE_MILLIS` can no longer be revealed.
+//!
+//! A registration lasts for the registration period of the registry, after which the name expires
+//! and can be registered by anyone. The owner of a name can `renew` it before it expires, which
+//! extends the expiry by another registration period. The owner can also `transfer` the name to a
+//! new owner, and `set_resolution` to change the address the name resolves to.
+//!
+//! Anyone can call `sweep_expired` to remove the expired names and the stale commitments from the
+//! state, keeping the state small.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use std::collections::BTreeMap;
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::Address;
+use pbc_contract_common::context::ContractContext;
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use read_write_state_derive::ReadWriteState;
+use sha2::{Digest, Sha256};
+
+#[cfg(test)]
+mod tests;
+
+/// The minimum time in milliseconds between committing to a name and registering it.
+const MIN_COMMITMENT_AGE_MILLIS: i64 = 60 * 1000;
+
+/// The maximum time in milliseconds between committing to a name and registering it.
+const MAX_COMMITMENT_AGE_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// The maximum length of a name in bytes.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A commitment to register a name.
+///
+/// ### Fields:
+///
+///   * `committer`: [`Address`], the user that made the commitment.
+///
+///   * `committed_at_millis`: [`i64`], the time in milliseconds at which the commitment was made.
+///
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Commitment {
+    committer: Address,
+    committed_at_millis: i64,
+}
+
+/// The record of a registered name.
+///
+/// ### Fields:
+///
+///   * `owner`: [`Address`], the owner of the name.
+///
+///   * `registered_at_millis`: [`i64`], the time in milliseconds at which the name was registered.
+///
+///   * `expires_at_millis`: [`i64`], the time in milliseconds at which the name expires.
+///
+///   * `resolution`: [`Option<Address>`], the address the name resolves to, if any.
+///
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct NameRecord {
+    owner: Address,
+    registered_at_millis: i64,
+    expires_at_millis: i64,
+    resolution: Option<Address>,
+}
+
+impl NameRecord {
+    /// Determines whether the name has expired at the given time.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `time_millis`: [`i64`], the time to check the expiry at.
+    ///
+    /// ### Returns
+    ///
+    /// True if the name has expired.
+    fn is_expired(&self, time_millis: i64) -> bool {
+        time_millis >= self.expires_at_millis
+    }
+}
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `registration_period_millis`: [`i64`], the time in milliseconds a registration or renewal
+///     lasts.
+///
+///   * `commitments`: [`BTreeMap<Hash, Commitment>`], the commitments to names that have not been
+///     registered yet.
+///
+///   * `names`: [`BTreeMap<String, NameRecord>`], the records of the registered names.
+///
+#[state]
+pub struct RegistryState {
+    registration_period_millis: i64,
+    commitments: BTreeMap<Hash, Commitment>,
+    names: BTreeMap<String, NameRecord>,
+}
+
+impl RegistryState {
+    /// Resolves a name to the address of its resolution record.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `name`: [`&str`], the name to resolve.
+    ///
+    /// * `time_millis`: [`i64`], the time to resolve the name at.
+    ///
+    /// ### Returns
+    ///
+    /// The address the name resolves to, or None if the name is not registered, has expired or has
+    /// no resolution.
+    pub fn resolve(&self, name: &str, time_millis: i64) -> Option<Address> {
+        self.names
+            .get(name)
+            .filter(|record| !record.is_expired(time_millis))
+            .and_then(|record| record.resolution)
+    }
+
+    /// Finds the record of a name owned by `sender` that has not expired.
+    /// Throws an error if the name is not registered, has expired or is not owned by `sender`.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `context`: [`&ContractContext`], the context for the action call.
+    ///
+    /// * `name`: [`&str`], the name to find.
+    ///
+    /// ### Returns
+    ///
+    /// The mutable record of the name.
+    fn owned_record(&mut self, context: &ContractContext, name: &str) -> &mut NameRecord {
+        let record = match self.names.get_mut(name) {
+            Some(record) => record,
+            None => panic!("The name is not registered"),
+        };
+        if record.is_expired(context.block_production_time) {
+            panic!("The name has expired");
+        }
+        if record.owner != context.sender {
+            panic!("Only the owner of the name can change it");
+        }
+        record
+    }
+}
+
+/// Computes the commitment to register a name, which is the SHA-256 hash of the bytes of the name,
+/// followed by the identifier of the registrant and the salt. Including the registrant prevents
+/// others from revealing the commitment.
+///
+/// ### Parameters:
+///
+/// * `name`: [`&str`], the name to register.
+///
+/// * `registrant`: [`&Address`], the user registering the name.
+///
+/// * `salt`: [`&Hash`], the secret salt chosen by the registrant.
+///
+/// ### Returns
+///
+/// The commitment to the registration.
+pub fn registration_commitment(name: &str, registrant: &Address, salt: &Hash) -> Hash {
+    let mut preimage: Vec<u8> = name.as_bytes().to_vec();
+    preimage.extend_from_slice(&registrant.identifier);
+    preimage.extend_from_slice(salt);
+    Sha256::digest(preimage).into()
+}
+
+/// Initial function to bootstrap the contract's state.
+///
+/// ### Parameters
+///
+///   * `_context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `registration_period_days`: [`u32`], the number of days a registration or renewal lasts.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`] with no registered names.
+///
+#[init]
+pub fn initialize(_context: ContractContext, registration_period_days: u32) -> RegistryState {
+    if registration_period_days == 0 {
+        panic!("The registration period must be at least one day");
+    }
+    RegistryState {
+        registration_period_millis: i64::from(registration_period_days) * 24 * 60 * 60 * 1000,
+        commitments: BTreeMap::new(),
+        names: BTreeMap::new(),
+    }
+}
+
+/// Action for committing to register a name, without revealing the name.
+/// Throws an error if the commitment has already been made.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// * `commitment`: [`Hash`], the commitment computed by [`registration_commitment`].
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x01)]
+pub fn commit(
+    context: ContractContext,
+    state: RegistryState,
+    commitment: Hash,
+) -> (RegistryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if new_state.commitments.contains_key(&commitment) {
+        panic!("The commitment has already been made");
+    }
+    new_state.commitments.insert(
+        commitment,
+        Commitment {
+            committer: context.sender,
+            committed_at_millis: context.block_production_time,
+        },
+    );
+    (new_state, vec![])
+}
+
+/// Action for registering a name by revealing a commitment to it.
+/// Throws an error if the name is invalid, if no matching commitment has been made by the sender,
+/// if the commitment is too recent or too old, or if the name is registered and has not expired.
+/// The name resolves to the sender after the registration.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// * `name`: [`String`], the name to register.
+///
+/// * `salt`: [`Hash`], the salt used for the commitment.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x02)]
+pub fn register(
+    context: ContractContext,
+    state: RegistryState,
+    name: String,
+    salt: Hash,
+) -> (RegistryState, Vec<EventGroup>) {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        panic!("The name must be between 1 and {MAX_NAME_LENGTH} bytes long");
+    }
+    let now = context.block_production_time;
+    let mut new_state = state;
+    let commitment = registration_commitment(&name, &context.sender, &salt);
+    let committed_at_millis = match new_state.commitments.remove(&commitment) {
+        Some(commitment) if commitment.committer == context.sender => {
+            commitment.committed_at_millis
+        }
+        _ => panic!("No matching commitment has been made"),
+    };
+    if now < committed_at_millis + MIN_COMMITMENT_AGE_MILLIS {
+        panic!("The commitment is too recent to be revealed");
+    }
+    if now > committed_at_millis + MAX_COMMITMENT_AGE_MILLIS {
+        panic!("The commitment is too old to be revealed");
+    }
+    if let Some(record) = new_state.names.get(&name) {
+        if !record.is_expired(now) {
+            panic!("The name is already registered");
+        }
+    }
+
+    let record = NameRecord {
+        owner: context.sender,
+        registered_at_millis: now,
+        expires_at_millis: now + new_state.registration_period_millis,
+        resolution: Some(context.sender),
+    };
+    new_state.names.insert(name, record);
+    (new_state, vec![])
+}
+
+/// Action for renewing a name, extending its expiry by the registration period.
+/// Throws an error if the name is not owned by the sender or has expired.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// * `name`: [`String`], the name to renew.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x03)]
+pub fn renew(
+    context: ContractContext,
+    state: RegistryState,
+    name: String,
+) -> (RegistryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let registration_period_millis = new_state.registration_period_millis;
+    let record = new_state.owned_record(&context, &name);
+    record.expires_at_millis += registration_period_millis;
+    (new_state, vec![])
+}
+
+/// Action for transferring a name to a new owner. The resolution record is cleared, such that the
+/// name does not resolve to an address chosen by the previous owner.
+/// Throws an error if the name is not owned by the sender or has expired.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// * `name`: [`String`], the name to transfer.
+///
+/// * `new_owner`: [`Address`], the new owner of the name.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x04)]
+pub fn transfer(
+    context: ContractContext,
+    state: RegistryState,
+    name: String,
+    new_owner: Address,
+) -> (RegistryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let record = new_state.owned_record(&context, &name);
+    record.owner = new_owner;
+    record.resolution = None;
+    (new_state, vec![])
+}
+
+/// Action for setting the address a name resolves to.
+/// Throws an error if the name is not owned by the sender or has expired.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// * `name`: [`String`], the name to change the resolution of.
+///
+/// * `resolution`: [`Option<Address>`], the address the name resolves to, or None to clear the
+///   resolution.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x05)]
+pub fn set_resolution(
+    context: ContractContext,
+    state: RegistryState,
+    name: String,
+    resolution: Option<Address>,
+) -> (RegistryState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let record = new_state.owned_record(&context, &name);
+    record.resolution = resolution;
+    (new_state, vec![])
+}
+
+/// Action for removing the expired names and the commitments that are too old to be revealed.
+/// Can be called by anyone.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`RegistryState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`RegistryState`].
+///
+#[action(shortname = 0x06)]
+pub fn sweep_expired(
+    context: ContractContext,
+    state: RegistryState,
+) -> (RegistryState, Vec<EventGroup>) {
+    let now = context.block_production_time;
+    let mut new_state = state;
+    new_state.names.retain(|_, record| !record.is_expired(now));
+    new_state
+        .commitments
+        .retain(|_, commitment| now <= commitment.committed_at_millis + MAX_COMMITMENT_AGE_MILLIS);
+    (new_state, vec![])
+}
@@ -0,0 +1,178 @@
+use pbc_contract_common::address::{Address, AddressType};
+use pbc_contract_common::context::ContractContext;
+use pbc_contract_common::Hash;
+
+use crate::{
+    commit, initialize, register, registration_commitment, renew, set_resolution, sweep_expired,
+    transfer, RegistryState,
+};
+
+const SECOND_MILLIS: i64 = 1000;
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+const SALT: Hash = [9u8; 32];
+
+fn create_ctx(sender: Address, seconds: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 3),
+        sender,
+        block_time: seconds,
+        block_production_time: seconds * SECOND_MILLIS,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_alice_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_bob_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+/// Registers `name` for `registrant`, committing at `seconds` and revealing a minute later, in a
+/// registry with a registration period of 10 days.
+fn registered(
+    state: RegistryState,
+    registrant: Address,
+    name: &str,
+    seconds: i64,
+) -> RegistryState {
+    let commitment = registration_commitment(name, &registrant, &SALT);
+    let (state, _) = commit(create_ctx(registrant, seconds), state, commitment);
+    let (state, _) = register(
+        create_ctx(registrant, seconds + 60),
+        state,
+        name.to_string(),
+        SALT,
+    );
+    state
+}
+
+fn registry() -> RegistryState {
+    initialize(create_ctx(get_alice_address(), 0), 10)
+}
+
+#[test]
+pub fn test_register_and_resolve() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    assert_eq!(
+        state.resolve("alice", 60 * SECOND_MILLIS),
+        Some(get_alice_address())
+    );
+    assert!(state.commitments.is_empty());
+
+    let record = state.names.get("alice").unwrap();
+    assert_eq!(
+        record.expires_at_millis,
+        (60 + 10 * DAY_SECONDS) * SECOND_MILLIS
+    );
+    assert_eq!(state.resolve("alice", record.expires_at_millis), None);
+}
+
+#[test]
+#[should_panic(expected = "The commitment is too recent to be revealed")]
+pub fn test_register_too_early() {
+    let commitment = registration_commitment("alice", &get_alice_address(), &SALT);
+    let (state, _) = commit(create_ctx(get_alice_address(), 0), registry(), commitment);
+    register(
+        create_ctx(get_alice_address(), 59),
+        state,
+        "alice".to_string(),
+        SALT,
+    );
+}
+
+#[test]
+#[should_panic(expected = "No matching commitment has been made")]
+pub fn test_front_running_commitment_cannot_be_revealed() {
+    let commitment = registration_commitment("alice", &get_alice_address(), &SALT);
+    let (state, _) = commit(create_ctx(get_alice_address(), 0), registry(), commitment);
+    // Bob learns the name and the salt, but cannot reveal the commitment of Alice
+    register(
+        create_ctx(get_bob_address(), 60),
+        state,
+        "alice".to_string(),
+        SALT,
+    );
+}
+
+#[test]
+#[should_panic(expected = "The name is already registered")]
+pub fn test_register_taken_name() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    registered(state, get_bob_address(), "alice", 100);
+}
+
+#[test]
+pub fn test_register_expired_name() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    let state = registered(state, get_bob_address(), "alice", 10 * DAY_SECONDS);
+    assert_eq!(state.names.get("alice").unwrap().owner, get_bob_address());
+}
+
+#[test]
+pub fn test_renew_transfer_and_set_resolution() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    let (state, _) = renew(
+        create_ctx(get_alice_address(), 100),
+        state,
+        "alice".to_string(),
+    );
+    let expires_at_millis = state.names.get("alice").unwrap().expires_at_millis;
+    assert_eq!(expires_at_millis, (60 + 20 * DAY_SECONDS) * SECOND_MILLIS);
+
+    let (state, _) = transfer(
+        create_ctx(get_alice_address(), 200),
+        state,
+        "alice".to_string(),
+        get_bob_address(),
+    );
+    assert_eq!(state.resolve("alice", 200 * SECOND_MILLIS), None);
+
+    let (state, _) = set_resolution(
+        create_ctx(get_bob_address(), 300),
+        state,
+        "alice".to_string(),
+        Some(get_bob_address()),
+    );
+    assert_eq!(
+        state.resolve("alice", 300 * SECOND_MILLIS),
+        Some(get_bob_address())
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only the owner of the name can change it")]
+pub fn test_set_resolution_not_owner() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    set_resolution(
+        create_ctx(get_bob_address(), 100),
+        state,
+        "alice".to_string(),
+        Some(get_bob_address()),
+    );
+}
+
+#[test]
+pub fn test_sweep_expired() {
+    let state = registered(registry(), get_alice_address(), "alice", 0);
+    let state = registered(state, get_bob_address(), "bob", 5 * DAY_SECONDS);
+    let stale_commitment = registration_commitment("carol", &get_alice_address(), &SALT);
+    let (state, _) = commit(create_ctx(get_alice_address(), 0), state, stale_commitment);
+
+    let (state, _) = sweep_expired(create_ctx(get_bob_address(), 10 * DAY_SECONDS + 60), state);
+    assert!(!state.names.contains_key("alice"));
+    assert!(state.names.contains_key("bob"));
+    assert!(state.commitments.is_empty());
+}
@@ -0,0 +1,347 @@
+//! Example smart contract implementing a time-locked savings vault, where depositors lock tokens
+//! for a chosen duration, and depositors withdrawing early pay a penalty to the depositors who stay.
+//!
+//! How it works
+//! * The vault is initialized with the token it holds, the penalty for early withdrawals in per
+//!   mille and the maximum duration of a lock.
+//! * Anyone can deposit tokens, choosing the duration they are locked for. The tokens are
+//!   transferred to the vault, and are locked until the chosen duration has passed. Depositing
+//!   again adds to the deposit, which stays locked until the later of the current and the new
+//!   unlock time.
+//! * A depositor can withdraw their whole deposit at any time. Once the deposit is unlocked it is
+//!   withdrawn in full, while withdrawing a locked deposit costs the penalty in per mille of it.
+//! * The penalty is redistributed to the remaining depositors, pro-rata to their deposits. The
+//!   redistributed penalties are tracked as an amount per deposited token, such that paying a
+//!   penalty takes constant work regardless of the number of depositors. A redistributed penalty
+//!   is added to a deposit when the depositor deposits again or withdraws.
+//! * A depositor withdrawing early when no other depositors remain pays no penalty, since there is
+//!   no one to redistribute it to.
+//! * If the transfer of a withdrawal fails, the withdrawn tokens are returned to the vault as an
+//!   unlocked deposit of the depositor, which can be withdrawn again without penalty.
+#![allow(unused_variables)]
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+extern crate pbc_contract_common;
+
+use std::collections::BTreeMap;
+
+use create_type_spec_derive::CreateTypeSpec;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use read_write_state_derive::ReadWriteState;
+
+#[cfg(test)]
+mod tests;
+
+/// The precision of the penalties redistributed per deposited token.
+pub const PENALTY_PRECISION: u128 = 1_000_000_000_000;
+
+/// The deposit of a depositor.
+#[derive(ReadWriteState, CreateTypeSpec)]
+pub struct Deposit {
+    /// The amount of deposited tokens, including redistributed penalties added to the deposit.
+    pub amount: u128,
+    /// The time in UTC millis at which the deposit is unlocked.
+    pub unlock_at_millis: i64,
+    /// The penalties redistributed per deposited token when the redistributed penalties were last
+    /// added to the deposit, scaled by [`PENALTY_PRECISION`].
+    pub penalty_checkpoint: u128,
+}
+
+/// The state of the vault, which is persisted on-chain.
+#[state]
+pub struct VaultState {
+    /// The address of the token contract of the deposited tokens.
+    pub token: Address,
+    /// The penalty in per mille of a deposit withdrawn before it is unlocked.
+    pub early_withdrawal_penalty_per_mille: u32,
+    /// The maximum duration of a lock in millis.
+    pub max_lock_millis: i64,
+    /// The deposits of the depositors.
+    pub deposits: BTreeMap<Address, Deposit>,
+    /// The sum of the amounts of all deposits.
+    pub total_deposited: u128,
+    /// The penalties redistributed per deposited token since the vault was initialized, scaled by
+    /// [`PENALTY_PRECISION`].
+    pub penalty_per_token: u128,
+    /// The remainder of the scaled penalties that could not be divided evenly between the
+    /// deposited tokens, which is included in the next redistribution.
+    pub penalty_remainder: u128,
+}
+
+impl VaultState {
+    /// The tokens that `depositor` can withdraw, being their deposit and the penalties
+    /// redistributed to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `depositor` - the address of the depositor.
+    ///
+    /// # Returns
+    ///
+    /// The balance of the depositor, which is 0 if they have no deposit.
+    ///
+    pub fn balance_of(&self, depositor: &Address) -> u128 {
+        match self.deposits.get(depositor) {
+            Some(deposit) => deposit.amount + self.redistributed_to(deposit),
+            None => 0,
+        }
+    }
+
+    /// The penalties redistributed to `deposit` since they were last added to it.
+    fn redistributed_to(&self, deposit: &Deposit) -> u128 {
+        deposit.amount * (self.penalty_per_token - deposit.penalty_checkpoint) / PENALTY_PRECISION
+    }
+
+    /// Adds `amount` to the deposit of `depositor`, along with the penalties redistributed to it,
+    /// and locks the deposit until at least `unlock_at_millis`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depositor` - the address of the depositor.
+    /// * `amount` - the amount of tokens added to the deposit.
+    /// * `unlock_at_millis` - the time in UTC millis until which the deposit is locked.
+    ///
+    fn credit(&mut self, depositor: Address, amount: u128, unlock_at_millis: i64) {
+        let redistributed = self
+            .deposits
+            .get(&depositor)
+            .map_or(0, |deposit| self.redistributed_to(deposit));
+        let penalty_per_token = self.penalty_per_token;
+        let deposit = self.deposits.entry(depositor).or_insert(Deposit {
+            amount: 0,
+            unlock_at_millis,
+            penalty_checkpoint: penalty_per_token,
+        });
+        deposit.amount += redistributed + amount;
+        deposit.unlock_at_millis = deposit.unlock_at_millis.max(unlock_at_millis);
+        deposit.penalty_checkpoint = penalty_per_token;
+        self.total_deposited += redistributed + amount;
+    }
+
+    /// Redistributes `penalty` to the deposits, pro-rata to their amounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `penalty` - the amount of tokens to redistribute.
+    ///
+    fn redistribute(&mut self, penalty: u128) {
+        let scaled = penalty * PENALTY_PRECISION + self.penalty_remainder;
+        self.penalty_per_token += scaled / self.total_deposited;
+        self.penalty_remainder = scaled % self.total_deposited;
+    }
+}
+
+/// Initialize a new vault.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `token` - the address of the token contract of the deposited tokens.
+/// * `early_withdrawal_penalty_per_mille` - the penalty in per mille of a deposit withdrawn before
+///   it is unlocked.
+/// * `max_lock_millis` - the maximum duration of a lock in millis.
+///
+/// # Returns
+///
+/// The initial state of the vault, with no deposits.
+///
+#[init]
+pub fn initialize(
+    ctx: ContractContext,
+    token: Address,
+    early_withdrawal_penalty_per_mille: u32,
+    max_lock_millis: i64,
+) -> VaultState {
+    assert_eq!(
+        token.address_type,
+        AddressType::PublicContract,
+        "The token must be a public contract"
+    );
+    assert!(
+        early_withdrawal_penalty_per_mille <= 1000,
+        "The penalty cannot exceed 1000 per mille"
+    );
+    assert!(
+        max_lock_millis > 0,
+        "The maximum duration of a lock must be positive"
+    );
+    VaultState {
+        token,
+        early_withdrawal_penalty_per_mille,
+        max_lock_millis,
+        deposits: BTreeMap::new(),
+        total_deposited: 0,
+        penalty_per_token: 0,
+        penalty_remainder: 0,
+    }
+}
+
+/// Deposit tokens, locking them for `lock_millis`. The tokens are transferred from the sender to
+/// the vault, and the deposit is recorded in `deposit_callback`.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vault.
+/// * `amount` - the amount of tokens to deposit.
+/// * `lock_millis` - the duration in millis the tokens are locked for, at most the maximum
+///   duration of a lock.
+///
+/// # Returns
+///
+/// The unchanged state, and the transfer of the tokens.
+///
+#[action(shortname = 0x01)]
+pub fn deposit(
+    ctx: ContractContext,
+    state: VaultState,
+    amount: u128,
+    lock_millis: i64,
+) -> (VaultState, Vec<EventGroup>) {
+    assert!(amount > 0, "The amount must be positive");
+    assert!(
+        0 <= lock_millis && lock_millis <= state.max_lock_millis,
+        "The lock must be between 0 and {} millis",
+        state.max_lock_millis
+    );
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.token, token_contract_transfer_from())
+        .argument(ctx.sender)
+        .argument(ctx.contract_address)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(ctx.sender)
+        .argument(amount)
+        .argument(lock_millis)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Records a deposit. The tokens are added to the deposit of the depositor, which is locked until
+/// the later of its current unlock time and `lock_millis` from now.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the vault.
+/// * `depositor` - the depositor of the tokens.
+/// * `amount` - the amount of deposited tokens.
+/// * `lock_millis` - the duration in millis the tokens are locked for.
+///
+/// # Returns
+///
+/// The updated state with the deposit recorded.
+///
+#[callback(shortname = 0x10)]
+pub fn deposit_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VaultState,
+    depositor: Address,
+    amount: u128,
+    lock_millis: i64,
+) -> VaultState {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the deposit did not succeed"
+    );
+    let mut new_state = state;
+    new_state.credit(depositor, amount, ctx.block_production_time + lock_millis);
+    new_state
+}
+
+/// Withdraw the whole deposit of the sender, along with the penalties redistributed to it. If the
+/// deposit is still locked, the penalty is deducted and redistributed to the remaining depositors,
+/// unless no other depositors remain.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vault.
+///
+/// # Returns
+///
+/// The updated state without the deposit of the sender, and the transfer of the withdrawn tokens.
+///
+#[action(shortname = 0x02)]
+pub fn withdraw(ctx: ContractContext, state: VaultState) -> (VaultState, Vec<EventGroup>) {
+    let mut new_state = state;
+    let balance = new_state.balance_of(&ctx.sender);
+    let deposit = new_state
+        .deposits
+        .remove(&ctx.sender)
+        .unwrap_or_else(|| panic!("The sender has no deposit"));
+    new_state.total_deposited -= deposit.amount;
+
+    let penalty =
+        if ctx.block_production_time < deposit.unlock_at_millis && new_state.total_deposited > 0 {
+            balance * new_state.early_withdrawal_penalty_per_mille as u128 / 1000
+        } else {
+            0
+        };
+    if penalty > 0 {
+        new_state.redistribute(penalty);
+    }
+
+    let withdrawn = balance - penalty;
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(new_state.token, token_contract_transfer())
+        .argument(ctx.sender)
+        .argument(withdrawn)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_CALLBACK)
+        .argument(ctx.sender)
+        .argument(withdrawn)
+        .done();
+    (new_state, vec![event_group.build()])
+}
+
+/// Records the outcome of a withdrawal. If the transfer failed, the withdrawn tokens are returned
+/// to the vault as an unlocked deposit of the depositor.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the vault.
+/// * `depositor` - the depositor who withdrew the tokens.
+/// * `amount` - the amount of withdrawn tokens.
+///
+/// # Returns
+///
+/// The updated state.
+///
+#[callback(shortname = 0x11)]
+pub fn withdraw_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VaultState,
+    depositor: Address,
+    amount: u128,
+) -> VaultState {
+    let mut new_state = state;
+    if !callback_ctx.success && amount > 0 {
+        new_state.credit(depositor, amount, ctx.block_production_time);
+    }
+    new_state
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
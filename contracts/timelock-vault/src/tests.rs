@@ -0,0 +1,269 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+
+use crate::{
+    deposit, deposit_callback, initialize, withdraw, withdraw_callback, VaultState,
+    SHORTNAME_DEPOSIT_CALLBACK, SHORTNAME_WITHDRAW_CALLBACK,
+};
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn depositor(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+fn token() -> Address {
+    create_address(AddressType::PublicContract, 2)
+}
+
+fn callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![],
+    }
+}
+
+/// A vault with a penalty of 100 per mille and locks of at most 1000 millis.
+fn initial_state() -> VaultState {
+    initialize(create_ctx(depositor(1), 0), token(), 100, 1000)
+}
+
+/// Records a deposit of `amount` by `depositor` at time `now`, locked for `lock_millis`.
+fn deposited(
+    state: VaultState,
+    depositor: Address,
+    amount: u128,
+    lock_millis: i64,
+    now: i64,
+) -> VaultState {
+    deposit_callback(
+        create_ctx(depositor, now),
+        callback_ctx(true),
+        state,
+        depositor,
+        amount,
+        lock_millis,
+    )
+}
+
+/// A vault where depositors 1, 2 and 3 have deposited 100, 200 and 300 at time 0, locked for 1000
+/// millis.
+fn state_with_deposits() -> VaultState {
+    let mut state = initial_state();
+    for (id, amount) in [(1, 100), (2, 200), (3, 300)] {
+        state = deposited(state, depositor(id), amount, 1000, 0);
+    }
+    state
+}
+
+/// Withdraws the deposit of `depositor` at time `now`, returning the withdrawn amount, which is
+/// less the penalty of 100 per mille if the deposit is locked and other depositors remain.
+fn withdrawn(state: VaultState, depositor: Address, now: i64) -> (VaultState, u128) {
+    let balance = state.balance_of(&depositor);
+    let locked = now < state.deposits.get(&depositor).unwrap().unlock_at_millis;
+    let penalty = if locked && state.deposits.len() > 1 {
+        balance * 100 / 1000
+    } else {
+        0
+    };
+    let (state, events) = withdraw(create_ctx(depositor, now), state);
+    let amount = balance - penalty;
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x01))
+        .argument(depositor)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_CALLBACK)
+        .argument(depositor)
+        .argument(amount)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+    (state, amount)
+}
+
+#[test]
+fn deposit_is_transferred_to_the_vault() {
+    let (state, events) = deposit(create_ctx(depositor(1), 0), initial_state(), 100, 500);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x03))
+        .argument(depositor(1))
+        .argument(create_address(AddressType::PublicContract, 1))
+        .argument(100u128)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(depositor(1))
+        .argument(100u128)
+        .argument(500i64)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+
+    let state = deposited(state, depositor(1), 100, 500, 10);
+    let deposit = state.deposits.get(&depositor(1)).unwrap();
+    assert_eq!(deposit.amount, 100);
+    assert_eq!(deposit.unlock_at_millis, 510);
+    assert_eq!(state.total_deposited, 100);
+}
+
+#[test]
+fn depositing_again_keeps_the_later_unlock_time() {
+    let state = deposited(initial_state(), depositor(1), 100, 800, 0);
+    let state = deposited(state, depositor(1), 50, 100, 200);
+    let deposit = state.deposits.get(&depositor(1)).unwrap();
+    assert_eq!(deposit.amount, 150);
+    assert_eq!(deposit.unlock_at_millis, 800);
+
+    let state = deposited(state, depositor(1), 50, 1000, 300);
+    assert_eq!(
+        state.deposits.get(&depositor(1)).unwrap().unlock_at_millis,
+        1300
+    );
+}
+
+#[test]
+fn early_withdrawal_penalty_is_redistributed_pro_rata() {
+    let (state, amount) = withdrawn(state_with_deposits(), depositor(3), 500);
+    assert_eq!(amount, 270);
+    assert!(!state.deposits.contains_key(&depositor(3)));
+    assert_eq!(state.total_deposited, 300);
+    assert_eq!(state.balance_of(&depositor(1)), 110);
+    assert_eq!(state.balance_of(&depositor(2)), 220);
+
+    let (state, amount) = withdrawn(state, depositor(2), 600);
+    assert_eq!(amount, 198);
+    assert_eq!(state.balance_of(&depositor(1)), 132);
+}
+
+#[test]
+fn unlocked_deposit_is_withdrawn_without_penalty() {
+    let (state, amount) = withdrawn(state_with_deposits(), depositor(3), 1000);
+    assert_eq!(amount, 300);
+    assert_eq!(state.balance_of(&depositor(1)), 100);
+    assert_eq!(state.penalty_per_token, 0);
+}
+
+#[test]
+fn redistributed_penalties_are_added_to_new_deposits() {
+    let (state, _) = withdrawn(state_with_deposits(), depositor(3), 500);
+    let state = deposited(state, depositor(1), 100, 0, 600);
+    let deposit = state.deposits.get(&depositor(1)).unwrap();
+    assert_eq!(deposit.amount, 210);
+    assert_eq!(state.balance_of(&depositor(1)), 210);
+    assert_eq!(state.total_deposited, 410);
+}
+
+#[test]
+fn last_depositor_pays_no_penalty() {
+    let state = deposited(initial_state(), depositor(1), 100, 1000, 0);
+    let (state, events) = withdraw(create_ctx(depositor(1), 10), state);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token(), Shortname::from_u32(0x01))
+        .argument(depositor(1))
+        .argument(100u128)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_CALLBACK)
+        .argument(depositor(1))
+        .argument(100u128)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+    assert_eq!(state.total_deposited, 0);
+}
+
+#[test]
+fn failed_withdrawal_returns_an_unlocked_deposit() {
+    let (state, amount) = withdrawn(state_with_deposits(), depositor(3), 500);
+    let state = withdraw_callback(
+        create_ctx(depositor(3), 510),
+        callback_ctx(false),
+        state,
+        depositor(3),
+        amount,
+    );
+    let deposit = state.deposits.get(&depositor(3)).unwrap();
+    assert_eq!(deposit.amount, 270);
+    assert_eq!(deposit.unlock_at_millis, 510);
+    assert_eq!(state.total_deposited, 570);
+
+    let (_, amount) = withdrawn(state, depositor(3), 520);
+    assert_eq!(amount, 270);
+}
+
+#[test]
+#[should_panic(expected = "The lock must be between 0 and 1000 millis")]
+fn lock_cannot_exceed_maximum() {
+    deposit(create_ctx(depositor(1), 0), initial_state(), 100, 1001);
+}
+
+#[test]
+#[should_panic(expected = "The sender has no deposit")]
+fn cannot_withdraw_without_deposit() {
+    withdraw(create_ctx(depositor(1), 0), initial_state());
+}
+
+#[test]
+#[should_panic(expected = "The penalty cannot exceed 1000 per mille")]
+fn penalty_cannot_exceed_whole_deposit() {
+    initialize(create_ctx(depositor(1), 0), token(), 1001, 1000);
+}
+
+#[test]
+fn redistribution_stress_test() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(10);
+    for _ in 0..20 {
+        let mut state = initial_state();
+        let mut deposited_total: u128 = 0;
+        let mut withdrawn_total: u128 = 0;
+        for now in 0..2000 {
+            let id = rng.gen_range(1..=100);
+            if rng.gen_bool(0.6) {
+                let amount = rng.gen_range(1..=1_000_000);
+                let lock_millis = rng.gen_range(0..=1000);
+                state = deposited(state, depositor(id), amount, lock_millis, now);
+                deposited_total += amount;
+            } else if state.deposits.contains_key(&depositor(id)) {
+                let amount;
+                (state, amount) = withdrawn(state, depositor(id), now);
+                withdrawn_total += amount;
+            }
+
+            // The vault can always pay out every depositor, losing at most one token to rounding
+            // for each deposit and withdrawal
+            let owed: u128 = state
+                .deposits
+                .keys()
+                .map(|depositor| state.balance_of(depositor))
+                .sum();
+            let held = deposited_total - withdrawn_total;
+            assert!(owed <= held);
+            assert!(held - owed <= now as u128 + 1);
+        }
+    }
+}
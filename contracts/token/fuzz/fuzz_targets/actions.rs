@@ -0,0 +1,48 @@
+//! Fuzzes the token contract, invoking the generated entry points of `initialize` and of every
+//! action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+use token_contract::{
+    __pbc_autogen__approve_wrapped, __pbc_autogen__balance_of_wrapped,
+    __pbc_autogen__bulk_transfer_from_wrapped, __pbc_autogen__bulk_transfer_wrapped,
+    __pbc_autogen__initialize_wrapped, __pbc_autogen__transfer_from_wrapped,
+    __pbc_autogen__transfer_wrapped,
+};
+
+const CALLS: [Call; 6] = [
+    Call::Action(__pbc_autogen__transfer_wrapped),
+    Call::Action(__pbc_autogen__bulk_transfer_wrapped),
+    Call::Action(__pbc_autogen__transfer_from_wrapped),
+    Call::Action(__pbc_autogen__bulk_transfer_from_wrapped),
+    Call::Action(__pbc_autogen__approve_wrapped),
+    Call::Action(__pbc_autogen__balance_of_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
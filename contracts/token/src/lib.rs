@@ -6,6 +6,9 @@
 //! Any token owner can then `transfer` tokens to other accounts, or `approve` other accounts to use their tokens.
 //! If a Alice has been approved tokens from Bob, then Alice can use `transfer_from` to use Bob's tokens.
 //!
+//! Other contracts can read the balance of an account with `balance_of`, which returns the balance
+//! as return data to the callback of the calling contract.
+//!
 //! The contract is inspired by the ERC20 token contract.\
 //! <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-20.md>
 #![allow(unused_variables)]
@@ -22,8 +25,6 @@ use pbc_contract_common::address::Address;
 use pbc_contract_common::context::ContractContext;
 use pbc_contract_common::events::EventGroup;
 
-
-
 /// Custom struct for the state of the contract.
 ///
 /// The "state" attribute is attached.
@@ -293,6 +294,31 @@ pub fn approve(
     (new_state, vec![])
 }
 
+/// Returns the balance of `owner` as return data, such that other contracts can read it in a callback.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`TokenContractState`], the current state of the contract.
+///
+/// * `owner`: [`Address`], the address to query the balance of.
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`TokenContractState`] and the event group returning the balance as a [`u128`].
+#[action(shortname = 0x06)]
+pub fn balance_of(
+    context: ContractContext,
+    state: TokenState,
+    owner: Address,
+) -> (TokenState, Vec<EventGroup>) {
+    let balance = state.balances.get(&owner).copied().unwrap_or(0);
+    let mut event_group = EventGroup::builder();
+    event_group.return_data(balance);
+    (state, vec![event_group.build()])
+}
+
 /// Transfers `amount` of tokens to address `to` from the caller.
 /// The function throws if the message caller's account
 /// balance does not have enough tokens to spend.
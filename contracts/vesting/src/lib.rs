@@ -0,0 +1,346 @@
+//! This is an example Vesting contract
+//!
+//! The vesting contract holds a grant of tokens for a beneficiary, which is released gradually
+//! according to a vesting schedule.
+//! The contract handles a specific token type.
+//! The owner deposits tokens into the contract, and no tokens are vested before the cliff time.
+//! From the cliff time the tokens vest linearly, measured from the start of the schedule, until
+//! all tokens are vested at the end of the vesting duration.
+//! The beneficiary can call `release` at any time to withdraw the tokens vested so far.
+//! If the schedule is revocable, the owner can `revoke` the grant, which transfers the unvested
+//! tokens back to the owner. The tokens vested at the time of revocation can still be released by
+//! the beneficiary.
+
+#[macro_use]
+extern crate pbc_contract_codegen;
+
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+
+#[cfg(test)]
+mod tests;
+
+/// The contract state.
+///
+/// ### Fields:
+///
+///   * `owner`: [`Address`], the owner that deposits the tokens of the grant.
+///
+///   * `beneficiary`: [`Address`], the receiver of the vested tokens.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `total_amount`: [`u128`], the total amount of tokens of the grant.
+///
+///   * `released_amount`: [`u128`], the amount of tokens released to the beneficiary so far.
+///
+///   * `start_time_millis`: [`i64`], the start time of the vesting schedule in milliseconds.
+///
+///   * `cliff_time_millis`: [`i64`], the time in milliseconds before which no tokens are vested.
+///
+///   * `vesting_duration_millis`: [`i64`], the duration of the vesting in milliseconds, after
+///     which all tokens are vested.
+///
+///   * `revocable`: [`bool`], whether the owner can revoke the unvested tokens.
+///
+///   * `revoked`: [`bool`], whether the grant has been revoked.
+///
+#[state]
+pub struct VestingState {
+    owner: Address,
+    beneficiary: Address,
+    token_type: Address,
+    total_amount: u128,
+    released_amount: u128,
+    start_time_millis: i64,
+    cliff_time_millis: i64,
+    vesting_duration_millis: i64,
+    revocable: bool,
+    revoked: bool,
+}
+
+impl VestingState {
+    /// Computes the amount of tokens vested at the given time.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `time_millis`: [`i64`], the time to compute the vested amount at.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of vested tokens, including the tokens already released.
+    fn vested_amount(&self, time_millis: i64) -> u128 {
+        if self.revoked {
+            // The unvested tokens were returned to the owner when revoking
+            self.total_amount
+        } else if time_millis < self.cliff_time_millis {
+            0
+        } else if time_millis >= self.start_time_millis + self.vesting_duration_millis {
+            self.total_amount
+        } else {
+            let elapsed_millis = (time_millis - self.start_time_millis) as u128;
+            self.total_amount * elapsed_millis / self.vesting_duration_millis as u128
+        }
+    }
+
+    /// Computes the amount of vested tokens that have not been released yet.
+    ///
+    /// ### Parameters:
+    ///
+    /// * `time_millis`: [`i64`], the time to compute the releasable amount at.
+    ///
+    /// ### Returns
+    ///
+    /// The amount of tokens that can be released.
+    fn releasable_amount(&self, time_millis: i64) -> u128 {
+        self.vested_amount(time_millis) - self.released_amount
+    }
+}
+
+/// Initial function to bootstrap the contract's state. The owner of the contract is the sender of
+/// the initialization, and the vesting schedule starts at the time of initialization.
+///
+/// ### Parameters
+///
+///   * `context`: [`ContractContext`] - the contract context containing sender and chain information.
+///
+///   * `beneficiary`: [`Address`] - the receiver of the vested tokens.
+///
+///   * `token_type`: [`Address`], the address of the token used in the contract.
+///
+///   * `cliff_time_millis`: [`i64`], the time in milliseconds before which no tokens are vested.
+///
+///   * `vesting_duration_millis`: [`i64`], the duration of the vesting in milliseconds.
+///
+///   * `revocable`: [`bool`], whether the owner can revoke the unvested tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`VestingState`] with no tokens deposited.
+///
+#[init]
+pub fn initialize(
+    context: ContractContext,
+    beneficiary: Address,
+    token_type: Address,
+    cliff_time_millis: i64,
+    vesting_duration_millis: i64,
+    revocable: bool,
+) -> VestingState {
+    if token_type.address_type != AddressType::PublicContract {
+        panic!("Tried to create a contract vesting a non publicContract token");
+    }
+    if vesting_duration_millis <= 0 {
+        panic!("The vesting duration must be positive");
+    }
+    let start_time_millis = context.block_production_time;
+    if cliff_time_millis < start_time_millis
+        || cliff_time_millis > start_time_millis + vesting_duration_millis
+    {
+        panic!("The cliff time must be within the vesting schedule");
+    }
+    VestingState {
+        owner: context.sender,
+        beneficiary,
+        token_type,
+        total_amount: 0,
+        released_amount: 0,
+        start_time_millis,
+        cliff_time_millis,
+        vesting_duration_millis,
+        revocable,
+        revoked: false,
+    }
+}
+
+/// Action for the owner to deposit tokens into the grant.
+/// Throws an error if not called by the `owner` or if the grant has been revoked.
+/// The function creates a transfer event of tokens from the `owner` to the contract, and
+/// a callback to `deposit_callback`.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`VestingState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of tokens to deposit
+///
+/// ### Returns
+///
+/// The unchanged state object of type [`VestingState`] and the event group containing the
+/// transfer event and the callback event.
+///
+#[action(shortname = 0x01)]
+pub fn deposit(
+    context: ContractContext,
+    state: VestingState,
+    amount: u128,
+) -> (VestingState, Vec<EventGroup>) {
+    if context.sender != state.owner {
+        panic!("Deposit can only be called by the owner");
+    }
+    if state.revoked {
+        panic!("Cannot deposit tokens after the grant has been revoked");
+    }
+    // Create transfer event of tokens from the owner to the contract
+    // transfer should callback to deposit_callback
+    let mut e = EventGroup::builder();
+    e.call(state.token_type, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(amount)
+        .done();
+    e.with_callback(SHORTNAME_DEPOSIT_CALLBACK)
+        .argument(amount)
+        .done();
+    let event_group: EventGroup = e.build();
+
+    (state, vec![event_group])
+}
+
+/// Callback for depositing tokens. If the transfer was successful the tokens are added to the
+/// grant. Otherwise the callback panics.
+///
+/// ### Parameters:
+///
+/// * `ctx`: [`ContractContext`], the contractContext for the callback.
+///
+/// * `callback_ctx`: [`CallbackContext`], the callbackContext.
+///
+/// * `state`: [`VestingState`], the current state of the contract.
+///
+/// * `amount`: [`u128`], the amount of deposited tokens.
+///
+/// ### Returns
+///
+/// The new state object of type [`VestingState`].
+///
+#[callback(shortname = 0x02)]
+pub fn deposit_callback(
+    _ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VestingState,
+    amount: u128,
+) -> (VestingState, Vec<EventGroup>) {
+    if !callback_ctx.success {
+        panic!("Transfer event did not succeed for deposit");
+    }
+    let mut new_state = state;
+    new_state.total_amount += amount;
+    (new_state, vec![])
+}
+
+/// Action for the beneficiary to release the tokens vested so far.
+/// Throws an error if not called by the `beneficiary` or if no tokens can be released.
+/// The released tokens are transferred to the beneficiary.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`VestingState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`VestingState`] and the event group containing the transfer
+/// event.
+///
+#[action(shortname = 0x03)]
+pub fn release(context: ContractContext, state: VestingState) -> (VestingState, Vec<EventGroup>) {
+    if context.sender != state.beneficiary {
+        panic!("Only the beneficiary can release vested tokens");
+    }
+    let amount = state.releasable_amount(context.block_production_time);
+    if amount == 0 {
+        panic!("No vested tokens to release");
+    }
+
+    let event_group = transfer_event(state.token_type, state.beneficiary, amount);
+
+    let mut new_state = state;
+    new_state.released_amount += amount;
+    (new_state, vec![event_group])
+}
+
+/// Action for the owner to revoke the grant.
+/// Throws an error if not called by the `owner`, if the grant is not revocable or if it has
+/// already been revoked.
+/// The tokens vested at the time of revocation remain in the grant, such that the beneficiary can
+/// still release them, while the unvested tokens are transferred back to the owner.
+///
+/// ### Parameters:
+///
+/// * `context`: [`ContractContext`], the context for the action call.
+///
+/// * `state`: [`VestingState`], the current state of the contract.
+///
+/// ### Returns
+///
+/// The new state object of type [`VestingState`] and an event group possibly containing a
+/// transfer event.
+///
+#[action(shortname = 0x04)]
+pub fn revoke(context: ContractContext, state: VestingState) -> (VestingState, Vec<EventGroup>) {
+    if context.sender != state.owner {
+        panic!("Only the owner can revoke the grant");
+    }
+    if !state.revocable {
+        panic!("The grant is not revocable");
+    }
+    if state.revoked {
+        panic!("The grant has already been revoked");
+    }
+
+    let vested_amount = state.vested_amount(context.block_production_time);
+    let unvested_amount = state.total_amount - vested_amount;
+
+    let mut new_state = state;
+    new_state.total_amount = vested_amount;
+    new_state.revoked = true;
+
+    let events = if unvested_amount > 0 {
+        vec![transfer_event(
+            new_state.token_type,
+            new_state.owner,
+            unvested_amount,
+        )]
+    } else {
+        vec![]
+    };
+    (new_state, events)
+}
+
+/// Creates an event group transferring tokens from the contract.
+///
+/// ### Parameters:
+///
+/// * `token_type`: [`Address`], the address of the token to transfer.
+///
+/// * `to`: [`Address`], the receiver of the tokens.
+///
+/// * `amount`: [`u128`], the amount of tokens to transfer.
+///
+/// ### Returns
+///
+/// The event group containing the transfer event.
+fn transfer_event(token_type: Address, to: Address, amount: u128) -> EventGroup {
+    let mut e = EventGroup::builder();
+    e.call(token_type, token_contract_transfer())
+        .argument(to)
+        .argument(amount)
+        .done();
+    e.build()
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
@@ -0,0 +1,151 @@
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+
+use crate::{deposit_callback, initialize, release, revoke, VestingState};
+
+const HOUR_MILLIS: i64 = 3_600_000;
+
+fn create_ctx(sender: Address, block_time: i64) -> ContractContext {
+    let hash: Hash = [1u8; 32];
+    ContractContext {
+        contract_address: get_contract_address(),
+        sender,
+        block_time,
+        block_production_time: block_time * HOUR_MILLIS,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn get_owner_address() -> Address {
+    create_address(AddressType::Account, 1)
+}
+
+fn get_beneficiary_address() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn get_contract_address() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn get_token_address() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn create_callback_ctx(success: bool) -> CallbackContext {
+    CallbackContext {
+        success,
+        results: vec![ExecutionResult {
+            succeeded: success,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// Creates a grant of 1000 tokens starting at hour 0, with a cliff at hour 25 and a vesting
+/// duration of 100 hours.
+fn funded_grant(revocable: bool) -> VestingState {
+    let state = initialize(
+        create_ctx(get_owner_address(), 0),
+        get_beneficiary_address(),
+        get_token_address(),
+        25 * HOUR_MILLIS,
+        100 * HOUR_MILLIS,
+        revocable,
+    );
+    let (state, _) = deposit_callback(
+        create_ctx(get_owner_address(), 0),
+        create_callback_ctx(true),
+        state,
+        1000,
+    );
+    state
+}
+
+fn expected_transfer(to: Address, amount: u128) -> EventGroup {
+    let mut expected_event = EventGroup::builder();
+    expected_event
+        .call(get_token_address(), Shortname::from_u32(1))
+        .argument(to)
+        .argument(amount)
+        .done();
+    expected_event.build()
+}
+
+#[test]
+pub fn test_vested_amount() {
+    let state = funded_grant(false);
+    assert_eq!(state.vested_amount(24 * HOUR_MILLIS), 0);
+    assert_eq!(state.vested_amount(25 * HOUR_MILLIS), 250);
+    assert_eq!(state.vested_amount(50 * HOUR_MILLIS), 500);
+    assert_eq!(state.vested_amount(100 * HOUR_MILLIS), 1000);
+    assert_eq!(state.vested_amount(200 * HOUR_MILLIS), 1000);
+}
+
+#[test]
+pub fn test_release() {
+    let state = funded_grant(false);
+    let (state, events) = release(create_ctx(get_beneficiary_address(), 40), state);
+    assert_eq!(state.released_amount, 400);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_beneficiary_address(), 400)]
+    );
+
+    let (state, events) = release(create_ctx(get_beneficiary_address(), 150), state);
+    assert_eq!(state.released_amount, 1000);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_beneficiary_address(), 600)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "No vested tokens to release")]
+pub fn test_release_before_cliff() {
+    let state = funded_grant(false);
+    release(create_ctx(get_beneficiary_address(), 10), state);
+}
+
+#[test]
+#[should_panic(expected = "Only the beneficiary can release vested tokens")]
+pub fn test_release_not_beneficiary() {
+    let state = funded_grant(false);
+    release(create_ctx(get_owner_address(), 40), state);
+}
+
+#[test]
+pub fn test_revoke() {
+    let state = funded_grant(true);
+    let (state, events) = revoke(create_ctx(get_owner_address(), 30), state);
+    assert!(state.revoked);
+    assert_eq!(state.total_amount, 300);
+    assert_eq!(events, vec![expected_transfer(get_owner_address(), 700)]);
+
+    // The tokens vested at revocation can still be released
+    let (state, events) = release(create_ctx(get_beneficiary_address(), 150), state);
+    assert_eq!(state.released_amount, 300);
+    assert_eq!(
+        events,
+        vec![expected_transfer(get_beneficiary_address(), 300)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "The grant is not revocable")]
+pub fn test_revoke_not_revocable() {
+    let state = funded_grant(false);
+    revoke(create_ctx(get_owner_address(), 30), state);
+}
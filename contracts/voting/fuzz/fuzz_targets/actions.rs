@@ -0,0 +1,64 @@
+//! Fuzzes the actions of the voting contract, invoking the generated entry points of `initialize`
+//! and of every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{arbitrary_address, arbitrary_context, invoke, Call, EntryPointInput};
+use libfuzzer_sys::fuzz_target;
+use voting::{
+    __pbc_autogen__close_registration_wrapped, __pbc_autogen__commit_vote_wrapped,
+    __pbc_autogen__count_wrapped, __pbc_autogen__delegate_wrapped,
+    __pbc_autogen__initialize_wrapped, __pbc_autogen__pause_wrapped,
+    __pbc_autogen__register_voter_wrapped, __pbc_autogen__register_with_stake_callback_wrapped,
+    __pbc_autogen__register_with_stake_wrapped, __pbc_autogen__reopen_wrapped,
+    __pbc_autogen__resume_wrapped, __pbc_autogen__reveal_vote_wrapped,
+    __pbc_autogen__vote_as_contract_callback_wrapped, __pbc_autogen__vote_as_contract_wrapped,
+    __pbc_autogen__vote_by_signature_wrapped, __pbc_autogen__vote_wrapped,
+    __pbc_autogen__withdraw_stake_callback_wrapped, __pbc_autogen__withdraw_stake_wrapped,
+};
+
+const CALLS: [Call; 17] = [
+    Call::Action(__pbc_autogen__vote_wrapped),
+    Call::Action(__pbc_autogen__vote_as_contract_wrapped),
+    Call::Callback(__pbc_autogen__vote_as_contract_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__vote_by_signature_wrapped),
+    Call::Action(__pbc_autogen__commit_vote_wrapped),
+    Call::Action(__pbc_autogen__reveal_vote_wrapped),
+    Call::Action(__pbc_autogen__delegate_wrapped),
+    Call::Action(__pbc_autogen__count_wrapped),
+    Call::Action(__pbc_autogen__pause_wrapped),
+    Call::Action(__pbc_autogen__resume_wrapped),
+    Call::Action(__pbc_autogen__reopen_wrapped),
+    Call::Action(__pbc_autogen__register_voter_wrapped),
+    Call::Action(__pbc_autogen__close_registration_wrapped),
+    Call::Action(__pbc_autogen__register_with_stake_wrapped),
+    Call::Callback(__pbc_autogen__register_with_stake_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__withdraw_stake_wrapped),
+    Call::Callback(__pbc_autogen__withdraw_stake_callback_wrapped, 1),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx).rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,60 @@
 //! Example smart contract implementing a simple majority open ballot vote for a proposal among a fixed list of eligible voters.
 //!
 //! How it works
-//! * The owner of the proposal deploys a Vote smart contract to the blockchain and initializes it.
-//! * Eligible voters can cast their vote until the deadline.
+//! * The owner of the proposal deploys a Vote smart contract to the blockchain and initializes it
+//!   with the options that can be voted for. Each option has an id, and the hash of its label,
+//!   such that frontends can render the label in any language while votes and results only
+//!   reference the option id.
+//! * Eligible voters can cast their vote for an option until the deadline.
+//! * Instead of voting, an eligible voter can delegate their voting power to another eligible voter.
+//!   A vote cast directly always takes precedence over a delegation.
 //! * After the deadline passes anyone can initiate counting of the votes.
+//!   The option receiving the votes of a majority of the eligible voters wins.
+//! * The vote can require a quorum, such that no option wins unless at least a given fraction of
+//!   the eligible voters participated in the vote, directly or through delegation.
+//! * The vote can be configured with a keeper reward, such that the first account to count the
+//!   votes after the deadline receives a reward in tokens. This gives an incentive for counting
+//!   the votes in time. The reward tokens must be transferred to the vote contract beforehand.
+//! * The vote can be configured as an anonymous ballot using commit-reveal. Until the deadline
+//!   voters commit to their vote by submitting the hash of the vote and a secret salt, and after
+//!   the deadline they reveal the vote and the salt before the reveal deadline. Only revealed
+//!   votes matching the commitments of the voters are counted, and counting must wait until the
+//!   reveal deadline has passed.
+//! * The vote can be configured with a notification contract, which receives a vote receipt
+//!   containing the voter and the proposal id whenever a vote is cast or committed. This allows
+//!   external systems to track the turnout in real time.
+//! * The vote can be configured with a registry contract, to which the result is certified when
+//!   the votes are counted, such that the results of many votes can be aggregated centrally.
+//! * A relayer can submit a vote on behalf of a voter, who has signed the vote off-chain with the
+//!   key of their account. This allows voters to participate without paying for the transaction.
+//!   Each signed vote includes a nonce, such that a signed vote can only be submitted once.
+//! * The vote can be configured with a lock time before the deadline, after which votes,
+//!   commitments and delegations can no longer be changed, preventing last-second flips from
+//!   manipulating the outcome. Voters who have not yet participated can still do so until the
+//!   deadline.
+//! * Instead of a fixed list of eligible voters, the vote can be configured with a stake token and
+//!   a stake. During the registration phase voters register themselves by locking the stake in
+//!   the vote contract, such that the same tokens cannot register more than one voter. The
+//!   eligible voters are fixed when the registration closes, and the quorum and majority are
+//!   measured against them. The stakes can be withdrawn once the votes have been counted.
+//! * The owner of the proposal can pause the vote in an emergency, such as a dispute or an error
+//!   discovered in the list of voters. While the vote is paused, no votes, commitments or
+//!   delegations can be made, and the votes cannot be counted. When the owner resumes the vote,
+//!   the deadlines and the vote lock are postponed by the duration of the pause, such that the
+//!   voters keep the time that remained when the vote was paused.
+//! * After the votes have been counted, the owner can reopen the vote with a new deadline, such
+//!   that a revised proposal can be voted on again. The votes, delegations and result of the
+//!   counted round are archived in the history of the vote, and the voters start over.
+//! * The vote can start with a registration phase, during which the voter list is built before
+//!   the voting starts. Depending on the configured criterion, any address or any account can
+//!   register itself as a voter, while the owner can register any address. The registration is
+//!   closed by the owner, or by anyone once the registration deadline has passed, after which the
+//!   voting phase runs until the deadline of the vote.
+//! * Contracts can be eligible voters, such as a multisig or a DAO voting on behalf of its
+//!   members. Anyone can submit the vote of a contract voter, and the vote contract asks the voter
+//!   contract to confirm that it consents to the vote before recording it.
+//! * A vote deployed by a multi-voting contract can be configured with the multi-voting contract
+//!   as its parent, such that the result is pushed to the parent when the votes are counted.
 #![allow(unused_variables)]
 
 #[macro_use]
@@ -12,93 +63,1003 @@ extern crate pbc_contract_common;
 
 use std::collections::{BTreeMap, BTreeSet};
 
-use pbc_contract_common::address::Address;
-use pbc_contract_common::context::ContractContext;
+use create_type_spec_derive::CreateTypeSpec;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use pbc_traits::{ReadRPC, WriteRPC};
+use read_write_rpc_derive::ReadWriteRPC;
+use read_write_state_derive::ReadWriteState;
+use sha2::{Digest, Sha256};
+
+#[cfg(test)]
+mod tests;
+
+/// An option that can be voted for.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct OptionSpec {
+    /// The id of the option, referenced by votes and results.
+    pub id: u32,
+    /// The hash of the label of the option.
+    pub label_hash: Hash,
+}
+
+/// The reward given to the account counting the votes.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct KeeperReward {
+    /// The address of the token the reward is paid in.
+    pub token: Address,
+    /// The amount of tokens rewarded.
+    pub amount: u128,
+}
+
+/// The stake deciding the eligibility of voters, instead of a fixed list of voters.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct StakingEligibility {
+    /// The address of the token staked by voters.
+    pub stake_token: Address,
+    /// The amount of stake tokens each voter locks in the vote contract to register.
+    pub stake: u128,
+}
+
+/// The criterion an address must meet to register itself as a voter during the registration
+/// phase. The owner can register any address, regardless of the criterion.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum RegistrationCriterion {
+    /// Any address can register itself.
+    #[discriminant(0)]
+    Anyone {},
+    /// Any account can register itself, while contracts must be registered by the owner.
+    #[discriminant(1)]
+    Accounts {},
+    /// Only the owner can register voters.
+    #[discriminant(2)]
+    OwnerApproval {},
+}
+
+/// The registration phase preceding the voting phase.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct RegistrationWindow {
+    /// The deadline of the registration in UTC millis, after which anyone can close it.
+    pub deadline_utc_millis: i64,
+    /// The criterion an address must meet to register itself.
+    pub criterion: RegistrationCriterion,
+}
+
+/// The phase of the vote.
+#[derive(ReadWriteState, CreateTypeSpec, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum VotePhase {
+    /// Voters are registering, and no votes can be cast.
+    #[discriminant(0)]
+    Registration {},
+    /// The voter list is fixed, and the voters can vote until the deadline.
+    #[discriminant(1)]
+    Voting {},
+}
+
+/// The fraction of the eligible voters required to participate for the vote to be decided.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+pub struct QuorumFraction {
+    /// The numerator of the fraction.
+    pub numerator: u32,
+    /// The denominator of the fraction.
+    pub denominator: u32,
+}
+
+impl QuorumFraction {
+    /// Determines whether `participants` out of `eligible` voters meets the quorum.
+    fn is_met(&self, participants: u32, eligible: u32) -> bool {
+        participants as u64 * self.denominator as u64 >= eligible as u64 * self.numerator as u64
+    }
+}
+
+/// A signature made with the secp256k1 key of an account, in the format used for signing
+/// transactions on the blockchain.
+#[derive(ReadWriteRPC, CreateTypeSpec, Clone)]
+pub struct VoteSignature {
+    /// The recovery id of the signature.
+    pub recovery_id: u8,
+    /// The r value of the signature.
+    pub value_r: [u8; 32],
+    /// The s value of the signature.
+    pub value_s: [u8; 32],
+}
+
+/// The status of a counted vote.
+#[derive(ReadWriteState, CreateTypeSpec, Clone, PartialEq, Eq)]
+pub enum ResultStatus {
+    /// An option got the votes of a majority of the eligible voters.
+    #[discriminant(0)]
+    Decided {},
+    /// No option got the votes of a majority of the eligible voters.
+    #[discriminant(1)]
+    NoMajority {},
+    /// Too few eligible voters participated in the vote.
+    #[discriminant(2)]
+    QuorumNotMet {},
+}
+
+/// The number of votes counted for an option.
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct OptionTally {
+    /// The id of the option.
+    pub option_id: u32,
+    /// The number of voters whose vote counted for the option.
+    pub votes: u32,
+}
+
+/// The result of a counted vote.
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct VoteResult {
+    /// The number of votes for each option, in the order of the options.
+    pub tally: Vec<OptionTally>,
+    /// The id of the option voted for by a majority of the eligible voters,
+    /// or None if no option got a majority or the quorum was not met.
+    pub winning_option: Option<u32>,
+    /// The status of the vote.
+    pub status: ResultStatus,
+}
+
+/// A counted round of the vote, archived when the vote is reopened.
+#[derive(ReadWriteState, CreateTypeSpec, Clone)]
+pub struct VoteRound {
+    /// The deadline of the round in UTC millis.
+    pub deadline_utc_millis: i64,
+    /// The votes cast by the voters in the round.
+    pub votes: BTreeMap<Address, u32>,
+    /// The delegations made by the voters in the round.
+    pub delegations: BTreeMap<Address, Address>,
+    /// The result of the round.
+    pub result: VoteResult,
+}
 
 /// The state of the vote, which is persisted on-chain.
 #[state]
 pub struct VoteState {
     /// Identification of the proposal being voted for.
     pub proposal_id: u64,
-    /// The list of eligible voters.
+    /// The list of eligible voters. With staking eligibility this is the voters who have locked
+    /// their stake during the registration phase.
     pub voters: Vec<Address>,
     /// The deadline of the vote in UTC millis
     /// (milliseconds after 1970-01-01 00:00:00 UTC)
     pub deadline_utc_millis: i64,
-    /// The votes cast by the voters.
-    /// true is for the proposal, false is against.
-    pub votes: BTreeMap<Address, bool>,
+    /// The options that can be voted for.
+    pub options: Vec<OptionSpec>,
+    /// The votes cast by the voters, referencing the id of the option voted for.
+    pub votes: BTreeMap<Address, u32>,
+    /// The delegations made by the voters.
+    /// Maps a voter to the voter that they have delegated their voting power to.
+    pub delegations: BTreeMap<Address, Address>,
     /// The result of the vote.
-    /// None until the votes has been counted,
-    /// Some(true) if the proposal passed,
-    /// Some(false) if the proposal failed.
-    pub result: Option<bool>,
+    /// None until the votes has been counted.
+    pub result: Option<VoteResult>,
+    /// The reward given to the account counting the votes, if any.
+    pub keeper_reward: Option<KeeperReward>,
+    /// The fraction of the eligible voters required to participate in the vote.
+    pub quorum: QuorumFraction,
+    /// The deadline in UTC millis for revealing committed votes,
+    /// or None if the vote is an open ballot.
+    pub reveal_deadline_utc_millis: Option<i64>,
+    /// The vote commitments made by the voters in an anonymous ballot.
+    /// Maps a voter to the hash of their vote and salt.
+    pub commitments: BTreeMap<Address, Hash>,
+    /// The contract receiving a vote receipt whenever a vote is cast or committed, if any.
+    pub notification_contract: Option<Address>,
+    /// The registry contract the result is certified to when the votes are counted, if any.
+    pub registry_address: Option<Address>,
+    /// The nonces of the voters, being the number of signed votes submitted for each voter.
+    /// The next signed vote of a voter must be signed with their current nonce.
+    pub vote_nonces: BTreeMap<Address, u64>,
+    /// The time in UTC millis after which votes, commitments and delegations can no longer be
+    /// changed, or None if they can be changed until the deadline.
+    pub lock_votes_after_millis: Option<i64>,
+    /// The time in UTC millis at which each voter last cast a vote, committed to a vote or
+    /// delegated their voting power.
+    pub vote_times: BTreeMap<Address, i64>,
+    /// The stake deciding the eligibility of voters, or None if the voters are fixed.
+    pub staking_eligibility: Option<StakingEligibility>,
+    /// The stakes locked in the vote contract by the voters who registered with staking
+    /// eligibility, until they are withdrawn.
+    pub stakes: BTreeMap<Address, u128>,
+    /// The owner of the proposal, who can pause and resume the vote.
+    pub owner: Address,
+    /// The time in UTC millis at which the vote was paused, or None if the vote is not paused.
+    pub paused_at_millis: Option<i64>,
+    /// The accumulated duration in millis of the pauses of the vote, by which the deadlines have
+    /// been postponed.
+    pub total_paused_millis: i64,
+    /// The earlier rounds of the vote, in the order they were counted.
+    pub history: Vec<VoteRound>,
+    /// The registration phase preceding the voting phase, if any.
+    pub registration: Option<RegistrationWindow>,
+    /// The current phase of the vote.
+    pub phase: VotePhase,
+    /// The multi-voting contract the result is pushed to when the votes are counted, if any.
+    pub parent: Option<Address>,
+}
+
+impl VoteState {
+    /// Resolves the vote that counts for `voter`, by following the chain of delegations
+    /// until a voter who has voted directly is reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the voter to resolve the vote for.
+    ///
+    /// # Returns
+    ///
+    /// The resolved vote, or None if the chain ends without a vote or contains a cycle.
+    ///
+    fn resolve_vote(&self, voter: &Address) -> Option<u32> {
+        let mut visited: BTreeSet<Address> = BTreeSet::new();
+        let mut current = voter;
+        loop {
+            if let Some(vote) = self.votes.get(current) {
+                return Some(*vote);
+            }
+            if !visited.insert(*current) {
+                return None;
+            }
+            current = self.delegations.get(current)?;
+        }
+    }
+
+    /// Asserts that `voter` can cast a vote for `option_id` at `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the voter casting the vote.
+    /// * `option_id` - the id of the option voted for.
+    /// * `now` - the current time in UTC millis.
+    ///
+    fn assert_can_vote(&self, voter: &Address, option_id: u32, now: i64) {
+        self.assert_ballot_open(option_id, now);
+        assert!(self.voters.contains(voter), "Not an eligible voter");
+        self.assert_not_locked(voter, now);
+    }
+
+    /// Asserts that votes for `option_id` can be cast at `now`, regardless of the voter.
+    ///
+    /// # Arguments
+    ///
+    /// * `option_id` - the id of the option voted for.
+    /// * `now` - the current time in UTC millis.
+    ///
+    fn assert_ballot_open(&self, option_id: u32, now: i64) {
+        assert!(
+            self.result.is_none() && now < self.deadline_utc_millis,
+            "The deadline has passed"
+        );
+        self.assert_not_paused();
+        self.assert_voting_phase();
+        assert!(
+            !self.is_anonymous(),
+            "Votes must be committed in an anonymous ballot"
+        );
+        assert!(
+            self.options.iter().any(|option| option.id == option_id),
+            "Unknown option id {option_id}"
+        );
+    }
+
+    /// Records the vote of `voter` for `option_id` cast at `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the voter casting the vote.
+    /// * `option_id` - the id of the option voted for.
+    /// * `now` - the current time in UTC millis.
+    ///
+    /// # Returns
+    ///
+    /// The vote receipt sent to the notification contract.
+    ///
+    fn record_vote(&mut self, voter: Address, option_id: u32, now: i64) -> Vec<EventGroup> {
+        self.votes.insert(voter, option_id);
+        self.vote_times.insert(voter, now);
+        self.vote_receipt(voter)
+    }
+
+    /// Asserts that `voter` can still change their participation at `now`, which is the case
+    /// unless the votes are locked and the voter has already participated.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the voter casting a vote, committing to a vote or delegating.
+    /// * `now` - the current time in UTC millis.
+    ///
+    fn assert_not_locked(&self, voter: &Address, now: i64) {
+        if let Some(lock_time) = self.lock_votes_after_millis {
+            assert!(
+                now < lock_time || !self.vote_times.contains_key(voter),
+                "Votes are locked and can no longer be changed"
+            );
+        }
+    }
+
+    /// Asserts that the vote is not paused.
+    fn assert_not_paused(&self) {
+        assert!(self.paused_at_millis.is_none(), "The vote is paused");
+    }
+
+    /// Asserts that the registration of voters has been closed, if the vote has a registration
+    /// phase.
+    fn assert_voting_phase(&self) {
+        assert!(
+            self.phase == VotePhase::Voting {},
+            "The registration of voters has not been closed"
+        );
+    }
+
+    /// Returns the deadline of the registration, panicking unless the vote is in the
+    /// registration phase.
+    fn registration_deadline(&self) -> i64 {
+        assert!(
+            self.phase == VotePhase::Registration {},
+            "The registration of voters is closed"
+        );
+        self.registration.as_ref().unwrap().deadline_utc_millis
+    }
+
+    /// Asserts that `voter` can register itself during the registration phase at `now`, by the
+    /// registration criterion of the vote.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the address registering itself as a voter.
+    /// * `now` - the current time in UTC millis.
+    ///
+    fn assert_can_register_itself(&self, voter: &Address, now: i64) {
+        assert!(
+            now < self.registration_deadline(),
+            "The registration deadline has passed"
+        );
+        self.assert_not_paused();
+        match self.registration.as_ref().unwrap().criterion {
+            RegistrationCriterion::Anyone {} => {}
+            RegistrationCriterion::Accounts {} => assert_eq!(
+                voter.address_type,
+                AddressType::Account,
+                "Only accounts can register themselves"
+            ),
+            RegistrationCriterion::OwnerApproval {} => {
+                panic!("Voters must be registered by the owner")
+            }
+        }
+        assert!(
+            !self.voters.contains(voter),
+            "The voter is already registered"
+        );
+    }
+
+    /// Determines whether the vote is an anonymous ballot using commit-reveal.
+    fn is_anonymous(&self) -> bool {
+        self.reveal_deadline_utc_millis.is_some()
+    }
+
+    /// Creates the events sending a vote receipt for `voter` to the notification contract.
+    ///
+    /// # Arguments
+    ///
+    /// * `voter` - the voter who cast or committed a vote.
+    ///
+    /// # Returns
+    ///
+    /// The event group calling the notification contract, or no events if the vote has no
+    /// notification contract.
+    ///
+    fn vote_receipt(&self, voter: Address) -> Vec<EventGroup> {
+        match self.notification_contract {
+            Some(notification_contract) => {
+                let mut event_group = EventGroup::builder();
+                event_group
+                    .call(notification_contract, notification_contract_vote_receipt())
+                    .argument(voter)
+                    .argument(self.proposal_id)
+                    .done();
+                vec![event_group.build()]
+            }
+            None => vec![],
+        }
+    }
+
+    /// Creates the events certifying the result of the vote to the registry contract.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - the result of the counted vote.
+    /// * `participants` - the number of eligible voters whose vote was counted.
+    ///
+    /// # Returns
+    ///
+    /// The event group calling the registry contract, or no events if the vote has no registry.
+    ///
+    fn certify_result(&self, result: &VoteResult, participants: u32) -> Vec<EventGroup> {
+        match self.registry_address {
+            Some(registry_address) => {
+                let votes_for: u32 = result
+                    .tally
+                    .iter()
+                    .filter(|option| Some(option.option_id) == result.winning_option)
+                    .map(|option| option.votes)
+                    .sum();
+                let mut event_group = EventGroup::builder();
+                event_group
+                    .call(registry_address, registry_contract_certify_result())
+                    .argument(self.proposal_id)
+                    .argument(result.winning_option)
+                    .argument(votes_for)
+                    .argument(participants - votes_for)
+                    .done();
+                vec![event_group.build()]
+            }
+            None => vec![],
+        }
+    }
+
+    /// Creates the events pushing the result of the vote to the parent multi-voting contract.
+    /// The proposal passed if an option won the vote.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - the result of the counted vote.
+    ///
+    /// # Returns
+    ///
+    /// The event group calling the parent contract, or no events if the vote has no parent.
+    ///
+    fn push_result(&self, result: &VoteResult) -> Vec<EventGroup> {
+        match self.parent {
+            Some(parent) => {
+                let mut event_group = EventGroup::builder();
+                event_group
+                    .call(parent, parent_contract_receive_result())
+                    .argument(self.proposal_id)
+                    .argument(result.winning_option.is_some())
+                    .done();
+                vec![event_group.build()]
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// Computes the commitment to a vote, which is the SHA-256 hash of the big-endian bytes of the
+/// option id followed by the salt.
+///
+/// # Arguments
+///
+/// * `option_id` - the id of the option voted for.
+/// * `salt` - the secret salt chosen by the voter.
+///
+/// # Returns
+///
+/// The commitment to the vote.
+///
+pub fn vote_commitment(option_id: u32, salt: &Hash) -> Hash {
+    let mut preimage: Vec<u8> = option_id.to_be_bytes().to_vec();
+    preimage.extend_from_slice(salt);
+    Sha256::digest(preimage).into()
+}
+
+/// Computes the hash signed by a voter to vote by signature, which is the SHA-256 hash of the RPC
+/// serialization of the vote contract, the proposal id, the voter, the option id, the nonce and
+/// the expiry. Including the vote contract and the proposal id prevents the signed vote from being
+/// submitted to any other vote.
+///
+/// # Arguments
+///
+/// * `vote_contract` - the address of the vote contract.
+/// * `proposal_id` - the id of the proposal voted on.
+/// * `voter` - the voter signing the vote.
+/// * `option_id` - the id of the option voted for.
+/// * `nonce` - the current nonce of the voter.
+/// * `expiry_utc_millis` - the time in UTC millis after which the signed vote cannot be submitted.
+///
+/// # Returns
+///
+/// The hash to be signed by the voter.
+///
+pub fn signed_vote_hash(
+    vote_contract: &Address,
+    proposal_id: u64,
+    voter: &Address,
+    option_id: u32,
+    nonce: u64,
+    expiry_utc_millis: i64,
+) -> Hash {
+    let mut preimage: Vec<u8> = vec![];
+    vote_contract.rpc_write_to(&mut preimage).unwrap();
+    proposal_id.rpc_write_to(&mut preimage).unwrap();
+    voter.rpc_write_to(&mut preimage).unwrap();
+    option_id.rpc_write_to(&mut preimage).unwrap();
+    nonce.rpc_write_to(&mut preimage).unwrap();
+    expiry_utc_millis.rpc_write_to(&mut preimage).unwrap();
+    Sha256::digest(preimage).into()
+}
+
+/// Recovers the account that made a signature, whose address is the last 20 bytes of the SHA-256
+/// hash of the compressed public key of the account.
+///
+/// # Arguments
+///
+/// * `message_hash` - the hash that was signed.
+/// * `signature` - the signature of the hash.
+///
+/// # Returns
+///
+/// The address of the signing account, or None if the signature is invalid.
+///
+pub fn recover_signer(message_hash: &Hash, signature: &VoteSignature) -> Option<Address> {
+    let mut signature_bytes: Vec<u8> = signature.value_r.to_vec();
+    signature_bytes.extend_from_slice(&signature.value_s);
+    let ecdsa_signature = Signature::from_slice(&signature_bytes).ok()?;
+    let recovery_id = RecoveryId::from_byte(signature.recovery_id)?;
+    let public_key =
+        VerifyingKey::recover_from_prehash(message_hash, &ecdsa_signature, recovery_id).ok()?;
+    let public_key_hash: Hash = Sha256::digest(public_key.to_sec1_bytes()).into();
+    let mut identifier = [0u8; 20];
+    identifier.copy_from_slice(&public_key_hash[12..]);
+    Some(Address {
+        address_type: AddressType::Account,
+        identifier,
+    })
 }
 
 /// Initialize a new vote for a proposal
 ///
 /// # Arguments
 ///
-/// * `_ctx` - the contract context containing information about the sender and the blockchain.
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+///   The sender becomes the owner of the proposal.
 /// * `proposal_id` - the id of the proposal.
 /// * `voters` - the list of eligible voters.
 /// * `deadline_utc_millis` - deadline of the vote in UTC millis.
+/// * `options` - the options that can be voted for. Option ids must be unique.
+/// * `keeper_reward` - the reward given to the account counting the votes, if any.
+/// * `quorum` - the fraction of the eligible voters required to participate in the vote.
+///   A quorum of 0 means that no quorum is required.
+/// * `reveal_deadline_utc_millis` - the deadline for revealing committed votes in UTC millis,
+///   making the vote an anonymous ballot, or None for an open ballot.
+/// * `notification_contract` - the contract receiving vote receipts, if any. Vote receipts are
+///   sent to the action with shortname 0x01, with the voter and the proposal id as arguments.
+/// * `registry_address` - the registry contract the result is certified to, if any. Results are
+///   sent to the action with shortname 0x01, with the proposal id, the winning option, the votes
+///   for the winning option and the votes against it as arguments.
+/// * `lock_votes_after_millis` - the time in UTC millis after which votes, commitments and
+///   delegations can no longer be changed, or None if they can be changed until the deadline.
+/// * `staking_eligibility` - the stake deciding the eligibility of voters, if any, in which case
+///   `voters` must be empty and a registration phase is required, during which voters register
+///   themselves by locking the stake, see [`register_with_stake`]. The registration criterion
+///   cannot require owner approval. Staking eligibility cannot be used in an anonymous ballot.
+/// * `registration` - the registration phase preceding the voting phase, if any, in which case
+///   `voters` are the voters registered up front and may be empty. The registration deadline
+///   cannot be after the deadline.
+/// * `parent` - the multi-voting contract the result is pushed to when the votes are counted, if
+///   any. Results are sent to the action with shortname 0x01, with the proposal id and whether
+///   the proposal passed as arguments.
 ///
 /// # Returns
 ///
 /// The initial state of the vote.
 ///
 #[init]
+#[allow(clippy::too_many_arguments)]
 pub fn initialize(
-    _ctx: ContractContext,
+    ctx: ContractContext,
     proposal_id: u64,
     voters: Vec<Address>,
     deadline_utc_millis: i64,
+    options: Vec<OptionSpec>,
+    keeper_reward: Option<KeeperReward>,
+    quorum: QuorumFraction,
+    reveal_deadline_utc_millis: Option<i64>,
+    notification_contract: Option<Address>,
+    registry_address: Option<Address>,
+    lock_votes_after_millis: Option<i64>,
+    staking_eligibility: Option<StakingEligibility>,
+    registration: Option<RegistrationWindow>,
+    parent: Option<Address>,
 ) -> VoteState {
-    assert_ne!(voters.len(), 0, "Voters are required");
+    if staking_eligibility.is_some() {
+        assert_eq!(
+            voters.len(),
+            0,
+            "Voters cannot be given when eligibility is decided by staking"
+        );
+        assert!(
+            reveal_deadline_utc_millis.is_none(),
+            "Staking eligibility cannot be used in an anonymous ballot"
+        );
+        let criterion = registration
+            .as_ref()
+            .expect("Staking eligibility requires a registration phase")
+            .criterion;
+        assert!(
+            criterion != RegistrationCriterion::OwnerApproval {},
+            "Voters must be able to register themselves with staking eligibility"
+        );
+    } else if registration.is_none() {
+        assert_ne!(voters.len(), 0, "Voters are required");
+    }
+    if let Some(registration) = &registration {
+        assert!(
+            registration.deadline_utc_millis <= deadline_utc_millis,
+            "The registration deadline cannot be after the deadline"
+        );
+    }
+    assert!(options.len() >= 2, "At least two options are required");
+    let unique_options: BTreeSet<u32> = options.iter().map(|option| option.id).collect();
+    assert_eq!(
+        options.len(),
+        unique_options.len(),
+        "All option ids must be unique"
+    );
     let unique_voters: BTreeSet<Address> = voters.iter().cloned().collect();
     assert_eq!(
         voters.len(),
         unique_voters.len(),
         "All voters must be unique"
     );
+    assert!(
+        quorum.denominator > 0 && quorum.numerator <= quorum.denominator,
+        "The quorum must be a fraction between 0 and 1"
+    );
+    if let Some(reveal_deadline) = reveal_deadline_utc_millis {
+        assert!(
+            reveal_deadline >= deadline_utc_millis,
+            "The reveal deadline cannot be before the deadline"
+        );
+    }
+    if let Some(lock_time) = lock_votes_after_millis {
+        assert!(
+            lock_time <= deadline_utc_millis,
+            "The vote lock cannot be after the deadline"
+        );
+    }
     VoteState {
         proposal_id,
         voters,
         deadline_utc_millis,
+        options,
         votes: BTreeMap::new(),
+        delegations: BTreeMap::new(),
         result: None,
+        keeper_reward,
+        quorum,
+        reveal_deadline_utc_millis,
+        commitments: BTreeMap::new(),
+        notification_contract,
+        registry_address,
+        vote_nonces: BTreeMap::new(),
+        lock_votes_after_millis,
+        vote_times: BTreeMap::new(),
+        staking_eligibility,
+        stakes: BTreeMap::new(),
+        owner: ctx.sender,
+        paused_at_millis: None,
+        total_paused_millis: 0,
+        history: vec![],
+        phase: if registration.is_some() {
+            VotePhase::Registration {}
+        } else {
+            VotePhase::Voting {}
+        },
+        registration,
+        parent,
     }
 }
 
-/// Cast a vote for the proposal.
+/// Cast a vote for an option.
 /// The vote is cast by the sender of the action.
-/// Voters can cast and update their vote until the deadline.
+/// Voters can cast and update their vote until the deadline, unless the votes are locked.
+/// Votes cannot be cast directly in an anonymous ballot, see [`commit_vote`].
 ///
 /// # Arguments
 ///
 /// * `ctx` - the contract context containing information about the sender and the blockchain.
 /// * `state` - the current state of the vote.
-/// * `vote` - the vote being cast by the sender.
+/// * `option_id` - the id of the option voted for by the sender.
 ///
 /// # Returns
 ///
-/// The updated vote state reflecting the newly cast vote.
+/// The updated vote state reflecting the newly cast vote, and the vote receipt sent to the
+/// notification contract.
 ///
 #[action(shortname = 0x01)]
-pub fn vote(ctx: ContractContext, state: VoteState, vote: bool) -> VoteState {
+pub fn vote(
+    ctx: ContractContext,
+    state: VoteState,
+    option_id: u32,
+) -> (VoteState, Vec<EventGroup>) {
+    state.assert_can_vote(&ctx.sender, option_id, ctx.block_production_time);
+    let mut new_state = state;
+    let events = new_state.record_vote(ctx.sender, option_id, ctx.block_production_time);
+    (new_state, events)
+}
+
+/// Cast a vote for an option on behalf of a contract voter.
+/// Anyone can submit the vote of a contract voter, which asks the voter contract to confirm the
+/// vote. The voter contract is called on the action with shortname 0x01, with the proposal id, the
+/// option id and the sender as arguments, and must return a `bool` consenting to the vote. The vote
+/// is recorded in [`vote_as_contract_callback`] once the voter contract has consented, and the same
+/// rules as in [`vote`] apply to the vote.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `voter_contract` - the contract voter voting.
+/// * `option_id` - the id of the option voted for by the voter contract.
+///
+/// # Returns
+///
+/// The unchanged vote state, and the request for consent sent to the voter contract.
+///
+#[action(shortname = 0x08)]
+pub fn vote_as_contract(
+    ctx: ContractContext,
+    state: VoteState,
+    voter_contract: Address,
+    option_id: u32,
+) -> (VoteState, Vec<EventGroup>) {
+    assert_ne!(
+        voter_contract.address_type,
+        AddressType::Account,
+        "Accounts must cast their votes directly"
+    );
+    state.assert_can_vote(&voter_contract, option_id, ctx.block_production_time);
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(voter_contract, voter_contract_confirm_vote())
+        .argument(state.proposal_id)
+        .argument(option_id)
+        .argument(ctx.sender)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_VOTE_AS_CONTRACT_CALLBACK)
+        .argument(voter_contract)
+        .argument(option_id)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Called with the consent of a contract voter to a vote submitted with [`vote_as_contract`].
+/// If the voter contract consented, the vote is recorded. The vote must still be allowed when the
+/// consent is received.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the consent returned by the voter contract.
+/// * `state` - the current state of the vote.
+/// * `voter_contract` - the contract voter voting.
+/// * `option_id` - the id of the option voted for by the voter contract.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the newly cast vote, and the vote receipt sent to the
+/// notification contract.
+///
+#[callback(shortname = 0x09)]
+pub fn vote_as_contract_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VoteState,
+    voter_contract: Address,
+    option_id: u32,
+) -> (VoteState, Vec<EventGroup>) {
+    assert!(
+        callback_ctx.success,
+        "The voter contract did not confirm the vote"
+    );
+    let consent = bool::rpc_read_from(&mut callback_ctx.results[0].return_data.as_slice());
+    assert!(consent, "The voter contract did not consent to the vote");
+    state.assert_can_vote(&voter_contract, option_id, ctx.block_production_time);
+    let mut new_state = state;
+    let events = new_state.record_vote(voter_contract, option_id, ctx.block_production_time);
+    (new_state, events)
+}
+
+/// Cast a vote for an option on behalf of a voter, who has signed the vote off-chain.
+/// Anyone can submit a signed vote, allowing a relayer to pay for the transaction of the voter.
+/// The signature must be made on the hash computed by [`signed_vote_hash`], using the current
+/// nonce of the voter, which is incremented when the vote is cast. The signed vote cannot be
+/// submitted after it expires, and the same rules as in [`vote`] apply to the vote.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `voter` - the voter who signed the vote.
+/// * `option_id` - the id of the option voted for by the voter.
+/// * `expiry_utc_millis` - the time in UTC millis after which the signed vote cannot be submitted.
+/// * `signature` - the signature of the voter.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the newly cast vote, and the vote receipt sent to the
+/// notification contract.
+///
+#[action(shortname = 0x06)]
+pub fn vote_by_signature(
+    ctx: ContractContext,
+    state: VoteState,
+    voter: Address,
+    option_id: u32,
+    expiry_utc_millis: i64,
+    signature: VoteSignature,
+) -> (VoteState, Vec<EventGroup>) {
+    assert!(
+        ctx.block_production_time < expiry_utc_millis,
+        "The signed vote has expired"
+    );
+    state.assert_can_vote(&voter, option_id, ctx.block_production_time);
+    let nonce = state.vote_nonces.get(&voter).copied().unwrap_or(0);
+    let message_hash = signed_vote_hash(
+        &ctx.contract_address,
+        state.proposal_id,
+        &voter,
+        option_id,
+        nonce,
+        expiry_utc_millis,
+    );
+    assert_eq!(
+        recover_signer(&message_hash, &signature),
+        Some(voter),
+        "The signature was not made by the voter"
+    );
+    let mut new_state = state;
+    new_state.vote_nonces.insert(voter, nonce + 1);
+    let events = new_state.record_vote(voter, option_id, ctx.block_production_time);
+    (new_state, events)
+}
+
+/// Commit to a vote in an anonymous ballot.
+/// The commitment is the hash of the vote and a secret salt, see [`vote_commitment`].
+/// Voters can commit and update their commitment until the deadline, unless the votes are locked.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `commitment` - the commitment to the vote of the sender.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the new commitment, and the vote receipt sent to the
+/// notification contract.
+///
+#[action(shortname = 0x04)]
+pub fn commit_vote(
+    ctx: ContractContext,
+    state: VoteState,
+    commitment: Hash,
+) -> (VoteState, Vec<EventGroup>) {
+    assert!(state.is_anonymous(), "The vote is an open ballot");
+    assert!(
+        ctx.block_production_time < state.deadline_utc_millis,
+        "The deadline has passed"
+    );
+    state.assert_not_paused();
+    state.assert_voting_phase();
+    assert!(state.voters.contains(&ctx.sender), "Not an eligible voter");
+    state.assert_not_locked(&ctx.sender, ctx.block_production_time);
+    let mut new_state = state;
+    new_state.commitments.insert(ctx.sender, commitment);
+    new_state
+        .vote_times
+        .insert(ctx.sender, ctx.block_production_time);
+    let events = new_state.vote_receipt(ctx.sender);
+    (new_state, events)
+}
+
+/// Reveal a committed vote in an anonymous ballot.
+/// Votes can be revealed after the deadline and until the reveal deadline.
+/// The revealed vote and salt must match the commitment of the sender.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `option_id` - the id of the option voted for by the sender.
+/// * `salt` - the salt used in the commitment of the sender.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the revealed vote.
+///
+#[action(shortname = 0x05)]
+pub fn reveal_vote(
+    ctx: ContractContext,
+    state: VoteState,
+    option_id: u32,
+    salt: Hash,
+) -> VoteState {
+    let reveal_deadline = state
+        .reveal_deadline_utc_millis
+        .expect("The vote is an open ballot");
+    assert!(
+        ctx.block_production_time >= state.deadline_utc_millis,
+        "Votes cannot be revealed before the deadline"
+    );
+    assert!(
+        ctx.block_production_time < reveal_deadline,
+        "The reveal deadline has passed"
+    );
+    let commitment = state
+        .commitments
+        .get(&ctx.sender)
+        .expect("No commitment to reveal");
+    assert_eq!(
+        *commitment,
+        vote_commitment(option_id, &salt),
+        "The revealed vote does not match the commitment"
+    );
+    assert!(
+        state.options.iter().any(|option| option.id == option_id),
+        "Unknown option id {option_id}"
+    );
+    let mut new_state = state;
+    new_state.votes.insert(ctx.sender, option_id);
+    new_state
+}
+
+/// Delegate the voting power of the sender to another eligible voter.
+/// Voters can change their delegation until the deadline, unless the votes are locked.
+/// A delegation is ignored if the sender casts a vote directly.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `to` - the voter receiving the voting power of the sender.
+///
+/// # Returns
+///
+/// The updated vote state reflecting the new delegation.
+///
+#[action(shortname = 0x03)]
+pub fn delegate(ctx: ContractContext, state: VoteState, to: Address) -> VoteState {
     assert!(
         state.result.is_none() && ctx.block_production_time < state.deadline_utc_millis,
         "The deadline has passed"
     );
+    state.assert_not_paused();
+    state.assert_voting_phase();
     assert!(state.voters.contains(&ctx.sender), "Not an eligible voter");
+    assert!(
+        state.voters.contains(&to),
+        "Cannot delegate to a non-eligible voter"
+    );
+    assert_ne!(ctx.sender, to, "Cannot delegate to oneself");
+    state.assert_not_locked(&ctx.sender, ctx.block_production_time);
     let mut new_state = state;
-    new_state.votes.insert(ctx.sender, vote);
+    new_state.delegations.insert(ctx.sender, to);
+    new_state
+        .vote_times
+        .insert(ctx.sender, ctx.block_production_time);
     new_state
 }
 
 /// Count the votes and publish the result.
-/// Counting will fail if the deadline has not passed.
+/// Counting will fail if the deadline has not passed, or if the vote is paused.
+/// In an anonymous ballot counting will fail if the reveal deadline has not passed,
+/// and only the revealed votes are counted.
+/// The winning option is the option voted for by a majority of the eligible voters, if any.
+/// If the quorum is not met no option wins, regardless of the votes.
+/// If the vote has a keeper reward, the reward is transferred to the sender.
+/// If the vote has a registry, the result is certified to the registry with the votes for the
+/// winning option, which are 0 if no option won, and the votes counted for any other option.
+/// If the vote has a parent, the result is pushed to the parent, the proposal passing if an option
+/// won.
+/// Delegated voting power is counted towards the vote found at the end of the delegation chain.
+/// Voting power in delegation chains without a vote, or with a cycle, is not counted.
 ///
 /// # Arguments
 ///
@@ -107,18 +1068,454 @@ pub fn vote(ctx: ContractContext, state: VoteState, vote: bool) -> VoteState {
 ///
 /// # Returns
 ///
-/// The updated state reflecting the result of the vote.
+/// The updated state reflecting the result of the vote, the transfer of the keeper reward and the
+/// certification of the result to the registry and the parent.
 ///
 #[action(shortname = 0x02)]
-pub fn count(ctx: ContractContext, state: VoteState) -> VoteState {
-    assert_eq!(state.result, None, "The votes have already been counted");
+pub fn count(ctx: ContractContext, state: VoteState) -> (VoteState, Vec<EventGroup>) {
+    assert!(
+        state.result.is_none(),
+        "The votes have already been counted"
+    );
+    state.assert_not_paused();
+    state.assert_voting_phase();
     assert!(
         ctx.block_production_time >= state.deadline_utc_millis,
         "The deadline has not yet passed"
     );
-    let voters_approving = state.votes.values().filter(|vote| **vote).count();
-    let vote_passed = voters_approving > state.voters.len() / 2;
+    if let Some(reveal_deadline) = state.reveal_deadline_utc_millis {
+        assert!(
+            ctx.block_production_time >= reveal_deadline,
+            "The reveal deadline has not yet passed"
+        );
+    }
+    let tally: Vec<OptionTally> = state
+        .options
+        .iter()
+        .map(|option| OptionTally {
+            option_id: option.id,
+            votes: state
+                .voters
+                .iter()
+                .filter(|voter| state.resolve_vote(voter) == Some(option.id))
+                .count() as u32,
+        })
+        .collect();
+    let participants: u32 = tally.iter().map(|option| option.votes).sum();
+    let majority_option = tally
+        .iter()
+        .find(|option| option.votes as usize > state.voters.len() / 2)
+        .map(|option| option.option_id);
+    let (winning_option, status) = if !state.quorum.is_met(participants, state.voters.len() as u32)
+    {
+        (None, ResultStatus::QuorumNotMet {})
+    } else if majority_option.is_some() {
+        (majority_option, ResultStatus::Decided {})
+    } else {
+        (None, ResultStatus::NoMajority {})
+    };
+    let result = VoteResult {
+        tally,
+        winning_option,
+        status,
+    };
+    let mut events = state.certify_result(&result, participants);
+    events.extend(state.push_result(&result));
+    let mut new_state = state;
+    new_state.result = Some(result);
+
+    if let Some(reward) = &new_state.keeper_reward {
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(reward.token, token_contract_transfer())
+            .argument(ctx.sender)
+            .argument(reward.amount)
+            .done();
+        events.push(event_group.build());
+    }
+    (new_state, events)
+}
+
+/// Pause the vote in an emergency. Only the owner can pause the vote, and only before the
+/// deadline. While the vote is paused, no votes, commitments or delegations can be made, and the
+/// votes cannot be counted.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The updated vote state, which is paused.
+///
+#[action(shortname = 0x0A)]
+pub fn pause(ctx: ContractContext, state: VoteState) -> VoteState {
+    assert_eq!(ctx.sender, state.owner, "Only the owner can pause the vote");
+    state.assert_not_paused();
+    assert!(
+        state.result.is_none() && ctx.block_production_time < state.deadline_utc_millis,
+        "The deadline has passed"
+    );
+    let mut new_state = state;
+    new_state.paused_at_millis = Some(ctx.block_production_time);
+    new_state
+}
+
+/// Resume a paused vote. Only the owner can resume the vote.
+/// The deadline and the reveal deadline are postponed by the duration of the pause, which is added
+/// to the accumulated paused duration. The vote lock and the registration deadline are postponed
+/// as well, unless they had already passed when the vote was paused.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The updated vote state, with the deadlines postponed.
+///
+#[action(shortname = 0x0B)]
+pub fn resume(ctx: ContractContext, state: VoteState) -> VoteState {
+    assert_eq!(
+        ctx.sender, state.owner,
+        "Only the owner can resume the vote"
+    );
+    let paused_at = state.paused_at_millis.expect("The vote is not paused");
+    let paused_millis = ctx.block_production_time - paused_at;
+    let mut new_state = state;
+    new_state.paused_at_millis = None;
+    new_state.total_paused_millis += paused_millis;
+    new_state.deadline_utc_millis += paused_millis;
+    if let Some(reveal_deadline) = new_state.reveal_deadline_utc_millis.as_mut() {
+        *reveal_deadline += paused_millis;
+    }
+    if let Some(lock_time) = new_state.lock_votes_after_millis.as_mut() {
+        if *lock_time > paused_at {
+            *lock_time += paused_millis;
+        }
+    }
+    if let (VotePhase::Registration {}, Some(registration)) =
+        (new_state.phase, new_state.registration.as_mut())
+    {
+        if registration.deadline_utc_millis > paused_at {
+            registration.deadline_utc_millis += paused_millis;
+        }
+    }
+    new_state
+}
+
+/// Reopen a counted vote for a new round with a new deadline. Only the owner can reopen the vote.
+/// The votes, delegations and result of the counted round are archived in the history, and the
+/// votes, commitments and delegations are cleared, such that the voters start over. The reveal
+/// deadline and the vote lock keep their distance to the deadline. Signed vote nonces are kept,
+/// such that signed votes of earlier rounds cannot be submitted again.
+/// If the vote has a keeper reward, the reward tokens for the new round must be transferred to the
+/// vote contract again. A vote with staking eligibility cannot be reopened, as the stakes can be
+/// withdrawn once the votes have been counted.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `new_deadline_utc_millis` - the deadline of the new round in UTC millis.
+///
+/// # Returns
+///
+/// The updated vote state, open for the new round.
+///
+#[action(shortname = 0x0C)]
+pub fn reopen(ctx: ContractContext, state: VoteState, new_deadline_utc_millis: i64) -> VoteState {
+    assert_eq!(
+        ctx.sender, state.owner,
+        "Only the owner can reopen the vote"
+    );
+    assert!(
+        state.staking_eligibility.is_none(),
+        "A vote with staking eligibility cannot be reopened"
+    );
+    assert!(
+        new_deadline_utc_millis > ctx.block_production_time,
+        "The new deadline must be in the future"
+    );
     let mut new_state = state;
-    new_state.result = Some(vote_passed);
+    let result = new_state
+        .result
+        .take()
+        .expect("The votes have not been counted");
+    let postponed_millis = new_deadline_utc_millis - new_state.deadline_utc_millis;
+    new_state.history.push(VoteRound {
+        deadline_utc_millis: new_state.deadline_utc_millis,
+        votes: std::mem::take(&mut new_state.votes),
+        delegations: std::mem::take(&mut new_state.delegations),
+        result,
+    });
+    new_state.commitments.clear();
+    new_state.vote_times.clear();
+    new_state.total_paused_millis = 0;
+    new_state.deadline_utc_millis = new_deadline_utc_millis;
+    if let Some(reveal_deadline) = new_state.reveal_deadline_utc_millis.as_mut() {
+        *reveal_deadline += postponed_millis;
+    }
+    if let Some(lock_time) = new_state.lock_votes_after_millis.as_mut() {
+        *lock_time += postponed_millis;
+    }
     new_state
 }
+
+/// Register a voter during the registration phase, until the registration deadline.
+/// The owner can register any address. Any other sender can only register itself, if it meets
+/// the registration criterion of the vote. With staking eligibility voters must register
+/// themselves with [`register_with_stake`] instead.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+/// * `voter` - the address registered as a voter.
+///
+/// # Returns
+///
+/// The updated vote state, with the voter added to the eligible voters.
+///
+#[action(shortname = 0x0D)]
+pub fn register_voter(ctx: ContractContext, state: VoteState, voter: Address) -> VoteState {
+    assert!(
+        state.staking_eligibility.is_none(),
+        "Voters must register by locking their stake"
+    );
+    if ctx.sender != state.owner {
+        assert_eq!(
+            ctx.sender, voter,
+            "Only the owner can register other addresses"
+        );
+        state.assert_can_register_itself(&voter, ctx.block_production_time);
+    } else {
+        assert!(
+            ctx.block_production_time < state.registration_deadline(),
+            "The registration deadline has passed"
+        );
+        state.assert_not_paused();
+        assert!(
+            !state.voters.contains(&voter),
+            "The voter is already registered"
+        );
+    }
+    let mut new_state = state;
+    new_state.voters.push(voter);
+    new_state
+}
+
+/// Close the registration phase and start the voting phase, fixing the list of eligible voters.
+/// The owner can close the registration at any time, and anyone can close it once the
+/// registration deadline has passed.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The updated vote state, in the voting phase.
+///
+#[action(shortname = 0x0E)]
+pub fn close_registration(ctx: ContractContext, state: VoteState) -> VoteState {
+    let registration_deadline = state.registration_deadline();
+    assert!(
+        ctx.sender == state.owner || ctx.block_production_time >= registration_deadline,
+        "Only the owner can close the registration before the registration deadline"
+    );
+    assert_ne!(state.voters.len(), 0, "Voters are required");
+    let mut new_state = state;
+    new_state.phase = VotePhase::Voting {};
+    new_state
+}
+
+/// Register the sender as a voter with staking eligibility, by locking the stake in the vote
+/// contract. The stake is transferred from the sender to the vote contract, which must have been
+/// approved by the sender beforehand, and the voter is registered in
+/// [`register_with_stake_callback`] once the transfer has succeeded. The registration criterion
+/// and deadline apply as in [`register_voter`].
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The unchanged vote state, and the transfer of the stake to the vote contract.
+///
+#[action(shortname = 0x0F)]
+pub fn register_with_stake(ctx: ContractContext, state: VoteState) -> (VoteState, Vec<EventGroup>) {
+    let staking = state
+        .staking_eligibility
+        .as_ref()
+        .expect("The vote has no staking eligibility");
+    state.assert_can_register_itself(&ctx.sender, ctx.block_production_time);
+    assert!(
+        !state.stakes.contains_key(&ctx.sender),
+        "The voter has already locked a stake"
+    );
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(staking.stake_token, token_contract_transfer_from())
+        .argument(ctx.sender)
+        .argument(ctx.contract_address)
+        .argument(staking.stake)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_REGISTER_WITH_STAKE_CALLBACK)
+        .argument(ctx.sender)
+        .argument(staking.stake)
+        .done();
+    (state, vec![event_group.build()])
+}
+
+/// Called when the stake of a voter registering with [`register_with_stake`] has been
+/// transferred. The stake is locked for the voter, and the voter is added to the eligible voters
+/// if the registration is still open. A stake transferred after the registration has closed is
+/// locked without registering the voter, and can be withdrawn right away.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the vote.
+/// * `voter` - the voter registering.
+/// * `stake` - the amount of stake tokens transferred.
+///
+/// # Returns
+///
+/// The updated vote state, with the stake locked and the voter registered.
+///
+#[callback(shortname = 0x07)]
+pub fn register_with_stake_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VoteState,
+    voter: Address,
+    stake: u128,
+) -> (VoteState, Vec<EventGroup>) {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the stake did not succeed"
+    );
+    let mut new_state = state;
+    *new_state.stakes.entry(voter).or_insert(0) += stake;
+    if new_state.phase == (VotePhase::Registration {}) && !new_state.voters.contains(&voter) {
+        new_state.voters.push(voter);
+    }
+    (new_state, vec![])
+}
+
+/// Withdraw the stake locked by the sender. The stake of an eligible voter stays locked until
+/// the votes have been counted, while a stake that did not register the sender can be withdrawn
+/// at any time. The stake is transferred to the sender, with a callback to
+/// [`withdraw_stake_callback`] locking it again if the transfer failed.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `state` - the current state of the vote.
+///
+/// # Returns
+///
+/// The updated vote state without the stake of the sender, and the transfer of the stake.
+///
+#[action(shortname = 0x10)]
+pub fn withdraw_stake(ctx: ContractContext, state: VoteState) -> (VoteState, Vec<EventGroup>) {
+    assert!(
+        state.result.is_some() || !state.voters.contains(&ctx.sender),
+        "The stake is locked until the votes have been counted"
+    );
+    let mut new_state = state;
+    let stake = new_state
+        .stakes
+        .remove(&ctx.sender)
+        .unwrap_or_else(|| panic!("No stake locked for {:?}", ctx.sender));
+    let stake_token = new_state
+        .staking_eligibility
+        .as_ref()
+        .expect("The vote has no staking eligibility")
+        .stake_token;
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(stake_token, token_contract_transfer())
+        .argument(ctx.sender)
+        .argument(stake)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_STAKE_CALLBACK)
+        .argument(ctx.sender)
+        .argument(stake)
+        .done();
+    (new_state, vec![event_group.build()])
+}
+
+/// Called when a stake withdrawn with [`withdraw_stake`] has been transferred. If the transfer
+/// failed the stake is locked for the voter again, such that it can be withdrawn later.
+///
+/// # Arguments
+///
+/// * `ctx` - the contract context containing information about the sender and the blockchain.
+/// * `callback_ctx` - the callback context containing the result of the transfer.
+/// * `state` - the current state of the vote.
+/// * `voter` - the voter who withdrew the stake.
+/// * `stake` - the amount of stake tokens withdrawn.
+///
+/// # Returns
+///
+/// The vote state, with the stake restored if the transfer failed.
+///
+#[callback(shortname = 0x11)]
+pub fn withdraw_stake_callback(
+    ctx: ContractContext,
+    callback_ctx: CallbackContext,
+    state: VoteState,
+    voter: Address,
+    stake: u128,
+) -> (VoteState, Vec<EventGroup>) {
+    let mut new_state = state;
+    if !callback_ctx.success {
+        *new_state.stakes.entry(voter).or_insert(0) += stake;
+    }
+    (new_state, vec![])
+}
+
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
+/// Notification contract actions
+#[inline]
+fn notification_contract_vote_receipt() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Voter contract actions
+#[inline]
+fn voter_contract_confirm_vote() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Registry contract actions
+#[inline]
+fn registry_contract_certify_result() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+/// Parent contract actions
+#[inline]
+fn parent_contract_receive_result() -> Shortname {
+    Shortname::from_u32(0x01)
+}
@@ -0,0 +1,865 @@
+use k256::ecdsa::SigningKey;
+use pbc_contract_common::address::{Address, AddressType, Shortname, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::Hash;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    close_registration, count, delegate, initialize, pause, recover_signer, register_voter,
+    register_with_stake, register_with_stake_callback, reopen, resume, signed_vote_hash, vote,
+    vote_as_contract, vote_as_contract_callback, vote_by_signature, withdraw_stake,
+    withdraw_stake_callback, OptionSpec, QuorumFraction, RegistrationCriterion, RegistrationWindow,
+    ResultStatus, StakingEligibility, VotePhase, VoteSignature, VoteState,
+};
+
+fn create_ctx(sender: Address, block_production_time: i64) -> ContractContext {
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 0,
+        block_production_time,
+        current_transaction: [0u8; 32],
+        original_transaction: [0u8; 32],
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn voter(id: u8) -> Address {
+    create_address(AddressType::Account, id)
+}
+
+fn owner() -> Address {
+    voter(100)
+}
+
+fn stake_token() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn transfer_ctx(succeeded: bool) -> CallbackContext {
+    CallbackContext {
+        success: succeeded,
+        results: vec![ExecutionResult {
+            succeeded,
+            return_data: vec![],
+        }],
+    }
+}
+
+/// A vote among three voters on the options 1 and 2, with the deadline at 100 and the votes
+/// locked after 80.
+fn initial_state() -> VoteState {
+    state_with_registration((1..=3).map(voter).collect(), None)
+}
+
+/// A vote like [`initial_state`] among the given voters, with the given registration phase.
+fn state_with_registration(
+    voters: Vec<Address>,
+    registration: Option<RegistrationWindow>,
+) -> VoteState {
+    let options = (1..=2)
+        .map(|id| OptionSpec {
+            id,
+            label_hash: [id as u8; 32],
+        })
+        .collect();
+    initialize(
+        create_ctx(owner(), 0),
+        1,
+        voters,
+        100,
+        options,
+        None,
+        QuorumFraction {
+            numerator: 0,
+            denominator: 1,
+        },
+        None,
+        None,
+        None,
+        Some(80),
+        None,
+        registration,
+        None,
+    )
+}
+
+/// A vote with staking eligibility, where accounts register until 50 by locking a stake of 10,
+/// and a quorum of half of the registered voters.
+fn staking_state() -> VoteState {
+    let options = (1..=2)
+        .map(|id| OptionSpec {
+            id,
+            label_hash: [id as u8; 32],
+        })
+        .collect();
+    initialize(
+        create_ctx(owner(), 0),
+        1,
+        vec![],
+        100,
+        options,
+        None,
+        QuorumFraction {
+            numerator: 1,
+            denominator: 2,
+        },
+        None,
+        None,
+        None,
+        None,
+        Some(StakingEligibility {
+            stake_token: stake_token(),
+            stake: 10,
+        }),
+        Some(RegistrationWindow {
+            deadline_utc_millis: 50,
+            criterion: RegistrationCriterion::Accounts {},
+        }),
+        None,
+    )
+}
+
+/// A vote like [`staking_state`] where the given voters have locked their stake, with the
+/// registration still open.
+fn staked_state(voters: &[u8]) -> VoteState {
+    let mut state = staking_state();
+    for id in voters {
+        (state, _) = register_with_stake_callback(
+            create_ctx(stake_token(), 10),
+            transfer_ctx(true),
+            state,
+            voter(*id),
+            10,
+        );
+    }
+    state
+}
+
+/// A vote with a registration phase until 50 where accounts can register themselves, and with
+/// voter 1 registered up front.
+fn registering_state() -> VoteState {
+    state_with_registration(
+        vec![voter(1)],
+        Some(RegistrationWindow {
+            deadline_utc_millis: 50,
+            criterion: RegistrationCriterion::Accounts {},
+        }),
+    )
+}
+
+#[test]
+fn resume_postpones_deadline_by_paused_duration() {
+    let mut state = pause(create_ctx(owner(), 40), initial_state());
+    assert_eq!(state.paused_at_millis, Some(40));
+    state = resume(create_ctx(owner(), 70), state);
+    assert_eq!(state.paused_at_millis, None);
+    assert_eq!(state.total_paused_millis, 30);
+    assert_eq!(state.deadline_utc_millis, 130);
+    assert_eq!(state.lock_votes_after_millis, Some(110));
+}
+
+#[test]
+fn repeated_pauses_accumulate() {
+    let mut state = initial_state();
+    state = pause(create_ctx(owner(), 10), state);
+    state = resume(create_ctx(owner(), 30), state);
+    (state, _) = vote(create_ctx(voter(1), 40), state, 1);
+    state = pause(create_ctx(owner(), 50), state);
+    state = resume(create_ctx(owner(), 55), state);
+    // The deadline is now 125, so the vote is still open at 120.
+    state = pause(create_ctx(owner(), 120), state);
+    state = resume(create_ctx(owner(), 220), state);
+    assert_eq!(state.total_paused_millis, 125);
+    assert_eq!(state.deadline_utc_millis, 225);
+    (state, _) = vote(create_ctx(voter(2), 221), state, 1);
+
+    let (state, _) = count(create_ctx(voter(3), 225), state);
+    assert_eq!(state.result.unwrap().winning_option, Some(1));
+}
+
+#[test]
+fn lock_is_not_postponed_if_votes_were_locked_when_paused() {
+    let mut state = pause(create_ctx(owner(), 90), initial_state());
+    state = resume(create_ctx(owner(), 150), state);
+    assert_eq!(state.deadline_utc_millis, 160);
+    assert_eq!(state.lock_votes_after_millis, Some(80));
+}
+
+#[test]
+#[should_panic(expected = "The vote is paused")]
+fn cannot_vote_while_paused() {
+    let state = pause(create_ctx(owner(), 40), initial_state());
+    vote(create_ctx(voter(1), 50), state, 1);
+}
+
+#[test]
+#[should_panic(expected = "The vote is paused")]
+fn cannot_delegate_while_paused() {
+    let state = pause(create_ctx(owner(), 40), initial_state());
+    delegate(create_ctx(voter(1), 50), state, voter(2));
+}
+
+#[test]
+#[should_panic(expected = "The vote is paused")]
+fn cannot_count_while_paused() {
+    let state = pause(create_ctx(owner(), 40), initial_state());
+    count(create_ctx(voter(1), 200), state);
+}
+
+#[test]
+#[should_panic(expected = "The vote is paused")]
+fn cannot_pause_twice() {
+    let state = pause(create_ctx(owner(), 40), initial_state());
+    pause(create_ctx(owner(), 50), state);
+}
+
+#[test]
+#[should_panic(expected = "The vote is not paused")]
+fn cannot_resume_unpaused_vote() {
+    resume(create_ctx(owner(), 40), initial_state());
+}
+
+#[test]
+#[should_panic(expected = "Only the owner can pause the vote")]
+fn only_owner_can_pause() {
+    pause(create_ctx(voter(1), 40), initial_state());
+}
+
+#[test]
+#[should_panic(expected = "The deadline has passed")]
+fn cannot_pause_after_deadline() {
+    pause(create_ctx(owner(), 100), initial_state());
+}
+
+#[test]
+fn reopen_archives_counted_round() {
+    let mut state = initial_state();
+    (state, _) = vote(create_ctx(voter(1), 10), state, 1);
+    state = delegate(create_ctx(voter(2), 20), state, voter(1));
+    (state, _) = count(create_ctx(voter(3), 100), state);
+
+    state = reopen(create_ctx(owner(), 110), state, 300);
+    assert!(state.result.is_none());
+    assert!(state.votes.is_empty());
+    assert!(state.delegations.is_empty());
+    assert_eq!(state.deadline_utc_millis, 300);
+    assert_eq!(state.lock_votes_after_millis, Some(280));
+    assert_eq!(state.history.len(), 1);
+    let round = &state.history[0];
+    assert_eq!(round.deadline_utc_millis, 100);
+    assert_eq!(round.votes.get(&voter(1)), Some(&1));
+    assert_eq!(round.delegations.get(&voter(2)), Some(&voter(1)));
+    assert_eq!(round.result.winning_option, Some(1));
+
+    (state, _) = vote(create_ctx(voter(1), 120), state, 2);
+    (state, _) = vote(create_ctx(voter(3), 130), state, 2);
+    (state, _) = count(create_ctx(voter(3), 300), state);
+    assert_eq!(state.result.unwrap().winning_option, Some(2));
+    assert!(state.history[0].result.status == ResultStatus::Decided {});
+}
+
+#[test]
+fn count_pushes_result_to_parent() {
+    let parent = create_address(AddressType::PublicContract, 2);
+    let mut state = initial_state();
+    state.parent = Some(parent);
+    (state, _) = vote(create_ctx(voter(1), 10), state, 1);
+    (state, _) = vote(create_ctx(voter(2), 20), state, 1);
+    let (_, events) = count(create_ctx(voter(3), 100), state);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(parent, Shortname::from_u32(0x01))
+        .argument(1u64)
+        .argument(true)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+}
+
+#[test]
+fn undecided_result_is_pushed_as_not_passed() {
+    let parent = create_address(AddressType::PublicContract, 2);
+    let mut state = initial_state();
+    state.parent = Some(parent);
+    (state, _) = vote(create_ctx(voter(1), 10), state, 1);
+    (state, _) = vote(create_ctx(voter(2), 20), state, 2);
+    let (_, events) = count(create_ctx(voter(3), 100), state);
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(parent, Shortname::from_u32(0x01))
+        .argument(1u64)
+        .argument(false)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+}
+
+#[test]
+#[should_panic(expected = "The votes have not been counted")]
+fn cannot_reopen_uncounted_vote() {
+    reopen(create_ctx(owner(), 110), initial_state(), 300);
+}
+
+#[test]
+#[should_panic(expected = "Only the owner can reopen the vote")]
+fn only_owner_can_reopen() {
+    let (state, _) = count(create_ctx(voter(1), 100), initial_state());
+    reopen(create_ctx(voter(1), 110), state, 300);
+}
+
+#[test]
+#[should_panic(expected = "The new deadline must be in the future")]
+fn cannot_reopen_with_past_deadline() {
+    let (state, _) = count(create_ctx(voter(1), 100), initial_state());
+    reopen(create_ctx(owner(), 110), state, 110);
+}
+
+#[test]
+fn registered_voters_vote_after_registration_closes() {
+    let mut state = registering_state();
+    assert_eq!(state.phase, VotePhase::Registration {});
+    state = register_voter(create_ctx(voter(2), 10), state, voter(2));
+    let contract_voter = create_address(AddressType::PublicContract, 9);
+    state = register_voter(create_ctx(owner(), 20), state, contract_voter);
+    assert_eq!(state.voters, vec![voter(1), voter(2), contract_voter]);
+
+    state = close_registration(create_ctx(voter(3), 50), state);
+    assert_eq!(state.phase, VotePhase::Voting {});
+    (state, _) = vote(create_ctx(voter(1), 60), state, 2);
+    (state, _) = vote(create_ctx(voter(2), 70), state, 2);
+    let (state, _) = count(create_ctx(voter(3), 100), state);
+    assert_eq!(state.result.unwrap().winning_option, Some(2));
+}
+
+#[test]
+fn owner_can_close_registration_early() {
+    let state = close_registration(create_ctx(owner(), 10), registering_state());
+    assert_eq!(state.phase, VotePhase::Voting {});
+}
+
+#[test]
+fn pause_postpones_registration_deadline() {
+    let mut state = pause(create_ctx(owner(), 40), registering_state());
+    state = resume(create_ctx(owner(), 70), state);
+    assert_eq!(state.registration.unwrap().deadline_utc_millis, 80);
+}
+
+#[test]
+#[should_panic(expected = "The registration of voters has not been closed")]
+fn cannot_vote_during_registration() {
+    vote(create_ctx(voter(1), 10), registering_state(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Only accounts can register themselves")]
+fn contract_cannot_register_itself() {
+    let contract_voter = create_address(AddressType::PublicContract, 9);
+    register_voter(
+        create_ctx(contract_voter, 10),
+        registering_state(),
+        contract_voter,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Voters must be registered by the owner")]
+fn owner_approval_is_required_to_register() {
+    let state = state_with_registration(
+        vec![],
+        Some(RegistrationWindow {
+            deadline_utc_millis: 50,
+            criterion: RegistrationCriterion::OwnerApproval {},
+        }),
+    );
+    register_voter(create_ctx(voter(2), 10), state, voter(2));
+}
+
+#[test]
+#[should_panic(expected = "The registration deadline has passed")]
+fn cannot_register_after_registration_deadline() {
+    register_voter(create_ctx(voter(2), 50), registering_state(), voter(2));
+}
+
+#[test]
+#[should_panic(
+    expected = "Only the owner can close the registration before the registration deadline"
+)]
+fn only_owner_can_close_registration_early() {
+    close_registration(create_ctx(voter(1), 49), registering_state());
+}
+
+#[test]
+#[should_panic(expected = "The registration of voters is closed")]
+fn cannot_register_after_registration_is_closed() {
+    let state = close_registration(create_ctx(owner(), 10), registering_state());
+    register_voter(create_ctx(voter(2), 20), state, voter(2));
+}
+
+#[test]
+fn register_with_stake_locks_stake_in_vote_contract() {
+    let (_, events) = register_with_stake(create_ctx(voter(1), 10), staking_state());
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(stake_token(), Shortname::from_u32(0x03))
+        .argument(voter(1))
+        .argument(create_address(AddressType::PublicContract, 1))
+        .argument(10u128)
+        .done();
+    event_group
+        .with_callback(ShortnameCallback::from_u32(0x07))
+        .argument(voter(1))
+        .argument(10u128)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+}
+
+#[test]
+fn locked_stake_registers_voter() {
+    let state = staked_state(&[1]);
+    assert_eq!(state.voters, vec![voter(1)]);
+    assert_eq!(state.stakes.get(&voter(1)), Some(&10));
+}
+
+#[test]
+#[should_panic(expected = "Transfer of the stake did not succeed")]
+fn failed_stake_transfer_does_not_register_voter() {
+    register_with_stake_callback(
+        create_ctx(stake_token(), 10),
+        transfer_ctx(false),
+        staking_state(),
+        voter(1),
+        10,
+    );
+}
+
+#[test]
+#[should_panic(expected = "The voter is already registered")]
+fn cannot_register_with_stake_twice() {
+    register_with_stake(create_ctx(voter(1), 20), staked_state(&[1]));
+}
+
+#[test]
+#[should_panic(expected = "Voters must register by locking their stake")]
+fn owner_cannot_register_voters_without_stake() {
+    register_voter(create_ctx(owner(), 10), staking_state(), voter(1));
+}
+
+#[test]
+#[should_panic(expected = "Staking eligibility requires a registration phase")]
+fn staking_eligibility_requires_registration() {
+    let mut state = staking_state();
+    initialize(
+        create_ctx(owner(), 0),
+        1,
+        vec![],
+        100,
+        state.options.drain(..).collect(),
+        None,
+        state.quorum,
+        None,
+        None,
+        None,
+        None,
+        state.staking_eligibility,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn stake_transferred_after_registration_closed_does_not_register_voter() {
+    let mut state = close_registration(create_ctx(owner(), 20), staked_state(&[1]));
+    (state, _) = register_with_stake_callback(
+        create_ctx(stake_token(), 30),
+        transfer_ctx(true),
+        state,
+        voter(2),
+        10,
+    );
+    assert_eq!(state.voters, vec![voter(1)]);
+
+    let (state, events) = withdraw_stake(create_ctx(voter(2), 40), state);
+    assert!(!state.stakes.contains_key(&voter(2)));
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn quorum_is_measured_against_registered_voters() {
+    let mut state = close_registration(create_ctx(owner(), 50), staked_state(&[1, 2, 3]));
+    (state, _) = vote(create_ctx(voter(1), 60), state, 1);
+    let (state, _) = count(create_ctx(voter(1), 100), state);
+
+    let result = state.result.unwrap();
+    assert!(result.status == ResultStatus::QuorumNotMet {});
+    assert_eq!(result.winning_option, None);
+}
+
+#[test]
+fn majority_is_measured_against_registered_voters() {
+    let mut state = close_registration(create_ctx(owner(), 50), staked_state(&[1, 2, 3, 4]));
+    (state, _) = vote(create_ctx(voter(1), 60), state, 1);
+    (state, _) = vote(create_ctx(voter(2), 60), state, 1);
+    (state, _) = vote(create_ctx(voter(3), 60), state, 2);
+    let (state, _) = count(create_ctx(voter(1), 100), state);
+
+    let result = state.result.unwrap();
+    assert!(result.status == ResultStatus::NoMajority {});
+}
+
+#[test]
+#[should_panic(expected = "Not an eligible voter")]
+fn staker_cannot_vote_without_registering() {
+    let state = close_registration(create_ctx(owner(), 50), staked_state(&[1]));
+    vote(create_ctx(voter(2), 60), state, 1);
+}
+
+#[test]
+#[should_panic(expected = "The stake is locked until the votes have been counted")]
+fn registered_voter_cannot_withdraw_stake_before_count() {
+    let state = close_registration(create_ctx(owner(), 50), staked_state(&[1]));
+    withdraw_stake(create_ctx(voter(1), 60), state);
+}
+
+#[test]
+fn stake_can_be_withdrawn_after_count() {
+    let mut state = close_registration(create_ctx(owner(), 50), staked_state(&[1]));
+    (state, _) = vote(create_ctx(voter(1), 60), state, 1);
+    (state, _) = count(create_ctx(voter(1), 100), state);
+    let (state, events) = withdraw_stake(create_ctx(voter(1), 110), state);
+
+    assert!(state.stakes.is_empty());
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(stake_token(), Shortname::from_u32(0x01))
+        .argument(voter(1))
+        .argument(10u128)
+        .done();
+    event_group
+        .with_callback(ShortnameCallback::from_u32(0x11))
+        .argument(voter(1))
+        .argument(10u128)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+}
+
+#[test]
+fn failed_withdrawal_locks_stake_again() {
+    let mut state = close_registration(create_ctx(owner(), 50), staked_state(&[1]));
+    (state, _) = count(create_ctx(voter(1), 100), state);
+    (state, _) = withdraw_stake(create_ctx(voter(1), 110), state);
+    let (state, _) = withdraw_stake_callback(
+        create_ctx(stake_token(), 110),
+        transfer_ctx(false),
+        state,
+        voter(1),
+        10,
+    );
+    assert_eq!(state.stakes.get(&voter(1)), Some(&10));
+}
+
+#[test]
+#[should_panic(expected = "A vote with staking eligibility cannot be reopened")]
+fn cannot_reopen_vote_with_staking_eligibility() {
+    let mut state = close_registration(create_ctx(owner(), 50), staked_state(&[1]));
+    (state, _) = count(create_ctx(voter(1), 100), state);
+    reopen(create_ctx(owner(), 110), state, 300);
+}
+
+/// The signing key of the voter signing votes, fixed such that the tests are deterministic.
+fn signing_key() -> SigningKey {
+    SigningKey::from_slice(&[7; 32]).unwrap()
+}
+
+/// The account address of the voter with the signing key `key`.
+fn signer_address(key: &SigningKey) -> Address {
+    let public_key_hash: Hash = Sha256::digest(key.verifying_key().to_sec1_bytes()).into();
+    let mut identifier = [0u8; 20];
+    identifier.copy_from_slice(&public_key_hash[12..]);
+    Address {
+        address_type: AddressType::Account,
+        identifier,
+    }
+}
+
+/// Signs a vote of the voter with the signing key `key` for the vote contract `vote_contract`.
+fn sign(
+    key: &SigningKey,
+    vote_contract: &Address,
+    proposal_id: u64,
+    option_id: u32,
+    nonce: u64,
+    expiry_utc_millis: i64,
+) -> VoteSignature {
+    let message_hash = signed_vote_hash(
+        vote_contract,
+        proposal_id,
+        &signer_address(key),
+        option_id,
+        nonce,
+        expiry_utc_millis,
+    );
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&message_hash).unwrap();
+    let bytes = signature.to_bytes();
+    VoteSignature {
+        recovery_id: recovery_id.to_byte(),
+        value_r: bytes[..32].try_into().unwrap(),
+        value_s: bytes[32..].try_into().unwrap(),
+    }
+}
+
+/// A vote like [`initial_state`] among the signer and the voters 1 and 2.
+fn state_with_signer() -> VoteState {
+    state_with_registration(
+        vec![signer_address(&signing_key()), voter(1), voter(2)],
+        None,
+    )
+}
+
+fn vote_contract() -> Address {
+    create_address(AddressType::PublicContract, 1)
+}
+
+#[test]
+fn signature_recovers_account_of_signer() {
+    let key = signing_key();
+    let signature = sign(&key, &vote_contract(), 1, 2, 0, 50);
+    let message_hash = signed_vote_hash(&vote_contract(), 1, &signer_address(&key), 2, 0, 50);
+    assert_eq!(
+        recover_signer(&message_hash, &signature),
+        Some(signer_address(&key))
+    );
+}
+
+#[test]
+fn signed_vote_is_recorded_for_voter() {
+    let key = signing_key();
+    let signer = signer_address(&key);
+    let signature = sign(&key, &vote_contract(), 1, 2, 0, 50);
+    let (state, _) = vote_by_signature(
+        create_ctx(voter(2), 10),
+        state_with_signer(),
+        signer,
+        2,
+        50,
+        signature,
+    );
+    assert_eq!(state.votes.get(&signer), Some(&2));
+    assert_eq!(state.vote_nonces.get(&signer), Some(&1));
+}
+
+#[test]
+#[should_panic(expected = "The signature was not made by the voter")]
+fn signed_vote_cannot_be_replayed() {
+    let key = signing_key();
+    let signer = signer_address(&key);
+    let signature = sign(&key, &vote_contract(), 1, 2, 0, 50);
+    let (state, _) = vote_by_signature(
+        create_ctx(voter(2), 10),
+        state_with_signer(),
+        signer,
+        2,
+        50,
+        signature.clone(),
+    );
+    vote_by_signature(create_ctx(voter(2), 20), state, signer, 2, 50, signature);
+}
+
+#[test]
+#[should_panic(expected = "The signature was not made by the voter")]
+fn signed_vote_for_another_contract_is_rejected() {
+    let key = signing_key();
+    let other_contract = create_address(AddressType::PublicContract, 9);
+    let signature = sign(&key, &other_contract, 1, 2, 0, 50);
+    vote_by_signature(
+        create_ctx(voter(2), 10),
+        state_with_signer(),
+        signer_address(&key),
+        2,
+        50,
+        signature,
+    );
+}
+
+#[test]
+#[should_panic(expected = "The signature was not made by the voter")]
+fn signed_vote_for_another_proposal_is_rejected() {
+    let key = signing_key();
+    let signature = sign(&key, &vote_contract(), 2, 2, 0, 50);
+    vote_by_signature(
+        create_ctx(voter(2), 10),
+        state_with_signer(),
+        signer_address(&key),
+        2,
+        50,
+        signature,
+    );
+}
+
+#[test]
+#[should_panic(expected = "The signed vote has expired")]
+fn expired_signed_vote_is_rejected() {
+    let key = signing_key();
+    let signature = sign(&key, &vote_contract(), 1, 2, 0, 50);
+    vote_by_signature(
+        create_ctx(voter(2), 50),
+        state_with_signer(),
+        signer_address(&key),
+        2,
+        50,
+        signature,
+    );
+}
+
+fn voter_contract() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+/// A vote like [`initial_state`] among the voter contract and the voters 1 and 2.
+fn state_with_voter_contract() -> VoteState {
+    state_with_registration(vec![voter_contract(), voter(1), voter(2)], None)
+}
+
+fn consent_ctx(succeeded: bool, consent: bool) -> CallbackContext {
+    CallbackContext {
+        success: succeeded,
+        results: vec![ExecutionResult {
+            succeeded,
+            return_data: if succeeded {
+                vec![consent as u8]
+            } else {
+                vec![]
+            },
+        }],
+    }
+}
+
+#[test]
+fn vote_as_contract_asks_voter_contract_for_consent() {
+    let (state, events) = vote_as_contract(
+        create_ctx(voter(1), 10),
+        state_with_voter_contract(),
+        voter_contract(),
+        2,
+    );
+    assert!(state.votes.is_empty());
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(voter_contract(), Shortname::from_u32(0x01))
+        .argument(1u64)
+        .argument(2u32)
+        .argument(voter(1))
+        .done();
+    event_group
+        .with_callback(ShortnameCallback::from_u32(0x09))
+        .argument(voter_contract())
+        .argument(2u32)
+        .done();
+    assert_eq!(events, vec![event_group.build()]);
+}
+
+#[test]
+fn consent_of_voter_contract_records_vote() {
+    let (state, _) = vote_as_contract_callback(
+        create_ctx(voter_contract(), 10),
+        consent_ctx(true, true),
+        state_with_voter_contract(),
+        voter_contract(),
+        2,
+    );
+    assert_eq!(state.votes.get(&voter_contract()), Some(&2));
+}
+
+#[test]
+#[should_panic(expected = "The voter contract did not consent to the vote")]
+fn refusal_of_voter_contract_is_rejected() {
+    vote_as_contract_callback(
+        create_ctx(voter_contract(), 10),
+        consent_ctx(true, false),
+        state_with_voter_contract(),
+        voter_contract(),
+        2,
+    );
+}
+
+#[test]
+#[should_panic(expected = "The voter contract did not confirm the vote")]
+fn failed_call_to_voter_contract_is_rejected() {
+    vote_as_contract_callback(
+        create_ctx(voter_contract(), 10),
+        consent_ctx(false, false),
+        state_with_voter_contract(),
+        voter_contract(),
+        2,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Accounts must cast their votes directly")]
+fn account_cannot_vote_as_contract() {
+    vote_as_contract(create_ctx(voter(2), 10), initial_state(), voter(1), 2);
+}
+
+/// The votes counted for each of the options 1 and 2.
+fn counted_votes(state: &VoteState) -> Vec<u32> {
+    let result = state.result.as_ref().unwrap();
+    result.tally.iter().map(|option| option.votes).collect()
+}
+
+#[test]
+fn delegation_chain_counts_towards_vote_at_its_end() {
+    let mut state = state_with_registration((1..=4).map(voter).collect(), None);
+    state = delegate(create_ctx(voter(1), 10), state, voter(2));
+    state = delegate(create_ctx(voter(2), 20), state, voter(3));
+    (state, _) = vote(create_ctx(voter(3), 30), state, 2);
+    assert_eq!(state.resolve_vote(&voter(1)), Some(2));
+    assert_eq!(state.resolve_vote(&voter(4)), None);
+
+    (state, _) = count(create_ctx(voter(4), 100), state);
+    assert_eq!(counted_votes(&state), vec![0, 3]);
+    assert_eq!(state.result.unwrap().winning_option, Some(2));
+}
+
+#[test]
+fn delegation_cycle_is_not_counted() {
+    let mut state = initial_state();
+    state = delegate(create_ctx(voter(1), 10), state, voter(2));
+    state = delegate(create_ctx(voter(2), 20), state, voter(1));
+    (state, _) = vote(create_ctx(voter(3), 30), state, 1);
+    assert_eq!(state.resolve_vote(&voter(1)), None);
+    assert_eq!(state.resolve_vote(&voter(2)), None);
+
+    (state, _) = count(create_ctx(voter(3), 100), state);
+    assert_eq!(counted_votes(&state), vec![1, 0]);
+    let result = state.result.unwrap();
+    assert_eq!(result.winning_option, None);
+    assert!(result.status == ResultStatus::NoMajority {});
+}
+
+#[test]
+fn direct_vote_overrides_delegation() {
+    let mut state = initial_state();
+    state = delegate(create_ctx(voter(1), 10), state, voter(2));
+    state = delegate(create_ctx(voter(3), 20), state, voter(1));
+    (state, _) = vote(create_ctx(voter(2), 30), state, 1);
+    (state, _) = vote(create_ctx(voter(1), 40), state, 2);
+    assert_eq!(state.delegations.get(&voter(1)), Some(&voter(2)));
+    assert_eq!(state.resolve_vote(&voter(1)), Some(2));
+    // the chain of voter 3 ends at the direct vote of voter 1
+    assert_eq!(state.resolve_vote(&voter(3)), Some(2));
+
+    (state, _) = count(create_ctx(voter(3), 100), state);
+    assert_eq!(counted_votes(&state), vec![1, 2]);
+    assert_eq!(state.result.unwrap().winning_option, Some(2));
+}
@@ -0,0 +1,54 @@
+//! Fuzzes the average salary contract, invoking the generated entry points of `initialize` and of
+//! every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use average_salary::{
+    __pbc_autogen__abort_wrapped, __pbc_autogen__compute_average_salary_wrapped,
+    __pbc_autogen__initialize_wrapped,
+};
+use fuzz_support::{
+    arbitrary_address, arbitrary_context, arbitrary_zk_state, invoke, Call, EntryPointInput,
+};
+use libfuzzer_sys::fuzz_target;
+
+const CALLS: [Call; 2] = [
+    Call::Action(__pbc_autogen__compute_average_salary_wrapped),
+    Call::Action(__pbc_autogen__abort_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+/// Generates the metadata of a secret variable, holding the lowest and highest included salaries
+/// and the department of a salary.
+fn metadata(u: &mut Unstructured) -> Result<Vec<u8>> {
+    Ok(u.bytes(17)?.to_vec())
+}
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_zk_state = arbitrary_zk_state(u, metadata)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx)
+            .state(&init_zk_state)
+            .rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let zk_state = arbitrary_zk_state(u, metadata)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.state(&zk_state).rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
@@ -7,12 +7,41 @@
 //! This implementation works in following steps:
 //!
 //! 1. Initialization on the blockchain.
-//! 2. Receival of multiple secret salaries, using the real zk protocol.
+//! 2. Receival of multiple secret salaries, using the real zk protocol. Each salary is submitted
+//!    along with the public id of the department of the employee, which is stored as metadata of
+//!    the secret variable.
 //! 3. Once enough salaries have been received, the contract owner can start the ZK computation.
-//! 4. The Zk computation sums all the given salaries together.
-//! 5. Once the zk computation is complete, the contract will publicize the the summed variable.
-//! 6. Once the summed variable is public, the contract will compute the average and store it in
-//!    the state, such that the value can be read by all.
+//! 4. The Zk computation sums all the given salaries together, sums the squares of the salaries,
+//!    counts the salaries, and finds the minimum and maximum salaries. Salaries outside the public
+//!    bounds given at initialization are excluded inside the computation, such that a single
+//!    outlier cannot destroy the average. The included salaries are also summed and counted per
+//!    department, using the department id in the metadata of each variable.
+//! 5. Once the zk computation is complete, the contract will publicize the the summed variables
+//!    and the number of included salaries, and the minimum and maximum salaries if the contract
+//!    was initialized to reveal them. The number of included salaries of a department is
+//!    publicized if at least [`MIN_NUM_EMPLOYEES`] employees of the department submitted a salary.
+//! 6. Once the summed variables are public, the contract will compute the average and the
+//!    variance of the included salaries and store them in the state. The sum of a department is
+//!    then only publicized if at least [`MIN_NUM_EMPLOYEES`] of its salaries were included, such
+//!    that the salaries of small departments are not revealed, even when most of their salaries
+//!    are outside the bounds.
+//! 7. Once the sums of the departments are public, the contract will compute the average of each
+//!    publicized department, such that the values can be read by all.
+//!
+//! The contract is long-lived, and computes the statistics once per epoch. When the statistics of
+//! an epoch have been computed, the average is stored for the epoch, and all secret variables are
+//! deleted, such that employees can submit their updated salaries for the next epoch. The
+//! administrator then starts the computation of the next epoch, once enough salaries have been
+//! received again.
+//!
+//! Salaries must be submitted before the submission deadline of the epoch, and at most
+//! [`MAX_NUM_EMPLOYEES`] salaries can be submitted per epoch. If fewer than [`MIN_NUM_EMPLOYEES`]
+//! salaries have been submitted by the deadline, the anonymity set is too small to compute the
+//! statistics without revealing too much about the individual salaries. Anyone can then abort the
+//! contract, which deletes all secret variables without computing, and closes the contract.
+//!
+//! Salaries are 64-bit variables, such that the sum of squares can be computed without
+//! overflowing for realistic salaries and numbers of employees.
 //!
 //! NOTE: This contract is missing several features that a production ready contract should
 //! possess, including:
@@ -22,6 +51,8 @@
 
 #![allow(unused_variables)]
 
+use std::collections::BTreeMap;
+
 #[macro_use]
 extern crate pbc_contract_codegen;
 extern crate pbc_contract_common;
@@ -34,55 +65,201 @@ use pbc_contract_common::zk::{CalculationStatus, SecretVarId, ZkInputDef, ZkStat
 use read_write_rpc_derive::ReadWriteRPC;
 use read_write_state_derive::ReadWriteState;
 
-/// Secret variable metadata. Unused for this contract, so we use a zero-sized struct to save space.
+/// Secret variable metadata, carrying the public bounds of the included salaries and the
+/// department of the employee into the zk computation. The bounds are the same for every salary
+/// variable.
 #[derive(ReadWriteState, ReadWriteRPC, Debug)]
 struct SecretVarMetadata {
+    /// The smallest salary included in the statistics
+    min_included_salary: i64,
+    /// The largest salary included in the statistics
+    max_included_salary: i64,
+    /// The department of the employee, given by the submitter
+    department_id: u8,
     #[cfg(feature = "plus_metadata")]
     metadata: u32,
 }
 
 /// The maximum size of MPC variables.
-const BITLENGTH_OF_SECRET_SALARY_VARIABLES: u32 = 32;
+const BITLENGTH_OF_SECRET_SALARY_VARIABLES: u32 = 64;
+
+/// Number of departments that salaries can be submitted for.
+const NUM_DEPARTMENTS: u8 = 4;
+
+/// Number of output variables of the zk computation summarizing all salaries: the sum, the sum of
+/// squares, the number of included salaries, the minimum and the maximum salary.
+const NUM_OUTPUT_VARIABLES: usize = 5;
+
+/// Number of output variables of the zk computation for each department: the sum and the number
+/// of included salaries. The department outputs follow the outputs summarizing all salaries.
+const NUM_DEPARTMENT_OUTPUT_VARIABLES: usize = 2;
+
+/// Number of output variables that are always opened: the sum, the sum of squares and the number
+/// of included salaries.
+const NUM_SUM_OUTPUT_VARIABLES: usize = 3;
 
 /// Number of employees to wait for before starting computation. A value of 2 or below is useless.
 const MIN_NUM_EMPLOYEES: u32 = 3;
 
+/// Maximum number of employees that can submit a salary in an epoch.
+const MAX_NUM_EMPLOYEES: u32 = 1000;
+
 /// This contract's state
 #[state]
 struct ContractState {
     /// Address allowed to start computation
     administrator: Address,
-    /// Will contain the result (average) when computation is complete
-    average_salary_result: Option<u32>,
+    /// Whether the minimum and maximum salaries are revealed
+    reveal_min_max: bool,
+    /// The smallest salary included in the statistics
+    min_included_salary: i64,
+    /// The largest salary included in the statistics
+    max_included_salary: i64,
+    /// Will contain the result (average) of the latest epoch when computation is complete
+    average_salary_result: Option<u64>,
+    /// Will contain the (population) variance of the salaries when computation is complete
+    salary_variance_result: Option<u128>,
+    /// Will contain the minimum salary when computation is complete, if revealed
+    min_salary_result: Option<u64>,
+    /// Will contain the maximum salary when computation is complete, if revealed
+    max_salary_result: Option<u64>,
     /// Will contain the number of employees after starting the computation
     num_employees: Option<u32>,
+    /// Will contain the number of employees whose salary is within the bounds when computation is
+    /// complete
+    num_included_employees: Option<u32>,
+    /// Will contain the number of employees of each department after starting the computation
+    department_num_employees: BTreeMap<u8, u32>,
+    /// Will contain the number of employees whose salary is within the bounds of each department
+    /// with at least [`MIN_NUM_EMPLOYEES`] employees when computation is complete
+    department_num_included_employees: BTreeMap<u8, u32>,
+    /// The output variables of the computation of the current epoch, until the epoch ends
+    output_variables: Vec<SecretVarId>,
+    /// Will contain the average of the included salaries of each department with at least
+    /// [`MIN_NUM_EMPLOYEES`] included salaries when computation is complete
+    department_average_salary_results: BTreeMap<u8, u64>,
+    /// The epoch whose salaries are currently being submitted or computed
+    current_epoch: u32,
+    /// The average salary of each completed epoch with at least one included salary
+    epoch_average_salary_results: BTreeMap<u32, u64>,
+    /// The duration in millis of the submission of salaries in each epoch
+    submission_duration_millis: i64,
+    /// The time in UTC millis after which salaries can no longer be submitted in the current epoch
+    submission_deadline_utc_millis: i64,
+    /// Whether the contract was aborted, because too few salaries were submitted by the deadline
+    closed: bool,
+}
+
+impl ContractState {
+    /// The departments whose numbers of included salaries are publicized, which are the
+    /// departments where at least [`MIN_NUM_EMPLOYEES`] employees have submitted a salary.
+    fn published_departments(&self) -> Vec<u8> {
+        self.department_num_employees
+            .iter()
+            .filter(|(_, num_employees)| **num_employees >= MIN_NUM_EMPLOYEES)
+            .map(|(department_id, _)| *department_id)
+            .collect()
+    }
+
+    /// The departments whose sums are publicized, which are the departments where at least
+    /// [`MIN_NUM_EMPLOYEES`] salaries were included.
+    fn averaged_departments(&self) -> Vec<u8> {
+        self.department_num_included_employees
+            .iter()
+            .filter(|(_, num_included)| **num_included >= MIN_NUM_EMPLOYEES)
+            .map(|(department_id, _)| *department_id)
+            .collect()
+    }
+
+    /// The output variable of the computation at `offset` among the outputs of the department
+    /// `department_id`, being 0 for the sum and 1 for the number of included salaries.
+    fn department_output(&self, department_id: u8, offset: usize) -> SecretVarId {
+        let start = NUM_OUTPUT_VARIABLES + department_id as usize * NUM_DEPARTMENT_OUTPUT_VARIABLES;
+        self.output_variables[start + offset]
+    }
+
+    /// Asserts that the contract has not been aborted.
+    fn assert_not_closed(&self) {
+        assert!(!self.closed, "The contract is closed");
+    }
 }
 
 /// Initializes contract
 ///
-/// Note that administrator is set to whoever initializes the contact.
+/// Note that administrator is set to whoever initializes the contact. If `reveal_min_max` is set,
+/// the minimum and maximum salaries are revealed along with the average and variance. Only
+/// salaries within `[min_included_salary, max_included_salary]` are included in the statistics.
+/// Salaries can be submitted for `submission_duration_millis` from the start of each epoch.
 #[init]
-fn initialize(ctx: ContractContext, zk_state: ZkState<SecretVarMetadata>) -> ContractState {
+fn initialize(
+    ctx: ContractContext,
+    zk_state: ZkState<SecretVarMetadata>,
+    reveal_min_max: bool,
+    min_included_salary: i64,
+    max_included_salary: i64,
+    submission_duration_millis: i64,
+) -> ContractState {
+    assert!(
+        0 <= min_included_salary && min_included_salary <= max_included_salary,
+        "The salary bounds must satisfy 0 <= min <= max, but were [{min_included_salary}, {max_included_salary}]"
+    );
+    assert!(
+        submission_duration_millis > 0,
+        "The submission duration must be positive, but was {submission_duration_millis}"
+    );
     ContractState {
         administrator: ctx.sender,
+        reveal_min_max,
+        min_included_salary,
+        max_included_salary,
         average_salary_result: None,
+        salary_variance_result: None,
+        min_salary_result: None,
+        max_salary_result: None,
         num_employees: None,
+        num_included_employees: None,
+        department_num_employees: BTreeMap::new(),
+        department_num_included_employees: BTreeMap::new(),
+        output_variables: vec![],
+        department_average_salary_results: BTreeMap::new(),
+        current_epoch: 0,
+        epoch_average_salary_results: BTreeMap::new(),
+        submission_duration_millis,
+        submission_deadline_utc_millis: ctx
+            .block_production_time
+            .saturating_add(submission_duration_millis),
+        closed: false,
     }
 }
 
-/// Adds another salary variable
+/// Adds another salary variable for an employee of the department `department_id`, for the current
+/// epoch. Salaries cannot be submitted while the statistics of an epoch are being computed, after
+/// the submission deadline of the epoch, or once [`MAX_NUM_EMPLOYEES`] salaries have been submitted.
 ///
-/// The ZkInputDef encodes that the variable should have size [`BITLENGTH_OF_SECRET_SALARY_VARIABLES`].
+/// The ZkInputDef encodes that the variable should have size [`BITLENGTH_OF_SECRET_SALARY_VARIABLES`],
+/// and attaches the salary bounds and the department as metadata for the zk computation.
 #[zk_on_secret_input(shortname = 0x40)]
 fn add_salary(
     context: ContractContext,
     state: ContractState,
     zk_state: ZkState<SecretVarMetadata>,
+    department_id: u8,
 ) -> (
     ContractState,
     Vec<EventGroup>,
     ZkInputDef<SecretVarMetadata>,
 ) {
+    state.assert_not_closed();
+    assert!(
+        context.block_production_time < state.submission_deadline_utc_millis,
+        "Salaries can only be submitted before the submission deadline at {}",
+        state.submission_deadline_utc_millis
+    );
+    let num_submitted = zk_state.secret_variables.len() + zk_state.pending_inputs.len();
+    assert!(
+        num_submitted < MAX_NUM_EMPLOYEES as usize,
+        "At most {MAX_NUM_EMPLOYEES} employees can submit a salary in an epoch"
+    );
     assert!(
         zk_state
             .secret_variables
@@ -92,9 +269,22 @@ fn add_salary(
         "Each address is only allowed to send one salary variable. Sender: {:?}",
         context.sender
     );
+    assert_eq!(
+        zk_state.calculation_state,
+        CalculationStatus::Waiting,
+        "Salaries can only be submitted in the Waiting state, but was {:?}",
+        zk_state.calculation_state,
+    );
+    assert!(
+        department_id < NUM_DEPARTMENTS,
+        "The department id must be below {NUM_DEPARTMENTS}, but was {department_id}"
+    );
     let input_def = ZkInputDef {
         seal: false,
         metadata: SecretVarMetadata {
+            min_included_salary: state.min_included_salary,
+            max_included_salary: state.max_included_salary,
+            department_id,
             #[cfg(feature = "plus_metadata")]
             metadata: 0x01020304,
         },
@@ -116,7 +306,7 @@ fn inputted_variable(
     state
 }
 
-/// Allows the administrator to start the computation of the average salary.
+/// Allows the administrator to start the computation of the average salary of the current epoch.
 ///
 /// The averaging computation is automatic beyond this call, involving several steps, as described in the module documentation.
 #[action(shortname = 0x01)]
@@ -129,6 +319,7 @@ fn compute_average_salary(
         context.sender, state.administrator,
         "Only administrator can start computation"
     );
+    state.assert_not_closed();
     assert_eq!(
         zk_state.calculation_state,
         CalculationStatus::Waiting,
@@ -140,38 +331,104 @@ fn compute_average_salary(
     assert!(num_employees >= MIN_NUM_EMPLOYEES , "At least {MIN_NUM_EMPLOYEES} employees must have submitted and confirmed their inputs, before starting computation, but had only {num_employees}");
 
     state.num_employees = Some(num_employees);
+    state.department_num_employees.clear();
+    for variable in &zk_state.secret_variables {
+        *state
+            .department_num_employees
+            .entry(variable.metadata.department_id)
+            .or_insert(0) += 1;
+    }
+    let num_output_variables =
+        NUM_OUTPUT_VARIABLES + NUM_DEPARTMENTS as usize * NUM_DEPARTMENT_OUTPUT_VARIABLES;
+    let output_metadata = (0..num_output_variables)
+        .map(|_| SecretVarMetadata {
+            min_included_salary: state.min_included_salary,
+            max_included_salary: state.max_included_salary,
+            department_id: 0,
+            #[cfg(feature = "plus_metadata")]
+            metadata: 1111,
+        })
+        .collect();
     (
         state,
         vec![],
-        vec![ZkStateChange::start_computation(vec![SecretVarMetadata {
-            #[cfg(feature = "plus_metadata")]
-            metadata: 1111,
-        }])],
+        vec![ZkStateChange::start_computation(output_metadata)],
     )
 }
 
+/// Aborts the contract when fewer than [`MIN_NUM_EMPLOYEES`] employees have submitted a salary by
+/// the submission deadline of the current epoch. Anyone can abort the contract.
+///
+/// All secret variables are deleted without computing, protecting the privacy of the submitted
+/// salaries when the anonymity set is too small, and the contract is closed.
+#[action(shortname = 0x02)]
+fn abort(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    state.assert_not_closed();
+    assert_eq!(
+        zk_state.calculation_state,
+        CalculationStatus::Waiting,
+        "The contract can only be aborted in the Waiting state, but was {:?}",
+        zk_state.calculation_state,
+    );
+    assert!(
+        context.block_production_time >= state.submission_deadline_utc_millis,
+        "The contract can only be aborted after the submission deadline at {}",
+        state.submission_deadline_utc_millis
+    );
+    let num_employees = zk_state.secret_variables.len() as u32;
+    assert!(
+        num_employees < MIN_NUM_EMPLOYEES,
+        "The contract cannot be aborted, as {num_employees} employees have submitted their salary"
+    );
+    state.closed = true;
+    (state, vec![], vec![delete_all_variables(&zk_state)])
+}
+
 /// Automatically called when the computation is completed
 ///
-/// The only thing we do is to instantly open/declassify the output variables.
+/// The only thing we do is to instantly open/declassify the output variables. The minimum and
+/// maximum salaries are only opened if the contract was initialized to reveal them, and the number
+/// of included salaries of a department is only opened if the department is publicized. The sums
+/// of the departments are opened once their numbers of included salaries are known, see
+/// [`open_sum_variable`].
 #[zk_on_compute_complete]
 fn sum_compute_complete(
     context: ContractContext,
-    state: ContractState,
+    mut state: ContractState,
     zk_state: ZkState<SecretVarMetadata>,
     output_variables: Vec<SecretVarId>,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let num_opened_outputs = if state.reveal_min_max {
+        NUM_OUTPUT_VARIABLES
+    } else {
+        NUM_SUM_OUTPUT_VARIABLES
+    };
+    state.output_variables = output_variables;
+    let mut variables = state.output_variables[..num_opened_outputs].to_vec();
+    for department_id in state.published_departments() {
+        variables.push(state.department_output(department_id, 1));
+    }
     (
         state,
         vec![],
-        vec![ZkStateChange::OpenVariables {
-            variables: output_variables,
-        }],
+        vec![ZkStateChange::OpenVariables { variables }],
     )
 }
 
-/// Automatically called when a variable is opened/declassified.
+/// Automatically called when variables are opened/declassified.
+///
+/// We can now read the sum variables, and compute the average and variance of the included
+/// salaries, which will be our final result, along with the minimum and maximum salaries if
+/// revealed. If no salary was within the bounds, no statistics are computed. Otherwise the sums of
+/// the departments with at least [`MIN_NUM_EMPLOYEES`] included salaries are opened next, after
+/// which the averages of these departments are computed, see [`open_department_sums`].
 ///
-/// We can now read the sum variable, and compute the average, which will be our final result.
+/// The average is stored as the result of the current epoch, and all secret variables are
+/// deleted before the next epoch begins, see [`end_epoch`].
 #[zk_on_variables_opened]
 fn open_sum_variable(
     context: ContractContext,
@@ -179,25 +436,135 @@ fn open_sum_variable(
     zk_state: ZkState<SecretVarMetadata>,
     opened_variables: Vec<SecretVarId>,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    if opened_variables.first() != state.output_variables.first() {
+        return open_department_sums(&context, state, &zk_state, &opened_variables);
+    }
+    let num_opened_outputs = if state.reveal_min_max {
+        NUM_OUTPUT_VARIABLES
+    } else {
+        NUM_SUM_OUTPUT_VARIABLES
+    };
+    let published_departments = state.published_departments();
+    let expected_num_variables = num_opened_outputs + published_departments.len();
     assert_eq!(
         opened_variables.len(),
-        1,
+        expected_num_variables,
         "Unexpected number of output variables"
     );
-    let sum = read_variable_u32_le(&zk_state, opened_variables.get(0));
-    let num_employees = state.num_employees.unwrap();
-    state.average_salary_result = Some(sum / num_employees);
-    (state, vec![], vec![ZkStateChange::ContractDone])
+    let sum = read_variable_u64_le(&zk_state, opened_variables.get(0));
+    let sum_of_squares = read_variable_u64_le(&zk_state, opened_variables.get(1));
+    let num_included_employees = read_variable_u64_le(&zk_state, opened_variables.get(2)) as u32;
+    state.num_included_employees = Some(num_included_employees);
+    state.department_average_salary_results.clear();
+    state.department_num_included_employees.clear();
+    if num_included_employees == 0 {
+        state.average_salary_result = None;
+        state.salary_variance_result = None;
+        state.min_salary_result = None;
+        state.max_salary_result = None;
+        return end_epoch(&context, state, &zk_state);
+    }
+    let average_salary = sum / num_included_employees as u64;
+    state.average_salary_result = Some(average_salary);
+    state
+        .epoch_average_salary_results
+        .insert(state.current_epoch, average_salary);
+    state.salary_variance_result = Some(variance(num_included_employees, sum, sum_of_squares));
+    if state.reveal_min_max {
+        state.min_salary_result = Some(read_variable_u64_le(&zk_state, opened_variables.get(3)));
+        state.max_salary_result = Some(read_variable_u64_le(&zk_state, opened_variables.get(4)));
+    }
+    for (index, department_id) in published_departments.into_iter().enumerate() {
+        let department_num_included =
+            read_variable_u64_le(&zk_state, opened_variables.get(num_opened_outputs + index));
+        state
+            .department_num_included_employees
+            .insert(department_id, department_num_included as u32);
+    }
+    let variables: Vec<SecretVarId> = state
+        .averaged_departments()
+        .into_iter()
+        .map(|department_id| state.department_output(department_id, 0))
+        .collect();
+    if variables.is_empty() {
+        return end_epoch(&context, state, &zk_state);
+    }
+    (
+        state,
+        vec![],
+        vec![ZkStateChange::OpenVariables { variables }],
+    )
+}
+
+/// Reads the opened sums of the departments with at least [`MIN_NUM_EMPLOYEES`] included
+/// salaries, and computes their averages, after which the epoch ends, see [`end_epoch`].
+fn open_department_sums(
+    context: &ContractContext,
+    mut state: ContractState,
+    zk_state: &ZkState<SecretVarMetadata>,
+    opened_variables: &[SecretVarId],
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let averaged_departments = state.averaged_departments();
+    assert_eq!(
+        opened_variables.len(),
+        averaged_departments.len(),
+        "Unexpected number of department sums"
+    );
+    for (index, department_id) in averaged_departments.into_iter().enumerate() {
+        let department_sum = read_variable_u64_le(zk_state, opened_variables.get(index));
+        let department_num_included = state.department_num_included_employees[&department_id];
+        state.department_average_salary_results.insert(
+            department_id,
+            department_sum / department_num_included as u64,
+        );
+    }
+    end_epoch(context, state, zk_state)
+}
+
+/// Ends the current epoch by deleting all secret variables, being the salaries of the epoch and
+/// the outputs of its computation, such that employees can submit their salaries again in the
+/// next epoch. The submission of the next epoch starts now.
+fn end_epoch(
+    context: &ContractContext,
+    mut state: ContractState,
+    zk_state: &ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    state.current_epoch += 1;
+    state.output_variables.clear();
+    state.submission_deadline_utc_millis = context
+        .block_production_time
+        .saturating_add(state.submission_duration_millis);
+    (state, vec![], vec![delete_all_variables(zk_state)])
+}
+
+/// Creates the state change deleting all secret variables.
+fn delete_all_variables(zk_state: &ZkState<SecretVarMetadata>) -> ZkStateChange {
+    let variables_to_delete = zk_state
+        .secret_variables
+        .iter()
+        .map(|variable| variable.variable_id)
+        .collect();
+    ZkStateChange::DeleteVariables {
+        variables_to_delete,
+    }
+}
+
+/// Computes the population variance of the salaries, `sum_of_squares / n - (sum / n)^2`, as
+/// `(n * sum_of_squares - sum^2) / n^2`, rounded down.
+fn variance(num_employees: u32, sum: u64, sum_of_squares: u64) -> u128 {
+    let n = num_employees as u128;
+    let sum = sum as u128;
+    (n * sum_of_squares as u128 - sum * sum) / (n * n)
 }
 
-/// Reads a variable's data as an u32.
-fn read_variable_u32_le(
+/// Reads a variable's data as an u64.
+fn read_variable_u64_le(
     zk_state: &ZkState<SecretVarMetadata>,
     sum_variable_id: Option<&SecretVarId>,
-) -> u32 {
+) -> u64 {
     let sum_variable_id = *sum_variable_id.unwrap();
     let sum_variable = zk_state.get_variable(sum_variable_id).unwrap();
-    let mut buffer = [0u8; 4];
+    let mut buffer = [0u8; 8];
     buffer.copy_from_slice(sum_variable.data.as_ref().unwrap().as_slice());
-    <u32>::from_le_bytes(buffer)
+    <u64>::from_le_bytes(buffer)
 }
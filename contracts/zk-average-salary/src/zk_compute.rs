@@ -1,18 +1,117 @@
 use pbc_zk::*;
 
-/// Perform a zk computation on secret-shared data sum the secret variables.
+/// The public bounds of the salaries included in the statistics, and the department of the
+/// employee. These are attached by the contract as the metadata of each salary variable.
+struct SalaryMetadata {
+    min_included_salary: i64,
+    max_included_salary: i64,
+    department_id: u8,
+}
+
+/// Sum and count the salaries of a single department that are within the public bounds.
+///
+/// ### Parameters:
+///
+/// * `department_id`: The department whose salaries are summed.
+///
+/// ### Returns:
+///
+/// The sum of the included salaries of the department, and the number of included salaries.
+fn department_statistics(department_id: u8) -> (Sbi64, Sbi64) {
+    let mut sum: Sbi64 = Sbi64::from(0);
+    let mut included_count: Sbi64 = Sbi64::from(0);
+
+    // Iterate the ids of the remaining variables, as the variables of previous epochs have been
+    // deleted
+    for variable_id in secret_variable_ids() {
+        let metadata = load_metadata::<SalaryMetadata>(variable_id);
+        // The department is public, so only the salaries of the department are loaded
+        if metadata.department_id == department_id {
+            let salary = load_sbi::<Sbi64>(variable_id);
+            if salary >= Sbi64::from(metadata.min_included_salary) {
+                if salary <= Sbi64::from(metadata.max_included_salary) {
+                    sum = sum + salary;
+                    included_count = included_count + Sbi64::from(1);
+                }
+            }
+        }
+    }
+    (sum, included_count)
+}
+
+/// Perform a zk computation on secret-shared data to compute statistics of the secret variables.
+/// Salaries outside the public bounds are excluded, such that a single outlier cannot distort the
+/// statistics.
 ///
 /// ### Returns:
 ///
-/// The sum of the secret variables.
-pub fn sum_everything() -> Sbi32 {
-    // Initialize state
-    let mut sum: Sbi32 = Sbi32::from(0);
+/// The sum of the included secret variables, the sum of their squares, the number of included
+/// variables, the minimum and maximum of the included secret variables, and then the sum and the
+/// number of included variables of each of the departments 0 to 3. The departments are listed one
+/// by one, as the outputs are returned as a tuple, and must match `NUM_DEPARTMENTS` of the
+/// contract.
+pub fn salary_statistics() -> (
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+    Sbi64,
+) {
+    // Initialize state. The minimum and maximum are only meaningful if a salary is included
+    let mut sum: Sbi64 = Sbi64::from(0);
+    let mut sum_of_squares: Sbi64 = Sbi64::from(0);
+    let mut included_count: Sbi64 = Sbi64::from(0);
+    let mut min: Sbi64 = Sbi64::from(i64::MAX);
+    let mut max: Sbi64 = Sbi64::from(i64::MIN);
 
-    // Sum each variable
-    for variable_id in 1..(num_secret_variables() + 1) {
-        sum = sum + load_sbi::<Sbi32>(variable_id);
+    // Accumulate each variable within the bounds, which are read from the metadata of the
+    // variable itself, iterating the ids of the remaining variables of the current epoch
+    for variable_id in secret_variable_ids() {
+        let bounds = load_metadata::<SalaryMetadata>(variable_id);
+        let lower_bound: Sbi64 = Sbi64::from(bounds.min_included_salary);
+        let upper_bound: Sbi64 = Sbi64::from(bounds.max_included_salary);
+        let salary = load_sbi::<Sbi64>(variable_id);
+        if salary >= lower_bound {
+            if salary <= upper_bound {
+                sum = sum + salary;
+                sum_of_squares = sum_of_squares + salary * salary;
+                included_count = included_count + Sbi64::from(1);
+                if salary < min {
+                    min = salary;
+                }
+                if salary > max {
+                    max = salary;
+                }
+            }
+        }
     }
 
-    sum
+    let (department_0_sum, department_0_count) = department_statistics(0);
+    let (department_1_sum, department_1_count) = department_statistics(1);
+    let (department_2_sum, department_2_count) = department_statistics(2);
+    let (department_3_sum, department_3_count) = department_statistics(3);
+
+    (
+        sum,
+        sum_of_squares,
+        included_count,
+        min,
+        max,
+        department_0_sum,
+        department_0_count,
+        department_1_sum,
+        department_1_count,
+        department_2_sum,
+        department_2_count,
+        department_3_sum,
+        department_3_count,
+    )
 }
@@ -0,0 +1,68 @@
+//! Fuzzes the second price auction contract, invoking the generated entry points of `initialize`
+//! and of every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{
+    arbitrary_address, arbitrary_context, arbitrary_zk_state, invoke, Call, EntryPointInput,
+};
+use libfuzzer_sys::fuzz_target;
+use zk_second_price::{
+    __pbc_autogen__claim_wrapped, __pbc_autogen__commit_bids_wrapped,
+    __pbc_autogen__compute_winner_wrapped, __pbc_autogen__initialize_wrapped,
+    __pbc_autogen__pay_deposit_callback_wrapped, __pbc_autogen__pay_deposit_wrapped,
+    __pbc_autogen__refund_deposits_callback_wrapped, __pbc_autogen__register_callback_wrapped,
+    __pbc_autogen__register_wrapped, __pbc_autogen__settle_payment_callback_wrapped,
+    __pbc_autogen__settle_payout_callback_wrapped, __pbc_autogen__start_new_round_wrapped,
+    __pbc_autogen__unregister_wrapped,
+};
+
+const CALLS: [Call; 12] = [
+    Call::Action(__pbc_autogen__register_wrapped),
+    Call::Callback(__pbc_autogen__register_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__unregister_wrapped),
+    Call::Action(__pbc_autogen__pay_deposit_wrapped),
+    Call::Callback(__pbc_autogen__pay_deposit_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__commit_bids_wrapped),
+    Call::Action(__pbc_autogen__compute_winner_wrapped),
+    Call::Action(__pbc_autogen__start_new_round_wrapped),
+    Call::Callback(__pbc_autogen__settle_payment_callback_wrapped, 1),
+    Call::Callback(__pbc_autogen__settle_payout_callback_wrapped, 2),
+    Call::Callback(__pbc_autogen__refund_deposits_callback_wrapped, 2),
+    Call::Action(__pbc_autogen__claim_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+/// Generates the metadata of a secret variable, holding the bidder id and the maximum bid of a bid.
+fn metadata(u: &mut Unstructured) -> Result<Vec<u8>> {
+    Ok(u.bytes(8)?.to_vec())
+}
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_zk_state = arbitrary_zk_state(u, metadata)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx)
+            .state(&init_zk_state)
+            .rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let zk_state = arbitrary_zk_state(u, metadata)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.state(&zk_state).rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
@@ -8,12 +8,50 @@
 //! This implementation works in the following steps:
 //!
 //! 1. Initialization on the blockchain.
-//! 2. Receival of secret bids, using zero-knowledge protocols.
-//! 3. Once enough bids have been received, the owner of the contract can initialize the auction.
-//! 4. The ZK computation computes the winning bid in a secure manner.
-//! 5. Once the ZK computation concludes, the winning bid will be published and the winner will be
+//! 2. Registration of bidders. Anyone can `register` as a bidder by staking the registration stake
+//! in the payment token, which is transferred to the contract using `transfer_from`. Once the
+//! transfer succeeds, the bidder is assigned the next bidder id. A bidder without a bid or a
+//! deposit in the current round can `unregister`, after which the stake can be claimed back.
+//! 3. Receival of secret bids, using zero-knowledge protocols. Only registered bidders can bid.
+//! 4. Once enough bids have been received, the owner of the contract calls `commit_bids`, which
+//! closes the bidding and has the nodes attest a commitment to the ids and owners of the secret
+//! bids. Once the commitment is attested, the owner can initialize the auction computation, which
+//! only starts if the secret bids are exactly the committed bids. The result can thereby be
+//! audited against the attested input set, and the owner cannot drop inconvenient bids before the
+//! computation.
+//! 5. The ZK computation computes the winning bid in a secure manner.
+//! 6. Once the ZK computation concludes, the winning bid will be published and the winner will be
 //! stored in the state, together with their bid.
+//! 7. Once the result is attested, the auction is settled automatically. The winner pays the
+//! second highest bid in the payment token using `transfer_from`, after which the payment is
+//! transferred to the owner and the sold asset, held by the contract, is transferred to the winner.
+//! If any of the transfers fail, the tokens are instead credited to the claim map, from which they
+//! can be claimed using `claim`. If the winner fails to pay, the owner can claim the asset back.
+//! 8. After the result is attested, and any bids revealed after the end of the round have been
+//! opened, the owner can call `start_new_round`, which deletes the secret bids and the result, and
+//! opens a new bidding round with the same registered bidders. This lets a single deployment run
+//! repeated auctions, for instance when the winner failed to pay.
 //!
+//! The owner must transfer the sold asset to the contract before each round is settled.
+//!
+//! The auction can be configured with `reveal_all_after_end`, making it auditable post-hoc. Once
+//! the result of a round is attested, all the bids of the round are opened and stored in the
+//! state by the id of their bidder, such that anyone can verify that the winner and the price were
+//! computed correctly. The bids are only revealed after the result is final, so revealing them
+//! cannot influence the auction.
+//!
+//! The auction can be configured to require a fixed public `bid_deposit` from each bidder, which
+//! must be paid using `pay_deposit` before the secret bid is submitted. Since the deposit is the
+//! same for every bidder, it reveals nothing about the bids. The deposit of the winner is applied
+//! towards the payment, and the deposits of all other bidders are refunded after attestation.
+//! If the winner fails to pay, their deposit is forfeited to the owner.
+//!
+//! The auction can be configured with a public `max_bid`. Bids exceeding the maximum bid are
+//! discarded secretly inside the computation, such that a buggy or malicious bid with an absurd
+//! amount, which its bidder could never settle, cannot win the auction or set the price. The
+//! number of discarded bids is published with the result for transparency, without revealing
+//! which bids were discarded. If every bid is discarded, the round has no winner: the deposits of
+//! all bidders are refunded, and the owner can claim the asset back.
 //!
 
 #![allow(unused_variables)]
@@ -22,9 +60,12 @@
 extern crate pbc_contract_codegen;
 extern crate pbc_contract_common;
 
+use std::collections::BTreeMap;
+
+use claim_map::{payout_event_group, Claim, ClaimMap, Payout};
 use create_type_spec_derive::CreateTypeSpec;
-use pbc_contract_common::address::Address;
-use pbc_contract_common::context::ContractContext;
+use pbc_contract_common::address::{Address, AddressType, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
 use pbc_contract_common::events::EventGroup;
 use pbc_contract_common::zk::{
     AttestationId, CalculationStatus, SecretVarId, ZkInputDef, ZkState, ZkStateChange,
@@ -34,29 +75,63 @@ use read_write_rpc_derive::ReadRPC;
 use read_write_rpc_derive::WriteRPC;
 use read_write_state_derive::ReadWriteState;
 
+#[cfg(test)]
+mod tests;
+
 /// Id of a contract bidder.
 #[repr(transparent)]
-#[derive(PartialEq, ReadRPC, WriteRPC, ReadWriteState, Debug, Clone, Copy, CreateTypeSpec)]
+#[derive(
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ReadRPC,
+    WriteRPC,
+    ReadWriteState,
+    Debug,
+    Clone,
+    Copy,
+    CreateTypeSpec,
+)]
 #[non_exhaustive]
 struct BidderId {
     id: i32,
 }
 
-/// Secret variable metadata. Contains unique ID of the bidder.
+/// Secret variable metadata. Contains unique ID of the bidder, and the public maximum bid of the
+/// auction, which the computation compares the bid against.
 #[derive(ReadWriteState, ReadRPC, WriteRPC, Debug)]
 struct SecretVarMetadata {
     bidder_id: BidderId,
+    max_bid: BidAmount,
 }
 
 /// The size of the MPC bid input variables.
 const BITLENGTH_OF_SECRET_BID_VARIABLES: [u32; 1] = [32];
 
+/// The bidder id of the metadata of the output variables of the computation.
+const OUTPUT_BIDDER_ID: BidderId = BidderId { id: -1 };
+
+/// The winner computed when no bid is positive and within the maximum bid.
+const NO_WINNER: BidderId = BidderId { id: -1 };
+
 /// Number of bids required before starting auction computation.
 const MIN_NUM_BIDDERS: u32 = 3;
 
 /// Type of tracking bid amount
 type BidAmount = i32;
 
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
 /// This state of the contract.
 #[state]
 struct ContractState {
@@ -64,16 +139,105 @@ struct ContractState {
     owner: Address,
     /// Registered bidders - only registered bidders are allowed to bid.
     registered_bidders: Vec<RegisteredBidder>,
+    /// The amount of payment tokens staked by each bidder to register.
+    registration_stake: u128,
+    /// The id assigned to the next registered bidder.
+    next_bidder_id: i32,
     /// The auction result
     auction_result: Option<AuctionResult>,
+    /// The token the winner pays with
+    payment_token: Address,
+    /// The token sold by the auction
+    asset_token: Address,
+    /// The amount of the sold token, held by the contract
+    asset_amount: u128,
+    /// Tokens that could not be transferred during settlement, and can be claimed instead
+    claim_map: BTreeMap<Address, SettlementClaim>,
+    /// The deposit of payment tokens each bidder must pay before bidding. Zero if no deposit is
+    /// required.
+    bid_deposit: u128,
+    /// The current round of the auction, starting at zero. Each round leaves one attestation of
+    /// its result.
+    round: u32,
+    /// Whether all bids of a round are opened once its result has been attested.
+    reveal_all_after_end: bool,
+    /// The opened bids of the current round by the id of their bidder, if the bids are revealed
+    /// after the end of the round.
+    revealed_bids: BTreeMap<BidderId, BidAmount>,
+    /// The maximum bid. Bids exceeding it are discarded by the computation. None if bids are not
+    /// limited.
+    max_bid: Option<BidAmount>,
+    /// The commitment to the bids of the current round, once the bidding has been closed.
+    bid_commitment: Option<BidCommitment>,
+    /// Whether the commitment to the bids of the current round has been attested.
+    bid_commitment_attested: bool,
+}
+
+/// A refund of a bid deposit.
+#[derive(ReadRPC, WriteRPC)]
+struct DepositRefund {
+    /// The bidder receiving the refund
+    bidder: Address,
+    /// The refunded amount of payment tokens
+    amount: u128,
+}
+
+/// Tokens claimable by an address after settlement.
+#[derive(Clone, ReadWriteState, CreateTypeSpec)]
+struct SettlementClaim {
+    /// Claimable amount of the payment token
+    payment_tokens: u128,
+    /// Claimable amount of the sold token
+    asset_tokens: u128,
+}
+
+impl Claim for SettlementClaim {
+    fn empty() -> Self {
+        SettlementClaim {
+            payment_tokens: 0,
+            asset_tokens: 0,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.payment_tokens += other.payment_tokens;
+        self.asset_tokens += other.asset_tokens;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.payment_tokens == 0 && self.asset_tokens == 0
+    }
 }
 
 #[derive(Clone, ReadWriteState, CreateTypeSpec, ReadRPC, WriteRPC)]
 struct AuctionResult {
-    /// Bidder id of the auction winner
+    /// Bidder id of the auction winner, or [`NO_WINNER`] if the round has no winner
     winner: BidderId,
     /// The winning bid
     second_highest_bid: BidAmount,
+    /// The number of bids discarded for exceeding the maximum bid
+    discarded_bids: i32,
+}
+
+/// A secret bid included in the commitment of a round.
+#[derive(Clone, PartialEq, ReadWriteState, CreateTypeSpec, ReadRPC, WriteRPC)]
+struct CommittedBid {
+    /// The id of the secret variable holding the bid
+    variable_id: SecretVarId,
+    /// The owner of the secret variable
+    owner: Address,
+    /// The bidder id of the bidder
+    bidder_id: BidderId,
+}
+
+/// The commitment to the set of secret bids that the result of a round is computed from. The
+/// commitment is attested by the nodes before the computation is started.
+#[derive(Clone, ReadWriteState, CreateTypeSpec, ReadRPC, WriteRPC)]
+struct BidCommitment {
+    /// The round of the committed bids
+    round: u32,
+    /// The committed bids, ordered by variable id
+    bids: Vec<CommittedBid>,
 }
 
 /// Representation of a registered bidder with an address
@@ -81,59 +245,244 @@ struct AuctionResult {
 struct RegisteredBidder {
     bidder_id: BidderId,
     address: Address,
+    /// Whether the bidder has paid the bid deposit
+    deposit_paid: bool,
 }
 
 /// Initializes contract
 ///
-/// Note that owner is set to whoever initializes the contact.
+/// Note that owner is set to whoever initializes the contact. The winner pays in `payment_token`
+/// and receives `asset_amount` of `asset_token`. Bidders register by staking `registration_stake`
+/// payment tokens, which must be positive. If `bid_deposit` is non-zero, each bidder must
+/// pay a deposit of `bid_deposit` payment tokens before bidding. If `reveal_all_after_end` is set,
+/// all bids of a round are opened once the result of the round has been attested. If `max_bid` is
+/// set, bids exceeding it are discarded by the computation.
 #[init]
-fn initialize(context: ContractContext, zk_state: ZkState<SecretVarMetadata>) -> ContractState {
+#[allow(clippy::too_many_arguments)]
+fn initialize(
+    context: ContractContext,
+    zk_state: ZkState<SecretVarMetadata>,
+    payment_token: Address,
+    asset_token: Address,
+    asset_amount: u128,
+    registration_stake: u128,
+    bid_deposit: u128,
+    reveal_all_after_end: bool,
+    max_bid: Option<BidAmount>,
+) -> ContractState {
+    assert!(
+        payment_token.address_type == AddressType::PublicContract
+            && asset_token.address_type == AddressType::PublicContract,
+        "Payment and asset tokens must be public contracts"
+    );
+    assert!(
+        max_bid.is_none_or(|max_bid| max_bid > 0),
+        "The maximum bid must be positive"
+    );
+    assert!(
+        registration_stake > 0,
+        "The registration stake must be positive"
+    );
     ContractState {
         owner: context.sender,
         registered_bidders: Vec::new(),
+        registration_stake,
+        next_bidder_id: 0,
         auction_result: None,
+        payment_token,
+        asset_token,
+        asset_amount,
+        claim_map: BTreeMap::new(),
+        bid_deposit,
+        round: 0,
+        reveal_all_after_end,
+        revealed_bids: BTreeMap::new(),
+        max_bid,
+        bid_commitment: None,
+        bid_commitment_attested: false,
     }
 }
 
-/// Registers a bidder with an address and updates the state accordingly.
-////
-/// Ensures that only the owner of the contract is able to register bidders.
+/// Registers the sender as a bidder, by transferring the registration stake of payment tokens
+/// from the sender to the contract. The bidder is registered in `register_callback`.
+///
+/// Anyone can register, but each address only once.
 #[action(shortname = 0x30)]
-fn register_bidder(
+fn register(
     context: ContractContext,
-    mut state: ContractState,
+    state: ContractState,
     zk_state: ZkState<SecretVarMetadata>,
-    bidder_id: i32,
-    address: Address,
-) -> ContractState {
-    let bidder_id = BidderId { id: bidder_id };
-
-    assert_eq!(
-        context.sender, state.owner,
-        "Only the owner can register bidders"
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        !state.is_registered(&context.sender),
+        "Duplicate bidder address: {:?}",
+        context.sender,
     );
 
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.payment_token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(state.registration_stake)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_REGISTER_CALLBACK)
+        .argument(context.sender)
+        .done();
+
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when a registration stake has been transferred. Registers the bidder with the next
+/// bidder id if the transfer succeeded.
+///
+/// A stake duplicating the registration of an already registered bidder is credited to the claim
+/// map of the bidder instead, such that it can be claimed back using `claim`.
+#[callback(shortname = 0x14)]
+fn register_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    bidder: Address,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
     assert!(
-        state
-            .registered_bidders
-            .iter()
-            .all(|x| x.address != address),
-        "Duplicate bidder address: {address:?}",
+        callback_ctx.success,
+        "Transfer of the registration stake did not succeed"
     );
+    if state.is_registered(&bidder) {
+        state.claim_map.credit(
+            bidder,
+            SettlementClaim {
+                payment_tokens: state.registration_stake,
+                asset_tokens: 0,
+            },
+        );
+    } else {
+        state.registered_bidders.push(RegisteredBidder {
+            bidder_id: BidderId {
+                id: state.next_bidder_id,
+            },
+            address: bidder,
+            deposit_paid: false,
+        });
+        state.next_bidder_id += 1;
+    }
+    (state, vec![], vec![])
+}
 
+/// Unregisters the sender, crediting the registration stake to the claim map of the sender, from
+/// which it can be claimed using `claim`.
+///
+/// Ensures that bidders can only unregister once the result of the round has been attested, or if
+/// they have neither paid a deposit nor bid in the current round.
+#[action(shortname = 0x32)]
+fn unregister(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let bidder_info = state.registered_bidder(&context.sender);
+    let has_bid = zk_state
+        .secret_variables
+        .iter()
+        .chain(zk_state.pending_inputs.iter())
+        .any(|v| v.owner == context.sender);
     assert!(
-        state
-            .registered_bidders
-            .iter()
-            .all(|x| x.bidder_id != bidder_id),
-        "Duplicate bidder id: {bidder_id:?}",
+        state.auction_result.is_some() || !(bidder_info.deposit_paid || has_bid),
+        "Bidders cannot unregister while taking part in the current round"
     );
 
     state
         .registered_bidders
-        .push(RegisteredBidder { bidder_id, address });
+        .retain(|x| x.address != context.sender);
+    state.claim_map.credit(
+        context.sender,
+        SettlementClaim {
+            payment_tokens: state.registration_stake,
+            asset_tokens: 0,
+        },
+    );
+    (state, vec![], vec![])
+}
 
-    state
+/// Pays the bid deposit of the sender, by transferring `bid_deposit` payment tokens to the
+/// contract.
+///
+/// Ensures that only registered bidders, who have not yet paid, can pay a deposit.
+#[action(shortname = 0x31)]
+fn pay_deposit(
+    context: ContractContext,
+    state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(state.bid_deposit > 0, "The auction requires no bid deposit");
+    assert_eq!(
+        zk_state.calculation_state,
+        CalculationStatus::Waiting,
+        "Deposits can only be paid before the auction is computed"
+    );
+    assert!(
+        state.auction_result.is_none(),
+        "Deposits can only be paid before the auction is computed"
+    );
+    let bidder_info = state.registered_bidder(&context.sender);
+    assert!(
+        !bidder_info.deposit_paid,
+        "The deposit has already been paid"
+    );
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(state.payment_token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(state.bid_deposit)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_PAY_DEPOSIT_CALLBACK)
+        .argument(context.sender)
+        .done();
+
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when a bid deposit has been transferred. Marks the deposit as paid if the transfer
+/// succeeded.
+///
+/// A deposit arriving after the auction result has been attested, duplicating an already paid
+/// deposit, or paid by a bidder who unregistered while the transfer was in flight, is not
+/// escrowed. It is credited to the claim map of the bidder instead, such that it can be claimed
+/// back using `claim`.
+#[callback(shortname = 0x12)]
+fn pay_deposit_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    bidder: Address,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the deposit did not succeed"
+    );
+    let settled = state.auction_result.is_some();
+    let unpaid_bidder = state
+        .registered_bidders
+        .iter_mut()
+        .find(|x| x.address == bidder && !x.deposit_paid);
+    match unpaid_bidder {
+        Some(bidder_info) if !settled => bidder_info.deposit_paid = true,
+        _ => state.claim_map.credit(
+            bidder,
+            SettlementClaim {
+                payment_tokens: state.bid_deposit,
+                asset_tokens: 0,
+            },
+        ),
+    }
+    (state, vec![], vec![])
 }
 
 /// Adds another bid variable to the ZkState.
@@ -149,15 +498,20 @@ fn add_bid(
     Vec<EventGroup>,
     ZkInputDef<SecretVarMetadata>,
 ) {
-    let bidder_info = state
-        .registered_bidders
-        .iter()
-        .find(|x| x.address == context.sender);
+    let bidder_info = state.registered_bidder(&context.sender);
 
-    let bidder_info = match bidder_info {
-        Some(bidder_info) => bidder_info,
-        None => panic!("{:?} is not a registered bidder", context.sender),
-    };
+    assert!(
+        state.auction_result.is_none(),
+        "Bids are not accepted until a new round is started"
+    );
+    assert!(
+        state.bid_commitment.is_none(),
+        "Bids are not accepted once the bids of the round have been committed"
+    );
+    assert!(
+        state.bid_deposit == 0 || bidder_info.deposit_paid,
+        "The bid deposit must be paid before bidding"
+    );
 
     // Assert that only one bid is placed per bidder
     assert!(
@@ -172,16 +526,56 @@ fn add_bid(
 
     let input_def = ZkInputDef {
         seal: false,
-        metadata: SecretVarMetadata {
-            bidder_id: bidder_info.bidder_id,
-        },
+        metadata: state.variable_metadata(bidder_info.bidder_id),
         expected_bit_lengths: BITLENGTH_OF_SECRET_BID_VARIABLES.to_vec(),
     };
 
     (state, vec![], input_def)
 }
 
+/// Allows the owner of the contract to close the bidding of the current round, committing to the
+/// secret bids received so far. The nodes are asked to attest the commitment, which lists the id,
+/// owner and bidder id of every secret bid, such that the result of the round can later be audited
+/// against the exact set of bids it was computed from.
+#[action(shortname = 0x04)]
+fn commit_bids(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only contract owner can commit the bids"
+    );
+    assert!(
+        state.bid_commitment.is_none(),
+        "The bids of this round have already been committed"
+    );
+    state.assert_round_not_attested(&zk_state);
+    assert!(
+        zk_state.pending_inputs.is_empty(),
+        "Cannot commit the bids while bids are being submitted"
+    );
+    let amount_of_bidders = zk_state.secret_variables.len() as u32;
+    assert!(
+        amount_of_bidders >= MIN_NUM_BIDDERS,
+        "At least {MIN_NUM_BIDDERS} bidders must have submitted bids for the bids to be committed",
+    );
+
+    let bid_commitment = BidCommitment {
+        round: state.round,
+        bids: committed_bids(&zk_state),
+    };
+    let attest_request = ZkStateChange::Attest {
+        data_to_attest: serialize_as_big_endian(&bid_commitment),
+    };
+    state.bid_commitment = Some(bid_commitment);
+    (state, vec![], vec![attest_request])
+}
+
 /// Allows the owner of the contract to start the computation, computing the winner of the auction.
+/// The bids of the round must have been committed and the commitment attested, and the secret bids
+/// must be exactly the committed bids.
 ///
 /// The second price auction computation is beyond this call, involving several ZK computation steps.
 #[action(shortname = 0x01)]
@@ -196,34 +590,35 @@ fn compute_winner(
         "Computation must start from Waiting state, but was {:?}",
         zk_state.calculation_state,
     );
-    assert_eq!(
-        zk_state.data_attestations.len(),
-        0,
-        "Auction must have exactly zero data_attestations at this point"
-    );
+    state.assert_round_not_attested(&zk_state);
 
     assert_eq!(
         context.sender, state.owner,
         "Only contract owner can start the auction"
     );
-    let amount_of_bidders = zk_state.secret_variables.len() as u32;
-
     assert!(
-        amount_of_bidders >= MIN_NUM_BIDDERS,
-        "At least {MIN_NUM_BIDDERS} bidders must have submitted bids for the auction to start",
+        state.auction_result.is_none(),
+        "The auction of this round has already been computed"
+    );
+    assert!(
+        state.bid_commitment_attested,
+        "The bids must be committed and attested before the auction can start"
+    );
+    let bid_commitment = state.bid_commitment.as_ref().unwrap();
+    assert!(
+        bid_commitment.bids == committed_bids(&zk_state),
+        "The bids have changed since they were committed"
     );
 
+    let output_metadata = vec![
+        state.variable_metadata(OUTPUT_BIDDER_ID),
+        state.variable_metadata(OUTPUT_BIDDER_ID),
+        state.variable_metadata(OUTPUT_BIDDER_ID),
+    ];
     (
         state,
         vec![],
-        vec![ZkStateChange::start_computation(vec![
-            SecretVarMetadata {
-                bidder_id: BidderId { id: -1 },
-            },
-            SecretVarMetadata {
-                bidder_id: BidderId { id: -1 },
-            },
-        ])],
+        vec![ZkStateChange::start_computation(output_metadata)],
     )
 }
 
@@ -237,11 +632,7 @@ fn auction_compute_complete(
     zk_state: ZkState<SecretVarMetadata>,
     output_variables: Vec<SecretVarId>,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
-    assert_eq!(
-        zk_state.data_attestations.len(),
-        0,
-        "Auction must have exactly zero data_attestations at this point"
-    );
+    state.assert_round_not_attested(&zk_state);
     (
         state,
         vec![],
@@ -253,27 +644,35 @@ fn auction_compute_complete(
 
 /// Automatically called when the auction result is declassified. Updates state to contain result,
 /// and requests attestation from nodes.
+///
+/// If the result has already been attested, the opened variables are instead the bids revealed
+/// after the end of the round, which are stored in the state by the id of their bidder.
 #[zk_on_variables_opened]
 fn open_auction_variable(
     context: ContractContext,
-    state: ContractState,
+    mut state: ContractState,
     zk_state: ZkState<SecretVarMetadata>,
     opened_variables: Vec<SecretVarId>,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    if state.auction_result.is_some() {
+        for variable_id in &opened_variables {
+            let variable = zk_state.get_variable(*variable_id).unwrap();
+            let bid: BidAmount = read_variable(&zk_state, Some(variable_id));
+            state.revealed_bids.insert(variable.metadata.bidder_id, bid);
+        }
+        return (state, vec![], vec![]);
+    }
     assert_eq!(
         opened_variables.len(),
-        2,
+        3,
         "Unexpected number of output variables"
     );
-    assert_eq!(
-        zk_state.data_attestations.len(),
-        0,
-        "Auction must have exactly zero data_attestations at this point"
-    );
+    state.assert_round_not_attested(&zk_state);
 
     let auction_result = AuctionResult {
         winner: read_variable(&zk_state, opened_variables.get(0)),
         second_highest_bid: read_variable(&zk_state, opened_variables.get(1)),
+        discarded_bids: read_variable(&zk_state, opened_variables.get(2)),
     };
 
     let attest_request = ZkStateChange::Attest {
@@ -283,7 +682,17 @@ fn open_auction_variable(
     (state, vec![], vec![attest_request])
 }
 
-/// Automatically called when some data is attested
+/// Automatically called when some data is attested. If the commitment to the bids of the round has
+/// not been attested yet, the attested data is the commitment, which is marked as attested.
+///
+/// Otherwise the attested data is the auction result. Stores the result and starts settlement, by
+/// transferring the second highest bid, minus the deposit of the winner, from the winner to the
+/// contract. The deposits of all other bidders, and any deposit exceeding the price, are refunded.
+/// If the round has no winner, all deposits are refunded and the asset is credited back to the
+/// owner.
+///
+/// The computation is marked as complete, such that a new round can be started afterwards. If the
+/// bids are revealed after the end of the round, all bids of the round are opened.
 #[zk_on_attestation_complete]
 fn auction_results_attested(
     context: ContractContext,
@@ -291,20 +700,403 @@ fn auction_results_attested(
     zk_state: ZkState<SecretVarMetadata>,
     attestation_id: AttestationId,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
-    assert_eq!(
-        zk_state.data_attestations.len(),
-        1,
-        "Auction must have exactly one attestation"
-    );
     let attestation = zk_state.get_attestation(attestation_id).unwrap();
 
     assert_eq!(attestation.signatures.len(), 4, "Must have four signatures");
 
+    if !state.bid_commitment_attested {
+        assert_eq!(
+            zk_state.data_attestations.len(),
+            state.completed_rounds_attestations() + 1,
+            "Auction round must have exactly one attestation"
+        );
+        state.bid_commitment_attested = true;
+        return (state, vec![], vec![]);
+    }
+    assert_eq!(
+        zk_state.data_attestations.len(),
+        state.completed_rounds_attestations() + 2,
+        "Auction round must have exactly two attestations"
+    );
+
     let auction_result = AuctionResult::rpc_read_from(&mut attestation.data.as_slice());
+    if auction_result.winner == NO_WINNER {
+        state.auction_result = Some(auction_result);
+        let refunds: Vec<DepositRefund> = state
+            .registered_bidders
+            .iter()
+            .filter(|x| x.deposit_paid)
+            .map(|x| DepositRefund {
+                bidder: x.address,
+                amount: state.bid_deposit,
+            })
+            .collect();
+        state.claim_map.credit(
+            state.owner,
+            SettlementClaim {
+                payment_tokens: 0,
+                asset_tokens: state.asset_amount,
+            },
+        );
+        let mut event_groups = vec![];
+        if !refunds.is_empty() {
+            event_groups.push(state.refund_deposits(refunds));
+        }
+        let zk_state_changes = state.round_output_complete(&zk_state);
+        return (state, event_groups, zk_state_changes);
+    }
+    let winner = state.bidder_address(auction_result.winner);
+    let price = auction_result.second_highest_bid as u128;
 
     state.auction_result = Some(auction_result);
 
-    (state, vec![], vec![ZkStateChange::ContractDone])
+    let winner_deposit = state.bid_deposit;
+    let remaining_payment = price.saturating_sub(winner_deposit);
+
+    let mut refunds: Vec<DepositRefund> = state
+        .registered_bidders
+        .iter()
+        .filter(|x| x.deposit_paid && x.address != winner)
+        .map(|x| DepositRefund {
+            bidder: x.address,
+            amount: state.bid_deposit,
+        })
+        .collect();
+    if winner_deposit > price {
+        refunds.push(DepositRefund {
+            bidder: winner,
+            amount: winner_deposit - price,
+        });
+    }
+
+    let mut event_groups = vec![];
+    if remaining_payment > 0 {
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(state.payment_token, token_contract_transfer_from())
+            .argument(winner)
+            .argument(context.contract_address)
+            .argument(remaining_payment)
+            .done();
+        event_group
+            .with_callback(SHORTNAME_SETTLE_PAYMENT_CALLBACK)
+            .argument(winner)
+            .argument(price)
+            .done();
+        event_groups.push(event_group.build());
+    } else {
+        event_groups.push(state.settle_payout(winner, price));
+    }
+    if !refunds.is_empty() {
+        event_groups.push(state.refund_deposits(refunds));
+    }
+
+    let zk_state_changes = state.round_output_complete(&zk_state);
+    (state, event_groups, zk_state_changes)
+}
+
+/// Allows the owner to start a new bidding round after the result of the current round has been
+/// attested, no computation is running, and, if the bids are revealed after the end of the round,
+/// all the bids of the current round have been revealed. The secret bids and results of the current round are deleted, the auction result,
+/// the commitment to the bids and any revealed bids are reset, and the registered bidders must pay
+/// their deposits again before bidding.
+#[action(shortname = 0x03)]
+fn start_new_round(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert_eq!(
+        context.sender, state.owner,
+        "Only contract owner can start a new round"
+    );
+    assert!(
+        state.auction_result.is_some(),
+        "A new round can only be started after the result of the current round has been attested"
+    );
+    assert_eq!(
+        zk_state.calculation_state,
+        CalculationStatus::Waiting,
+        "A new round can only be started when no computation is running"
+    );
+    assert!(
+        !state.reveal_all_after_end || state.bids_revealed(&zk_state),
+        "A new round can only be started once the bids of the current round have been revealed"
+    );
+
+    state.auction_result = None;
+    state.bid_commitment = None;
+    state.bid_commitment_attested = false;
+    state.revealed_bids.clear();
+    state.round += 1;
+    for bidder in state.registered_bidders.iter_mut() {
+        bidder.deposit_paid = false;
+    }
+
+    let variables_to_delete = zk_state
+        .secret_variables
+        .iter()
+        .map(|variable| variable.variable_id)
+        .collect();
+    (
+        state,
+        vec![],
+        vec![ZkStateChange::DeleteVariables {
+            variables_to_delete,
+        }],
+    )
+}
+
+/// Called when the winner has paid for the auction. If the payment succeeded, the payment is
+/// transferred to the owner and the asset to the winner. If it failed, the owner can claim the
+/// asset back, together with the forfeited deposit of the winner.
+#[callback(shortname = 0x10)]
+fn settle_payment_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    winner: Address,
+    price: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    if !callback_ctx.success {
+        state.claim_map.credit(
+            state.owner,
+            SettlementClaim {
+                payment_tokens: state.bid_deposit,
+                asset_tokens: state.asset_amount,
+            },
+        );
+        return (state, vec![], vec![]);
+    }
+
+    let event_group = state.settle_payout(winner, price);
+    (state, vec![event_group], vec![])
+}
+
+/// Called when the payment and the asset have been transferred. Any transfer that failed is
+/// credited to the claim map of its receiver instead.
+#[callback(shortname = 0x11)]
+fn settle_payout_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    winner: Address,
+    price: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    if !callback_ctx.results[0].succeeded {
+        state.claim_map.credit(
+            state.owner,
+            SettlementClaim {
+                payment_tokens: price,
+                asset_tokens: 0,
+            },
+        );
+    }
+    if !callback_ctx.results[1].succeeded {
+        state.claim_map.credit(
+            winner,
+            SettlementClaim {
+                payment_tokens: 0,
+                asset_tokens: state.asset_amount,
+            },
+        );
+    }
+    (state, vec![], vec![])
+}
+
+/// Called when bid deposits have been refunded. Any refund that failed is credited to the claim
+/// map of its bidder instead.
+#[callback(shortname = 0x13)]
+fn refund_deposits_callback(
+    context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    refunds: Vec<DepositRefund>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    for (refund, result) in refunds.into_iter().zip(callback_ctx.results.iter()) {
+        if !result.succeeded {
+            state.claim_map.credit(
+                refund.bidder,
+                SettlementClaim {
+                    payment_tokens: refund.amount,
+                    asset_tokens: 0,
+                },
+            );
+        }
+    }
+    (state, vec![], vec![])
+}
+
+/// Claims the tokens credited to the sender, if the automatic settlement failed.
+#[action(shortname = 0x02)]
+fn claim(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let claimable = match state.claim_map.take_claim(&context.sender) {
+        Some(claimable) if !claimable.is_empty() => claimable,
+        _ => panic!("Nothing to claim for {:?}", context.sender),
+    };
+    let payouts = [
+        Payout {
+            token: state.payment_token,
+            amount: claimable.payment_tokens,
+        },
+        Payout {
+            token: state.asset_token,
+            amount: claimable.asset_tokens,
+        },
+    ];
+    let event_group = payout_event_group(context.sender, &payouts);
+    (state, vec![event_group.build()], vec![])
+}
+
+impl ContractState {
+    /// Asserts that the result of the current round has not been attested yet. Every previous
+    /// round has left exactly two attestations, of the commitment to its bids and of its result.
+    /// The commitment to the bids of the current round may have been attested.
+    fn assert_round_not_attested(&self, zk_state: &ZkState<SecretVarMetadata>) {
+        assert_eq!(
+            zk_state.data_attestations.len(),
+            self.completed_rounds_attestations() + self.bid_commitment_attested as usize,
+            "Auction round must not have attested its result at this point"
+        );
+    }
+
+    /// The number of attestations left by the previous rounds, each attesting the commitment to
+    /// its bids and its result.
+    fn completed_rounds_attestations(&self) -> usize {
+        2 * self.round as usize
+    }
+
+    /// Creates the metadata of a secret variable of the given bidder, carrying the maximum bid
+    /// into the computation. Without a maximum bid, no bid can exceed the maximum.
+    fn variable_metadata(&self, bidder_id: BidderId) -> SecretVarMetadata {
+        SecretVarMetadata {
+            bidder_id,
+            max_bid: self.max_bid.unwrap_or(BidAmount::MAX),
+        }
+    }
+
+    /// Creates the changes marking the computation of the round as complete, opening all bids of
+    /// the round if the bids are revealed after the end of the round.
+    fn round_output_complete(&self, zk_state: &ZkState<SecretVarMetadata>) -> Vec<ZkStateChange> {
+        let mut zk_state_changes = vec![ZkStateChange::OutputComplete {
+            variables_to_delete: vec![],
+        }];
+        if self.reveal_all_after_end {
+            let bid_variables = zk_state
+                .secret_variables
+                .iter()
+                .filter(|variable| variable.metadata.bidder_id != OUTPUT_BIDDER_ID)
+                .map(|variable| variable.variable_id)
+                .collect();
+            zk_state_changes.push(ZkStateChange::OpenVariables {
+                variables: bid_variables,
+            });
+        }
+        zk_state_changes
+    }
+
+    /// Whether the bids of the round, opened by `round_output_complete`, have all been revealed.
+    fn bids_revealed(&self, zk_state: &ZkState<SecretVarMetadata>) -> bool {
+        zk_state
+            .secret_variables
+            .iter()
+            .filter(|variable| variable.metadata.bidder_id != OUTPUT_BIDDER_ID)
+            .all(|variable| {
+                self.revealed_bids
+                    .contains_key(&variable.metadata.bidder_id)
+            })
+    }
+
+    /// Whether the given address is a registered bidder.
+    fn is_registered(&self, address: &Address) -> bool {
+        self.registered_bidders
+            .iter()
+            .any(|x| x.address == *address)
+    }
+
+    /// Finds the registered bidder with the given address.
+    fn registered_bidder(&self, address: &Address) -> &RegisteredBidder {
+        match self
+            .registered_bidders
+            .iter()
+            .find(|x| x.address == *address)
+        {
+            Some(bidder_info) => bidder_info,
+            None => panic!("{:?} is not a registered bidder", address),
+        }
+    }
+
+    /// Creates the event group transferring the payment to the owner and the asset to the winner,
+    /// with a callback to `settle_payout_callback`.
+    fn settle_payout(&self, winner: Address, price: u128) -> EventGroup {
+        let mut event_group = EventGroup::builder();
+        event_group
+            .call(self.payment_token, token_contract_transfer())
+            .argument(self.owner)
+            .argument(price)
+            .done();
+        event_group
+            .call(self.asset_token, token_contract_transfer())
+            .argument(winner)
+            .argument(self.asset_amount)
+            .done();
+        event_group
+            .with_callback(SHORTNAME_SETTLE_PAYOUT_CALLBACK)
+            .argument(winner)
+            .argument(price)
+            .done();
+        event_group.build()
+    }
+
+    /// Creates the event group refunding bid deposits, with a callback to
+    /// `refund_deposits_callback`.
+    fn refund_deposits(&self, refunds: Vec<DepositRefund>) -> EventGroup {
+        let mut event_group = EventGroup::builder();
+        for refund in &refunds {
+            event_group
+                .call(self.payment_token, token_contract_transfer())
+                .argument(refund.bidder)
+                .argument(refund.amount)
+                .done();
+        }
+        event_group
+            .with_callback(SHORTNAME_REFUND_DEPOSITS_CALLBACK)
+            .argument(refunds)
+            .done();
+        event_group.build()
+    }
+
+    /// Finds the address of a registered bidder.
+    fn bidder_address(&self, bidder_id: BidderId) -> Address {
+        self.registered_bidders
+            .iter()
+            .find(|x| x.bidder_id == bidder_id)
+            .map(|x| x.address)
+            .unwrap_or_else(|| panic!("Unknown bidder id: {bidder_id:?}"))
+    }
+}
+
+/// Lists the secret bids of the current round, ordered by variable id, as committed to by
+/// `commit_bids`.
+fn committed_bids(zk_state: &ZkState<SecretVarMetadata>) -> Vec<CommittedBid> {
+    let mut bids: Vec<CommittedBid> = zk_state
+        .secret_variables
+        .iter()
+        .map(|variable| CommittedBid {
+            variable_id: variable.variable_id,
+            owner: variable.owner,
+            bidder_id: variable.metadata.bidder_id,
+        })
+        .collect();
+    bids.sort_by_key(|bid| bid.variable_id);
+    bids
 }
 
 /// Writes some value as RPC data.
@@ -0,0 +1,421 @@
+use pbc_contract_common::address::{Address, AddressType, ShortnameCallback};
+use pbc_contract_common::context::{CallbackContext, ContractContext, ExecutionResult};
+use pbc_contract_common::events::EventGroup;
+use pbc_contract_common::zk::{
+    AttestationId, CalculationStatus, DataAttestation, SecretVarId, ZkClosed, ZkState,
+    ZkStateChange,
+};
+use pbc_contract_common::Hash;
+
+use crate::{
+    auction_results_attested, initialize, pay_deposit_callback, refund_deposits_callback,
+    serialize_as_big_endian, settle_payment_callback, settle_payout_callback, start_new_round,
+    token_contract_transfer, token_contract_transfer_from, unregister, AuctionResult, BidderId,
+    ContractState, DepositRefund, RegisteredBidder, SecretVarMetadata,
+};
+
+const ASSET_AMOUNT: u128 = 10;
+const BID_DEPOSIT: u128 = 5;
+
+fn create_ctx(sender: Address) -> ContractContext {
+    let hash: Hash = [
+        0u8, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1,
+    ];
+    ContractContext {
+        contract_address: create_address(AddressType::PublicContract, 1),
+        sender,
+        block_time: 100,
+        block_production_time: 100,
+        current_transaction: hash,
+        original_transaction: hash,
+    }
+}
+
+fn create_address(address_type: AddressType, id: u8) -> Address {
+    let mut identifier = [0u8; 20];
+    identifier[19] = id;
+    Address {
+        address_type,
+        identifier,
+    }
+}
+
+fn create_callback_ctx(results: &[bool]) -> CallbackContext {
+    CallbackContext {
+        success: results.iter().all(|succeeded| *succeeded),
+        results: results
+            .iter()
+            .map(|succeeded| ExecutionResult {
+                succeeded: *succeeded,
+                return_data: vec![],
+            })
+            .collect(),
+    }
+}
+
+fn owner() -> Address {
+    create_address(AddressType::Account, 2)
+}
+
+fn payment_token() -> Address {
+    create_address(AddressType::PublicContract, 3)
+}
+
+fn asset_token() -> Address {
+    create_address(AddressType::PublicContract, 4)
+}
+
+fn bidder(id: u8) -> Address {
+    create_address(AddressType::Account, 10 + id)
+}
+
+fn empty_zk_state() -> ZkState<SecretVarMetadata> {
+    ZkState {
+        calculation_state: CalculationStatus::Waiting,
+        pending_inputs: vec![],
+        secret_variables: vec![],
+        data_attestations: vec![],
+    }
+}
+
+/// An auction with three registered bidders, who have all paid their deposits.
+fn create_state() -> ContractState {
+    let mut state = initialize(
+        create_ctx(owner()),
+        empty_zk_state(),
+        payment_token(),
+        asset_token(),
+        ASSET_AMOUNT,
+        1,
+        BID_DEPOSIT,
+        false,
+        None,
+    );
+    for id in 0..3 {
+        state.registered_bidders.push(RegisteredBidder {
+            bidder_id: BidderId { id },
+            address: bidder(id as u8),
+            deposit_paid: true,
+        });
+    }
+    state.next_bidder_id = 3;
+    state
+}
+
+fn create_attestation(raw_id: u32, data: Vec<u8>) -> DataAttestation {
+    DataAttestation {
+        attestation_id: AttestationId { raw_id },
+        signatures: vec![None, None, None, None],
+        data,
+    }
+}
+
+fn claim_of(state: &ContractState, address: &Address) -> (u128, u128) {
+    state
+        .claim_map
+        .get(address)
+        .map(|claim| (claim.payment_tokens, claim.asset_tokens))
+        .unwrap_or((0, 0))
+}
+
+fn expected_payout(winner: Address, price: u128) -> EventGroup {
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(payment_token(), token_contract_transfer())
+        .argument(owner())
+        .argument(price)
+        .done();
+    event_group
+        .call(asset_token(), token_contract_transfer())
+        .argument(winner)
+        .argument(ASSET_AMOUNT)
+        .done();
+    event_group
+        .with_callback(ShortnameCallback::from_u32(0x11))
+        .argument(winner)
+        .argument(price)
+        .done();
+    event_group.build()
+}
+
+#[test]
+fn attested_result_requests_payment_and_refunds_losing_deposits() {
+    let mut state = create_state();
+    state.bid_commitment_attested = true;
+    let auction_result = AuctionResult {
+        winner: BidderId { id: 1 },
+        second_highest_bid: 25,
+        discarded_bids: 0,
+    };
+    let mut zk_state = empty_zk_state();
+    zk_state.data_attestations = vec![
+        create_attestation(1, vec![]),
+        create_attestation(2, serialize_as_big_endian(&auction_result)),
+    ];
+
+    let (state, events, zk_state_changes) = auction_results_attested(
+        create_ctx(owner()),
+        state,
+        zk_state,
+        AttestationId { raw_id: 2 },
+    );
+
+    let mut payment = EventGroup::builder();
+    payment
+        .call(payment_token(), token_contract_transfer_from())
+        .argument(bidder(1))
+        .argument(create_address(AddressType::PublicContract, 1))
+        .argument(25 - BID_DEPOSIT)
+        .done();
+    payment
+        .with_callback(ShortnameCallback::from_u32(0x10))
+        .argument(bidder(1))
+        .argument(25u128)
+        .done();
+    let mut refunds = EventGroup::builder();
+    for id in [0, 2] {
+        refunds
+            .call(payment_token(), token_contract_transfer())
+            .argument(bidder(id))
+            .argument(BID_DEPOSIT)
+            .done();
+    }
+    refunds
+        .with_callback(ShortnameCallback::from_u32(0x13))
+        .argument(vec![
+            DepositRefund {
+                bidder: bidder(0),
+                amount: BID_DEPOSIT,
+            },
+            DepositRefund {
+                bidder: bidder(2),
+                amount: BID_DEPOSIT,
+            },
+        ])
+        .done();
+    assert_eq!(events, vec![payment.build(), refunds.build()]);
+    assert_eq!(
+        zk_state_changes,
+        vec![ZkStateChange::OutputComplete {
+            variables_to_delete: vec![],
+        }]
+    );
+    assert!(state.auction_result.is_some());
+    assert!(state.claim_map.is_empty());
+}
+
+#[test]
+fn successful_payment_pays_out_to_owner_and_winner() {
+    let (state, events, _) = settle_payment_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert_eq!(events, vec![expected_payout(bidder(1), 25)]);
+    assert!(state.claim_map.is_empty());
+}
+
+#[test]
+fn failed_payment_credits_asset_and_forfeited_deposit_to_owner() {
+    let (state, events, _) = settle_payment_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[false]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert!(events.is_empty());
+    assert_eq!(claim_of(&state, &owner()), (BID_DEPOSIT, ASSET_AMOUNT));
+    assert_eq!(claim_of(&state, &bidder(1)), (0, 0));
+}
+
+#[test]
+fn successful_payout_credits_nothing() {
+    let (state, events, _) = settle_payout_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true, true]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert!(events.is_empty());
+    assert!(state.claim_map.is_empty());
+}
+
+#[test]
+fn failed_payment_transfer_credits_only_the_owner() {
+    let (state, _, _) = settle_payout_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[false, true]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert_eq!(claim_of(&state, &owner()), (25, 0));
+    assert_eq!(claim_of(&state, &bidder(1)), (0, 0));
+}
+
+#[test]
+fn failed_asset_transfer_credits_only_the_winner() {
+    let (state, _, _) = settle_payout_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true, false]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert_eq!(claim_of(&state, &owner()), (0, 0));
+    assert_eq!(claim_of(&state, &bidder(1)), (0, ASSET_AMOUNT));
+}
+
+#[test]
+fn failed_payout_transfers_credit_both_receivers() {
+    let (state, _, _) = settle_payout_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[false, false]),
+        create_state(),
+        empty_zk_state(),
+        bidder(1),
+        25,
+    );
+    assert_eq!(claim_of(&state, &owner()), (25, 0));
+    assert_eq!(claim_of(&state, &bidder(1)), (0, ASSET_AMOUNT));
+}
+
+#[test]
+fn failed_refunds_are_credited_to_their_bidders() {
+    let refunds = vec![
+        DepositRefund {
+            bidder: bidder(0),
+            amount: BID_DEPOSIT,
+        },
+        DepositRefund {
+            bidder: bidder(1),
+            amount: 2,
+        },
+        DepositRefund {
+            bidder: bidder(2),
+            amount: BID_DEPOSIT,
+        },
+    ];
+    let (state, _, _) = refund_deposits_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true, false, false]),
+        create_state(),
+        empty_zk_state(),
+        refunds,
+    );
+    assert_eq!(claim_of(&state, &bidder(0)), (0, 0));
+    assert_eq!(claim_of(&state, &bidder(1)), (2, 0));
+    assert_eq!(claim_of(&state, &bidder(2)), (BID_DEPOSIT, 0));
+    assert_eq!(state.claim_map.len(), 2);
+}
+
+#[test]
+fn deposit_of_unregistered_bidder_is_credited() {
+    let mut state = create_state();
+    state.registered_bidders[0].deposit_paid = false;
+    let (state, _, _) = unregister(create_ctx(bidder(0)), state, empty_zk_state());
+    let stake = state.registration_stake;
+
+    // The deposit was paid while the bidder unregistered
+    let (state, _, _) = pay_deposit_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true]),
+        state,
+        empty_zk_state(),
+        bidder(0),
+    );
+    assert_eq!(claim_of(&state, &bidder(0)), (stake + BID_DEPOSIT, 0));
+    assert!(state
+        .registered_bidders
+        .iter()
+        .all(|x| x.address != bidder(0)));
+}
+
+#[test]
+fn deposit_of_registered_bidder_is_marked_as_paid() {
+    let mut state = create_state();
+    state.registered_bidders[0].deposit_paid = false;
+    let (state, _, _) = pay_deposit_callback(
+        create_ctx(payment_token()),
+        create_callback_ctx(&[true]),
+        state,
+        empty_zk_state(),
+        bidder(0),
+    );
+    assert!(state.registered_bidders[0].deposit_paid);
+    assert_eq!(claim_of(&state, &bidder(0)), (0, 0));
+}
+
+/// A settled round of an auction revealing all bids, where each of the three bidders has bid.
+fn settled_revealing_round() -> (ContractState, ZkState<SecretVarMetadata>) {
+    let mut state = create_state();
+    state.reveal_all_after_end = true;
+    state.auction_result = Some(AuctionResult {
+        winner: BidderId { id: 1 },
+        second_highest_bid: 25,
+        discarded_bids: 0,
+    });
+    let mut zk_state = empty_zk_state();
+    for id in 0..3 {
+        zk_state.secret_variables.push(ZkClosed {
+            variable_id: SecretVarId::new(id as u32 + 1),
+            owner: bidder(id as u8),
+            is_sealed: false,
+            metadata: state.variable_metadata(BidderId { id }),
+            data: None,
+        });
+    }
+    (state, zk_state)
+}
+
+#[test]
+fn new_round_starts_once_bids_are_revealed() {
+    let (mut state, zk_state) = settled_revealing_round();
+    for id in 0..3 {
+        state.revealed_bids.insert(BidderId { id }, 10 * id);
+    }
+    let (state, _, zk_state_changes) = start_new_round(create_ctx(owner()), state, zk_state);
+    assert!(state.auction_result.is_none());
+    assert!(state.revealed_bids.is_empty());
+    assert_eq!(
+        zk_state_changes,
+        vec![ZkStateChange::DeleteVariables {
+            variables_to_delete: vec![
+                SecretVarId::new(1),
+                SecretVarId::new(2),
+                SecretVarId::new(3)
+            ],
+        }]
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "A new round can only be started once the bids of the current round have been revealed"
+)]
+fn new_round_waits_for_reveal() {
+    let (mut state, zk_state) = settled_revealing_round();
+    state.revealed_bids.insert(BidderId { id: 0 }, 10);
+    start_new_round(create_ctx(owner()), state, zk_state);
+}
+
+#[test]
+#[should_panic(expected = "A new round can only be started when no computation is running")]
+fn new_round_waits_for_computation() {
+    let (mut state, mut zk_state) = settled_revealing_round();
+    for id in 0..3 {
+        state.revealed_bids.insert(BidderId { id }, 10 * id);
+    }
+    zk_state.calculation_state = CalculationStatus::Output;
+    start_new_round(create_ctx(owner()), state, zk_state);
+}
@@ -2,23 +2,42 @@
 /// Finds the highest bidder and the amount of the second-highest bid
 use pbc_zk::*;
 
-pub fn zk_compute() -> (Sbi32, Sbi32) {
+/// The bidder id output when no bid is positive and within the maximum bid, such that there is no
+/// winner.
+const NO_WINNER_ID: i32 = -1;
+
+/// The metadata attached by the contract to each bid variable: the id of the bidder, and the
+/// public maximum bid of the auction.
+struct BidMetadata {
+    bidder_id: i32,
+    max_bid: i32,
+}
+
+pub fn zk_compute() -> (Sbi32, Sbi32, Sbi32) {
     // Initialize state
-    let mut highest_bidder: Sbi32 = Sbi32::from(load_metadata::<i32>(1));
+    let mut highest_bidder: Sbi32 = Sbi32::from(NO_WINNER_ID);
     let mut highest_amount: Sbi32 = Sbi32::from(0);
     let mut second_highest_amount: Sbi32 = Sbi32::from(0);
+    let mut discarded_bids: Sbi32 = Sbi32::from(0);
 
-    // Determine max
-    for variable_id in 1..(num_secret_variables() + 1) {
-        if load_sbi::<Sbi32>(variable_id) > highest_amount {
+    // Determine max, discarding bids exceeding the maximum bid. The ids of the remaining
+    // variables are iterated, as the variables of previous rounds have been deleted
+    for variable_id in secret_variable_ids() {
+        let metadata = load_metadata::<BidMetadata>(variable_id);
+        let max_bid: Sbi32 = Sbi32::from(metadata.max_bid);
+        let bid = load_sbi::<Sbi32>(variable_id);
+        if bid > max_bid {
+            discarded_bids = discarded_bids + Sbi32::from(1);
+        } else if bid > highest_amount {
             second_highest_amount = highest_amount;
-            highest_amount = load_sbi::<Sbi32>(variable_id);
-            highest_bidder = Sbi32::from(load_metadata::<i32>(variable_id));
-        } else if load_sbi::<Sbi32>(variable_id) > second_highest_amount {
-            second_highest_amount = load_sbi::<Sbi32>(variable_id);
+            highest_amount = bid;
+            highest_bidder = Sbi32::from(metadata.bidder_id);
+        } else if bid > second_highest_amount {
+            second_highest_amount = bid;
         }
     }
 
-    // Return highest bidder index, and second highest amount
-    (highest_bidder, second_highest_amount)
+    // Return highest bidder index, or NO_WINNER_ID if every bid was discarded or zero, the second
+    // highest amount, and the number of discarded bids
+    (highest_bidder, second_highest_amount, discarded_bids)
 }
@@ -0,0 +1,62 @@
+//! Fuzzes the secret voting contract, invoking the generated entry points of `initialize` and of
+//! every action and callback with arbitrary RPC payloads.
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use fuzz_support::{
+    arbitrary_address, arbitrary_context, arbitrary_zk_state, invoke, Call, EntryPointInput,
+};
+use libfuzzer_sys::fuzz_target;
+use secret_voting::{
+    __pbc_autogen__initialize_wrapped, __pbc_autogen__register_as_voter_callback_wrapped,
+    __pbc_autogen__register_as_voter_wrapped, __pbc_autogen__retract_vote_wrapped,
+    __pbc_autogen__start_vote_counting_wrapped, __pbc_autogen__transfer_administration_wrapped,
+    __pbc_autogen__withdraw_locked_tokens_callback_wrapped,
+    __pbc_autogen__withdraw_locked_tokens_wrapped,
+};
+
+const CALLS: [Call; 7] = [
+    Call::Action(__pbc_autogen__register_as_voter_wrapped),
+    Call::Callback(__pbc_autogen__register_as_voter_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__withdraw_locked_tokens_wrapped),
+    Call::Callback(__pbc_autogen__withdraw_locked_tokens_callback_wrapped, 1),
+    Call::Action(__pbc_autogen__retract_vote_wrapped),
+    Call::Action(__pbc_autogen__start_vote_counting_wrapped),
+    Call::Action(__pbc_autogen__transfer_administration_wrapped),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzz_actions(&mut Unstructured::new(data));
+});
+
+/// Generates the metadata of a secret variable, holding the type of a variable, being a vote or a
+/// count of yes votes, and whether it is a choice ballot.
+fn metadata(u: &mut Unstructured) -> Result<Vec<u8>> {
+    Ok(vec![u.int_in_range(1..=2)?, u.arbitrary::<bool>()? as u8])
+}
+
+fn fuzz_actions(u: &mut Unstructured) -> Result<()> {
+    let contract = arbitrary_address(u)?;
+    let init_ctx = arbitrary_context(u, contract)?;
+    let init_zk_state = arbitrary_zk_state(u, metadata)?;
+    let init_payload: Vec<u8> = u.arbitrary()?;
+    let Some(mut state) = invoke(
+        __pbc_autogen__initialize_wrapped,
+        EntryPointInput::new(&init_ctx)
+            .state(&init_zk_state)
+            .rpc(&init_payload),
+    ) else {
+        return Ok(());
+    };
+
+    while !u.is_empty() {
+        let ctx = arbitrary_context(u, contract)?;
+        let (entry_point, input) = u.choose(&CALLS)?.input(u, &ctx, &state)?;
+        let zk_state = arbitrary_zk_state(u, metadata)?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if let Some(next_state) = invoke(entry_point, input.state(&zk_state).rpc(&payload)) {
+            state = next_state;
+        }
+    }
+    Ok(())
+}
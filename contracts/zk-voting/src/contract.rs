@@ -4,41 +4,81 @@
 //! voting upon some question, without revealing their personal preference, similar to many
 //! democratic election processes.
 //!
+//! A single ballot can carry up to [`MAX_NUM_QUESTIONS`] questions, each with its own voting
+//! rules. Voters answer all questions at once with a secret bit-vector.
+//!
 //! This contract's flow follows as:
 //!
 //! 1. Initialization of contract with voting information, including voting treshhold,
 //!    administrator, voting addresses, and minimum voting period.
-//! 2. Voters send their votes. (0 is against, any other value is for)
-//! 3. At some point after the minimum voting period, the administrator starts the voting counting
-//!    process.
-//! 4. Zk Computation sums yes votes and no votes, and output each as a separate variable.
+//! 2. Voters send their votes. (Bit `i` of the vote is the answer to question `i`; 0 is against,
+//!    1 is for) Until the voting deadline a voter can retract their vote, and then vote again.
+//! 3. At some point after the minimum voting period, the administrator or a co-administrator
+//!    starts the voting counting process. If none of them have done so within a grace period, the
+//!    counting can be started by anyone.
+//! 4. Zk Computation sums yes votes for each question, and output each sum as a separate variable.
 //! 5. When computation is complete the contract will open the output variables.
-//! 6. The contract computes whether the vote was accepted or rejected.
+//! 6. The contract computes whether the vote on each question was accepted or rejected.
+//!
+//! Instead of relying solely on the fixed list of allowed voters, the contract can be initialized
+//! with an eligibility token. Holders of the token can then register as voters using
+//! `register_as_voter`, which locks a fixed amount of their tokens in the contract using the
+//! `transfer_from` action of the token contract, and admits them as voters in the callback once
+//! the tokens have arrived. Since the tokens stay locked until the vote has been counted, the same
+//! tokens cannot be moved to another account to register it as well, and each registered voter is
+//! backed by their own locked tokens. Once the vote has been counted, the voters withdraw their
+//! locked tokens using `withdraw_locked_tokens`. The contract is only marked as done once all
+//! locked tokens have been withdrawn.
+//!
+//! The administrator can hand over the administration to another account using
+//! `transfer_administration`. Together with the co-administrators and the grace period for
+//! starting the counting, this ensures that the vote can be counted even if the original
+//! administrator disappears.
+//!
+//! Instead of questions, a ballot can carry up to [`MAX_NUM_OPTIONS`] options, of which each voter
+//! secretly chooses one by submitting the index of the option as their vote. The zk computation
+//! then counts the votes for each option, and the voting rules decide for each option whether it
+//! got the required ratio of the votes. The winner is the option with the most votes among those
+//! that got the required ratio, unless several of them are tied.
+//!
+//! The contract can be initialized to publish the turnout of the vote. The number of secret votes
+//! that have been committed is then counted in the public state as votes are confirmed on chain and
+//! retracted, such that observers can follow the participation without learning any votes.
+//!
+//! In attestation mode the attested results are followed by a digest of the voters who submitted
+//! a secret vote, being the SHA-256 hash of their addresses in sorted order. Off-chain verifiers
+//! can thereby audit the participation against the list of voters, without learning any votes.
 
 #[macro_use]
 extern crate pbc_contract_codegen;
 extern crate pbc_contract_common;
 
+use std::collections::BTreeMap;
+
 use create_type_spec_derive::CreateTypeSpec;
-use pbc_contract_common::address::Address;
-use pbc_contract_common::context::ContractContext;
+use pbc_contract_common::address::{Address, Shortname};
+use pbc_contract_common::context::{CallbackContext, ContractContext};
 use pbc_contract_common::events::EventGroup;
 #[cfg(feature = "attestation")]
 use pbc_contract_common::zk::AttestationId;
 use pbc_contract_common::zk::{CalculationStatus, SecretVarId, ZkInputDef, ZkState, ZkStateChange};
+use pbc_contract_common::Hash;
 use pbc_traits::ReadWriteState;
 use read_write_rpc_derive::ReadWriteRPC;
 use read_write_state_derive::ReadWriteState;
+use sha2::{Digest, Sha256};
 
 mod fraction;
 
 use fraction::Fraction;
 
-/// Secret variable metadata. Unused for this contract, so we use a zero-sized struct to save space.
+/// Secret variable metadata, telling the zk computation how to count the votes.
 #[derive(ReadWriteState, Debug)]
 #[repr(C)]
 struct SecretVarMetadata {
     variable_type: SecretVarType,
+    /// Whether the vote is the index of the chosen option, rather than a bit-vector of answers
+    choice_ballot: bool,
 }
 
 #[derive(ReadWriteState, Debug, PartialEq)]
@@ -51,6 +91,14 @@ enum SecretVarType {
 /// The maximum size of MPC variables.
 const BITLENGTH_OF_SECRET_VOTE_VARIABLES: u32 = 32;
 
+/// The maximum number of questions on a ballot. The zk computation always outputs a yes count for
+/// this many questions, of which only the first `num_questions` are used.
+const MAX_NUM_QUESTIONS: u8 = 8;
+
+/// The maximum number of options on a choice ballot. The zk computation outputs the count of each
+/// option in place of the yes counts of the first questions.
+const MAX_NUM_OPTIONS: u8 = 4;
+
 /// Definition of the voting rules
 #[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
 struct VoteBasis {
@@ -75,6 +123,16 @@ impl VoteBasis {
     };
 }
 
+/// Eligibility of voters based on their holdings of an MPC-20 token.
+#[derive(ReadWriteRPC, ReadWriteState, CreateTypeSpec, Clone)]
+struct TokenEligibility {
+    /// The token whose holders are eligible to vote
+    token: Address,
+    /// The amount of the token each voter locks in the contract to register, until the vote has
+    /// been counted
+    lock_amount: u128,
+}
+
 #[derive(ReadWriteState, CreateTypeSpec, Clone)]
 struct VoteResult {
     votes_for: u32,
@@ -82,6 +140,15 @@ struct VoteResult {
     passed: bool,
 }
 
+/// The data attested when the vote has been counted.
+#[derive(ReadWriteState)]
+struct AttestedResults {
+    /// Result of the vote on each question
+    vote_results: Vec<VoteResult>,
+    /// Digest of the voters who submitted a secret vote, see [`participation_digest`]
+    participation_digest: Hash,
+}
+
 impl VoteBasis {
     fn assert_valid(&self) {
         self.required_ratio.assert_valid()
@@ -91,8 +158,10 @@ impl VoteBasis {
 /// This contract's state
 #[state]
 struct ContractState {
-    /// Address allowed to start computation
+    /// Address allowed to start computation, and to transfer the administration
     administrator: Address,
+    /// Additional addresses allowed to start computation
+    co_administrators: Vec<Address>,
     /// When the voting stops; at this point all inputs must have been made, though not necessarily
     /// finalized.
     ///
@@ -107,11 +176,27 @@ struct ContractState {
     deadline_commitment_time: i64,
     /// Allowed voting addresses
     allowed_voters: Vec<Address>,
+    /// Token holdings allowing additional voters to register, if any
+    eligibility_token: Option<TokenEligibility>,
+    /// The tokens locked by each registered voter, until they are withdrawn after the vote has been
+    /// counted
+    locked_tokens: BTreeMap<Address, u128>,
+    /// Whether the vote has been counted, and attested in attestation mode
+    completed: bool,
 
-    /// Definition of the voting rules
-    vote_definition: VoteBasis,
-
-    vote_result: Option<VoteResult>,
+    /// Number of questions on the ballot, which is zero for a choice ballot
+    num_questions: u8,
+    /// Labels of the options of a choice ballot, or empty if the ballot carries questions
+    options: Vec<String>,
+    /// Definition of the voting rules of each question, or of each option of a choice ballot
+    vote_definitions: Vec<VoteBasis>,
+    /// Result of the vote on each question or option, once counted
+    vote_results: Option<Vec<VoteResult>>,
+    /// Index of the winning option of a choice ballot, once counted. None if no option got the
+    /// required ratio of the votes, or several of them are tied.
+    winning_option: Option<u8>,
+    /// Number of secret votes committed so far, if the turnout is published
+    turnout: Option<u32>,
 }
 
 /// Number of milliseconds between closing for inputs, and when the counting can start at the
@@ -120,34 +205,105 @@ struct ContractState {
 /// Milliseconds equal to an hour.
 const ESTIMATED_MAX_INPUT_COMMITMENT_DURATION_MS: i64 = 60 * 60 * 1000;
 
+/// Number of milliseconds after [`ContractState::deadline_commitment_time`] during which only the
+/// administrators can start the counting. Afterwards anybody can start it.
+///
+/// Milliseconds equal to a day.
+const ADMINISTRATOR_COUNTING_GRACE_PERIOD_MS: i64 = 24 * 60 * 60 * 1000;
+
 /// Initializes contract
 ///
 /// Note that administrator is set to whoever initializes the contact.
+///
+/// `vote_definitions` must either contain the voting rules of each of the `num_questions`
+/// questions, or a single definition used for all questions.
+///
+/// If `options` are given, the ballot is a choice ballot with no questions, and `num_questions`
+/// must be zero. The `vote_definitions` then contain the voting rules of each option, or a single
+/// definition used for all options.
+///
+/// If `eligibility_token` is given, holders of the token can register as voters in addition to
+/// the `allowed_voters`, by locking tokens in the contract.
+///
+/// If `publish_turnout` is set, the number of committed secret votes is counted in the state.
+///
+/// The `co_administrators` can start the counting along with the administrator.
 #[init]
+#[allow(clippy::too_many_arguments)]
 fn initialize(
     ctx: ContractContext,
     _zk_state: ZkState<SecretVarMetadata>,
     voting_duration_ms: u32,
     allowed_voters: Vec<Address>,
-    vote_definition: VoteBasis,
+    num_questions: u8,
+    vote_definitions: Vec<VoteBasis>,
+    eligibility_token: Option<TokenEligibility>,
+    publish_turnout: bool,
+    co_administrators: Vec<Address>,
+    options: Vec<String>,
 ) -> ContractState {
-    vote_definition.assert_valid();
+    let num_definitions = if options.is_empty() {
+        assert!(
+            0 < num_questions && num_questions <= MAX_NUM_QUESTIONS,
+            "The number of questions must be between 1 and {}, but was {}",
+            MAX_NUM_QUESTIONS,
+            num_questions,
+        );
+        num_questions as usize
+    } else {
+        assert!(
+            2 <= options.len() && options.len() <= MAX_NUM_OPTIONS as usize,
+            "The number of options must be between 2 and {}, but was {}",
+            MAX_NUM_OPTIONS,
+            options.len(),
+        );
+        assert_eq!(
+            num_questions, 0,
+            "A choice ballot cannot carry questions, but had {}",
+            num_questions,
+        );
+        options.len()
+    };
+    let vote_definitions = if vote_definitions.len() == 1 {
+        vec![vote_definitions[0].clone(); num_definitions]
+    } else {
+        vote_definitions
+    };
+    assert_eq!(
+        vote_definitions.len(),
+        num_definitions,
+        "Expected a vote definition for each of the {} questions or options, or a single definition for all",
+        num_definitions,
+    );
+    for vote_definition in &vote_definitions {
+        vote_definition.assert_valid();
+    }
     let deadline_voting_time = ctx.block_production_time + (voting_duration_ms as i64);
     let deadline_commitment_time =
         deadline_voting_time + ESTIMATED_MAX_INPUT_COMMITMENT_DURATION_MS;
     ContractState {
         administrator: ctx.sender,
+        co_administrators,
         deadline_voting_time,
         deadline_commitment_time,
         allowed_voters,
-        vote_definition,
-        vote_result: None,
+        eligibility_token,
+        locked_tokens: BTreeMap::new(),
+        completed: false,
+        num_questions,
+        options,
+        vote_definitions,
+        vote_results: None,
+        winning_option: None,
+        turnout: if publish_turnout { Some(0) } else { None },
     }
 }
 
 /// Adds another vote.
 ///
 /// The ZkInputDef encodes that the variable should have size [`BITLENGTH_OF_SECRET_VOTE_VARIABLES`].
+/// On a choice ballot the vote is the index of the chosen option. A vote for an index outside the
+/// options counts against every option.
 #[zk_on_secret_input(shortname = 0x40)]
 fn add_vote(
     context: ContractContext,
@@ -181,13 +337,202 @@ fn add_vote(
         seal: false,
         metadata: SecretVarMetadata {
             variable_type: SecretVarType::Vote,
+            choice_ballot: !state.options.is_empty(),
         },
         expected_bit_lengths: vec![BITLENGTH_OF_SECRET_VOTE_VARIABLES],
     };
     (state, vec![], input_def)
 }
 
-/// Allows anybody to start the computation of the vote, but only after the counting period.
+/// Automatically called when a variable is confirmed on chain.
+///
+/// Counts the confirmed vote in the turnout, if the turnout is published.
+#[zk_on_variable_inputted]
+fn inputted_variable(
+    _context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+    inputted_variable: SecretVarId,
+) -> ContractState {
+    let is_vote = zk_state
+        .get_variable(inputted_variable)
+        .map(|v| v.metadata.variable_type == SecretVarType::Vote)
+        .unwrap_or(false);
+    if let (Some(turnout), true) = (state.turnout.as_mut(), is_vote) {
+        *turnout += 1;
+    }
+    state
+}
+
+/// Registers the sender as a voter, by locking the lock amount of the eligibility token in the
+/// contract. The tokens are transferred from the sender to the contract, and the sender is
+/// admitted as a voter in `register_as_voter_callback`.
+///
+/// Voters can only register before the voting deadline.
+#[action(shortname = 0x03)]
+fn register_as_voter(
+    context: ContractContext,
+    state: ContractState,
+    _zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    let eligibility_token = state
+        .eligibility_token
+        .as_ref()
+        .expect("Voters cannot register, since the vote has no eligibility token");
+    assert!(
+        context.block_production_time < state.deadline_voting_time,
+        "Not allowed to register after the deadline at {} ms UTC, current time is {} ms UTC",
+        state.deadline_voting_time,
+        context.block_production_time,
+    );
+    assert!(
+        !state.allowed_voters.contains(&context.sender),
+        "Already registered as a voter: {:?}",
+        context.sender
+    );
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(eligibility_token.token, token_contract_transfer_from())
+        .argument(context.sender)
+        .argument(context.contract_address)
+        .argument(eligibility_token.lock_amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_REGISTER_AS_VOTER_CALLBACK)
+        .argument(context.sender)
+        .argument(eligibility_token.lock_amount)
+        .done();
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when the tokens of a registering voter have been locked. The locked tokens are recorded
+/// for the voter, who is admitted as a voter.
+#[callback(shortname = 0x04)]
+fn register_as_voter_callback(
+    _context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    _zk_state: ZkState<SecretVarMetadata>,
+    voter: Address,
+    amount: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        callback_ctx.success,
+        "Transfer of the locked tokens did not succeed"
+    );
+    *state.locked_tokens.entry(voter).or_insert(0) += amount;
+    if !state.allowed_voters.contains(&voter) {
+        state.allowed_voters.push(voter);
+    }
+    (state, vec![], vec![])
+}
+
+/// Withdraws the tokens locked by the sender to register as a voter. The tokens can only be
+/// withdrawn once the vote has been counted, and are transferred back to the sender with a
+/// callback to `withdraw_locked_tokens_callback`.
+#[action(shortname = 0x06)]
+fn withdraw_locked_tokens(
+    context: ContractContext,
+    mut state: ContractState,
+    _zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        state.completed,
+        "Locked tokens can only be withdrawn once the vote has been counted"
+    );
+    let amount = state
+        .locked_tokens
+        .remove(&context.sender)
+        .unwrap_or_else(|| panic!("No locked tokens for {:?}", context.sender));
+    let token = state.eligibility_token.as_ref().unwrap().token;
+
+    let mut event_group = EventGroup::builder();
+    event_group
+        .call(token, token_contract_transfer())
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    event_group
+        .with_callback(SHORTNAME_WITHDRAW_LOCKED_TOKENS_CALLBACK)
+        .argument(context.sender)
+        .argument(amount)
+        .done();
+    (state, vec![event_group.build()], vec![])
+}
+
+/// Called when locked tokens have been transferred back to a voter. If the transfer failed, the
+/// tokens are locked for the voter again, such that they can retry the withdrawal. Once all locked
+/// tokens have been withdrawn, the contract is marked as done.
+#[callback(shortname = 0x07)]
+fn withdraw_locked_tokens_callback(
+    _context: ContractContext,
+    callback_ctx: CallbackContext,
+    mut state: ContractState,
+    _zk_state: ZkState<SecretVarMetadata>,
+    voter: Address,
+    amount: u128,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    if !callback_ctx.success {
+        *state.locked_tokens.entry(voter).or_insert(0) += amount;
+    }
+    let zk_state_changes = state.complete();
+    (state, vec![], zk_state_changes)
+}
+
+/// Retracts the secret vote of the sender, such that the sender can vote again. Votes can only be
+/// retracted before the voting deadline.
+///
+/// Both confirmed votes and votes that are still pending input are deleted. Retracted confirmed
+/// votes are no longer counted in the turnout.
+#[action(shortname = 0x02)]
+fn retract_vote(
+    context: ContractContext,
+    mut state: ContractState,
+    zk_state: ZkState<SecretVarMetadata>,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert!(
+        context.block_production_time < state.deadline_voting_time,
+        "Not allowed to retract votes after the deadline at {} ms UTC, current time is {} ms UTC",
+        state.deadline_voting_time,
+        context.block_production_time,
+    );
+    let votes_to_delete: Vec<SecretVarId> = zk_state
+        .secret_variables
+        .iter()
+        .filter(|v| v.owner == context.sender && v.metadata.variable_type == SecretVarType::Vote)
+        .map(|v| v.variable_id)
+        .collect();
+    let pending_votes: Vec<SecretVarId> = zk_state
+        .pending_inputs
+        .iter()
+        .filter(|v| v.owner == context.sender)
+        .map(|v| v.variable_id)
+        .collect();
+    assert!(
+        !votes_to_delete.is_empty() || !pending_votes.is_empty(),
+        "No vote to retract for sender: {:?}",
+        context.sender
+    );
+
+    let mut changes: Vec<ZkStateChange> = pending_votes
+        .into_iter()
+        .map(|variable| ZkStateChange::DeletePendingInput { variable })
+        .collect();
+    if let Some(turnout) = state.turnout.as_mut() {
+        *turnout -= votes_to_delete.len() as u32;
+    }
+    if !votes_to_delete.is_empty() {
+        changes.push(ZkStateChange::DeleteVariables {
+            variables_to_delete: votes_to_delete,
+        });
+    }
+    (state, vec![], changes)
+}
+
+/// Allows the administrators to start the computation of the vote, but only after the counting
+/// period. If the administrators have not started the computation within
+/// [`ADMINISTRATOR_COUNTING_GRACE_PERIOD_MS`] of the counting period, anybody can start it.
 ///
 /// The vote computation is automatic beyond this call, involving several steps, as described in the module documentation.
 ///
@@ -204,6 +549,16 @@ fn start_vote_counting(
         state.deadline_commitment_time,
         context.block_production_time,
     );
+    let public_counting_time =
+        state.deadline_commitment_time + ADMINISTRATOR_COUNTING_GRACE_PERIOD_MS;
+    assert!(
+        context.block_production_time >= public_counting_time
+            || context.sender == state.administrator
+            || state.co_administrators.contains(&context.sender),
+        "Only administrators can start the vote counting before {} ms UTC, current time is {} ms UTC",
+        public_counting_time,
+        context.block_production_time,
+    );
     assert_eq!(
         zk_state.calculation_state,
         CalculationStatus::Waiting,
@@ -211,15 +566,36 @@ fn start_vote_counting(
         zk_state.calculation_state,
     );
 
+    let output_metadata = (0..MAX_NUM_QUESTIONS)
+        .map(|_| SecretVarMetadata {
+            variable_type: SecretVarType::CountedYesVotes,
+            choice_ballot: !state.options.is_empty(),
+        })
+        .collect();
     (
         state,
         vec![],
-        vec![ZkStateChange::start_computation(vec![SecretVarMetadata {
-            variable_type: SecretVarType::CountedYesVotes,
-        }])],
+        vec![ZkStateChange::start_computation(output_metadata)],
     )
 }
 
+/// Transfers the administration of the vote to `new_administrator`. Only the administrator can
+/// transfer the administration.
+#[action(shortname = 0x05)]
+fn transfer_administration(
+    context: ContractContext,
+    mut state: ContractState,
+    _zk_state: ZkState<SecretVarMetadata>,
+    new_administrator: Address,
+) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
+    assert_eq!(
+        context.sender, state.administrator,
+        "Only the administrator can transfer the administration"
+    );
+    state.administrator = new_administrator;
+    (state, vec![], vec![])
+}
+
 /// Automatically called when the computation is completed
 ///
 /// The only thing we do is to instantly open/declassify the output variables.
@@ -241,7 +617,9 @@ fn counting_complete(
 
 /// Automatically called when a variable is opened/declassified.
 ///
-/// We can now read the for and against variables, and compute the result
+/// We can now read the yes counts of each question, or the counts of each option of a choice
+/// ballot, and compute the results. In attestation mode the results are attested along with the
+/// digest of the participating voters.
 #[zk_on_variables_opened]
 fn open_sum_variable(
     _context: ContractContext,
@@ -251,54 +629,106 @@ fn open_sum_variable(
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
     assert_eq!(
         opened_variables.len(),
-        1,
+        MAX_NUM_QUESTIONS as usize,
         "Unexpected number of output variables"
     );
-    let votes_for = read_variable_u32_le(&zk_state, opened_variables.get(0));
-    let total_votes = zk_state
+    let participants: Vec<Address> = zk_state
         .secret_variables
         .iter()
         .filter(|x| x.metadata.variable_type == SecretVarType::Vote)
-        .count();
-    let votes_against = (total_votes as u32) - votes_for;
+        .map(|x| x.owner)
+        .collect();
+    let total_votes = participants.len() as u32;
 
-    let vote_result = determine_result(
-        &state.vote_definition,
-        state.allowed_voters.len() as u32,
-        votes_for,
-        votes_against,
-    );
-    state.vote_result = Some(vote_result.clone());
+    let vote_results: Vec<VoteResult> = state
+        .vote_definitions
+        .iter()
+        .zip(opened_variables.iter())
+        .map(|(vote_definition, yes_count_variable)| {
+            let votes_for = read_variable_u32_le(&zk_state, Some(yes_count_variable));
+            determine_result(
+                vote_definition,
+                state.allowed_voters.len() as u32,
+                votes_for,
+                total_votes - votes_for,
+            )
+        })
+        .collect();
+    if !state.options.is_empty() {
+        state.winning_option = determine_winner(&vote_results);
+    }
+    state.vote_results = Some(vote_results.clone());
 
     if cfg!(feature = "attestation") {
         (
             state,
             vec![],
             vec![ZkStateChange::Attest {
-                data_to_attest: serialize(vote_result),
+                data_to_attest: serialize(AttestedResults {
+                    vote_results,
+                    participation_digest: participation_digest(participants),
+                }),
             }],
         )
     } else {
-        (state, vec![], vec![ZkStateChange::ContractDone])
+        let zk_state_changes = state.complete();
+        (state, vec![], zk_state_changes)
+    }
+}
+
+impl ContractState {
+    /// Marks the vote as completed, and the contract as done unless voters still have locked
+    /// tokens to withdraw.
+    fn complete(&mut self) -> Vec<ZkStateChange> {
+        self.completed = true;
+        if self.locked_tokens.is_empty() {
+            vec![ZkStateChange::ContractDone]
+        } else {
+            vec![]
+        }
     }
 }
 
+/// Token contract actions
+#[inline]
+fn token_contract_transfer() -> Shortname {
+    Shortname::from_u32(0x01)
+}
+
+#[inline]
+fn token_contract_transfer_from() -> Shortname {
+    Shortname::from_u32(0x03)
+}
+
 fn serialize<T: ReadWriteState>(it: T) -> Vec<u8> {
     let mut output: Vec<u8> = vec![];
     it.state_write_to(&mut output).expect("Could not serialize");
     output
 }
 
+/// Computes the digest of the voters who submitted a secret vote, being the SHA-256 hash of the
+/// serialized addresses of the voters in sorted order. The order in which the votes were submitted
+/// is thereby not revealed.
+fn participation_digest(mut participants: Vec<Address>) -> Hash {
+    participants.sort();
+    let mut preimage: Vec<u8> = vec![];
+    for participant in participants {
+        preimage.extend(serialize(participant));
+    }
+    Sha256::digest(preimage).into()
+}
+
 /// Attestation complete
 #[cfg(feature = "attestation")]
 #[zk_on_attestation_complete]
 fn handle_attestation(
     _context: ContractContext,
-    state: ContractState,
+    mut state: ContractState,
     _zk_state: ZkState<SecretVarMetadata>,
     _attestation_id: AttestationId,
 ) -> (ContractState, Vec<EventGroup>, Vec<ZkStateChange>) {
-    (state, vec![], vec![ZkStateChange::ContractDone])
+    let zk_state_changes = state.complete();
+    (state, vec![], zk_state_changes)
 }
 
 /// Reads a variable's data as an u32.
@@ -333,3 +763,26 @@ fn determine_result(
         passed,
     }
 }
+
+/// Determines the winning option of a choice ballot, being the option with the most votes among
+/// the options that passed.
+///
+/// ### Returns:
+///
+/// The index of the winning option, or None if no option passed or several passed options are
+/// tied for the most votes.
+fn determine_winner(option_results: &[VoteResult]) -> Option<u8> {
+    let most_votes = option_results
+        .iter()
+        .filter(|result| result.passed)
+        .map(|result| result.votes_for)
+        .max()?;
+    let mut leaders = option_results
+        .iter()
+        .enumerate()
+        .filter(|(_, result)| result.passed && result.votes_for == most_votes);
+    match (leaders.next(), leaders.next()) {
+        (Some((index, _)), None) => Some(index as u8),
+        _ => None,
+    }
+}
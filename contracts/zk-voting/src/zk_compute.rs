@@ -1,20 +1,63 @@
 use pbc_zk::*;
 
-/// Perform a zk computation on secret-shared data to count the number
-/// of accepting votes (non-zero).
+/// The maximum number of options on a choice ballot.
+const MAX_NUM_OPTIONS: i32 = 4;
+
+/// The public metadata attached by the contract to each vote.
+struct VoteMetadata {
+    variable_type: u8,
+    choice_ballot: bool,
+}
+
+/// Count the number of accepting votes for a single question or option. On a ballot with questions
+/// a vote accepts question `i` if bit `i` is set. On a choice ballot a vote accepts option `i` if
+/// the vote is `i`.
+///
+/// ### Parameters:
+///
+/// * `question`: The index of the question, which is the index of the bit in each vote, or the
+///   index of the option on a choice ballot.
 ///
 /// ### Returns:
 ///
-/// The number of accepting votes.
-pub fn zk_compute() -> Sbi32 {
-    // Initialize votes
+/// The number of accepting votes for the question or option.
+fn count_question(question: i32) -> Sbi32 {
     let mut votes_for: Sbi32 = Sbi32::from(0);
 
-    // Count votes
-    for variable_id in 1..(num_secret_variables() + 1) {
-        if load_sbi::<Sbi32>(variable_id) != Sbi32::from(0) {
+    // Iterate the ids of the remaining variables, as retracted votes leave gaps in the ids
+    for variable_id in secret_variable_ids() {
+        let vote = load_sbi::<Sbi32>(variable_id);
+        // The kind of ballot is public, so only the matching comparison is made
+        if load_metadata::<VoteMetadata>(variable_id).choice_ballot {
+            if question < MAX_NUM_OPTIONS {
+                if vote == Sbi32::from(question) {
+                    votes_for = votes_for + Sbi32::from(1);
+                }
+            }
+        } else if (vote >> question) & Sbi32::from(1) != Sbi32::from(0) {
             votes_for = votes_for + Sbi32::from(1);
         }
     }
     votes_for
 }
+
+/// Perform a zk computation on secret-shared data to count the number
+/// of accepting votes for each question. Bit `i` of a vote is the answer to question `i`.
+/// On a choice ballot the first outputs are instead the number of votes for each option, and the
+/// remaining outputs are zero.
+///
+/// ### Returns:
+///
+/// The number of accepting votes for each of the eight possible questions.
+pub fn zk_compute() -> (Sbi32, Sbi32, Sbi32, Sbi32, Sbi32, Sbi32, Sbi32, Sbi32) {
+    (
+        count_question(0),
+        count_question(1),
+        count_question(2),
+        count_question(3),
+        count_question(4),
+        count_question(5),
+        count_question(6),
+        count_question(7),
+    )
+}